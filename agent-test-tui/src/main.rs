@@ -56,13 +56,15 @@ fn load_config() -> Result<Vec<McpServerConfig>> {
     let servers = config
         .mcp_servers
         .into_iter()
-        .map(|(name, entry)| McpServerConfig {
-            name,
-            transport: McpTransport::Stdio {
-                command: entry.command,
-                args: entry.args,
-                env: entry.env,
-            },
+        .map(|(name, entry)| {
+            McpServerConfig::new(
+                name,
+                McpTransport::Stdio {
+                    command: entry.command,
+                    args: entry.args,
+                    env: entry.env,
+                },
+            )
         })
         .collect();
 
@@ -203,9 +205,12 @@ async fn main() -> Result<()> {
                     continue;
                 }
                 let client = Arc::new(client);
-                match registry.merge_mcp(client.clone(), Some(&name)).await {
-                    Ok(count) => {
-                        println!("Connected to {} ({} tools)", name, count);
+                match registry
+                    .merge_mcp(client.clone(), McpToolNaming::server_name())
+                    .await
+                {
+                    Ok(tools) => {
+                        println!("Connected to {} ({} tools)", name, tools.len());
                         mcp_clients.push(client);
                     }
                     Err(e) => eprintln!("Warning: Failed to list tools from {}: {}", name, e),
@@ -1,78 +1,33 @@
-// ABOUTME: Code editing agent with read, write, search, and bash tools.
+// ABOUTME: Code editing agent with read, write, edit, search, and bash tools.
 // ABOUTME: Demonstrates using mux-rs built-in tools.
 
+use std::collections::HashMap;
 use std::io::Write;
 
 use anyhow::Result;
 use futures::StreamExt;
 use rustyline::DefaultEditor;
 
+use mux::llm::partial_json::partial_string_field;
+use mux::llm::stream_accumulator::StreamAccumulator;
 use mux::prelude::*;
 
+/// Model the agent talks to.
+const MODEL: &str = "claude-sonnet-4-20250514";
+
 // ============================================================================
 // Agent Loop (with streaming)
 // ============================================================================
 
-/// Accumulator for building content blocks from stream events.
-#[derive(Default)]
-struct StreamAccumulator {
-    content_blocks: Vec<ContentBlock>,
-    current_text: String,
-    current_tool_id: Option<String>,
-    current_tool_name: Option<String>,
-    current_tool_input: String,
-}
-
-impl StreamAccumulator {
-    fn handle_event(&mut self, event: StreamEvent) {
-        match event {
-            StreamEvent::ContentBlockStart { block, .. } => {
-                // Start a new block
-                match &block {
-                    ContentBlock::Text { .. } => {
-                        self.current_text.clear();
-                    }
-                    ContentBlock::ToolUse { id, name, .. } => {
-                        self.current_tool_id = Some(id.clone());
-                        self.current_tool_name = Some(name.clone());
-                        self.current_tool_input.clear();
-                    }
-                    _ => {}
-                }
-            }
-            StreamEvent::ContentBlockDelta { text, .. } => {
-                // Text content delta - only accumulate if not in a tool block
-                if self.current_tool_id.is_none() {
-                    self.current_text.push_str(&text);
-                }
-            }
-            StreamEvent::InputJsonDelta { partial_json, .. } => {
-                // Tool input JSON delta - accumulate for tool calls
-                self.current_tool_input.push_str(&partial_json);
-            }
-            StreamEvent::ContentBlockStop { .. } => {
-                // Finalize the current block
-                if let (Some(id), Some(name)) =
-                    (self.current_tool_id.take(), self.current_tool_name.take())
-                {
-                    // Parse the accumulated JSON input
-                    let input: serde_json::Value =
-                        serde_json::from_str(&self.current_tool_input).unwrap_or_default();
-                    self.content_blocks
-                        .push(ContentBlock::ToolUse { id, name, input });
-                    self.current_tool_input.clear();
-                } else if !self.current_text.is_empty() {
-                    self.content_blocks.push(ContentBlock::Text {
-                        text: std::mem::take(&mut self.current_text),
-                    });
-                }
-            }
-            _ => {}
-        }
-    }
-
-    fn into_content(self) -> Vec<ContentBlock> {
-        self.content_blocks
+/// Describe what a file tool is doing once its `path` argument has streamed in.
+fn describe_tool_target(tool_name: &str, path: &str) -> String {
+    match tool_name {
+        "write_file" => format!("writing to {}...", path),
+        "edit" => format!("editing {}...", path),
+        "read_file" => format!("reading {}...", path),
+        "list_files" => format!("listing {}...", path),
+        "search" => format!("searching {}...", path),
+        _ => format!("{} {}...", tool_name, path),
     }
 }
 
@@ -80,6 +35,10 @@ async fn run_agent_loop(registry: &Registry) -> Result<()> {
     let client = AnthropicClient::from_env()?;
     let mut history: Vec<Message> = Vec::new();
     let mut rl = DefaultEditor::new()?;
+    let system_prompt = SystemPromptBuilder::new("You are a helpful coding assistant. You have access to tools for reading files, writing files, editing files in place, searching code, listing files, and running bash commands. Use these tools to help the user with their coding tasks. Be concise in your responses.")
+        .workspace(std::env::current_dir()?.display().to_string())
+        .current_date()
+        .build();
 
     println!("Code Agent (streaming) - Type 'quit' to exit.\n");
 
@@ -102,23 +61,24 @@ async fn run_agent_loop(registry: &Registry) -> Result<()> {
 
         // Agent loop: keep calling Claude until no more tool calls
         loop {
-            let request = Request::new("claude-sonnet-4-20250514")
+            let request = Request::new(MODEL)
                 .messages(history.clone())
                 .tools(registry.to_definitions().await)
-                .system("You are a helpful coding assistant. You have access to tools for reading files, writing files, searching code, listing files, and running bash commands. Use these tools to help the user with their coding tasks. Be concise in your responses.")
+                .system(system_prompt.clone())
                 .max_tokens(4096);
 
             // Use streaming API
             let mut stream = client.create_message_stream(&request);
-            let mut accumulator = StreamAccumulator::default();
+            let mut accumulator = StreamAccumulator::new();
             let mut printed_newline = false;
+            let mut announced_target = false;
 
             while let Some(event) = stream.next().await {
                 let event = event?;
 
                 // Print text deltas as they arrive
                 if let StreamEvent::ContentBlockDelta { ref text, .. } = event {
-                    if accumulator.current_tool_id.is_none() {
+                    if !accumulator.in_tool_use() {
                         // Only print if we're in a text block, not tool input
                         if !printed_newline {
                             println!();
@@ -136,60 +96,82 @@ async fn run_agent_loop(registry: &Registry) -> Result<()> {
                 } = &event
                 {
                     println!("\n[Calling {}...]", name);
+                    announced_target = false;
                 }
 
-                accumulator.handle_event(event);
+                let is_input_delta = matches!(event, StreamEvent::InputJsonDelta { .. });
+                accumulator.handle_event(&event);
+
+                // Show the target path as soon as it has streamed in, before the
+                // rest of the arguments (e.g. file content) arrive
+                if is_input_delta
+                    && !announced_target
+                    && let Some(path) =
+                        partial_string_field(accumulator.current_tool_input(), "path")
+                {
+                    println!(
+                        "  {}",
+                        describe_tool_target(accumulator.current_tool_name(), &path)
+                    );
+                    announced_target = true;
+                }
             }
 
             if printed_newline {
                 println!("\n");
             }
 
-            let content = accumulator.into_content();
+            let response = accumulator.into_response(MODEL);
+            // Calls whose arguments weren't valid JSON go back to the model as errors
+            let invalid: HashMap<usize, &str> = response
+                .invalid_tool_inputs
+                .iter()
+                .map(|invalid| (invalid.index, invalid.error.as_str()))
+                .collect();
+            let content = response.content;
 
             // Check for tool calls
             if content
                 .iter()
                 .any(|b| matches!(b, ContentBlock::ToolUse { .. }))
             {
-                let mut tool_results = Vec::new();
-
-                for block in content.iter() {
-                    if let ContentBlock::ToolUse { id, name, input } = block {
-                        match registry.get(name).await {
-                            Some(tool) => match tool.execute(input.clone()).await {
-                                Ok(result) => {
-                                    // Truncate long outputs for display
-                                    let display = if result.content.len() > 500 {
-                                        format!(
-                                            "{}...\n[truncated, {} bytes total]",
-                                            &result.content[..500],
-                                            result.content.len()
-                                        )
-                                    } else {
-                                        result.content.clone()
-                                    };
-                                    println!("{}\n", display);
-
-                                    if result.is_error {
-                                        tool_results
-                                            .push(ContentBlock::tool_error(id, &result.content));
-                                    } else {
-                                        tool_results
-                                            .push(ContentBlock::tool_result(id, &result.content));
-                                    }
-                                }
-                                Err(e) => {
-                                    let error = format!("Error: {}", e);
-                                    println!("{}\n", error);
-                                    tool_results.push(ContentBlock::tool_error(id, error));
-                                }
-                            },
-                            None => {
-                                let error = format!("Tool not found: {}", name);
-                                println!("{}\n", error);
-                                tool_results.push(ContentBlock::tool_error(id, error));
-                            }
+                // Calls run concurrently, except that a tool that isn't
+                // parallel-safe (like bash) waits for the calls before it
+                let runnable: Vec<ContentBlock> = content
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !invalid.contains_key(index))
+                    .map(|(_, block)| block.clone())
+                    .collect();
+                let mut ran = registry.execute_batch(&runnable).await.into_iter();
+                let tool_results: Vec<ContentBlock> = content
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, block)| match block {
+                        ContentBlock::ToolUse { id, .. } => match invalid.get(&index) {
+                            Some(error) => Some(ContentBlock::tool_error(
+                                id,
+                                format!(
+                                    "invalid JSON arguments: {}. Call the tool again with valid JSON arguments.",
+                                    error
+                                ),
+                            )),
+                            None => ran.next(),
+                        },
+                        _ => None,
+                    })
+                    .collect();
+                for block in &tool_results {
+                    if let ContentBlock::ToolResult { content, .. } = block {
+                        // Truncate long outputs for display
+                        if content.len() > 500 {
+                            println!(
+                                "{}...\n[truncated, {} bytes total]\n",
+                                &content[..500],
+                                content.len()
+                            );
+                        } else {
+                            println!("{}\n", content);
                         }
                     }
                 }
@@ -225,9 +207,10 @@ async fn main() -> Result<()> {
     let registry = Registry::new();
     registry.register(ReadFileTool).await;
     registry.register(WriteFileTool).await;
+    registry.register(EditTool).await;
     registry.register(SearchTool).await;
     registry.register(ListFilesTool).await;
-    registry.register(BashTool).await;
+    registry.register(BashTool::new()).await;
 
     let tools: Vec<_> = registry
         .list()
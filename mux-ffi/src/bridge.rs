@@ -10,15 +10,24 @@ use std::sync::Arc;
 
 /// Bridges Swift HookHandler to Rust Hook trait
 pub struct FfiHookBridge {
-    handler: Arc<Box<dyn HookHandler>>,
+    handler: Arc<dyn HookHandler>,
+    full_request: bool,
 }
 
 impl FfiHookBridge {
-    pub fn new(handler: Box<dyn HookHandler>) -> Self {
+    pub fn new(handler: Arc<dyn HookHandler>) -> Self {
         Self {
-            handler: Arc::new(handler),
+            handler,
+            full_request: false,
         }
     }
+
+    /// Fill in `request_json` for LlmRequest events. Without it the
+    /// handler gets the request summary and an empty `request_json`.
+    pub fn with_full_request(mut self, full_request: bool) -> Self {
+        self.full_request = full_request;
+        self
+    }
 }
 
 #[async_trait]
@@ -65,6 +74,39 @@ impl Hook for FfiHookBridge {
                 agent_id: agent_id.clone(),
                 iteration: *iteration as u32,
             },
+            HookEvent::LlmRequest {
+                agent_id,
+                model,
+                request_summary,
+                request,
+            } => {
+                let request_json = if self.full_request {
+                    serde_json::to_string(request)
+                        .map_err(|e| anyhow::anyhow!("Failed to serialize LLM request: {}", e))?
+                } else {
+                    String::new()
+                };
+                HookEventType::LlmRequest {
+                    agent_id: agent_id.clone(),
+                    model: model.clone(),
+                    message_count: request_summary.messages as u32,
+                    tool_names: request_summary.tools.clone(),
+                    estimated_input_tokens: request_summary.estimated_input_tokens as u32,
+                    request_json,
+                }
+            }
+            HookEvent::LlmResponse {
+                agent_id,
+                model,
+                usage,
+                stop_reason,
+            } => HookEventType::LlmResponse {
+                agent_id: agent_id.clone(),
+                model: model.clone(),
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                stop_reason: format!("{:?}", stop_reason),
+            },
             // Session, subagent, response, and streaming events - pass through without FFI callback.
             // StreamDelta/StreamUsage are forwarded via SubagentEventHandler in task_tool.rs;
             // HookEventType (used by HookHandler) doesn't need streaming variants.
@@ -73,9 +115,12 @@ impl Hook for FfiHookBridge {
             | HookEvent::Stop { .. }
             | HookEvent::SubagentStart { .. }
             | HookEvent::SubagentStop { .. }
+            | HookEvent::PostResponse { .. }
             | HookEvent::ResponseReceived { .. }
             | HookEvent::StreamDelta { .. }
-            | HookEvent::StreamUsage { .. } => {
+            | HookEvent::ToolInputDelta { .. }
+            | HookEvent::StreamUsage { .. }
+            | HookEvent::ToolProgress { .. } => {
                 return Ok(HookAction::Continue);
             }
         };
@@ -238,7 +283,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ffi_hook_bridge_continue() {
-        let handler = Box::new(MockHookHandler::new(HookResponse::Continue));
+        let handler = Arc::new(MockHookHandler::new(HookResponse::Continue));
         let bridge = FfiHookBridge::new(handler);
 
         let event = HookEvent::PreToolUse {
@@ -252,7 +297,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ffi_hook_bridge_block() {
-        let handler = Box::new(MockHookHandler::new(HookResponse::Block {
+        let handler = Arc::new(MockHookHandler::new(HookResponse::Block {
             reason: "Not allowed".to_string(),
         }));
         let bridge = FfiHookBridge::new(handler);
@@ -271,7 +316,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ffi_hook_bridge_transform() {
-        let handler = Box::new(MockHookHandler::new(HookResponse::Transform {
+        let handler = Arc::new(MockHookHandler::new(HookResponse::Transform {
             new_input: r#"{"modified": true}"#.to_string(),
         }));
         let bridge = FfiHookBridge::new(handler);
@@ -290,9 +335,35 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_ffi_hook_bridge_block_llm_request() {
+        let handler = Arc::new(MockHookHandler::new(HookResponse::Block {
+            reason: "Over budget".to_string(),
+        }));
+        let bridge = FfiHookBridge::new(handler);
+
+        let event = HookEvent::LlmRequest {
+            agent_id: "agent-1".to_string(),
+            model: "test-model".to_string(),
+            request_summary: mux::hook::RequestSummary {
+                messages: 1,
+                tools: vec!["read_file".to_string()],
+                max_tokens: None,
+                estimated_input_tokens: 10,
+            },
+            request: mux::llm::Request::new("test-model"),
+        };
+
+        let result = bridge.on_event(&event).await.unwrap();
+        match result {
+            HookAction::Block(reason) => assert_eq!(reason, "Over budget"),
+            _ => panic!("Expected Block action"),
+        }
+    }
+
     #[tokio::test]
     async fn test_ffi_hook_bridge_accepts_all() {
-        let handler = Box::new(MockHookHandler::new(HookResponse::Continue));
+        let handler = Arc::new(MockHookHandler::new(HookResponse::Continue));
         let bridge = FfiHookBridge::new(handler);
 
         let events = vec![
@@ -2,7 +2,8 @@
 // ABOUTME: Swift implements these traits to receive streaming updates.
 
 use crate::types::{
-    HookEventType, HookResponse, LlmRequest, LlmResponse, SubagentResult, ToolExecutionResult,
+    AgentStopReason, HookEventType, HookResponse, LlmRequest, LlmResponse, SubagentResult,
+    ToolExecutionResult, UsageSummary,
 };
 
 /// Represents a tool use request that will be sent to Swift for display/logging.
@@ -22,7 +23,16 @@ pub struct ChatResult {
     pub tool_use_count: u32,
     pub input_tokens: u32,
     pub output_tokens: u32,
+    pub cache_read_tokens: u32,
+    pub cache_write_tokens: u32,
     pub context_usage: crate::context::ContextUsage,
+    /// Files the agent's tools created or modified during this turn.
+    pub files_changed: Vec<String>,
+    /// Token usage for the whole conversation so far, per model.
+    pub conversation_usage: UsageSummary,
+    /// True if the turn was cut off at the conversation's iteration cap;
+    /// `final_text` then explains that instead of answering.
+    pub hit_iteration_limit: bool,
 }
 
 /// Callback interface that Swift implements to receive streaming chat updates.
@@ -36,7 +46,8 @@ pub trait ChatCallback: Send + Sync {
     fn on_tool_use(&self, request: ToolUseRequest);
 
     /// Called when a tool execution completes with a result.
-    fn on_tool_result(&self, tool_id: String, result: String);
+    /// `metadata_json` is the tool's structured metadata as a JSON object (`{}` if none).
+    fn on_tool_result(&self, tool_id: String, result: String, metadata_json: String);
 
     /// Called when the entire chat completion finishes successfully.
     fn on_complete(&self, result: ChatResult);
@@ -47,12 +58,20 @@ pub trait ChatCallback: Send + Sync {
     /// Called when context usage exceeds the warning threshold.
     /// Swift can use this to show a UI warning or trigger compaction.
     fn on_context_warning(&self, usage: crate::context::ContextUsage);
+
+    /// Called when older messages were automatically compacted before an LLM call.
+    /// `compacted_messages` is how many messages were summarized, dropped or cut short;
+    /// `usage` reflects the conversation after compaction.
+    fn on_context_compacted(&self, compacted_messages: u32, usage: crate::context::ContextUsage);
 }
 
 /// Hook handler interface - Swift implements to intercept lifecycle events.
 /// Hooks allow interception of tool execution and agent lifecycle for
 /// validation, logging, or transformation of inputs/outputs.
-#[uniffi::export(callback_interface)]
+///
+/// Exported as a foreign trait rather than a callback interface so one
+/// handler can be shared by the chat loop and the subagents it spawns.
+#[uniffi::export(with_foreign)]
 pub trait HookHandler: Send + Sync {
     /// Called when a hook event occurs. Returns a response indicating
     /// whether to continue, block, or transform the operation.
@@ -115,24 +134,28 @@ pub trait SubagentEventHandler: Send + Sync {
     fn on_tool_use(&self, subagent_id: String, tool_name: String, arguments_json: String);
 
     /// Called when a tool execution completes.
+    /// `metadata_json` is the tool's structured metadata as a JSON object (`{}` if none).
     fn on_tool_result(
         &self,
         subagent_id: String,
         tool_name: String,
         result: String,
         is_error: bool,
+        metadata_json: String,
     );
 
     /// Called when the subagent completes an iteration of its think-act loop.
     fn on_iteration(&self, subagent_id: String, iteration: u32);
 
-    /// Called when the subagent completes successfully.
+    /// Called when the subagent finishes without error.
+    /// `stop_reason` tells whether it completed or was cut short (e.g. iteration limit).
     fn on_agent_completed(
         &self,
         subagent_id: String,
         content: String,
         tool_use_count: u32,
         iterations: u32,
+        stop_reason: AgentStopReason,
         transcript_saved: bool,
     );
 
@@ -1,14 +1,13 @@
 // ABOUTME: Adapts Swift's LlmProvider callback to Rust's LlmClient trait.
 // ABOUTME: Enables on-device models to integrate with Mux orchestration.
 
-use std::pin::Pin;
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use futures::Stream;
 
 use mux::error::LlmError;
-use mux::llm::{ContentBlock, LlmClient, Request, Response, StopReason, StreamEvent, Usage};
+use mux::llm::{ContentBlock, InvalidToolInput, LlmClient, Request, Response, StopReason, Usage};
 
 use crate::callback::LlmProvider;
 use crate::types::{ChatMessage, ChatRole, FfiToolDefinition, LlmRequest};
@@ -26,7 +25,21 @@ impl CallbackLlmClient {
     }
 
     /// Convert mux Request to FFI LlmRequest
-    fn convert_request(req: &Request) -> LlmRequest {
+    ///
+    /// `ChatMessage` only carries text, so requests with images are rejected
+    /// rather than silently dropping them.
+    fn convert_request(req: &Request) -> Result<LlmRequest, LlmError> {
+        let has_images = req
+            .messages
+            .iter()
+            .flat_map(|m| &m.content)
+            .any(|b| matches!(b, ContentBlock::Image { .. }));
+        if has_images {
+            return Err(LlmError::InvalidRequest(
+                "Callback LLM providers do not support image content".to_string(),
+            ));
+        }
+
         let messages: Vec<ChatMessage> = req
             .messages
             .iter()
@@ -63,19 +76,19 @@ impl CallbackLlmClient {
             })
             .collect();
 
-        LlmRequest {
+        Ok(LlmRequest {
             messages,
             tools,
-            system_prompt: req.system.clone(),
+            system_prompt: req.collapsed_system().map(Cow::into_owned),
             max_tokens: req.max_tokens,
-        }
+        })
     }
 }
 
 #[async_trait]
 impl LlmClient for CallbackLlmClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
-        let llm_request = Self::convert_request(req);
+        let llm_request = Self::convert_request(req)?;
 
         // Call Swift provider (blocking call)
         let provider = self.provider.clone();
@@ -96,6 +109,7 @@ impl LlmClient for CallbackLlmClient {
 
         // Build content blocks
         let mut content: Vec<ContentBlock> = Vec::new();
+        let mut invalid_tool_inputs = Vec::new();
 
         if !llm_response.text.is_empty() {
             content.push(ContentBlock::Text {
@@ -104,14 +118,10 @@ impl LlmClient for CallbackLlmClient {
         }
 
         for tool_call in llm_response.tool_calls {
-            let input: serde_json::Value =
-                serde_json::from_str(&tool_call.arguments).map_err(|e| LlmError::Api {
-                    status: 0,
-                    message: format!(
-                        "Invalid JSON in tool call '{}' arguments: {}",
-                        tool_call.name, e
-                    ),
-                })?;
+            // Malformed arguments are reported so the agent can ask the model to retry
+            let (input, invalid) = InvalidToolInput::check(content.len(), &tool_call.arguments);
+            invalid_tool_inputs.extend(invalid);
+            // On-device models often leave the ID out; the agent names those calls
             content.push(ContentBlock::ToolUse {
                 id: tool_call.id,
                 name: tool_call.name,
@@ -134,30 +144,19 @@ impl LlmClient for CallbackLlmClient {
             content,
             stop_reason,
             model: req.model.clone(),
+            served_model: None,
+            system_fingerprint: None,
             usage: Usage {
                 input_tokens: llm_response.usage.input_tokens,
                 output_tokens: llm_response.usage.output_tokens,
                 cache_read_tokens: 0,
                 cache_write_tokens: 0,
             },
+            attempts: 1,
+            citations: Vec::new(),
+            invalid_tool_inputs,
         })
     }
-
-    fn create_message_stream(
-        &self,
-        _req: &Request,
-    ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
-        // Streaming not supported for callback providers - return error stream
-        // Use create_message() for non-streaming generation instead
-        Box::pin(futures::stream::once(async {
-            Err(LlmError::Api {
-                status: 0,
-                message:
-                    "Streaming not supported for callback LLM providers. Use non-streaming API."
-                        .to_string(),
-            })
-        }))
-    }
 }
 
 #[cfg(test)]
@@ -233,6 +232,42 @@ mod tests {
         assert_eq!(tool_uses.len(), 1);
     }
 
+    struct UnnamedToolsProvider;
+
+    impl LlmProvider for UnnamedToolsProvider {
+        fn generate(&self, _request: LlmRequest) -> LlmResponse {
+            let call = |name: &str| LlmToolCall {
+                id: String::new(),
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            };
+            LlmResponse {
+                text: String::new(),
+                tool_calls: vec![call("read_file"), call("list_files")],
+                usage: LlmUsage::default(),
+                error: None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_callback_client_leaves_unnamed_tool_calls_to_the_agent() {
+        let client = CallbackLlmClient::new(Box::new(UnnamedToolsProvider));
+        let request = Request::new("test-model").message(Message::user("Look around"));
+
+        let response = client.create_message(&request).await.unwrap();
+
+        let ids: Vec<&str> = response
+            .tool_uses()
+            .into_iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, ["", ""]);
+    }
+
     struct ErrorProvider;
 
     impl LlmProvider for ErrorProvider {
@@ -276,16 +311,40 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_callback_client_invalid_json_error() {
+    async fn test_callback_client_keeps_invalid_json_arguments() {
         let client = CallbackLlmClient::new(Box::new(InvalidJsonToolProvider));
         let request = Request::new("test-model").message(Message::user("Use a tool"));
 
-        let result = client.create_message(&request).await;
+        let response = client.create_message(&request).await.unwrap();
 
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Invalid JSON"));
-        assert!(err.to_string().contains("bad_tool"));
+        // The raw arguments are passed on for the agent loop to report
+        match &response.content[0] {
+            ContentBlock::ToolUse { name, input, .. } => {
+                assert_eq!(name, "bad_tool");
+                assert_eq!(input, &serde_json::json!({}));
+            }
+            other => panic!("Expected ToolUse, got {:?}", other),
+        }
+        assert_eq!(response.invalid_tool_inputs.len(), 1);
+        assert_eq!(response.invalid_tool_inputs[0].index, 0);
+        assert_eq!(
+            response.invalid_tool_inputs[0].arguments,
+            "not valid json {{{"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_callback_client_rejects_images() {
+        let client = CallbackLlmClient::new(Box::new(EchoProvider));
+        let request = Request::new("test-model").message(Message::user_with_image(
+            "What is this?",
+            mux::llm::ImageSource::url("https://example.com/cat.png"),
+        ));
+
+        let err = client.create_message(&request).await.unwrap_err();
+
+        assert!(matches!(err, LlmError::InvalidRequest(_)));
+        assert!(err.to_string().contains("image"));
     }
 
     #[tokio::test]
@@ -1,8 +1,10 @@
 // ABOUTME: Context management types and token estimation utilities.
 // ABOUTME: Supports small context models like Apple Foundation Models (4K).
 
+use crate::types::Provider;
+
 /// Approximate bytes per token for estimation (conservative)
-pub const APPROX_BYTES_PER_TOKEN: usize = 4;
+pub use mux::llm::APPROX_BYTES_PER_TOKEN;
 
 /// Safety margin to avoid hitting exact limit
 pub const SAFETY_MARGIN: f32 = 0.8;
@@ -12,6 +14,12 @@ pub const SAFETY_MARGIN: f32 = 0.8;
 /// Models with context_limit > this use summarization (intelligent compression).
 pub const SMALL_CONTEXT_THRESHOLD: u32 = 8192;
 
+/// Default fraction of the context limit that triggers automatic compaction.
+pub const DEFAULT_COMPACTION_THRESHOLD: f32 = 0.9;
+
+/// Default number of recent user turns kept verbatim by summarization.
+pub const DEFAULT_PRESERVE_TURNS: u32 = 2;
+
 /// System prompt for LLM summarization during compaction.
 pub const SUMMARIZATION_PROMPT: &str = r#"You are performing a CONTEXT CHECKPOINT COMPACTION. Create a handoff summary for another LLM that will resume the task.
 
@@ -74,13 +82,18 @@ pub struct ModelContextConfig {
     pub warning_threshold: f32,
     /// Optional model to use for summarization (e.g., cheaper model like Haiku).
     /// If None, uses the conversation's configured model.
-    ///
-    /// CAVEAT: The compaction_model uses the engine's default provider, not a
-    /// per-model provider. If you set compaction_model to a model from a different
-    /// provider (e.g., "claude-3-haiku" when default provider is OpenAI), the
-    /// summarization will fail. Ensure the compaction_model is compatible with
-    /// your configured provider.
     pub compaction_model: Option<String>,
+    /// Provider serving compaction_model. If None, the engine's default
+    /// provider is used.
+    #[uniffi(default = None)]
+    pub compaction_provider: Option<Provider>,
+    /// Fraction of context_limit at which sending a message first compacts the
+    /// conversation automatically. 0 disables automatic compaction.
+    #[uniffi(default = 0.9)]
+    pub compaction_threshold: f32,
+    /// Number of most recent user turns kept verbatim by automatic summarization.
+    #[uniffi(default = 2)]
+    pub preserve_turns: u32,
 }
 
 impl ModelContextConfig {
@@ -91,6 +104,9 @@ impl ModelContextConfig {
             compaction_mode: CompactionMode::default(),
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+            preserve_turns: DEFAULT_PRESERVE_TURNS,
         }
     }
 
@@ -110,12 +126,46 @@ impl ModelContextConfig {
         self.compaction_model = Some(model);
         self
     }
+
+    /// Set the provider that serves the compaction model.
+    pub fn with_compaction_provider(mut self, provider: Provider) -> Self {
+        self.compaction_provider = Some(provider);
+        self
+    }
+
+    /// Set the usage fraction that triggers automatic compaction (0 disables it).
+    pub fn with_compaction_threshold(mut self, threshold: f32) -> Self {
+        self.compaction_threshold = threshold;
+        self
+    }
+
+    /// Set how many recent user turns survive summarization verbatim.
+    pub fn with_preserve_turns(mut self, turns: u32) -> Self {
+        self.preserve_turns = turns;
+        self
+    }
+
+    /// Returns true if a conversation of `estimated_tokens` should be compacted
+    /// before the next LLM call.
+    pub fn needs_compaction(&self, estimated_tokens: u32) -> bool {
+        self.context_limit > 0
+            && self.compaction_threshold > 0.0
+            && estimated_tokens as f32 >= self.context_limit as f32 * self.compaction_threshold
+    }
+
+    /// Token count that automatic compaction cuts a conversation down to:
+    /// the safety margin, or just under the compaction threshold if lower.
+    pub fn compaction_target(&self) -> u32 {
+        let below_threshold = (self.context_limit as f32 * self.compaction_threshold) as u32;
+        effective_limit(self.context_limit).min(below_threshold.saturating_sub(1))
+    }
 }
 
-/// Estimate token count from text using byte-based heuristic
+/// Estimate token count from text using byte-based heuristic.
+/// Uses the same estimator as `LlmClient::count_tokens` for providers that
+/// can't count exactly.
 pub fn estimate_tokens(text: &str) -> u32 {
-    let bytes = text.len();
-    ((bytes + APPROX_BYTES_PER_TOKEN - 1) / APPROX_BYTES_PER_TOKEN) as u32
+    mux::llm::estimate_text_tokens(text) as u32
 }
 
 /// Calculate effective limit with safety margin.
@@ -162,6 +212,31 @@ mod tests {
         assert!(percent > 48.0 && percent < 49.0);
     }
 
+    #[test]
+    fn test_needs_compaction() {
+        let config = ModelContextConfig::new("m".to_string(), 1000);
+        assert!(!config.needs_compaction(899));
+        assert!(config.needs_compaction(900));
+
+        assert!(
+            !config
+                .clone()
+                .with_compaction_threshold(0.0)
+                .needs_compaction(5000)
+        );
+        assert!(!ModelContextConfig::new("m".to_string(), 0).needs_compaction(5000));
+    }
+
+    #[test]
+    fn test_compaction_target_is_below_threshold() {
+        let config = ModelContextConfig::new("m".to_string(), 1000);
+        assert_eq!(config.compaction_target(), 800);
+
+        let config = config.with_compaction_threshold(0.5);
+        assert_eq!(config.compaction_target(), 499);
+        assert!(!config.needs_compaction(config.compaction_target()));
+    }
+
     #[test]
     fn test_context_usage_no_limit() {
         let usage = ContextUsage::new(5, 2000, None);
@@ -0,0 +1,184 @@
+// ABOUTME: Loading agent configs from markdown files, and hot-reloading them.
+// ABOUTME: Tracks whether each agent came from a file or from register_agent.
+
+use super::MuxEngine;
+use crate::MuxFfiError;
+use crate::types::{AgentConfig, AgentSource};
+use mux::agent::{AgentDefinition, load_agent_dir};
+use std::path::{Path, PathBuf};
+
+/// Agent loading from directories
+#[uniffi::export]
+impl MuxEngine {
+    /// Load every `*.md` agent file in `path` and remember the directory for
+    /// `reload_agents`. Returns the names of the agents loaded.
+    ///
+    /// Agents registered with `register_agent` take precedence: a file with
+    /// the same name is skipped.
+    pub fn load_agents_from_dir(&self, path: String) -> Result<Vec<String>, MuxFfiError> {
+        let dir = PathBuf::from(path);
+        let files = read_agent_dir(&dir)?;
+        {
+            let mut dirs = self.agent_dirs.write();
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        Ok(self.apply_agent_files(files))
+    }
+
+    /// Re-read every directory passed to `load_agents_from_dir`, replacing
+    /// the agents loaded from files. Programmatic agents are left alone.
+    ///
+    /// If any file fails to load, nothing changes and the error is returned,
+    /// so a half-edited file doesn't unregister the agents in use.
+    pub fn reload_agents(&self) -> Result<Vec<String>, MuxFfiError> {
+        let dirs = self.agent_dirs.read().clone();
+        let mut files = Vec::new();
+        for dir in &dirs {
+            files.extend(read_agent_dir(dir)?);
+        }
+
+        {
+            let mut configs = self.agent_configs.write();
+            let mut sources = self.agent_sources.write();
+            sources.retain(|name, source| {
+                let from_file = matches!(source, AgentSource::File { .. });
+                if from_file {
+                    configs.remove(name);
+                }
+                !from_file
+            });
+        }
+        Ok(self.apply_agent_files(files))
+    }
+
+    /// Where a registered agent came from, or `None` if it isn't registered.
+    pub fn get_agent_source(&self, name: String) -> Option<AgentSource> {
+        self.agent_sources.read().get(&name).cloned()
+    }
+}
+
+impl MuxEngine {
+    /// Register file-loaded agents, skipping names registered programmatically.
+    fn apply_agent_files(&self, files: Vec<(PathBuf, AgentDefinition)>) -> Vec<String> {
+        let mut configs = self.agent_configs.write();
+        let mut sources = self.agent_sources.write();
+        let mut loaded = Vec::new();
+        for (path, definition) in files {
+            let name = definition.agent_type.clone();
+            if sources.get(&name) == Some(&AgentSource::Programmatic) {
+                continue;
+            }
+            configs.insert(name.clone(), AgentConfig::from(&definition));
+            sources.insert(
+                name.clone(),
+                AgentSource::File {
+                    path: path.to_string_lossy().to_string(),
+                },
+            );
+            loaded.push(name);
+        }
+        loaded
+    }
+}
+
+fn read_agent_dir(dir: &Path) -> Result<Vec<(PathBuf, AgentDefinition)>, MuxFfiError> {
+    load_agent_dir(dir).map_err(|e| MuxFfiError::Engine {
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn fresh_engine() -> Arc<MuxEngine> {
+        let dir = std::env::temp_dir().join(format!("mux-test-agents-{}", Uuid::new_v4()));
+        MuxEngine::new(dir.to_string_lossy().to_string()).unwrap()
+    }
+
+    fn agent_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mux-test-agent-files-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_and_reload_agents() {
+        let engine = fresh_engine();
+        let dir = agent_dir();
+        std::fs::write(
+            dir.join("reviewer.md"),
+            "---\nmodel: model-a\nmax_iterations: 3\n---\nYou review code.",
+        )
+        .unwrap();
+        std::fs::write(dir.join("writer.md"), "You write docs.").unwrap();
+
+        let loaded = engine
+            .load_agents_from_dir(dir.to_string_lossy().to_string())
+            .unwrap();
+        assert_eq!(loaded, vec!["reviewer", "writer"]);
+        assert!(matches!(
+            engine.get_agent_source("reviewer".into()),
+            Some(AgentSource::File { path }) if path.ends_with("reviewer.md")
+        ));
+
+        // Edit one file and remove the other
+        std::fs::write(
+            dir.join("reviewer.md"),
+            "---\nmodel: model-b\n---\nYou review code carefully.",
+        )
+        .unwrap();
+        std::fs::remove_file(dir.join("writer.md")).unwrap();
+        assert_eq!(engine.reload_agents().unwrap(), vec!["reviewer"]);
+
+        let config = engine
+            .agent_configs
+            .read()
+            .get("reviewer")
+            .cloned()
+            .unwrap();
+        assert_eq!(config.model.as_deref(), Some("model-b"));
+        assert_eq!(config.system_prompt, "You review code carefully.");
+        assert_eq!(config.max_iterations, 10);
+        assert_eq!(engine.list_agents(), vec!["reviewer"]);
+
+        // A broken file leaves the loaded agents in place
+        std::fs::write(dir.join("broken.md"), "---\nstreaming: maybe\n---\nHi").unwrap();
+        assert!(engine.reload_agents().is_err());
+        assert_eq!(engine.list_agents(), vec!["reviewer"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_keeps_programmatic_agents() {
+        let engine = fresh_engine();
+        let dir = agent_dir();
+        std::fs::write(dir.join("coder.md"), "From a file.").unwrap();
+        std::fs::write(dir.join("helper.md"), "From a file.").unwrap();
+
+        engine
+            .register_agent(AgentConfig::new("coder".into(), "From code.".into()))
+            .unwrap();
+        let loaded = engine
+            .load_agents_from_dir(dir.to_string_lossy().to_string())
+            .unwrap();
+        assert_eq!(loaded, vec!["helper"]);
+
+        engine.reload_agents().unwrap();
+        let configs = engine.agent_configs.read();
+        assert_eq!(configs["coder"].system_prompt, "From code.");
+        assert_eq!(configs["helper"].system_prompt, "From a file.");
+        assert_eq!(
+            engine.get_agent_source("coder".into()),
+            Some(AgentSource::Programmatic)
+        );
+        drop(configs);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
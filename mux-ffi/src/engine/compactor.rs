@@ -0,0 +1,336 @@
+// ABOUTME: ContextCompactor - summarizes older conversation turns with the LLM.
+// ABOUTME: Keeps the most recent turns verbatim so the ongoing exchange is undisturbed.
+
+use super::persistence::StoredMessage;
+use crate::context::{
+    APPROX_BYTES_PER_TOKEN, SUMMARIZATION_PROMPT, SUMMARY_PREFIX, estimate_tokens,
+};
+use mux::llm::{LlmClient, Message, Request};
+use mux::prelude::{ContentBlock, Role};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Instruction appended after the old messages so the model answers with a summary.
+const SUMMARIZE_REQUEST: &str =
+    "Summarize the conversation above following your instructions. Reply with the summary only.";
+
+/// Appended where `truncate_to_budget` cut text short.
+const TRUNCATION_MARKER: &str = "\n[truncated to fit the context limit]";
+
+/// The outcome of a successful compaction.
+pub(super) struct Compaction {
+    /// The new history: one user-role summary message followed by the preserved turns.
+    pub messages: Vec<StoredMessage>,
+    /// How many messages the summary replaced.
+    pub summarized: usize,
+}
+
+/// Replaces all but the most recent turns of a conversation with an LLM summary.
+///
+/// A turn starts at a user message with text (not a tool-results message), so
+/// tool calls and their results always stay together.
+pub(super) struct ContextCompactor {
+    client: Arc<dyn LlmClient>,
+    model: String,
+    preserve_turns: usize,
+}
+
+impl ContextCompactor {
+    pub fn new(
+        client: Arc<dyn LlmClient>,
+        model: impl Into<String>,
+        preserve_turns: usize,
+    ) -> Self {
+        Self {
+            client,
+            model: model.into(),
+            preserve_turns,
+        }
+    }
+
+    /// Index of the first preserved message, or None if there is nothing older to summarize.
+    fn split_point(&self, messages: &[StoredMessage]) -> Option<usize> {
+        if self.preserve_turns == 0 {
+            return (!messages.is_empty()).then_some(messages.len());
+        }
+
+        let turn_starts: Vec<usize> = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| {
+                m.role == Role::User
+                    && m.content
+                        .iter()
+                        .any(|b| matches!(b, ContentBlock::Text { .. }))
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let split = *turn_starts.iter().rev().nth(self.preserve_turns - 1)?;
+        (split > 0).then_some(split)
+    }
+
+    /// Summarize the messages before the preserved turns.
+    ///
+    /// Returns None if there are too few turns to compact or the summary
+    /// wouldn't be smaller than what it replaces.
+    pub async fn compact(&self, messages: &[StoredMessage]) -> Result<Option<Compaction>, String> {
+        let Some(split) = self.split_point(messages) else {
+            return Ok(None);
+        };
+        let (old, recent) = messages.split_at(split);
+
+        let mut request_messages: Vec<Message> = old
+            .iter()
+            .map(|m| Message {
+                role: m.role,
+                content: m.content.clone(),
+            })
+            .collect();
+        request_messages.push(Message::user(SUMMARIZE_REQUEST));
+
+        let request = Request::new(&self.model)
+            .system(SUMMARIZATION_PROMPT)
+            .messages(request_messages)
+            .max_tokens(4096);
+        let response = self
+            .client
+            .create_message(&request)
+            .await
+            .map_err(|e| format!("Summarization LLM call failed: {}", e))?;
+
+        let summary = response.text();
+        if summary.trim().is_empty() {
+            return Err("Compaction produced empty summary".to_string());
+        }
+
+        // The summary stands in for the oldest messages, so it takes their time
+        let summary_msg = StoredMessage {
+            role: Role::User,
+            content: vec![ContentBlock::text(format!(
+                "{}\n\n{}",
+                SUMMARY_PREFIX, summary
+            ))],
+            created_at: old.first().and_then(|m| m.created_at),
+        };
+
+        // INFINITE LOOP GUARD: only compact if it actually saves tokens
+        let old_tokens: u32 = old.iter().map(StoredMessage::estimated_tokens).sum();
+        if summary_msg.estimated_tokens() >= old_tokens {
+            return Ok(None);
+        }
+
+        let mut compacted = Vec::with_capacity(recent.len() + 1);
+        compacted.push(summary_msg);
+        compacted.extend_from_slice(recent);
+
+        Ok(Some(Compaction {
+            messages: compacted,
+            summarized: old.len(),
+        }))
+    }
+}
+
+/// Cut the longest text and tool result blocks in `messages` until they fit
+/// in `budget` tokens, returning how many messages were cut.
+///
+/// This is the fallback for history that summarizing can't shrink enough,
+/// e.g. a single turn whose tool results alone exceed the context limit.
+/// Tool calls and thinking blocks are left whole so the turn stays valid.
+pub(super) fn truncate_to_budget(messages: &mut [StoredMessage], budget: u32) -> usize {
+    let mut truncated = HashSet::new();
+    let mut total: u32 = messages.iter().map(StoredMessage::estimated_tokens).sum();
+    while total > budget {
+        let longest = messages
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(i, m)| m.content.iter_mut().map(move |block| (i, block)))
+            .filter_map(|(i, block)| match block {
+                ContentBlock::Text { text } | ContentBlock::ToolResult { content: text, .. } => {
+                    Some((i, text))
+                }
+                _ => None,
+            })
+            .filter(|(_, text)| text.len() > TRUNCATION_MARKER.len())
+            .max_by_key(|(_, text)| text.len());
+        let Some((i, text)) = longest else {
+            break;
+        };
+
+        let before = estimate_tokens(text);
+        let excess = (total - budget) as usize * APPROX_BYTES_PER_TOKEN + TRUNCATION_MARKER.len();
+        let mut keep = text.len().saturating_sub(excess);
+        while !text.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        text.truncate(keep);
+        text.push_str(TRUNCATION_MARKER);
+        total = total - before + estimate_tokens(text);
+        truncated.insert(i);
+    }
+    truncated.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mux::llm::{Response, StopReason, StreamEvent, Usage};
+    use mux::prelude::LlmError;
+    use std::sync::Mutex;
+
+    /// Client that records requests and answers with a fixed summary.
+    struct SummaryClient {
+        summary: String,
+        requests: Mutex<Vec<Request>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for SummaryClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            self.requests.lock().unwrap().push(req.clone());
+            Ok(Response {
+                id: "summary".to_string(),
+                content: vec![ContentBlock::text(&self.summary)],
+                stop_reason: StopReason::EndTurn,
+                model: req.model.clone(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    fn text(role: Role, text: &str) -> StoredMessage {
+        StoredMessage {
+            role,
+            content: vec![ContentBlock::text(text)],
+            created_at: None,
+        }
+    }
+
+    fn long_conversation() -> Vec<StoredMessage> {
+        let filler = "x".repeat(400);
+        vec![
+            text(Role::User, &format!("first question {}", filler)),
+            StoredMessage {
+                role: Role::Assistant,
+                content: vec![ContentBlock::ToolUse {
+                    id: "t1".to_string(),
+                    name: "read_file".to_string(),
+                    input: serde_json::json!({"path": "/tmp/a"}),
+                }],
+                created_at: None,
+            },
+            StoredMessage {
+                role: Role::User,
+                content: vec![ContentBlock::tool_result("t1", filler.clone())],
+                created_at: None,
+            },
+            text(Role::Assistant, &format!("first answer {}", filler)),
+            text(Role::User, "second question"),
+            text(Role::Assistant, "second answer"),
+        ]
+    }
+
+    fn compactor(summary: &str, preserve_turns: usize) -> (Arc<SummaryClient>, ContextCompactor) {
+        let client = Arc::new(SummaryClient {
+            summary: summary.to_string(),
+            requests: Mutex::new(Vec::new()),
+        });
+        let compactor = ContextCompactor::new(client.clone(), "summary-model", preserve_turns);
+        (client, compactor)
+    }
+
+    #[test]
+    fn test_compact_keeps_recent_turns_and_tool_pairs() {
+        let (client, compactor) = compactor("They read /tmp/a.", 1);
+        let messages = long_conversation();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let compaction = rt.block_on(compactor.compact(&messages)).unwrap().unwrap();
+
+        assert_eq!(compaction.summarized, 4);
+        assert_eq!(compaction.messages.len(), 3);
+        assert_eq!(compaction.messages[0].role, Role::User);
+        let ContentBlock::Text { text: summary } = &compaction.messages[0].content[0] else {
+            panic!("summary should be text");
+        };
+        assert!(summary.starts_with(SUMMARY_PREFIX));
+        assert!(summary.ends_with("They read /tmp/a."));
+        assert!(matches!(
+            &compaction.messages[1].content[0],
+            ContentBlock::Text { text } if text == "second question"
+        ));
+
+        // Only the old turn was sent, followed by the summarize instruction
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests[0].model, "summary-model");
+        assert_eq!(requests[0].messages.len(), 5);
+    }
+
+    #[test]
+    fn test_compact_skips_when_too_few_turns() {
+        let (client, compactor) = compactor("summary", 2);
+        let messages = long_conversation();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        assert!(
+            rt.block_on(compactor.compact(&messages[..4]))
+                .unwrap()
+                .is_none()
+        );
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compact_skips_when_summary_is_not_smaller() {
+        let (_client, compactor) = compactor(&"y".repeat(4000), 1);
+        let messages = long_conversation();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        assert!(rt.block_on(compactor.compact(&messages)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_truncate_to_budget_cuts_an_oversized_turn() {
+        let mut messages = long_conversation();
+        messages.truncate(4);
+        let before: u32 = messages.iter().map(StoredMessage::estimated_tokens).sum();
+
+        let truncated = truncate_to_budget(&mut messages, 50);
+
+        let after: u32 = messages.iter().map(StoredMessage::estimated_tokens).sum();
+        assert!(before > 50 && after <= 50, "{} -> {}", before, after);
+        assert!(truncated > 0);
+        // The tool call and its result are both still there
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(
+            &messages[1].content[0],
+            ContentBlock::ToolUse { .. }
+        ));
+        let ContentBlock::ToolResult { content, .. } = &messages[2].content[0] else {
+            panic!("tool result should be kept");
+        };
+        assert!(content.ends_with(TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn test_truncate_to_budget_leaves_fitting_history_alone() {
+        let mut messages = long_conversation();
+        let original = messages.clone();
+
+        assert_eq!(truncate_to_budget(&mut messages, 10_000), 0);
+        assert_eq!(messages.len(), original.len());
+    }
+}
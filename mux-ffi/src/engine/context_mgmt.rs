@@ -2,12 +2,14 @@
 // ABOUTME: Supports small context models by tracking and managing conversation size.
 
 use super::MuxEngine;
+use super::compactor::{ContextCompactor, truncate_to_budget};
 use super::persistence::StoredMessage;
 use crate::MuxFfiError;
 use crate::callback::ChatCallback;
 use crate::context::{
-    CompactionMode, ContextUsage, ModelContextConfig, SMALL_CONTEXT_THRESHOLD,
-    SUMMARIZATION_PROMPT, SUMMARY_PREFIX, effective_limit, estimate_tokens,
+    CompactionMode, ContextUsage, DEFAULT_COMPACTION_THRESHOLD, DEFAULT_PRESERVE_TURNS,
+    ModelContextConfig, SMALL_CONTEXT_THRESHOLD, SUMMARIZATION_PROMPT, SUMMARY_PREFIX,
+    effective_limit,
 };
 use crate::types::Provider;
 use mux::llm::{AnthropicClient, GeminiClient, LlmClient, Message, OpenAIClient, Request};
@@ -40,6 +42,8 @@ impl MuxEngine {
                 compaction_mode: CompactionMode::Summarize,
                 warning_threshold: 0.8,
                 compaction_model: None,
+                compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+                preserve_turns: DEFAULT_PRESERVE_TURNS,
             })
     }
 
@@ -176,23 +180,7 @@ impl MuxEngine {
         let history = self.message_history.read();
         if let Some(messages) = history.get(conversation_id) {
             let message_count = messages.len() as u32;
-            let total_tokens: u32 = messages
-                .iter()
-                .map(|m| {
-                    m.content
-                        .iter()
-                        .map(|block| match block {
-                            mux::llm::ContentBlock::Text { text } => estimate_tokens(text),
-                            mux::llm::ContentBlock::ToolUse { input, .. } => {
-                                estimate_tokens(&input.to_string())
-                            }
-                            mux::llm::ContentBlock::ToolResult { content, .. } => {
-                                estimate_tokens(content)
-                            }
-                        })
-                        .sum::<u32>()
-                })
-                .sum();
+            let total_tokens: u32 = messages.iter().map(StoredMessage::estimated_tokens).sum();
             (message_count, total_tokens)
         } else {
             (0, 0)
@@ -281,19 +269,7 @@ impl MuxEngine {
             let mut keep_from = messages.len();
 
             for (i, msg) in messages.iter().enumerate().rev() {
-                let msg_tokens: u32 = msg
-                    .content
-                    .iter()
-                    .map(|block| match block {
-                        mux::llm::ContentBlock::Text { text } => estimate_tokens(text),
-                        mux::llm::ContentBlock::ToolUse { input, .. } => {
-                            estimate_tokens(&input.to_string())
-                        }
-                        mux::llm::ContentBlock::ToolResult { content, .. } => {
-                            estimate_tokens(content)
-                        }
-                    })
-                    .sum();
+                let msg_tokens = msg.estimated_tokens();
 
                 if kept_tokens + msg_tokens <= target_tokens {
                     kept_tokens += msg_tokens;
@@ -344,7 +320,7 @@ impl MuxEngine {
         let llm_messages: Vec<Message> = messages
             .iter()
             .map(|m| Message {
-                role: m.role.clone(),
+                role: m.role,
                 content: m.content.clone(),
             })
             .collect();
@@ -392,9 +368,11 @@ impl MuxEngine {
 
         // Build compacted history
         let summary_content = format!("{}\n\n{}", SUMMARY_PREFIX, summary);
+        // The summary stands in for the oldest messages, so it takes their time
         let summary_msg = StoredMessage {
-            role: Role::Assistant,
+            role: Role::User,
             content: vec![ContentBlock::text(summary_content)],
+            created_at: messages.first().and_then(|m| m.created_at),
         };
 
         // Get most recent user message
@@ -404,14 +382,14 @@ impl MuxEngine {
             .find(|m| m.role == Role::User)
             .cloned();
 
-        // Build new history: [summary_as_assistant, most_recent_user]
+        // Build new history: [summary_as_user, most_recent_user]
         let mut compacted = vec![summary_msg];
         if let Some(user_msg) = recent_user_msg {
             compacted.push(user_msg);
         }
 
         // INFINITE LOOP GUARD: Check if compaction actually reduces tokens
-        let compacted_tokens = self.estimate_messages_tokens(&compacted);
+        let compacted_tokens: u32 = compacted.iter().map(StoredMessage::estimated_tokens).sum();
         let original_tokens = self.estimate_conversation_tokens(conversation_id).1;
 
         if compacted_tokens >= original_tokens {
@@ -420,21 +398,142 @@ impl MuxEngine {
         }
 
         // Commit compaction
+        self.replace_compacted(conversation_id, messages.len(), compacted);
+
+        Ok(())
+    }
+
+    /// Compact the conversation before the next LLM call if it has passed the
+    /// model's compaction threshold.
+    ///
+    /// The size is the one the previous run's last response reported, falling
+    /// back to the estimate when there is none or the history changed since.
+    ///
+    /// Small context models are truncated like in `compact_context`. Otherwise
+    /// older turns are summarized with the compaction model, keeping the model's
+    /// `preserve_turns` most recent turns; if those alone are still over the
+    /// limit, their longest text and tool results are cut down. Fires
+    /// `on_context_compacted` when messages were removed or cut.
+    pub(super) async fn auto_compact_context(
+        &self,
+        conversation_id: &str,
+        model: &str,
+        callback: &dyn ChatCallback,
+    ) -> Result<(), String> {
+        let config = self.model_context_configs.read().get(model).cloned();
+        let Some(config) = config else {
+            return Ok(());
+        };
+
+        let (message_count, estimated_tokens) = self.estimate_conversation_tokens(conversation_id);
+        if message_count == 0 {
+            return Ok(());
+        }
+        // The size the last response reported, while the history is as that run left it
+        let tokens = self
+            .context_tokens
+            .read()
+            .get(conversation_id)
+            .filter(|(count, _)| *count == message_count)
+            .map_or(estimated_tokens, |(_, tokens)| *tokens);
+        if !config.needs_compaction(tokens) {
+            return Ok(());
+        }
+
+        let compacted = if config.context_limit <= SMALL_CONTEXT_THRESHOLD {
+            self.truncate_oldest(conversation_id, effective_limit(config.context_limit));
+            let remaining = self.estimate_conversation_tokens(conversation_id).0;
+            self.save_messages(conversation_id);
+            message_count.saturating_sub(remaining)
+        } else {
+            let messages = self
+                .message_history
+                .read()
+                .get(conversation_id)
+                .cloned()
+                .unwrap_or_default();
+            let compacted_from = messages.len();
+            let client = self
+                .build_compaction_client(&config)
+                .map_err(|e| e.to_string())?;
+            let compactor = Self::build_compactor(client, model, &config);
+            let (mut messages, summarized) = match compactor.compact(&messages).await? {
+                Some(compaction) => (compaction.messages, compaction.summarized),
+                None => (messages, 0),
+            };
+            // A turn too large for the limit can't be summarized away
+            let truncated = truncate_to_budget(&mut messages, config.compaction_target());
+            if summarized + truncated > 0
+                && self.replace_compacted(conversation_id, compacted_from, messages)
+            {
+                (summarized + truncated) as u32
+            } else {
+                0
+            }
+        };
+
+        if compacted > 0 {
+            let (message_count, estimated_tokens) =
+                self.estimate_conversation_tokens(conversation_id);
+            let usage =
+                ContextUsage::new(message_count, estimated_tokens, Some(config.context_limit));
+            callback.on_context_compacted(compacted, usage);
+        }
+
+        Ok(())
+    }
+
+    /// Create a compactor using the configured compaction model, or `model` if none.
+    fn build_compactor(
+        client: Arc<dyn LlmClient>,
+        model: &str,
+        config: &ModelContextConfig,
+    ) -> ContextCompactor {
+        let summary_model = config
+            .compaction_model
+            .clone()
+            .unwrap_or_else(|| model.to_string());
+        ContextCompactor::new(client, summary_model, config.preserve_turns as usize)
+    }
+
+    /// Replace the first `compacted_from` messages of a conversation's history
+    /// with their compacted form and persist it. Messages appended while they
+    /// were being compacted are kept after it.
+    ///
+    /// Returns false, leaving the history alone, if it no longer has that many
+    /// messages (e.g. it was cleared meanwhile).
+    pub(super) fn replace_compacted(
+        &self,
+        conversation_id: &str,
+        compacted_from: usize,
+        mut compacted: Vec<StoredMessage>,
+    ) -> bool {
         {
             let mut history = self.message_history.write();
-            history.insert(conversation_id.to_string(), compacted);
+            let Some(messages) = history.get_mut(conversation_id) else {
+                return false;
+            };
+            if messages.len() < compacted_from {
+                return false;
+            }
+            compacted.extend(messages.drain(compacted_from..));
+            *messages = compacted;
         }
+        self.context_tokens.write().remove(conversation_id);
         self.save_messages(conversation_id);
-
-        Ok(())
+        true
     }
 
-    /// Build LLM client for compaction based on provider configuration.
+    /// Build LLM client for compaction, for the compaction provider if one is
+    /// configured and the default provider otherwise.
     fn build_compaction_client(
         &self,
-        _config: &ModelContextConfig,
+        config: &ModelContextConfig,
     ) -> Result<Arc<dyn LlmClient>, MuxFfiError> {
-        let provider = self.default_provider.read().clone();
+        let provider = config
+            .compaction_provider
+            .clone()
+            .unwrap_or_else(|| self.default_provider.read().clone());
 
         match &provider {
             Provider::Custom { name } => self
@@ -471,25 +570,4 @@ impl MuxEngine {
             }
         }
     }
-
-    /// Estimate tokens for a slice of messages (used by infinite loop guard).
-    fn estimate_messages_tokens(&self, messages: &[StoredMessage]) -> u32 {
-        messages
-            .iter()
-            .map(|m| {
-                m.content
-                    .iter()
-                    .map(|block| match block {
-                        mux::llm::ContentBlock::Text { text } => estimate_tokens(text),
-                        mux::llm::ContentBlock::ToolUse { input, .. } => {
-                            estimate_tokens(&input.to_string())
-                        }
-                        mux::llm::ContentBlock::ToolResult { content, .. } => {
-                            estimate_tokens(content)
-                        }
-                    })
-                    .sum::<u32>()
-            })
-            .sum()
-    }
 }
@@ -0,0 +1,329 @@
+// ABOUTME: Diagnostic snapshot of the engine for support bundles.
+// ABOUTME: Keeps a log of recent errors and reports state with secrets left out.
+
+use super::MuxEngine;
+use super::export::{REDACTED, is_secret_name};
+use super::mcp::McpClientHandle;
+use crate::MuxFfiError;
+use crate::types::{
+    DiagnosticsReport, ErrorLogEntry, McpServerDiagnostics, McpServerStatus, Provider,
+    WorkspaceDiagnostics,
+};
+use mux::mcp::McpHealth;
+use std::collections::HashMap;
+
+/// Errors kept for `diagnostics`; older ones are dropped.
+const ERROR_LOG_CAPACITY: usize = 50;
+
+/// Prefixes of well-known API key and token formats.
+const KEY_PREFIXES: &[&str] = &["sk-", "AIza", "ghp_", "github_pat_", "xoxb-"];
+
+/// Configured secrets shorter than this aren't masked, so a short key
+/// can't blank out ordinary words.
+const MIN_SECRET_LEN: usize = 8;
+
+fn provider_name(provider: &Provider) -> String {
+    match provider {
+        Provider::Custom { name } => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn mcp_status(handle: &McpClientHandle) -> McpServerStatus {
+    // A client that's locked is busy with a request, so it's connected
+    let Ok(client) = handle.client.try_lock() else {
+        return McpServerStatus::Connected;
+    };
+    match client.health() {
+        McpHealth::Connected => McpServerStatus::Connected,
+        McpHealth::Reconnecting { .. } => McpServerStatus::Reconnecting,
+        McpHealth::Disconnected { .. } => McpServerStatus::Disconnected,
+    }
+}
+
+/// Mask credentials in an error message: the `secrets` themselves, values
+/// that look like API keys, values after secret-sounding names
+/// (`api_key=x`, `x-api-key: x`) and bearer tokens.
+fn redact_message(message: &str, secrets: &[String]) -> String {
+    let mut message = message.to_string();
+    for secret in secrets.iter().filter(|s| s.len() >= MIN_SECRET_LEN) {
+        message = message.replace(secret.as_str(), REDACTED);
+    }
+
+    let mut redacted = String::with_capacity(message.len());
+    let mut mask_next = false;
+    for piece in message.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end();
+        let space = &piece[word.len()..];
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+
+        if word.is_empty() {
+            redacted.push_str(space);
+            continue;
+        }
+
+        if mask_next && !word.eq_ignore_ascii_case("bearer") {
+            redacted.push_str(REDACTED);
+            mask_next = false;
+        } else if let Some((name, _)) = word.split_once('=')
+            && is_secret_name(name)
+        {
+            redacted.push_str(name);
+            redacted.push('=');
+            redacted.push_str(REDACTED);
+        } else if KEY_PREFIXES.iter().any(|prefix| bare.starts_with(prefix)) {
+            redacted.push_str(REDACTED);
+        } else {
+            redacted.push_str(word);
+            mask_next = word.eq_ignore_ascii_case("bearer")
+                || (word.ends_with(':') && is_secret_name(word));
+        }
+        redacted.push_str(space);
+    }
+    redacted
+}
+
+/// Diagnostics for support bundles
+#[uniffi::export]
+impl MuxEngine {
+    /// Gather diagnostics to attach to a bug report: versions, configured
+    /// providers, per-workspace MCP server status, conversation and message
+    /// counts, and recent errors.
+    ///
+    /// The report holds no secrets. Providers are listed by name only, MCP
+    /// servers without their commands, arguments or URLs, and API keys and
+    /// other credentials are masked in error messages.
+    pub fn diagnostics(&self) -> DiagnosticsReport {
+        let mut providers: Vec<String> = self.api_keys.read().keys().map(provider_name).collect();
+        providers.sort();
+        let mut callback_providers: Vec<String> =
+            self.callback_providers.read().keys().cloned().collect();
+        callback_providers.sort();
+
+        // Take one lock at a time, since other paths hold several at once
+        let conversation_ids: HashMap<String, Vec<String>> = self
+            .conversations
+            .read()
+            .iter()
+            .map(|(ws, convs)| (ws.clone(), convs.iter().map(|c| c.id.clone()).collect()))
+            .collect();
+        let message_counts: HashMap<String, usize> = self
+            .message_history
+            .read()
+            .iter()
+            .map(|(id, messages)| (id.clone(), messages.len()))
+            .collect();
+        let servers: Vec<(String, Vec<McpServerDiagnostics>)> = {
+            let clients = self.mcp_clients.read();
+            self.workspaces
+                .read()
+                .values()
+                .map(|ws| {
+                    let connected = clients.get(&ws.id);
+                    let servers = ws
+                        .mcp_servers
+                        .iter()
+                        .map(|server| {
+                            let handle = connected.and_then(|c| c.get(&server.name));
+                            McpServerDiagnostics {
+                                name: server.name.clone(),
+                                transport_type: server.transport_type,
+                                enabled: server.enabled,
+                                status: handle.map_or(McpServerStatus::NotConnected, mcp_status),
+                                tool_count: handle.map_or(0, |h| h.tools.len() as u32),
+                            }
+                        })
+                        .collect();
+                    (ws.id.clone(), servers)
+                })
+                .collect()
+        };
+
+        let mut workspaces: Vec<WorkspaceDiagnostics> = servers
+            .into_iter()
+            .map(|(id, mcp_servers)| {
+                let conversations = conversation_ids.get(&id).map(Vec::as_slice).unwrap_or(&[]);
+                let message_count: usize = conversations
+                    .iter()
+                    .map(|c| message_counts.get(c).copied().unwrap_or(0))
+                    .sum();
+                WorkspaceDiagnostics {
+                    id,
+                    conversation_count: conversations.len() as u32,
+                    message_count: message_count as u32,
+                    mcp_servers,
+                }
+            })
+            .collect();
+        workspaces.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let secrets = self.secrets();
+        DiagnosticsReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            providers,
+            callback_providers,
+            default_provider: provider_name(&self.default_provider.read()),
+            workspaces,
+            conversation_count: conversation_ids.values().map(Vec::len).sum::<usize>() as u32,
+            message_count: message_counts.values().sum::<usize>() as u32,
+            running_agent_count: self.running_agents.read().len() as u32,
+            // Mask again in case a key was set after the error was logged
+            recent_errors: self
+                .error_log
+                .read()
+                .iter()
+                .map(|entry| ErrorLogEntry {
+                    message: redact_message(&entry.message, &secrets),
+                    ..entry.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// `diagnostics` as pretty-printed JSON.
+    pub fn diagnostics_json(&self) -> Result<String, MuxFfiError> {
+        serde_json::to_string_pretty(&self.diagnostics()).map_err(|e| MuxFfiError::Engine {
+            message: format!("Failed to serialize diagnostics: {}", e),
+        })
+    }
+}
+
+impl MuxEngine {
+    /// Remember an error reported to a callback, for `diagnostics`.
+    pub(super) fn record_error(&self, source: &str, message: &str) {
+        let entry = ErrorLogEntry {
+            timestamp_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            source: source.to_string(),
+            message: redact_message(message, &self.secrets()),
+        };
+
+        let mut log = self.error_log.write();
+        if log.len() == ERROR_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+
+    /// Every configured API key and key reference, to mask wherever it appears.
+    fn secrets(&self) -> Vec<String> {
+        let mut secrets: Vec<String> = self
+            .api_keys
+            .read()
+            .values()
+            .map(|config| config.api_key.clone())
+            .collect();
+        secrets.extend(
+            self.workspaces
+                .read()
+                .values()
+                .filter_map(|ws| ws.llm_config.as_ref()?.api_key_ref.clone()),
+        );
+        secrets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::McpServerConfig;
+    use mux::prelude::Role;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn fresh_engine() -> Arc<MuxEngine> {
+        let dir = std::env::temp_dir().join(format!("mux-test-diagnostics-{}", Uuid::new_v4()));
+        MuxEngine::new(dir.to_string_lossy().to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_diagnostics_names_providers_without_keys() {
+        let engine = fresh_engine();
+        engine.set_api_key(Provider::Anthropic, "sk-ant-api03-secret".to_string());
+        engine.set_provider_config(
+            Provider::OpenAI,
+            "openai-proxy-secret".to_string(),
+            Some("https://proxy.example.com".to_string()),
+            None,
+        );
+        engine.record_error(
+            "send_message",
+            "HTTP 401: invalid x-api-key: sk-ant-api03-secret (fallback openai-proxy-secret)",
+        );
+
+        let report = engine.diagnostics();
+        assert_eq!(report.providers, vec!["Anthropic", "OpenAI"]);
+        assert_eq!(report.default_provider, "Anthropic");
+        assert_eq!(report.recent_errors.len(), 1);
+        assert_eq!(report.recent_errors[0].source, "send_message");
+
+        let json = engine.diagnostics_json().unwrap();
+        assert!(json.contains("\"Anthropic\""));
+        assert!(!json.contains("sk-ant-api03-secret"));
+        assert!(!json.contains("openai-proxy-secret"));
+    }
+
+    #[test]
+    fn test_diagnostics_counts_workspaces_and_conversations() {
+        let engine = fresh_engine();
+        let ws = engine
+            .create_workspace("Diagnosed".to_string(), None)
+            .unwrap();
+        engine
+            .add_mcp_server(
+                ws.id.clone(),
+                McpServerConfig::stdio(
+                    "github".to_string(),
+                    "github-mcp".to_string(),
+                    vec!["--token".to_string(), "ghp_secret".to_string()],
+                ),
+            )
+            .unwrap();
+        let first = engine
+            .create_conversation(ws.id.clone(), "First".to_string())
+            .unwrap();
+        engine
+            .create_conversation(ws.id.clone(), "Second".to_string())
+            .unwrap();
+        engine.inject_test_message(&first.id, Role::User, "Hello");
+        engine.inject_test_message(&first.id, Role::Assistant, "Hi");
+
+        let report = engine.diagnostics();
+        assert_eq!(report.conversation_count, 2);
+        assert_eq!(report.message_count, 2);
+        assert_eq!(report.workspaces.len(), 1);
+
+        let workspace = &report.workspaces[0];
+        assert_eq!(workspace.id, ws.id);
+        assert_eq!(workspace.conversation_count, 2);
+        assert_eq!(workspace.message_count, 2);
+        assert_eq!(workspace.mcp_servers[0].name, "github");
+        assert_eq!(
+            workspace.mcp_servers[0].status,
+            McpServerStatus::NotConnected
+        );
+        assert!(!engine.diagnostics_json().unwrap().contains("ghp_secret"));
+    }
+
+    #[test]
+    fn test_redact_message() {
+        let secrets = vec!["configured-key".to_string(), "short".to_string()];
+        assert_eq!(
+            redact_message(
+                "Authorization: Bearer abc123 failed for configured-key",
+                &secrets
+            ),
+            "Authorization: Bearer <redacted> failed for <redacted>"
+        );
+        assert_eq!(
+            redact_message("GET /v1?api_key=xyz returned 403, a short\nreply", &secrets),
+            "GET /v1?api_key=<redacted> returned 403, a short\nreply"
+        );
+        assert_eq!(
+            redact_message("bad key \"sk-proj-123\" for task-runner", &secrets),
+            "bad key <redacted> for task-runner"
+        );
+    }
+}
@@ -1,15 +1,25 @@
 // ABOUTME: Shared helper utilities for MuxEngine modules.
 // ABOUTME: Contains pure functions that are used across multiple engine submodules.
 
+use mux::mcp::McpToolNaming;
+
+/// How the engine names MCP tools: `server:tool`.
+pub(crate) fn tool_naming() -> McpToolNaming {
+    McpToolNaming::server_name().with_separator(":")
+}
+
+/// Qualify `tool` from `server` as `server:tool`.
+pub(crate) fn qualified_tool_name(server: &str, tool: &str) -> String {
+    tool_naming().qualify(server, tool)
+}
+
 /// Parse a qualified tool name (server:tool) into its components.
-/// Returns None if the name doesn't contain a colon separator.
+/// Returns None if the name doesn't contain a colon separator. Server names
+/// can't contain a colon, so the server is everything before the first one.
 pub(crate) fn parse_qualified_tool_name(qualified_name: &str) -> Option<(String, String)> {
-    let parts: Vec<&str> = qualified_name.splitn(2, ':').collect();
-    if parts.len() == 2 {
-        Some((parts[0].to_string(), parts[1].to_string()))
-    } else {
-        None
-    }
+    let (server, _) = qualified_name.split_once(':')?;
+    let tool = tool_naming().parse(server, qualified_name)?;
+    Some((server.to_string(), tool.to_string()))
 }
 
 #[cfg(test)]
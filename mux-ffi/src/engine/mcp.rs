@@ -10,19 +10,24 @@ use crate::types::{
     PromptArgumentValue,
 };
 use mux::mcp::{
-    McpPromptContent, McpPromptInfo as MuxMcpPromptInfo,
+    McpErrorKind, McpPromptContent, McpPromptInfo as MuxMcpPromptInfo,
     McpResourceContent as MuxMcpResourceContent, McpResourceInfo as MuxMcpResourceInfo,
-    McpResourceTemplate as MuxMcpResourceTemplate,
+    McpResourceTemplate as MuxMcpResourceTemplate, McpServerEvent,
 };
 use mux::prelude::{
-    McpClient, McpContentBlock, McpServerConfig as MuxMcpServerConfig, McpToolInfo, McpTransport,
-    Tool, ToolDefinition,
+    McpClient, McpServerConfig as MuxMcpServerConfig, McpToolInfo, McpTransport, Tool,
+    ToolDefinition, ToolResult,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex as TokioMutex;
 
+/// How long `ping_mcp_server` waits, including for a tool call already
+/// holding the client, before reporting the server unresponsive.
+const PING_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Holds a connected MCP client and its cached capabilities.
 pub(super) struct McpClientHandle {
     pub client: Arc<TokioMutex<McpClient>>,
@@ -175,6 +180,44 @@ impl MuxEngine {
         });
     }
 
+    /// Ping a connected MCP server, returning the round-trip latency in
+    /// milliseconds. An error means the server is unresponsive, so the
+    /// caller may want to disconnect it.
+    pub fn ping_mcp_server(
+        self: Arc<Self>,
+        workspace_id: String,
+        server_name: String,
+    ) -> Result<u64, MuxFfiError> {
+        let client = {
+            let clients = self.mcp_clients.read();
+            clients
+                .get(&workspace_id)
+                .and_then(|ws| ws.get(&server_name))
+                .map(|h| h.client.clone())
+                .ok_or_else(|| MuxFfiError::Engine {
+                    message: format!("MCP server '{}' not connected", server_name),
+                })?
+        };
+
+        let handle = std::thread::spawn(move || {
+            let rt = Runtime::new().map_err(|e| MuxFfiError::Engine {
+                message: format!("Failed to create runtime: {}", e),
+            })?;
+
+            rt.block_on(async move {
+                let ping = async { client.lock().await.ping().await };
+                let latency = tokio::time::timeout(PING_TIMEOUT, ping)
+                    .await
+                    .map_err(|_| mux::error::McpError::Timeout(PING_TIMEOUT))??;
+                Ok(latency.as_millis() as u64)
+            })
+        });
+
+        handle.join().map_err(|e| MuxFfiError::Engine {
+            message: format!("Thread panicked: {:?}", e),
+        })?
+    }
+
     /// List all MCP resources available across connected servers in a workspace.
     pub fn list_mcp_resources(&self, workspace_id: String) -> Vec<McpResourceInfo> {
         let clients = self.mcp_clients.read();
@@ -282,13 +325,10 @@ impl MuxEngine {
 
             rt.block_on(async move {
                 let locked = client.lock().await;
-                let contents =
-                    locked
-                        .read_resource(&uri)
-                        .await
-                        .map_err(|e| MuxFfiError::Engine {
-                            message: e.to_string(),
-                        })?;
+                let contents = locked
+                    .read_resource(&uri)
+                    .await
+                    .map_err(MuxFfiError::from)?;
 
                 // Convert mux types to FFI types
                 Ok(contents
@@ -362,13 +402,10 @@ impl MuxEngine {
 
             rt.block_on(async move {
                 let locked = client.lock().await;
-                let result =
-                    locked
-                        .get_prompt(&name, args_map)
-                        .await
-                        .map_err(|e| MuxFfiError::Engine {
-                            message: e.to_string(),
-                        })?;
+                let result = locked
+                    .get_prompt(&name, args_map)
+                    .await
+                    .map_err(MuxFfiError::from)?;
 
                 // Convert to FFI type - simplify content to text only for v1
                 Ok(McpPromptResult {
@@ -430,7 +467,7 @@ impl MuxEngine {
         let mut workspace_clients: HashMap<String, McpClientHandle> = HashMap::new();
 
         for config in server_configs {
-            match self.connect_single_server(&config).await {
+            match self.connect_single_server(&workspace_id, &config).await {
                 Ok(handle) => {
                     eprintln!(
                         "Connected to MCP server '{}' with {} tools, {} resources, {} prompts",
@@ -443,6 +480,7 @@ impl MuxEngine {
                 }
                 Err(e) => {
                     eprintln!("Failed to connect to MCP server '{}': {}", config.name, e);
+                    self.record_error(&format!("mcp:{}", config.name), &e.to_string());
                 }
             }
         }
@@ -458,15 +496,15 @@ impl MuxEngine {
     /// Connect to a single MCP server.
     async fn connect_single_server(
         &self,
+        workspace_id: &str,
         config: &McpServerConfig,
-    ) -> Result<McpClientHandle, String> {
+    ) -> Result<McpClientHandle, MuxFfiError> {
         // Convert FFI config to mux config
         let transport = match config.transport_type {
             McpTransportType::Stdio => {
-                let command = config
-                    .command
-                    .as_ref()
-                    .ok_or_else(|| "Stdio transport requires command".to_string())?;
+                let command = config.command.as_ref().ok_or_else(|| MuxFfiError::Engine {
+                    message: "Stdio transport requires command".into(),
+                })?;
                 McpTransport::Stdio {
                     command: command.clone(),
                     args: config.args.clone(),
@@ -474,28 +512,25 @@ impl MuxEngine {
                 }
             }
             McpTransportType::Sse => {
-                let url = config
-                    .url
-                    .as_ref()
-                    .ok_or_else(|| "SSE transport requires URL".to_string())?;
-                McpTransport::Sse { url: url.clone() }
+                let url = config.url.as_ref().ok_or_else(|| MuxFfiError::Engine {
+                    message: "SSE transport requires URL".into(),
+                })?;
+                McpTransport::Sse {
+                    url: url.clone(),
+                    headers: HashMap::new(),
+                }
             }
         };
 
-        let mux_config = MuxMcpServerConfig {
-            name: config.name.clone(),
-            transport,
-        };
+        let mux_config = MuxMcpServerConfig::new(config.name.clone(), transport);
 
         // Connect and initialize
-        let mut client = McpClient::connect(mux_config)
-            .await
-            .map_err(|e| e.to_string())?;
+        let mut client = McpClient::connect(mux_config).await?;
 
-        client.initialize().await.map_err(|e| e.to_string())?;
+        client.initialize().await?;
 
         // Fetch available tools
-        let tools = client.list_tools().await.map_err(|e| e.to_string())?;
+        let tools = client.list_tools().await?;
 
         // Pagination safety limit to prevent infinite loops from buggy servers
         const MAX_PAGES: usize = 100;
@@ -512,10 +547,7 @@ impl MuxEngine {
                 );
                 break;
             }
-            let result = client
-                .list_resources(cursor.as_deref())
-                .await
-                .map_err(|e| e.to_string())?;
+            let result = client.list_resources(cursor.as_deref()).await?;
             resources.extend(result.resources);
             cursor = result.next_cursor;
             pages += 1;
@@ -536,10 +568,7 @@ impl MuxEngine {
                 );
                 break;
             }
-            let result = client
-                .list_resource_templates(cursor.as_deref())
-                .await
-                .map_err(|e| e.to_string())?;
+            let result = client.list_resource_templates(cursor.as_deref()).await?;
             resource_templates.extend(result.resource_templates);
             cursor = result.next_cursor;
             pages += 1;
@@ -560,10 +589,7 @@ impl MuxEngine {
                 );
                 break;
             }
-            let result = client
-                .list_prompts(cursor.as_deref())
-                .await
-                .map_err(|e| e.to_string())?;
+            let result = client.list_prompts(cursor.as_deref()).await?;
             prompts.extend(result.prompts);
             cursor = result.next_cursor;
             pages += 1;
@@ -572,6 +598,27 @@ impl MuxEngine {
             }
         }
 
+        // Keep the cached lists current when the server announces changes
+        let clients = Arc::clone(&self.mcp_clients);
+        let workspace_id = workspace_id.to_string();
+        let server_name = config.name.clone();
+        client.on_event(move |event| {
+            let mut clients = clients.write();
+            let Some(handle) = clients
+                .get_mut(&workspace_id)
+                .and_then(|servers| servers.get_mut(&server_name))
+            else {
+                return;
+            };
+            match event {
+                McpServerEvent::ToolsChanged(tools) => handle.tools = tools.clone(),
+                McpServerEvent::ResourcesChanged(resources) => handle.resources = resources.clone(),
+                McpServerEvent::PromptsChanged(prompts) => handle.prompts = prompts.clone(),
+                McpServerEvent::ResourceUpdated { .. } => {}
+            }
+        });
+        client.listen();
+
         Ok(McpClientHandle {
             client: Arc::new(TokioMutex::new(client)),
             tools,
@@ -666,7 +713,7 @@ impl MuxEngine {
             for handle in workspace_clients.values() {
                 for mcp_tool in &handle.tools {
                     tools.push(ToolDefinition {
-                        name: format!("{}:{}", handle.server_name, mcp_tool.name),
+                        name: helpers::qualified_tool_name(&handle.server_name, &mcp_tool.name),
                         description: mcp_tool.description.clone(),
                         input_schema: mcp_tool.input_schema.clone(),
                     });
@@ -684,40 +731,29 @@ impl MuxEngine {
 
     /// Execute a tool call using pre-captured MCP client references.
     /// This is immune to race conditions from workspace disconnection during message processing.
+    ///
+    /// Errors are classified with [`McpErrorKind`]: `Tool` when the tool ran and
+    /// reported a failure, `Protocol` when it could not be run at all.
     pub(super) async fn execute_tool_with_captured_client(
         captured_clients: &HashMap<String, Arc<TokioMutex<McpClient>>>,
         server_name: &str,
         tool_name: &str,
         arguments: serde_json::Value,
-    ) -> Result<String, String> {
-        let client_arc = captured_clients
-            .get(server_name)
-            .ok_or_else(|| format!("Server '{}' not available", server_name))?;
+    ) -> ToolResult {
+        let Some(client_arc) = captured_clients.get(server_name) else {
+            return McpErrorKind::Protocol
+                .tool_result(format!("Server '{}' not available", server_name));
+        };
 
         let client = client_arc.lock().await;
-        let result = client
-            .call_tool(tool_name, arguments)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        // Convert McpToolResult to string
-        let content_text: String = result
-            .content
-            .iter()
-            .map(|block| match block {
-                McpContentBlock::Text { text } => text.clone(),
-                McpContentBlock::Image { data, mime_type } => {
-                    format!("[Image: {} bytes, type: {}]", data.len(), mime_type)
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+        let result = match client.call_tool(tool_name, arguments).await {
+            Ok(result) => result,
+            Err(e) => return McpErrorKind::Protocol.tool_result(e.to_string()),
+        };
 
-        if result.is_error {
-            Err(content_text)
-        } else {
-            Ok(content_text)
-        }
+        // Images stay attached; the agent describes them in text for
+        // providers that can't take them
+        ToolResult::from(result)
     }
 }
 
@@ -725,7 +761,7 @@ impl MuxEngine {
 mod tests {
     use super::*;
     use crate::engine::MuxEngine;
-    use crate::types::McpServerConfig;
+    use crate::types::{AgentStopReason, McpServerConfig};
 
     fn test_dir(name: &str) -> String {
         std::env::temp_dir()
@@ -1063,9 +1099,17 @@ mod tests {
         impl SubagentEventHandler for DummyHandler {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -1152,6 +1196,19 @@ mod tests {
         engine.delete_workspace(ws.id).unwrap();
     }
 
+    #[test]
+    fn test_ping_mcp_server_not_connected() {
+        let engine = create_test_engine();
+        let ws = engine.create_workspace("Ping".to_string(), None).unwrap();
+
+        let result = engine
+            .clone()
+            .ping_mcp_server(ws.id.clone(), "unknown_server".to_string());
+        assert!(result.unwrap_err().to_string().contains("not connected"));
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
     #[test]
     fn test_read_mcp_resource_empty_uri() {
         let engine = create_test_engine();
@@ -9,7 +9,7 @@ use crate::callback::{ChatCallback, ChatResult, ToolUseRequest};
 use crate::task_tool::FfiTaskTool;
 use crate::types::Provider;
 use async_trait::async_trait;
-use mux::agent::{AgentDefinition, AgentRegistry, SubAgent};
+use mux::agent::{AgentDefinition, AgentRegistry, SubAgent, SystemPromptBuilder};
 use mux::hook::{Hook, HookAction, HookEvent, HookRegistry};
 use mux::llm::GeminiClient;
 use mux::prelude::{
@@ -20,6 +20,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex as TokioMutex;
 
+/// Think-act iterations a chat turn may take unless the conversation sets
+/// its own cap with `set_max_iterations`.
+pub(super) const DEFAULT_MAX_ITERATIONS: u32 = 50;
+
 /// Hook that proxies SubAgent events to ChatCallback for streaming UI updates.
 struct ChatCallbackHook {
     callback: Arc<Box<dyn ChatCallback>>,
@@ -74,9 +78,11 @@ impl Hook for ChatCallbackHook {
                 let callback = self.callback.clone();
                 let tool_id = tool_use_id.clone();
                 let content = result.content.clone();
+                let metadata_json =
+                    serde_json::to_string(&result.metadata).unwrap_or_else(|_| "{}".to_string());
 
                 tokio::task::spawn_blocking(move || {
-                    callback.on_tool_result(tool_id, content);
+                    callback.on_tool_result(tool_id, content, metadata_json);
                 })
                 .await
                 .ok();
@@ -192,7 +198,9 @@ impl MuxEngine {
                             }
                             Arc::new(client)
                         }
-                        Provider::Gemini => Arc::new(GeminiClient::new(&c.api_key)),
+                        Provider::Gemini => {
+                            Arc::new(GeminiClient::new(&c.api_key).with_id_source(self.id_source()))
+                        }
                         Provider::Custom { .. } => unreachable!(),
                     },
                     _ => {
@@ -206,14 +214,16 @@ impl MuxEngine {
                             let messages = history
                                 .entry(conversation_id.clone())
                                 .or_insert_with(Vec::new);
-                            messages.push(StoredMessage {
-                                role: Role::User,
-                                content: vec![ContentBlock::text(content.clone())],
-                            });
-                            messages.push(StoredMessage {
-                                role: Role::Assistant,
-                                content: vec![ContentBlock::text(echo_text.clone())],
-                            });
+                            StoredMessage::push(
+                                messages,
+                                Role::User,
+                                vec![ContentBlock::text(content.clone())],
+                            );
+                            StoredMessage::push(
+                                messages,
+                                Role::Assistant,
+                                vec![ContentBlock::text(echo_text.clone())],
+                            );
                         }
                         self.save_messages(&conversation_id);
                         self.check_and_warn_context(&conversation_id, callback.as_ref().as_ref());
@@ -224,9 +234,15 @@ impl MuxEngine {
                             tool_use_count: 0,
                             input_tokens: 0,
                             output_tokens: 0,
+                            cache_read_tokens: 0,
+                            cache_write_tokens: 0,
                             context_usage: self
                                 .get_context_usage(conversation_id.clone())
                                 .unwrap_or_default(),
+                            files_changed: Vec::new(),
+                            conversation_usage: self
+                                .get_conversation_usage(conversation_id.clone()),
+                            hit_iteration_limit: false,
                         });
                     }
                 }
@@ -283,32 +299,28 @@ impl MuxEngine {
             })
             .unwrap_or_else(|| ("~".to_string(), None));
 
-        let tool_list: String = tool_registry
-            .to_definitions()
-            .await
-            .iter()
-            .map(|t| format!("- {}: {}", t.name, t.description))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let base_prompt = custom_prompt.unwrap_or_else(|| {
-            "You are a helpful AI assistant with access to local tools.".to_string()
-        });
-
-        let system_prompt = format!(
-            "{}\n\n\
-            Available tools:\n{}\n\n\
-            IMPORTANT: When using file tools, always use ABSOLUTE paths (starting with / or ~).\n\
-            The workspace directory is: {}\n\
-            For example, use '{}/file.txt' instead of just 'file.txt'.",
-            base_prompt, tool_list, workspace_path, workspace_path
-        );
+        let system_prompt = custom_prompt
+            .map(SystemPromptBuilder::new)
+            .unwrap_or_default()
+            .tools(&tool_registry.to_definitions().await)
+            .workspace(workspace_path)
+            .build();
 
         // Create AgentDefinition with iteration limit
-        const MAX_AGENTIC_ITERATIONS: usize = 50;
+        let max_iterations = self.get_max_iterations(conversation_id.clone());
         let definition = AgentDefinition::new("chat", &system_prompt)
             .model(&model)
-            .max_iterations(MAX_AGENTIC_ITERATIONS);
+            .max_iterations(max_iterations as usize);
+
+        // Summarize or truncate older turns first if the history is near the model's limit
+        if let Err(e) = self
+            .auto_compact_context(&conversation_id, &model, callback.as_ref().as_ref())
+            .await
+        {
+            let error_msg = format!("Context compaction failed: {}", e);
+            callback.on_error(error_msg.clone());
+            return Err(error_msg);
+        }
 
         // Get existing conversation history
         let existing_messages: Vec<Message> = {
@@ -339,46 +351,41 @@ impl MuxEngine {
                 existing_messages,
             )
         };
+        subagent = subagent.with_tool_result_limits(self.tool_result_limits.read().clone());
 
-        // Attach hook registry with ChatCallbackHook for streaming
+        // Attach hook registry with ChatCallbackHook for streaming, then the
+        // user's hook handler so it can block or transform tool calls
         let hook_registry = Arc::new(HookRegistry::new());
         hook_registry
             .register(ChatCallbackHook::new(callback.clone()))
             .await;
+        if let Some(hook) = self.user_hook() {
+            hook_registry.register(hook).await;
+        }
         subagent = subagent.with_hooks(hook_registry);
 
         // Run the agent with the user's message
-        let result = match subagent.run(&content).await {
+        let mut result = match subagent.run(&content).await {
             Ok(result) => result,
             Err(e) => {
-                let error_str = e.to_string();
-                // Check if this is a max iterations error - handle gracefully
-                if error_str.contains("exceeded max iterations") {
-                    // Capture actual usage and tool count from the subagent
-                    let actual_usage = subagent.usage().clone();
-                    let actual_tool_count = subagent.tool_use_count();
-                    let termination_msg = format!(
-                        "Agent loop terminated after {} iterations to prevent infinite loops.",
-                        MAX_AGENTIC_ITERATIONS
-                    );
-                    mux::agent::SubAgentResult {
-                        agent_id: subagent.agent_id().to_string(),
-                        content: termination_msg,
-                        tool_use_count: actual_tool_count,
-                        usage: actual_usage,
-                        iterations: MAX_AGENTIC_ITERATIONS,
-                    }
-                } else {
-                    // On other errors, return without saving transcript.
-                    // This means the failed attempt is lost, but the conversation
-                    // remains consistent - user can retry with the same message.
-                    let error_msg = format!("Agent error: {}", e);
-                    callback.on_error(error_msg.clone());
-                    return Err(error_msg);
-                }
+                // On errors, return without saving transcript.
+                // This means the failed attempt is lost, but the conversation
+                // remains consistent - user can retry with the same message.
+                let error_msg = format!("Agent error: {}", e);
+                callback.on_error(error_msg.clone());
+                return Err(error_msg);
             }
         };
 
+        // Hitting the iteration cap is handled gracefully with an explanatory message
+        let hit_iteration_limit = result.hit_iteration_limit();
+        if hit_iteration_limit {
+            result.content = format!(
+                "Agent loop terminated after {} iterations to prevent infinite loops.",
+                max_iterations
+            );
+        }
+
         // Extract transcript and save to history
         let transcript = subagent.transcript();
         {
@@ -386,30 +393,56 @@ impl MuxEngine {
             let messages = history
                 .entry(conversation_id.clone())
                 .or_insert_with(Vec::new);
+            // The transcript starts with the existing history, so keep those
+            // timestamps and only stamp the messages added by this run
+            let previous: Vec<Option<u64>> = messages.iter().map(|m| m.created_at).collect();
             messages.clear();
-            for msg in transcript {
-                messages.push(StoredMessage {
-                    role: msg.role,
-                    content: msg.content.clone(),
-                });
+            for (i, msg) in transcript.iter().enumerate() {
+                match previous.get(i) {
+                    Some(&created_at) => messages.push(StoredMessage {
+                        role: msg.role,
+                        content: msg.content.clone(),
+                        created_at,
+                    }),
+                    None => StoredMessage::push(messages, msg.role, msg.content.clone()),
+                }
             }
         }
         self.save_messages(&conversation_id);
 
+        // Remember the context size the provider reported for the next turn's compaction check
+        self.context_tokens.write().insert(
+            conversation_id.clone(),
+            (transcript.len() as u32, subagent.context_tokens() as u32),
+        );
+
         // Check context warning
         self.check_and_warn_context(&conversation_id, callback.as_ref().as_ref());
 
+        // Add this turn's usage to the conversation's running total
+        self.conversation_usage
+            .write()
+            .entry(conversation_id.clone())
+            .or_default()
+            .merge(&result.usage_total);
+
         // Return result
         let context_usage = self
             .get_context_usage(conversation_id.clone())
             .unwrap_or_default();
+        let conversation_usage = self.get_conversation_usage(conversation_id.clone());
         Ok(ChatResult {
             conversation_id,
             final_text: result.content,
             tool_use_count: result.tool_use_count as u32,
             input_tokens: result.usage.input_tokens,
             output_tokens: result.usage.output_tokens,
+            cache_read_tokens: result.usage.cache_read_tokens,
+            cache_write_tokens: result.usage.cache_write_tokens,
             context_usage,
+            files_changed: result.files_changed,
+            conversation_usage,
+            hit_iteration_limit,
         })
     }
 
@@ -499,6 +532,7 @@ impl MuxEngine {
         let provider_clone = provider.clone();
         let api_key = provider_config.as_ref().map(|c| c.api_key.clone());
         let base_url = provider_config.as_ref().and_then(|c| c.base_url.clone());
+        let ids = self.id_source();
 
         // For custom providers, capture the client Arc upfront to avoid race conditions
         // (provider could be unregistered between validation and factory execution)
@@ -528,7 +562,9 @@ impl MuxEngine {
                     }
                     Arc::new(c)
                 }
-                Provider::Gemini => Arc::new(GeminiClient::new(api_key.as_deref().unwrap_or(""))),
+                Provider::Gemini => Arc::new(
+                    GeminiClient::new(api_key.as_deref().unwrap_or("")).with_id_source(ids.clone()),
+                ),
             }
         };
 
@@ -539,13 +575,16 @@ impl MuxEngine {
             engine_handler: self.subagent_event_handler.clone(),
         };
 
-        let task_tool = FfiTaskTool::new(
+        let mut task_tool = FfiTaskTool::new(
             agent_registry,
             tool_registry,
             client_factory,
             Box::new(handler_proxy),
         )
         .with_transcript_store(self.transcript_store.clone());
+        if let Some(hook) = self.user_hook() {
+            task_tool = task_tool.with_hook(Arc::new(hook));
+        }
 
         task_tool.execute(params).await.map_err(|e| e.to_string())
     }
@@ -555,8 +594,8 @@ impl MuxEngine {
 mod tests {
     use super::*;
     use crate::callback::{ChatCallback, SubagentEventHandler};
-    use crate::context::ContextUsage;
-    use crate::types::AgentConfig;
+    use crate::context::{ContextUsage, ModelContextConfig, SUMMARY_PREFIX};
+    use crate::types::{AgentConfig, AgentStopReason, ChatRole};
     use std::sync::atomic::{AtomicBool, Ordering};
 
     fn test_dir(name: &str) -> String {
@@ -573,16 +612,20 @@ mod tests {
     // Mock callback that tracks calls
     struct TrackingCallback {
         text_received: std::sync::Mutex<String>,
+        tool_results: std::sync::Mutex<Vec<(String, String, String)>>,
         error_received: std::sync::Mutex<Option<String>>,
         complete_called: AtomicBool,
+        compactions: std::sync::Mutex<Vec<u32>>,
     }
 
     impl TrackingCallback {
         fn new() -> Self {
             Self {
                 text_received: std::sync::Mutex::new(String::new()),
+                tool_results: std::sync::Mutex::new(Vec::new()),
                 error_received: std::sync::Mutex::new(None),
                 complete_called: AtomicBool::new(false),
+                compactions: std::sync::Mutex::new(Vec::new()),
             }
         }
     }
@@ -594,7 +637,12 @@ mod tests {
 
         fn on_tool_use(&self, _request: ToolUseRequest) {}
 
-        fn on_tool_result(&self, _tool_use_id: String, _result: String) {}
+        fn on_tool_result(&self, tool_use_id: String, result: String, metadata_json: String) {
+            self.tool_results
+                .lock()
+                .unwrap()
+                .push((tool_use_id, result, metadata_json));
+        }
 
         fn on_complete(&self, _result: ChatResult) {
             self.complete_called.store(true, Ordering::SeqCst);
@@ -605,6 +653,10 @@ mod tests {
         }
 
         fn on_context_warning(&self, _usage: ContextUsage) {}
+
+        fn on_context_compacted(&self, compacted_messages: u32, _usage: ContextUsage) {
+            self.compactions.lock().unwrap().push(compacted_messages);
+        }
     }
 
     #[test]
@@ -635,8 +687,8 @@ mod tests {
                     fn on_tool_use(&self, r: ToolUseRequest) {
                         self.0.on_tool_use(r);
                     }
-                    fn on_tool_result(&self, id: String, result: String) {
-                        self.0.on_tool_result(id, result);
+                    fn on_tool_result(&self, id: String, result: String, metadata_json: String) {
+                        self.0.on_tool_result(id, result, metadata_json);
                     }
                     fn on_complete(&self, r: ChatResult) {
                         self.0.on_complete(r);
@@ -647,6 +699,9 @@ mod tests {
                     fn on_context_warning(&self, u: ContextUsage) {
                         self.0.on_context_warning(u);
                     }
+                    fn on_context_compacted(&self, n: u32, u: ContextUsage) {
+                        self.0.on_context_compacted(n, u);
+                    }
                 }
                 Wrapper(callback.clone())
             })),
@@ -699,8 +754,8 @@ mod tests {
                     fn on_tool_use(&self, r: ToolUseRequest) {
                         self.0.on_tool_use(r);
                     }
-                    fn on_tool_result(&self, id: String, result: String) {
-                        self.0.on_tool_result(id, result);
+                    fn on_tool_result(&self, id: String, result: String, metadata_json: String) {
+                        self.0.on_tool_result(id, result, metadata_json);
                     }
                     fn on_complete(&self, r: ChatResult) {
                         self.0.on_complete(r);
@@ -711,6 +766,9 @@ mod tests {
                     fn on_context_warning(&self, u: ContextUsage) {
                         self.0.on_context_warning(u);
                     }
+                    fn on_context_compacted(&self, n: u32, u: ContextUsage) {
+                        self.0.on_context_compacted(n, u);
+                    }
                 }
                 Wrapper(callback.clone())
             })),
@@ -729,6 +787,67 @@ mod tests {
         engine.delete_workspace(ws.id).unwrap();
     }
 
+    #[test]
+    fn test_sent_messages_are_timestamped_in_order() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Timestamp Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        for text in ["first", "second"] {
+            let callback: Arc<Box<dyn ChatCallback>> = Arc::new(Box::new(TrackingCallback::new()));
+            rt.block_on(engine.do_send_message(conv.id.clone(), text.to_string(), callback))
+                .unwrap();
+        }
+
+        let messages = engine.get_messages(conv.id.clone());
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].role, ChatRole::User);
+        assert_eq!(messages[0].text, "first");
+        assert_eq!(messages[3].role, ChatRole::Assistant);
+
+        let stamps: Vec<u64> = messages.iter().map(|m| m.created_at.unwrap()).collect();
+        assert!(stamps.windows(2).all(|w| w[0] < w[1]));
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    #[test]
+    fn test_timestamps_survive_reload() {
+        let dir = test_dir("mux-test-message-timestamps");
+        let engine = MuxEngine::new(dir.clone()).unwrap();
+        let ws = engine
+            .create_workspace("Reload Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        engine.inject_test_message(&conv.id, Role::User, "question");
+        engine.inject_test_message(&conv.id, Role::Assistant, "answer");
+        engine.save_messages(&conv.id);
+        let before = engine.get_messages(conv.id.clone());
+        drop(engine);
+
+        let reloaded = MuxEngine::new(dir).unwrap();
+        let after = reloaded.get_messages(conv.id.clone());
+
+        assert_eq!(after.len(), 2);
+        assert_eq!(after[0].text, "question");
+        assert_eq!(after[1].text, "answer");
+        for (a, b) in before.iter().zip(&after) {
+            assert!(a.created_at.is_some());
+            assert_eq!(a.created_at, b.created_at);
+        }
+        assert!(after[0].created_at < after[1].created_at);
+
+        reloaded.delete_workspace(ws.id).unwrap();
+    }
+
     #[test]
     fn test_execute_task_tool_no_handler() {
         let engine = create_test_engine();
@@ -756,9 +875,17 @@ mod tests {
         impl SubagentEventHandler for DummyHandler {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -792,9 +919,17 @@ mod tests {
         impl SubagentEventHandler for DummyHandler {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -836,9 +971,17 @@ mod tests {
         impl SubagentEventHandler for DummyHandler {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -957,8 +1100,8 @@ mod tests {
         fn on_tool_use(&self, r: ToolUseRequest) {
             self.0.on_tool_use(r);
         }
-        fn on_tool_result(&self, id: String, result: String) {
-            self.0.on_tool_result(id, result);
+        fn on_tool_result(&self, id: String, result: String, metadata_json: String) {
+            self.0.on_tool_result(id, result, metadata_json);
         }
         fn on_complete(&self, r: ChatResult) {
             self.0.on_complete(r);
@@ -969,6 +1112,32 @@ mod tests {
         fn on_context_warning(&self, u: ContextUsage) {
             self.0.on_context_warning(u);
         }
+        fn on_context_compacted(&self, n: u32, u: ContextUsage) {
+            self.0.on_context_compacted(n, u);
+        }
+    }
+
+    #[test]
+    fn test_chat_callback_hook_forwards_tool_metadata() {
+        let callback = Arc::new(TrackingCallback::new());
+        let hook = ChatCallbackHook::new(Arc::new(Box::new(CallbackWrapper(callback.clone()))));
+
+        let event = HookEvent::PostToolUse {
+            tool_name: "query".to_string(),
+            tool_use_id: "tu_1".to_string(),
+            input: serde_json::json!({}),
+            result: mux::tool::ToolResult::text("3 rows").with_metadata("row_count", 3),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(hook.on_event(&event)).unwrap();
+
+        let results = callback.tool_results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        let (tool_id, content, metadata_json) = &results[0];
+        assert_eq!(tool_id, "tu_1");
+        assert_eq!(content, "3 rows");
+        let metadata: serde_json::Value = serde_json::from_str(metadata_json).unwrap();
+        assert_eq!(metadata, serde_json::json!({"row_count": 3}));
     }
 
     #[test]
@@ -1015,6 +1184,211 @@ mod tests {
         engine.delete_workspace(ws.id).unwrap();
     }
 
+    #[test]
+    fn test_do_send_message_compacts_long_history_first() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Compaction Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        // First call produces the summary, second answers the new message
+        let mock_provider = MockLlmProvider::new(vec![
+            MockLlmProvider::text_response("They discussed three long topics."),
+            MockLlmProvider::text_response("Answer after compaction"),
+        ]);
+        engine.register_llm_provider("mock-compact-llm".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-compact-llm".to_string(),
+        });
+        engine.set_model_context_config(
+            ModelContextConfig::new("mock-compact-llm".to_string(), 10_000).with_preserve_turns(1),
+        );
+
+        let filler = "x".repeat(15_000);
+        for _ in 0..3 {
+            engine.inject_test_message(&conv.id, Role::User, &filler);
+            engine.inject_test_message(&conv.id, Role::Assistant, &filler);
+        }
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(engine.do_send_message(
+                conv.id.clone(),
+                "What next?".to_string(),
+                Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+            ))
+            .unwrap();
+
+        assert_eq!(result.final_text, "Answer after compaction");
+        assert_eq!(*callback.compactions.lock().unwrap(), vec![4]);
+
+        // The summary replaces the first two turns; the last one is kept verbatim
+        let history = engine.message_history.read();
+        let messages = history.get(&conv.id).unwrap();
+        assert!(matches!(
+            &messages[0].content[0],
+            ContentBlock::Text { text } if text.starts_with(SUMMARY_PREFIX)
+        ));
+        assert!(matches!(
+            &messages[1].content[0],
+            ContentBlock::Text { text } if *text == filler
+        ));
+        drop(history);
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    #[test]
+    fn test_do_send_message_summarizes_with_compaction_provider() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Compaction Provider Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        // The chat provider only has the answer; the summary comes from the other one
+        engine.register_llm_provider(
+            "mock-chat-llm".to_string(),
+            Box::new(MockLlmProvider::new(vec![MockLlmProvider::text_response(
+                "Answer after compaction",
+            )])),
+        );
+        engine.register_llm_provider(
+            "mock-summary-llm".to_string(),
+            Box::new(MockLlmProvider::new(vec![MockLlmProvider::text_response(
+                "They discussed three long topics.",
+            )])),
+        );
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-chat-llm".to_string(),
+        });
+        engine.set_model_context_config(
+            ModelContextConfig::new("mock-chat-llm".to_string(), 10_000)
+                .with_preserve_turns(1)
+                .with_compaction_provider(Provider::Custom {
+                    name: "mock-summary-llm".to_string(),
+                }),
+        );
+
+        let filler = "x".repeat(15_000);
+        for _ in 0..3 {
+            engine.inject_test_message(&conv.id, Role::User, &filler);
+            engine.inject_test_message(&conv.id, Role::Assistant, &filler);
+        }
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(engine.do_send_message(
+                conv.id.clone(),
+                "What next?".to_string(),
+                Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+            ))
+            .unwrap();
+
+        assert_eq!(result.final_text, "Answer after compaction");
+        assert_eq!(*callback.compactions.lock().unwrap(), vec![4]);
+        let history = engine.message_history.read();
+        assert_eq!(history.get(&conv.id).unwrap()[0].role, Role::User);
+        drop(history);
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    #[test]
+    fn test_replace_compacted_keeps_messages_appended_meanwhile() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Replace Compacted Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+        let conv_id = conv.id.as_str();
+        for text in ["q1", "a1", "q2", "a2"] {
+            engine.inject_test_message(conv_id, Role::User, text);
+        }
+        let summary = StoredMessage {
+            role: Role::User,
+            content: vec![ContentBlock::text("summary")],
+            created_at: None,
+        };
+
+        // Compacted from the first three; "a2" and "q3" arrived after the snapshot
+        engine.inject_test_message(conv_id, Role::User, "q3");
+        assert!(engine.replace_compacted(conv_id, 3, vec![summary.clone()]));
+        let texts: Vec<String> = engine.message_history.read()[conv_id]
+            .iter()
+            .map(|m| match &m.content[0] {
+                ContentBlock::Text { text } => text.clone(),
+                other => panic!("unexpected block {:?}", other),
+            })
+            .collect();
+        assert_eq!(texts, ["summary", "a2", "q3"]);
+
+        // A history shorter than the snapshot was changed meanwhile, so it's left alone
+        assert!(!engine.replace_compacted(conv_id, 5, vec![summary]));
+        assert_eq!(engine.get_message_count(conv_id), 3);
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    #[test]
+    fn test_do_send_message_checks_compaction_with_reported_usage() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Reported Usage Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        // Only the answer is queued, so a summary request would fail the run
+        let mock_provider =
+            MockLlmProvider::new(vec![MockLlmProvider::text_response("No compaction needed")]);
+        engine.register_llm_provider("mock-usage-llm".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-usage-llm".to_string(),
+        });
+        engine.set_model_context_config(
+            ModelContextConfig::new("mock-usage-llm".to_string(), 10_000).with_preserve_turns(1),
+        );
+
+        // The estimate is far over the limit, but the last response said otherwise
+        let filler = "x".repeat(15_000);
+        for _ in 0..3 {
+            engine.inject_test_message(&conv.id, Role::User, &filler);
+            engine.inject_test_message(&conv.id, Role::Assistant, &filler);
+        }
+        engine
+            .context_tokens
+            .write()
+            .insert(conv.id.clone(), (6, 2_000));
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(engine.do_send_message(
+                conv.id.clone(),
+                "What next?".to_string(),
+                Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+            ))
+            .unwrap();
+
+        assert_eq!(result.final_text, "No compaction needed");
+        assert!(callback.compactions.lock().unwrap().is_empty());
+        // The new record covers the user message and the reply's 10 + 20 tokens
+        assert_eq!(engine.context_tokens.read().get(&conv.id), Some(&(7, 30)));
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
     #[test]
     fn test_do_send_message_with_mock_llm_tool_use() {
         let engine = create_test_engine();
@@ -1056,6 +1430,180 @@ mod tests {
         engine.delete_workspace(ws.id).unwrap();
     }
 
+    #[test]
+    fn test_do_send_message_reports_files_changed() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Files Changed Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        let path = test_dir("mux-ffi-files-changed.txt");
+        let args = serde_json::json!({"path": path, "content": "hello"}).to_string();
+        let mock_provider = MockLlmProvider::new(vec![
+            MockLlmProvider::tool_call_response("write_file", &args),
+            MockLlmProvider::text_response("Wrote the file"),
+        ]);
+        engine.register_llm_provider("mock-write-llm".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-write-llm".to_string(),
+        });
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(engine.do_send_message(
+                conv.id.clone(),
+                "Write a file".to_string(),
+                Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+            ))
+            .unwrap();
+
+        assert_eq!(result.files_changed, vec![path.clone()]);
+
+        let _ = std::fs::remove_file(&path);
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    #[test]
+    fn test_do_send_message_truncates_tool_results() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Truncation Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        let path = test_dir("mux-ffi-truncation.txt");
+        std::fs::write(&path, "x".repeat(500)).unwrap();
+        let args = serde_json::json!({"path": path}).to_string();
+        let mock_provider = MockLlmProvider::new(vec![
+            MockLlmProvider::tool_call_response("read_file", &args),
+            MockLlmProvider::text_response("Read it"),
+        ]);
+        engine.register_llm_provider("mock-truncate-llm".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-truncate-llm".to_string(),
+        });
+        engine.set_max_tool_result_bytes(10_000);
+        engine.set_tool_result_limit("read_file".to_string(), 50);
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(engine.do_send_message(
+            conv.id.clone(),
+            "Read the file".to_string(),
+            Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+        ))
+        .unwrap();
+
+        let history = engine.message_history.read();
+        let result = history[&conv.id]
+            .iter()
+            .flat_map(|m| &m.content)
+            .find_map(|b| match b {
+                ContentBlock::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(result.contains("[truncated "), "{}", result);
+        assert!(result.len() < 100);
+        drop(history);
+
+        let _ = std::fs::remove_file(&path);
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
+    /// Hook handler that blocks `write_file` and records event names.
+    struct BlockWritesHook {
+        events: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl crate::callback::HookHandler for BlockWritesHook {
+        fn on_event(&self, event: crate::types::HookEventType) -> crate::types::HookResponse {
+            use crate::types::{HookEventType, HookResponse};
+
+            let name = match &event {
+                HookEventType::PreToolUse { tool_name, .. } => format!("pre:{}", tool_name),
+                HookEventType::PostToolUse { tool_name, .. } => format!("post:{}", tool_name),
+                HookEventType::AgentStart { .. } => "start".to_string(),
+                HookEventType::AgentStop { .. } => "stop".to_string(),
+                HookEventType::Iteration { iteration, .. } => format!("iteration:{}", iteration),
+                HookEventType::LlmRequest { .. } | HookEventType::LlmResponse { .. } => {
+                    return HookResponse::Continue;
+                }
+            };
+            self.events.lock().unwrap().push(name);
+            match event {
+                HookEventType::PreToolUse { tool_name, .. } if tool_name == "write_file" => {
+                    HookResponse::Block {
+                        reason: "read-only session".to_string(),
+                    }
+                }
+                _ => HookResponse::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn test_do_send_message_hook_handler_blocks_tool() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Hook Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        let path = test_dir("mux-ffi-hook-blocked.txt");
+        let _ = std::fs::remove_file(&path);
+        let args = serde_json::json!({"path": path, "content": "hi"}).to_string();
+        let mock_provider = MockLlmProvider::new(vec![
+            MockLlmProvider::tool_call_response("write_file", &args),
+            MockLlmProvider::text_response("Could not write"),
+        ]);
+        engine.register_llm_provider("mock-hook-llm".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-hook-llm".to_string(),
+        });
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        engine.set_hook_handler(Arc::new(BlockWritesHook {
+            events: events.clone(),
+        }));
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(engine.do_send_message(
+            conv.id.clone(),
+            "Write the file".to_string(),
+            Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+        ))
+        .unwrap();
+        engine.clear_hook_handler();
+
+        assert!(!std::path::Path::new(&path).exists());
+        let tool_results = callback.tool_results.lock().unwrap();
+        assert_eq!(tool_results.len(), 1);
+        let (_, result, _) = &tool_results[0];
+        assert!(result.contains("Blocked by hook: read-only session"));
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "start",
+                "iteration:1",
+                "pre:write_file",
+                "post:write_file",
+                "iteration:2",
+                "stop",
+            ]
+        );
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
     #[test]
     fn test_do_send_message_accumulates_tokens() {
         let engine = create_test_engine();
@@ -1147,12 +1695,51 @@ mod tests {
 
         // Should have hit the limit (50 iterations)
         assert!(chat_result.tool_use_count >= 49); // At least 49 tool uses
+        assert!(chat_result.hit_iteration_limit);
         assert!(chat_result.final_text.contains("terminated after"));
         assert!(chat_result.final_text.contains("50 iterations"));
 
         engine.delete_workspace(ws.id).unwrap();
     }
 
+    #[test]
+    fn test_do_send_message_conversation_iteration_cap() {
+        let engine = create_test_engine();
+        let ws = engine
+            .create_workspace("Iter Cap Test".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        let mock_provider = MockLlmProvider::new(vec![MockLlmProvider::tool_call_response(
+            "read_file",
+            r#"{"path": "/tmp/loop.txt"}"#,
+        )]);
+        engine.register_llm_provider("iter-cap-test".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "iter-cap-test".to_string(),
+        });
+        assert_eq!(engine.get_max_iterations(conv.id.clone()), 50);
+        engine.set_max_iterations(conv.id.clone(), 3);
+
+        let callback = Arc::new(TrackingCallback::new());
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let chat_result = rt
+            .block_on(engine.do_send_message(
+                conv.id.clone(),
+                "Loop forever".to_string(),
+                Arc::new(Box::new(CallbackWrapper(callback.clone()))),
+            ))
+            .unwrap();
+
+        assert!(chat_result.hit_iteration_limit);
+        assert_eq!(chat_result.tool_use_count, 3);
+        assert!(chat_result.final_text.contains("3 iterations"));
+
+        engine.delete_workspace(ws.id).unwrap();
+    }
+
     #[test]
     fn test_do_send_message_llm_error_response() {
         let engine = create_test_engine();
@@ -1267,4 +1854,144 @@ mod tests {
 
         engine.delete_workspace(ws.id).unwrap();
     }
+
+    /// Serve one OpenAI chat completion on a local port, returning the base
+    /// URL and a handle that yields the JSON body of the request received.
+    fn serve_openai_completion(text: &str) -> (String, std::thread::JoinHandle<serde_json::Value>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let body = serde_json::json!({
+            "id": "chatcmpl-test",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": text},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        })
+        .to_string();
+
+        let handle = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let request_body = loop {
+                let n = socket.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break text[header_end + 4..].to_string();
+                    }
+                }
+                assert!(n > 0, "connection closed before the request was read");
+            };
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).unwrap();
+            serde_json::from_str(&request_body).unwrap()
+        });
+
+        (base_url, handle)
+    }
+
+    #[test]
+    fn test_tool_history_replays_to_openai_after_reload() {
+        let dir = test_dir("mux-test-openai-replay");
+        let engine = MuxEngine::new(dir.clone()).unwrap();
+        let ws = engine
+            .create_workspace("OpenAI Replay".to_string(), None)
+            .unwrap();
+        let conv = engine
+            .create_conversation(ws.id.clone(), "Test Conv".to_string())
+            .unwrap();
+
+        // A first turn that calls a tool, saved to disk as it completes
+        let mock_provider = MockLlmProvider::new(vec![
+            MockLlmProvider::tool_call_response("read_file", r#"{"path": "/tmp/replay.txt"}"#),
+            MockLlmProvider::text_response("The file is missing."),
+        ]);
+        engine.register_llm_provider("mock-replay".to_string(), Box::new(mock_provider));
+        engine.set_default_provider(Provider::Custom {
+            name: "mock-replay".to_string(),
+        });
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(engine.do_send_message(
+            conv.id.clone(),
+            "Read /tmp/replay.txt".to_string(),
+            Arc::new(Box::new(CallbackWrapper(Arc::new(TrackingCallback::new())))),
+        ))
+        .unwrap();
+        let tool_use_id = engine.message_history.read()[&conv.id]
+            .iter()
+            .flat_map(|m| &m.content)
+            .find_map(|block| match block {
+                ContentBlock::ToolUse { id, .. } => Some(id.clone()),
+                _ => None,
+            })
+            .unwrap();
+        drop(engine);
+
+        // After a restart, the next turn goes to OpenAI with that history
+        let reloaded = MuxEngine::new(dir).unwrap();
+        let (base_url, server) = serve_openai_completion("You're welcome.");
+        reloaded.set_provider_config(
+            Provider::OpenAI,
+            "test-key".to_string(),
+            Some(base_url),
+            Some("gpt-4o".to_string()),
+        );
+        reloaded.set_default_provider(Provider::OpenAI);
+        let result = rt
+            .block_on(reloaded.do_send_message(
+                conv.id.clone(),
+                "Thanks".to_string(),
+                Arc::new(Box::new(CallbackWrapper(Arc::new(TrackingCallback::new())))),
+            ))
+            .unwrap();
+        assert_eq!(result.final_text, "You're welcome.");
+
+        let body = server.join().unwrap();
+        let messages: Vec<&serde_json::Value> = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|m| m["role"] != "system")
+            .collect();
+        let roles: Vec<&str> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            roles,
+            vec!["user", "assistant", "tool", "assistant", "user"]
+        );
+
+        let call = &messages[1]["tool_calls"][0];
+        assert_eq!(call["id"], tool_use_id.as_str());
+        assert_eq!(call["function"]["name"], "read_file");
+        let arguments: serde_json::Value =
+            serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(arguments, serde_json::json!({"path": "/tmp/replay.txt"}));
+        assert_eq!(messages[2]["tool_call_id"], tool_use_id.as_str());
+        assert_eq!(messages[3]["content"], "The file is missing.");
+        assert_eq!(messages[4]["content"], "Thanks");
+
+        reloaded.delete_workspace(ws.id).unwrap();
+    }
 }
@@ -1,7 +1,11 @@
 // ABOUTME: MuxEngine - the main entry point for the FFI layer.
 // ABOUTME: Manages workspaces, conversations, and bridges to mux core.
 
+mod agents;
+mod compactor;
 mod context_mgmt;
+mod diagnostics;
+mod export;
 mod helpers;
 mod mcp;
 mod messaging;
@@ -11,22 +15,24 @@ mod tool_wrappers;
 mod workspace;
 
 use crate::MuxFfiError;
-use crate::bridge::FfiToolBridge;
+use crate::bridge::{FfiHookBridge, FfiToolBridge};
 use crate::callback::{
     ChatCallback, CustomTool, HookHandler, LlmProvider, SubagentCallback, SubagentEventHandler,
 };
 use crate::callback_client::CallbackLlmClient;
 use crate::context::ModelContextConfig;
 use crate::types::{
-    AgentConfig, ApprovalDecision, Conversation, Provider, TranscriptData, Workspace,
+    AgentConfig, AgentSource, ApprovalDecision, Conversation, ErrorLogEntry, Provider,
+    TranscriptData, UsageSummary, Workspace,
 };
-use mux::agent::MemoryTranscriptStore;
+use mux::agent::{CancellationToken, MemoryTranscriptStore};
+use mux::llm::{IdSource, PriceTable, SeqIdSource, UsageTracker, UuidIdSource};
 #[cfg(test)]
 use mux::prelude::{ContentBlock, Role};
-use mux::tool::Tool;
+use mux::tool::{Tool, ToolResultLimits};
 use mux::tools::{BashTool, ListFilesTool, ReadFileTool, SearchTool, WriteFileTool};
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -42,7 +48,9 @@ struct ProviderConfig {
 }
 
 use mcp::McpClientHandle;
+use messaging::DEFAULT_MAX_ITERATIONS;
 use persistence::{MESSAGES_DIR, StoredMessage};
+use subagent::SpawnRequest;
 
 #[derive(uniffi::Object)]
 pub struct MuxEngine {
@@ -65,8 +73,14 @@ pub struct MuxEngine {
     builtin_tools: Vec<Arc<dyn Tool>>,
     /// Registered agent configurations
     agent_configs: Arc<RwLock<HashMap<String, AgentConfig>>>,
-    /// Hook handler (optional)
-    hook_handler: Arc<RwLock<Option<Box<dyn HookHandler>>>>,
+    /// Where each registered agent came from, keyed by agent name
+    agent_sources: Arc<RwLock<HashMap<String, AgentSource>>>,
+    /// Directories loaded with load_agents_from_dir, re-read by reload_agents
+    agent_dirs: Arc<RwLock<Vec<PathBuf>>>,
+    /// Hook handler (optional), consulted by chat turns and subagents
+    hook_handler: Arc<RwLock<Option<Arc<dyn HookHandler>>>>,
+    /// Whether LlmRequest hook events carry the full request JSON
+    hook_full_request: Arc<RwLock<bool>>,
     /// Custom tools registered from Swift
     custom_tools: Arc<RwLock<HashMap<String, Arc<FfiToolBridge>>>>,
     /// Transcript storage for resume capability
@@ -79,6 +93,23 @@ pub struct MuxEngine {
     callback_providers: Arc<RwLock<HashMap<String, Arc<CallbackLlmClient>>>>,
     /// Per-model context configuration (context limit, compaction mode, etc.)
     model_context_configs: Arc<RwLock<HashMap<String, ModelContextConfig>>>,
+    /// Cancellation tokens for running subagents, keyed by agent_id
+    running_agents: Arc<RwLock<HashMap<String, CancellationToken>>>,
+    /// Per-model prices used for cost estimates, in USD per million tokens
+    model_prices: Arc<RwLock<PriceTable>>,
+    /// Token usage per conversation (in-memory only)
+    conversation_usage: Arc<RwLock<HashMap<String, UsageTracker>>>,
+    /// Context size per conversation as the last response reported it, with
+    /// the message count it covers (in-memory only)
+    context_tokens: Arc<RwLock<HashMap<String, (u32, u32)>>>,
+    /// Iteration caps set per conversation (in-memory only)
+    conversation_max_iterations: Arc<RwLock<HashMap<String, u32>>>,
+    /// Recent errors reported to callbacks, oldest first, for diagnostics
+    error_log: Arc<RwLock<VecDeque<ErrorLogEntry>>>,
+    /// Mints tool-call IDs that providers and hooks don't supply
+    id_source: Arc<RwLock<Arc<dyn IdSource>>>,
+    /// Caps on tool output sent to the model, applied to every agent run
+    tool_result_limits: Arc<RwLock<ToolResultLimits>>,
 }
 
 #[uniffi::export]
@@ -108,7 +139,7 @@ impl MuxEngine {
             Arc::new(WriteFileTool),
             Arc::new(ListFilesTool),
             Arc::new(SearchTool),
-            Arc::new(BashTool),
+            Arc::new(BashTool::new()),
         ];
 
         Ok(Arc::new(Self {
@@ -121,13 +152,24 @@ impl MuxEngine {
             pending_approvals: Arc::new(RwLock::new(HashMap::new())),
             builtin_tools,
             agent_configs: Arc::new(RwLock::new(HashMap::new())),
+            agent_sources: Arc::new(RwLock::new(HashMap::new())),
+            agent_dirs: Arc::new(RwLock::new(Vec::new())),
             hook_handler: Arc::new(RwLock::new(None)),
+            hook_full_request: Arc::new(RwLock::new(false)),
             custom_tools: Arc::new(RwLock::new(HashMap::new())),
             transcript_store: MemoryTranscriptStore::shared(),
             default_provider: Arc::new(RwLock::new(Provider::Anthropic)),
             subagent_event_handler: Arc::new(RwLock::new(None)),
             callback_providers: Arc::new(RwLock::new(HashMap::new())),
             model_context_configs: Arc::new(RwLock::new(HashMap::new())),
+            running_agents: Arc::new(RwLock::new(HashMap::new())),
+            model_prices: Arc::new(RwLock::new(PriceTable::new())),
+            conversation_usage: Arc::new(RwLock::new(HashMap::new())),
+            context_tokens: Arc::new(RwLock::new(HashMap::new())),
+            conversation_max_iterations: Arc::new(RwLock::new(HashMap::new())),
+            error_log: Arc::new(RwLock::new(VecDeque::new())),
+            id_source: Arc::new(RwLock::new(Arc::new(UuidIdSource))),
+            tool_result_limits: Arc::new(RwLock::new(ToolResultLimits::new())),
         }))
     }
 
@@ -182,9 +224,48 @@ impl MuxEngine {
         self.default_provider.read().clone()
     }
 
+    /// Set a model's price for cost estimates, in USD per million tokens.
+    /// `model` also prices longer model names that start with it, so
+    /// "claude-sonnet-4" covers "claude-sonnet-4-20250514".
+    pub fn set_model_price(&self, model: String, input_per_million: f64, output_per_million: f64) {
+        self.model_prices
+            .write()
+            .insert(model, (input_per_million, output_per_million));
+    }
+
+    /// Token usage for a conversation since the engine started, per model.
+    pub fn get_conversation_usage(&self, conversation_id: String) -> UsageSummary {
+        let usage = self.conversation_usage.read();
+        match usage.get(&conversation_id) {
+            Some(tracker) => UsageSummary::from_tracker(tracker, &self.model_prices.read()),
+            None => UsageSummary::default(),
+        }
+    }
+
+    /// Stop each turn of a conversation after `max_iterations` think-act
+    /// iterations. Turns that hit the cap report `hit_iteration_limit`.
+    pub fn set_max_iterations(&self, conversation_id: String, max_iterations: u32) {
+        self.conversation_max_iterations
+            .write()
+            .insert(conversation_id, max_iterations);
+    }
+
+    /// The iteration cap for a conversation's turns: the one set with
+    /// `set_max_iterations`, or 50.
+    pub fn get_max_iterations(&self, conversation_id: String) -> u32 {
+        self.conversation_max_iterations
+            .read()
+            .get(&conversation_id)
+            .copied()
+            .unwrap_or(DEFAULT_MAX_ITERATIONS)
+    }
+
     /// Register an agent configuration
     pub fn register_agent(&self, config: AgentConfig) -> Result<(), MuxFfiError> {
         let name = config.name.clone();
+        self.agent_sources
+            .write()
+            .insert(name.clone(), AgentSource::Programmatic);
         self.agent_configs.write().insert(name, config);
         Ok(())
     }
@@ -199,11 +280,15 @@ impl MuxEngine {
         if self.agent_configs.write().remove(&name).is_none() {
             return Err(MuxFfiError::AgentNotFound { name });
         }
+        self.agent_sources.write().remove(&name);
         Ok(())
     }
 
-    /// Set the hook handler for intercepting lifecycle events
-    pub fn set_hook_handler(&self, handler: Box<dyn HookHandler>) {
+    /// Set the hook handler for intercepting lifecycle events. Chat turns
+    /// and subagents started after this call report their tool calls,
+    /// iterations and LLM calls to it, and honor its Block and Transform
+    /// responses.
+    pub fn set_hook_handler(&self, handler: Arc<dyn HookHandler>) {
         *self.hook_handler.write() = Some(handler);
     }
 
@@ -212,6 +297,14 @@ impl MuxEngine {
         *self.hook_handler.write() = None;
     }
 
+    /// Send the whole request, conversation and images included, as
+    /// `request_json` in LlmRequest hook events. Off by default, so the
+    /// handler only gets the request summary; turn it on to Transform
+    /// requests.
+    pub fn set_hook_full_request(&self, enabled: bool) {
+        *self.hook_full_request.write() = enabled;
+    }
+
     /// Set the subagent event handler for TaskTool events.
     /// This handler receives streaming updates when subagents are spawned.
     pub fn set_subagent_event_handler(&self, handler: Box<dyn SubagentEventHandler>) {
@@ -223,6 +316,37 @@ impl MuxEngine {
         *self.subagent_event_handler.write() = None;
     }
 
+    /// Truncate tool results to `max_bytes` before they're sent to the model,
+    /// for tools without a limit of their own. Truncated results end with a
+    /// `[truncated N of M bytes]` marker.
+    pub fn set_max_tool_result_bytes(&self, max_bytes: u64) {
+        let mut limits = self.tool_result_limits.write();
+        *limits = std::mem::take(&mut *limits).max_bytes(max_bytes as usize);
+    }
+
+    /// Truncate results from the tool called `tool_name` to `max_bytes`,
+    /// overriding the limit set with `set_max_tool_result_bytes`.
+    pub fn set_tool_result_limit(&self, tool_name: String, max_bytes: u64) {
+        let mut limits = self.tool_result_limits.write();
+        *limits = std::mem::take(&mut *limits).for_tool(tool_name, max_bytes as usize);
+    }
+
+    /// Remove every tool result limit, so results are sent whole.
+    pub fn clear_tool_result_limits(&self) {
+        *self.tool_result_limits.write() = ToolResultLimits::new();
+    }
+
+    /// Name tool calls `<prefix>1`, `<prefix>2`, and so on instead of with
+    /// random UUIDs, so transcripts are reproducible across runs. Pass `None`
+    /// to go back to UUIDs. Turns already running keep their IDs.
+    pub fn set_tool_id_prefix(&self, prefix: Option<String>) {
+        let ids: Arc<dyn IdSource> = match prefix {
+            Some(prefix) => Arc::new(SeqIdSource::new(prefix)),
+            None => Arc::new(UuidIdSource),
+        };
+        *self.id_source.write() = ids;
+    }
+
     /// Register a callback-based LLM provider.
     /// The provider can then be used via `set_default_provider(Provider::Custom { name })`.
     pub fn register_llm_provider(&self, name: String, provider: Box<dyn LlmProvider>) {
@@ -287,7 +411,10 @@ impl MuxEngine {
                     .await
                 {
                     Ok(result) => cb.on_complete(result),
-                    Err(e) => cb.on_error(e),
+                    Err(e) => {
+                        engine.record_error("send_message", &e);
+                        cb.on_error(e)
+                    }
                 }
             });
         });
@@ -295,6 +422,7 @@ impl MuxEngine {
 
     /// Spawn a subagent to perform a task.
     /// The agent runs asynchronously and results are delivered via the callback.
+    /// Returns the agent_id, which can be passed to `cancel_agent`.
     pub fn spawn_agent(
         self: Arc<Self>,
         workspace_id: String,
@@ -302,35 +430,62 @@ impl MuxEngine {
         task: String,
         save_transcript: bool,
         callback: Box<dyn SubagentCallback>,
-    ) {
+    ) -> String {
         let engine = self.clone();
         let callback = Arc::new(callback);
+        let agent_id = uuid::Uuid::new_v4().to_string();
+        let cancel_token = self.register_running_agent(&agent_id);
+        let returned_id = agent_id.clone();
 
         std::thread::spawn(move || {
             let rt = match Runtime::new() {
                 Ok(rt) => rt,
                 Err(e) => {
-                    callback.on_error("".to_string(), format!("Failed to create runtime: {}", e));
+                    engine.running_agents.write().remove(&agent_id);
+                    callback.on_error(agent_id, format!("Failed to create runtime: {}", e));
                     return;
                 }
             };
 
             rt.block_on(async move {
-                match engine
+                let result = engine
                     .do_spawn_agent(
-                        workspace_id,
-                        agent_name,
-                        task,
-                        save_transcript,
+                        SpawnRequest {
+                            workspace_id,
+                            agent_id: agent_id.clone(),
+                            agent_name,
+                            task,
+                            save_transcript,
+                        },
+                        cancel_token,
                         callback.clone(),
                     )
-                    .await
-                {
+                    .await;
+                engine.running_agents.write().remove(&agent_id);
+                match result {
                     Ok(result) => callback.on_complete(result),
-                    Err(e) => callback.on_error("".to_string(), e),
+                    Err(e) => {
+                        engine.record_error("spawn_agent", &e);
+                        callback.on_error(agent_id, e)
+                    }
                 }
             });
         });
+
+        returned_id
+    }
+
+    /// Cancel a running subagent started with `spawn_agent` or `resume_agent`.
+    /// The agent stops at the next opportunity and its callback receives a result
+    /// with stop_reason `Cancelled`. Returns false if no such agent is running.
+    pub fn cancel_agent(&self, agent_id: String) -> bool {
+        match self.running_agents.read().get(&agent_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
     }
 
     /// Resume an agent from a saved transcript.
@@ -343,26 +498,60 @@ impl MuxEngine {
         let engine = self.clone();
         let callback = Arc::new(callback);
         let agent_id = transcript.agent_id.clone();
+        let cancel_token = self.register_running_agent(&agent_id);
 
         std::thread::spawn(move || {
             let rt = match Runtime::new() {
                 Ok(rt) => rt,
                 Err(e) => {
+                    engine.running_agents.write().remove(&agent_id);
                     callback.on_error(agent_id.clone(), format!("Failed to create runtime: {}", e));
                     return;
                 }
             };
 
             rt.block_on(async move {
-                match engine.do_resume_agent(transcript, callback.clone()).await {
+                let result = engine
+                    .do_resume_agent(transcript, cancel_token, callback.clone())
+                    .await;
+                engine.running_agents.write().remove(&agent_id);
+                match result {
                     Ok(result) => callback.on_complete(result),
-                    Err(e) => callback.on_error(agent_id, e),
+                    Err(e) => {
+                        engine.record_error("resume_agent", &e);
+                        callback.on_error(agent_id, e)
+                    }
                 }
             });
         });
     }
 }
 
+impl MuxEngine {
+    /// Create and store the cancellation token for an agent about to run.
+    fn register_running_agent(&self, agent_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.running_agents
+            .write()
+            .insert(agent_id.to_string(), token.clone());
+        token
+    }
+
+    /// The source for tool-call IDs minted on this engine's behalf.
+    fn id_source(&self) -> Arc<dyn IdSource> {
+        self.id_source.read().clone()
+    }
+
+    /// The user's hook handler as a core hook, if one is set.
+    fn user_hook(&self) -> Option<FfiHookBridge> {
+        let full_request = *self.hook_full_request.read();
+        self.hook_handler
+            .read()
+            .clone()
+            .map(|handler| FfiHookBridge::new(handler).with_full_request(full_request))
+    }
+}
+
 /// Test helper methods - only available in test builds
 #[cfg(test)]
 impl MuxEngine {
@@ -370,10 +559,7 @@ impl MuxEngine {
     pub(crate) fn inject_test_message(&self, conversation_id: &str, role: Role, text: &str) {
         let mut history = self.message_history.write();
         let messages = history.entry(conversation_id.to_string()).or_default();
-        messages.push(StoredMessage {
-            role,
-            content: vec![ContentBlock::text(text)],
-        });
+        StoredMessage::push(messages, role, vec![ContentBlock::text(text)]);
     }
 
     /// Get message count for a conversation (for test assertions).
@@ -3,6 +3,7 @@
 
 use super::MuxEngine;
 use crate::types::Conversation;
+use mux::llm::estimate_content_tokens;
 use mux::prelude::{ContentBlock, Role};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -20,6 +21,37 @@ pub(super) const MESSAGES_DIR: &str = "messages";
 pub(super) struct StoredMessage {
     pub role: Role,
     pub content: Vec<ContentBlock>,
+    /// Creation time in milliseconds since the Unix epoch.
+    /// None for messages saved before timestamps were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+}
+
+impl StoredMessage {
+    /// Append a new message to `messages`, stamped with the current time.
+    ///
+    /// Timestamps are kept strictly increasing within a history, so messages
+    /// created in the same millisecond still sort in the order they were added.
+    pub fn push(messages: &mut Vec<StoredMessage>, role: Role, content: Vec<ContentBlock>) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let created_at = match messages.iter().rev().find_map(|m| m.created_at) {
+            Some(last) if last >= now => last + 1,
+            _ => now,
+        };
+        messages.push(StoredMessage {
+            role,
+            content,
+            created_at: Some(created_at),
+        });
+    }
+
+    /// Rough token count of this message's content.
+    pub fn estimated_tokens(&self) -> u32 {
+        estimate_content_tokens(&self.content) as u32
+    }
 }
 
 /// Legacy format (pre-v0.6.2) stored content as String.
@@ -35,6 +67,7 @@ impl From<LegacyStoredMessage> for StoredMessage {
         StoredMessage {
             role: legacy.role,
             content: vec![ContentBlock::text(legacy.content)],
+            created_at: None,
         }
     }
 }
@@ -3,9 +3,10 @@
 
 use super::MuxEngine;
 use crate::callback::{SubagentCallback, SubagentEventHandler, ToolUseRequest};
-use crate::types::{Provider, SubagentResult, TranscriptData};
+use crate::types::{AgentStopReason, Provider, SubagentResult, TranscriptData, UsageSummary};
+use mux::agent::CancellationToken;
 use mux::hook::HookRegistry;
-use mux::llm::GeminiClient;
+use mux::llm::{GeminiClient, IdSource, UuidIdSource};
 use mux::prelude::{
     AgentDefinition, AnthropicClient, LlmClient, Message, OpenAIClient, Registry, SubAgent,
 };
@@ -44,9 +45,10 @@ impl SubagentEventHandler for TaskToolEventProxy {
         tool_name: String,
         result: String,
         is_error: bool,
+        metadata_json: String,
     ) {
         if let Some(handler) = self.engine_handler.read().as_ref() {
-            handler.on_tool_result(subagent_id, tool_name, result, is_error);
+            handler.on_tool_result(subagent_id, tool_name, result, is_error, metadata_json);
         }
     }
 
@@ -62,6 +64,7 @@ impl SubagentEventHandler for TaskToolEventProxy {
         content: String,
         tool_use_count: u32,
         iterations: u32,
+        stop_reason: AgentStopReason,
         transcript_saved: bool,
     ) {
         if let Some(handler) = self.engine_handler.read().as_ref() {
@@ -70,6 +73,7 @@ impl SubagentEventHandler for TaskToolEventProxy {
                 content,
                 tool_use_count,
                 iterations,
+                stop_reason,
                 transcript_saved,
             );
         }
@@ -94,16 +98,29 @@ impl SubagentEventHandler for TaskToolEventProxy {
     }
 }
 
-/// A hook that proxies tool events to the SubagentCallback.
-/// This enables streaming tool use/result events to Swift during subagent execution.
+/// A hook that proxies tool and text events to the SubagentCallback.
+/// This enables streaming text deltas and tool use/result events to Swift
+/// during subagent execution.
 pub(super) struct CallbackProxyHook {
     agent_id: String,
     callback: Arc<Box<dyn SubagentCallback>>,
+    /// Names tool uses, since PreToolUse doesn't carry the call's ID
+    ids: Arc<dyn IdSource>,
 }
 
 impl CallbackProxyHook {
     pub fn new(agent_id: String, callback: Arc<Box<dyn SubagentCallback>>) -> Self {
-        Self { agent_id, callback }
+        Self {
+            agent_id,
+            callback,
+            ids: Arc::new(UuidIdSource),
+        }
+    }
+
+    /// Mint tool-use IDs with `ids` instead of UUIDs.
+    pub fn with_id_source(mut self, ids: Arc<dyn IdSource>) -> Self {
+        self.ids = ids;
+        self
     }
 }
 
@@ -119,13 +136,17 @@ impl mux::hook::Hook for CallbackProxyHook {
                 self.callback.on_tool_use(
                     self.agent_id.clone(),
                     ToolUseRequest {
-                        id: uuid::Uuid::new_v4().to_string(),
+                        id: self.ids.next_id(),
                         tool_name: tool_name.clone(),
                         server_name: "builtin".to_string(),
                         arguments: serde_json::to_string(input).unwrap_or_default(),
                     },
                 );
             }
+            mux::hook::HookEvent::StreamDelta { text, .. } => {
+                self.callback
+                    .on_text_delta(self.agent_id.clone(), text.clone());
+            }
             mux::hook::HookEvent::PostToolUse {
                 tool_name: _,
                 tool_use_id,
@@ -151,17 +172,33 @@ impl mux::hook::Hook for CallbackProxyHook {
     }
 }
 
+/// The agent and task `spawn_agent` was asked to run.
+pub(super) struct SpawnRequest {
+    pub workspace_id: String,
+    /// Id handed back by spawn_agent, so cancel_agent can find the run.
+    pub agent_id: String,
+    pub agent_name: String,
+    pub task: String,
+    pub save_transcript: bool,
+}
+
 /// Subagent implementation methods.
 impl MuxEngine {
     /// Internal implementation of spawn_agent.
     pub(super) async fn do_spawn_agent(
         &self,
-        _workspace_id: String,
-        agent_name: String,
-        task: String,
-        save_transcript: bool,
+        request: SpawnRequest,
+        cancel_token: CancellationToken,
         callback: Arc<Box<dyn SubagentCallback>>,
     ) -> Result<SubagentResult, String> {
+        let SpawnRequest {
+            workspace_id: _workspace_id,
+            agent_id,
+            agent_name,
+            task,
+            save_transcript,
+        } = request;
+
         // Get agent config
         let config = self
             .agent_configs
@@ -197,7 +234,10 @@ impl MuxEngine {
                         }
                         Arc::new(c)
                     }
-                    Provider::Gemini => Arc::new(GeminiClient::new(&provider_config.api_key)),
+                    Provider::Gemini => Arc::new(
+                        GeminiClient::new(&provider_config.api_key)
+                            .with_id_source(self.id_source()),
+                    ),
                     Provider::Custom { .. } => unreachable!(),
                 }
             }
@@ -226,9 +266,12 @@ impl MuxEngine {
                 agent_name
             )
         })?;
+        // Stream so text reaches on_text_delta as it arrives. Providers without
+        // a streaming API replay their full response as a single delta.
         let mut definition = AgentDefinition::new(&agent_name, &config.system_prompt)
             .model(&model)
-            .max_iterations(config.max_iterations as usize);
+            .max_iterations(config.max_iterations as usize)
+            .streaming(true);
 
         // Apply allowed tools (empty means all allowed, so only set if non-empty)
         if !config.allowed_tools.is_empty() {
@@ -240,22 +283,24 @@ impl MuxEngine {
             definition = definition.denied_tools(config.denied_tools.clone());
         }
 
-        // Create subagent
-        let mut subagent = SubAgent::new(definition, client, registry);
-        let agent_id = subagent.agent_id().to_string();
+        // Create subagent under the id handed back by spawn_agent, so cancel_agent can find it
+        let mut subagent = SubAgent::new(definition, client, registry)
+            .with_agent_id(&agent_id)
+            .with_cancellation(cancel_token)
+            .with_tool_result_limits(self.tool_result_limits.read().clone());
 
         // Wire up callback via hook for tool events
         // We always want to proxy tool events to the callback, regardless of whether
         // a user hook handler is set
         let hook_registry = HookRegistry::new();
-        let proxy_hook = CallbackProxyHook::new(agent_id.clone(), callback.clone());
+        let proxy_hook = CallbackProxyHook::new(agent_id.clone(), callback.clone())
+            .with_id_source(self.id_source());
         hook_registry.register(proxy_hook).await;
 
-        // Note: User-provided hook handlers are stored in self.hook_handler, but
-        // we can't clone Box<dyn HookHandler> to pass to the subagent. The user's
-        // hook functionality is available via the callback interface for now.
-        // Future: Support cloneable hook handlers or use a different pattern.
-        let _ = &self.hook_handler; // Acknowledge hook_handler exists
+        // The user's hook handler sees, and can block, the subagent's tool calls
+        if let Some(hook) = self.user_hook() {
+            hook_registry.register(hook).await;
+        }
 
         subagent = subagent.with_hooks(Arc::new(hook_registry));
 
@@ -273,7 +318,9 @@ impl MuxEngine {
             content: result.content,
             tool_use_count: result.tool_use_count as u32,
             iterations: result.iterations as u32,
+            stop_reason: result.stop_reason.into(),
             transcript_json,
+            usage: UsageSummary::from_tracker(&result.usage_total, &self.model_prices.read()),
         })
     }
 
@@ -281,6 +328,7 @@ impl MuxEngine {
     pub(super) async fn do_resume_agent(
         &self,
         transcript: TranscriptData,
+        cancel_token: CancellationToken,
         callback: Arc<Box<dyn SubagentCallback>>,
     ) -> Result<SubagentResult, String> {
         // Parse transcript messages
@@ -314,7 +362,10 @@ impl MuxEngine {
                         }
                         Arc::new(c)
                     }
-                    Provider::Gemini => Arc::new(GeminiClient::new(&provider_config.api_key)),
+                    Provider::Gemini => Arc::new(
+                        GeminiClient::new(&provider_config.api_key)
+                            .with_id_source(self.id_source()),
+                    ),
                     Provider::Custom { .. } => unreachable!(),
                 }
             }
@@ -335,8 +386,9 @@ impl MuxEngine {
         }
 
         // Create definition for resume
-        let definition =
-            AgentDefinition::new("resumed", "You are a helpful assistant.").max_iterations(10);
+        let definition = AgentDefinition::new("resumed", "You are a helpful assistant.")
+            .max_iterations(10)
+            .streaming(true);
 
         // Resume agent with transcript
         let mut subagent = SubAgent::resume(
@@ -345,12 +397,18 @@ impl MuxEngine {
             client,
             registry,
             messages,
-        );
+        )
+        .with_cancellation(cancel_token)
+        .with_tool_result_limits(self.tool_result_limits.read().clone());
 
         // Wire up callback via hook for tool events
         let hook_registry = HookRegistry::new();
-        let proxy_hook = CallbackProxyHook::new(transcript.agent_id.clone(), callback.clone());
+        let proxy_hook = CallbackProxyHook::new(transcript.agent_id.clone(), callback.clone())
+            .with_id_source(self.id_source());
         hook_registry.register(proxy_hook).await;
+        if let Some(hook) = self.user_hook() {
+            hook_registry.register(hook).await;
+        }
         subagent = subagent.with_hooks(Arc::new(hook_registry));
 
         let result = subagent
@@ -363,7 +421,9 @@ impl MuxEngine {
             content: result.content,
             tool_use_count: result.tool_use_count as u32,
             iterations: result.iterations as u32,
+            stop_reason: result.stop_reason.into(),
             transcript_json: Some(serde_json::to_string(subagent.transcript()).unwrap_or_default()),
+            usage: UsageSummary::from_tracker(&result.usage_total, &self.model_prices.read()),
         })
     }
 }
@@ -378,6 +438,7 @@ mod tests {
         started_count: AtomicU32,
         tool_use_count: AtomicU32,
         tool_result_count: AtomicU32,
+        last_tool_metadata: parking_lot::Mutex<Option<String>>,
         iteration_count: AtomicU32,
         completed_count: AtomicU32,
         error_count: AtomicU32,
@@ -389,6 +450,7 @@ mod tests {
                 started_count: AtomicU32::new(0),
                 tool_use_count: AtomicU32::new(0),
                 tool_result_count: AtomicU32::new(0),
+                last_tool_metadata: parking_lot::Mutex::new(None),
                 iteration_count: AtomicU32::new(0),
                 completed_count: AtomicU32::new(0),
                 error_count: AtomicU32::new(0),
@@ -403,13 +465,22 @@ mod tests {
         fn on_tool_use(&self, _: String, _: String, _: String) {
             self.tool_use_count.fetch_add(1, Ordering::SeqCst);
         }
-        fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {
+        fn on_tool_result(&self, _: String, _: String, _: String, _: bool, metadata_json: String) {
             self.tool_result_count.fetch_add(1, Ordering::SeqCst);
+            *self.last_tool_metadata.lock() = Some(metadata_json);
         }
         fn on_iteration(&self, _: String, _: u32) {
             self.iteration_count.fetch_add(1, Ordering::SeqCst);
         }
-        fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {
+        fn on_agent_completed(
+            &self,
+            _: String,
+            _: String,
+            _: u32,
+            _: u32,
+            _: AgentStopReason,
+            _: bool,
+        ) {
             self.completed_count.fetch_add(1, Ordering::SeqCst);
         }
         fn on_agent_error(&self, _: String, _: String) {
@@ -435,14 +506,22 @@ mod tests {
                 fn on_tool_use(&self, a: String, b: String, c: String) {
                     self.0.on_tool_use(a, b, c);
                 }
-                fn on_tool_result(&self, a: String, b: String, c: String, d: bool) {
-                    self.0.on_tool_result(a, b, c, d);
+                fn on_tool_result(&self, a: String, b: String, c: String, d: bool, e: String) {
+                    self.0.on_tool_result(a, b, c, d, e);
                 }
                 fn on_iteration(&self, a: String, b: u32) {
                     self.0.on_iteration(a, b);
                 }
-                fn on_agent_completed(&self, a: String, b: String, c: u32, d: u32, e: bool) {
-                    self.0.on_agent_completed(a, b, c, d, e);
+                fn on_agent_completed(
+                    &self,
+                    a: String,
+                    b: String,
+                    c: u32,
+                    d: u32,
+                    e: AgentStopReason,
+                    f: bool,
+                ) {
+                    self.0.on_agent_completed(a, b, c, d, e, f);
                 }
                 fn on_agent_error(&self, a: String, b: String) {
                     self.0.on_agent_error(a, b);
@@ -473,9 +552,17 @@ mod tests {
             fn on_tool_use(&self, a: String, b: String, c: String) {
                 self.0.on_tool_use(a, b, c);
             }
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -500,11 +587,19 @@ mod tests {
         impl SubagentEventHandler for ForwardToArc {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, a: String, b: String, c: String, d: bool) {
-                self.0.on_tool_result(a, b, c, d);
+            fn on_tool_result(&self, a: String, b: String, c: String, d: bool, e: String) {
+                self.0.on_tool_result(a, b, c, d, e);
             }
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -515,8 +610,18 @@ mod tests {
             engine_handler: engine_handler.clone(),
         };
 
-        proxy.on_tool_result("id".into(), "tool".into(), "result".into(), false);
+        proxy.on_tool_result(
+            "id".into(),
+            "tool".into(),
+            "result".into(),
+            false,
+            r#"{"lines_changed":4}"#.into(),
+        );
         assert_eq!(handler.tool_result_count.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            handler.last_tool_metadata.lock().as_deref(),
+            Some(r#"{"lines_changed":4}"#)
+        );
     }
 
     #[test]
@@ -529,11 +634,19 @@ mod tests {
         impl SubagentEventHandler for ForwardToArc {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, a: String, b: u32) {
                 self.0.on_iteration(a, b);
             }
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
             fn on_stream_usage(&self, _subagent_id: String, _input_tokens: u32, _output_tokens: u32) {}
@@ -558,10 +671,18 @@ mod tests {
         impl SubagentEventHandler for ForwardToArc {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, a: String, b: String, c: u32, d: u32, e: bool) {
-                self.0.on_agent_completed(a, b, c, d, e);
+            fn on_agent_completed(
+                &self,
+                a: String,
+                b: String,
+                c: u32,
+                d: u32,
+                e: AgentStopReason,
+                f: bool,
+            ) {
+                self.0.on_agent_completed(a, b, c, d, e, f);
             }
             fn on_agent_error(&self, _: String, _: String) {}
             fn on_stream_delta(&self, _subagent_id: String, _text: String) {}
@@ -573,7 +694,14 @@ mod tests {
             engine_handler: engine_handler.clone(),
         };
 
-        proxy.on_agent_completed("id".into(), "content".into(), 3, 2, true);
+        proxy.on_agent_completed(
+            "id".into(),
+            "content".into(),
+            3,
+            2,
+            AgentStopReason::Completed,
+            true,
+        );
         assert_eq!(handler.completed_count.load(Ordering::SeqCst), 1);
     }
 
@@ -587,9 +715,17 @@ mod tests {
         impl SubagentEventHandler for ForwardToArc {
             fn on_agent_started(&self, _: String, _: String, _: String, _: String) {}
             fn on_tool_use(&self, _: String, _: String, _: String) {}
-            fn on_tool_result(&self, _: String, _: String, _: String, _: bool) {}
+            fn on_tool_result(&self, _: String, _: String, _: String, _: bool, _: String) {}
             fn on_iteration(&self, _: String, _: u32) {}
-            fn on_agent_completed(&self, _: String, _: String, _: u32, _: u32, _: bool) {}
+            fn on_agent_completed(
+                &self,
+                _: String,
+                _: String,
+                _: u32,
+                _: u32,
+                _: AgentStopReason,
+                _: bool,
+            ) {}
             fn on_agent_error(&self, a: String, b: String) {
                 self.0.on_agent_error(a, b);
             }
@@ -619,9 +755,22 @@ mod tests {
         // None of these should panic
         proxy.on_agent_started("id".into(), "type".into(), "task".into(), "desc".into());
         proxy.on_tool_use("id".into(), "tool".into(), "{}".into());
-        proxy.on_tool_result("id".into(), "tool".into(), "result".into(), false);
+        proxy.on_tool_result(
+            "id".into(),
+            "tool".into(),
+            "result".into(),
+            false,
+            "{}".into(),
+        );
         proxy.on_iteration("id".into(), 1);
-        proxy.on_agent_completed("id".into(), "content".into(), 0, 0, false);
+        proxy.on_agent_completed(
+            "id".into(),
+            "content".into(),
+            0,
+            0,
+            AgentStopReason::Completed,
+            false,
+        );
         proxy.on_agent_error("id".into(), "error".into());
     }
 
@@ -677,6 +826,7 @@ mod tests {
                 content: "result".to_string(),
                 is_error: false,
                 metadata: HashMap::new(),
+                images: Vec::new(),
             },
         };
         assert!(hook.accepts(&post_tool));
@@ -738,6 +888,40 @@ mod tests {
         assert!(callback.tool_use_called.load(Ordering::SeqCst));
     }
 
+    #[test]
+    fn test_callback_proxy_hook_ids_from_source() {
+        use crate::callback::SubagentCallback;
+        use mux::hook::{Hook, HookEvent};
+        use mux::llm::SeqIdSource;
+        use std::sync::Mutex;
+
+        struct IdCallback(Arc<Mutex<Vec<String>>>);
+        impl SubagentCallback for IdCallback {
+            fn on_text_delta(&self, _: String, _: String) {}
+            fn on_tool_use(&self, _: String, request: ToolUseRequest) {
+                self.0.lock().unwrap().push(request.id);
+            }
+            fn on_tool_result(&self, _: String, _: String, _: String) {}
+            fn on_complete(&self, _: SubagentResult) {}
+            fn on_error(&self, _: String, _: String) {}
+        }
+
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let callback: Arc<Box<dyn SubagentCallback>> = Arc::new(Box::new(IdCallback(ids.clone())));
+        let hook = CallbackProxyHook::new("agent-456".to_string(), callback)
+            .with_id_source(Arc::new(SeqIdSource::new("tool_")));
+
+        let event = HookEvent::PreToolUse {
+            tool_name: "read_file".to_string(),
+            input: serde_json::json!({"path": "/tmp/test"}),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(hook.on_event(&event)).unwrap();
+        rt.block_on(hook.on_event(&event)).unwrap();
+
+        assert_eq!(*ids.lock().unwrap(), ["tool_1", "tool_2"]);
+    }
+
     #[test]
     fn test_callback_proxy_hook_on_event_post_tool_use() {
         use crate::callback::SubagentCallback;
@@ -785,6 +969,7 @@ mod tests {
                 content: "file1.txt\nfile2.txt".to_string(),
                 is_error: false,
                 metadata: HashMap::new(),
+                images: Vec::new(),
             },
         };
 
@@ -795,6 +980,41 @@ mod tests {
         assert!(callback.tool_result_called.load(Ordering::SeqCst));
     }
 
+    #[test]
+    fn test_callback_proxy_hook_forwards_stream_delta() {
+        use crate::callback::SubagentCallback;
+        use mux::hook::{Hook, HookEvent};
+        use std::sync::Mutex;
+
+        struct TextCallback(Arc<Mutex<String>>);
+        impl SubagentCallback for TextCallback {
+            fn on_text_delta(&self, agent_id: String, text: String) {
+                assert_eq!(agent_id, "agent-def");
+                self.0.lock().unwrap().push_str(&text);
+            }
+            fn on_tool_use(&self, _: String, _: ToolUseRequest) {}
+            fn on_tool_result(&self, _: String, _: String, _: String) {}
+            fn on_complete(&self, _: SubagentResult) {}
+            fn on_error(&self, _: String, _: String) {}
+        }
+
+        let text = Arc::new(Mutex::new(String::new()));
+        let callback: Arc<Box<dyn SubagentCallback>> =
+            Arc::new(Box::new(TextCallback(text.clone())));
+        let hook = CallbackProxyHook::new("agent-def".to_string(), callback);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        for delta in ["Hello", ", world"] {
+            let event = HookEvent::StreamDelta {
+                agent_id: "agent-def".to_string(),
+                text: delta.to_string(),
+            };
+            rt.block_on(hook.on_event(&event)).unwrap();
+        }
+
+        assert_eq!(*text.lock().unwrap(), "Hello, world");
+    }
+
     #[test]
     fn test_callback_proxy_hook_on_event_other_events() {
         use crate::callback::SubagentCallback;
@@ -823,4 +1043,17 @@ mod tests {
 
         assert!(matches!(result, HookAction::Continue));
     }
+
+    #[test]
+    fn test_cancel_agent_triggers_registered_token() {
+        let dir = std::env::temp_dir().join("mux-test-subagent-cancel");
+        let engine = MuxEngine::new(dir.to_string_lossy().to_string()).unwrap();
+
+        assert!(!engine.cancel_agent("unknown".to_string()));
+
+        let token = engine.register_running_agent("agent-1");
+        assert!(!token.is_cancelled());
+        assert!(engine.cancel_agent("agent-1".to_string()));
+        assert!(token.is_cancelled());
+    }
 }
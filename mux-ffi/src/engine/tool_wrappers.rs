@@ -2,23 +2,13 @@
 // ABOUTME: Enables SubAgent to execute FFI-layer tools through its standard Registry.
 
 use async_trait::async_trait;
-use mux::mcp::McpContentBlock;
+use mux::mcp::McpErrorKind;
 use mux::prelude::McpClient;
 use mux::tool::{Tool, ToolResult};
 use std::sync::Arc;
 use tokio::sync::Mutex as TokioMutex;
 
-/// Convert MCP content blocks to a single string.
-fn mcp_content_to_string(content: &[McpContentBlock]) -> String {
-    content
-        .iter()
-        .filter_map(|block| match block {
-            McpContentBlock::Text { text } => Some(text.as_str()),
-            McpContentBlock::Image { .. } => Some("[image]"),
-        })
-        .collect::<Vec<_>>()
-        .join("\n")
-}
+use super::helpers;
 
 /// Wraps an MCP tool so it can be registered in a mux tool Registry.
 /// The tool name is prefixed with "server_name:" to match the LLM's tool calls.
@@ -46,7 +36,7 @@ impl McpToolWrapper {
         tool_schema: serde_json::Value,
         client: Arc<TokioMutex<McpClient>>,
     ) -> Self {
-        let qualified_name = format!("{}:{}", server_name, tool_name);
+        let qualified_name = helpers::qualified_tool_name(&server_name, &tool_name);
         Self {
             qualified_name,
             server_name,
@@ -80,15 +70,8 @@ impl Tool for McpToolWrapper {
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         let client = self.client.lock().await;
         match client.call_tool(&self.tool_name, params).await {
-            Ok(result) => {
-                let content = mcp_content_to_string(&result.content);
-                if result.is_error {
-                    Ok(ToolResult::error(content))
-                } else {
-                    Ok(ToolResult::text(content))
-                }
-            }
-            Err(e) => Ok(ToolResult::error(format!("MCP tool error: {}", e))),
+            Ok(result) => Ok(ToolResult::from(result)),
+            Err(e) => Ok(McpErrorKind::Protocol.tool_result(e.to_string())),
         }
     }
 }
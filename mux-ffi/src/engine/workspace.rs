@@ -3,7 +3,8 @@
 
 use super::MuxEngine;
 use crate::MuxFfiError;
-use crate::types::{Conversation, Workspace, WorkspaceSummary};
+use crate::types::{ChatRole, Conversation, ConversationMessage, Workspace, WorkspaceSummary};
+use mux::prelude::Role;
 
 /// Workspace and Conversation CRUD operations
 #[uniffi::export]
@@ -140,6 +141,34 @@ impl MuxEngine {
             .unwrap_or_default()
     }
 
+    /// Get the message history for a conversation, oldest first.
+    pub fn get_messages(&self, conversation_id: String) -> Vec<ConversationMessage> {
+        let history = self.message_history.read();
+        let Some(messages) = history.get(&conversation_id) else {
+            return Vec::new();
+        };
+
+        messages
+            .iter()
+            .map(|m| ConversationMessage {
+                role: match m.role {
+                    Role::User => ChatRole::User,
+                    Role::Assistant => ChatRole::Assistant,
+                },
+                text: m
+                    .content
+                    .iter()
+                    .filter_map(|block| match block {
+                        mux::prelude::ContentBlock::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(""),
+                created_at: m.created_at,
+            })
+            .collect()
+    }
+
     /// Set a custom system prompt for a workspace.
     /// Tool guidance is automatically appended to this prompt.
     /// Pass None to reset to the default prompt.
@@ -40,6 +40,37 @@ pub enum MuxFfiError {
     TranscriptInvalid { reason: String },
     #[error("Hook failed: {reason}")]
     HookFailed { reason: String },
+    #[error("MCP error: {message}")]
+    Mcp {
+        failure: McpFailure,
+        /// The JSON-RPC error code, when the server sent one.
+        code: Option<i32>,
+        message: String,
+    },
+}
+
+impl From<mux::error::McpError> for MuxFfiError {
+    fn from(error: mux::error::McpError) -> Self {
+        use mux::error::McpError;
+
+        let (failure, code) = match &error {
+            McpError::Connection(_) | McpError::Io(_) => (McpFailure::Connection, None),
+            McpError::UnsetVariable(_) => (McpFailure::Configuration, None),
+            McpError::Timeout(_) => (McpFailure::Timeout, None),
+            McpError::ToolNotFound { .. } => (McpFailure::ToolNotFound, None),
+            McpError::Rpc { code, .. } => (McpFailure::Protocol, Some(*code)),
+            McpError::Server { code, .. } => (McpFailure::Server, Some(*code)),
+            McpError::Protocol(_)
+            | McpError::Json(_)
+            | McpError::ResponseTooLarge { .. }
+            | McpError::OutputSchema { .. } => (McpFailure::Protocol, None),
+        };
+        MuxFfiError::Mcp {
+            failure,
+            code,
+            message: error.to_string(),
+        }
+    }
 }
 
 #[uniffi::export]
@@ -66,6 +97,44 @@ mod tests {
         assert!(!version().is_empty());
     }
 
+    #[test]
+    fn test_mcp_error_keeps_its_cause() {
+        use mux::error::McpError;
+
+        let err = MuxFfiError::from(McpError::Server {
+            code: -32002,
+            message: "Resource not found".into(),
+            data: None,
+        });
+        assert!(matches!(
+            err,
+            MuxFfiError::Mcp {
+                failure: McpFailure::Server,
+                code: Some(-32002),
+                ..
+            }
+        ));
+
+        let err = MuxFfiError::from(McpError::Timeout(std::time::Duration::from_secs(30)));
+        assert!(matches!(
+            err,
+            MuxFfiError::Mcp {
+                failure: McpFailure::Timeout,
+                code: None,
+                ..
+            }
+        ));
+
+        let err = MuxFfiError::from(McpError::UnsetVariable("GITHUB_TOKEN".into()));
+        assert!(matches!(
+            err,
+            MuxFfiError::Mcp {
+                failure: McpFailure::Configuration,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_workspace_creation() {
         let ws = Workspace::new("test-workspace".to_string(), None);
@@ -221,6 +290,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         };
         engine.set_model_context_config(config.clone());
 
@@ -264,6 +336,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.5,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         // Create conversation
@@ -333,6 +408,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.5,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
@@ -429,6 +507,9 @@ mod tests {
             compaction_mode: CompactionMode::Summarize, // This is ignored - auto-selects based on limit
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
@@ -478,6 +559,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest, // This is ignored - auto-selects based on limit
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
@@ -523,6 +607,9 @@ mod tests {
             compaction_mode: CompactionMode::Summarize,
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
@@ -544,6 +631,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         // Above threshold, should try summarization (needs API key)
@@ -575,6 +665,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
@@ -624,6 +717,9 @@ mod tests {
             compaction_mode: CompactionMode::TruncateOldest,
             warning_threshold: 0.8,
             compaction_model: None,
+            compaction_provider: None,
+            compaction_threshold: 0.9,
+            preserve_turns: 2,
         });
 
         let conv = engine
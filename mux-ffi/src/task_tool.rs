@@ -48,9 +48,17 @@ impl Hook for SubagentEventProxyHook {
                 let tool_name = tool_name.clone();
                 let result_content = result.content.clone();
                 let is_error = result.is_error;
+                let metadata_json =
+                    serde_json::to_string(&result.metadata).unwrap_or_else(|_| "{}".to_string());
 
                 tokio::task::spawn_blocking(move || {
-                    handler.on_tool_result(agent_id, tool_name, result_content, is_error);
+                    handler.on_tool_result(
+                        agent_id,
+                        tool_name,
+                        result_content,
+                        is_error,
+                        metadata_json,
+                    );
                 })
                 .await
                 .ok();
@@ -71,7 +79,12 @@ impl Hook for SubagentEventProxyHook {
             | HookEvent::Stop { .. }
             | HookEvent::SubagentStart { .. }
             | HookEvent::SubagentStop { .. }
-            | HookEvent::ResponseReceived { .. } => {
+            | HookEvent::PostResponse { .. }
+            | HookEvent::ResponseReceived { .. }
+            | HookEvent::ToolInputDelta { .. }
+            | HookEvent::LlmRequest { .. }
+            | HookEvent::LlmResponse { .. }
+            | HookEvent::ToolProgress { .. } => {
                 // These are handled at the FfiTaskTool level or not relevant
             }
             HookEvent::StreamDelta { text, .. } => {
@@ -119,6 +132,9 @@ pub struct FfiTaskTool {
 
     /// Event handler for streaming updates to Swift.
     event_handler: Arc<Box<dyn SubagentEventHandler>>,
+
+    /// Optional user hook registered on every spawned subagent.
+    hook: Option<Arc<dyn Hook>>,
 }
 
 impl FfiTaskTool {
@@ -138,6 +154,7 @@ impl FfiTaskTool {
             client_factory: Arc::new(client_factory),
             transcript_store: None,
             event_handler: Arc::new(event_handler),
+            hook: None,
         }
     }
 
@@ -146,6 +163,12 @@ impl FfiTaskTool {
         self.transcript_store = Some(store);
         self
     }
+
+    /// Register `hook` on spawned subagents, after the event proxy.
+    pub fn with_hook(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
 }
 
 #[async_trait]
@@ -315,6 +338,9 @@ impl Tool for FfiTaskTool {
                 self.event_handler.clone(),
             ))
             .await;
+        if let Some(hook) = &self.hook {
+            hook_registry.register_arc(hook.clone()).await;
+        }
 
         // Attach hooks and run
         let mut subagent = subagent.with_hooks(hook_registry);
@@ -341,6 +367,7 @@ impl Tool for FfiTaskTool {
                     let content = result.content.clone();
                     let tool_use_count = result.tool_use_count as u32;
                     let iterations = result.iterations as u32;
+                    let stop_reason = result.stop_reason.into();
 
                     tokio::task::spawn_blocking(move || {
                         handler.on_agent_completed(
@@ -348,6 +375,7 @@ impl Tool for FfiTaskTool {
                             content,
                             tool_use_count,
                             iterations,
+                            stop_reason,
                             transcript_saved,
                         );
                     })
@@ -360,6 +388,7 @@ impl Tool for FfiTaskTool {
                     "content": result.content,
                     "tool_use_count": result.tool_use_count,
                     "iterations": result.iterations,
+                    "stop_reason": result.stop_reason,
                     "tokens": {
                         "input": result.usage.input_tokens,
                         "output": result.usage.output_tokens
@@ -391,8 +420,11 @@ impl Tool for FfiTaskTool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bridge::FfiHookBridge;
+    use crate::callback::HookHandler;
+    use crate::types::{AgentStopReason, HookEventType, HookResponse};
     use mux::prelude::AgentDefinition;
-    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
     /// Mock event handler for testing
     struct MockEventHandler {
@@ -430,6 +462,7 @@ mod tests {
             _tool_name: String,
             _result: String,
             _is_error: bool,
+            _metadata_json: String,
         ) {
         }
 
@@ -441,6 +474,7 @@ mod tests {
             _content: String,
             _tool_use_count: u32,
             _iterations: u32,
+            _stop_reason: AgentStopReason,
             _transcript_saved: bool,
         ) {
             self.completed_count.fetch_add(1, Ordering::SeqCst);
@@ -511,4 +545,79 @@ mod tests {
         assert!(result.content.contains("not found"));
         assert!(result.content.contains("researcher"));
     }
+
+    /// Tool that records whether it ran.
+    struct FlagTool(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl Tool for FlagTool {
+        fn name(&self) -> &str {
+            "flag"
+        }
+
+        fn description(&self) -> &str {
+            "Sets a flag"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(ToolResult::text("set"))
+        }
+    }
+
+    /// User hook handler that blocks every tool call.
+    struct DenyTools;
+
+    impl HookHandler for DenyTools {
+        fn on_event(&self, event: HookEventType) -> HookResponse {
+            match event {
+                HookEventType::PreToolUse { .. } => HookResponse::Block {
+                    reason: "denied".to_string(),
+                },
+                _ => HookResponse::Continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ffi_task_tool_user_hook_blocks_subagent_tool() {
+        let agent_registry = AgentRegistry::new();
+        agent_registry
+            .register(AgentDefinition::new("worker", "You work").model("test-model"))
+            .await;
+        let ran = Arc::new(AtomicBool::new(false));
+        let tool_registry = Registry::new();
+        tool_registry.register(FlagTool(ran.clone())).await;
+        let handler = Box::new(MockEventHandler::new());
+
+        let tool = FfiTaskTool::new(
+            agent_registry,
+            tool_registry,
+            |_| {
+                Arc::new(
+                    mux::llm::MockClient::new()
+                        .with_tool_use("flag", serde_json::json!({}))
+                        .with_text("Could not set it"),
+                )
+            },
+            handler,
+        )
+        .with_hook(Arc::new(FfiHookBridge::new(Arc::new(DenyTools))));
+
+        let result = tool
+            .execute(serde_json::json!({
+                "agent_type": "worker",
+                "task": "Set the flag",
+                "description": "test"
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error, "{}", result.content);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
 }
@@ -96,6 +96,36 @@ impl Conversation {
     }
 }
 
+/// A message from a conversation's history.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ConversationMessage {
+    pub role: ChatRole,
+    /// Text content of the message; tool calls and results are omitted.
+    pub text: String,
+    /// Creation time in milliseconds since the Unix epoch.
+    /// None for messages saved before timestamps were recorded.
+    pub created_at: Option<u64>,
+}
+
+/// What went wrong talking to an MCP server, so callers can decide
+/// whether to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum McpFailure {
+    /// The server could not be reached or went away. Worth retrying.
+    Connection,
+    /// The server did not answer in time. Worth retrying.
+    Timeout,
+    /// A message was malformed or broke the protocol, on either side.
+    Protocol,
+    /// The server has no tool with the requested name.
+    ToolNotFound,
+    /// The server reported an error of its own.
+    Server,
+    /// The server config is unusable, e.g. it names an unset variable.
+    /// Not worth retrying until the config or environment changes.
+    Configuration,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, uniffi::Enum)]
 pub enum McpTransportType {
     Stdio,
@@ -255,6 +285,28 @@ impl AgentConfig {
     }
 }
 
+impl From<&mux::agent::AgentDefinition> for AgentConfig {
+    fn from(definition: &mux::agent::AgentDefinition) -> Self {
+        Self {
+            name: definition.agent_type.clone(),
+            system_prompt: definition.system_prompt.clone(),
+            model: definition.model.clone(),
+            allowed_tools: definition.allowed_tools.clone().unwrap_or_default(),
+            denied_tools: definition.denied_tools.clone(),
+            max_iterations: definition.max_iterations as u32,
+        }
+    }
+}
+
+/// Where a registered agent came from.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum AgentSource {
+    /// Registered with `register_agent`.
+    Programmatic,
+    /// Loaded from a markdown file by `load_agents_from_dir`.
+    File { path: String },
+}
+
 #[derive(Debug, Clone, uniffi::Enum)]
 pub enum HookEventType {
     PreToolUse {
@@ -277,6 +329,24 @@ pub enum HookEventType {
         agent_id: String,
         iteration: u32,
     },
+    /// About to call the model. Responding with Block ends the run;
+    /// Transform with a request JSON object replaces the request.
+    /// `request_json` is empty unless `set_hook_full_request` is on.
+    LlmRequest {
+        agent_id: String,
+        model: String,
+        message_count: u32,
+        tool_names: Vec<String>,
+        estimated_input_tokens: u32,
+        request_json: String,
+    },
+    LlmResponse {
+        agent_id: String,
+        model: String,
+        input_tokens: u32,
+        output_tokens: u32,
+        stop_reason: String,
+    },
 }
 
 #[derive(Debug, Clone, uniffi::Enum)]
@@ -292,13 +362,83 @@ pub struct TranscriptData {
     pub messages_json: String,
 }
 
+/// Why a subagent stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum AgentStopReason {
+    Completed,
+    MaxIterations,
+    Budget,
+    Timeout,
+    Cancelled,
+    Error,
+    Refused,
+}
+
+impl From<mux::agent::AgentStopReason> for AgentStopReason {
+    fn from(reason: mux::agent::AgentStopReason) -> Self {
+        match reason {
+            mux::agent::AgentStopReason::Completed => Self::Completed,
+            mux::agent::AgentStopReason::MaxIterations => Self::MaxIterations,
+            mux::agent::AgentStopReason::Budget => Self::Budget,
+            mux::agent::AgentStopReason::Timeout => Self::Timeout,
+            mux::agent::AgentStopReason::Cancelled => Self::Cancelled,
+            mux::agent::AgentStopReason::Error => Self::Error,
+            mux::agent::AgentStopReason::Refused => Self::Refused,
+        }
+    }
+}
+
+/// Token usage for one model.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ModelUsage {
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub cache_read_tokens: u32,
+    pub cache_write_tokens: u32,
+}
+
+/// Token usage broken down by model, with a cost estimate.
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct UsageSummary {
+    pub models: Vec<ModelUsage>,
+    /// Estimated cost in USD. `None` unless every model has a price set
+    /// via `set_model_price`.
+    pub estimated_cost_usd: Option<f64>,
+}
+
+impl UsageSummary {
+    pub(crate) fn from_tracker(
+        tracker: &mux::llm::UsageTracker,
+        prices: &mux::llm::PriceTable,
+    ) -> Self {
+        Self {
+            models: tracker
+                .by_model()
+                .iter()
+                .map(|(model, usage)| ModelUsage {
+                    model: model.clone(),
+                    input_tokens: usage.input_tokens,
+                    output_tokens: usage.output_tokens,
+                    cache_read_tokens: usage.cache_read_tokens,
+                    cache_write_tokens: usage.cache_write_tokens,
+                })
+                .collect(),
+            estimated_cost_usd: tracker.estimated_cost(prices),
+        }
+    }
+}
+
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct SubagentResult {
     pub agent_id: String,
     pub content: String,
     pub tool_use_count: u32,
     pub iterations: u32,
+    pub stop_reason: AgentStopReason,
     pub transcript_json: Option<String>,
+    /// Token usage for this run, per model.
+    pub usage: UsageSummary,
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -383,3 +523,70 @@ pub struct LlmResponse {
     /// Error message if generation failed
     pub error: Option<String>,
 }
+
+// ============================================================================
+// Diagnostics Types
+// ============================================================================
+
+/// A snapshot of engine state for support bundles, from
+/// `MuxEngine::diagnostics`. Holds no secrets: providers are listed by name
+/// and error messages have credentials masked.
+#[derive(Debug, Clone, Serialize, uniffi::Record)]
+pub struct DiagnosticsReport {
+    /// Version of the mux FFI library.
+    pub version: String,
+    /// Providers with an API key set, by name.
+    pub providers: Vec<String>,
+    /// Providers registered with `register_llm_provider`, by name.
+    pub callback_providers: Vec<String>,
+    pub default_provider: String,
+    pub workspaces: Vec<WorkspaceDiagnostics>,
+    pub conversation_count: u32,
+    pub message_count: u32,
+    pub running_agent_count: u32,
+    /// The most recent errors, oldest first.
+    pub recent_errors: Vec<ErrorLogEntry>,
+}
+
+/// Per-workspace counts and MCP server status.
+#[derive(Debug, Clone, Serialize, uniffi::Record)]
+pub struct WorkspaceDiagnostics {
+    pub id: String,
+    pub conversation_count: u32,
+    pub message_count: u32,
+    pub mcp_servers: Vec<McpServerDiagnostics>,
+}
+
+/// An MCP server's configuration summary and connection state. Commands,
+/// arguments and URLs are left out since they may carry credentials.
+#[derive(Debug, Clone, Serialize, uniffi::Record)]
+pub struct McpServerDiagnostics {
+    pub name: String,
+    pub transport_type: McpTransportType,
+    pub enabled: bool,
+    pub status: McpServerStatus,
+    /// Tools the server offered when last listed; 0 if not connected.
+    pub tool_count: u32,
+}
+
+/// Connection state of a workspace's MCP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, uniffi::Enum)]
+pub enum McpServerStatus {
+    /// Not connected: disabled, not yet connected, or failed to connect.
+    NotConnected,
+    Connected,
+    Reconnecting,
+    /// The connection dropped and reconnecting failed.
+    Disconnected,
+}
+
+/// An error the engine reported to a callback.
+#[derive(Debug, Clone, Serialize, uniffi::Record)]
+pub struct ErrorLogEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    /// What failed, such as `send_message` or `mcp:github`.
+    pub source: String,
+    /// The error message, with credentials masked.
+    pub message: String,
+}
@@ -0,0 +1,230 @@
+// ABOUTME: AgentTool - exposes one agent definition to the model as a plain tool.
+// ABOUTME: Each call runs a fresh subagent on the given input and returns its answer.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::definition::AgentDefinition;
+use super::runner::SubAgent;
+use crate::llm::LlmClient;
+use crate::tool::{Registry, Tool, ToolResult};
+
+/// A tool that runs a specific agent.
+///
+/// Unlike [`TaskTool`](super::TaskTool), which lets the model pick any
+/// registered agent type, an `AgentTool` shows the model a single
+/// well-named capability (e.g. `summarize_pr`) that takes one `input` string.
+/// Each call runs a new [`SubAgent`] with the definition and returns its
+/// final answer.
+pub struct AgentTool {
+    name: String,
+    description: String,
+    definition: AgentDefinition,
+    registry: Registry,
+    client: Arc<dyn LlmClient>,
+}
+
+impl AgentTool {
+    /// Create a tool named after the definition's `agent_type`.
+    ///
+    /// The subagent uses `client` and the tools in `registry` that its
+    /// definition allows.
+    pub fn from_definition(
+        definition: AgentDefinition,
+        registry: Registry,
+        client: Arc<dyn LlmClient>,
+    ) -> Self {
+        Self {
+            name: definition.agent_type.clone(),
+            description: format!(
+                "Run the '{}' agent on the given input and return its answer.",
+                definition.agent_type
+            ),
+            definition,
+            registry,
+            client,
+        }
+    }
+
+    /// Use a different tool name than the definition's `agent_type`.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the description the model sees for this tool.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for AgentTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "input": {
+                    "type": "string",
+                    "description": "The input for the agent to work on"
+                }
+            },
+            "required": ["input"]
+        })
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        #[derive(Deserialize)]
+        struct Params {
+            input: String,
+        }
+        let params: Params = serde_json::from_value(params)?;
+
+        let mut subagent = SubAgent::new(
+            self.definition.clone(),
+            self.client.clone(),
+            self.registry.clone(),
+        );
+        match subagent.run(&params.input).await {
+            Ok(result) => Ok(ToolResult::text(result.content)
+                .with_metadata("agent_id", result.agent_id)
+                .with_metadata("stop_reason", result.stop_reason)
+                .with_metadata("tool_use_count", result.tool_use_count)
+                .with_files_changed(result.files_changed)),
+            Err(e) => Ok(ToolResult::error(format!(
+                "Agent '{}' failed: {}",
+                self.name, e
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::error::LlmError;
+    use crate::llm::{ContentBlock, Request, Response, StopReason, StreamEvent, Usage};
+
+    /// Client that answers with a summary of the last user message.
+    #[derive(Default)]
+    struct SummaryClient {
+        requests: Mutex<Vec<Request>>,
+    }
+
+    #[async_trait]
+    impl LlmClient for SummaryClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            self.requests.lock().unwrap().push(req.clone());
+            let input = match req.messages.last().map(|m| &m.content[0]) {
+                Some(ContentBlock::Text { text }) => text.clone(),
+                _ => String::new(),
+            };
+            Ok(Response {
+                id: "msg_1".into(),
+                content: vec![ContentBlock::text(format!("Summary: {}", input))],
+                stop_reason: StopReason::EndTurn,
+                model: req.model.clone(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    fn summarizer() -> AgentDefinition {
+        AgentDefinition::new("summarize_pr", "You summarize pull requests.").model("test-model")
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_definition() {
+        let tool = AgentTool::from_definition(
+            summarizer(),
+            Registry::new(),
+            Arc::new(SummaryClient::default()),
+        )
+        .with_description("Summarize a pull request diff.");
+
+        let parent = Registry::new();
+        parent.register(tool).await;
+        let definitions = parent.to_definitions().await;
+        assert_eq!(definitions[0].name, "summarize_pr");
+        assert_eq!(definitions[0].description, "Summarize a pull request diff.");
+        assert_eq!(definitions[0].input_schema["required"][0], "input");
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_runs_subagent_through_parent_registry() {
+        let client = Arc::new(SummaryClient::default());
+        let parent = Registry::new();
+        parent
+            .register(AgentTool::from_definition(
+                summarizer(),
+                Registry::new(),
+                client.clone(),
+            ))
+            .await;
+
+        let results = parent
+            .execute_batch(&[ContentBlock::ToolUse {
+                id: "call_1".into(),
+                name: "summarize_pr".into(),
+                input: serde_json::json!({"input": "Fix the login bug"}),
+            }])
+            .await;
+        assert!(matches!(
+            &results[0],
+            ContentBlock::ToolResult { content, is_error: false, .. }
+                if content == "Summary: Fix the login bug"
+        ));
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].system.as_deref(),
+            Some("You summarize pull requests.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_tool_reports_failures() {
+        // Without a model the subagent cannot run
+        let definition = AgentDefinition::new("summarize_pr", "You summarize pull requests.");
+        let tool = AgentTool::from_definition(
+            definition,
+            Registry::new(),
+            Arc::new(SummaryClient::default()),
+        );
+
+        let result = tool
+            .execute(serde_json::json!({"input": "anything"}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(result.content.contains("summarize_pr"));
+    }
+}
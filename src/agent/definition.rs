@@ -6,6 +6,34 @@ use std::sync::Arc;
 
 use tokio::sync::RwLock;
 
+/// How repeated tool failures affect whether an agent keeps running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToolErrorPolicy {
+    /// Feed every error back to the model and keep going.
+    #[default]
+    Continue,
+    /// Stop the agent once the same tool has failed `after` times in a row.
+    Abort { after: usize },
+}
+
+/// How an agent handles the model repeating the same tool call.
+///
+/// Calls repeat when they come one after another with the same tool name
+/// and the same arguments; key order in the arguments doesn't matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatedCallPolicy {
+    /// Run every call.
+    #[default]
+    Allow,
+    /// Run the same call at most `after` times in a row. Further repeats
+    /// aren't run; the model is reminded of the result it already has.
+    /// An `after` of 0 counts as 1, so the first call always runs.
+    Nudge { after: usize },
+    /// Stop the agent when the same call comes more than `after` times in a row.
+    /// An `after` of 0 counts as 1.
+    Abort { after: usize },
+}
+
 /// Definition of an agent type that can be spawned.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -40,6 +68,14 @@ pub struct AgentDefinition {
     /// When true, the agent uses `create_message_stream()` and fires
     /// `StreamDelta` / `StreamUsage` hooks for real-time token delivery.
     pub streaming: bool,
+
+    /// What to do when the same tool keeps failing.
+    /// Defaults to feeding errors back to the model indefinitely.
+    pub on_repeated_tool_error: ToolErrorPolicy,
+
+    /// What to do when the model keeps making the same tool call.
+    /// Defaults to running every call.
+    pub on_repeated_tool_call: RepeatedCallPolicy,
 }
 
 impl AgentDefinition {
@@ -54,6 +90,8 @@ impl AgentDefinition {
             fork_context: false,
             max_iterations: 10,
             streaming: false,
+            on_repeated_tool_error: ToolErrorPolicy::Continue,
+            on_repeated_tool_call: RepeatedCallPolicy::Allow,
         }
     }
 
@@ -92,6 +130,18 @@ impl AgentDefinition {
         self.streaming = enabled;
         self
     }
+
+    /// Set the policy for repeated tool failures.
+    pub fn on_repeated_tool_error(mut self, policy: ToolErrorPolicy) -> Self {
+        self.on_repeated_tool_error = policy;
+        self
+    }
+
+    /// Set the policy for repeated identical tool calls.
+    pub fn on_repeated_tool_call(mut self, policy: RepeatedCallPolicy) -> Self {
+        self.on_repeated_tool_call = policy;
+        self
+    }
 }
 
 /// Registry of available agent definitions.
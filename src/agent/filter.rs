@@ -3,17 +3,24 @@
 
 use std::sync::Arc;
 
+use regex::Regex;
+
 use crate::llm::ToolDefinition;
-use crate::tool::{Registry, Tool};
+use crate::tool::{ProgressReporter, Registry, Tool};
 
 /// A filtered view of a Registry that restricts tool access.
 ///
 /// Uses the decorator pattern to wrap a Registry and filter tool access
 /// based on allowlist/denylist rules. Denylist takes precedence.
+///
+/// Each list can be extended with regexes matched against the full tool
+/// name, such as `^github:.*delete.*` for a prefixed MCP tool.
 pub struct FilteredRegistry {
     source: Registry,
     allowed_tools: Option<Vec<String>>,
     denied_tools: Vec<String>,
+    allow_patterns: Vec<Regex>,
+    deny_patterns: Vec<Regex>,
 }
 
 impl FilteredRegistry {
@@ -23,6 +30,8 @@ impl FilteredRegistry {
             source,
             allowed_tools: None,
             denied_tools: Vec::new(),
+            allow_patterns: Vec::new(),
+            deny_patterns: Vec::new(),
         }
     }
 
@@ -38,18 +47,42 @@ impl FilteredRegistry {
         self
     }
 
+    /// Also allow tools whose names match any of these patterns.
+    ///
+    /// Patterns restrict access just like an allowlist: once any are set,
+    /// a tool must match one of them or be in the allowlist.
+    pub fn with_allow_patterns(mut self, patterns: impl IntoIterator<Item = Regex>) -> Self {
+        self.allow_patterns.extend(patterns);
+        self
+    }
+
+    /// Also deny tools whose names match any of these patterns. Like the
+    /// denylist, these take precedence over everything that allows a tool.
+    pub fn with_deny_patterns(mut self, patterns: impl IntoIterator<Item = Regex>) -> Self {
+        self.deny_patterns.extend(patterns);
+        self
+    }
+
+    /// The unfiltered registry this view wraps.
+    pub fn source(&self) -> &Registry {
+        &self.source
+    }
+
     /// Check if a tool name passes the filter.
     pub fn is_allowed(&self, name: &str) -> bool {
         // Denylist always wins
-        if self.denied_tools.iter().any(|d| d == name) {
+        if self.denied_tools.iter().any(|d| d == name)
+            || self.deny_patterns.iter().any(|p| p.is_match(name))
+        {
             return false;
         }
 
-        // If no allowlist, everything (not denied) is allowed
-        match &self.allowed_tools {
-            None => true,
-            Some(allowed) => allowed.iter().any(|a| a == name),
+        // If no allowlist or allow patterns, everything (not denied) is allowed
+        if self.allowed_tools.is_none() && self.allow_patterns.is_empty() {
+            return true;
         }
+        self.allowed_tools.iter().flatten().any(|a| a == name)
+            || self.allow_patterns.iter().any(|p| p.is_match(name))
     }
 
     /// Get a tool by name if it passes the filter.
@@ -60,6 +93,28 @@ impl FilteredRegistry {
         self.source.get(name).await
     }
 
+    /// Execute a tool, enforcing the source registry's timeouts.
+    pub async fn execute_tool(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+    ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+        self.source.execute_tool(tool, params).await
+    }
+
+    /// Execute a tool that reports to `progress`, enforcing the source
+    /// registry's timeouts.
+    pub async fn execute_tool_with_progress(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+        progress: &ProgressReporter,
+    ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+        self.source
+            .execute_tool_with_progress(tool, params, progress)
+            .await
+    }
+
     /// List all tool names that pass the filter.
     pub async fn list(&self) -> Vec<String> {
         self.source
@@ -104,6 +159,8 @@ impl Clone for FilteredRegistry {
             source: self.source.clone(),
             allowed_tools: self.allowed_tools.clone(),
             denied_tools: self.denied_tools.clone(),
+            allow_patterns: self.allow_patterns.clone(),
+            deny_patterns: self.deny_patterns.clone(),
         }
     }
 }
@@ -253,4 +310,64 @@ mod tests {
         assert_eq!(defs.len(), 1);
         assert_eq!(defs[0].name, "read");
     }
+
+    fn pattern(re: &str) -> Regex {
+        Regex::new(re).unwrap()
+    }
+
+    async fn github_registry() -> Registry {
+        let registry = Registry::new();
+        for name in [
+            "github:get_issue",
+            "github:delete_repo",
+            "github:delete_branch",
+            "slack:post_message",
+            "read",
+        ] {
+            registry.register(MockTool { name: name.into() }).await;
+        }
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_filtered_deny_patterns() {
+        let filtered = FilteredRegistry::new(github_registry().await)
+            .with_deny_patterns([pattern("^github:.*delete.*")]);
+
+        let mut names = filtered.list().await;
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["github:get_issue", "read", "slack:post_message"]
+        );
+        assert!(filtered.get("github:delete_repo").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_patterns_combine_with_lists() {
+        // Allow patterns and the allowlist both grant access; deny patterns
+        // override them, and so does the denylist
+        let filtered = FilteredRegistry::new(github_registry().await)
+            .allowed(Some(vec!["read".into(), "github:delete_branch".into()]))
+            .with_allow_patterns([pattern("^github:")])
+            .with_deny_patterns([pattern("delete_repo$")])
+            .denied(vec!["github:get_issue".into()]);
+
+        assert!(filtered.is_allowed("read"));
+        assert!(filtered.is_allowed("github:delete_branch"));
+        assert!(!filtered.is_allowed("github:delete_repo"));
+        assert!(!filtered.is_allowed("github:get_issue"));
+        assert!(!filtered.is_allowed("slack:post_message"));
+
+        // A deny pattern wins over an exact allowlist entry
+        let filtered = FilteredRegistry::new(github_registry().await)
+            .allowed(Some(vec!["github:delete_branch".into()]))
+            .with_deny_patterns([pattern("^github:.*delete.*")]);
+        assert_eq!(filtered.count().await, 0);
+
+        // Allow patterns alone restrict the view like an allowlist
+        let filtered = FilteredRegistry::new(github_registry().await)
+            .with_allow_patterns([pattern("^slack:")]);
+        assert_eq!(filtered.list().await, vec!["slack:post_message"]);
+    }
 }
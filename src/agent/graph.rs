@@ -0,0 +1,488 @@
+// ABOUTME: AgentGraph and Orchestrator - run named subagents in dependency order.
+// ABOUTME: Independent agents run concurrently; downstream tasks include upstream results.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use uuid::Uuid;
+
+use super::definition::AgentDefinition;
+use super::runner::{SubAgent, SubAgentResult};
+use crate::coordinator::Coordinator;
+use crate::error::{GraphError, LlmError};
+use crate::hook::{HookEvent, HookRegistry};
+use crate::llm::LlmClient;
+use crate::tool::Registry;
+
+/// One agent in a graph.
+#[derive(Debug, Clone)]
+struct GraphNode {
+    name: String,
+    definition: AgentDefinition,
+    task: String,
+    /// Indices of the nodes whose results this node receives, in edge order.
+    upstream: Vec<usize>,
+    /// Indices of the nodes that receive this node's result.
+    downstream: Vec<usize>,
+}
+
+/// A validated set of named agents and the dependencies between them.
+///
+/// Built with [`AgentGraph::builder`] and run with an [`Orchestrator`].
+/// An edge `from -> to` means `to` starts after `from` completes, and its
+/// task includes `from`'s final response.
+#[derive(Debug, Clone)]
+pub struct AgentGraph {
+    nodes: Vec<GraphNode>,
+}
+
+impl AgentGraph {
+    /// Start building a graph.
+    pub fn builder() -> AgentGraphBuilder {
+        AgentGraphBuilder::default()
+    }
+
+    /// The agent names, in the order they were added.
+    pub fn names(&self) -> Vec<&str> {
+        self.nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    /// The agents whose results `name` receives, or `None` if there is no
+    /// such agent.
+    pub fn dependencies(&self, name: &str) -> Option<Vec<&str>> {
+        let node = self.nodes.iter().find(|node| node.name == name)?;
+        Some(
+            node.upstream
+                .iter()
+                .map(|&i| self.nodes[i].name.as_str())
+                .collect(),
+        )
+    }
+}
+
+/// Builder for [`AgentGraph`].
+#[derive(Debug, Default)]
+pub struct AgentGraphBuilder {
+    agents: Vec<(String, AgentDefinition, String)>,
+    edges: Vec<(String, String)>,
+}
+
+impl AgentGraphBuilder {
+    /// Add an agent that runs `definition` on `task`.
+    pub fn agent(
+        mut self,
+        name: impl Into<String>,
+        definition: AgentDefinition,
+        task: impl Into<String>,
+    ) -> Self {
+        self.agents.push((name.into(), definition, task.into()));
+        self
+    }
+
+    /// Make `to` wait for `from` and receive its result.
+    pub fn edge(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.edges.push((from.into(), to.into()));
+        self
+    }
+
+    /// Validate the graph.
+    ///
+    /// Fails if a name is used twice, an edge names an unknown agent, or the
+    /// edges form a cycle.
+    pub fn build(self) -> Result<AgentGraph, GraphError> {
+        let mut index = HashMap::new();
+        let mut nodes = Vec::with_capacity(self.agents.len());
+        for (name, definition, task) in self.agents {
+            if index.insert(name.clone(), nodes.len()).is_some() {
+                return Err(GraphError::DuplicateAgent(name));
+            }
+            nodes.push(GraphNode {
+                name,
+                definition,
+                task,
+                upstream: Vec::new(),
+                downstream: Vec::new(),
+            });
+        }
+
+        for (from, to) in &self.edges {
+            let lookup = |name: &String| {
+                index
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| GraphError::UnknownAgent(name.clone()))
+            };
+            let (from, to) = (lookup(from)?, lookup(to)?);
+            if !nodes[to].upstream.contains(&from) {
+                nodes[to].upstream.push(from);
+                nodes[from].downstream.push(to);
+            }
+        }
+
+        if let Some(cycle) = find_cycle(&nodes) {
+            return Err(GraphError::Cycle(
+                cycle.into_iter().map(|i| nodes[i].name.clone()).collect(),
+            ));
+        }
+        Ok(AgentGraph { nodes })
+    }
+}
+
+/// A cycle as node indices, starting and ending at the same node.
+fn find_cycle(nodes: &[GraphNode]) -> Option<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        OnPath,
+        Done,
+    }
+
+    fn visit(i: usize, nodes: &[GraphNode], marks: &mut [Mark], path: &mut Vec<usize>) -> bool {
+        marks[i] = Mark::OnPath;
+        path.push(i);
+        for &next in &nodes[i].downstream {
+            match marks[next] {
+                Mark::OnPath => {
+                    let start = path.iter().position(|&n| n == next).unwrap_or(0);
+                    path.drain(..start);
+                    path.push(next);
+                    return true;
+                }
+                Mark::Unvisited => {
+                    if visit(next, nodes, marks, path) {
+                        return true;
+                    }
+                }
+                Mark::Done => {}
+            }
+        }
+        path.pop();
+        marks[i] = Mark::Done;
+        false
+    }
+
+    let mut marks = vec![Mark::Unvisited; nodes.len()];
+    let mut path = Vec::new();
+    (0..nodes.len()).find_map(|i| {
+        (marks[i] == Mark::Unvisited && visit(i, nodes, &mut marks, &mut path))
+            .then(|| path.clone())
+    })
+}
+
+/// Runs an [`AgentGraph`], starting each agent once everything it depends
+/// on has finished.
+///
+/// Agents whose dependencies are done run concurrently. An agent's result
+/// flows downstream whether or not it completed normally; check each
+/// result's `stop_reason`. If any agent returns an error, the agents still
+/// running are cancelled and the error is returned.
+pub struct Orchestrator {
+    id: String,
+    client: Arc<dyn LlmClient>,
+    registry: Registry,
+    hooks: Option<Arc<HookRegistry>>,
+    coordinator: Option<Arc<Coordinator>>,
+}
+
+impl Orchestrator {
+    /// Create an orchestrator whose agents share `client` and `registry`.
+    pub fn new(client: Arc<dyn LlmClient>, registry: Registry) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            client,
+            registry,
+            hooks: None,
+            coordinator: None,
+        }
+    }
+
+    /// Fire `SubagentStart`/`SubagentStop` for each agent, with this
+    /// orchestrator's ID as the parent, and pass the hooks to every agent.
+    pub fn with_hooks(mut self, hooks: Arc<HookRegistry>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Hold one of the coordinator's LLM slots while each agent runs, so at
+    /// most its `max_concurrent_llm` agents run at once. The agents also
+    /// share its tool slots (see [`SubAgent::with_coordinator`]).
+    pub fn with_coordinator(mut self, coordinator: Arc<Coordinator>) -> Self {
+        self.coordinator = Some(coordinator);
+        self
+    }
+
+    /// The parent ID reported in subagent hook events.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Run every agent in `graph` and return their results by name.
+    pub async fn run(
+        &self,
+        graph: &AgentGraph,
+    ) -> Result<HashMap<String, SubAgentResult>, GraphError> {
+        let nodes = &graph.nodes;
+        let mut waiting_on: Vec<usize> = nodes.iter().map(|node| node.upstream.len()).collect();
+        let mut results: HashMap<String, SubAgentResult> = HashMap::new();
+        let mut running = FuturesUnordered::new();
+
+        for (i, node) in nodes.iter().enumerate() {
+            if node.upstream.is_empty() {
+                running.push(self.run_agent(i, node, node.task.clone()));
+            }
+        }
+
+        while let Some((i, outcome)) = running.next().await {
+            results.insert(nodes[i].name.clone(), outcome?);
+
+            for &next in &nodes[i].downstream {
+                waiting_on[next] -= 1;
+                if waiting_on[next] == 0 {
+                    let node = &nodes[next];
+                    let task = task_with_upstream(node, nodes, &results);
+                    running.push(self.run_agent(next, node, task));
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn run_agent(
+        &self,
+        index: usize,
+        node: &GraphNode,
+        task: String,
+    ) -> (usize, Result<SubAgentResult, GraphError>) {
+        let _slot = match &self.coordinator {
+            Some(coordinator) => Some(coordinator.acquire_llm_slot().await),
+            None => None,
+        };
+
+        let mut agent = SubAgent::new(
+            node.definition.clone(),
+            self.client.clone(),
+            self.registry.clone(),
+        );
+        if let Some(hooks) = &self.hooks {
+            agent = agent.with_hooks(hooks.clone());
+        }
+        if let Some(coordinator) = &self.coordinator {
+            agent = agent.with_coordinator(coordinator.clone());
+        }
+        let child_id = agent.agent_id().to_string();
+
+        let start = HookEvent::SubagentStart {
+            parent_id: self.id.clone(),
+            child_id: child_id.clone(),
+            name: node.name.clone(),
+        };
+        let outcome = match self.fire(&start).await {
+            Ok(()) => agent.run(&task).await,
+            Err(e) => Err(e),
+        };
+
+        let stop = HookEvent::SubagentStop {
+            parent_id: self.id.clone(),
+            child_id,
+            name: node.name.clone(),
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+        };
+        let outcome = match (outcome, self.fire(&stop).await) {
+            (Ok(result), Ok(())) => Ok(result),
+            (Err(e), _) | (Ok(_), Err(e)) => Err(GraphError::AgentFailed {
+                agent: node.name.clone(),
+                source: e,
+            }),
+        };
+        (index, outcome)
+    }
+
+    async fn fire(&self, event: &HookEvent) -> Result<(), LlmError> {
+        if let Some(hooks) = &self.hooks {
+            hooks.fire(event).await.map_err(|e| LlmError::Api {
+                status: 0,
+                message: format!("Hook error: {}", e),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// The node's task followed by the final responses of its dependencies.
+fn task_with_upstream(
+    node: &GraphNode,
+    nodes: &[GraphNode],
+    results: &HashMap<String, SubAgentResult>,
+) -> String {
+    let mut task = format!(
+        "{}\n\nResults from the agents this task depends on:",
+        node.task
+    );
+    for &i in &node.upstream {
+        let name = &nodes[i].name;
+        let content = results
+            .get(name)
+            .map_or("", |result| result.content.as_str());
+        task.push_str(&format!(
+            "\n\n<result agent=\"{}\">\n{}\n</result>",
+            name, content
+        ));
+    }
+    task
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ContentBlock, Request, Response, StopReason, StreamEvent, Usage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn def(name: &str) -> AgentDefinition {
+        AgentDefinition::new(name, "You work.").model("test-model")
+    }
+
+    /// Client that answers with the task it was given, after a short delay,
+    /// tracking how many calls are in flight at once.
+    #[derive(Default)]
+    struct EchoClient {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for EchoClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let task = match req.messages.last().map(|m| &m.content[0]) {
+                Some(ContentBlock::Text { text }) => text.clone(),
+                _ => String::new(),
+            };
+            Ok(Response {
+                id: "msg".into(),
+                content: vec![ContentBlock::text(format!("did: {}", task))],
+                stop_reason: StopReason::EndTurn,
+                model: req.model.clone(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    fn diamond() -> AgentGraph {
+        AgentGraph::builder()
+            .agent("research", def("researcher"), "Find facts")
+            .agent("outline", def("planner"), "Outline the post")
+            .agent("draft", def("writer"), "Draft the post")
+            .agent("publish", def("writer"), "Polish and publish")
+            .edge("research", "outline")
+            .edge("research", "draft")
+            .edge("outline", "publish")
+            .edge("draft", "publish")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_graphs() {
+        let err = AgentGraph::builder()
+            .agent("a", def("a"), "A")
+            .agent("b", def("b"), "B")
+            .agent("c", def("c"), "C")
+            .edge("a", "b")
+            .edge("b", "c")
+            .edge("c", "b")
+            .build()
+            .unwrap_err();
+        assert!(matches!(&err, GraphError::Cycle(path) if path == &["b", "c", "b"]));
+
+        let err = AgentGraph::builder()
+            .agent("a", def("a"), "A")
+            .edge("a", "a")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, GraphError::Cycle(_)));
+
+        let err = AgentGraph::builder()
+            .agent("a", def("a"), "A")
+            .edge("a", "missing")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, GraphError::UnknownAgent(name) if name == "missing"));
+
+        let err = AgentGraph::builder()
+            .agent("a", def("a"), "A")
+            .agent("a", def("a"), "A again")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, GraphError::DuplicateAgent(name) if name == "a"));
+    }
+
+    #[tokio::test]
+    async fn test_run_passes_results_downstream() {
+        let graph = diamond();
+        assert_eq!(
+            graph.dependencies("publish"),
+            Some(vec!["outline", "draft"])
+        );
+
+        let client = Arc::new(EchoClient::default());
+        let hooks = Arc::new(HookRegistry::new());
+        let started = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = started.clone();
+        hooks
+            .on_subagent_start(move |_, _, name| {
+                log.lock().unwrap().push(name.to_string());
+                crate::hook::HookAction::Continue
+            })
+            .await;
+
+        let orchestrator = Orchestrator::new(client.clone(), Registry::new()).with_hooks(hooks);
+        let results = orchestrator.run(&graph).await.unwrap();
+
+        assert_eq!(results.len(), 4);
+        let publish = &results["publish"].content;
+        assert!(publish.starts_with("did: Polish and publish"));
+        assert!(publish.contains("<result agent=\"outline\">\ndid: Outline the post"));
+        assert!(publish.contains("<result agent=\"draft\">\ndid: Draft the post"));
+        assert!(publish.contains("did: Find facts"));
+
+        // outline and draft ran side by side
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 2);
+        let started = started.lock().unwrap();
+        assert_eq!(started.first().map(String::as_str), Some("research"));
+        assert_eq!(started.last().map(String::as_str), Some("publish"));
+    }
+
+    #[tokio::test]
+    async fn test_run_respects_coordinator_limit() {
+        let client = Arc::new(EchoClient::default());
+        let coordinator = Arc::new(Coordinator::new().with_max_concurrent_llm(1));
+        let orchestrator =
+            Orchestrator::new(client.clone(), Registry::new()).with_coordinator(coordinator);
+
+        let results = orchestrator.run(&diamond()).await.unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+}
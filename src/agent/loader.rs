@@ -0,0 +1,296 @@
+// ABOUTME: Loads agent definitions from markdown files with a frontmatter header.
+// ABOUTME: AgentRegistry::load_from_dir registers every `*.md` file in a directory.
+
+use std::path::{Path, PathBuf};
+
+use super::definition::{AgentDefinition, AgentRegistry};
+use crate::error::AgentLoadError;
+
+// An agent file is markdown whose body is the system prompt. An optional
+// frontmatter block sets the other fields:
+//
+//     ---
+//     name: reviewer
+//     model: claude-sonnet-4-20250514
+//     tools: read_file, search
+//     denied_tools: [bash]
+//     max_iterations: 5
+//     ---
+//     You review code for bugs.
+//
+// `name` defaults to the file name without `.md`. Lists are written inline
+// (comma-separated, optionally in brackets) or as `- item` lines under the
+// key. Unknown keys, such as `description`, are ignored so files written for
+// other tools still load.
+
+impl AgentDefinition {
+    /// Load an agent from a markdown file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AgentLoadError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| AgentLoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let default_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        parse(&text, &default_name).map_err(|message| AgentLoadError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+}
+
+impl AgentRegistry {
+    /// Register every agent defined in `dir`; see [`load_agent_dir`].
+    ///
+    /// Nothing is registered if any file fails to load. Returns the agent
+    /// types registered, in file name order.
+    pub async fn load_from_dir(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Vec<String>, AgentLoadError> {
+        let mut loaded = Vec::new();
+        for (_, definition) in load_agent_dir(dir)? {
+            loaded.push(definition.agent_type.clone());
+            self.register(definition).await;
+        }
+        Ok(loaded)
+    }
+}
+
+/// Load every `*.md` file in `dir` as an agent definition, sorted by path.
+///
+/// Subdirectories and other files are skipped.
+pub fn load_agent_dir(
+    dir: impl AsRef<Path>,
+) -> Result<Vec<(PathBuf, AgentDefinition)>, AgentLoadError> {
+    let dir = dir.as_ref();
+    let io_error = |source| AgentLoadError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let definition = AgentDefinition::from_file(&path)?;
+            Ok((path, definition))
+        })
+        .collect()
+}
+
+fn parse(text: &str, default_name: &str) -> Result<AgentDefinition, String> {
+    let (fields, body) = split_frontmatter(text)?;
+
+    let system_prompt = body.trim();
+    if system_prompt.is_empty() {
+        return Err("system prompt is empty".into());
+    }
+
+    let mut definition = AgentDefinition::new(default_name, system_prompt);
+    for (key, value) in fields {
+        match key.as_str() {
+            "name" => definition.agent_type = value,
+            "model" => definition.model = Some(value),
+            "tools" | "allowed_tools" => definition.allowed_tools = Some(parse_list(&value)),
+            "denied_tools" | "disallowed_tools" => definition.denied_tools = parse_list(&value),
+            "max_iterations" => {
+                definition.max_iterations = match value.parse() {
+                    Ok(max) if max > 0 => max,
+                    _ => {
+                        return Err(format!(
+                            "max_iterations must be a positive integer, got '{}'",
+                            value
+                        ));
+                    }
+                }
+            }
+            "streaming" => definition.streaming = parse_bool(&key, &value)?,
+            "fork_context" => definition.fork_context = parse_bool(&key, &value)?,
+            _ => {}
+        }
+    }
+
+    if definition.agent_type.is_empty() {
+        return Err("agent name is empty".into());
+    }
+    Ok(definition)
+}
+
+/// `key: value` pairs from a frontmatter block, in file order.
+type Fields = Vec<(String, String)>;
+
+/// Split off the frontmatter, returning its fields and the body.
+fn split_frontmatter(text: &str) -> Result<(Fields, &str), String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return Ok((Vec::new(), text));
+    };
+
+    let mut fields = Fields::new();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" {
+            return Ok((fields, &rest[offset..]));
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // `- item` lines continue the list of the key above them
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            match fields.last_mut() {
+                Some((_, value)) => {
+                    if !value.is_empty() {
+                        value.push(',');
+                    }
+                    value.push_str(item.trim());
+                }
+                None => return Err(format!("list item without a key: '{}'", trimmed)),
+            }
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            return Err(format!(
+                "expected 'key: value' in frontmatter, got '{}'",
+                trimmed
+            ));
+        };
+        fields.push((key.trim().to_string(), unquote(value.trim()).to_string()));
+    }
+
+    Err("frontmatter is missing its closing '---'".into())
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value);
+    value
+        .split(',')
+        .map(|item| unquote(item.trim()))
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", key, value)),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let text = "---\n\
+                    name: reviewer\n\
+                    description: Reviews code\n\
+                    model: \"claude-sonnet-4-20250514\"\n\
+                    tools: [read_file, 'search']\n\
+                    denied_tools:\n  - bash\n  - write_file\n\
+                    max_iterations: 5\n\
+                    streaming: true\n\
+                    ---\n\
+                    \n\
+                    You review code for bugs.\n";
+
+        let def = parse(text, "ignored").unwrap();
+        assert_eq!(def.agent_type, "reviewer");
+        assert_eq!(def.model.as_deref(), Some("claude-sonnet-4-20250514"));
+        assert_eq!(
+            def.allowed_tools,
+            Some(vec!["read_file".to_string(), "search".to_string()])
+        );
+        assert_eq!(def.denied_tools, vec!["bash", "write_file"]);
+        assert_eq!(def.max_iterations, 5);
+        assert!(def.streaming);
+        assert_eq!(def.system_prompt, "You review code for bugs.");
+    }
+
+    #[test]
+    fn test_parse_without_frontmatter_uses_default_name() {
+        let def = parse("You write docs.\n", "writer").unwrap();
+        assert_eq!(def.agent_type, "writer");
+        assert_eq!(def.system_prompt, "You write docs.");
+        assert_eq!(def.allowed_tools, None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(
+            parse("---\nname: x\n", "x")
+                .unwrap_err()
+                .contains("closing")
+        );
+        assert!(
+            parse("---\nmax_iterations: many\n---\nHi", "x")
+                .unwrap_err()
+                .contains("max_iterations")
+        );
+        assert!(
+            parse("---\nname: x\n---\n  \n", "x")
+                .unwrap_err()
+                .contains("empty")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("writer.md"), "You write docs.").unwrap();
+        std::fs::write(
+            dir.path().join("b.md"),
+            "---\nname: coder\nmodel: gpt-4o\n---\nYou write code.",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not an agent").unwrap();
+
+        let registry = AgentRegistry::new();
+        let loaded = registry.load_from_dir(dir.path()).await.unwrap();
+        assert_eq!(loaded, vec!["coder", "writer"]);
+        assert_eq!(
+            registry.get("coder").await.unwrap().model.as_deref(),
+            Some("gpt-4o")
+        );
+
+        // One bad file keeps the whole directory from loading
+        std::fs::write(dir.path().join("bad.md"), "---\nstreaming: yes\n---\nHi").unwrap();
+        let err = AgentRegistry::new()
+            .load_from_dir(dir.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentLoadError::Invalid { path, .. } if path.ends_with("bad.md")));
+    }
+}
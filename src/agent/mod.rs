@@ -1,20 +1,33 @@
 // ABOUTME: Subagent orchestration module - spawn and manage child agents.
-// ABOUTME: Provides TaskTool, AgentDefinition, FilteredRegistry, SubAgent runner, and transcript storage.
+// ABOUTME: Provides TaskTool, AgentTool, AgentDefinition, file loading, FilteredRegistry, SubAgent runner, agent graphs, review, system prompts, and transcript storage.
 
+mod agent_tool;
 mod async_handle;
 mod definition;
 mod filter;
+mod graph;
+mod loader;
 mod presets;
+mod prompt;
+mod review;
 mod runner;
 mod task;
 mod transcript;
 
+pub use agent_tool::AgentTool;
 pub use async_handle::{RunHandle, RunStatus};
-pub use definition::{AgentDefinition, AgentRegistry};
+pub use definition::{AgentDefinition, AgentRegistry, RepeatedCallPolicy, ToolErrorPolicy};
 pub use filter::FilteredRegistry;
+pub use graph::{AgentGraph, AgentGraphBuilder, Orchestrator};
+pub use loader::load_agent_dir;
 pub use presets::{
     EXPLORER, PLANNER, Preset, RESEARCHER, REVIEWER, WRITER, all_presets, get_preset,
 };
-pub use runner::{SubAgent, SubAgentResult};
+pub use prompt::{DEFAULT_BASE_PROMPT, SystemPromptBuilder};
+pub use review::{APPROVED_MARKER, Review, ReviewVerdict, ReviewedResult};
+pub use runner::{AgentStopReason, SubAgent, SubAgentResult, ToolUse};
 pub use task::TaskTool;
-pub use transcript::{MemoryTranscriptStore, TranscriptStore};
+pub use tokio_util::sync::CancellationToken;
+pub use transcript::{
+    FileTranscriptStore, MemoryTranscriptStore, TRANSCRIPT_VERSION, Transcript, TranscriptStore,
+};
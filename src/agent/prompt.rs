@@ -0,0 +1,188 @@
+// ABOUTME: SystemPromptBuilder - composes an agent's system prompt from sections.
+// ABOUTME: Base instructions, tool list, workspace guidance, date, and appended text.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::llm::ToolDefinition;
+
+/// Instructions used when none are given.
+pub const DEFAULT_BASE_PROMPT: &str = "You are a helpful AI assistant with access to local tools.";
+
+/// Builds a system prompt from optional sections, in a fixed order:
+/// base instructions, available tools, workspace, date, then appended text.
+///
+/// ```
+/// use mux::agent::SystemPromptBuilder;
+///
+/// let prompt = SystemPromptBuilder::default()
+///     .workspace("/home/me/project")
+///     .append("Be concise.")
+///     .build();
+/// assert!(prompt.contains("The workspace directory is: /home/me/project"));
+/// ```
+///
+/// Sections are separated by a blank line. Sections that are unset, or
+/// empty like a tool list with no tools, are left out.
+#[derive(Debug, Clone)]
+pub struct SystemPromptBuilder {
+    base: String,
+    tools: Vec<(String, String)>,
+    workspace: Option<String>,
+    date: Option<String>,
+    appended: Vec<String>,
+}
+
+impl Default for SystemPromptBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_PROMPT)
+    }
+}
+
+impl SystemPromptBuilder {
+    /// Start from `base` instructions.
+    pub fn new(base: impl Into<String>) -> Self {
+        Self {
+            base: base.into(),
+            tools: Vec::new(),
+            workspace: None,
+            date: None,
+            appended: Vec::new(),
+        }
+    }
+
+    /// List `tools` by name and description.
+    pub fn tools(mut self, tools: &[ToolDefinition]) -> Self {
+        self.tools.extend(
+            tools
+                .iter()
+                .map(|t| (t.name.clone(), t.description.clone())),
+        );
+        self
+    }
+
+    /// Name the workspace directory and ask for absolute file paths in it.
+    pub fn workspace(mut self, path: impl Into<String>) -> Self {
+        self.workspace = Some(path.into());
+        self
+    }
+
+    /// State the current date and time as `date`.
+    pub fn date(mut self, date: impl Into<String>) -> Self {
+        self.date = Some(date.into());
+        self
+    }
+
+    /// State the current UTC date and time, to the minute.
+    pub fn current_date(self) -> Self {
+        self.date(format_utc(SystemTime::now()))
+    }
+
+    /// Add text after every other section. Repeated calls add in order.
+    pub fn append(mut self, text: impl Into<String>) -> Self {
+        self.appended.push(text.into());
+        self
+    }
+
+    /// Render the prompt.
+    pub fn build(&self) -> String {
+        let mut sections = vec![self.base.clone()];
+        if !self.tools.is_empty() {
+            let list: Vec<String> = self
+                .tools
+                .iter()
+                .map(|(name, description)| format!("- {}: {}", name, description))
+                .collect();
+            sections.push(format!("Available tools:\n{}", list.join("\n")));
+        }
+        if let Some(path) = &self.workspace {
+            sections.push(format!(
+                "IMPORTANT: When using file tools, always use ABSOLUTE paths (starting with / or ~).\n\
+                The workspace directory is: {}\n\
+                For example, use '{}/file.txt' instead of just 'file.txt'.",
+                path, path
+            ));
+        }
+        if let Some(date) = &self.date {
+            sections.push(format!("Current date and time: {}", date));
+        }
+        sections.extend(self.appended.iter().cloned());
+
+        sections.retain(|section| !section.is_empty());
+        sections.join("\n\n")
+    }
+}
+
+/// `time` as `YYYY-MM-DD HH:MM UTC`.
+fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_build_contains_each_section() {
+        let tools = [ToolDefinition {
+            name: "read_file".into(),
+            description: "Read a file".into(),
+            input_schema: serde_json::json!({"type": "object"}),
+        }];
+        let prompt = SystemPromptBuilder::new("You review code.")
+            .tools(&tools)
+            .workspace("/work")
+            .date("2026-10-16 09:30 UTC")
+            .append("Be concise.")
+            .build();
+
+        assert_eq!(
+            prompt,
+            "You review code.\n\n\
+            Available tools:\n- read_file: Read a file\n\n\
+            IMPORTANT: When using file tools, always use ABSOLUTE paths (starting with / or ~).\n\
+            The workspace directory is: /work\n\
+            For example, use '/work/file.txt' instead of just 'file.txt'.\n\n\
+            Current date and time: 2026-10-16 09:30 UTC\n\n\
+            Be concise."
+        );
+    }
+
+    #[test]
+    fn test_unset_sections_are_left_out() {
+        let prompt = SystemPromptBuilder::default().tools(&[]).append("").build();
+        assert_eq!(prompt, DEFAULT_BASE_PROMPT);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01 00:00 UTC");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_utc(leap_day), "2024-02-29 12:34 UTC");
+    }
+}
@@ -0,0 +1,121 @@
+// ABOUTME: Reviewer pattern - a second agent critiques the primary agent's output.
+// ABOUTME: Defines review verdicts, reviewed results, and the prompts passed between agents.
+
+use super::runner::SubAgentResult;
+use crate::llm::Usage;
+
+/// The line a reviewer starts its reply with to accept a result.
+pub const APPROVED_MARKER: &str = "APPROVED";
+
+/// What a reviewer decided about a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewVerdict {
+    /// The result is acceptable as is.
+    Approved,
+    /// The reviewer asked for changes.
+    RevisionRequested,
+}
+
+/// One round of review.
+#[derive(Debug, Clone)]
+pub struct Review {
+    /// Whether the result was accepted.
+    pub verdict: ReviewVerdict,
+
+    /// The reviewer's full reply.
+    pub feedback: String,
+}
+
+impl Review {
+    /// Parse a reviewer's reply.
+    ///
+    /// The result is approved when the first non-blank line starts with
+    /// [`APPROVED_MARKER`], ignoring case and surrounding markdown emphasis.
+    pub fn parse(reply: &str) -> Self {
+        let first_line = reply
+            .lines()
+            .map(|line| line.trim().trim_matches(|c| c == '*' || c == '#').trim())
+            .find(|line| !line.is_empty())
+            .unwrap_or("");
+        let approved = first_line
+            .get(..APPROVED_MARKER.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(APPROVED_MARKER));
+
+        Self {
+            verdict: if approved {
+                ReviewVerdict::Approved
+            } else {
+                ReviewVerdict::RevisionRequested
+            },
+            feedback: reply.to_string(),
+        }
+    }
+}
+
+/// Result from [`SubAgent::run_with_review`](super::SubAgent::run_with_review).
+#[derive(Debug, Clone)]
+pub struct ReviewedResult {
+    /// The primary agent's final result, after any revisions.
+    pub result: SubAgentResult,
+
+    /// Every review, in order. Empty if the primary run didn't complete.
+    pub reviews: Vec<Review>,
+
+    /// Number of times the primary agent was asked to revise.
+    pub revisions: usize,
+
+    /// Token usage of the reviewer across all rounds.
+    pub reviewer_usage: Usage,
+}
+
+impl ReviewedResult {
+    /// Returns true if the last review approved the result.
+    pub fn approved(&self) -> bool {
+        self.reviews
+            .last()
+            .is_some_and(|review| review.verdict == ReviewVerdict::Approved)
+    }
+}
+
+/// The task given to the reviewer for one round.
+pub(crate) fn review_prompt(task: &str, response: &str) -> String {
+    format!(
+        "Review another agent's response to a task.\n\n\
+         <task>\n{}\n</task>\n\n\
+         <response>\n{}\n</response>\n\n\
+         If the response fully and correctly completes the task, reply with {} on the first line. \
+         Otherwise, list the specific changes it needs.",
+        task, response, APPROVED_MARKER
+    )
+}
+
+/// The follow-up sent to the primary agent when the reviewer asks for changes.
+pub(crate) fn revision_prompt(feedback: &str) -> String {
+    format!(
+        "A reviewer asked for changes to your response:\n\n{}\n\n\
+         Address this feedback and give your complete revised response.",
+        feedback
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_approval() {
+        assert_eq!(Review::parse("APPROVED").verdict, ReviewVerdict::Approved);
+        assert_eq!(
+            Review::parse("\n  **Approved.** Looks good").verdict,
+            ReviewVerdict::Approved
+        );
+    }
+
+    #[test]
+    fn test_parse_revision_request() {
+        let review = Review::parse("The total is wrong.\nAPPROVED once fixed.");
+        assert_eq!(review.verdict, ReviewVerdict::RevisionRequested);
+        assert_eq!(review.feedback, "The total is wrong.\nAPPROVED once fixed.");
+        assert_eq!(Review::parse("").verdict, ReviewVerdict::RevisionRequested);
+    }
+}
@@ -1,23 +1,81 @@
 // ABOUTME: SubAgent runner - executes the think-act loop for a spawned agent.
 // ABOUTME: Handles tool execution, conversation management, hooks, and result aggregation.
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use super::definition::AgentDefinition;
+use super::definition::{AgentDefinition, RepeatedCallPolicy, ToolErrorPolicy};
 use super::filter::FilteredRegistry;
+use super::review::{Review, ReviewVerdict, ReviewedResult, review_prompt, revision_prompt};
+use super::transcript::{Transcript, TranscriptStore};
 use futures::StreamExt;
 
-use crate::error::LlmError;
-use crate::hook::{HookAction, HookEvent, HookRegistry};
+use crate::coordinator::{Coordinator, ScopedRateLimiter};
+use crate::error::{LlmError, PermissionError};
+use crate::hook::{HookAction, HookEvent, HookRegistry, RequestSummary};
 use crate::llm::stream_accumulator::StreamAccumulator;
-use crate::llm::{ContentBlock, LlmClient, Message, Request, Response, Role, StreamEvent, Usage};
-use crate::permission::{ApprovalContext, ApprovalHandler};
-use crate::tool::Registry;
+use crate::llm::{
+    ContentBlock, LlmClient, Message, Request, Response, Role, StopReason, StreamEvent, Usage,
+    UsageTracker, estimate_tokens, merge_adjacent_text,
+};
+use crate::permission::{ApprovalContext, ApprovalHandler, Decision, Policy, PolicySession};
+use crate::tool::{
+    CachingRegistry, ProgressReporter, Registry, ToolResult, ToolResultLimits, canonical_json,
+};
+
+/// How many responses in a row may contain malformed tool arguments before
+/// the run is aborted.
+const MAX_INVALID_TOOL_INPUT_RETRIES: usize = 2;
+
+/// Why a subagent stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentStopReason {
+    /// The model finished its turn without requesting more tools.
+    #[default]
+    Completed,
+    /// The think-act loop hit `max_iterations` before the model finished.
+    MaxIterations,
+    /// A token or cost budget was exhausted.
+    Budget,
+    /// The run exceeded its time limit.
+    Timeout,
+    /// The run was cancelled by the caller.
+    Cancelled,
+    /// The run stopped because of an unrecoverable error.
+    Error,
+    /// The model refused, or the provider's safety filter blocked its reply.
+    Refused,
+}
+
+/// The `SessionEnd` reason for a run that stopped with `reason`.
+fn session_end_reason(reason: AgentStopReason) -> &'static str {
+    match reason {
+        AgentStopReason::Completed => "complete",
+        AgentStopReason::MaxIterations => "iteration_limit",
+        AgentStopReason::Budget => "budget",
+        AgentStopReason::Timeout => "timeout",
+        AgentStopReason::Cancelled => "cancelled",
+        AgentStopReason::Error => "error",
+        AgentStopReason::Refused => "refused",
+    }
+}
+
+impl AgentStopReason {
+    /// Returns true if the agent finished on its own terms.
+    ///
+    /// Any other reason means the result was cut short and may be incomplete.
+    pub fn is_complete(&self) -> bool {
+        matches!(self, AgentStopReason::Completed)
+    }
+}
 
 /// Result from running a subagent.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubAgentResult {
     /// Unique identifier for this agent run.
     pub agent_id: String,
@@ -31,8 +89,73 @@ pub struct SubAgentResult {
     /// Total token usage across all LLM calls.
     pub usage: Usage,
 
+    /// The same usage broken down by the model that served each call.
+    #[serde(default)]
+    pub usage_total: UsageTracker,
+
     /// Number of iterations in the think-act loop.
     pub iterations: usize,
+
+    /// Why the agent stopped.
+    pub stop_reason: AgentStopReason,
+
+    /// Files the agent's tools created or modified, in the order first changed.
+    /// Collected from each tool result's [`FILES_CHANGED`](crate::tool::FILES_CHANGED) metadata.
+    #[serde(default)]
+    pub files_changed: Vec<String>,
+}
+
+impl SubAgentResult {
+    /// Returns true if the run stopped because it reached the definition's
+    /// `max_iterations`, so `content` may be an unfinished answer.
+    pub fn hit_iteration_limit(&self) -> bool {
+        self.stop_reason == AgentStopReason::MaxIterations
+    }
+}
+
+/// A tool call from a response, checked but not yet recorded in the transcript.
+enum PlannedCall {
+    /// Answered without running the tool.
+    Answered(ContentBlock),
+    /// The previous call again, answered with that call's result.
+    Repeated {
+        id: String,
+        name: String,
+        times: usize,
+    },
+    /// To run with `input`, unless a hook already decided the result.
+    Run {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+        result: Option<ToolResult>,
+    },
+}
+
+/// A tool call to place in a subagent's conversation without running it.
+///
+/// Paired with the [`ToolResult`] the model should see, it lets an agent start
+/// out knowing something, such as a file's contents. See
+/// [`SubAgent::with_seeded_context`].
+#[derive(Debug, Clone)]
+pub struct ToolUse {
+    /// The call's id. Left empty, a unique one is assigned.
+    pub id: String,
+    /// Name of the tool the call appears to come from.
+    pub name: String,
+    /// The tool's input.
+    pub input: serde_json::Value,
+}
+
+impl ToolUse {
+    /// A call to `name` with `input` and an id assigned when it is seeded.
+    pub fn new(name: impl Into<String>, input: serde_json::Value) -> Self {
+        Self {
+            id: String::new(),
+            name: name.into(),
+            input,
+        }
+    }
 }
 
 /// A subagent that can be spawned to handle a specific task.
@@ -58,11 +181,51 @@ pub struct SubAgent {
     /// Running total of token usage.
     usage: Usage,
 
+    /// Running total of token usage per model.
+    usage_total: UsageTracker,
+
+    /// Usage of the latest LLM response, with the number of messages it was
+    /// given.
+    last_response: Option<(usize, Usage)>,
+
+    /// Files changed by tool calls so far, without duplicates.
+    files_changed: Vec<String>,
+
     /// Optional hook registry for lifecycle events.
     hooks: Option<Arc<HookRegistry>>,
 
     /// Optional approval handler for tools requiring user approval.
     approval_handler: Option<Arc<dyn ApprovalHandler>>,
+
+    /// Optional permission policy, with call counts for this agent's session.
+    policy: Option<PolicySession>,
+
+    /// Optional store the transcript is saved to after every turn.
+    transcript_store: Option<Arc<dyn TranscriptStore>>,
+
+    /// Cancels the run when triggered.
+    cancel_token: CancellationToken,
+
+    /// Whether adjacent text blocks in responses are merged before storing.
+    merge_text_blocks: bool,
+
+    /// Whether to print a warning when a provider serves a different model.
+    warn_on_model_substitution: bool,
+
+    /// Optional rate limits, with the provider name used to pick the scope.
+    rate_limiter: Option<(Arc<ScopedRateLimiter>, String)>,
+
+    /// Tool calls and results to add after the task on the next run.
+    seeded_context: Vec<(ToolUse, ToolResult)>,
+
+    /// How much of each tool result is kept in the transcript.
+    tool_result_limits: ToolResultLimits,
+
+    /// Optional coordinator whose tool slots cap concurrent tool calls.
+    coordinator: Option<Arc<Coordinator>>,
+
+    /// Optional cache that tool calls run through.
+    tool_cache: Option<CachingRegistry>,
 }
 
 impl SubAgent {
@@ -84,8 +247,21 @@ impl SubAgent {
             messages: Vec::new(),
             tool_use_count: 0,
             usage: Usage::default(),
+            usage_total: UsageTracker::new(),
+            last_response: None,
+            files_changed: Vec::new(),
             hooks: None,
             approval_handler: None,
+            policy: None,
+            transcript_store: None,
+            cancel_token: CancellationToken::new(),
+            merge_text_blocks: true,
+            warn_on_model_substitution: false,
+            rate_limiter: None,
+            seeded_context: Vec::new(),
+            tool_result_limits: ToolResultLimits::new(),
+            coordinator: None,
+            tool_cache: None,
         }
     }
 
@@ -94,7 +270,7 @@ impl SubAgent {
     /// This creates an agent that continues from where a previous run left off,
     /// preserving the conversation history and agent ID.
     ///
-    /// Note: `tool_use_count` and `usage` are reset to zero for the resumed run.
+    /// Note: `tool_use_count`, `usage` and `files_changed` are reset for the resumed run.
     /// The returned `SubAgentResult` will only reflect metrics from this run,
     /// not cumulative totals across all runs. To track totals, accumulate the
     /// results from each run externally.
@@ -117,9 +293,57 @@ impl SubAgent {
             messages: transcript,
             tool_use_count: 0,
             usage: Usage::default(),
+            usage_total: UsageTracker::new(),
+            last_response: None,
+            files_changed: Vec::new(),
             hooks: None,
             approval_handler: None,
+            policy: None,
+            transcript_store: None,
+            cancel_token: CancellationToken::new(),
+            merge_text_blocks: true,
+            warn_on_model_substitution: false,
+            rate_limiter: None,
+            seeded_context: Vec::new(),
+            tool_result_limits: ToolResultLimits::new(),
+            coordinator: None,
+            tool_cache: None,
+        }
+    }
+
+    /// Resume a subagent from an exported [`Transcript`], as with
+    /// [`SubAgent::resume`].
+    ///
+    /// The transcript's model is used when `definition` doesn't name one.
+    /// The transcript's usage carries over into [`usage`](Self::usage) and
+    /// [`usage_total`](Self::usage_total), counted under its model.
+    /// Transcripts without an agent ID, from before schema version 1, get a
+    /// new one.
+    pub fn resume_transcript(
+        transcript: Transcript,
+        mut definition: AgentDefinition,
+        client: Arc<dyn LlmClient>,
+        registry: Registry,
+    ) -> Self {
+        if definition.model.is_none() {
+            definition.model = transcript.model.clone();
+        }
+        let agent_id = if transcript.agent_id.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            transcript.agent_id
+        };
+        let model = transcript
+            .model
+            .or_else(|| definition.model.clone())
+            .unwrap_or_default();
+
+        let mut agent = Self::resume(agent_id, definition, client, registry, transcript.messages);
+        if transcript.usage != Usage::default() {
+            agent.usage_total.record(&model, &transcript.usage);
+            agent.usage = transcript.usage;
         }
+        agent
     }
 
     /// Set the hook registry for lifecycle events.
@@ -134,6 +358,124 @@ impl SubAgent {
         self
     }
 
+    /// Check every tool call against a permission policy.
+    ///
+    /// Denied and rate-limited calls return an error result to the model
+    /// instead of running. `Ask` decisions go through the approval handler.
+    /// Rate-limit counts belong to this agent and start at zero, including
+    /// for agents created with [`SubAgent::resume`].
+    pub fn with_policy(mut self, policy: Arc<Policy>) -> Self {
+        self.policy = Some(PolicySession::new(policy));
+        self
+    }
+
+    /// Save the transcript to `store` after every turn, keyed by agent ID.
+    ///
+    /// The transcript is written once the task is added, again after each
+    /// batch of tool results, and once more with the final answer, so a
+    /// crashed run can be picked up with [`SubAgent::resume`] from the last
+    /// completed turn.
+    pub fn with_incremental_save(mut self, store: Arc<dyn TranscriptStore>) -> Self {
+        self.transcript_store = Some(store);
+        self
+    }
+
+    /// Cancel the run when `token` is triggered.
+    ///
+    /// The run stops before the next iteration, abandoning any LLM call or
+    /// tool call in flight, and returns a result with
+    /// [`AgentStopReason::Cancelled`]. Every tool call already in the
+    /// transcript gets a result, so the transcript can still be resumed.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel_token = token;
+        self
+    }
+
+    /// Use a caller-chosen agent ID instead of a generated one.
+    ///
+    /// Useful when the caller needs the ID before the run starts, e.g. to
+    /// cancel it.
+    pub fn with_agent_id(mut self, agent_id: impl Into<String>) -> Self {
+        self.agent_id = agent_id.into();
+        self
+    }
+
+    /// Merge adjacent text blocks in each response before it joins the
+    /// transcript (default: on). Text on either side of a tool call is never
+    /// merged. Turn off to keep responses exactly as the provider sent them.
+    pub fn with_text_block_merging(mut self, enabled: bool) -> Self {
+        self.merge_text_blocks = enabled;
+        self
+    }
+
+    /// Print a warning to stderr when a response comes from a different
+    /// model than requested (default: off). See [`Response::model_substituted`].
+    pub fn with_model_substitution_warning(mut self, enabled: bool) -> Self {
+        self.warn_on_model_substitution = enabled;
+        self
+    }
+
+    /// Wait for rate limits before every LLM call.
+    ///
+    /// Calls are counted against the `provider:model` scope (see
+    /// [`ScopedRateLimiter::scope_for`]) using the request's model, with its
+    /// estimated input tokens. Waiting for capacity is cancelled along with
+    /// the run.
+    pub fn with_rate_limiter(
+        mut self,
+        limiter: Arc<ScopedRateLimiter>,
+        provider: impl Into<String>,
+    ) -> Self {
+        self.rate_limiter = Some((limiter, provider.into()));
+        self
+    }
+
+    /// Start the agent off with tool calls it never had to make.
+    ///
+    /// On the next [`run`](SubAgent::run), the task message is followed by an
+    /// assistant turn with these calls and a user turn with their results, so
+    /// the model's first request already holds the data, paired the same way
+    /// as real calls. The tools aren't run, no hooks fire, and the calls don't
+    /// count towards `tool_use_count`.
+    pub fn with_seeded_context(mut self, context: Vec<(ToolUse, ToolResult)>) -> Self {
+        self.seeded_context = context;
+        self
+    }
+
+    /// Truncate tool results to these limits before they join the
+    /// transcript (default: no limits), so one huge output can't fill the
+    /// context. Hooks still see the whole result in `PostToolUse`.
+    pub fn with_tool_result_limits(mut self, limits: ToolResultLimits) -> Self {
+        self.tool_result_limits = limits;
+        self
+    }
+
+    /// Hold one of the coordinator's tool slots while each tool call runs,
+    /// so agents sharing it run at most its `max_concurrent_tools` calls at
+    /// once. Calls in the same turn run concurrently either way, unless the
+    /// tool isn't [parallel-safe](crate::tool::Tool::parallel_safe).
+    pub fn with_coordinator(mut self, coordinator: Arc<Coordinator>) -> Self {
+        self.coordinator = Some(coordinator);
+        self
+    }
+
+    /// Run tool calls through `cache`, so a repeated call to a
+    /// [cacheable](crate::tool::Tool::cacheable) tool reuses the earlier
+    /// result. Calls that change files clear it (see [`CachingRegistry`]).
+    ///
+    /// Tools still come from this agent's registry, but run with the
+    /// timeouts and redaction of the one `cache` wraps, so wrap the same
+    /// registry. Clones of `cache` share it, e.g. across agents in a session.
+    pub fn with_tool_cache(mut self, cache: CachingRegistry) -> Self {
+        self.tool_cache = Some(cache);
+        self
+    }
+
+    /// A token that cancels this agent's run when triggered.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
     /// Get the agent ID.
     pub fn agent_id(&self) -> &str {
         &self.agent_id
@@ -146,18 +488,62 @@ impl SubAgent {
         &self.messages
     }
 
+    /// Export the conversation, agent ID, model and usage so far as a
+    /// [`Transcript`], for [`SubAgent::resume_transcript`].
+    pub fn export_transcript(&self) -> Transcript {
+        Transcript {
+            model: self.definition.model.clone(),
+            usage: self.usage.clone(),
+            ..Transcript::new(self.agent_id.clone(), self.messages.clone())
+        }
+    }
+
     /// Get the current accumulated token usage.
     ///
-    /// Useful for retrieving partial usage after an error (e.g., max iterations).
+    /// Useful for retrieving partial usage after an error.
     pub fn usage(&self) -> &Usage {
         &self.usage
     }
 
+    /// Get the accumulated token usage per model.
+    pub fn usage_total(&self) -> &UsageTracker {
+        &self.usage_total
+    }
+
+    /// Tokens the conversation takes up, for deciding when to compact it.
+    ///
+    /// Taken from the latest LLM response's usage: its input, cached or not,
+    /// plus its output. Only messages added after that reply are estimated,
+    /// and the whole transcript is estimated before the first response.
+    pub fn context_tokens(&self) -> usize {
+        let (counted, tokens) = match &self.last_response {
+            // A final answer isn't kept, so the reply may not follow what was sent
+            Some((sent, usage)) => (
+                match self.messages.get(*sent) {
+                    Some(reply) if reply.role == Role::Assistant => sent + 1,
+                    _ => *sent,
+                },
+                usage.input_tokens
+                    + usage.cache_read_tokens
+                    + usage.cache_write_tokens
+                    + usage.output_tokens,
+            ),
+            None => (0, 0),
+        };
+        let rest = self.messages.get(counted..).unwrap_or_default().to_vec();
+        tokens as usize + estimate_tokens(&Request::default().messages(rest))
+    }
+
     /// Get the current tool use count.
     pub fn tool_use_count(&self) -> usize {
         self.tool_use_count
     }
 
+    /// Get the files changed by tool calls so far, in the order first changed.
+    pub fn files_changed(&self) -> &[String] {
+        &self.files_changed
+    }
+
     /// Fork conversation context from a parent agent.
     pub fn fork_messages(&mut self, parent_messages: Vec<Message>) {
         self.messages = parent_messages;
@@ -166,17 +552,129 @@ impl SubAgent {
     /// Fire a hook event and handle the result.
     async fn fire_hook(&self, event: HookEvent) -> Result<HookAction, LlmError> {
         if let Some(hooks) = &self.hooks {
-            hooks.fire(&event).await.map_err(|e| LlmError::Api {
-                status: 0,
-                message: format!("Hook error: {}", e),
-            })
+            hooks
+                .fire(&event)
+                .await
+                .map_err(|e| match e.downcast::<LlmError>() {
+                    Ok(err) => err,
+                    Err(e) => LlmError::Api {
+                        status: 0,
+                        message: format!("Hook error: {}", e),
+                    },
+                })
         } else {
             Ok(HookAction::Continue)
         }
     }
 
+    /// Save `messages` as the transcript if incremental saving is enabled.
+    async fn save_transcript(&self, messages: &[Message]) -> Result<(), LlmError> {
+        if let Some(store) = &self.transcript_store {
+            store
+                .save(&self.agent_id, messages)
+                .await
+                .map_err(|e| LlmError::Api {
+                    status: 0,
+                    message: format!("Transcript save error: {}", e),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// The transcript block for a tool's result. Images go with it if the
+    /// client accepts them in tool results, and are described in text if not.
+    fn tool_result_block(&self, id: &str, result: &ToolResult) -> ContentBlock {
+        let images_supported = self.client.supports_tool_result_images();
+        let mut content = result.content.clone();
+        if !images_supported {
+            for image in &result.images {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content.push_str(&image.placeholder());
+            }
+        }
+
+        let block = if result.is_error {
+            ContentBlock::tool_error(id, content)
+        } else {
+            ContentBlock::tool_result(id, content)
+        };
+        if images_supported {
+            block.with_images(result.images.iter().cloned())
+        } else {
+            block
+        }
+    }
+
+    /// Add the seeded tool calls and their results to the conversation.
+    fn push_seeded_context(&mut self) {
+        let seeded = std::mem::take(&mut self.seeded_context);
+        if seeded.is_empty() {
+            return;
+        }
+
+        let (calls, results): (Vec<_>, Vec<_>) = seeded.into_iter().unzip();
+        let mut content: Vec<ContentBlock> = calls
+            .into_iter()
+            .map(|call| ContentBlock::ToolUse {
+                id: call.id,
+                name: call.name,
+                input: call.input,
+            })
+            .collect();
+        assign_tool_use_ids(&mut content, 0, &mut tool_use_ids(&self.messages));
+
+        let results = content
+            .iter()
+            .zip(results)
+            .filter_map(|(call, result)| match call {
+                ContentBlock::ToolUse { id, .. } => Some(self.tool_result_block(id, &result)),
+                _ => None,
+            })
+            .collect();
+
+        self.messages.push(Message {
+            role: Role::Assistant,
+            content,
+        });
+        self.messages.push(Message::tool_results(results));
+    }
+
     /// Run the agent on a task and return the result.
+    ///
+    /// The run is bracketed by `SessionStart` and `SessionEnd` hook events.
+    /// `SessionEnd` fires on every exit, with a reason such as
+    /// "iteration_limit" and the error if the run failed.
     pub async fn run(&mut self, task: &str) -> Result<SubAgentResult, LlmError> {
+        self.fire_hook(HookEvent::SessionStart {
+            session_id: self.agent_id.clone(),
+            source: "run".to_string(),
+            prompt: task.to_string(),
+        })
+        .await?;
+
+        let outcome = self.run_session(task).await;
+        let (reason, error) = match &outcome {
+            Ok(result) => (session_end_reason(result.stop_reason), None),
+            Err(e) => ("error", Some(e.to_string())),
+        };
+        let ended = self
+            .fire_hook(HookEvent::SessionEnd {
+                session_id: self.agent_id.clone(),
+                error,
+                reason: reason.to_string(),
+            })
+            .await;
+
+        // The run's own error takes precedence over a failing SessionEnd hook
+        let result = outcome?;
+        ended?;
+        Ok(result)
+    }
+
+    /// The body of [`run`](Self::run), between the session hooks.
+    async fn run_session(&mut self, task: &str) -> Result<SubAgentResult, LlmError> {
         // Fire AgentStart hook
         self.fire_hook(HookEvent::AgentStart {
             agent_id: self.agent_id.clone(),
@@ -186,11 +684,57 @@ impl SubAgent {
 
         // Add the task as a user message
         self.messages.push(Message::user(task));
+        self.push_seeded_context();
+        self.save_transcript(&self.messages).await?;
 
         let mut iterations = 0;
 
+        // Text from the most recent response, reported if the loop is cut short
+        let mut last_text = String::new();
+
+        // Consecutive failures per tool, reset when the tool succeeds
+        let mut consecutive_failures: HashMap<String, usize> = HashMap::new();
+
+        // Consecutive responses with unparseable tool arguments
+        let mut invalid_input_streak = 0;
+
+        // The latest tool call (name and arguments), the result it got, and
+        // how many times in a row the model has made it
+        let mut last_call: Option<(String, String)> = None;
+        let mut last_call_result = String::new();
+        let mut repeat_streak = 0;
+
+        // Tool call ids already in the conversation, so new ones stay unique
+        let mut seen_tool_ids = tool_use_ids(&self.messages);
+
         // Think-act loop
         let result = loop {
+            if self.cancel_token.is_cancelled() {
+                break SubAgentResult {
+                    agent_id: self.agent_id.clone(),
+                    content: last_text,
+                    tool_use_count: self.tool_use_count,
+                    usage: self.usage.clone(),
+                    usage_total: self.usage_total.clone(),
+                    iterations,
+                    stop_reason: AgentStopReason::Cancelled,
+                    files_changed: self.files_changed.clone(),
+                };
+            }
+
+            if iterations >= self.definition.max_iterations {
+                break SubAgentResult {
+                    agent_id: self.agent_id.clone(),
+                    content: last_text,
+                    tool_use_count: self.tool_use_count,
+                    usage: self.usage.clone(),
+                    usage_total: self.usage_total.clone(),
+                    iterations,
+                    stop_reason: AgentStopReason::MaxIterations,
+                    files_changed: self.files_changed.clone(),
+                };
+            }
+
             iterations += 1;
 
             // Fire Iteration hook
@@ -200,16 +744,6 @@ impl SubAgent {
             })
             .await?;
 
-            if iterations > self.definition.max_iterations {
-                return Err(LlmError::Api {
-                    status: 0,
-                    message: format!(
-                        "Agent exceeded max iterations ({})",
-                        self.definition.max_iterations
-                    ),
-                });
-            }
-
             // Build the request - model must be configured
             let model = self.definition.model.clone().ok_or_else(|| {
                 LlmError::Configuration(
@@ -218,20 +752,82 @@ impl SubAgent {
                 )
             })?;
 
-            let request = Request::new(&model)
+            let mut request = Request::new(&model)
                 .system(&self.definition.system_prompt)
                 .messages(self.messages.clone())
                 .tools(self.tools.to_definitions().await)
                 .max_tokens(4096);
 
-            // Call the LLM
-            let response = self.call_llm(&request).await?;
+            match self
+                .fire_hook(HookEvent::LlmRequest {
+                    agent_id: self.agent_id.clone(),
+                    model: model.clone(),
+                    request_summary: RequestSummary::of(&request),
+                    request: request.clone(),
+                })
+                .await?
+            {
+                HookAction::Block(reason) => return Err(LlmError::Blocked(reason)),
+                HookAction::Transform(value) => {
+                    request = serde_json::from_value(value).map_err(|e| {
+                        LlmError::InvalidRequest(format!(
+                            "LlmRequest transform is not a request: {}",
+                            e
+                        ))
+                    })?;
+                }
+                HookAction::Continue => {}
+            }
+
+            // Call the LLM, abandoning the call if the run is cancelled
+            let cancel_token = self.cancel_token.clone();
+            let mut response = tokio::select! {
+                response = self.call_llm(&request) => response?,
+                _ = cancel_token.cancelled() => continue,
+            };
+            assign_tool_use_ids(&mut response.content, iterations, &mut seen_tool_ids);
+            let invalid_inputs = invalid_tool_inputs(&response);
+            if self.merge_text_blocks {
+                merge_adjacent_text(&mut response.content);
+            }
 
             // Aggregate usage
-            self.usage.input_tokens += response.usage.input_tokens;
-            self.usage.output_tokens += response.usage.output_tokens;
-            self.usage.cache_read_tokens += response.usage.cache_read_tokens;
-            self.usage.cache_write_tokens += response.usage.cache_write_tokens;
+            self.usage.add(&response.usage);
+            self.last_response = Some((self.messages.len(), response.usage.clone()));
+            if self.warn_on_model_substitution && response.model_substituted() {
+                eprintln!(
+                    "Warning: requested model '{}' but '{}' served the response",
+                    response.model,
+                    response.actual_model()
+                );
+            }
+            let model = if response.actual_model().is_empty() {
+                &request.model
+            } else {
+                response.actual_model()
+            };
+            self.usage_total.record(model, &response.usage);
+            self.fire_hook(HookEvent::LlmResponse {
+                agent_id: self.agent_id.clone(),
+                model: model.to_string(),
+                usage: response.usage.clone(),
+                stop_reason: response.stop_reason.clone(),
+            })
+            .await?;
+
+            // Let post-processors rewrite the response before it's reported or stored
+            if let HookAction::Transform(content) = self
+                .fire_hook(HookEvent::PostResponse {
+                    agent_id: self.agent_id.clone(),
+                    content: response.content.clone(),
+                })
+                .await?
+            {
+                response.content = serde_json::from_value(content).map_err(|e| LlmError::Api {
+                    status: 0,
+                    message: format!("Hook error: {}", e),
+                })?;
+            }
 
             // Fire ResponseReceived hook for streaming callbacks
             let response_text = response.text();
@@ -254,6 +850,8 @@ impl SubAgent {
             })
             .await?;
 
+            last_text = response_text;
+
             // Check for tool use
             if response.has_tool_use() {
                 // Add assistant response to history
@@ -262,11 +860,64 @@ impl SubAgent {
                     content: response.content.clone(),
                 });
 
-                // Execute each tool
-                let mut tool_results = Vec::new();
+                // Check each call and fire PreToolUse in order, run the calls
+                // that pass, then record every result in the order of the calls
+                let mut planned = Vec::new();
+                let mut aborted_by: Option<(String, String)> = None;
+                let mut repeated_call: Option<(String, usize)> = None;
 
                 for block in &response.content {
                     if let ContentBlock::ToolUse { id, name, input } = block {
+                        // Answer the remaining calls without running them
+                        if self.cancel_token.is_cancelled() {
+                            planned.push(PlannedCall::Answered(ContentBlock::tool_error(
+                                id,
+                                "Cancelled before the tool ran",
+                            )));
+                            continue;
+                        }
+
+                        // Ask the model to resend a call whose arguments didn't parse
+                        if let Some(error) = invalid_inputs.get(id) {
+                            planned.push(PlannedCall::Answered(ContentBlock::tool_error(
+                                id,
+                                format!(
+                                    "invalid JSON arguments: {}. Call the tool again with valid JSON arguments.",
+                                    error
+                                ),
+                            )));
+                            continue;
+                        }
+
+                        // Catch the model repeating the call it just made, whatever
+                        // order it puts the argument keys in
+                        let call = (name.clone(), canonical_json(input));
+                        if last_call.as_ref() == Some(&call) {
+                            repeat_streak += 1;
+                        } else {
+                            last_call = Some(call);
+                            repeat_streak = 1;
+                        }
+                        match self.definition.on_repeated_tool_call {
+                            RepeatedCallPolicy::Nudge { after } if repeat_streak > after.max(1) => {
+                                planned.push(PlannedCall::Repeated {
+                                    id: id.clone(),
+                                    name: name.clone(),
+                                    times: repeat_streak - 1,
+                                });
+                                continue;
+                            }
+                            RepeatedCallPolicy::Abort { after } if repeat_streak > after.max(1) => {
+                                planned.push(PlannedCall::Answered(ContentBlock::tool_error(
+                                    id,
+                                    "Not run: the same call was repeated too many times",
+                                )));
+                                repeated_call.get_or_insert((name.clone(), repeat_streak));
+                                continue;
+                            }
+                            _ => {}
+                        }
+
                         self.tool_use_count += 1;
 
                         // Fire PreToolUse hook
@@ -278,61 +929,179 @@ impl SubAgent {
                             .await?;
 
                         // Check if hook blocked the tool, track effective input
-                        let (tool_result, effective_input) = match hook_action {
+                        let (input, result) = match hook_action {
                             HookAction::Block(msg) => (
-                                crate::tool::ToolResult::error(format!("Blocked by hook: {}", msg)),
                                 input.clone(),
+                                Some(ToolResult::error(format!("Blocked by hook: {}", msg))),
                             ),
-                            HookAction::Transform(new_input) => {
-                                // Use transformed input
-                                let result = self.execute_tool(name, new_input.clone()).await;
-                                (result, new_input)
-                            }
-                            HookAction::Continue => {
-                                let result = self.execute_tool(name, input.clone()).await;
-                                (result, input.clone())
-                            }
+                            HookAction::Transform(new_input) => (new_input, None),
+                            HookAction::Continue => (input.clone(), None),
                         };
+                        planned.push(PlannedCall::Run {
+                            id: id.clone(),
+                            name: name.clone(),
+                            input,
+                            result,
+                        });
+                    }
+                }
 
-                        // Fire PostToolUse hook with the effective input (after any transform)
-                        self.fire_hook(HookEvent::PostToolUse {
-                            tool_name: name.clone(),
-                            tool_use_id: id.clone(),
-                            input: effective_input,
-                            result: tool_result.clone(),
-                        })
-                        .await?;
-
-                        let result_block = if tool_result.is_error {
-                            ContentBlock::tool_error(id, &tool_result.content)
-                        } else {
-                            ContentBlock::tool_result(id, &tool_result.content)
-                        };
+                self.run_planned_calls(&mut planned).await;
+
+                let mut tool_results = Vec::new();
+                for call in planned {
+                    let (id, name, effective_input, tool_result) = match call {
+                        PlannedCall::Answered(block) => {
+                            tool_results.push(block);
+                            continue;
+                        }
+                        PlannedCall::Repeated { id, name, times } => {
+                            tool_results.push(ContentBlock::tool_error(
+                                id,
+                                format!(
+                                    "Not run: you already called {} with these arguments {} times in a row. The result was:\n{}\nUse that result instead of calling again.",
+                                    name, times, last_call_result
+                                ),
+                            ));
+                            continue;
+                        }
+                        PlannedCall::Run {
+                            id,
+                            name,
+                            input,
+                            result,
+                        } => (id, name, input, result.expect("planned calls have run")),
+                    };
+
+                    // Fire PostToolUse hook with the effective input (after any transform)
+                    self.fire_hook(HookEvent::PostToolUse {
+                        tool_name: name.clone(),
+                        tool_use_id: id.clone(),
+                        input: effective_input,
+                        result: tool_result.clone(),
+                    })
+                    .await?;
+                    let tool_result = self.tool_result_limits.apply(&name, tool_result);
 
-                        tool_results.push(result_block);
+                    for path in tool_result.files_changed() {
+                        if !self.files_changed.contains(&path) {
+                            self.files_changed.push(path);
+                        }
+                    }
+
+                    if tool_result.is_error {
+                        let failures = consecutive_failures.entry(name.clone()).or_default();
+                        *failures += 1;
+                        if let ToolErrorPolicy::Abort { after } =
+                            self.definition.on_repeated_tool_error
+                            && *failures >= after
+                            && aborted_by.is_none()
+                        {
+                            aborted_by = Some((name.clone(), tool_result.content.clone()));
+                        }
+                    } else {
+                        consecutive_failures.remove(&name);
                     }
+                    last_call_result = tool_result.content.clone();
+                    let result_block = self.tool_result_block(&id, &tool_result);
+
+                    tool_results.push(result_block);
                 }
 
                 // Add tool results to history
                 self.messages.push(Message::tool_results(tool_results));
+                self.save_transcript(&self.messages).await?;
+
+                // Give up if the model keeps sending arguments we can't parse
+                if invalid_inputs.is_empty() {
+                    invalid_input_streak = 0;
+                } else {
+                    invalid_input_streak += 1;
+                    if invalid_input_streak > MAX_INVALID_TOOL_INPUT_RETRIES {
+                        break SubAgentResult {
+                            agent_id: self.agent_id.clone(),
+                            content: format!(
+                                "Aborted: the model sent invalid JSON tool arguments {} times in a row",
+                                invalid_input_streak
+                            ),
+                            tool_use_count: self.tool_use_count,
+                            usage: self.usage.clone(),
+                            usage_total: self.usage_total.clone(),
+                            iterations,
+                            stop_reason: AgentStopReason::Error,
+                            files_changed: self.files_changed.clone(),
+                        };
+                    }
+                }
+
+                // Stop instead of letting the model retry a tool that keeps failing
+                if let Some((tool_name, error)) = aborted_by {
+                    break SubAgentResult {
+                        agent_id: self.agent_id.clone(),
+                        content: format!(
+                            "Aborted: tool '{}' failed {} times in a row: {}",
+                            tool_name, consecutive_failures[&tool_name], error
+                        ),
+                        tool_use_count: self.tool_use_count,
+                        usage: self.usage.clone(),
+                        usage_total: self.usage_total.clone(),
+                        iterations,
+                        stop_reason: AgentStopReason::Error,
+                        files_changed: self.files_changed.clone(),
+                    };
+                }
+
+                // Stop a model that keeps making the same call
+                if let Some((tool_name, count)) = repeated_call {
+                    break SubAgentResult {
+                        agent_id: self.agent_id.clone(),
+                        content: format!(
+                            "Aborted: the model called '{}' with the same arguments {} times in a row",
+                            tool_name, count
+                        ),
+                        tool_use_count: self.tool_use_count,
+                        usage: self.usage.clone(),
+                        usage_total: self.usage_total.clone(),
+                        iterations,
+                        stop_reason: AgentStopReason::Error,
+                        files_changed: self.files_changed.clone(),
+                    };
+                }
 
                 // Continue the loop
                 continue;
             }
 
-            // No tool use - agent is done
+            // No tool use - agent is done, unless the reply was refused or filtered
             let content = response.text();
+            let stop_reason = match response.stop_reason {
+                StopReason::ContentFilter | StopReason::Refusal => AgentStopReason::Refused,
+                _ => AgentStopReason::Completed,
+            };
+
+            // The final answer isn't kept in the history, but the saved transcript ends with it
+            if self.transcript_store.is_some() {
+                let mut messages = self.messages.clone();
+                messages.push(Message {
+                    role: Role::Assistant,
+                    content: response.content.clone(),
+                });
+                self.save_transcript(&messages).await?;
+            }
 
             break SubAgentResult {
                 agent_id: self.agent_id.clone(),
                 content,
                 tool_use_count: self.tool_use_count,
                 usage: self.usage.clone(),
+                usage_total: self.usage_total.clone(),
                 iterations,
+                stop_reason,
+                files_changed: self.files_changed.clone(),
             };
         };
 
-        // Fire AgentStop hook
+        // Fire AgentStop hook; its result says why the run stopped
         self.fire_hook(HookEvent::AgentStop {
             agent_id: self.agent_id.clone(),
             result: result.clone(),
@@ -342,12 +1111,90 @@ impl SubAgent {
         Ok(result)
     }
 
+    /// Run the task, then have a reviewer agent critique the result.
+    ///
+    /// The reviewer sees the task and this agent's final response and either
+    /// approves it or asks for changes (see [`Review::parse`]). Requested
+    /// changes are sent back to this agent in the same conversation, at most
+    /// `max_revisions` times, and each revision is reviewed again.
+    ///
+    /// The reviewer shares this agent's client, tools, tool cache, hooks,
+    /// approval handler, permission policy, rate limiter, tool result limits,
+    /// coordinator and cancellation token, and falls back to this agent's model if its
+    /// definition has none. Each review gets its own policy rate-limit counts.
+    /// Review stops early if either agent's run doesn't complete.
+    pub async fn run_with_review(
+        &mut self,
+        task: &str,
+        mut reviewer: AgentDefinition,
+        max_revisions: usize,
+    ) -> Result<ReviewedResult, LlmError> {
+        if reviewer.model.is_none() {
+            reviewer.model = self.definition.model.clone();
+        }
+
+        let mut result = self.run(task).await?;
+        let mut reviews = Vec::new();
+        let mut revisions = 0;
+        let mut reviewer_usage = Usage::default();
+
+        while result.stop_reason.is_complete() {
+            let mut critic = SubAgent::new(
+                reviewer.clone(),
+                self.client.clone(),
+                self.tools.source().clone(),
+            )
+            .with_cancellation(self.cancel_token.clone())
+            .with_tool_result_limits(self.tool_result_limits.clone());
+            critic.hooks = self.hooks.clone();
+            critic.approval_handler = self.approval_handler.clone();
+            critic.policy = self.policy.as_ref().map(PolicySession::restart);
+            critic.rate_limiter = self.rate_limiter.clone();
+            critic.coordinator = self.coordinator.clone();
+            critic.tool_cache = self.tool_cache.clone();
+
+            let critique = critic.run(&review_prompt(task, &result.content)).await?;
+            reviewer_usage.add(&critique.usage);
+            if !critique.stop_reason.is_complete() {
+                break;
+            }
+
+            let review = Review::parse(&critique.content);
+            let approved = review.verdict == ReviewVerdict::Approved;
+            let feedback = revision_prompt(&review.feedback);
+            reviews.push(review);
+            if approved || revisions >= max_revisions {
+                break;
+            }
+
+            // run() doesn't keep the final answer, but the revision should build on it
+            self.messages.push(Message::assistant(&result.content));
+            revisions += 1;
+            result = self.run(&feedback).await?;
+        }
+
+        Ok(ReviewedResult {
+            result,
+            reviews,
+            revisions,
+            reviewer_usage,
+        })
+    }
+
     /// Call the LLM, using streaming or non-streaming based on definition.
     ///
-    /// When streaming is enabled, fires `StreamDelta` hooks for text tokens
-    /// and `StreamUsage` hooks for MessageDelta events, then assembles the
-    /// final `Response` from accumulated stream events.
+    /// When streaming is enabled, fires `StreamDelta` hooks for text tokens,
+    /// `ToolInputDelta` hooks as tool arguments arrive, and `StreamUsage` hooks
+    /// for MessageDelta events, then assembles the final `Response` from
+    /// accumulated stream events.
     async fn call_llm(&self, request: &Request) -> Result<Response, LlmError> {
+        if let Some((limiter, provider)) = &self.rate_limiter {
+            let scope = ScopedRateLimiter::scope_for(provider, &request.model);
+            limiter
+                .acquire(&scope, estimate_tokens(request) as f64)
+                .await;
+        }
+
         if !self.definition.streaming {
             return self.client.create_message(request).await;
         }
@@ -355,21 +1202,17 @@ impl SubAgent {
         // Streaming path
         let mut stream = self.client.create_message_stream(request);
         let mut accumulator = StreamAccumulator::new();
-        let mut message_id = String::new();
-        let mut model = String::new();
-        let mut stop_reason = None;
-        let mut usage = Usage::default();
+        let mut current_tool = (String::new(), String::new());
 
         while let Some(event_result) = stream.next().await {
             let event = event_result?;
 
             match &event {
-                StreamEvent::MessageStart {
-                    id: msg_id,
-                    model: msg_model,
+                StreamEvent::ContentBlockStart {
+                    block: ContentBlock::ToolUse { id, name, .. },
+                    ..
                 } => {
-                    message_id = msg_id.clone();
-                    model = msg_model.clone();
+                    current_tool = (id.clone(), name.clone());
                 }
                 StreamEvent::ContentBlockDelta { text, .. } if !accumulator.in_tool_use() => {
                     // Fire StreamDelta hook for text tokens
@@ -380,11 +1223,8 @@ impl SubAgent {
                     .await?;
                 }
                 StreamEvent::MessageDelta {
-                    stop_reason: sr,
-                    usage: delta_usage,
+                    usage: delta_usage, ..
                 } => {
-                    stop_reason = *sr;
-                    usage = delta_usage.clone();
                     // Fire StreamUsage hook
                     self.fire_hook(HookEvent::StreamUsage {
                         agent_id: self.agent_id.clone(),
@@ -396,27 +1236,111 @@ impl SubAgent {
             }
 
             accumulator.handle_event(&event);
+
+            if let StreamEvent::InputJsonDelta { .. } = &event {
+                // Fire after accumulating so hooks see the input received so far
+                self.fire_hook(HookEvent::ToolInputDelta {
+                    agent_id: self.agent_id.clone(),
+                    tool_use_id: current_tool.0.clone(),
+                    tool_name: current_tool.1.clone(),
+                    partial_json: accumulator.current_tool_input().to_string(),
+                })
+                .await?;
+            }
         }
 
-        Ok(Response {
-            id: message_id,
-            content: accumulator.into_content(),
-            stop_reason: stop_reason.unwrap_or(crate::llm::StopReason::EndTurn),
-            model,
-            usage,
-        })
+        Ok(accumulator.into_response(request.model.clone()))
+    }
+
+    /// Run every planned call that has no result yet and fill its result in.
+    ///
+    /// Calls run in the groups given by
+    /// [`Registry::parallel_groups`](crate::tool::Registry::parallel_groups):
+    /// consecutive calls to [parallel-safe](crate::tool::Tool::parallel_safe)
+    /// tools run concurrently, and a call to any other tool waits for the
+    /// calls before it and runs on its own.
+    async fn run_planned_calls(&self, planned: &mut [PlannedCall]) {
+        let mut pending: Vec<(&str, &str, &serde_json::Value, &mut Option<ToolResult>)> = planned
+            .iter_mut()
+            .filter_map(|call| match call {
+                PlannedCall::Run {
+                    id,
+                    name,
+                    input,
+                    result,
+                } if result.is_none() => Some((id.as_str(), name.as_str(), &*input, result)),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<&str> = pending.iter().map(|(_, name, _, _)| *name).collect();
+
+        for group in self.tools.source().parallel_groups(&names).await {
+            let results = futures::future::join_all(
+                pending[group.clone()]
+                    .iter()
+                    .map(|(id, name, input, _)| self.execute_tool(id, name, (*input).clone())),
+            )
+            .await;
+            for ((_, _, _, slot), result) in pending[group].iter_mut().zip(results) {
+                **slot = Some(result);
+            }
+        }
+    }
+
+    /// Execute a tool, holding one of the coordinator's tool slots if there
+    /// is a coordinator, and returning an error result if the run is
+    /// cancelled first.
+    async fn execute_tool(
+        &self,
+        id: &str,
+        name: &str,
+        input: serde_json::Value,
+    ) -> crate::tool::ToolResult {
+        let run = async {
+            let _slot = match &self.coordinator {
+                Some(coordinator) => Some(coordinator.acquire_tool_slot().await),
+                None => None,
+            };
+            self.execute_tool_uncancelled(id, name, input).await
+        };
+        tokio::select! {
+            result = run => result,
+            _ = self.cancel_token.cancelled() => {
+                crate::tool::ToolResult::error(format!("Tool '{}' was cancelled", name))
+            }
+        }
     }
 
     /// Execute a tool and return the result.
     ///
-    /// If the tool requires approval and an approval handler is set,
-    /// this will request approval before executing. If denied, returns
-    /// an error result without executing the tool.
-    async fn execute_tool(&self, name: &str, input: serde_json::Value) -> crate::tool::ToolResult {
+    /// The permission policy, if set, is checked first. If the tool requires
+    /// approval and an approval handler is set, this will request approval
+    /// before executing. If denied, returns an error result without
+    /// executing the tool. Its progress is passed on as `ToolProgress` hook
+    /// events.
+    async fn execute_tool_uncancelled(
+        &self,
+        id: &str,
+        name: &str,
+        input: serde_json::Value,
+    ) -> crate::tool::ToolResult {
         match self.tools.get(name).await {
             Some(tool) => {
+                let policy_decision = match &self.policy {
+                    Some(policy) => match policy.evaluate(name, &input) {
+                        Ok(decision) => decision,
+                        Err(e) => return crate::tool::ToolResult::error(e.to_string()),
+                    },
+                    None => Decision::Allow,
+                };
+                if policy_decision == Decision::Deny {
+                    return crate::tool::ToolResult::error(
+                        PermissionError::Denied(name.to_string()).to_string(),
+                    );
+                }
+
                 // Check if tool requires approval
-                if tool.requires_approval(&input) {
+                if policy_decision == Decision::Ask || tool.requires_approval(&input) {
                     if let Some(handler) = &self.approval_handler {
                         let context = ApprovalContext {
                             tool_description: tool.description().to_string(),
@@ -450,11 +1374,53 @@ impl SubAgent {
                     }
                 }
 
-                // Execute the tool
-                match tool.execute(input).await {
-                    Ok(r) => r,
-                    Err(e) => crate::tool::ToolResult::error(e.to_string()),
+                // Only calls cleared to run count against rate limits
+                if let Some(policy) = &self.policy
+                    && let Err(e) = policy.record(name)
+                {
+                    return crate::tool::ToolResult::error(e.to_string());
                 }
+
+                // Execute the tool, forwarding its progress until the done event
+                let (progress, mut updates) = ProgressReporter::channel();
+                let run = async move {
+                    let result = match &self.tool_cache {
+                        Some(cache) => {
+                            cache
+                                .execute_tool_with_progress(&*tool, input, &progress)
+                                .await
+                        }
+                        None => {
+                            self.tools
+                                .execute_tool_with_progress(&*tool, input, &progress)
+                                .await
+                        }
+                    };
+                    let result = match result {
+                        Ok(r) => r,
+                        Err(e) => crate::tool::ToolResult::error(e.to_string()),
+                    };
+                    progress.complete(result.clone());
+                    result
+                };
+                let forward = async {
+                    while let Some(update) = updates.recv().await {
+                        let done = update.done;
+                        // Progress is informational; a failing hook doesn't fail the call
+                        let _ = self
+                            .fire_hook(HookEvent::ToolProgress {
+                                agent_id: self.agent_id.clone(),
+                                tool_use_id: id.to_string(),
+                                tool_name: name.to_string(),
+                                progress: update,
+                            })
+                            .await;
+                        if done {
+                            break;
+                        }
+                    }
+                };
+                tokio::join!(run, forward).0
             }
             None => {
                 crate::tool::ToolResult::error(format!("Tool '{}' not found or not allowed", name))
@@ -463,12 +1429,63 @@ impl SubAgent {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The parse error for each tool call in `response` whose arguments
+/// weren't valid JSON, keyed by tool use id.
+fn invalid_tool_inputs(response: &Response) -> HashMap<String, String> {
+    response
+        .invalid_tool_inputs
+        .iter()
+        .filter_map(|invalid| match response.content.get(invalid.index) {
+            Some(ContentBlock::ToolUse { id, .. }) => Some((id.clone(), invalid.error.clone())),
+            _ => None,
+        })
+        .collect()
+}
 
-    #[test]
-    fn test_subagent_result() {
+/// Ids of every tool call in `messages`.
+fn tool_use_ids(messages: &[Message]) -> HashSet<String> {
+    messages
+        .iter()
+        .flat_map(|m| &m.content)
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, .. } => Some(id.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Give every tool call in `content` a non-empty id that is unique within the
+/// conversation, so each tool result pairs with exactly one call.
+///
+/// Some OpenAI-compatible servers omit ids or reuse them. Those calls get
+/// `call_<iteration>_<position>`, where position counts tool calls in the response.
+fn assign_tool_use_ids(content: &mut [ContentBlock], iteration: usize, seen: &mut HashSet<String>) {
+    let tool_ids = content.iter_mut().filter_map(|block| match block {
+        ContentBlock::ToolUse { id, .. } => Some(id),
+        _ => None,
+    });
+
+    for (position, id) in tool_ids.enumerate() {
+        if id.is_empty() || seen.contains(id.as_str()) {
+            let base = format!("call_{}_{}", iteration, position);
+            let mut candidate = base.clone();
+            let mut suffix = 1;
+            while seen.contains(&candidate) {
+                candidate = format!("{}_{}", base, suffix);
+                suffix += 1;
+            }
+            *id = candidate;
+        }
+        seen.insert(id.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subagent_result() {
         let result = SubAgentResult {
             agent_id: "test-123".into(),
             content: "Done".into(),
@@ -479,7 +1496,10 @@ mod tests {
                 cache_read_tokens: 20,
                 cache_write_tokens: 10,
             },
+            usage_total: UsageTracker::new(),
             iterations: 2,
+            stop_reason: AgentStopReason::Completed,
+            files_changed: Vec::new(),
         };
 
         assert_eq!(result.agent_id, "test-123");
@@ -487,5 +1507,2205 @@ mod tests {
         assert_eq!(result.iterations, 2);
         assert_eq!(result.usage.cache_read_tokens, 20);
         assert_eq!(result.usage.cache_write_tokens, 10);
+        assert!(result.stop_reason.is_complete());
+    }
+
+    /// Client that requests a tool call until `tool_turns` responses have been sent.
+    struct ScriptedClient {
+        tool_turns: usize,
+        tool_name: &'static str,
+        input: serde_json::Value,
+        /// Raw tool arguments to send instead of `input`.
+        arguments: Option<&'static str>,
+        images: bool,
+        calls: std::sync::atomic::AtomicUsize,
+        requests: std::sync::Mutex<Vec<Request>>,
+    }
+
+    impl ScriptedClient {
+        fn new(tool_turns: usize) -> Self {
+            Self {
+                tool_turns,
+                tool_name: "missing_tool",
+                input: serde_json::json!({}),
+                arguments: None,
+                images: false,
+                calls: std::sync::atomic::AtomicUsize::new(0),
+                requests: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        fn accepting_images(mut self) -> Self {
+            self.images = true;
+            self
+        }
+
+        fn calling(mut self, tool_name: &'static str) -> Self {
+            self.tool_name = tool_name;
+            self
+        }
+
+        fn with_input(mut self, input: serde_json::Value) -> Self {
+            self.input = input;
+            self
+        }
+
+        fn with_arguments(mut self, arguments: &'static str) -> Self {
+            self.arguments = Some(arguments);
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for ScriptedClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            self.requests.lock().unwrap().push(req.clone());
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let mut invalid_tool_inputs = Vec::new();
+            let (content, stop_reason) = if call < self.tool_turns {
+                let input = match self.arguments {
+                    Some(arguments) => {
+                        let (input, invalid) = crate::llm::InvalidToolInput::check(1, arguments);
+                        invalid_tool_inputs.extend(invalid);
+                        input
+                    }
+                    None => self.input.clone(),
+                };
+                (
+                    vec![
+                        ContentBlock::text(format!("Working on step {}", call + 1)),
+                        ContentBlock::ToolUse {
+                            id: format!("tool_{}", call),
+                            name: self.tool_name.into(),
+                            input,
+                        },
+                    ],
+                    crate::llm::StopReason::ToolUse,
+                )
+            } else {
+                (
+                    vec![ContentBlock::text("All done")],
+                    crate::llm::StopReason::EndTurn,
+                )
+            };
+
+            Ok(Response {
+                id: format!("msg_{}", call),
+                content,
+                stop_reason,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    ..Default::default()
+                },
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs,
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+
+        fn supports_tool_result_images(&self) -> bool {
+            self.images
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_completes_normally() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(5);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(1)),
+            Registry::new(),
+        );
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(result.content, "All done");
+        assert_eq!(result.iterations, 2);
+        assert_eq!(result.tool_use_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_thinking_is_kept_but_not_the_answer() {
+        use crate::llm::MockClient;
+
+        let reply = |content: Vec<ContentBlock>, stop_reason| Response {
+            id: String::new(),
+            content,
+            stop_reason,
+            model: String::new(),
+            served_model: None,
+            system_fingerprint: None,
+            usage: Usage::default(),
+            attempts: 1,
+            citations: Vec::new(),
+            invalid_tool_inputs: Vec::new(),
+        };
+
+        for streaming in [false, true] {
+            let client = MockClient::new()
+                .with_response(reply(
+                    vec![
+                        ContentBlock::thinking("Look around first.", "sig_1"),
+                        ContentBlock::ToolUse {
+                            id: "call_0".into(),
+                            name: "list_files".into(),
+                            input: serde_json::json!({}),
+                        },
+                    ],
+                    crate::llm::StopReason::ToolUse,
+                ))
+                .with_response(reply(
+                    vec![
+                        ContentBlock::thinking("Nothing there.", "sig_2"),
+                        ContentBlock::text("The directory is empty."),
+                    ],
+                    crate::llm::StopReason::EndTurn,
+                ));
+            let definition = AgentDefinition::new("worker", "You work.")
+                .model("test-model")
+                .streaming(streaming);
+            let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new());
+
+            let result = agent.run("What's here?").await.unwrap();
+
+            assert_eq!(result.content, "The directory is empty.");
+            // The reasoning stays in the transcript so it's sent back
+            assert!(matches!(
+                &agent.transcript()[1].content[..],
+                [ContentBlock::Thinking { text, signature }, ContentBlock::ToolUse { .. }]
+                    if text == "Look around first." && signature == "sig_1"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exported_transcript_resumes() {
+        use crate::agent::Transcript;
+        use crate::llm::MockClient;
+
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(MockClient::new().with_text("First answer")),
+            Registry::new(),
+        );
+        agent.run("first task").await.unwrap();
+        let json = agent.export_transcript().to_json().unwrap();
+
+        // The definition in the resuming process doesn't name a model
+        let client = Arc::new(
+            MockClient::new()
+                .with_text("Second answer")
+                .expecting(|req| {
+                    assert_eq!(req.model, "test-model");
+                    assert!(matches!(
+                        &req.messages[0].content[0],
+                        ContentBlock::Text { text } if text == "first task"
+                    ));
+                }),
+        );
+        let mut resumed = SubAgent::resume_transcript(
+            Transcript::from_json(&json).unwrap(),
+            AgentDefinition::new("worker", "You work."),
+            client.clone(),
+            Registry::new(),
+        );
+
+        assert_eq!(resumed.agent_id(), agent.agent_id());
+        resumed.run("second task").await.unwrap();
+        assert_eq!(client.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_resumed_transcript_keeps_its_usage() {
+        use crate::agent::Transcript;
+        use crate::llm::MockClient;
+
+        let usage = Usage {
+            input_tokens: 120,
+            output_tokens: 30,
+            ..Usage::default()
+        };
+        let transcript = Transcript {
+            model: Some("test-model".into()),
+            usage: usage.clone(),
+            ..Transcript::new("agent-1", Vec::new())
+        };
+
+        let resumed = SubAgent::resume_transcript(
+            transcript,
+            AgentDefinition::new("worker", "You work."),
+            Arc::new(MockClient::new()),
+            Registry::new(),
+        );
+
+        assert_eq!(resumed.usage(), &usage);
+        assert_eq!(resumed.usage_total().by_model()["test-model"], usage);
+    }
+
+    #[tokio::test]
+    async fn test_run_tracks_usage_per_model() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(5);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(2)),
+            Registry::new(),
+        );
+
+        let result = agent.run("do it").await.unwrap();
+
+        let per_model = result.usage_total.by_model();
+        assert_eq!(per_model.len(), 1);
+        assert_eq!(per_model["test-model"].input_tokens, 30);
+        assert_eq!(per_model["test-model"].output_tokens, 15);
+        assert_eq!(result.usage_total.total(), result.usage);
+
+        let mut prices = crate::llm::PriceTable::new();
+        prices.insert("test-model".into(), (1.0, 2.0));
+        let cost = result.usage_total.estimated_cost(&prices).unwrap();
+        assert!((cost - 0.00006).abs() < 1e-12);
+    }
+
+    /// Records the stop reason of each AgentStop event.
+    struct AgentStopLog(Arc<std::sync::Mutex<Vec<AgentStopReason>>>);
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for AgentStopLog {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            if let HookEvent::AgentStop { result, .. } = event {
+                self.0.lock().unwrap().push(result.stop_reason);
+            }
+            Ok(HookAction::Continue)
+        }
+    }
+
+    /// Records the payload of each SessionStart and SessionEnd event.
+    struct SessionLog(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for SessionLog {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            let entry = match event {
+                HookEvent::SessionStart {
+                    session_id,
+                    source,
+                    prompt,
+                } => format!("start {} {} {}", session_id, source, prompt),
+                HookEvent::SessionEnd {
+                    session_id,
+                    error,
+                    reason,
+                } => format!("end {} {} {:?}", session_id, reason, error),
+                _ => return Ok(HookAction::Continue),
+            };
+            self.0.lock().unwrap().push(entry);
+            Ok(HookAction::Continue)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_hits_max_iterations() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(3);
+        let stops = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sessions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks.register(AgentStopLog(stops.clone())).await;
+        hooks.register(SessionLog(sessions.clone())).await;
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(usize::MAX)),
+            Registry::new(),
+        )
+        .with_hooks(hooks);
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::MaxIterations);
+        assert!(!result.stop_reason.is_complete());
+        assert!(result.hit_iteration_limit());
+        assert_eq!(*stops.lock().unwrap(), [AgentStopReason::MaxIterations]);
+        let id = agent.agent_id();
+        assert_eq!(
+            *sessions.lock().unwrap(),
+            [
+                format!("start {} run do it", id),
+                format!("end {} iteration_limit None", id),
+            ]
+        );
+        assert_eq!(result.iterations, 3);
+        assert_eq!(result.tool_use_count, 3);
+        assert_eq!(result.content, "Working on step 3");
+        assert_eq!(result.usage.input_tokens, 30);
+    }
+
+    #[tokio::test]
+    async fn test_session_end_reports_run_error() {
+        use crate::llm::MockClient;
+
+        let sessions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks.register(SessionLog(sessions.clone())).await;
+        let client = MockClient::new().with_error(LlmError::Api {
+            status: 500,
+            message: "boom".into(),
+        });
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, Arc::new(client), Registry::new()).with_hooks(hooks);
+
+        let err = agent.run("do it").await.unwrap_err();
+
+        let id = agent.agent_id();
+        assert_eq!(
+            *sessions.lock().unwrap(),
+            [
+                format!("start {} run do it", id),
+                format!("end {} error Some({:?})", id, err.to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_aborts_on_repeated_tool_error() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(10)
+            .on_repeated_tool_error(ToolErrorPolicy::Abort { after: 3 });
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(usize::MAX)),
+            Registry::new(),
+        );
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Error);
+        assert_eq!(result.iterations, 3);
+        assert_eq!(result.tool_use_count, 3);
+        assert!(result.content.contains("'missing_tool' failed 3 times"));
+        // The failing results stay in the transcript so the run can be inspected
+        assert!(matches!(
+            agent.transcript().last().unwrap().content[0],
+            ContentBlock::ToolResult { is_error: true, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tool_errors_continue_by_default() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(5);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(usize::MAX)),
+            Registry::new(),
+        );
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::MaxIterations);
+        assert_eq!(result.iterations, 5);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_response_is_not_a_completed_turn() {
+        use crate::llm::MockClient;
+
+        for stop_reason in [StopReason::ContentFilter, StopReason::Refusal] {
+            let client = MockClient::new().with_response(Response {
+                id: String::new(),
+                content: vec![ContentBlock::text("I can't help with that.")],
+                stop_reason,
+                model: String::new(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            });
+            let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+            let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new());
+
+            let result = agent.run("do it").await.unwrap();
+
+            assert_eq!(result.stop_reason, AgentStopReason::Refused);
+            assert!(!result.stop_reason.is_complete());
+            assert_eq!(result.content, "I can't help with that.");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_tool_input_is_reported_to_the_model() {
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let client = ScriptedClient::new(1).with_arguments(r#"{"path": "#);
+        let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new());
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(result.tool_use_count, 0);
+        let transcript = agent.transcript();
+        // The bad call is stored with an empty input so the history stays valid
+        assert!(matches!(
+            &transcript[1].content[1],
+            ContentBlock::ToolUse { input, .. } if *input == serde_json::json!({})
+        ));
+        let ContentBlock::ToolResult {
+            content, is_error, ..
+        } = &transcript[2].content[0]
+        else {
+            panic!("expected a tool result");
+        };
+        assert!(is_error);
+        assert!(content.starts_with("invalid JSON arguments: EOF while parsing"));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_malformed_tool_input_aborts() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(10);
+        let client = ScriptedClient::new(usize::MAX).with_arguments("not json");
+        let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new());
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Error);
+        assert_eq!(result.iterations, MAX_INVALID_TOOL_INPUT_RETRIES + 1);
+        assert!(result.content.contains("invalid JSON tool arguments"));
+    }
+
+    struct SendEmailTool;
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for SendEmailTool {
+        fn name(&self) -> &str {
+            "send_email"
+        }
+
+        fn description(&self) -> &str {
+            "Sends an email"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+        ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+            Ok(crate::tool::ToolResult::text("sent"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_tool_is_denied_after_limit() {
+        let registry = Registry::new();
+        registry.register(SendEmailTool).await;
+        let policy = Policy::builder()
+            .rate_limit("send_email", 3)
+            .default(Decision::Allow)
+            .build();
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(10);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(5).calling("send_email")),
+            registry,
+        )
+        .with_policy(Arc::new(policy));
+
+        let result = agent.run("email everyone").await.unwrap();
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+
+        let results: Vec<(bool, String)> = agent
+            .transcript()
+            .iter()
+            .flat_map(|m| &m.content)
+            .filter_map(|b| match b {
+                ContentBlock::ToolResult {
+                    content, is_error, ..
+                } => Some((*is_error, content.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 5);
+        assert!(
+            results[..3]
+                .iter()
+                .all(|(err, text)| !err && text == "sent")
+        );
+        for (is_error, text) in &results[3..] {
+            assert!(is_error);
+            assert!(text.contains("limit of 3 calls per session"));
+        }
+    }
+
+    struct ReadFileTool;
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for ReadFileTool {
+        fn name(&self) -> &str {
+            "read_file"
+        }
+
+        fn description(&self) -> &str {
+            "Reads a file"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+        ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+            Ok(crate::tool::ToolResult::text("# Project"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_results_truncated_to_limit() {
+        use crate::llm::MockClient;
+        use crate::tool::ToolResultLimits;
+
+        let registry = Registry::new();
+        registry.register(ReadFileTool).await;
+        let client = MockClient::new()
+            .with_tool_use("read_file", serde_json::json!({"path": "README.md"}))
+            .with_text("Done")
+            .expecting(|req| {
+                let last = req.messages.last().unwrap();
+                assert!(matches!(
+                    &last.content[0],
+                    ContentBlock::ToolResult { content, .. }
+                        if content == "# P\n[truncated 6 of 9 bytes]"
+                ));
+            });
+        let definition = AgentDefinition::new("reader", "You read.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry)
+            .with_tool_result_limits(
+                ToolResultLimits::new()
+                    .max_bytes(1000)
+                    .for_tool("read_file", 3),
+            );
+
+        let result = agent.run("Read the README").await.unwrap();
+        assert_eq!(result.content, "Done");
+    }
+
+    /// An agent whose model reads the same file on each of its first 8 turns.
+    async fn rereading_agent(policy: RepeatedCallPolicy) -> SubAgent {
+        let registry = Registry::new();
+        registry.register(ReadFileTool).await;
+        let definition = AgentDefinition::new("reader", "You read.")
+            .model("test-model")
+            .max_iterations(10)
+            .on_repeated_tool_call(policy);
+        let client = ScriptedClient::new(8)
+            .calling("read_file")
+            .with_input(serde_json::json!({"path": "README.md", "limit": 100}));
+        SubAgent::new(definition, Arc::new(client), registry)
+    }
+
+    #[tokio::test]
+    async fn test_repeated_call_nudges_model() {
+        let mut agent = rereading_agent(RepeatedCallPolicy::Nudge { after: 2 }).await;
+        let result = agent.run("Summarize the project").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(result.tool_use_count, 2);
+
+        let results: Vec<(bool, String)> = agent
+            .transcript()
+            .iter()
+            .flat_map(|m| &m.content)
+            .filter_map(|b| match b {
+                ContentBlock::ToolResult {
+                    content, is_error, ..
+                } => Some((*is_error, content.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 8);
+        assert_eq!(results[1], (false, "# Project".to_string()));
+        let (is_error, nudge) = &results[2];
+        assert!(is_error);
+        assert!(nudge.contains("already called read_file with these arguments 2 times"));
+        assert!(nudge.contains("# Project"));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_call_aborts_before_max_iterations() {
+        let mut agent = rereading_agent(RepeatedCallPolicy::Abort { after: 3 }).await;
+        let result = agent.run("Summarize the project").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Error);
+        assert_eq!(result.iterations, 4);
+        assert_eq!(result.tool_use_count, 3);
+        assert!(
+            result
+                .content
+                .contains("called 'read_file' with the same arguments 4 times")
+        );
+
+        // Without a policy the model rereads the file until it stops on its own
+        let mut agent = rereading_agent(RepeatedCallPolicy::Allow).await;
+        let result = agent.run("Summarize the project").await.unwrap();
+        assert_eq!(result.tool_use_count, 8);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_call_limit_of_zero_still_runs_the_first_call() {
+        for policy in [
+            RepeatedCallPolicy::Nudge { after: 0 },
+            RepeatedCallPolicy::Abort { after: 0 },
+        ] {
+            let mut agent = rereading_agent(policy).await;
+            let result = agent.run("Summarize the project").await.unwrap();
+            assert_eq!(result.tool_use_count, 1, "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_call_ignores_argument_key_order() {
+        use crate::llm::MockClient;
+
+        let registry = Registry::new();
+        registry.register(ReadFileTool).await;
+        let definition = AgentDefinition::new("reader", "You read.")
+            .model("test-model")
+            .on_repeated_tool_call(RepeatedCallPolicy::Nudge { after: 1 });
+        let mut first = serde_json::Map::new();
+        first.insert("path".into(), "README.md".into());
+        first.insert("limit".into(), 100.into());
+        let mut second = serde_json::Map::new();
+        second.insert("limit".into(), 100.into());
+        second.insert("path".into(), "README.md".into());
+        let client = MockClient::new()
+            .with_tool_use("read_file", first.into())
+            .with_tool_use("read_file", second.into())
+            .with_text("Done");
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry);
+
+        let result = agent.run("Summarize the project").await.unwrap();
+
+        assert_eq!(result.tool_use_count, 1);
+    }
+
+    struct ScreenshotTool;
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for ScreenshotTool {
+        fn name(&self) -> &str {
+            "screenshot"
+        }
+
+        fn description(&self) -> &str {
+            "Takes a screenshot"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+        ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+            Ok(crate::tool::ToolResult::text("Captured the screen")
+                .with_image(crate::llm::ImageSource::base64("image/png", "iVBOR")))
+        }
+    }
+
+    async fn screenshot_result(client: ScriptedClient) -> ContentBlock {
+        let registry = Registry::new();
+        registry.register(ScreenshotTool).await;
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(client.calling("screenshot")), registry);
+        agent.run("look at the screen").await.unwrap();
+
+        agent
+            .transcript()
+            .iter()
+            .flat_map(|m| &m.content)
+            .find(|b| matches!(b, ContentBlock::ToolResult { .. }))
+            .cloned()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tool_result_images_follow_client_support() {
+        let ContentBlock::ToolResult {
+            content, images, ..
+        } = screenshot_result(ScriptedClient::new(1).accepting_images()).await
+        else {
+            unreachable!()
+        };
+        assert_eq!(content, "Captured the screen");
+        assert_eq!(
+            images,
+            vec![crate::llm::ImageSource::base64("image/png", "iVBOR")]
+        );
+
+        // Text-only providers get a placeholder instead
+        let ContentBlock::ToolResult {
+            content, images, ..
+        } = screenshot_result(ScriptedClient::new(1)).await
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            content,
+            "Captured the screen\n[Image: 5 bytes, type: image/png]"
+        );
+        assert!(images.is_empty());
+    }
+
+    /// Post-processor that masks the word "step" and "done" in text blocks.
+    struct Redactor;
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for Redactor {
+        fn accepts(&self, event: &HookEvent) -> bool {
+            matches!(event, HookEvent::PostResponse { .. })
+        }
+
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            let HookEvent::PostResponse { content, .. } = event else {
+                return Ok(HookAction::Continue);
+            };
+            let redacted: Vec<ContentBlock> = content
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => {
+                        ContentBlock::text(text.replace("step", "***").replace("done", "***"))
+                    }
+                    other => other.clone(),
+                })
+                .collect();
+            Ok(HookAction::Transform(serde_json::to_value(redacted)?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_response_hook_redacts_result_and_history() {
+        let hooks = Arc::new(HookRegistry::new());
+        hooks.register(Redactor).await;
+
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(1)),
+            Registry::new(),
+        )
+        .with_hooks(hooks);
+
+        let result = agent.run("Do the thing").await.unwrap();
+        assert_eq!(result.content, "All ***");
+        assert_eq!(result.tool_use_count, 1);
+
+        // The stored tool-use turn is redacted, and its tool call still ran
+        let assistant = &agent.transcript()[1];
+        assert_eq!(assistant.role, Role::Assistant);
+        assert!(matches!(
+            &assistant.content[..],
+            [ContentBlock::Text { text }, ContentBlock::ToolUse { name, .. }]
+                if text == "Working on *** 1" && name == "missing_tool"
+        ));
+    }
+
+    /// Records LLM calls, blocking those past the first `allow`.
+    struct LlmAudit {
+        allow: usize,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for LlmAudit {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            let mut log = self.log.lock().unwrap();
+            match event {
+                HookEvent::LlmRequest {
+                    model,
+                    request_summary,
+                    ..
+                } => {
+                    let requests = log.iter().filter(|e| e.starts_with("request")).count();
+                    log.push(format!(
+                        "request {} with {} messages",
+                        model, request_summary.messages
+                    ));
+                    if requests == self.allow {
+                        return Ok(HookAction::Block("over budget".into()));
+                    }
+                }
+                HookEvent::LlmResponse {
+                    usage, stop_reason, ..
+                } => log.push(format!(
+                    "response {:?} {}+{}",
+                    stop_reason, usage.input_tokens, usage.output_tokens
+                )),
+                _ => {}
+            }
+            Ok(HookAction::Continue)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_exchange_hooks_audit_and_block() {
+        use crate::llm::MockClient;
+
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks
+            .register(LlmAudit {
+                allow: 1,
+                log: log.clone(),
+            })
+            .await;
+        let client = MockClient::new()
+            .with_tool_use("missing_tool", serde_json::json!({}))
+            .with_text("never sent");
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, Arc::new(client), Registry::new()).with_hooks(hooks);
+
+        let err = agent.run("Do the thing").await.unwrap_err();
+
+        assert!(matches!(&err, LlmError::Blocked(reason) if reason == "over budget"));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "request test-model with 1 messages",
+                "response ToolUse 0+0",
+                "request test-model with 3 messages",
+            ]
+        );
+    }
+
+    /// Rewrites the outgoing request with `edit`.
+    struct RequestEditor(fn(&mut Request));
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for RequestEditor {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            let HookEvent::LlmRequest { request, .. } = event else {
+                return Ok(HookAction::Continue);
+            };
+            let mut request = request.clone();
+            (self.0)(&mut request);
+            Ok(HookAction::Transform(serde_json::to_value(request)?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_request_transform_replaces_request() {
+        use crate::llm::MockClient;
+
+        let hooks = Arc::new(HookRegistry::new());
+        hooks
+            .register(RequestEditor(|req| req.max_tokens = Some(256)))
+            .await;
+        hooks
+            .register(RequestEditor(|req| {
+                let system = req.system.take().unwrap_or_default();
+                req.system = Some(format!("{} Answer in French.", system));
+            }))
+            .await;
+        let client = Arc::new(MockClient::new().with_text("Bonjour"));
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, client.clone(), Registry::new()).with_hooks(hooks);
+
+        agent.run("Say hello").await.unwrap();
+
+        // Each hook sees the previous one's request, so both edits apply
+        let sent = client.requests().remove(0);
+        assert_eq!(sent.max_tokens, Some(256));
+        assert_eq!(sent.system.as_deref(), Some("You work. Answer in French."));
+        assert_eq!(sent.messages.len(), 1);
+    }
+
+    /// Reports two steps before finishing.
+    struct SteppingTool;
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for SteppingTool {
+        fn name(&self) -> &str {
+            "step"
+        }
+
+        fn description(&self) -> &str {
+            "Works in two steps"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            Ok(ToolResult::text("stepped"))
+        }
+
+        async fn execute_with_progress(
+            &self,
+            _params: serde_json::Value,
+            progress: &ProgressReporter,
+        ) -> Result<ToolResult, anyhow::Error> {
+            progress.report_fraction("step 1", 0.5);
+            progress.report_fraction("step 2", 1.0);
+            Ok(ToolResult::text("stepped"))
+        }
+    }
+
+    /// Records ToolProgress events as `id name message`.
+    struct ProgressLog(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for ProgressLog {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            if let HookEvent::ToolProgress {
+                tool_use_id,
+                tool_name,
+                progress,
+                ..
+            } = event
+            {
+                let mut line = format!("{} {} {}", tool_use_id, tool_name, progress.message);
+                if let Some(result) = &progress.result {
+                    line.push_str(&format!(": {}", result.content));
+                }
+                self.0.lock().unwrap().push(line);
+            }
+            Ok(HookAction::Continue)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_progress_fires_hook_events() {
+        use crate::llm::MockClient;
+
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks.register(ProgressLog(log.clone())).await;
+        let registry = Registry::new();
+        registry.register(SteppingTool).await;
+        let client = MockClient::new()
+            .with_tool_use("step", serde_json::json!({}))
+            .with_tool_use("missing_tool", serde_json::json!({}))
+            .with_text("Finished");
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry).with_hooks(hooks);
+
+        let result = agent.run("Do the thing").await.unwrap();
+
+        assert_eq!(result.content, "Finished");
+        // A tool that isn't run reports nothing
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "call_0 step step 1",
+                "call_0 step step 2",
+                "call_0 step done: stepped",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_context_tokens_uses_last_response_usage() {
+        use crate::llm::MockClient;
+
+        let reply = Response {
+            id: String::new(),
+            content: vec![ContentBlock::text("Done")],
+            stop_reason: StopReason::EndTurn,
+            model: String::new(),
+            served_model: None,
+            system_fingerprint: None,
+            usage: Usage {
+                input_tokens: 150,
+                output_tokens: 10,
+                cache_read_tokens: 50,
+                cache_write_tokens: 0,
+            },
+            attempts: 1,
+            citations: Vec::new(),
+            invalid_tool_inputs: Vec::new(),
+        };
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::resume(
+            "agent-1".into(),
+            definition,
+            Arc::new(MockClient::new().with_response(reply)),
+            Registry::new(),
+            vec![Message::user("twelve bytes")],
+        );
+
+        // Before any response the transcript is estimated
+        assert_eq!(agent.context_tokens(), 3);
+
+        agent.run("Go").await.unwrap();
+        assert_eq!(agent.context_tokens(), 210);
+
+        // Messages added after the response are estimated on top
+        agent.fork_messages(
+            agent
+                .transcript()
+                .iter()
+                .cloned()
+                .chain([Message::user("twelve bytes")])
+                .collect(),
+        );
+        assert_eq!(agent.context_tokens(), 213);
+    }
+
+    #[tokio::test]
+    async fn test_llm_request_transform_that_is_not_a_request_is_invalid() {
+        use crate::llm::MockClient;
+
+        struct BadTransform;
+
+        #[async_trait::async_trait]
+        impl crate::hook::Hook for BadTransform {
+            async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+                Ok(match event {
+                    HookEvent::LlmRequest { .. } => {
+                        HookAction::Transform(serde_json::json!({"max_tokens": "lots"}))
+                    }
+                    _ => HookAction::Continue,
+                })
+            }
+        }
+
+        let hooks = Arc::new(HookRegistry::new());
+        hooks.register(BadTransform).await;
+        let client = Arc::new(MockClient::new().with_text("never sent"));
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, client.clone(), Registry::new()).with_hooks(hooks);
+
+        let err = agent.run("Do the thing").await.unwrap_err();
+
+        assert!(matches!(err, LlmError::InvalidRequest(msg) if msg.contains("not a request")));
+        assert!(client.requests().is_empty());
+    }
+
+    /// Sleeps for `ms` milliseconds and echoes `tag`, tracking how many
+    /// calls overlap.
+    struct SleepTool {
+        parallel_safe: bool,
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for SleepTool {
+        fn name(&self) -> &str {
+            "sleep"
+        }
+
+        fn description(&self) -> &str {
+            "Sleeps"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn parallel_safe(&self) -> bool {
+            self.parallel_safe
+        }
+
+        async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            use std::sync::atomic::Ordering;
+
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            let ms = params["ms"].as_u64().unwrap_or(0);
+            tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(ToolResult::text(params["tag"].as_str().unwrap_or_default()))
+        }
+    }
+
+    /// Runs one turn of three `sleep` calls, returning the peak number of
+    /// overlapping calls and the results in transcript order.
+    async fn run_three_sleeps(
+        parallel_safe: bool,
+        coordinator: Option<Arc<crate::coordinator::Coordinator>>,
+    ) -> (usize, Vec<String>) {
+        use crate::llm::{MockClient, StopReason};
+
+        let calls = [("a", 40), ("b", 10), ("c", 25)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (tag, ms))| ContentBlock::ToolUse {
+                id: format!("call_{}", i),
+                name: "sleep".into(),
+                input: serde_json::json!({"ms": ms, "tag": tag}),
+            })
+            .collect();
+        let client = MockClient::new()
+            .with_response(Response {
+                id: String::new(),
+                content: calls,
+                stop_reason: StopReason::ToolUse,
+                model: String::new(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+            .with_text("Slept");
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let registry = Registry::new();
+        registry
+            .register(SleepTool {
+                parallel_safe,
+                in_flight: Default::default(),
+                peak: peak.clone(),
+            })
+            .await;
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry);
+        if let Some(coordinator) = coordinator {
+            agent = agent.with_coordinator(coordinator);
+        }
+
+        agent.run("Sleep three times").await.unwrap();
+
+        let results = agent.transcript()[2]
+            .content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => format!("{}={}", tool_use_id, content),
+                other => panic!("unexpected block {:?}", other),
+            })
+            .collect();
+        (peak.load(std::sync::atomic::Ordering::SeqCst), results)
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_in_a_turn_run_concurrently_in_order() {
+        let in_order = ["call_0=a", "call_1=b", "call_2=c"];
+
+        let (peak, results) = run_three_sleeps(true, None).await;
+        assert_eq!(peak, 3);
+        assert_eq!(results, in_order);
+
+        let (peak, results) = run_three_sleeps(false, None).await;
+        assert_eq!(peak, 1);
+        assert_eq!(results, in_order);
+
+        let coordinator =
+            Arc::new(crate::coordinator::Coordinator::new().with_max_concurrent_tools(2));
+        let (peak, results) = run_three_sleeps(true, Some(coordinator)).await;
+        assert_eq!(peak, 2);
+        assert_eq!(results, in_order);
+    }
+
+    /// Client that never answers.
+    struct HangingClient;
+
+    #[async_trait::async_trait]
+    impl LlmClient for HangingClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            std::future::pending().await
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send>>
+        {
+            Box::pin(futures::stream::pending())
+        }
+    }
+
+    /// Client that asks for two slow tool calls in one turn.
+    struct SlowToolClient;
+
+    #[async_trait::async_trait]
+    impl LlmClient for SlowToolClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            let call = |id: &str| ContentBlock::ToolUse {
+                id: id.into(),
+                name: "slow_tool".into(),
+                input: serde_json::json!({}),
+            };
+            Ok(Response {
+                id: "msg_0".into(),
+                content: vec![call("first"), call("second")],
+                stop_reason: crate::llm::StopReason::ToolUse,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    struct SlowTool;
+
+    #[async_trait::async_trait]
+    impl crate::tool::Tool for SlowTool {
+        fn name(&self) -> &str {
+            "slow_tool"
+        }
+
+        fn description(&self) -> &str {
+            "Takes a long time"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+        ) -> Result<crate::tool::ToolResult, anyhow::Error> {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            Ok(crate::tool::ToolResult::text("finished"))
+        }
+    }
+
+    struct StopRecorder {
+        stops: Arc<std::sync::Mutex<Vec<AgentStopReason>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for StopRecorder {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            if let HookEvent::AgentStop { result, .. } = event {
+                self.stops.lock().unwrap().push(result.stop_reason);
+            }
+            Ok(HookAction::Continue)
+        }
+    }
+
+    fn cancel_after(token: CancellationToken, millis: u64) {
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+            token.cancel();
+        });
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_llm_call() {
+        let stops = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks
+            .register(StopRecorder {
+                stops: stops.clone(),
+            })
+            .await;
+
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, Arc::new(HangingClient), Registry::new()).with_hooks(hooks);
+        cancel_after(agent.cancellation_token(), 20);
+
+        let result = agent.run("wait forever").await.unwrap();
+        assert_eq!(result.stop_reason, AgentStopReason::Cancelled);
+        assert!(!result.stop_reason.is_complete());
+        assert_eq!(*stops.lock().unwrap(), vec![AgentStopReason::Cancelled]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_tool_call_answers_every_call() {
+        let registry = Registry::new();
+        registry.register(SlowTool).await;
+        let token = CancellationToken::new();
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(SlowToolClient), registry)
+            .with_agent_id("agent-1")
+            .with_cancellation(token.clone());
+        cancel_after(token, 20);
+
+        let result = agent.run("do slow things").await.unwrap();
+        assert_eq!(result.agent_id, "agent-1");
+        assert_eq!(result.stop_reason, AgentStopReason::Cancelled);
+        assert_eq!(result.iterations, 1);
+        assert_eq!(result.tool_use_count, 2);
+
+        // Both calls were running concurrently when the run was cancelled
+        let results: Vec<(&str, &str, bool)> = agent
+            .transcript()
+            .last()
+            .unwrap()
+            .content
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                    ..
+                } => Some((tool_use_id.as_str(), content.as_str(), *is_error)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("first", "Tool 'slow_tool' was cancelled", true),
+                ("second", "Tool 'slow_tool' was cancelled", true),
+            ]
+        );
+    }
+
+    /// Plays both sides of a review: the primary answers 2 + 2 wrong until a
+    /// reviewer asks for a revision, and the reviewer approves only "= 4".
+    struct ReviewClient {
+        primary_calls: std::sync::atomic::AtomicUsize,
+        approve_fix: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for ReviewClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            let last = req.messages.last().unwrap().content[0].clone();
+            let ContentBlock::Text { text: last } = last else {
+                panic!("expected a text prompt");
+            };
+
+            let text = if req.system.as_deref() == Some("You review.") {
+                if self.approve_fix && last.contains("2 + 2 = 4") {
+                    "APPROVED".to_string()
+                } else {
+                    "The sum is wrong: 2 + 2 is 4.".to_string()
+                }
+            } else {
+                let call = self
+                    .primary_calls
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                if call > 0 && last.contains("2 + 2 is 4") {
+                    "2 + 2 = 4".to_string()
+                } else {
+                    "2 + 2 = 5".to_string()
+                }
+            };
+
+            Ok(Response {
+                id: "msg".into(),
+                content: vec![ContentBlock::text(text)],
+                stop_reason: crate::llm::StopReason::EndTurn,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    ..Default::default()
+                },
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_review_revises_once() {
+        let client = Arc::new(ReviewClient {
+            primary_calls: std::sync::atomic::AtomicUsize::new(0),
+            approve_fix: true,
+        });
+        let definition = AgentDefinition::new("adder", "You add.").model("test-model");
+        let mut agent = SubAgent::new(definition, client.clone(), Registry::new());
+
+        let reviewed = agent
+            .run_with_review(
+                "What is 2 + 2?",
+                AgentDefinition::new("critic", "You review."),
+                3,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client
+                .primary_calls
+                .load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+        assert_eq!(reviewed.revisions, 1);
+        assert_eq!(reviewed.reviews.len(), 2);
+        assert_eq!(
+            reviewed.reviews[0].verdict,
+            ReviewVerdict::RevisionRequested
+        );
+        assert!(reviewed.approved());
+        assert_eq!(reviewed.result.content, "2 + 2 = 4");
+        assert_eq!(reviewed.reviewer_usage.input_tokens, 20);
+
+        // The revision continued the primary conversation after its first answer
+        let transcript = agent.transcript();
+        assert_eq!(transcript.len(), 3);
+        assert_eq!(transcript[1].role, Role::Assistant);
+        assert!(matches!(
+            &transcript[1].content[0],
+            ContentBlock::Text { text } if text == "2 + 2 = 5"
+        ));
+    }
+
+    /// Answers as the primary agent. As the reviewer, sends an email before
+    /// approving and keeps the result it got back.
+    struct EmailingReviewerClient {
+        reviewer_tool_results: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for EmailingReviewerClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            let last = &req.messages.last().unwrap().content[0];
+            let (content, stop_reason) = if req.system.as_deref() != Some("You review.") {
+                (vec![ContentBlock::text("done")], StopReason::EndTurn)
+            } else if let ContentBlock::ToolResult { content, .. } = last {
+                self.reviewer_tool_results
+                    .lock()
+                    .unwrap()
+                    .push(content.clone());
+                (vec![ContentBlock::text("APPROVED")], StopReason::EndTurn)
+            } else {
+                (
+                    vec![ContentBlock::ToolUse {
+                        id: "tool_0".into(),
+                        name: "send_email".into(),
+                        input: serde_json::json!({}),
+                    }],
+                    StopReason::ToolUse,
+                )
+            };
+
+            Ok(Response {
+                id: "msg".into(),
+                content,
+                stop_reason,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_review_applies_policy_to_reviewer() {
+        let client = Arc::new(EmailingReviewerClient {
+            reviewer_tool_results: std::sync::Mutex::new(Vec::new()),
+        });
+        let registry = Registry::new();
+        registry.register(SendEmailTool).await;
+        let policy = Policy::builder()
+            .deny("send_email")
+            .default(Decision::Allow)
+            .build();
+        let definition = AgentDefinition::new("adder", "You add.").model("test-model");
+        let mut agent =
+            SubAgent::new(definition, client.clone(), registry).with_policy(Arc::new(policy));
+
+        let reviewed = agent
+            .run_with_review("Email me", AgentDefinition::new("critic", "You review."), 0)
+            .await
+            .unwrap();
+
+        assert!(reviewed.approved());
+        let results = client.reviewer_tool_results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_ne!(results[0], "sent");
+    }
+
+    #[tokio::test]
+    async fn test_run_with_review_is_bounded() {
+        let client = Arc::new(ReviewClient {
+            primary_calls: std::sync::atomic::AtomicUsize::new(0),
+            approve_fix: false,
+        });
+        let definition = AgentDefinition::new("adder", "You add.").model("test-model");
+        let mut agent = SubAgent::new(definition, client.clone(), Registry::new());
+
+        let reviewed = agent
+            .run_with_review(
+                "What is 2 + 2?",
+                AgentDefinition::new("critic", "You review."),
+                2,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client
+                .primary_calls
+                .load(std::sync::atomic::Ordering::SeqCst),
+            3
+        );
+        assert_eq!(reviewed.revisions, 2);
+        assert_eq!(reviewed.reviews.len(), 3);
+        assert!(!reviewed.approved());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_save_includes_final_answer() {
+        use crate::agent::FileTranscriptStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(FileTranscriptStore::new(dir.path()).unwrap());
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(5);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(3)),
+            Registry::new(),
+        )
+        .with_incremental_save(store.clone());
+
+        agent.run("do it").await.unwrap();
+
+        // The task, three tool turns and the answer
+        let saved = store.load(agent.agent_id()).await.unwrap().unwrap();
+        assert_eq!(saved.len(), 8);
+        assert_eq!(agent.transcript().len(), 7);
+        assert_eq!(saved[7].role, Role::Assistant);
+        assert!(matches!(
+            &saved[7].content[..],
+            [ContentBlock::Text { text }] if text == "All done"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_crashed_run_resumes_from_last_saved_turn() {
+        use crate::agent::FileTranscriptStore;
+        use crate::llm::MockClient;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(FileTranscriptStore::new(dir.path()).unwrap());
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(5);
+        let client = MockClient::new()
+            .with_tool_use("missing_tool", serde_json::json!({}))
+            .with_tool_use("missing_tool", serde_json::json!({}))
+            .with_error(LlmError::InvalidRequest("connection lost".into()));
+        let mut agent = SubAgent::new(definition.clone(), Arc::new(client), Registry::new())
+            .with_incremental_save(store.clone());
+
+        assert!(agent.run("do it").await.is_err());
+
+        // Saved after the second tool turn, before the failed call
+        let saved = store.load(agent.agent_id()).await.unwrap().unwrap();
+        assert_eq!(saved.len(), 5);
+        assert!(matches!(
+            saved.last().unwrap().content[0],
+            ContentBlock::ToolResult { .. }
+        ));
+
+        let mut resumed = SubAgent::resume(
+            agent.agent_id().to_string(),
+            definition,
+            Arc::new(MockClient::new().with_text("done")),
+            Registry::new(),
+            saved,
+        );
+        let result = resumed.run("carry on").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(resumed.transcript().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_context_is_in_first_request() {
+        let client = Arc::new(ScriptedClient::new(0));
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, client.clone(), Registry::new())
+            .with_seeded_context(vec![
+                (
+                    ToolUse::new("read_file", serde_json::json!({"path": "README.md"})),
+                    ToolResult::text("# Project"),
+                ),
+                (
+                    ToolUse {
+                        id: "search_1".into(),
+                        name: "search".into(),
+                        input: serde_json::json!({"pattern": "TODO"}),
+                    },
+                    ToolResult::error("No matches"),
+                ),
+            ]);
+
+        let result = agent.run("Summarize the project").await.unwrap();
+        assert_eq!(result.tool_use_count, 0);
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let messages = &requests[0].messages;
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(
+            &messages[0].content[..],
+            [ContentBlock::Text { text }] if text == "Summarize the project"
+        ));
+        assert_eq!(messages[1].role, Role::Assistant);
+        assert!(matches!(
+            &messages[1].content[..],
+            [
+                ContentBlock::ToolUse { id: first, name, input },
+                ContentBlock::ToolUse { id: second, .. },
+            ] if first == "call_0_0" && name == "read_file"
+                && input["path"] == "README.md" && second == "search_1"
+        ));
+        assert_eq!(messages[2].role, Role::User);
+        assert!(matches!(
+            &messages[2].content[..],
+            [
+                ContentBlock::ToolResult { tool_use_id: first, content, is_error: false, .. },
+                ContentBlock::ToolResult { tool_use_id: second, is_error: true, .. },
+            ] if first == "call_0_0" && content == "# Project" && second == "search_1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_context_is_added_once() {
+        let client = Arc::new(ScriptedClient::new(0));
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, client.clone(), Registry::new())
+            .with_seeded_context(vec![(
+                ToolUse::new("read_file", serde_json::json!({"path": "a.txt"})),
+                ToolResult::text("a"),
+            )]);
+
+        agent.run("first").await.unwrap();
+        agent.run("second").await.unwrap();
+
+        // The first task, the seeded call and result, then the second task
+        assert_eq!(agent.transcript().len(), 4);
+        let requests = client.requests.lock().unwrap();
+        assert!(matches!(
+            &requests[1].messages[3].content[..],
+            [ContentBlock::Text { text }] if text == "second"
+        ));
+    }
+
+    /// Client whose tool calls have missing, repeated, or colliding ids.
+    struct BadIdClient {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for BadIdClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let tool = |id: &str| ContentBlock::ToolUse {
+                id: id.into(),
+                name: "missing_tool".into(),
+                input: serde_json::json!({}),
+            };
+            let (content, stop_reason) = match call {
+                0 => (vec![tool(""), tool("")], crate::llm::StopReason::ToolUse),
+                1 => (
+                    vec![tool("dup"), tool("dup")],
+                    crate::llm::StopReason::ToolUse,
+                ),
+                // Collides with the id synthesized for the first call
+                2 => (vec![tool("call_1_0")], crate::llm::StopReason::ToolUse),
+                _ => (
+                    vec![ContentBlock::text("All done")],
+                    crate::llm::StopReason::EndTurn,
+                ),
+            };
+
+            Ok(Response {
+                id: format!("msg_{}", call),
+                content,
+                stop_reason,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_and_duplicate_tool_ids_are_paired() {
+        let definition = AgentDefinition::new("worker", "You work.")
+            .model("test-model")
+            .max_iterations(10);
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(BadIdClient {
+                calls: std::sync::atomic::AtomicUsize::new(0),
+            }),
+            Registry::new(),
+        );
+
+        let result = agent.run("do it").await.unwrap();
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(result.tool_use_count, 5);
+
+        let transcript = agent.transcript();
+        let mut all_ids = Vec::new();
+        for pair in transcript[1..].chunks(2) {
+            let call_ids: Vec<&str> = pair[0]
+                .content
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let result_ids: Vec<&str> = pair[1]
+                .content
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+                    _ => None,
+                })
+                .collect();
+
+            // Exactly one result per call, in call order
+            assert_eq!(call_ids, result_ids);
+            all_ids.extend(call_ids);
+        }
+
+        assert_eq!(
+            all_ids,
+            vec!["call_1_0", "call_1_1", "dup", "call_2_1", "call_3_0"]
+        );
+    }
+
+    /// Client that writes `a.txt` and `b.txt`, then rewrites `a.txt`, then finishes.
+    struct WriteFilesClient {
+        dir: std::path::PathBuf,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for WriteFilesClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let write = |file: &str| ContentBlock::ToolUse {
+                id: format!("write_{}_{}", call, file),
+                name: "write_file".into(),
+                input: serde_json::json!({
+                    "path": self.dir.join(file),
+                    "content": format!("turn {}", call),
+                }),
+            };
+            let (content, stop_reason) = match call {
+                0 => (
+                    vec![write("a.txt"), write("b.txt")],
+                    crate::llm::StopReason::ToolUse,
+                ),
+                1 => (vec![write("a.txt")], crate::llm::StopReason::ToolUse),
+                _ => (
+                    vec![ContentBlock::text("All done")],
+                    crate::llm::StopReason::EndTurn,
+                ),
+            };
+
+            Ok(Response {
+                id: format!("msg_{}", call),
+                content,
+                stop_reason,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_aggregates_files_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Registry::new();
+        registry.register(crate::tools::WriteFileTool).await;
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let client = WriteFilesClient {
+            dir: dir.path().to_path_buf(),
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        };
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry);
+
+        let result = agent.run("write files").await.unwrap();
+
+        let path = |file: &str| dir.path().join(file).to_string_lossy().into_owned();
+        assert_eq!(result.tool_use_count, 3);
+        assert_eq!(result.files_changed, vec![path("a.txt"), path("b.txt")]);
+        assert_eq!(agent.files_changed(), result.files_changed.as_slice());
+        assert_eq!(std::fs::read_to_string(path("a.txt")).unwrap(), "turn 1");
+    }
+
+    #[tokio::test]
+    async fn test_tool_cache_reuses_reads_until_a_write() {
+        use crate::llm::MockClient;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "original").unwrap();
+        let path = file.to_string_lossy().into_owned();
+
+        let registry = Registry::new();
+        registry.register(crate::tools::ReadFileTool).await;
+        registry.register(crate::tools::WriteFileTool).await;
+        let outside = file.clone();
+        let client = MockClient::new()
+            .with_tool_use("read_file", serde_json::json!({"path": path}))
+            // Changed behind the cache's back, so the next read is stale
+            .with_tool_use("read_file", serde_json::json!({"path": path}))
+            .expecting(move |_| std::fs::write(&outside, "outside").unwrap())
+            .with_tool_use(
+                "write_file",
+                serde_json::json!({"path": path, "content": "written"}),
+            )
+            .with_tool_use("read_file", serde_json::json!({"path": path}))
+            .with_text("done");
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, Arc::new(client), registry.clone())
+            .with_tool_cache(CachingRegistry::new(registry));
+
+        agent.run("read, write, read").await.unwrap();
+
+        let results: Vec<&str> = agent
+            .transcript()
+            .iter()
+            .flat_map(|m| &m.content)
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].contains("original"));
+        assert!(results[1].contains("original"));
+        assert!(results[3].contains("written"));
+    }
+
+    /// Client that streams a `write_file` call in fragments, then finishes.
+    struct StreamingToolClient {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for StreamingToolClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            unreachable!("streaming agent should not call create_message")
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let mut events = vec![StreamEvent::MessageStart {
+                id: format!("msg_{}", call),
+                model: "test-model".into(),
+            }];
+            if call == 0 {
+                events.push(StreamEvent::ContentBlockStart {
+                    index: 0,
+                    block: ContentBlock::ToolUse {
+                        id: "tool_1".into(),
+                        name: "write_file".into(),
+                        input: serde_json::json!({}),
+                    },
+                });
+                for fragment in [r#"{"path": "src/fo"#, r#"o.rs", "cont"#, r#"ent": "hi"}"#] {
+                    events.push(StreamEvent::InputJsonDelta {
+                        index: 0,
+                        partial_json: fragment.into(),
+                    });
+                }
+            } else {
+                events.push(StreamEvent::ContentBlockStart {
+                    index: 0,
+                    block: ContentBlock::text(""),
+                });
+                events.push(StreamEvent::ContentBlockDelta {
+                    index: 0,
+                    text: "Done".into(),
+                });
+            }
+            events.push(StreamEvent::ContentBlockStop {
+                index: 0,
+                block: None,
+            });
+            events.push(StreamEvent::MessageStop);
+            Box::pin(futures::stream::iter(events.into_iter().map(Ok)))
+        }
+    }
+
+    struct ToolInputRecorder {
+        partials: Arc<std::sync::Mutex<Vec<(String, String)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::hook::Hook for ToolInputRecorder {
+        async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+            if let HookEvent::ToolInputDelta {
+                tool_name,
+                partial_json,
+                ..
+            } = event
+            {
+                self.partials
+                    .lock()
+                    .unwrap()
+                    .push((tool_name.clone(), partial_json.clone()));
+            }
+            Ok(HookAction::Continue)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_emits_partial_tool_input() {
+        use crate::llm::partial_json::partial_string_field;
+
+        let partials = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Arc::new(HookRegistry::new());
+        hooks
+            .register(ToolInputRecorder {
+                partials: partials.clone(),
+            })
+            .await;
+
+        let definition = AgentDefinition::new("writer", "You write.")
+            .model("test-model")
+            .streaming(true);
+        let client = StreamingToolClient {
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        };
+        let mut agent =
+            SubAgent::new(definition, Arc::new(client), Registry::new()).with_hooks(hooks);
+
+        let result = agent.run("write a file").await.unwrap();
+        assert_eq!(result.content, "Done");
+
+        let partials = partials.lock().unwrap();
+        assert_eq!(partials.len(), 3);
+        assert!(partials.iter().all(|(name, _)| name == "write_file"));
+
+        // The first fragment has an unterminated path
+        assert_eq!(partial_string_field(&partials[0].1, "path"), None);
+
+        // After the second fragment the path is known but the JSON is still incomplete
+        let (_, second) = &partials[1];
+        assert!(serde_json::from_str::<serde_json::Value>(second).is_err());
+        assert_eq!(
+            partial_string_field(second, "path").as_deref(),
+            Some("src/foo.rs")
+        );
+    }
+
+    #[test]
+    fn test_stop_reason_serialization() {
+        let json = serde_json::to_string(&AgentStopReason::MaxIterations).unwrap();
+        assert_eq!(json, "\"max_iterations\"");
+    }
+
+    /// Client whose first response splits its text into several blocks around a tool call.
+    struct SplitTextClient {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmClient for SplitTextClient {
+        async fn create_message(&self, _req: &Request) -> Result<Response, LlmError> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let (content, stop_reason) = if call == 0 {
+                (
+                    vec![
+                        ContentBlock::text("Let me "),
+                        ContentBlock::text("look."),
+                        ContentBlock::ToolUse {
+                            id: "tool_0".into(),
+                            name: "missing_tool".into(),
+                            input: serde_json::json!({}),
+                        },
+                        ContentBlock::text("Then "),
+                        ContentBlock::text("report."),
+                    ],
+                    crate::llm::StopReason::ToolUse,
+                )
+            } else {
+                (
+                    vec![ContentBlock::text("All done")],
+                    crate::llm::StopReason::EndTurn,
+                )
+            };
+
+            Ok(Response {
+                id: format!("msg_{}", call),
+                content,
+                stop_reason,
+                model: "test-model".into(),
+                served_model: None,
+                system_fingerprint: None,
+                usage: Usage::default(),
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+
+        fn create_message_stream(
+            &self,
+            _req: &Request,
+        ) -> std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_merges_adjacent_text_blocks() {
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let client = SplitTextClient {
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        };
+        let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new());
+        agent.run("Do it").await.unwrap();
+
+        let assistant = &agent.transcript()[1];
+        assert_eq!(assistant.role, Role::Assistant);
+        assert_eq!(assistant.content.len(), 3);
+        assert!(
+            matches!(&assistant.content[0], ContentBlock::Text { text } if text == "Let me look.")
+        );
+        assert!(matches!(
+            &assistant.content[1],
+            ContentBlock::ToolUse { .. }
+        ));
+        assert!(
+            matches!(&assistant.content[2], ContentBlock::Text { text } if text == "Then report.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_keeps_text_blocks_when_merging_disabled() {
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let client = SplitTextClient {
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        };
+        let mut agent = SubAgent::new(definition, Arc::new(client), Registry::new())
+            .with_text_block_merging(false);
+        agent.run("Do it").await.unwrap();
+
+        assert_eq!(agent.transcript()[1].content.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_run_acquires_rate_limit_for_model_scope() {
+        use crate::coordinator::RateLimiter;
+
+        let limiter = Arc::new(
+            ScopedRateLimiter::new()
+                .with_scope("test:test-model", RateLimiter::new(10.0, 0.001))
+                .with_scope("test:other-model", RateLimiter::new(10.0, 0.001)),
+        );
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(
+            definition,
+            Arc::new(ScriptedClient::new(2)),
+            Registry::new(),
+        )
+        .with_rate_limiter(limiter.clone(), "test");
+        agent.run("Do it").await.unwrap();
+
+        // Three LLM calls were counted against the agent's model only
+        let used = limiter.for_scope("test:test-model").unwrap();
+        assert!((used.available().await - 7.0).abs() < 0.01);
+        let other = limiter.for_scope("test:other-model").unwrap();
+        assert!((other.available().await - 10.0).abs() < 0.01);
     }
 }
@@ -196,6 +196,7 @@ impl Tool for TaskTool {
                     "content": result.content,
                     "tool_use_count": result.tool_use_count,
                     "iterations": result.iterations,
+                    "stop_reason": result.stop_reason,
                     "tokens": {
                         "input": result.usage.input_tokens,
                         "output": result.usage.output_tokens
@@ -256,4 +257,32 @@ mod tests {
         assert!(result.content.contains("not found"));
         assert!(result.content.contains("researcher"));
     }
+
+    #[tokio::test]
+    async fn test_task_tool_reports_stop_reason() {
+        let agent_registry = AgentRegistry::new();
+        agent_registry
+            .register(
+                AgentDefinition::new("researcher", "You research things")
+                    .model("test-model")
+                    .max_iterations(1),
+            )
+            .await;
+        let client = crate::llm::MockClient::new()
+            .with_tool_use("missing_tool", serde_json::json!({}))
+            .with_text("never sent");
+        let tool = TaskTool::with_default_client(agent_registry, Registry::new(), Arc::new(client));
+
+        let result = tool
+            .execute(serde_json::json!({
+                "agent_type": "researcher",
+                "task": "look around",
+                "description": "test"
+            }))
+            .await
+            .unwrap();
+
+        let output: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(output["stop_reason"], "max_iterations");
+    }
 }
@@ -1,13 +1,91 @@
-// ABOUTME: Transcript storage for agent conversations.
+// ABOUTME: Transcript storage and a versioned JSON format for agent conversations.
 // ABOUTME: Enables agent resume by persisting conversation history.
 
 use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::sync::RwLock;
 
-use crate::llm::Message;
+use crate::llm::{Message, Usage};
+use crate::tools::write_atomic;
+
+/// The [`Transcript`] schema version written by this crate.
+pub const TRANSCRIPT_VERSION: u32 = 1;
+
+/// An agent conversation in a stable JSON format, for saving in one process
+/// and resuming in another with [`SubAgent::resume_transcript`].
+///
+/// ```json
+/// {"version": 1, "agent_id": "...", "model": "...", "messages": [...], "usage": {...}}
+/// ```
+///
+/// [`Transcript::from_json`] also reads older formats, upgrading them to
+/// [`TRANSCRIPT_VERSION`]:
+///
+/// - version 0: a bare message array, as serialized from
+///   [`SubAgent::transcript`]. The agent ID is empty and usage is zero.
+///
+/// [`SubAgent::resume_transcript`]: super::SubAgent::resume_transcript
+/// [`SubAgent::transcript`]: super::SubAgent::transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    pub version: u32,
+    pub agent_id: String,
+    /// The model the agent's definition named, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub messages: Vec<Message>,
+    /// Token usage of the run that produced the transcript.
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+impl Transcript {
+    /// Create a current-version transcript with no model and zero usage.
+    pub fn new(agent_id: impl Into<String>, messages: Vec<Message>) -> Self {
+        Self {
+            version: TRANSCRIPT_VERSION,
+            agent_id: agent_id.into(),
+            model: None,
+            messages,
+            usage: Usage::default(),
+        }
+    }
+
+    /// Serialize to JSON in the current schema.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Parse a transcript in the current schema or an older one.
+    ///
+    /// Fails on malformed JSON and on versions newer than
+    /// [`TRANSCRIPT_VERSION`].
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let value: Value = serde_json::from_str(json)?;
+        if value.is_array() {
+            return Ok(Self::new("", serde_json::from_value(value)?));
+        }
+
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| serde_json::Error::custom("transcript has no version"))?;
+        if version > u64::from(TRANSCRIPT_VERSION) {
+            return Err(serde_json::Error::custom(format!(
+                "transcript version {} is newer than supported version {}",
+                version, TRANSCRIPT_VERSION
+            )));
+        }
+        serde_json::from_value(value)
+    }
+}
 
 /// Trait for storing and retrieving agent transcripts.
 ///
@@ -81,6 +159,109 @@ impl TranscriptStore for MemoryTranscriptStore {
     }
 }
 
+/// Transcript store that keeps one JSON file per agent in a directory.
+///
+/// Each file holds a [`Transcript`] and is named after the agent ID, with
+/// bytes outside `[A-Za-z0-9_-]` percent-encoded so any ID is a safe file
+/// name. Saves write a temporary file and rename it into place, so
+/// concurrent saves never leave a partly written transcript: for the same
+/// agent, the last save wins.
+pub struct FileTranscriptStore {
+    dir: PathBuf,
+}
+
+impl FileTranscriptStore {
+    /// Store transcripts in `dir`, creating it if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// The directory transcripts are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, agent_id: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", encode_file_stem(agent_id)))
+    }
+}
+
+fn encode_file_stem(agent_id: &str) -> String {
+    let mut stem = String::with_capacity(agent_id.len());
+    for byte in agent_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            stem.push(byte as char);
+        } else {
+            stem.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    stem
+}
+
+/// The agent ID a file stem encodes, or `None` if it isn't one of ours.
+fn decode_file_stem(stem: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(stem.len());
+    let mut rest = stem.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            bytes.push(byte);
+            rest = tail;
+        } else {
+            return None;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[async_trait]
+impl TranscriptStore for FileTranscriptStore {
+    async fn save(&self, agent_id: &str, messages: &[Message]) -> Result<(), anyhow::Error> {
+        let json = Transcript::new(agent_id, messages.to_vec()).to_json()?;
+        let path = self.path(agent_id);
+        tokio::task::spawn_blocking(move || write_atomic(&path, json.as_bytes())).await??;
+        Ok(())
+    }
+
+    async fn load(&self, agent_id: &str) -> Result<Option<Vec<Message>>, anyhow::Error> {
+        match tokio::fs::read_to_string(self.path(agent_id)).await {
+            Ok(json) => Ok(Some(Transcript::from_json(&json)?.messages)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, agent_id: &str) -> Result<(), anyhow::Error> {
+        match tokio::fs::remove_file(self.path(agent_id)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<String>, anyhow::Error> {
+        let mut ids = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            // Skips temporary files from saves in progress, which end in .tmp
+            if let Some(id) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(decode_file_stem)
+            {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,6 +280,47 @@ mod tests {
         ]
     }
 
+    #[test]
+    fn test_transcript_json_round_trip() {
+        let mut transcript = Transcript::new("agent-1", sample_messages());
+        transcript.model = Some("claude-sonnet-4-20250514".into());
+        transcript.usage.input_tokens = 120;
+
+        let json = transcript.to_json().unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], TRANSCRIPT_VERSION);
+
+        let loaded = Transcript::from_json(&json).unwrap();
+        assert_eq!(loaded.agent_id, "agent-1");
+        assert_eq!(loaded.model.as_deref(), Some("claude-sonnet-4-20250514"));
+        assert_eq!(loaded.usage.input_tokens, 120);
+        assert_eq!(loaded.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_transcript_reads_bare_message_array() {
+        let json = serde_json::to_string(&sample_messages()).unwrap();
+
+        let loaded = Transcript::from_json(&json).unwrap();
+        assert_eq!(loaded.version, TRANSCRIPT_VERSION);
+        assert_eq!(loaded.agent_id, "");
+        assert_eq!(loaded.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_transcript_rejects_newer_version() {
+        let json = serde_json::json!({
+            "version": TRANSCRIPT_VERSION + 1,
+            "agent_id": "agent-1",
+            "messages": []
+        })
+        .to_string();
+
+        let err = Transcript::from_json(&json).unwrap_err();
+        assert!(err.to_string().contains("newer than supported"), "{}", err);
+        assert!(Transcript::from_json(r#"{"agent_id": "a"}"#).is_err());
+    }
+
     #[tokio::test]
     async fn test_memory_store_save_load() {
         let store = MemoryTranscriptStore::new();
@@ -167,4 +389,105 @@ mod tests {
             panic!("Expected text block");
         }
     }
+
+    #[tokio::test]
+    async fn test_file_store_survives_reopen() {
+        use crate::agent::{AgentDefinition, SubAgent};
+        use crate::llm::MockClient;
+        use crate::tool::Registry;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        {
+            let store = FileTranscriptStore::new(dir.path().join("transcripts")).unwrap();
+            store.save("agent-1", &sample_messages()).await.unwrap();
+        }
+
+        let store = FileTranscriptStore::new(dir.path().join("transcripts")).unwrap();
+        assert_eq!(store.list().await.unwrap(), vec!["agent-1"]);
+        let messages = store.load("agent-1").await.unwrap().unwrap();
+
+        let client = Arc::new(
+            MockClient::new()
+                .with_text("Welcome back")
+                .expecting(|req| {
+                    assert_eq!(req.messages.len(), 3);
+                }),
+        );
+        let mut agent = SubAgent::resume(
+            "agent-1".into(),
+            AgentDefinition::new("worker", "You work.").model("test-model"),
+            client,
+            Registry::new(),
+            messages,
+        );
+        let result = agent.run("Still there?").await.unwrap();
+        assert_eq!(result.content, "Welcome back");
+    }
+
+    #[tokio::test]
+    async fn test_file_store_delete_and_missing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = FileTranscriptStore::new(dir.path()).unwrap();
+
+        assert!(store.load("agent-1").await.unwrap().is_none());
+        store.save("agent-1", &sample_messages()).await.unwrap();
+        store.delete("agent-1").await.unwrap();
+        store.delete("agent-1").await.unwrap();
+
+        assert!(store.load("agent-1").await.unwrap().is_none());
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_encodes_unsafe_ids() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = FileTranscriptStore::new(dir.path().join("store")).unwrap();
+
+        store.save("../escape", &sample_messages()).await.unwrap();
+        store
+            .save("team/agent 1", &sample_messages())
+            .await
+            .unwrap();
+
+        // Nothing is written outside the store's directory
+        assert!(!dir.path().join("escape.json").exists());
+        let mut ids = store.list().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["../escape", "team/agent 1"]);
+        assert!(store.load("team/agent 1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_concurrent_saves() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Arc::new(FileTranscriptStore::new(dir.path()).unwrap());
+
+        let saves: Vec<_> = (0..20)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    let messages = vec![Message::user(format!("turn {}", i)); i + 1];
+                    store.save(&format!("agent-{}", i % 4), &messages).await
+                })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap().unwrap();
+        }
+
+        assert_eq!(store.list().await.unwrap().len(), 4);
+        for i in 0..4 {
+            // Whichever save landed last, the file is a whole transcript
+            let messages = store.load(&format!("agent-{}", i)).await.unwrap().unwrap();
+            assert!(!messages.is_empty());
+        }
+        let leftovers = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
 }
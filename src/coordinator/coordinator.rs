@@ -1,9 +1,10 @@
 // ABOUTME: Resource coordinator for multi-agent synchronization.
-// ABOUTME: Provides mutex-style locking for shared resources across agents.
+// ABOUTME: Provides mutex-style locking for shared resources and LLM concurrency slots.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore, TryAcquireError};
 
 /// Error type for resource lock operations.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,6 +22,8 @@ pub enum LockError {
     },
     /// Operation was cancelled.
     Cancelled,
+    /// All LLM slots are in use.
+    NoLlmSlot { max_concurrent: usize },
 }
 
 impl std::fmt::Display for LockError {
@@ -50,6 +53,9 @@ impl std::fmt::Display for LockError {
             LockError::Cancelled => {
                 write!(f, "operation cancelled")
             }
+            LockError::NoLlmSlot { max_concurrent } => {
+                write!(f, "all {} LLM slots are in use", max_concurrent)
+            }
         }
     }
 }
@@ -67,6 +73,19 @@ pub struct ResourceLock {
     pub acquired_at: Instant,
 }
 
+/// A held LLM concurrency slot. The slot is released when this is dropped,
+/// including when the holder panics and unwinds.
+#[derive(Debug)]
+pub struct LlmSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// A held tool-execution slot. The slot is released when this is dropped.
+#[derive(Debug)]
+pub struct ToolSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
 /// Resource coordinator for multi-agent synchronization.
 ///
 /// The coordinator allows multiple agents to coordinate access to shared
@@ -78,8 +97,24 @@ pub struct ResourceLock {
 /// - **Idempotent acquire:** If an agent already owns a resource, `acquire()` returns `Ok`.
 /// - **Ownership verification:** `release()` validates that the requesting agent owns the lock.
 /// - **Idempotent release_all:** Returns no error even if the agent holds no locks.
+///
+/// # LLM Slots
+///
+/// `acquire_llm_slot()` caps concurrent in-flight LLM requests across every
+/// agent sharing the coordinator. Waiters are served in FIFO order. The cap
+/// is unlimited unless set with `with_max_concurrent_llm()`.
+///
+/// # Tool Slots
+///
+/// `acquire_tool_slot()` does the same for tool calls, capped with
+/// `with_max_concurrent_tools()`. Agents given the coordinator with
+/// [`SubAgent::with_coordinator`](crate::agent::SubAgent::with_coordinator)
+/// hold a slot while each tool call runs.
 pub struct Coordinator {
     locks: Mutex<HashMap<String, ResourceLock>>,
+    llm_slots: Arc<Semaphore>,
+    max_concurrent_llm: usize,
+    tool_slots: Arc<Semaphore>,
 }
 
 impl Default for Coordinator {
@@ -93,9 +128,78 @@ impl Coordinator {
     pub fn new() -> Self {
         Self {
             locks: Mutex::new(HashMap::new()),
+            llm_slots: Arc::new(Semaphore::new(Semaphore::MAX_PERMITS)),
+            max_concurrent_llm: Semaphore::MAX_PERMITS,
+            tool_slots: Arc::new(Semaphore::new(Semaphore::MAX_PERMITS)),
+        }
+    }
+
+    /// Limit how many LLM requests may be in flight at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero, since no request could ever proceed.
+    pub fn with_max_concurrent_llm(mut self, max: usize) -> Self {
+        assert!(max > 0, "max_concurrent_llm must be at least 1");
+        let max = max.min(Semaphore::MAX_PERMITS);
+        self.llm_slots = Arc::new(Semaphore::new(max));
+        self.max_concurrent_llm = max;
+        self
+    }
+
+    /// Limit how many tool calls may run at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero, since no tool could ever run.
+    pub fn with_max_concurrent_tools(mut self, max: usize) -> Self {
+        assert!(max > 0, "max_concurrent_tools must be at least 1");
+        self.tool_slots = Arc::new(Semaphore::new(max.min(Semaphore::MAX_PERMITS)));
+        self
+    }
+
+    /// Wait for a tool slot. Hold the returned guard while the tool runs.
+    ///
+    /// Waiters are granted slots in the order they called this method.
+    pub async fn acquire_tool_slot(&self) -> ToolSlot {
+        let permit = Arc::clone(&self.tool_slots)
+            .acquire_owned()
+            .await
+            .expect("tool slot semaphore is never closed");
+        ToolSlot { _permit: permit }
+    }
+
+    /// Wait for an LLM slot. Hold the returned guard for the duration of the request.
+    ///
+    /// Waiters are granted slots in the order they called this method.
+    pub async fn acquire_llm_slot(&self) -> LlmSlot {
+        let permit = Arc::clone(&self.llm_slots)
+            .acquire_owned()
+            .await
+            .expect("LLM slot semaphore is never closed");
+        LlmSlot { _permit: permit }
+    }
+
+    /// Take an LLM slot without waiting.
+    ///
+    /// Returns `Err(LockError::NoLlmSlot)` if all slots are in use, or if
+    /// others are already queued for one.
+    pub fn try_acquire_llm_slot(&self) -> Result<LlmSlot, LockError> {
+        match Arc::clone(&self.llm_slots).try_acquire_owned() {
+            Ok(permit) => Ok(LlmSlot { _permit: permit }),
+            Err(TryAcquireError::NoPermits) | Err(TryAcquireError::Closed) => {
+                Err(LockError::NoLlmSlot {
+                    max_concurrent: self.max_concurrent_llm,
+                })
+            }
         }
     }
 
+    /// Number of LLM slots currently free.
+    pub fn available_llm_slots(&self) -> usize {
+        self.llm_slots.available_permits()
+    }
+
     /// Acquire a lock on a resource.
     ///
     /// Returns `Ok(())` if the lock was acquired or if the agent already owns it.
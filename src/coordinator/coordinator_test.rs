@@ -206,3 +206,102 @@ async fn test_concurrent_acquire_same_resource() {
         "Exactly one agent should acquire the lock"
     );
 }
+
+#[tokio::test]
+async fn test_llm_slots_cap_concurrency() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let coordinator = Arc::new(Coordinator::new().with_max_concurrent_llm(2));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let mut handles = Vec::new();
+
+    for _ in 0..8 {
+        let coordinator = coordinator.clone();
+        let in_flight = in_flight.clone();
+        let peak = peak.clone();
+        handles.push(tokio::spawn(async move {
+            let _slot = coordinator.acquire_llm_slot().await;
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    assert_eq!(coordinator.available_llm_slots(), 2);
+}
+
+#[tokio::test]
+async fn test_llm_slots_are_fifo() {
+    use std::sync::{Arc, Mutex};
+
+    let coordinator = Arc::new(Coordinator::new().with_max_concurrent_llm(1));
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let held = coordinator.acquire_llm_slot().await;
+
+    let mut handles = Vec::new();
+    for i in 0..5 {
+        let coordinator = coordinator.clone();
+        let order = order.clone();
+        handles.push(tokio::spawn(async move {
+            let _slot = coordinator.acquire_llm_slot().await;
+            order.lock().unwrap().push(i);
+        }));
+        // Let each waiter enqueue before spawning the next
+        tokio::task::yield_now().await;
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    }
+
+    drop(held);
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn test_try_acquire_llm_slot() {
+    let coordinator = Coordinator::new().with_max_concurrent_llm(1);
+
+    let slot = coordinator.try_acquire_llm_slot().unwrap();
+    let err = coordinator.try_acquire_llm_slot().unwrap_err();
+    assert_eq!(err, LockError::NoLlmSlot { max_concurrent: 1 });
+    assert!(err.to_string().contains("1 LLM slots"));
+
+    drop(slot);
+    assert!(coordinator.try_acquire_llm_slot().is_ok());
+}
+
+#[tokio::test]
+async fn test_llm_slot_released_on_panic() {
+    use std::sync::Arc;
+
+    let coordinator = Arc::new(Coordinator::new().with_max_concurrent_llm(1));
+
+    let task_coordinator = coordinator.clone();
+    let result = tokio::spawn(async move {
+        let _slot = task_coordinator.acquire_llm_slot().await;
+        panic!("request failed mid-flight");
+    })
+    .await;
+
+    assert!(result.unwrap_err().is_panic());
+    assert_eq!(coordinator.available_llm_slots(), 1);
+    assert!(coordinator.try_acquire_llm_slot().is_ok());
+}
+
+#[tokio::test]
+async fn test_llm_slots_unlimited_by_default() {
+    let coordinator = Coordinator::new();
+    let slots: Vec<_> = (0..100)
+        .map(|_| coordinator.try_acquire_llm_slot().unwrap())
+        .collect();
+    assert_eq!(slots.len(), 100);
+}
@@ -4,8 +4,8 @@
 mod coordinator;
 mod rate_limiter;
 
-pub use coordinator::{Coordinator, LockError, ResourceLock};
-pub use rate_limiter::RateLimiter;
+pub use coordinator::{Coordinator, LlmSlot, LockError, ResourceLock, ToolSlot};
+pub use rate_limiter::{RateLimiter, ScopedRateLimiter};
 
 #[cfg(test)]
 mod coordinator_test;
@@ -1,6 +1,8 @@
 // ABOUTME: Token bucket rate limiter for API call throttling.
-// ABOUTME: Allows bursts up to capacity while maintaining average rate.
+// ABOUTME: Allows bursts up to capacity, with independent per-key or per-scope buckets and token budgets.
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
@@ -27,10 +29,20 @@ struct RateLimiterState {
 /// The token bucket algorithm allows bursting up to `capacity` tokens,
 /// then refills at `refill_rate` tokens per second. This provides
 /// smooth rate limiting while allowing short bursts of activity.
+///
+/// # Per-key limits
+///
+/// `for_key()` hands out an independent limiter per key (for example
+/// `"anthropic:claude-sonnet-4"`), so providers and models with separate
+/// quotas never throttle each other. An optional LLM token budget set with
+/// `with_token_budget()` is tracked alongside the request budget; use
+/// `acquire()` to consume one request plus the estimated tokens.
 pub struct RateLimiter {
     state: Mutex<RateLimiterState>,
     capacity: f64,
     refill_rate: f64,
+    token_budget: Option<Box<RateLimiter>>,
+    keyed: std::sync::Mutex<HashMap<String, Arc<RateLimiter>>>,
 }
 
 impl RateLimiter {
@@ -55,6 +67,76 @@ impl RateLimiter {
             }),
             capacity,
             refill_rate,
+            token_budget: None,
+            keyed: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a limiter from per-minute request and LLM token quotas.
+    ///
+    /// Both buckets start full and refill evenly over a minute.
+    pub fn per_minute(requests: f64, tokens: f64) -> Self {
+        Self::new(requests, requests / 60.0).with_token_budget(tokens, tokens / 60.0)
+    }
+
+    /// Also limit LLM tokens, with its own bucket capacity and refill rate per second.
+    pub fn with_token_budget(mut self, capacity: f64, refill_rate: f64) -> Self {
+        self.token_budget = Some(Box::new(RateLimiter::new(capacity, refill_rate)));
+        self
+    }
+
+    /// Get the limiter for `key`, creating it with this limiter's configuration.
+    ///
+    /// Each key has its own buckets, starting full, independent of this
+    /// limiter and of every other key. The same key always returns the same
+    /// limiter.
+    pub fn for_key(&self, key: &str) -> Arc<RateLimiter> {
+        let mut keyed = self.keyed.lock().unwrap_or_else(|e| e.into_inner());
+        keyed
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(self.fresh()))
+            .clone()
+    }
+
+    /// Use this limiter as the template for every scope of a [`ScopedRateLimiter`].
+    ///
+    /// Each scope gets its own buckets with this configuration, unless the
+    /// scope is configured separately with [`ScopedRateLimiter::with_scope`].
+    pub fn scoped(self) -> ScopedRateLimiter {
+        ScopedRateLimiter::new().with_default(self)
+    }
+
+    /// A new, full limiter with the same configuration.
+    fn fresh(&self) -> RateLimiter {
+        let limiter = RateLimiter::new(self.capacity, self.refill_rate);
+        match &self.token_budget {
+            Some(budget) => limiter.with_token_budget(budget.capacity, budget.refill_rate),
+            None => limiter,
+        }
+    }
+
+    /// Consume one request and `estimated_tokens` LLM tokens, waiting for both.
+    ///
+    /// Never fails: when either budget is exhausted this waits until enough
+    /// capacity has refilled. Estimates larger than the token bucket are
+    /// capped at its capacity so a single huge request cannot wait forever.
+    /// Without a token budget only the request is counted.
+    pub async fn acquire(&self, estimated_tokens: f64) {
+        let never = std::future::pending::<()>;
+        // Neither take can fail without a cancellation future
+        let _ = self.take(1.0, never()).await;
+        if let Some(budget) = &self.token_budget {
+            let _ = budget
+                .take(estimated_tokens.min(budget.capacity), never())
+                .await;
+        }
+    }
+
+    /// Current LLM tokens available, if a token budget is configured.
+    pub async fn available_llm_tokens(&self) -> Option<f64> {
+        match &self.token_budget {
+            Some(budget) => Some(budget.available().await),
+            None => None,
         }
     }
 
@@ -149,3 +231,59 @@ impl RateLimiter {
         state.tokens
     }
 }
+
+/// Rate limits kept separately per scope, such as `"anthropic:claude-sonnet-4"`.
+///
+/// Apps that call several providers or models with independent quotas give
+/// each scope its own configuration with [`with_scope`](Self::with_scope).
+/// Other scopes get fresh buckets from the default template on first use, or
+/// are not limited at all if there is no default. Scopes never throttle each
+/// other.
+#[derive(Default)]
+pub struct ScopedRateLimiter {
+    default: Option<RateLimiter>,
+    scopes: HashMap<String, Arc<RateLimiter>>,
+}
+
+impl ScopedRateLimiter {
+    /// Create a limiter with no scopes, which limits nothing until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The scope name for a provider and model, e.g. `anthropic:claude-sonnet-4`.
+    pub fn scope_for(provider: &str, model: &str) -> String {
+        format!("{}:{}", provider, model)
+    }
+
+    /// Use `template`'s configuration for scopes without their own.
+    pub fn with_default(mut self, template: RateLimiter) -> Self {
+        self.default = Some(template);
+        self
+    }
+
+    /// Give `scope` its own limits.
+    pub fn with_scope(mut self, scope: impl Into<String>, limiter: RateLimiter) -> Self {
+        self.scopes.insert(scope.into(), Arc::new(limiter));
+        self
+    }
+
+    /// The limiter for `scope`, or None if the scope is not limited.
+    ///
+    /// The same scope always returns the same limiter.
+    pub fn for_scope(&self, scope: &str) -> Option<Arc<RateLimiter>> {
+        match self.scopes.get(scope) {
+            Some(limiter) => Some(limiter.clone()),
+            None => self.default.as_ref().map(|d| d.for_key(scope)),
+        }
+    }
+
+    /// Consume one request and `estimated_tokens` LLM tokens from `scope`,
+    /// waiting as [`RateLimiter::acquire`] does. Returns at once for scopes
+    /// that are not limited.
+    pub async fn acquire(&self, scope: &str, estimated_tokens: f64) {
+        if let Some(limiter) = self.for_scope(scope) {
+            limiter.acquire(estimated_tokens).await;
+        }
+    }
+}
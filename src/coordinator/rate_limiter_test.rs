@@ -1,9 +1,10 @@
 // ABOUTME: Tests for the token bucket rate limiter.
 // ABOUTME: Covers basic operations, refill behavior, and cancellation.
 
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use super::rate_limiter::{Cancelled, RateLimiter};
+use super::rate_limiter::{Cancelled, RateLimiter, ScopedRateLimiter};
 
 #[tokio::test]
 async fn test_new_limiter_starts_full() {
@@ -221,3 +222,128 @@ async fn test_concurrent_takes() {
         assert!(result.is_ok());
     }
 }
+
+#[tokio::test]
+async fn test_keys_do_not_block_each_other() {
+    let limiter = RateLimiter::per_minute(1.0, 10_000.0);
+    let anthropic = limiter.for_key("anthropic:claude-sonnet-4");
+    let openai = limiter.for_key("openai:gpt-4o");
+
+    // Exhaust the single request allowed for anthropic
+    anthropic.acquire(100.0).await;
+    let blocked = tokio::time::timeout(Duration::from_millis(50), anthropic.acquire(100.0)).await;
+    assert!(blocked.is_err(), "second anthropic request should wait");
+
+    // openai has its own budget and is not throttled
+    let start = Instant::now();
+    openai.acquire(100.0).await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn test_for_key_returns_same_limiter() {
+    let limiter = RateLimiter::new(5.0, 1.0);
+    let a = limiter.for_key("a");
+    let again = limiter.for_key("a");
+    let b = limiter.for_key("b");
+
+    assert!(std::sync::Arc::ptr_eq(&a, &again));
+    assert!(!std::sync::Arc::ptr_eq(&a, &b));
+}
+
+#[tokio::test]
+async fn test_acquire_waits_for_token_budget() {
+    // Plenty of requests, but only 100 LLM tokens refilling at 1000/s
+    let limiter = RateLimiter::new(10.0, 10.0).with_token_budget(100.0, 1000.0);
+
+    limiter.acquire(100.0).await;
+    let remaining = limiter.available_llm_tokens().await.unwrap();
+    assert!(
+        remaining < 5.0,
+        "Expected budget drained, got {}",
+        remaining
+    );
+
+    let start = Instant::now();
+    limiter.acquire(50.0).await;
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(30),
+        "Should wait for tokens to refill, waited {:?}",
+        elapsed
+    );
+}
+
+#[tokio::test]
+async fn test_acquire_caps_oversized_estimates() {
+    let limiter = RateLimiter::new(10.0, 10.0).with_token_budget(100.0, 1000.0);
+
+    // Larger than the whole bucket: consumes the bucket instead of waiting forever
+    tokio::time::timeout(Duration::from_secs(1), limiter.acquire(1_000_000.0))
+        .await
+        .expect("oversized estimate should not wait forever");
+}
+
+#[tokio::test]
+async fn test_acquire_without_token_budget_counts_requests() {
+    let limiter = RateLimiter::new(2.0, 1.0);
+    limiter.acquire(1_000_000.0).await;
+
+    assert!(limiter.available_llm_tokens().await.is_none());
+    let available = limiter.available().await;
+    assert!(
+        (available - 1.0).abs() < 0.1,
+        "Expected ~1, got {}",
+        available
+    );
+}
+
+#[tokio::test]
+async fn test_scoped_limits_are_independent() {
+    // One request per scope, refilling far too slowly to matter in a test
+    let limiter = ScopedRateLimiter::new()
+        .with_scope("anthropic:sonnet", RateLimiter::new(1.0, 0.001))
+        .with_scope("openai:gpt-4", RateLimiter::new(1.0, 0.001));
+
+    let start = Instant::now();
+    limiter.acquire("anthropic:sonnet", 0.0).await;
+    limiter.acquire("openai:gpt-4", 0.0).await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+
+    let sonnet = limiter.for_scope("anthropic:sonnet").unwrap();
+    let gpt4 = limiter.for_scope("openai:gpt-4").unwrap();
+    assert!(sonnet.available().await < 0.01);
+    assert!(gpt4.available().await < 0.01);
+
+    // Exhausting one scope blocks only that scope
+    let blocked = sonnet.take(1.0, tokio::time::sleep(Duration::from_millis(50)));
+    assert_eq!(blocked.await, Err(Cancelled));
+    assert!(Arc::ptr_eq(
+        &sonnet,
+        &limiter.for_scope("anthropic:sonnet").unwrap()
+    ));
+}
+
+#[tokio::test]
+async fn test_scoped_default_creates_buckets_on_demand() {
+    let limiter = RateLimiter::new(2.0, 0.001)
+        .scoped()
+        .with_scope("openai:gpt-4", RateLimiter::new(5.0, 0.001));
+
+    limiter.acquire("anthropic:sonnet", 0.0).await;
+    limiter.acquire("anthropic:sonnet", 0.0).await;
+    let sonnet = limiter.for_scope("anthropic:sonnet").unwrap();
+    assert!(sonnet.available().await < 0.01);
+
+    // Another model gets a full bucket of its own, and configured scopes keep their limits
+    let haiku = limiter.for_scope("anthropic:haiku").unwrap();
+    assert!((haiku.available().await - 2.0).abs() < 0.01);
+    let gpt4 = limiter.for_scope("openai:gpt-4").unwrap();
+    assert!((gpt4.available().await - 5.0).abs() < 0.01);
+
+    assert_eq!(
+        ScopedRateLimiter::scope_for("anthropic", "sonnet"),
+        "anthropic:sonnet"
+    );
+    assert!(ScopedRateLimiter::new().for_scope("anything").is_none());
+}
@@ -1,6 +1,8 @@
 // ABOUTME: Defines all error types for the mux library using thiserror.
 // ABOUTME: Each submodule has its own error enum, unified under MuxError.
 
+use crate::tool::SchemaViolation;
+
 /// Top-level error type for the mux library.
 #[derive(Debug, thiserror::Error)]
 pub enum MuxError {
@@ -15,6 +17,12 @@ pub enum MuxError {
 
     #[error("MCP error: {0}")]
     Mcp(#[from] McpError),
+
+    #[error("Agent load error: {0}")]
+    AgentLoad(#[from] AgentLoadError),
+
+    #[error("Agent graph error: {0}")]
+    Graph(#[from] GraphError),
 }
 
 /// Errors from LLM client operations.
@@ -34,6 +42,15 @@ pub enum LlmError {
 
     #[error("Configuration error: {0}")]
     Configuration(String),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Response exceeded the {limit} byte limit")]
+    ResponseTooLarge { limit: usize },
+
+    #[error("LLM request blocked by hook: {0}")]
+    Blocked(String),
 }
 
 /// Errors from tool operations.
@@ -47,6 +64,9 @@ pub enum ToolError {
 
     #[error("Execution failed: {0}")]
     Execution(#[source] anyhow::Error),
+
+    #[error("Tool '{0}' is already registered")]
+    Conflict(String),
 }
 
 /// Errors from permission checks.
@@ -60,9 +80,21 @@ pub enum PermissionError {
 
     #[error("Approval handler error: {0}")]
     Handler(#[source] anyhow::Error),
+
+    #[error(
+        "Tool '{tool}' has reached its limit of {limit} calls per session and cannot be called again"
+    )]
+    RateLimited { tool: String, limit: usize },
 }
 
 /// Errors from MCP operations.
+///
+/// JSON-RPC errors from the server are split by code: the reserved
+/// protocol codes (parse error, invalid request, method not found, invalid
+/// params, internal error) become `Rpc`, anything else `Server`. A
+/// `tools/call` naming a tool the server doesn't have becomes
+/// `ToolNotFound`. `Connection`, `Io` and `Timeout` are worth retrying;
+/// the rest usually aren't.
 #[derive(Debug, thiserror::Error)]
 pub enum McpError {
     #[error("Connection failed: {0}")]
@@ -74,9 +106,71 @@ pub enum McpError {
     #[error("RPC error ({code}): {message}")]
     Rpc { code: i32, message: String },
 
+    #[error("Server error ({code}): {message}")]
+    Server {
+        code: i32,
+        message: String,
+        data: Option<serde_json::Value>,
+    },
+
+    #[error("Tool '{name}' not found: {message}")]
+    ToolNotFound { name: String, message: String },
+
+    #[error("Request timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("Environment variable '{0}' is not set")]
+    UnsetVariable(String),
+
+    #[error("Response exceeded the {limit} byte limit")]
+    ResponseTooLarge { limit: usize },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+
+    #[error("Tool '{tool}' returned output that violates its schema: {violation}")]
+    OutputSchema {
+        tool: String,
+        violation: SchemaViolation,
+    },
+}
+
+/// Errors from loading agent definitions from files.
+#[derive(Debug, thiserror::Error)]
+pub enum AgentLoadError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid agent file {path}: {message}")]
+    Invalid {
+        path: std::path::PathBuf,
+        message: String,
+    },
+}
+
+/// Errors from building or running an agent graph.
+#[derive(Debug, thiserror::Error)]
+pub enum GraphError {
+    #[error("Agent '{0}' is added more than once")]
+    DuplicateAgent(String),
+
+    #[error("Edge refers to unknown agent '{0}'")]
+    UnknownAgent(String),
+
+    #[error("Agents depend on each other in a cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("Agent '{agent}' failed: {source}")]
+    AgentFailed {
+        agent: String,
+        #[source]
+        source: LlmError,
+    },
 }
@@ -5,14 +5,26 @@ use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::RwLock;
 
 use crate::agent::SubAgentResult;
-use crate::tool::ToolResult;
+use crate::error::LlmError;
+use crate::llm::{ContentBlock, Request, StopReason, Usage, estimate_tokens};
+use crate::tool::{ToolProgress, ToolResult};
+
+mod process;
+
+pub use process::ProcessHook;
 
 /// Events that can trigger hooks.
-#[derive(Debug, Clone)]
+///
+/// Serializes to JSON tagged by `hook_event_name`, e.g.
+/// `{"hook_event_name": "PreToolUse", "tool_name": "bash", "input": {...}}`.
+/// The `Stop` event's `continue_loop` flag is not serialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "hook_event_name")]
 pub enum HookEvent {
     /// Fired before a tool is executed.
     PreToolUse { tool_name: String, input: Value },
@@ -50,7 +62,8 @@ pub enum HookEvent {
         session_id: String,
         /// Error message if the session ended with an error.
         error: Option<String>,
-        /// Reason for ending: "complete", "error", or "cancelled"
+        /// Reason for ending: "complete", "error", "cancelled",
+        /// "iteration_limit", "budget", "timeout" or "refused"
         reason: String,
     },
 
@@ -61,6 +74,7 @@ pub enum HookEvent {
         final_text: String,
         /// Set to true to request the agent loop continue.
         /// Uses Arc<AtomicBool> for interior mutability across async hooks.
+        #[serde(skip)]
         continue_loop: Arc<AtomicBool>,
     },
 
@@ -80,6 +94,43 @@ pub enum HookEvent {
         error: Option<String>,
     },
 
+    /// Fired before each LLM call. Hooks may `Block` it, which ends the
+    /// agent's run with `LlmError::Blocked`.
+    ///
+    /// Hooks may also return `Transform` with a serialized [`Request`] to
+    /// replace the request for this call, e.g. to add to the system prompt
+    /// or change `max_tokens`. Later hooks see the replacement, and the last
+    /// transform wins. `request_summary` describes the request as built.
+    /// [`ProcessHook`] leaves `request` out unless asked to send it.
+    LlmRequest {
+        agent_id: String,
+        model: String,
+        request_summary: RequestSummary,
+        request: Request,
+    },
+
+    /// Fired when an LLM call returns, with the model that served it and
+    /// what it cost.
+    LlmResponse {
+        agent_id: String,
+        model: String,
+        usage: Usage,
+        stop_reason: StopReason,
+    },
+
+    /// Fired after each LLM response is received, before anything else sees it.
+    ///
+    /// Hooks may return `Transform` with a JSON array of content blocks to
+    /// rewrite the response, e.g. to redact it. The rewritten content is what
+    /// `ResponseReceived`, the agent's result and its history see. It doesn't
+    /// change what the provider returned or what was sent to it, and
+    /// `StreamDelta` events have already carried the original text. Leave
+    /// `ToolUse` blocks in place unless the calls should not run.
+    PostResponse {
+        agent_id: String,
+        content: Vec<ContentBlock>,
+    },
+
     /// Fired after each LLM response is received.
     /// Enables streaming text and tool use notifications to callbacks.
     ResponseReceived {
@@ -93,24 +144,73 @@ pub enum HookEvent {
     /// Fired for each text token during streaming.
     StreamDelta { agent_id: String, text: String },
 
+    /// Fired for each tool input fragment during streaming.
+    /// `partial_json` is the input accumulated so far, not just the new fragment.
+    ToolInputDelta {
+        agent_id: String,
+        tool_use_id: String,
+        tool_name: String,
+        partial_json: String,
+    },
+
     /// Fired when a MessageDelta event is received during streaming,
     /// carrying token usage counts.
     StreamUsage {
         agent_id: String,
         usage: crate::llm::Usage,
     },
+
+    /// Fired for each progress update from a running tool. The last one
+    /// for a call has `done` set and carries the result, so a UI can stop
+    /// its spinner; tools that don't report progress send only that one.
+    ToolProgress {
+        agent_id: String,
+        tool_use_id: String,
+        tool_name: String,
+        progress: ToolProgress,
+    },
+}
+
+/// The shape of an LLM request, for auditing without its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestSummary {
+    /// Messages in the conversation sent.
+    pub messages: usize,
+    /// Names of the tools offered.
+    pub tools: Vec<String>,
+    pub max_tokens: Option<u32>,
+    /// Rough input size; see [`estimate_tokens`].
+    pub estimated_input_tokens: usize,
+}
+
+impl RequestSummary {
+    /// Summarize `req`.
+    pub fn of(req: &Request) -> Self {
+        Self {
+            messages: req.messages.len(),
+            tools: req.tools.iter().map(|t| t.name.clone()).collect(),
+            max_tokens: req.max_tokens,
+            estimated_input_tokens: estimate_tokens(req),
+        }
+    }
 }
 
 /// Actions a hook can return to control execution flow.
-#[derive(Debug, Clone)]
+///
+/// Serializes as `{"action": "continue"}`, `{"action": "block", "value": "reason"}`
+/// or `{"action": "transform", "value": {...}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", content = "value", rename_all = "snake_case")]
 pub enum HookAction {
     /// Continue with normal execution.
     Continue,
 
-    /// Block the action with a message (only valid for Pre* events).
+    /// Block the action with a message (only valid for Pre* events and
+    /// LlmRequest).
     Block(String),
 
-    /// Transform the input (only valid for PreToolUse).
+    /// Transform the input (only valid for PreToolUse), the response
+    /// content (for PostResponse), or the request (for LlmRequest).
     Transform(Value),
 }
 
@@ -127,7 +227,8 @@ pub trait Hook: Send + Sync {
     ///
     /// Return `Ok(HookAction::Continue)` to proceed normally.
     /// Return `Ok(HookAction::Block(msg))` to block Pre* events.
-    /// Return `Ok(HookAction::Transform(value))` to modify PreToolUse input.
+    /// Return `Ok(HookAction::Transform(value))` to modify PreToolUse input,
+    /// PostResponse content or the LlmRequest request.
     /// Return `Err` to signal a hook failure (treated as Block).
     async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error>;
 
@@ -166,7 +267,8 @@ impl HookRegistry {
     ///
     /// Returns the final action after all hooks have processed.
     /// If any hook blocks, returns Block immediately.
-    /// If any hook transforms, uses the transformed value for subsequent hooks.
+    /// If any hook transforms, uses the transformed value for subsequent hooks,
+    /// so the last transform wins.
     pub async fn fire(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
         let hooks = self.hooks.read().await;
         let mut current_event = event.clone();
@@ -182,16 +284,42 @@ impl HookRegistry {
                 HookAction::Block(msg) => {
                     return Ok(HookAction::Block(msg));
                 }
-                HookAction::Transform(new_input) => {
-                    // Transform only valid for PreToolUse events
-                    if let HookEvent::PreToolUse { tool_name, .. } = &current_event {
+                HookAction::Transform(new_value) => match &current_event {
+                    HookEvent::PreToolUse { tool_name, .. } => {
                         current_event = HookEvent::PreToolUse {
                             tool_name: tool_name.clone(),
-                            input: new_input.clone(),
+                            input: new_value.clone(),
+                        };
+                        final_action = HookAction::Transform(new_value);
+                    }
+                    HookEvent::PostResponse { agent_id, .. } => {
+                        let content = serde_json::from_value(new_value.clone()).map_err(|e| {
+                            anyhow::anyhow!("PostResponse transform is not content blocks: {}", e)
+                        })?;
+                        current_event = HookEvent::PostResponse {
+                            agent_id: agent_id.clone(),
+                            content,
+                        };
+                        final_action = HookAction::Transform(new_value);
+                    }
+                    HookEvent::LlmRequest { agent_id, .. } => {
+                        let request: Request =
+                            serde_json::from_value(new_value.clone()).map_err(|e| {
+                                LlmError::InvalidRequest(format!(
+                                    "LlmRequest transform is not a request: {}",
+                                    e
+                                ))
+                            })?;
+                        current_event = HookEvent::LlmRequest {
+                            agent_id: agent_id.clone(),
+                            model: request.model.clone(),
+                            request_summary: RequestSummary::of(&request),
+                            request,
                         };
-                        final_action = HookAction::Transform(new_input);
-                    } else {
-                        // Transform action returned for non-PreToolUse event - this is a bug
+                        final_action = HookAction::Transform(new_value);
+                    }
+                    _ => {
+                        // Transform action returned for another event - this is a bug
                         let event_type = match &current_event {
                             HookEvent::PreToolUse { .. } => "PreToolUse",
                             HookEvent::PostToolUse { .. } => "PostToolUse",
@@ -203,16 +331,21 @@ impl HookRegistry {
                             HookEvent::Stop { .. } => "Stop",
                             HookEvent::SubagentStart { .. } => "SubagentStart",
                             HookEvent::SubagentStop { .. } => "SubagentStop",
+                            HookEvent::LlmRequest { .. } => "LlmRequest",
+                            HookEvent::LlmResponse { .. } => "LlmResponse",
+                            HookEvent::PostResponse { .. } => "PostResponse",
                             HookEvent::ResponseReceived { .. } => "ResponseReceived",
                             HookEvent::StreamDelta { .. } => "StreamDelta",
+                            HookEvent::ToolInputDelta { .. } => "ToolInputDelta",
                             HookEvent::StreamUsage { .. } => "StreamUsage",
+                            HookEvent::ToolProgress { .. } => "ToolProgress",
                         };
                         return Err(anyhow::anyhow!(
-                            "HookAction::Transform is only valid for PreToolUse events, got {}",
+                            "HookAction::Transform is only valid for PreToolUse, PostResponse and LlmRequest events, got {}",
                             event_type
                         ));
                     }
-                }
+                },
             }
         }
 
@@ -469,15 +602,26 @@ mod tests {
                 HookEvent::Stop { session_id, .. } => format!("stop_event:{}", session_id),
                 HookEvent::SubagentStart { child_id, .. } => format!("subagent_start:{}", child_id),
                 HookEvent::SubagentStop { child_id, .. } => format!("subagent_stop:{}", child_id),
+                HookEvent::LlmRequest { model, .. } => format!("llm_request:{}", model),
+                HookEvent::LlmResponse { model, .. } => format!("llm_response:{}", model),
+                HookEvent::PostResponse { agent_id, .. } => {
+                    format!("post_response:{}", agent_id)
+                }
                 HookEvent::ResponseReceived { agent_id, .. } => {
                     format!("response:{}", agent_id)
                 }
                 HookEvent::StreamDelta { agent_id, text } => {
                     format!("stream_delta:{}:{}", agent_id, text)
                 }
+                HookEvent::ToolInputDelta { tool_name, .. } => {
+                    format!("tool_input_delta:{}", tool_name)
+                }
                 HookEvent::StreamUsage { agent_id, .. } => {
                     format!("stream_usage:{}", agent_id)
                 }
+                HookEvent::ToolProgress { tool_name, .. } => {
+                    format!("tool_progress:{}", tool_name)
+                }
             };
             self.events.write().await.push(msg);
             Ok(HookAction::Continue)
@@ -647,6 +791,177 @@ mod tests {
         assert!(err_msg.contains("Iteration"));
     }
 
+    fn one_of_each_event() -> Vec<HookEvent> {
+        let result = SubAgentResult {
+            agent_id: "agent-1".into(),
+            content: "done".into(),
+            tool_use_count: 2,
+            usage: crate::llm::Usage::default(),
+            usage_total: crate::llm::UsageTracker::new(),
+            iterations: 3,
+            stop_reason: crate::agent::AgentStopReason::Completed,
+            files_changed: vec!["src/lib.rs".into()],
+        };
+        vec![
+            HookEvent::PreToolUse {
+                tool_name: "bash".into(),
+                input: serde_json::json!({"command": "ls"}),
+            },
+            HookEvent::PostToolUse {
+                tool_name: "bash".into(),
+                tool_use_id: "toolu_1".into(),
+                input: serde_json::json!({"command": "ls"}),
+                result: ToolResult::text("a.txt").with_metadata("exit_code", 0),
+            },
+            HookEvent::AgentStart {
+                agent_id: "agent-1".into(),
+                task: "list files".into(),
+            },
+            HookEvent::AgentStop {
+                agent_id: "agent-1".into(),
+                result,
+            },
+            HookEvent::Iteration {
+                agent_id: "agent-1".into(),
+                iteration: 1,
+            },
+            HookEvent::SessionStart {
+                session_id: "sess-1".into(),
+                source: "run".into(),
+                prompt: "hello".into(),
+            },
+            HookEvent::SessionEnd {
+                session_id: "sess-1".into(),
+                error: Some("boom".into()),
+                reason: "error".into(),
+            },
+            HookEvent::Stop {
+                session_id: "sess-1".into(),
+                final_text: "done".into(),
+                continue_loop: Arc::new(AtomicBool::new(true)),
+            },
+            HookEvent::SubagentStart {
+                parent_id: "parent-1".into(),
+                child_id: "child-1".into(),
+                name: "researcher".into(),
+            },
+            HookEvent::SubagentStop {
+                parent_id: "parent-1".into(),
+                child_id: "child-1".into(),
+                name: "researcher".into(),
+                error: None,
+            },
+            HookEvent::LlmRequest {
+                agent_id: "agent-1".into(),
+                model: "claude-sonnet".into(),
+                request_summary: RequestSummary {
+                    messages: 3,
+                    tools: vec!["bash".into()],
+                    max_tokens: Some(4096),
+                    estimated_input_tokens: 120,
+                },
+                request: Request::new("claude-sonnet").max_tokens(4096),
+            },
+            HookEvent::LlmResponse {
+                agent_id: "agent-1".into(),
+                model: "claude-sonnet".into(),
+                usage: crate::llm::Usage::default(),
+                stop_reason: StopReason::ToolUse,
+            },
+            HookEvent::ResponseReceived {
+                agent_id: "agent-1".into(),
+                text: "let me look".into(),
+                tool_uses: vec![(
+                    "bash".into(),
+                    "toolu_1".into(),
+                    serde_json::json!({"command": "ls"}),
+                )],
+            },
+            HookEvent::StreamDelta {
+                agent_id: "agent-1".into(),
+                text: "hel".into(),
+            },
+            HookEvent::ToolInputDelta {
+                agent_id: "agent-1".into(),
+                tool_use_id: "toolu_1".into(),
+                tool_name: "bash".into(),
+                partial_json: "{\"comm".into(),
+            },
+            HookEvent::StreamUsage {
+                agent_id: "agent-1".into(),
+                usage: crate::llm::Usage::default(),
+            },
+            HookEvent::ToolProgress {
+                agent_id: "agent-1".into(),
+                tool_use_id: "toolu_1".into(),
+                tool_name: "bash".into(),
+                progress: ToolProgress::update("building").with_fraction(0.5),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_hook_events_round_trip_through_json() {
+        for event in one_of_each_event() {
+            let json = serde_json::to_value(&event).unwrap();
+            let decoded: HookEvent = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_hook_event_wire_format() {
+        let event = HookEvent::PreToolUse {
+            tool_name: "bash".into(),
+            input: serde_json::json!({"command": "ls"}),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "hook_event_name": "PreToolUse",
+                "tool_name": "bash",
+                "input": {"command": "ls"}
+            })
+        );
+
+        // The continue flag is process-local, so it is not part of the wire format
+        let event = HookEvent::Stop {
+            session_id: "sess-1".into(),
+            final_text: "done".into(),
+            continue_loop: Arc::new(AtomicBool::new(true)),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert!(json.get("continue_loop").is_none());
+        let decoded: HookEvent = serde_json::from_value(json).unwrap();
+        let HookEvent::Stop { continue_loop, .. } = decoded else {
+            panic!("expected Stop");
+        };
+        assert!(!continue_loop.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_hook_action_wire_format() {
+        let cases = [
+            (
+                HookAction::Continue,
+                serde_json::json!({"action": "continue"}),
+            ),
+            (
+                HookAction::Block("no".into()),
+                serde_json::json!({"action": "block", "value": "no"}),
+            ),
+            (
+                HookAction::Transform(serde_json::json!({"x": 1})),
+                serde_json::json!({"action": "transform", "value": {"x": 1}}),
+            ),
+        ];
+        for (action, json) in cases {
+            assert_eq!(serde_json::to_value(&action).unwrap(), json);
+            let decoded: HookAction = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        }
+    }
+
     #[tokio::test]
     async fn test_session_start_event() {
         let registry = HookRegistry::new();
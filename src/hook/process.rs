@@ -0,0 +1,273 @@
+// ABOUTME: ProcessHook - forwards hook events to an external command over stdio.
+// ABOUTME: Lets hooks run sandboxed or be written in another language.
+
+use std::process::Stdio;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::{Hook, HookAction, HookEvent};
+
+/// Default time a hook process may run before it is killed.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A hook that runs an external command for each event.
+///
+/// The event is written to the command's stdin as JSON (see [`HookEvent`]) and
+/// stdin is closed. The command answers on stdout with a [`HookAction`], e.g.
+/// `{"action": "block", "value": "not allowed"}`. Empty output means continue.
+/// For `Stop` events, `{"continue": true}` asks the agent loop to keep going.
+///
+/// `LlmRequest` events are sent without their `request`, which carries the
+/// whole conversation; the `request_summary` describes it. Use
+/// [`with_full_request`](Self::with_full_request) to send it too.
+///
+/// A non-zero exit status or a timeout is a hook failure, reported with the
+/// command's stderr.
+pub struct ProcessHook {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    full_request: bool,
+}
+
+impl ProcessHook {
+    /// Create a hook that runs `program` for every event.
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            full_request: false,
+        }
+    }
+
+    /// Set the arguments passed to the program.
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set how long the program may run per event (default 60 seconds).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Include the full `request` in `LlmRequest` events, e.g. for a hook
+    /// that transforms it.
+    pub fn with_full_request(mut self) -> Self {
+        self.full_request = true;
+        self
+    }
+
+    async fn run(&self, input: &[u8]) -> Result<String, anyhow::Error> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn hook '{}': {}", self.program, e))?;
+
+        let stdin = child.stdin.take();
+        let write_input = async {
+            if let Some(mut stdin) = stdin {
+                // The program may exit without reading its input; that's not an error
+                match stdin.write_all(input).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+                    _ => {}
+                }
+            }
+            Ok(())
+        };
+        // Write stdin while draining stdout and stderr, so a program that
+        // writes before reading can't block on a full pipe
+        let exchange = async {
+            let (written, output) = tokio::join!(write_input, child.wait_with_output());
+            written?;
+            output
+        };
+
+        let output = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Hook '{}' timed out after {}ms",
+                    self.program,
+                    self.timeout.as_millis()
+                )
+            })??;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Hook '{}' exited with code {}: {}",
+                self.program,
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl Hook for ProcessHook {
+    async fn on_event(&self, event: &HookEvent) -> Result<HookAction, anyhow::Error> {
+        let mut input = serde_json::to_value(event)?;
+        if !self.full_request
+            && let HookEvent::LlmRequest { .. } = event
+            && let Some(fields) = input.as_object_mut()
+        {
+            fields.remove("request");
+        }
+        let input = serde_json::to_vec(&input)?;
+        let stdout = self.run(&input).await?;
+        if stdout.trim().is_empty() {
+            return Ok(HookAction::Continue);
+        }
+
+        let response: Value = serde_json::from_str(stdout.trim())
+            .map_err(|e| anyhow::anyhow!("Hook '{}' returned invalid JSON: {}", self.program, e))?;
+
+        if let HookEvent::Stop { continue_loop, .. } = event
+            && response.get("continue").and_then(Value::as_bool) == Some(true)
+        {
+            continue_loop.store(true, Ordering::SeqCst);
+        }
+
+        if response.get("action").is_none() {
+            return Ok(HookAction::Continue);
+        }
+        serde_json::from_value(response).map_err(|e| {
+            anyhow::anyhow!("Hook '{}' returned an invalid action: {}", self.program, e)
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    fn shell_hook(script: &str) -> ProcessHook {
+        ProcessHook::new("sh").with_args(["-c", script])
+    }
+
+    fn pre_tool_use(tool_name: &str) -> HookEvent {
+        HookEvent::PreToolUse {
+            tool_name: tool_name.into(),
+            input: serde_json::json!({"command": "ls"}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_continue_and_block() {
+        // Blocks only when the event on stdin is a PreToolUse for `rm`
+        let hook = shell_hook(
+            r#"if grep -q '"tool_name":"rm"'; then
+                 echo '{"action": "block", "value": "rm is not allowed"}'
+               else
+                 echo '{"action": "continue"}'
+               fi"#,
+        );
+
+        let action = hook.on_event(&pre_tool_use("ls")).await.unwrap();
+        assert!(matches!(action, HookAction::Continue));
+
+        let action = hook.on_event(&pre_tool_use("rm")).await.unwrap();
+        assert!(matches!(action, HookAction::Block(msg) if msg == "rm is not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_transform_and_empty_output() {
+        let hook =
+            shell_hook(r#"cat > /dev/null; echo '{"action": "transform", "value": {"x": 1}}'"#);
+        let action = hook.on_event(&pre_tool_use("ls")).await.unwrap();
+        assert!(matches!(action, HookAction::Transform(v) if v == serde_json::json!({"x": 1})));
+
+        let hook = shell_hook("cat > /dev/null");
+        let action = hook.on_event(&pre_tool_use("ls")).await.unwrap();
+        assert!(matches!(action, HookAction::Continue));
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_sends_full_request_only_when_asked() {
+        // Blocks when the event on stdin carries the request itself
+        let script = r#"if grep -q '"request":'; then
+                 echo '{"action": "block", "value": "full"}'
+               fi"#;
+        let request = crate::llm::Request::new("test-model");
+        let event = HookEvent::LlmRequest {
+            agent_id: "agent-1".into(),
+            model: "test-model".into(),
+            request_summary: crate::hook::RequestSummary::of(&request),
+            request,
+        };
+
+        let action = shell_hook(script).on_event(&event).await.unwrap();
+        assert!(matches!(action, HookAction::Continue));
+
+        let action = shell_hook(script)
+            .with_full_request()
+            .on_event(&event)
+            .await
+            .unwrap();
+        assert!(matches!(action, HookAction::Block(msg) if msg == "full"));
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_stop_continue_signal() {
+        let hook = shell_hook(r#"cat > /dev/null; echo '{"continue": true}'"#);
+        let continue_loop = Arc::new(AtomicBool::new(false));
+        let event = HookEvent::Stop {
+            session_id: "sess-1".into(),
+            final_text: "done".into(),
+            continue_loop: continue_loop.clone(),
+        };
+
+        let action = hook.on_event(&event).await.unwrap();
+        assert!(matches!(action, HookAction::Continue));
+        assert!(continue_loop.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_writes_before_reading_large_input() {
+        // Fills the stderr pipe before reading a stdin larger than a pipe buffer
+        let hook = shell_hook(
+            r#"head -c 200000 /dev/zero >&2; cat > /dev/null; echo '{"action": "continue"}'"#,
+        )
+        .with_timeout(Duration::from_secs(10));
+        let event = HookEvent::PreToolUse {
+            tool_name: "write_file".into(),
+            input: serde_json::json!({"content": "x".repeat(200_000)}),
+        };
+
+        let action = hook.on_event(&event).await.unwrap();
+        assert!(matches!(action, HookAction::Continue));
+    }
+
+    #[tokio::test]
+    async fn test_process_hook_failure_and_timeout() {
+        let hook = shell_hook("echo 'policy script broke' >&2; exit 3");
+        let err = hook.on_event(&pre_tool_use("ls")).await.unwrap_err();
+        assert!(err.to_string().contains("exited with code 3"));
+        assert!(err.to_string().contains("policy script broke"));
+
+        let hook = shell_hook("sleep 5").with_timeout(Duration::from_millis(100));
+        let err = hook.on_event(&pre_tool_use("ls")).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+}
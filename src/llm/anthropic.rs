@@ -1,8 +1,16 @@
 // ABOUTME: Anthropic Claude API client implementation.
 // ABOUTME: Implements LlmClient trait for Claude models.
 
+use super::body::{DEFAULT_MAX_RESPONSE_BYTES, read_json, read_text};
 use super::client::StreamEvent;
-use super::{ContentBlock, Message, Request, Response, StopReason, ToolDefinition, Usage};
+use super::http::default_http_client;
+use super::payload;
+use super::retry::{RetryPolicy, send_with_retry};
+use super::stream_accumulator::with_finished_blocks;
+use super::{
+    Citation, ContentBlock, ImageSource, Message, Request, Response, StopReason, ToolChoice,
+    ToolDefinition, Usage,
+};
 use crate::error::LlmError;
 use async_trait::async_trait;
 use futures::Stream;
@@ -19,20 +27,80 @@ pub struct AnthropicRequest {
     pub messages: Vec<AnthropicMessage>,
     pub max_tokens: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub system: Option<String>,
+    pub system: Option<AnthropicSystem>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<AnthropicThinking>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<AnthropicTool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<AnthropicToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
 }
 
+/// Extended thinking configuration.
+#[derive(Debug, Serialize)]
+pub struct AnthropicThinking {
+    #[serde(rename = "type")]
+    pub thinking_type: String,
+    pub budget_tokens: u32,
+}
+
+/// System prompt, sent as blocks when it carries a cache breakpoint.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum AnthropicSystem {
+    Text(String),
+    Blocks(Vec<AnthropicSystemBlock>),
+}
+
+/// A text block of the system prompt.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AnthropicSystemBlock {
+    #[serde(rename = "type")]
+    pub block_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<AnthropicCacheControl>,
+}
+
+/// Prompt caching breakpoint: everything up to and including the marked
+/// block is cached.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AnthropicCacheControl {
+    #[serde(rename = "type")]
+    pub cache_type: String,
+}
+
+impl AnthropicCacheControl {
+    /// The default short-lived cache.
+    pub fn ephemeral() -> Self {
+        Self {
+            cache_type: "ephemeral".into(),
+        }
+    }
+}
+
 /// Anthropic message format.
 #[derive(Debug, Serialize)]
 pub struct AnthropicMessage {
     pub role: String,
-    pub content: Vec<AnthropicContent>,
+    pub content: Vec<AnthropicMessageContent>,
+}
+
+/// A content block in a request message, with an optional cache breakpoint.
+#[derive(Debug, Serialize)]
+pub struct AnthropicMessageContent {
+    #[serde(flatten)]
+    pub content: AnthropicContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<AnthropicCacheControl>,
 }
 
 /// Anthropic content block.
@@ -41,6 +109,8 @@ pub struct AnthropicMessage {
 pub enum AnthropicContent {
     Text {
         text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        citations: Vec<AnthropicCitation>,
     },
     ToolUse {
         id: String,
@@ -49,10 +119,47 @@ pub enum AnthropicContent {
     },
     ToolResult {
         tool_use_id: String,
-        content: String,
+        content: AnthropicToolResultContent,
         #[serde(default)]
         is_error: bool,
     },
+    Image {
+        source: ImageSource,
+    },
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+    /// Server-side blocks, such as web search calls and their results,
+    /// which mux doesn't model. Dropped from responses.
+    #[serde(other)]
+    Other,
+}
+
+/// A citation attached to a text block. Only web search citations carry a
+/// URL; document citations are skipped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnthropicCitation {
+    #[serde(rename = "type")]
+    pub citation_type: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub cited_text: Option<String>,
+}
+
+/// Anthropic tool result content: a string, or blocks when it has images.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AnthropicToolResultContent {
+    Text(String),
+    Blocks(Vec<AnthropicContent>),
 }
 
 /// Anthropic tool definition.
@@ -61,6 +168,54 @@ pub struct AnthropicTool {
     pub name: String,
     pub description: String,
     pub input_schema: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<AnthropicCacheControl>,
+}
+
+/// Anthropic tool choice.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicToolChoice {
+    Auto,
+    /// Call at least one tool.
+    Any,
+    None,
+    Tool {
+        name: String,
+    },
+}
+
+impl From<&ToolChoice> for AnthropicToolChoice {
+    fn from(choice: &ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => AnthropicToolChoice::Auto,
+            ToolChoice::None => AnthropicToolChoice::None,
+            ToolChoice::Required => AnthropicToolChoice::Any,
+            ToolChoice::Tool(name) => AnthropicToolChoice::Tool { name: name.clone() },
+        }
+    }
+}
+
+/// Anthropic count_tokens request format: the message request without its
+/// generation parameters.
+#[derive(Debug, Serialize)]
+pub struct AnthropicCountTokensRequest {
+    pub model: String,
+    pub messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<AnthropicSystem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<AnthropicThinking>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<AnthropicToolChoice>,
+}
+
+/// Anthropic count_tokens response format.
+#[derive(Debug, Deserialize)]
+pub struct AnthropicCountTokensResponse {
+    pub input_tokens: usize,
 }
 
 /// Anthropic API response format.
@@ -69,6 +224,8 @@ pub struct AnthropicResponse {
     pub id: String,
     pub content: Vec<AnthropicContent>,
     pub stop_reason: String,
+    #[serde(default)]
+    pub stop_sequence: Option<String>,
     pub model: String,
     pub usage: AnthropicUsage,
 }
@@ -141,11 +298,15 @@ pub struct AnthropicMessageStart {
 pub enum AnthropicDelta {
     TextDelta { text: String },
     InputJsonDelta { partial_json: String },
+    ThinkingDelta { thinking: String },
+    SignatureDelta { signature: String },
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AnthropicMessageDeltaData {
     pub stop_reason: Option<String>,
+    #[serde(default)]
+    pub stop_sequence: Option<String>,
 }
 
 /// Client for the Anthropic Claude API.
@@ -154,6 +315,8 @@ pub struct AnthropicClient {
     api_key: String,
     base_url: String,
     http: reqwest::Client,
+    retry: RetryPolicy,
+    max_response_bytes: usize,
 }
 
 impl AnthropicClient {
@@ -162,7 +325,9 @@ impl AnthropicClient {
         Self {
             api_key: api_key.into(),
             base_url: ANTHROPIC_DEFAULT_BASE_URL.to_string(),
-            http: reqwest::Client::new(),
+            http: default_http_client(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            retry: RetryPolicy::none(),
         }
     }
 
@@ -172,6 +337,29 @@ impl AnthropicClient {
         self
     }
 
+    /// Send requests with `http`, e.g. one built from an
+    /// [`HttpConfig`](super::HttpConfig) with a proxy or custom
+    /// certificates. Defaults to a client with
+    /// [`HttpConfig::default`](super::HttpConfig::default) settings.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Retry rate-limited and overloaded requests according to `policy`.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Fail non-streaming responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
     /// Create a new Anthropic client from the ANTHROPIC_API_KEY environment variable.
     pub fn from_env() -> Result<Self, LlmError> {
         let api_key = std::env::var("ANTHROPIC_API_KEY").map_err(|_| LlmError::Api {
@@ -185,7 +373,10 @@ impl AnthropicClient {
 impl From<&ContentBlock> for AnthropicContent {
     fn from(block: &ContentBlock) -> Self {
         match block {
-            ContentBlock::Text { text } => AnthropicContent::Text { text: text.clone() },
+            ContentBlock::Text { text } => AnthropicContent::Text {
+                text: text.clone(),
+                citations: Vec::new(),
+            },
             ContentBlock::ToolUse { id, name, input } => AnthropicContent::ToolUse {
                 id: id.clone(),
                 name: name.clone(),
@@ -195,11 +386,36 @@ impl From<&ContentBlock> for AnthropicContent {
                 tool_use_id,
                 content,
                 is_error,
-            } => AnthropicContent::ToolResult {
-                tool_use_id: tool_use_id.clone(),
-                content: content.clone(),
-                is_error: *is_error,
+                images,
+            } => {
+                let content = if images.is_empty() {
+                    AnthropicToolResultContent::Text(content.clone())
+                } else {
+                    let text = (!content.is_empty()).then(|| AnthropicContent::Text {
+                        text: content.clone(),
+                        citations: Vec::new(),
+                    });
+                    let images = images.iter().map(|source| AnthropicContent::Image {
+                        source: source.clone(),
+                    });
+                    AnthropicToolResultContent::Blocks(text.into_iter().chain(images).collect())
+                };
+                AnthropicContent::ToolResult {
+                    tool_use_id: tool_use_id.clone(),
+                    content,
+                    is_error: *is_error,
+                }
+            }
+            ContentBlock::Image { source } => AnthropicContent::Image {
+                source: source.clone(),
+            },
+            ContentBlock::Thinking { text, signature } => AnthropicContent::Thinking {
+                thinking: text.clone(),
+                signature: signature.clone(),
             },
+            ContentBlock::RedactedThinking { data } => {
+                AnthropicContent::RedactedThinking { data: data.clone() }
+            }
         }
     }
 }
@@ -207,7 +423,7 @@ impl From<&ContentBlock> for AnthropicContent {
 impl From<AnthropicContent> for ContentBlock {
     fn from(content: AnthropicContent) -> Self {
         match content {
-            AnthropicContent::Text { text } => ContentBlock::Text { text },
+            AnthropicContent::Text { text, .. } => ContentBlock::Text { text },
             AnthropicContent::ToolUse { id, name, input } => {
                 ContentBlock::ToolUse { id, name, input }
             }
@@ -215,11 +431,40 @@ impl From<AnthropicContent> for ContentBlock {
                 tool_use_id,
                 content,
                 is_error,
-            } => ContentBlock::ToolResult {
-                tool_use_id,
-                content,
-                is_error,
+            } => {
+                let (content, images) = match content {
+                    AnthropicToolResultContent::Text(text) => (text, Vec::new()),
+                    AnthropicToolResultContent::Blocks(blocks) => {
+                        let mut texts = Vec::new();
+                        let mut images = Vec::new();
+                        for block in blocks {
+                            match block {
+                                AnthropicContent::Text { text, .. } => texts.push(text),
+                                AnthropicContent::Image { source } => images.push(source),
+                                _ => {}
+                            }
+                        }
+                        (texts.join("\n"), images)
+                    }
+                };
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                    images,
+                }
+            }
+            AnthropicContent::Image { source } => ContentBlock::Image { source },
+            AnthropicContent::Thinking {
+                thinking,
+                signature,
+            } => ContentBlock::Thinking {
+                text: thinking,
+                signature,
             },
+            AnthropicContent::RedactedThinking { data } => ContentBlock::RedactedThinking { data },
+            // Streamed server-side blocks become empty text, which is never sent back
+            AnthropicContent::Other => ContentBlock::text(""),
         }
     }
 }
@@ -231,57 +476,168 @@ impl From<&Message> for AnthropicMessage {
                 super::Role::User => "user".to_string(),
                 super::Role::Assistant => "assistant".to_string(),
             },
-            content: msg.content.iter().map(AnthropicContent::from).collect(),
+            content: payload::content_blocks_with_thinking(msg)
+                .map(|block| AnthropicMessageContent {
+                    content: AnthropicContent::from(block),
+                    cache_control: None,
+                })
+                .collect(),
         }
     }
 }
 
+impl AnthropicMessage {
+    /// Mark the last block as a cache breakpoint.
+    fn cache(mut self) -> Self {
+        if let Some(block) = self.content.last_mut() {
+            block.cache_control = Some(AnthropicCacheControl::ephemeral());
+        }
+        self
+    }
+}
+
 impl From<&ToolDefinition> for AnthropicTool {
     fn from(tool: &ToolDefinition) -> Self {
         AnthropicTool {
             name: tool.name.clone(),
             description: tool.description.clone(),
             input_schema: tool.input_schema.clone(),
+            cache_control: None,
         }
     }
 }
 
+impl AnthropicSystem {
+    /// The system prompt to send for `req`, as blocks if it should be cached.
+    fn from_request(req: &Request) -> Option<Self> {
+        let text = payload::system_prompt(req)?.into_owned();
+        Some(if req.cache_system {
+            AnthropicSystem::Blocks(vec![AnthropicSystemBlock {
+                block_type: "text".into(),
+                text,
+                cache_control: Some(AnthropicCacheControl::ephemeral()),
+            }])
+        } else {
+            AnthropicSystem::Text(text)
+        })
+    }
+}
+
 impl From<&Request> for AnthropicRequest {
     fn from(req: &Request) -> Self {
+        let mut tools: Vec<AnthropicTool> = req.tools.iter().map(AnthropicTool::from).collect();
+        if req.cache_tools
+            && let Some(tool) = tools.last_mut()
+        {
+            tool.cache_control = Some(AnthropicCacheControl::ephemeral());
+        }
+
         AnthropicRequest {
             model: req.model.clone(),
-            messages: req.messages.iter().map(AnthropicMessage::from).collect(),
-            max_tokens: req.max_tokens.unwrap_or(4096),
-            system: req.system.clone(),
+            messages: req
+                .messages
+                .iter()
+                .enumerate()
+                .filter(|(_, msg)| payload::has_content(msg))
+                .map(|(i, msg)| {
+                    let message = AnthropicMessage::from(msg);
+                    if req.cache_messages.contains(&i) {
+                        message.cache()
+                    } else {
+                        message
+                    }
+                })
+                .collect(),
+            max_tokens: req.max_tokens_with_thinking(4096),
+            system: AnthropicSystem::from_request(req),
             temperature: req.temperature,
-            tools: req.tools.iter().map(AnthropicTool::from).collect(),
+            top_p: req.top_p,
+            stop_sequences: req.stop_sequences.clone(),
+            thinking: req.thinking.map(|budget_tokens| AnthropicThinking {
+                thinking_type: "enabled".into(),
+                budget_tokens,
+            }),
+            tools,
+            tool_choice: payload::tool_choice(req).map(AnthropicToolChoice::from),
             stream: None,
         }
     }
 }
 
-fn parse_stop_reason(s: &str) -> StopReason {
-    match s {
-        "end_turn" => StopReason::EndTurn,
-        "tool_use" => StopReason::ToolUse,
-        "max_tokens" => StopReason::MaxTokens,
+impl From<&Request> for AnthropicCountTokensRequest {
+    fn from(req: &Request) -> Self {
+        // Built from the message request so both count the same content
+        let AnthropicRequest {
+            model,
+            messages,
+            system,
+            thinking,
+            tools,
+            tool_choice,
+            ..
+        } = AnthropicRequest::from(req);
+        AnthropicCountTokensRequest {
+            model,
+            messages,
+            system,
+            thinking,
+            tools,
+            tool_choice,
+        }
+    }
+}
+
+fn parse_stop_reason(s: &str, stop_sequence: Option<String>) -> StopReason {
+    match (s, stop_sequence) {
+        ("end_turn", _) => StopReason::EndTurn,
+        ("tool_use", _) => StopReason::ToolUse,
+        ("max_tokens", _) => StopReason::MaxTokens,
+        ("stop_sequence", Some(sequence)) => StopReason::StopSequence(sequence),
+        ("refusal", _) => StopReason::Refusal,
         _ => StopReason::EndTurn,
     }
 }
 
 impl From<AnthropicResponse> for Response {
     fn from(resp: AnthropicResponse) -> Self {
+        let citations = resp
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                AnthropicContent::Text { citations, .. } => Some(citations),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|citation| {
+                Some(Citation {
+                    url: citation.url.clone()?,
+                    title: citation.title.clone(),
+                    cited_text: citation.cited_text.clone(),
+                })
+            })
+            .collect();
+
         Response {
             id: resp.id,
-            content: resp.content.into_iter().map(ContentBlock::from).collect(),
-            stop_reason: parse_stop_reason(&resp.stop_reason),
-            model: resp.model,
+            content: resp
+                .content
+                .into_iter()
+                .filter(|content| !matches!(content, AnthropicContent::Other))
+                .map(ContentBlock::from)
+                .collect(),
+            stop_reason: parse_stop_reason(&resp.stop_reason, resp.stop_sequence),
+            model: resp.model.clone(),
+            served_model: Some(resp.model),
+            system_fingerprint: None,
             usage: Usage {
                 input_tokens: resp.usage.input_tokens,
                 output_tokens: resp.usage.output_tokens,
                 cache_read_tokens: resp.usage.cache_read_input_tokens.unwrap_or(0),
                 cache_write_tokens: resp.usage.cache_creation_input_tokens.unwrap_or(0),
             },
+            attempts: 1,
+            citations,
+            invalid_tool_inputs: Vec::new(),
         }
     }
 }
@@ -318,16 +674,26 @@ fn parse_sse_event(event_str: &str) -> Option<StreamEvent> {
                 index,
                 partial_json,
             }),
+            AnthropicDelta::ThinkingDelta { thinking } => Some(StreamEvent::ThinkingDelta {
+                index,
+                text: thinking,
+            }),
+            AnthropicDelta::SignatureDelta { signature } => {
+                Some(StreamEvent::SignatureDelta { index, signature })
+            }
         },
         AnthropicStreamEvent::ContentBlockStop { index } => {
-            Some(StreamEvent::ContentBlockStop { index })
+            Some(StreamEvent::ContentBlockStop { index, block: None })
         }
         AnthropicStreamEvent::MessageDelta { delta, usage } => Some(StreamEvent::MessageDelta {
-            stop_reason: delta.stop_reason.map(|s| parse_stop_reason(&s)),
+            stop_reason: delta
+                .stop_reason
+                .map(|s| parse_stop_reason(&s, delta.stop_sequence)),
             usage: Usage {
                 input_tokens: usage.input_tokens,
                 output_tokens: usage.output_tokens,
-                ..Default::default()
+                cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+                cache_write_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
             },
         }),
         AnthropicStreamEvent::MessageStop => Some(StreamEvent::MessageStop),
@@ -342,57 +708,102 @@ fn parse_sse_event(event_str: &str) -> Option<StreamEvent> {
 #[async_trait]
 impl super::client::LlmClient for AnthropicClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        req.validate()?;
+        req.validate_thinking()?;
+
         let anthropic_req = AnthropicRequest::from(req);
 
         let url = format!("{}/v1/messages", self.base_url);
-        let response = self
-            .http
-            .post(&url)
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", ANTHROPIC_VERSION)
-            .header("content-type", "application/json")
-            .json(&anthropic_req)
-            .send()
-            .await?;
+        let (response, attempts) = send_with_retry(&self.retry, || {
+            self.http
+                .post(&url)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .header("content-type", "application/json")
+                .json(&anthropic_req)
+        })
+        .await?;
 
         let status = response.status();
         if !status.is_success() {
-            let error: AnthropicError = response.json().await?;
+            let error: AnthropicError = read_json(response, self.max_response_bytes).await?;
             return Err(LlmError::Api {
                 status: status.as_u16(),
                 message: error.error.message,
             });
         }
 
-        let anthropic_resp: AnthropicResponse = response.json().await?;
-        Ok(Response::from(anthropic_resp))
+        let anthropic_resp: AnthropicResponse =
+            read_json(response, self.max_response_bytes).await?;
+        let mut response = Response::from(anthropic_resp);
+        response.model = req.model.clone();
+        response.attempts = attempts;
+        Ok(response)
+    }
+
+    /// Count input tokens exactly via the `/v1/messages/count_tokens` endpoint.
+    async fn count_tokens(&self, req: &Request) -> Result<usize, LlmError> {
+        let count_req = AnthropicCountTokensRequest::from(req);
+
+        let url = format!("{}/v1/messages/count_tokens", self.base_url);
+        let (response, _) = send_with_retry(&self.retry, || {
+            self.http
+                .post(&url)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .header("content-type", "application/json")
+                .json(&count_req)
+        })
+        .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error: AnthropicError = read_json(response, self.max_response_bytes).await?;
+            return Err(LlmError::Api {
+                status: status.as_u16(),
+                message: error.error.message,
+            });
+        }
+
+        let count: AnthropicCountTokensResponse =
+            read_json(response, self.max_response_bytes).await?;
+        Ok(count.input_tokens)
+    }
+
+    fn supports_tool_result_images(&self) -> bool {
+        true
     }
 
     fn create_message_stream(
         &self,
         req: &Request,
     ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+        let validation = req.validate().and_then(|()| req.validate_thinking());
         let mut anthropic_req = AnthropicRequest::from(req);
         anthropic_req.stream = Some(true);
 
         let api_key = self.api_key.clone();
         let base_url = self.base_url.clone();
         let http = self.http.clone();
+        let max_response_bytes = self.max_response_bytes;
+        let retry = self.retry.clone();
+
+        Box::pin(with_finished_blocks(async_stream::try_stream! {
+            validation?;
 
-        Box::pin(async_stream::try_stream! {
             let url = format!("{}/v1/messages", base_url);
-            let response = http
-                .post(&url)
-                .header("x-api-key", &api_key)
-                .header("anthropic-version", ANTHROPIC_VERSION)
-                .header("content-type", "application/json")
-                .json(&anthropic_req)
-                .send()
-                .await?;
+            let (response, _) = send_with_retry(&retry, || {
+                http.post(&url)
+                    .header("x-api-key", &api_key)
+                    .header("anthropic-version", ANTHROPIC_VERSION)
+                    .header("content-type", "application/json")
+                    .json(&anthropic_req)
+            })
+            .await?;
 
             let status = response.status();
             if !status.is_success() {
-                let error_text = response.text().await?;
+                let error_text = read_text(response, max_response_bytes).await?;
                 let error: AnthropicError = serde_json::from_str(&error_text)?;
                 Err(LlmError::Api {
                     status: status.as_u16(),
@@ -418,6 +829,6 @@ impl super::client::LlmClient for AnthropicClient {
                     }
                 }
             }
-        })
+        }))
     }
 }
@@ -2,6 +2,7 @@
 // ABOUTME: Verifies serialization matches Anthropic API format.
 
 use super::*;
+use crate::llm::test_server::serve_once;
 
 #[test]
 fn test_request_serialization() {
@@ -14,11 +15,26 @@ fn test_request_serialization() {
 
     assert_eq!(anthropic_req.model, "claude-sonnet-4-20250514");
     assert_eq!(anthropic_req.max_tokens, 1024);
-    assert_eq!(anthropic_req.system, Some("You are helpful".to_string()));
+    assert_eq!(
+        anthropic_req.system,
+        Some(AnthropicSystem::Text("You are helpful".to_string()))
+    );
     assert_eq!(anthropic_req.messages.len(), 1);
     assert_eq!(anthropic_req.messages[0].role, "user");
 }
 
+#[test]
+fn test_instruction_layers_collapse_into_system() {
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .system("Platform rules.")
+        .instruction(Instruction::developer("App rules."));
+
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(json["system"], "Platform rules.\n\n---\n\nApp rules.");
+    assert_eq!(json["messages"].as_array().unwrap().len(), 1);
+}
+
 #[test]
 fn test_request_json_format() {
     let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
@@ -53,6 +69,36 @@ fn test_tool_serialization() {
     assert!(json["input_schema"]["properties"]["name"].is_object());
 }
 
+#[test]
+fn test_tool_choice_serialization() {
+    let req = Request::new("m").tool(ToolDefinition {
+        name: "get_weather".to_string(),
+        description: "Get the weather".to_string(),
+        input_schema: serde_json::json!({"type": "object", "properties": {}}),
+    });
+
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert!(json.get("tool_choice").is_none());
+
+    for (choice, expected) in [
+        (ToolChoice::None, serde_json::json!({"type": "none"})),
+        (ToolChoice::Required, serde_json::json!({"type": "any"})),
+        (
+            ToolChoice::Tool("get_weather".into()),
+            serde_json::json!({"type": "tool", "name": "get_weather"}),
+        ),
+    ] {
+        let req = req.clone().tool_choice(choice);
+        let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+        assert_eq!(json["tool_choice"], expected);
+    }
+
+    // Nothing to choose from without tools
+    let req = Request::new("m").tool_choice(ToolChoice::None);
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert!(json.get("tool_choice").is_none());
+}
+
 #[test]
 fn test_response_deserialization() {
     let json = r#"{
@@ -72,6 +118,197 @@ fn test_response_deserialization() {
     assert_eq!(response.usage.input_tokens, 10);
 }
 
+#[test]
+fn test_stop_sequence_response() {
+    let json = r#"{
+        "id": "msg_789",
+        "content": [{"type": "text", "text": "1. First"}],
+        "stop_reason": "stop_sequence",
+        "stop_sequence": "\n2.",
+        "model": "claude-sonnet-4-20250514",
+        "usage": {"input_tokens": 10, "output_tokens": 5}
+    }"#;
+
+    let anthropic_resp: AnthropicResponse = serde_json::from_str(json).unwrap();
+    let response = Response::from(anthropic_resp);
+
+    assert_eq!(
+        response.stop_reason,
+        StopReason::StopSequence("\n2.".into())
+    );
+}
+
+#[test]
+fn test_refusal_response() {
+    let json = r#"{
+        "id": "msg_790",
+        "content": [],
+        "stop_reason": "refusal",
+        "model": "claude-sonnet-4-20250514",
+        "usage": {"input_tokens": 10, "output_tokens": 0}
+    }"#;
+
+    let anthropic_resp: AnthropicResponse = serde_json::from_str(json).unwrap();
+    let response = Response::from(anthropic_resp);
+
+    assert_eq!(response.stop_reason, StopReason::Refusal);
+}
+
+#[tokio::test]
+async fn test_stop_sequence_stream_delta() {
+    use crate::llm::LlmClient;
+    use crate::llm::test_server::{RecordedResponse, serve};
+    use futures::StreamExt;
+
+    let body = concat!(
+        "event: message_start\n",
+        "data: {\"type\": \"message_start\", \"message\": {\"id\": \"msg_1\", \"model\": \"claude-sonnet-4-20250514\"}}\n\n",
+        "event: message_delta\n",
+        "data: {\"type\": \"message_delta\", \"delta\": {\"stop_reason\": \"stop_sequence\", \"stop_sequence\": \"END\"}, \"usage\": {\"input_tokens\": 0, \"output_tokens\": 12}}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\": \"message_stop\"}\n\n",
+    );
+    let (base_url, _server) = serve(vec![RecordedResponse {
+        status: 200,
+        headers: vec![("content-type".into(), "text/event-stream".into())],
+        body: body.into(),
+    }])
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Count"))
+        .stop_sequences(["END"]);
+    let events: Vec<StreamEvent> = client
+        .create_message_stream(&req)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert!(events.iter().any(|e| matches!(
+        e,
+        StreamEvent::MessageDelta { stop_reason: Some(StopReason::StopSequence(s)), .. } if s == "END"
+    )));
+}
+
+#[test]
+fn test_cache_control_markers() {
+    use crate::llm::payload::audit::*;
+
+    let req = representative_request()
+        .cache_system(true)
+        .cache_tools(true)
+        .cache_message(1)
+        .cache_message(9);
+    let body = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+
+    let ephemeral = serde_json::json!({"type": "ephemeral"});
+    let cached = |block: &serde_json::Value| block.get("cache_control").is_some();
+    assert_eq!(
+        body["system"],
+        serde_json::json!([
+            {"type": "text", "text": "You are helpful.", "cache_control": ephemeral}
+        ])
+    );
+    assert_eq!(body["tools"][0]["cache_control"], ephemeral);
+
+    // Only the last block of a marked message carries the breakpoint
+    let messages = &body["messages"];
+    assert!(!cached(&messages[0]["content"][0]));
+    assert!(!cached(&messages[1]["content"][0]));
+    assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+    assert_eq!(messages[1]["content"][1]["cache_control"], ephemeral);
+    assert!(!cached(&messages[2]["content"][0]));
+
+    // Message indices refer to the request, even when empty messages are skipped
+    let req = sparse_request().cache_message(2);
+    let body = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    let messages = &body["messages"];
+    assert!(!cached(&messages[0]["content"][0]));
+    assert_eq!(messages[1]["content"][0]["text"], "Still there?");
+    assert_eq!(messages[1]["content"][0]["cache_control"], ephemeral);
+}
+
+#[tokio::test]
+async fn test_stream_reports_cache_usage() {
+    use crate::llm::LlmClient;
+    use crate::llm::test_server::{RecordedResponse, serve};
+    use futures::StreamExt;
+
+    let body = concat!(
+        "event: message_delta\n",
+        "data: {\"type\": \"message_delta\", \"delta\": {\"stop_reason\": \"end_turn\"}, \"usage\": {\"input_tokens\": 5, \"output_tokens\": 12, \"cache_read_input_tokens\": 900, \"cache_creation_input_tokens\": 100}}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\": \"message_stop\"}\n\n",
+    );
+    let (base_url, _server) = serve(vec![RecordedResponse {
+        status: 200,
+        headers: vec![("content-type".into(), "text/event-stream".into())],
+        body: body.into(),
+    }])
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hi"));
+    let events: Vec<StreamEvent> = client
+        .create_message_stream(&req)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert!(events.iter().any(|e| matches!(
+        e,
+        StreamEvent::MessageDelta { usage, .. }
+            if usage.cache_read_tokens == 900 && usage.cache_write_tokens == 100
+    )));
+}
+
+#[tokio::test]
+async fn test_stream_stop_carries_tool_input() {
+    use crate::llm::LlmClient;
+    use crate::llm::test_server::{RecordedResponse, serve};
+    use futures::StreamExt;
+
+    let body = concat!(
+        "event: content_block_start\n",
+        "data: {\"type\": \"content_block_start\", \"index\": 0, \"content_block\": {\"type\": \"tool_use\", \"id\": \"toolu_1\", \"name\": \"read_file\", \"input\": {}}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"input_json_delta\", \"partial_json\": \"{\\\"path\\\": \"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"input_json_delta\", \"partial_json\": \"\\\"src/lib.rs\\\"}\"}}\n\n",
+        "event: content_block_stop\n",
+        "data: {\"type\": \"content_block_stop\", \"index\": 0}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\": \"message_stop\"}\n\n",
+    );
+    let (base_url, _server) = serve(vec![RecordedResponse {
+        status: 200,
+        headers: vec![("content-type".into(), "text/event-stream".into())],
+        body: body.into(),
+    }])
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Read it"));
+    let events: Vec<StreamEvent> = client
+        .create_message_stream(&req)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    match &events[3] {
+        StreamEvent::ContentBlockStop {
+            index: 0,
+            block: Some(ContentBlock::ToolUse { id, name, input }),
+        } => {
+            assert_eq!(id, "toolu_1");
+            assert_eq!(name, "read_file");
+            assert_eq!(input, &serde_json::json!({"path": "src/lib.rs"}));
+        }
+        other => panic!("Expected finished tool use, got {:?}", other),
+    }
+}
+
 #[test]
 fn test_tool_use_response() {
     let json = r#"{
@@ -93,6 +330,49 @@ fn test_tool_use_response() {
     assert_eq!(response.tool_uses().len(), 1);
 }
 
+#[test]
+fn test_web_search_response_citations() {
+    let json = r#"{
+        "id": "msg_789",
+        "content": [
+            {"type": "server_tool_use", "id": "srvtoolu_1", "name": "web_search", "input": {"query": "rust 2024 edition"}},
+            {"type": "web_search_tool_result", "tool_use_id": "srvtoolu_1", "content": [
+                {"type": "web_search_result", "url": "https://blog.rust-lang.org/2025/02/20/Rust-1.85.0.html", "title": "Announcing Rust 1.85.0", "encrypted_content": "abc"}
+            ]},
+            {"type": "text", "text": "The 2024 edition shipped with Rust 1.85.", "citations": [
+                {
+                    "type": "web_search_result_location",
+                    "url": "https://blog.rust-lang.org/2025/02/20/Rust-1.85.0.html",
+                    "title": "Announcing Rust 1.85.0",
+                    "encrypted_index": "xyz",
+                    "cited_text": "We are excited to announce that the Rust 2024 edition is now stable!"
+                },
+                {"type": "char_location", "document_index": 0, "cited_text": "from a document"}
+            ]}
+        ],
+        "stop_reason": "end_turn",
+        "model": "claude-sonnet-4-20250514",
+        "usage": {"input_tokens": 20, "output_tokens": 15}
+    }"#;
+
+    let anthropic_resp: AnthropicResponse = serde_json::from_str(json).unwrap();
+    let response = Response::from(anthropic_resp);
+
+    assert_eq!(
+        response.citations(),
+        &[Citation {
+            url: "https://blog.rust-lang.org/2025/02/20/Rust-1.85.0.html".into(),
+            title: Some("Announcing Rust 1.85.0".into()),
+            cited_text: Some(
+                "We are excited to announce that the Rust 2024 edition is now stable!".into()
+            ),
+        }]
+    );
+    // Server-side search blocks are dropped from the content
+    assert_eq!(response.content.len(), 1);
+    assert_eq!(response.text(), "The 2024 edition shipped with Rust 1.85.");
+}
+
 #[test]
 fn test_tool_result_message() {
     let msg = Message::tool_results(vec![ContentBlock::tool_result("tu_1", "Hello, Alice!")]);
@@ -124,3 +404,568 @@ fn test_client_from_env_missing() {
         }
     }
 }
+
+#[test]
+fn test_count_tokens_request_format() {
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .system("You are helpful")
+        .max_tokens(1024);
+
+    let json = serde_json::to_value(AnthropicCountTokensRequest::from(&req)).unwrap();
+
+    assert_eq!(json["model"], "claude-sonnet-4-20250514");
+    assert_eq!(json["system"], "You are helpful");
+    assert_eq!(json["messages"][0]["content"][0]["text"], "Hello");
+    // count_tokens rejects generation parameters
+    assert!(json.get("max_tokens").is_none());
+    assert!(json.get("tools").is_none());
+}
+
+#[test]
+fn test_count_tokens_request_matches_message_request() {
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .system("You are helpful")
+        .tool(ToolDefinition {
+            name: "search".into(),
+            description: "Search".into(),
+            input_schema: serde_json::json!({"type": "object"}),
+        })
+        .tool_choice(ToolChoice::Required)
+        .thinking(2048);
+
+    let count = serde_json::to_value(AnthropicCountTokensRequest::from(&req)).unwrap();
+    let message = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+
+    for field in [
+        "model",
+        "messages",
+        "system",
+        "thinking",
+        "tools",
+        "tool_choice",
+    ] {
+        assert_eq!(count[field], message[field], "{}", field);
+        assert!(!count[field].is_null(), "{}", field);
+    }
+}
+
+#[tokio::test]
+async fn test_count_tokens_retries_rate_limit() {
+    use crate::llm::LlmClient;
+    use crate::llm::RetryPolicy;
+    use crate::llm::test_server::{RecordedResponse, serve};
+
+    let (base_url, server) = serve(vec![
+        RecordedResponse::json(
+            429,
+            r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "slow down"}}"#,
+        )
+        .header("retry-after", "0"),
+        RecordedResponse::json(200, r#"{"input_tokens": 12}"#),
+    ])
+    .await;
+    let client = AnthropicClient::new("test-key")
+        .with_base_url(base_url)
+        .with_retry(RetryPolicy {
+            max_retries: 1,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(10),
+        });
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+    assert_eq!(client.count_tokens(&req).await.unwrap(), 12);
+    assert_eq!(server.await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_count_tokens_recorded_response() {
+    use crate::llm::LlmClient;
+
+    let (base_url, server) = serve_once(200, r#"{"input_tokens": 2095}"#).await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+    let count = client.count_tokens(&req).await.unwrap();
+
+    assert_eq!(count, 2095);
+    let raw_request = &server.await.unwrap()[0];
+    assert!(raw_request.starts_with("POST /v1/messages/count_tokens "));
+    assert!(raw_request.contains("x-api-key: test-key"));
+}
+
+#[tokio::test]
+async fn test_count_tokens_api_error() {
+    use crate::llm::LlmClient;
+
+    let (base_url, _server) = serve_once(
+        400,
+        r#"{"type": "error", "error": {"type": "invalid_request_error", "message": "bad model"}}"#,
+    )
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("nope").message(Message::user("Hello"));
+    match client.count_tokens(&req).await {
+        Err(crate::error::LlmError::Api { status, message }) => {
+            assert_eq!(status, 400);
+            assert_eq!(message, "bad model");
+        }
+        other => panic!("Expected Api error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_count_tokens_default_estimates() {
+    use crate::llm::{LlmClient, estimate_tokens};
+
+    // Providers without a counting endpoint fall back to the heuristic
+    let client = crate::llm::OllamaClient::new("llama3");
+    let req = Request::new("llama3").message(Message::user("twelve bytes"));
+
+    assert_eq!(client.count_tokens(&req).await.unwrap(), 3);
+    assert_eq!(estimate_tokens(&req), 3);
+}
+
+const MESSAGE_RESPONSE: &str = r#"{
+    "id": "msg_retry",
+    "model": "claude-sonnet-4-20250514",
+    "content": [{"type": "text", "text": "Hi"}],
+    "stop_reason": "end_turn",
+    "usage": {"input_tokens": 5, "output_tokens": 1}
+}"#;
+
+#[tokio::test]
+async fn test_retry_on_rate_limit_honors_retry_after() {
+    use crate::llm::LlmClient;
+    use crate::llm::RetryPolicy;
+    use crate::llm::test_server::{RecordedResponse, serve};
+
+    let (base_url, server) = serve(vec![
+        RecordedResponse::json(
+            429,
+            r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "slow down"}}"#,
+        )
+        .header("retry-after", "0"),
+        RecordedResponse::json(
+            529,
+            r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "overloaded"}}"#,
+        ),
+        RecordedResponse::json(200, MESSAGE_RESPONSE),
+    ])
+    .await;
+    let client = AnthropicClient::new("test-key")
+        .with_base_url(base_url)
+        .with_retry(RetryPolicy {
+            max_retries: 3,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(10),
+        });
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+    let response = client.create_message(&req).await.unwrap();
+
+    assert_eq!(response.text(), "Hi");
+    assert_eq!(response.attempts, 3);
+    assert_eq!(server.await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_response_over_size_limit_rejected() {
+    use crate::llm::LlmClient;
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+
+    let (base_url, _server) = serve_once(200, MESSAGE_RESPONSE).await;
+    let client = AnthropicClient::new("test-key")
+        .with_base_url(base_url)
+        .with_max_response_size(64);
+    match client.create_message(&req).await {
+        Err(crate::error::LlmError::ResponseTooLarge { limit }) => assert_eq!(limit, 64),
+        other => panic!("Expected ResponseTooLarge, got {:?}", other),
+    }
+
+    // A body exactly at the limit is accepted
+    let (base_url, _server) = serve_once(200, MESSAGE_RESPONSE).await;
+    let client = AnthropicClient::new("test-key")
+        .with_base_url(base_url)
+        .with_max_response_size(MESSAGE_RESPONSE.len());
+    assert_eq!(client.create_message(&req).await.unwrap().text(), "Hi");
+}
+
+#[tokio::test]
+async fn test_no_retry_by_default() {
+    use crate::llm::LlmClient;
+
+    let (base_url, _server) = serve_once(
+        429,
+        r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "slow down"}}"#,
+    )
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+    match client.create_message(&req).await {
+        Err(crate::error::LlmError::Api { status, .. }) => assert_eq!(status, 429),
+        other => panic!("Expected Api error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sampling_params_serialized() {
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .temperature(0.5)
+        .top_p(0.8);
+
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(json["temperature"], 0.5);
+    assert_eq!(json["top_p"], 0.8);
+
+    let json = serde_json::to_value(AnthropicRequest::from(&Request::new("m"))).unwrap();
+    assert!(json.get("top_p").is_none());
+    assert!(json.get("stop_sequences").is_none());
+
+    let req = Request::new("m").stop_sequences(["END", "\n\nHuman:"]);
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(
+        json["stop_sequences"],
+        serde_json::json!(["END", "\n\nHuman:"])
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_sampling_params_rejected_before_sending() {
+    use crate::llm::LlmClient;
+    use futures::StreamExt;
+
+    // Nothing listens here; validation must fail before any connection attempt
+    let client = AnthropicClient::new("test-key").with_base_url("http://127.0.0.1:9");
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .top_p(2.0);
+
+    assert!(matches!(
+        client.create_message(&req).await,
+        Err(crate::error::LlmError::Configuration(_))
+    ));
+    assert!(matches!(
+        client.create_message_stream(&req).next().await,
+        Some(Err(crate::error::LlmError::Configuration(_)))
+    ));
+}
+
+#[test]
+fn test_thinking_serialized_with_adjusted_max_tokens() {
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Think hard"))
+        .max_tokens(2000)
+        .thinking(10000);
+
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(
+        json["thinking"],
+        serde_json::json!({"type": "enabled", "budget_tokens": 10000})
+    );
+    assert_eq!(json["max_tokens"], 11024);
+
+    let json = serde_json::to_value(AnthropicRequest::from(&Request::new("m"))).unwrap();
+    assert!(json.get("thinking").is_none());
+    assert_eq!(json["max_tokens"], 4096);
+}
+
+#[test]
+fn test_thinking_blocks_parsed_and_sent_back() {
+    let json = r#"{
+        "id": "msg_think",
+        "content": [
+            {"type": "thinking", "thinking": "The user wants the file.", "signature": "sig_abc"},
+            {"type": "text", "text": "Reading it now."},
+            {"type": "tool_use", "id": "tu_1", "name": "read_file", "input": {"path": "a.rs"}}
+        ],
+        "stop_reason": "tool_use",
+        "model": "claude-sonnet-4-20250514",
+        "usage": {"input_tokens": 20, "output_tokens": 50}
+    }"#;
+    let response = Response::from(serde_json::from_str::<AnthropicResponse>(json).unwrap());
+
+    assert!(matches!(
+        &response.content[0],
+        ContentBlock::Thinking { text, signature }
+            if text == "The user wants the file." && signature == "sig_abc"
+    ));
+    assert_eq!(response.text(), "Reading it now.");
+
+    // The next turn sends the signed reasoning back ahead of the tool call;
+    // reasoning without a signature can't be verified, so it's left out
+    let mut content = response.content;
+    content.insert(0, ContentBlock::thinking("Unsigned.", ""));
+    let req = Request::new("claude-sonnet-4-20250514")
+        .thinking(2048)
+        .message(Message::user("Read a.rs"))
+        .message(Message {
+            role: Role::Assistant,
+            content,
+        })
+        .message(Message::tool_results(vec![ContentBlock::tool_result(
+            "tu_1",
+            "fn main() {}",
+        )]));
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    let assistant = &json["messages"][1]["content"];
+    assert_eq!(assistant.as_array().unwrap().len(), 3);
+    assert_eq!(
+        assistant[0],
+        serde_json::json!({
+            "type": "thinking",
+            "thinking": "The user wants the file.",
+            "signature": "sig_abc"
+        })
+    );
+    assert_eq!(assistant[2]["type"], "tool_use");
+}
+
+#[test]
+fn test_redacted_thinking_kept_and_sent_back() {
+    let json = r#"{
+        "id": "msg_redacted",
+        "content": [
+            {"type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix"},
+            {"type": "tool_use", "id": "tu_1", "name": "read_file", "input": {"path": "a.rs"}}
+        ],
+        "stop_reason": "tool_use",
+        "model": "claude-sonnet-4-20250514",
+        "usage": {"input_tokens": 20, "output_tokens": 50}
+    }"#;
+    let response = Response::from(serde_json::from_str::<AnthropicResponse>(json).unwrap());
+
+    assert!(matches!(
+        &response.content[0],
+        ContentBlock::RedactedThinking { data } if data == "EmwKAhgBEgy3va3pzix"
+    ));
+    assert_eq!(response.text(), "");
+
+    let req = Request::new("claude-sonnet-4-20250514")
+        .thinking(2048)
+        .message(Message::user("Read a.rs"))
+        .message(Message {
+            role: Role::Assistant,
+            content: response.content,
+        })
+        .message(Message::tool_results(vec![ContentBlock::tool_result(
+            "tu_1",
+            "fn main() {}",
+        )]));
+    let json = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    let assistant = &json["messages"][1]["content"];
+    assert_eq!(
+        assistant[0],
+        serde_json::json!({"type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix"})
+    );
+    assert_eq!(assistant[1]["type"], "tool_use");
+}
+
+#[tokio::test]
+async fn test_stream_assembles_thinking_block() {
+    use crate::llm::LlmClient;
+    use crate::llm::test_server::{RecordedResponse, serve};
+    use futures::StreamExt;
+
+    let body = concat!(
+        "event: content_block_start\n",
+        "data: {\"type\": \"content_block_start\", \"index\": 0, \"content_block\": {\"type\": \"thinking\", \"thinking\": \"\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"thinking_delta\", \"thinking\": \"Two plus two \"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"thinking_delta\", \"thinking\": \"is four.\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"signature_delta\", \"signature\": \"sig_xyz\"}}\n\n",
+        "event: content_block_stop\n",
+        "data: {\"type\": \"content_block_stop\", \"index\": 0}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\": \"message_stop\"}\n\n",
+    );
+    let (base_url, _server) = serve(vec![RecordedResponse {
+        status: 200,
+        headers: vec![("content-type".into(), "text/event-stream".into())],
+        body: body.into(),
+    }])
+    .await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514")
+        .thinking(2048)
+        .message(Message::user("What is 2 + 2?"));
+    let events: Vec<StreamEvent> = client
+        .create_message_stream(&req)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert!(matches!(
+        &events[1],
+        StreamEvent::ThinkingDelta { index: 0, text } if text == "Two plus two "
+    ));
+    assert!(matches!(
+        &events[4],
+        StreamEvent::ContentBlockStop {
+            index: 0,
+            block: Some(ContentBlock::Thinking { text, signature }),
+        } if text == "Two plus two is four." && signature == "sig_xyz"
+    ));
+}
+
+#[tokio::test]
+async fn test_thinking_with_temperature_rejected_before_sending() {
+    use crate::llm::LlmClient;
+
+    let client = AnthropicClient::new("test-key").with_base_url("http://127.0.0.1:9");
+    let req = Request::new("claude-sonnet-4-20250514")
+        .message(Message::user("Hello"))
+        .thinking(2048)
+        .temperature(0.3);
+
+    assert!(matches!(
+        client.create_message(&req).await,
+        Err(crate::error::LlmError::InvalidRequest(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_dropping_stream_closes_connection() {
+    use crate::llm::LlmClient;
+    use crate::llm::test_server::serve_sse_until_closed;
+    use futures::StreamExt;
+
+    let message_start = "event: message_start\ndata: {\"type\": \"message_start\", \"message\": {\"id\": \"msg_1\", \"model\": \"claude-sonnet-4-20250514\", \"usage\": {\"input_tokens\": 5, \"output_tokens\": 0}}}\n\n";
+    let ping = "event: ping\ndata: {\"type\": \"ping\"}\n\n";
+    let (base_url, server) =
+        serve_sse_until_closed(vec![message_start.to_string()], ping.to_string(), 500).await;
+    let client = AnthropicClient::new("test-key").with_base_url(base_url);
+
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user("Hello"));
+    let mut stream = client.create_message_stream(&req);
+    let first = stream.next().await.unwrap().unwrap();
+    assert!(matches!(first, StreamEvent::MessageStart { .. }));
+    drop(stream);
+
+    // The server would need 5 seconds to send everything; it must see the close long before
+    let outcome = tokio::time::timeout(std::time::Duration::from_secs(2), server)
+        .await
+        .expect("connection stayed open after the stream was dropped")
+        .unwrap();
+    assert!(outcome.closed_early);
+    assert!(outcome.chunks_sent < 500);
+}
+
+#[test]
+fn test_request_golden() {
+    use crate::llm::payload::audit::*;
+
+    let body = serde_json::to_value(AnthropicRequest::from(&representative_request())).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "model": "test-model",
+            "messages": [
+                {"role": "user", "content": [{"type": "text", "text": "What time is it?"}]},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Checking."},
+                    {"type": "tool_use", "id": "call_1", "name": "get_time", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "12:00", "is_error": false}
+                ]}
+            ],
+            "max_tokens": 1024,
+            "system": "You are helpful.",
+            "temperature": 0.5,
+            "stop_sequences": ["END"],
+            "tools": [{
+                "name": "get_time",
+                "description": "Get the current time",
+                "input_schema": {"type": "object", "properties": {}}
+            }]
+        })
+    );
+
+    let body = serde_json::to_value(AnthropicRequest::from(&sparse_request())).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "model": "test-model",
+            "messages": [
+                {"role": "user", "content": [{"type": "text", "text": "Hi"}]},
+                {"role": "user", "content": [{"type": "text", "text": "Still there?"}]}
+            ],
+            "max_tokens": 4096
+        })
+    );
+}
+
+#[test]
+fn test_image_blocks_serialized() {
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::user_with_images(
+        "Compare these.",
+        [
+            ImageSource::base64("image/jpeg", "/9j/4AAQ"),
+            ImageSource::url("https://example.com/cat.png"),
+        ],
+    ));
+
+    let body = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(
+        body["messages"][0]["content"],
+        serde_json::json!([
+            {"type": "image", "source": {"type": "base64", "media_type": "image/jpeg", "data": "/9j/4AAQ"}},
+            {"type": "image", "source": {"type": "url", "url": "https://example.com/cat.png"}},
+            {"type": "text", "text": "Compare these."}
+        ])
+    );
+}
+
+#[test]
+fn test_tool_result_images_serialized_as_blocks() {
+    let req = Request::new("claude-sonnet-4-20250514").message(Message::tool_results(vec![
+        ContentBlock::tool_result("toolu_1", "Captured the screen")
+            .with_images([ImageSource::base64("image/png", "iVBOR")]),
+        ContentBlock::tool_result("toolu_2", "No image"),
+    ]));
+
+    let body = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+    assert_eq!(
+        body["messages"][0]["content"],
+        serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_1", "is_error": false, "content": [
+                {"type": "text", "text": "Captured the screen"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBOR"}}
+            ]},
+            {"type": "tool_result", "tool_use_id": "toolu_2", "content": "No image", "is_error": false}
+        ])
+    );
+
+    let content: AnthropicContent =
+        serde_json::from_value(body["messages"][0]["content"][0].clone()).unwrap();
+    let ContentBlock::ToolResult {
+        content, images, ..
+    } = ContentBlock::from(content)
+    else {
+        panic!("expected a tool result");
+    };
+    assert_eq!(content, "Captured the screen");
+    assert_eq!(images.len(), 1);
+    assert!(AnthropicClient::new("key").supports_tool_result_images());
+}
+
+#[test]
+fn test_request_bodies_have_no_empty_fields() {
+    use crate::llm::payload::audit::*;
+
+    for req in [representative_request(), sparse_request()] {
+        let body = serde_json::to_value(AnthropicRequest::from(&req)).unwrap();
+        assert_eq!(empty_fields(&body), Vec::<String>::new());
+        let body = serde_json::to_value(AnthropicCountTokensRequest::from(&req)).unwrap();
+        assert_eq!(empty_fields(&body), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,49 @@
+// ABOUTME: Size-limited reading of HTTP response bodies.
+// ABOUTME: Guards against providers returning bodies too large to buffer.
+
+use serde::de::DeserializeOwned;
+
+use crate::error::LlmError;
+
+/// Default cap on buffered (non-streaming) response bodies: 20 MiB.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Read a response body, failing with `ResponseTooLarge` once it exceeds `limit` bytes.
+///
+/// A declared `content-length` over the limit fails before anything is read.
+pub(crate) async fn read_body(
+    mut response: reqwest::Response,
+    limit: usize,
+) -> Result<Vec<u8>, LlmError> {
+    if response
+        .content_length()
+        .is_some_and(|len| len > limit as u64)
+    {
+        return Err(LlmError::ResponseTooLarge { limit });
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(LlmError::ResponseTooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Read and deserialize a JSON response body of at most `limit` bytes.
+pub(crate) async fn read_json<T: DeserializeOwned>(
+    response: reqwest::Response,
+    limit: usize,
+) -> Result<T, LlmError> {
+    Ok(serde_json::from_slice(&read_body(response, limit).await?)?)
+}
+
+/// Read a text response body of at most `limit` bytes.
+pub(crate) async fn read_text(
+    response: reqwest::Response,
+    limit: usize,
+) -> Result<String, LlmError> {
+    Ok(String::from_utf8_lossy(&read_body(response, limit).await?).into_owned())
+}
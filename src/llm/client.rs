@@ -2,13 +2,18 @@
 // ABOUTME: mux to work with any LLM provider (Anthropic, OpenAI, etc.)
 
 use std::pin::Pin;
+use std::sync::Arc;
 
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{Stream, StreamExt, TryStreamExt};
 
-use super::{Request, Response};
+use super::stream_accumulator::StreamAccumulator;
+use super::{ContentBlock, Request, Response};
 use crate::error::LlmError;
 
+/// Most candidate requests the default `create_messages` has in flight.
+const MAX_CONCURRENT_CANDIDATES: usize = 4;
+
 /// Event types for streaming responses.
 #[derive(Debug, Clone)]
 pub enum StreamEvent {
@@ -25,9 +30,17 @@ pub enum StreamEvent {
     /// Text deltas should be concatenated to build the complete text.
     ContentBlockDelta { index: usize, text: String },
 
+    /// Delta for a thinking block's reasoning. Concatenate like
+    /// `ContentBlockDelta`; it's not part of the answer.
+    ThinkingDelta { index: usize, text: String },
+
+    /// The signature of a thinking block, sent before its `ContentBlockStop`.
+    SignatureDelta { index: usize, signature: String },
+
     /// Delta for tool input JSON arguments.
     /// These arrive after `ContentBlockStart` for a `ToolUse` block.
-    /// Accumulate `partial_json` values and parse as JSON at `ContentBlockStop`.
+    /// Accumulate `partial_json` values and parse as JSON at `ContentBlockStop`,
+    /// or take the parsed input from the stop event's `block`.
     ///
     /// Event order for tool calls:
     /// 1. `ContentBlockStart` with `ToolUse { id, name, input: {} }`
@@ -38,7 +51,15 @@ pub enum StreamEvent {
     InputJsonDelta { index: usize, partial_json: String },
 
     /// A content block finished.
-    ContentBlockStop { index: usize },
+    ///
+    /// The built-in clients set `block` to the assembled block: the complete
+    /// text, or the tool call with its input parsed (see
+    /// [`parse_tool_input`](super::parse_tool_input)). Other sources may
+    /// leave it `None`, in which case consumers assemble the block themselves.
+    ContentBlockStop {
+        index: usize,
+        block: Option<super::ContentBlock>,
+    },
 
     /// Message metadata update.
     MessageDelta {
@@ -57,8 +78,430 @@ pub trait LlmClient: Send + Sync {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError>;
 
     /// Create a message with streaming response.
+    ///
+    /// Dropping the returned stream cancels the request: implementations keep
+    /// the HTTP response inside the stream so the connection is closed, not drained.
+    ///
+    /// The default calls [`create_message`](Self::create_message) and replays
+    /// the response as a single burst of events, so every client can be
+    /// streamed. Providers with a streaming API override this.
+    ///
+    /// The stream borrows the client; in mux 0.10 it was `'static`. To hold
+    /// a stream after the client borrow ends, e.g. to move it into a spawned
+    /// task, use [`stream_message`] with an `Arc` of the client.
     fn create_message_stream(
         &self,
         req: &Request,
-    ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>>;
+    ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + '_>> {
+        let req = req.clone();
+        Box::pin(async_stream::try_stream! {
+            let response = self.create_message(&req).await?;
+            for event in response_events(response) {
+                yield event;
+            }
+        })
+    }
+
+    /// Stream a message and return it once complete.
+    ///
+    /// Useful for providers that answer long requests faster, or only,
+    /// when streamed.
+    async fn create_message_collected(&self, req: &Request) -> Result<Response, LlmError> {
+        let mut stream = self.create_message_stream(req);
+        let mut accumulator = StreamAccumulator::new();
+        while let Some(event) = stream.next().await {
+            accumulator.handle_event(&event?);
+        }
+        Ok(accumulator.into_response(req.model.clone()))
+    }
+
+    /// Create [`Request::candidates`] completions of the same request, in
+    /// order.
+    ///
+    /// The default sends that many [`create_message`](Self::create_message)
+    /// calls, at most four at a time, each billed in full. Providers that
+    /// can generate several completions in one call override this. Usage is
+    /// split so that summing it over the responses gives the total either
+    /// way.
+    async fn create_messages(&self, req: &Request) -> Result<Vec<Response>, LlmError> {
+        req.validate()?;
+        let single = Request {
+            candidates: None,
+            ..req.clone()
+        };
+        let calls = (0..req.candidates.unwrap_or(1)).map(|_| self.create_message(&single));
+        futures::stream::iter(calls)
+            .buffered(MAX_CONCURRENT_CANDIDATES)
+            .try_collect()
+            .await
+    }
+
+    /// Count the input tokens a request would consume.
+    ///
+    /// Providers with a server-side counting endpoint override this to return
+    /// exact counts. The default falls back to [`estimate_tokens`].
+    async fn count_tokens(&self, req: &Request) -> Result<usize, LlmError> {
+        Ok(estimate_tokens(req))
+    }
+
+    /// Whether the provider accepts images inside tool results.
+    ///
+    /// When false (the default), agents replace tool result images with
+    /// [`ImageSource::placeholder`](super::ImageSource::placeholder) text.
+    fn supports_tool_result_images(&self) -> bool {
+        false
+    }
+}
+
+/// Stream `req` from `client`, keeping the client alive for as long as the
+/// stream is, so the stream can outlive the caller's borrow.
+pub fn stream_message(
+    client: Arc<dyn LlmClient>,
+    req: Request,
+) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+    Box::pin(async_stream::try_stream! {
+        let mut events = client.create_message_stream(&req);
+        while let Some(event) = events.next().await {
+            yield event?;
+        }
+    })
+}
+
+/// The events a streaming provider would have sent for `response`: each
+/// block arrives whole, text in one delta and tool input in one fragment.
+fn response_events(response: Response) -> Vec<StreamEvent> {
+    let mut events = vec![StreamEvent::MessageStart {
+        id: response.id.clone(),
+        model: response.actual_model().to_string(),
+    }];
+    for (index, block) in response.content.into_iter().enumerate() {
+        match &block {
+            ContentBlock::Text { text } => {
+                events.push(StreamEvent::ContentBlockStart {
+                    index,
+                    block: ContentBlock::text(""),
+                });
+                events.push(StreamEvent::ContentBlockDelta {
+                    index,
+                    text: text.clone(),
+                });
+            }
+            ContentBlock::Thinking { text, signature } => {
+                events.push(StreamEvent::ContentBlockStart {
+                    index,
+                    block: ContentBlock::thinking("", ""),
+                });
+                events.push(StreamEvent::ThinkingDelta {
+                    index,
+                    text: text.clone(),
+                });
+                events.push(StreamEvent::SignatureDelta {
+                    index,
+                    signature: signature.clone(),
+                });
+            }
+            ContentBlock::ToolUse { id, name, input } => {
+                events.push(StreamEvent::ContentBlockStart {
+                    index,
+                    block: ContentBlock::ToolUse {
+                        id: id.clone(),
+                        name: name.clone(),
+                        input: serde_json::json!({}),
+                    },
+                });
+                events.push(StreamEvent::InputJsonDelta {
+                    index,
+                    partial_json: input.to_string(),
+                });
+            }
+            _ => events.push(StreamEvent::ContentBlockStart {
+                index,
+                block: block.clone(),
+            }),
+        }
+        events.push(StreamEvent::ContentBlockStop {
+            index,
+            block: Some(block),
+        });
+    }
+    events.push(StreamEvent::MessageDelta {
+        stop_reason: Some(response.stop_reason),
+        usage: response.usage,
+    });
+    events.push(StreamEvent::MessageStop);
+    events
+}
+
+/// Approximate bytes per token for the heuristic estimator.
+pub const APPROX_BYTES_PER_TOKEN: usize = 4;
+
+/// Tokens counted per image. Providers charge by image size, which the
+/// estimator doesn't decode; this is Anthropic's cost for a ~1.2 megapixel
+/// image, close to the largest size it accepts without downscaling.
+pub const APPROX_IMAGE_TOKENS: usize = 1_600;
+
+/// Estimate the input tokens of a request using a byte-based heuristic.
+///
+/// Counts the system prompt and instruction layers, message content, and
+/// tool definitions. Each image counts as a flat [`APPROX_IMAGE_TOKENS`].
+pub fn estimate_tokens(req: &Request) -> usize {
+    let mut bytes = req.system.as_ref().map_or(0, |s| s.len());
+    bytes += req.instructions.iter().map(|i| i.text.len()).sum::<usize>();
+
+    bytes += req
+        .messages
+        .iter()
+        .map(|m| content_bytes(&m.content))
+        .sum::<usize>();
+
+    for tool in &req.tools {
+        bytes += tool.name.len() + tool.description.len() + tool.input_schema.to_string().len();
+    }
+
+    bytes.div_ceil(APPROX_BYTES_PER_TOKEN)
+}
+
+/// Estimate the tokens of a message's content, counted as in
+/// [`estimate_tokens`].
+pub fn estimate_content_tokens(content: &[ContentBlock]) -> usize {
+    content_bytes(content).div_ceil(APPROX_BYTES_PER_TOKEN)
+}
+
+/// Estimate the tokens of `text`, counted as in [`estimate_tokens`].
+pub fn estimate_text_tokens(text: &str) -> usize {
+    text.len().div_ceil(APPROX_BYTES_PER_TOKEN)
+}
+
+/// Bytes the estimator counts for `content`.
+fn content_bytes(content: &[ContentBlock]) -> usize {
+    content
+        .iter()
+        .map(|block| match block {
+            ContentBlock::Text { text } | ContentBlock::Thinking { text, .. } => text.len(),
+            ContentBlock::ToolUse { name, input, .. } => name.len() + input.to_string().len(),
+            ContentBlock::ToolResult { content, .. } => content.len(),
+            ContentBlock::Image { .. } => APPROX_IMAGE_TOKENS * APPROX_BYTES_PER_TOKEN,
+            ContentBlock::RedactedThinking { data } => data.len(),
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::llm::{StopReason, Usage, stream_accumulator::StreamAccumulator};
+
+    /// Client with no streaming support of its own.
+    struct NonStreamingClient;
+
+    #[async_trait]
+    impl LlmClient for NonStreamingClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            if req.messages.is_empty() {
+                return Err(LlmError::InvalidRequest("no messages".into()));
+            }
+            Ok(Response {
+                id: "msg_1".into(),
+                content: vec![
+                    ContentBlock::text("Let me look."),
+                    ContentBlock::ToolUse {
+                        id: "call_1".into(),
+                        name: "read_file".into(),
+                        input: serde_json::json!({"path": "README.md"}),
+                    },
+                ],
+                stop_reason: StopReason::ToolUse,
+                model: req.model.clone(),
+                served_model: Some("test-model-2025".into()),
+                system_fingerprint: None,
+                usage: Usage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    ..Default::default()
+                },
+                attempts: 1,
+                citations: Vec::new(),
+                invalid_tool_inputs: Vec::new(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_stream_replays_response() {
+        let req = Request::new("test-model").message(crate::llm::Message::user("Hi"));
+        let events: Vec<StreamEvent> = NonStreamingClient
+            .create_message_stream(&req)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert!(matches!(
+            &events[0],
+            StreamEvent::MessageStart { id, model } if id == "msg_1" && model == "test-model-2025"
+        ));
+        assert!(matches!(
+            &events[2],
+            StreamEvent::ContentBlockDelta { index: 0, text } if text == "Let me look."
+        ));
+        assert!(matches!(
+            &events[5],
+            StreamEvent::InputJsonDelta { index: 1, partial_json }
+                if partial_json == r#"{"path":"README.md"}"#
+        ));
+        assert!(matches!(
+            &events[7],
+            StreamEvent::MessageDelta { stop_reason: Some(StopReason::ToolUse), usage }
+                if usage.output_tokens == 5
+        ));
+        assert!(matches!(events[8], StreamEvent::MessageStop));
+        assert_eq!(events.len(), 9);
+
+        // Accumulating the events rebuilds the original content
+        let mut accumulator = StreamAccumulator::new();
+        for event in &events {
+            accumulator.handle_event(event);
+        }
+        let content = accumulator.into_content();
+        assert_eq!(content.len(), 2);
+        assert!(matches!(&content[0], ContentBlock::Text { text } if text == "Let me look."));
+        assert!(matches!(
+            &content[1],
+            ContentBlock::ToolUse { id, input, .. } if id == "call_1" && input["path"] == "README.md"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_default_stream_forwards_errors() {
+        let req = Request::new("test-model");
+        let events: Vec<_> = NonStreamingClient
+            .create_message_stream(&req)
+            .collect()
+            .await;
+        assert!(matches!(&events[..], [Err(LlmError::InvalidRequest(_))]));
+    }
+
+    #[tokio::test]
+    async fn test_stream_message_outlives_the_caller() {
+        let req = Request::new("test-model").message(crate::llm::Message::user("Hi"));
+        let stream = stream_message(Arc::new(NonStreamingClient), req);
+
+        let events = tokio::spawn(stream.collect::<Vec<_>>()).await.unwrap();
+
+        assert_eq!(events.len(), 9);
+        assert!(matches!(events[8], Ok(StreamEvent::MessageStop)));
+    }
+
+    #[tokio::test]
+    async fn test_create_message_collected_rebuilds_response() {
+        let req = Request::new("test-model").message(crate::llm::Message::user("Hi"));
+        let response = NonStreamingClient
+            .create_message_collected(&req)
+            .await
+            .unwrap();
+
+        assert_eq!(response.id, "msg_1");
+        assert_eq!(response.actual_model(), "test-model-2025");
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(response.usage.output_tokens, 5);
+        assert_eq!(response.text(), "Let me look.");
+        assert_eq!(response.tool_uses().len(), 1);
+
+        assert!(matches!(
+            NonStreamingClient
+                .create_message_collected(&Request::new("test-model"))
+                .await,
+            Err(LlmError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_default_create_messages_sends_one_request_per_candidate() {
+        let client = crate::llm::MockClient::new()
+            .with_text("First")
+            .with_text("Second")
+            .with_text("Third");
+        let req = Request::new("test-model")
+            .message(crate::llm::Message::user("Hi"))
+            .candidates(3);
+
+        let responses = client.create_messages(&req).await.unwrap();
+
+        assert_eq!(responses.len(), 3);
+        assert!(client.requests().iter().all(|r| r.candidates.is_none()));
+
+        // A single request is the default
+        let single = Request::new("test-model").message(crate::llm::Message::user("Hi"));
+        let client = crate::llm::MockClient::new().with_text("Only");
+        assert_eq!(client.create_messages(&single).await.unwrap().len(), 1);
+
+        assert!(matches!(
+            client.create_messages(&single.candidates(0)).await,
+            Err(LlmError::InvalidRequest(_))
+        ));
+    }
+
+    /// Delegates to a `MockClient`, recording how many calls overlap.
+    struct OverlapClient {
+        inner: crate::llm::MockClient,
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmClient for OverlapClient {
+        async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+            use std::sync::atomic::Ordering;
+
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.inner.create_message(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_create_messages_caps_concurrent_requests() {
+        let inner = (0..10).fold(crate::llm::MockClient::new(), |client, i| {
+            client.with_text(format!("Candidate {}", i))
+        });
+        let client = OverlapClient {
+            inner,
+            in_flight: Default::default(),
+            peak: Default::default(),
+        };
+        let req = Request::new("test-model")
+            .message(crate::llm::Message::user("Hi"))
+            .candidates(10);
+
+        let responses = client.create_messages(&req).await.unwrap();
+
+        assert_eq!(responses.len(), 10);
+        assert_eq!(
+            client.peak.load(std::sync::atomic::Ordering::SeqCst),
+            MAX_CONCURRENT_CANDIDATES
+        );
+    }
+
+    #[test]
+    fn test_content_estimate_matches_request_estimate() {
+        let content = vec![
+            ContentBlock::text("Read the readme"),
+            ContentBlock::ToolUse {
+                id: "call_1".into(),
+                name: "read_file".into(),
+                input: serde_json::json!({"path": "README.md"}),
+            },
+        ];
+        let req = Request::new("test-model").message(crate::llm::Message {
+            role: crate::llm::Role::Assistant,
+            content: content.clone(),
+        });
+
+        assert_eq!(estimate_content_tokens(&content), estimate_tokens(&req));
+        assert_eq!(estimate_text_tokens(""), 0);
+        assert_eq!(estimate_text_tokens("hello world!"), 3);
+    }
 }
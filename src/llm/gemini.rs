@@ -1,13 +1,22 @@
 // ABOUTME: Google Gemini API client implementation.
 // ABOUTME: Implements LlmClient trait for Gemini models.
 
+use super::body::{DEFAULT_MAX_RESPONSE_BYTES, read_json, read_text};
 use super::client::StreamEvent;
-use super::{ContentBlock, Message, Request, Response, Role, StopReason, ToolDefinition, Usage};
+use super::http::default_http_client;
+use super::ids::{IdSource, UuidIdSource};
+use super::payload;
+use super::stream_accumulator::with_finished_blocks;
+use super::{
+    Citation, ContentBlock, ImageSource, Message, Request, Response, Role, StopReason, ToolChoice,
+    ToolDefinition, Usage,
+};
 use crate::error::LlmError;
 use async_trait::async_trait;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
+use std::sync::Arc;
 
 const GEMINI_DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
 
@@ -22,13 +31,17 @@ pub struct GeminiRequest {
     pub generation_config: Option<GeminiGenerationConfig>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<GeminiTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<GeminiToolConfig>,
 }
 
 /// Gemini content (message).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeminiContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
+    /// Omitted by the API on final chunks that only carry a finish reason.
+    #[serde(default)]
     pub parts: Vec<GeminiPart>,
 }
 
@@ -43,6 +56,8 @@ pub struct GeminiPart {
     pub function_call: Option<GeminiFunctionCall>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub function_response: Option<GeminiFunctionResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<GeminiInlineData>,
 }
 
 impl GeminiPart {
@@ -52,6 +67,7 @@ impl GeminiPart {
             text: Some(text.into()),
             function_call: None,
             function_response: None,
+            inline_data: None,
         }
     }
 
@@ -64,6 +80,7 @@ impl GeminiPart {
                 args,
             }),
             function_response: None,
+            inline_data: None,
         }
     }
 
@@ -76,6 +93,20 @@ impl GeminiPart {
                 name: name.into(),
                 response,
             }),
+            inline_data: None,
+        }
+    }
+
+    /// Create an inline data part from base64-encoded bytes.
+    pub fn inline_data(mime_type: impl Into<String>, data: impl Into<String>) -> Self {
+        Self {
+            text: None,
+            function_call: None,
+            function_response: None,
+            inline_data: Some(GeminiInlineData {
+                mime_type: mime_type.into(),
+                data: data.into(),
+            }),
         }
     }
 }
@@ -94,14 +125,30 @@ pub struct GeminiFunctionResponse {
     pub response: serde_json::Value,
 }
 
+/// Gemini inline media, such as an image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiInlineData {
+    pub mime_type: String,
+    /// Base64-encoded bytes.
+    pub data: String,
+}
+
 /// Gemini generation config.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_output_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    /// Number of completions; only set by `create_messages`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
 }
 
 /// Gemini tool definition.
@@ -111,6 +158,40 @@ pub struct GeminiTool {
     pub function_declarations: Vec<GeminiFunctionDeclaration>,
 }
 
+/// Gemini tool configuration.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiToolConfig {
+    pub function_calling_config: GeminiFunctionCallingConfig,
+}
+
+/// Gemini function calling mode, optionally limited to some functions.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionCallingConfig {
+    /// `AUTO`, `ANY` (call at least one function) or `NONE`.
+    pub mode: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_function_names: Vec<String>,
+}
+
+impl From<&ToolChoice> for GeminiToolConfig {
+    fn from(choice: &ToolChoice) -> Self {
+        let (mode, allowed_function_names) = match choice {
+            ToolChoice::Auto => ("AUTO", Vec::new()),
+            ToolChoice::None => ("NONE", Vec::new()),
+            ToolChoice::Required => ("ANY", Vec::new()),
+            ToolChoice::Tool(name) => ("ANY", vec![name.clone()]),
+        };
+        GeminiToolConfig {
+            function_calling_config: GeminiFunctionCallingConfig {
+                mode: mode.to_string(),
+                allowed_function_names,
+            },
+        }
+    }
+}
+
 /// Gemini function declaration.
 #[derive(Debug, Serialize)]
 pub struct GeminiFunctionDeclaration {
@@ -123,18 +204,108 @@ pub struct GeminiFunctionDeclaration {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiResponse {
+    #[serde(default)]
     pub candidates: Vec<GeminiCandidate>,
     #[serde(default)]
     pub usage_metadata: Option<GeminiUsageMetadata>,
+    /// The model version that served the request.
+    #[serde(default)]
+    pub model_version: Option<String>,
 }
 
 /// Gemini response candidate.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiCandidate {
+    /// Absent when generation stops before producing output (e.g. safety).
+    #[serde(default)]
     pub content: GeminiContent,
     #[serde(default)]
     pub finish_reason: Option<String>,
+    /// Sources used when the request enabled Google Search grounding.
+    #[serde(default)]
+    pub grounding_metadata: Option<GeminiGroundingMetadata>,
+}
+
+/// Gemini grounding metadata: the sources consulted, and which parts of the
+/// response each one supports.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingMetadata {
+    #[serde(default)]
+    pub grounding_chunks: Vec<GeminiGroundingChunk>,
+    #[serde(default)]
+    pub grounding_supports: Vec<GeminiGroundingSupport>,
+}
+
+/// A grounding source. Only web sources are mapped to citations.
+#[derive(Debug, Deserialize)]
+pub struct GeminiGroundingChunk {
+    #[serde(default)]
+    pub web: Option<GeminiWebSource>,
+}
+
+/// A web page used for grounding.
+#[derive(Debug, Deserialize)]
+pub struct GeminiWebSource {
+    pub uri: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A span of the response and the chunks that support it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingSupport {
+    #[serde(default)]
+    pub segment: Option<GeminiSegment>,
+    #[serde(default)]
+    pub grounding_chunk_indices: Vec<usize>,
+}
+
+/// A span of response text.
+#[derive(Debug, Deserialize)]
+pub struct GeminiSegment {
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl GeminiGroundingMetadata {
+    /// One citation per supported span and source, followed by sources that
+    /// no span cites.
+    fn citations(&self) -> Vec<Citation> {
+        let web = |index: usize| {
+            self.grounding_chunks
+                .get(index)
+                .and_then(|chunk| chunk.web.as_ref())
+        };
+        let mut cited = vec![false; self.grounding_chunks.len()];
+        let mut citations = Vec::new();
+
+        for support in &self.grounding_supports {
+            let text = support.segment.as_ref().and_then(|s| s.text.clone());
+            for &index in &support.grounding_chunk_indices {
+                if let Some(source) = web(index) {
+                    cited[index] = true;
+                    citations.push(Citation {
+                        url: source.uri.clone(),
+                        title: source.title.clone(),
+                        cited_text: text.clone(),
+                    });
+                }
+            }
+        }
+        for (index, _) in cited.iter().enumerate().filter(|(_, cited)| !**cited) {
+            if let Some(source) = web(index) {
+                citations.push(Citation {
+                    url: source.uri.clone(),
+                    title: source.title.clone(),
+                    cited_text: None,
+                });
+            }
+        }
+        citations
+    }
 }
 
 /// Gemini usage metadata.
@@ -168,6 +339,8 @@ pub struct GeminiClient {
     api_key: String,
     base_url: String,
     http: reqwest::Client,
+    max_response_bytes: usize,
+    ids: Arc<dyn IdSource>,
 }
 
 impl GeminiClient {
@@ -176,7 +349,9 @@ impl GeminiClient {
         Self {
             api_key: api_key.into(),
             base_url: GEMINI_DEFAULT_BASE_URL.to_string(),
-            http: reqwest::Client::new(),
+            http: default_http_client(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            ids: Arc::new(UuidIdSource),
         }
     }
 
@@ -199,6 +374,62 @@ impl GeminiClient {
         self
     }
 
+    /// Send requests with `http`, e.g. one built from an
+    /// [`HttpConfig`](super::HttpConfig) with a proxy or custom
+    /// certificates. Defaults to a client with
+    /// [`HttpConfig::default`](super::HttpConfig::default) settings.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Fail non-streaming responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
+    /// Mint tool-call IDs with `ids`. Gemini doesn't name its function calls,
+    /// so the client does; the default is [`UuidIdSource`].
+    pub fn with_id_source(mut self, ids: Arc<dyn IdSource>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Send a `generateContent` request to `model`.
+    async fn generate(
+        &self,
+        model: &str,
+        gemini_req: &GeminiRequest,
+    ) -> Result<GeminiResponse, LlmError> {
+        let url = format!(
+            "{}?key={}",
+            self.endpoint(model, "generateContent"),
+            self.api_key
+        );
+
+        let response = self
+            .http
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(gemini_req)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error: GeminiError = read_json(response, self.max_response_bytes).await?;
+            return Err(LlmError::Api {
+                status: status.as_u16(),
+                message: error.error.message,
+            });
+        }
+
+        read_json(response, self.max_response_bytes).await
+    }
+
     /// Build the endpoint URL for a given model and method.
     fn endpoint(&self, model: &str, method: &str) -> String {
         format!("{}/models/{}:{}", self.base_url, model, method)
@@ -228,6 +459,25 @@ fn build_tool_name_lookup(messages: &[Message]) -> std::collections::HashMap<Str
     lookup
 }
 
+/// Gemini only takes image bytes inline; URL images must be fetched by the
+/// caller first.
+fn check_images(req: &Request) -> Result<(), LlmError> {
+    let has_url_image = req.messages.iter().flat_map(|m| &m.content).any(|block| {
+        matches!(
+            block,
+            ContentBlock::Image {
+                source: ImageSource::Url { .. }
+            }
+        )
+    });
+    if has_url_image {
+        return Err(LlmError::InvalidRequest(
+            "Gemini does not support image URLs; send the image as base64 data".into(),
+        ));
+    }
+    Ok(())
+}
+
 fn convert_message_to_content(
     msg: &Message,
     tool_name_lookup: &std::collections::HashMap<String, String>,
@@ -237,9 +487,7 @@ fn convert_message_to_content(
         Role::Assistant => "model",
     };
 
-    let parts: Vec<GeminiPart> = msg
-        .content
-        .iter()
+    let parts: Vec<GeminiPart> = payload::content_blocks(msg)
         .filter_map(|block| match block {
             ContentBlock::Text { text } => Some(GeminiPart::text(text)),
             ContentBlock::ToolUse { name, input, .. } => {
@@ -261,6 +509,15 @@ fn convert_message_to_content(
                     serde_json::json!({ "result": content }),
                 ))
             }
+            ContentBlock::Image { source } => match source {
+                ImageSource::Base64 { media_type, data } => {
+                    Some(GeminiPart::inline_data(media_type, data))
+                }
+                // Rejected by check_images before conversion
+                ImageSource::Url { url } => Some(GeminiPart::text(url)),
+            },
+            // Gemini doesn't take back reasoning
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => None,
         })
         .collect();
 
@@ -275,21 +532,27 @@ impl From<&Request> for GeminiRequest {
         // Build lookup for tool_use_id -> function name mapping
         let tool_name_lookup = build_tool_name_lookup(&req.messages);
 
-        let contents: Vec<GeminiContent> = req
-            .messages
+        let contents: Vec<GeminiContent> = payload::turns(&req.messages)
             .iter()
             .map(|msg| convert_message_to_content(msg, &tool_name_lookup))
             .collect();
 
-        let system_instruction = req.system.as_ref().map(|s| GeminiContent {
+        let system_instruction = payload::system_prompt(req).map(|s| GeminiContent {
             role: None,
             parts: vec![GeminiPart::text(s)],
         });
 
-        let generation_config = if req.max_tokens.is_some() || req.temperature.is_some() {
+        let generation_config = if req.max_tokens.is_some()
+            || req.temperature.is_some()
+            || req.top_p.is_some()
+            || !req.stop_sequences.is_empty()
+        {
             Some(GeminiGenerationConfig {
                 max_output_tokens: req.max_tokens,
                 temperature: req.temperature,
+                top_p: req.top_p,
+                stop_sequences: req.stop_sequences.clone(),
+                candidate_count: None,
             })
         } else {
             None
@@ -312,6 +575,7 @@ impl From<&Request> for GeminiRequest {
             system_instruction,
             generation_config,
             tools,
+            tool_config: payload::tool_choice(req).map(GeminiToolConfig::from),
         }
     }
 }
@@ -321,21 +585,79 @@ fn parse_stop_reason(s: Option<&str>) -> StopReason {
         Some("STOP") => StopReason::EndTurn,
         Some("MAX_TOKENS") => StopReason::MaxTokens,
         Some("TOOL_CODE") | Some("FUNCTION_CALL") => StopReason::ToolUse,
+        Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => {
+            StopReason::ContentFilter
+        }
         _ => StopReason::EndTurn,
     }
 }
 
-fn convert_gemini_response(resp: GeminiResponse, model: String) -> Result<Response, LlmError> {
-    let candidate = resp
-        .candidates
-        .into_iter()
-        .next()
-        .ok_or_else(|| LlmError::Api {
+/// Gemini reports `STOP` even when the turn ends in function calls.
+fn finish_stop_reason(reason: Option<&str>, has_tool_use: bool) -> StopReason {
+    match parse_stop_reason(reason) {
+        StopReason::EndTurn if has_tool_use => StopReason::ToolUse,
+        other => other,
+    }
+}
+
+fn convert_gemini_response(
+    resp: GeminiResponse,
+    model: String,
+    ids: &dyn IdSource,
+) -> Result<Response, LlmError> {
+    let mut responses = convert_gemini_candidates(resp, model, ids)?;
+    Ok(responses.swap_remove(0))
+}
+
+/// One response per candidate, in order. The usage, which Gemini reports
+/// for the whole call, goes on the first.
+fn convert_gemini_candidates(
+    resp: GeminiResponse,
+    model: String,
+    ids: &dyn IdSource,
+) -> Result<Vec<Response>, LlmError> {
+    if resp.candidates.is_empty() {
+        return Err(LlmError::Api {
             status: 0,
             message: "Gemini returned empty candidates (possibly blocked by safety filters)"
                 .to_string(),
-        })?;
+        });
+    }
+
+    let usage = resp.usage_metadata.unwrap_or(GeminiUsageMetadata {
+        prompt_token_count: 0,
+        candidates_token_count: 0,
+        total_token_count: 0,
+    });
+    let mut usage = Usage {
+        input_tokens: usage.prompt_token_count,
+        output_tokens: usage.candidates_token_count,
+        ..Default::default()
+    };
 
+    Ok(resp
+        .candidates
+        .into_iter()
+        .map(|candidate| {
+            candidate_response(
+                candidate,
+                model.clone(),
+                resp.model_version.clone(),
+                std::mem::take(&mut usage),
+                ids,
+            )
+        })
+        .collect())
+}
+
+/// The response for one candidate of a Gemini reply.
+fn candidate_response(
+    candidate: GeminiCandidate,
+    model: String,
+    served_model: Option<String>,
+    usage: Usage,
+    ids: &dyn IdSource,
+) -> Response {
     let blocks: Vec<ContentBlock> = candidate
         .content
         .parts
@@ -345,7 +667,7 @@ fn convert_gemini_response(resp: GeminiResponse, model: String) -> Result<Respon
                 Some(ContentBlock::Text { text })
             } else if let Some(fc) = part.function_call {
                 Some(ContentBlock::ToolUse {
-                    id: uuid::Uuid::new_v4().to_string(),
+                    id: ids.next_id(),
                     name: fc.name,
                     input: fc.args,
                 })
@@ -356,25 +678,27 @@ fn convert_gemini_response(resp: GeminiResponse, model: String) -> Result<Respon
         })
         .collect();
 
-    let stop_reason = parse_stop_reason(candidate.finish_reason.as_deref());
-
-    let usage = resp.usage_metadata.unwrap_or(GeminiUsageMetadata {
-        prompt_token_count: 0,
-        candidates_token_count: 0,
-        total_token_count: 0,
-    });
-
-    Ok(Response {
+    let has_tool_use = blocks
+        .iter()
+        .any(|block| matches!(block, ContentBlock::ToolUse { .. }));
+    let stop_reason = finish_stop_reason(candidate.finish_reason.as_deref(), has_tool_use);
+    let citations = candidate
+        .grounding_metadata
+        .map(|metadata| metadata.citations())
+        .unwrap_or_default();
+
+    Response {
         id: uuid::Uuid::new_v4().to_string(),
         content: blocks,
         stop_reason,
         model,
-        usage: Usage {
-            input_tokens: usage.prompt_token_count,
-            output_tokens: usage.candidates_token_count,
-            ..Default::default()
-        },
-    })
+        served_model,
+        system_fingerprint: None,
+        usage,
+        attempts: 1,
+        citations,
+        invalid_tool_inputs: Vec::new(),
+    }
 }
 
 /// Parse an SSE line from Gemini streaming response.
@@ -383,41 +707,42 @@ fn parse_gemini_sse(line: &str) -> Option<GeminiResponse> {
     serde_json::from_str(data).ok()
 }
 
+/// Parse an error payload sent on an SSE line.
+fn parse_gemini_sse_error(line: &str) -> Option<GeminiError> {
+    let data = line.strip_prefix("data: ")?;
+    serde_json::from_str(data).ok()
+}
+
 #[async_trait]
 impl super::client::LlmClient for GeminiClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
-        let gemini_req = GeminiRequest::from(req);
-        let url = format!(
-            "{}?key={}",
-            self.endpoint(&req.model, "generateContent"),
-            self.api_key
-        );
+        req.validate()?;
+        check_images(req)?;
 
-        let response = self
-            .http
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&gemini_req)
-            .send()
-            .await?;
+        let gemini_resp = self.generate(&req.model, &GeminiRequest::from(req)).await?;
+        convert_gemini_response(gemini_resp, req.model.clone(), self.ids.as_ref())
+    }
 
-        let status = response.status();
-        if !status.is_success() {
-            let error: GeminiError = response.json().await?;
-            return Err(LlmError::Api {
-                status: status.as_u16(),
-                message: error.error.message,
-            });
-        }
+    async fn create_messages(&self, req: &Request) -> Result<Vec<Response>, LlmError> {
+        req.validate()?;
+        check_images(req)?;
 
-        let gemini_resp: GeminiResponse = response.json().await?;
-        convert_gemini_response(gemini_resp, req.model.clone())
+        let mut gemini_req = GeminiRequest::from(req);
+        if let Some(n) = payload::candidates(req) {
+            gemini_req
+                .generation_config
+                .get_or_insert_with(Default::default)
+                .candidate_count = Some(n);
+        }
+        let gemini_resp = self.generate(&req.model, &gemini_req).await?;
+        convert_gemini_candidates(gemini_resp, req.model.clone(), self.ids.as_ref())
     }
 
     fn create_message_stream(
         &self,
         req: &Request,
     ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+        let validation = req.validate().and_then(|()| check_images(req));
         let gemini_req = GeminiRequest::from(req);
         let url = format!(
             "{}?key={}&alt=sse",
@@ -426,8 +751,12 @@ impl super::client::LlmClient for GeminiClient {
         );
         let model = req.model.clone();
         let http = self.http.clone();
+        let max_response_bytes = self.max_response_bytes;
+        let ids = self.ids.clone();
+
+        Box::pin(with_finished_blocks(async_stream::try_stream! {
+            validation?;
 
-        Box::pin(async_stream::try_stream! {
             let response = http
                 .post(&url)
                 .header("Content-Type", "application/json")
@@ -437,7 +766,7 @@ impl super::client::LlmClient for GeminiClient {
 
             let status = response.status();
             if !status.is_success() {
-                let error_text = response.text().await?;
+                let error_text = read_text(response, max_response_bytes).await?;
                 let error: GeminiError = serde_json::from_str(&error_text)?;
                 Err(LlmError::Api {
                     status: status.as_u16(),
@@ -447,24 +776,34 @@ impl super::client::LlmClient for GeminiClient {
             }
 
             let mut stream = response.bytes_stream();
-            let mut buffer = String::new();
+            // Buffer raw bytes so multi-byte characters split across chunks survive
+            let mut buffer: Vec<u8> = Vec::new();
             let mut message_started = false;
             let mut current_text_index: Option<usize> = None;
             let mut block_index = 0usize;
+            let mut has_tool_use = false;
 
             while let Some(chunk) = futures::StreamExt::next(&mut stream).await {
                 let chunk = chunk?;
-                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                buffer.extend_from_slice(&chunk);
 
                 // Process complete lines
-                while let Some(pos) = buffer.find('\n') {
-                    let line = buffer[..pos].trim().to_string();
-                    buffer = buffer[pos + 1..].to_string();
+                while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                    let line_bytes: Vec<u8> = buffer.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line_bytes).trim().to_string();
 
                     if line.is_empty() {
                         continue;
                     }
 
+                    // Errors after the stream has started arrive as data lines
+                    if let Some(error) = parse_gemini_sse_error(&line) {
+                        Err(LlmError::Api {
+                            status: u16::try_from(error.error.code).unwrap_or(0),
+                            message: error.error.message,
+                        })?;
+                    }
+
                     if let Some(gemini_resp) = parse_gemini_sse(&line) {
                         if !message_started {
                             yield StreamEvent::MessageStart {
@@ -497,16 +836,17 @@ impl super::client::LlmClient for GeminiClient {
                                 if let Some(fc) = part.function_call {
                                     // Close text block if open
                                     if let Some(idx) = current_text_index.take() {
-                                        yield StreamEvent::ContentBlockStop { index: idx };
+                                        yield StreamEvent::ContentBlockStop { index: idx, block: None };
                                     }
 
                                     let tool_index = block_index;
                                     block_index += 1;
+                                    has_tool_use = true;
 
                                     yield StreamEvent::ContentBlockStart {
                                         index: tool_index,
                                         block: ContentBlock::ToolUse {
-                                            id: uuid::Uuid::new_v4().to_string(),
+                                            id: ids.next_id(),
                                             name: fc.name,
                                             input: serde_json::Value::Object(serde_json::Map::new()),
                                         },
@@ -519,7 +859,7 @@ impl super::client::LlmClient for GeminiClient {
                                         partial_json: args_json,
                                     };
 
-                                    yield StreamEvent::ContentBlockStop { index: tool_index };
+                                    yield StreamEvent::ContentBlockStop { index: tool_index, block: None };
                                 }
 
                                 // function_response is input, not output - ignore
@@ -529,7 +869,7 @@ impl super::client::LlmClient for GeminiClient {
                             if let Some(reason) = candidate.finish_reason {
                                 // Close text block if open
                                 if let Some(idx) = current_text_index.take() {
-                                    yield StreamEvent::ContentBlockStop { index: idx };
+                                    yield StreamEvent::ContentBlockStop { index: idx, block: None };
                                 }
 
                                 let usage = gemini_resp.usage_metadata.as_ref().map(|u| Usage {
@@ -539,7 +879,7 @@ impl super::client::LlmClient for GeminiClient {
                                 }).unwrap_or_default();
 
                                 yield StreamEvent::MessageDelta {
-                                    stop_reason: Some(parse_stop_reason(Some(&reason))),
+                                    stop_reason: Some(finish_stop_reason(Some(&reason), has_tool_use)),
                                     usage,
                                 };
                                 yield StreamEvent::MessageStop;
@@ -548,13 +888,14 @@ impl super::client::LlmClient for GeminiClient {
                     }
                 }
             }
-        })
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::{Instruction, SeqIdSource};
 
     #[test]
     fn test_client_from_env_missing() {
@@ -579,6 +920,83 @@ mod tests {
         assert!(gemini_req.generation_config.is_some());
     }
 
+    #[test]
+    fn test_instruction_layers_collapse_into_system_instruction() {
+        let req = Request::new("gemini-2.0-flash")
+            .message(Message::user("Hello"))
+            .system("Platform rules.")
+            .instruction(Instruction::developer("App rules."));
+
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert_eq!(
+            json["systemInstruction"]["parts"][0]["text"],
+            "Platform rules.\n\n---\n\nApp rules."
+        );
+    }
+
+    #[test]
+    fn test_image_inline_data() {
+        let req = Request::new("gemini-2.0-flash").message(Message::user_with_image(
+            "What is this?",
+            ImageSource::base64("image/png", "iVBOR"),
+        ));
+        assert!(check_images(&req).is_ok());
+
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert_eq!(
+            json["contents"][0]["parts"],
+            serde_json::json!([
+                {"inlineData": {"mimeType": "image/png", "data": "iVBOR"}},
+                {"text": "What is this?"}
+            ])
+        );
+
+        let req = Request::new("gemini-2.0-flash").message(Message::user_with_image(
+            "What is this?",
+            ImageSource::url("https://example.com/cat.png"),
+        ));
+        let err = check_images(&req).unwrap_err();
+        assert!(matches!(err, LlmError::InvalidRequest(ref msg) if msg.contains("image URLs")));
+    }
+
+    #[test]
+    fn test_reasoning_blocks_not_sent() {
+        let req = Request::new("gemini-2.0-flash")
+            .message(Message::user("Hi"))
+            .message(Message {
+                role: Role::Assistant,
+                content: vec![
+                    ContentBlock::thinking("Say hello.", "sig_1"),
+                    ContentBlock::RedactedThinking {
+                        data: "opaque".into(),
+                    },
+                    ContentBlock::text("Hello!"),
+                ],
+            });
+
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert_eq!(
+            json["contents"][1]["parts"],
+            serde_json::json!([{"text": "Hello!"}])
+        );
+    }
+
+    #[test]
+    fn test_sampling_params_serialized() {
+        let req = Request::new("gemini-2.0-flash").top_p(0.7);
+
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert_eq!(json["generationConfig"]["topP"], 0.7);
+        assert!(json["generationConfig"].get("temperature").is_none());
+
+        let req = Request::new("gemini-2.0-flash").stop_sequences(["END"]);
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert_eq!(
+            json["generationConfig"]["stopSequences"],
+            serde_json::json!(["END"])
+        );
+    }
+
     #[test]
     fn test_tool_definition_conversion() {
         let tool = ToolDefinition {
@@ -596,4 +1014,346 @@ mod tests {
         assert_eq!(gemini_func.name, "get_weather");
         assert_eq!(gemini_func.description, "Get the weather");
     }
+
+    #[test]
+    fn test_tool_choice_serialization() {
+        let req = Request::new("gemini-2.0-flash").tool(ToolDefinition {
+            name: "get_weather".to_string(),
+            description: "Get the weather".to_string(),
+            input_schema: serde_json::json!({"type": "object", "properties": {}}),
+        });
+
+        let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+        assert!(json.get("toolConfig").is_none());
+
+        for (choice, expected) in [
+            (ToolChoice::None, serde_json::json!({"mode": "NONE"})),
+            (ToolChoice::Required, serde_json::json!({"mode": "ANY"})),
+            (
+                ToolChoice::Tool("get_weather".into()),
+                serde_json::json!({"mode": "ANY", "allowedFunctionNames": ["get_weather"]}),
+            ),
+        ] {
+            let req = req.clone().tool_choice(choice);
+            let json = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+            assert_eq!(json["toolConfig"]["functionCallingConfig"], expected);
+        }
+    }
+
+    async fn collect_stream(client: &GeminiClient, req: &Request) -> Vec<StreamEvent> {
+        use crate::llm::LlmClient;
+        use futures::StreamExt;
+
+        client
+            .create_message_stream(req)
+            .map(|event| event.unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_stream_text_and_tool_call() {
+        use crate::llm::stream_accumulator::StreamAccumulator;
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let body = [
+            r#"data: {"candidates": [{"content": {"role": "model", "parts": [{"text": "Checking "}]}}]}"#,
+            r#"data: {"candidates": [{"content": {"role": "model", "parts": [{"text": "the weather"}]}}]}"#,
+            r#"data: {"candidates": [{"content": {"role": "model", "parts": [{"functionCall": {"name": "get_weather", "args": {"location": "Paris"}}}]}, "finishReason": "STOP"}], "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 7}}"#,
+        ]
+        .map(|line| format!("{}\r\n\r\n", line))
+        .concat();
+        let (base_url, server) = serve(vec![RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/event-stream".into())],
+            body,
+        }])
+        .await;
+        let client = GeminiClient::new("test-key")
+            .with_base_url(base_url)
+            .with_id_source(Arc::new(SeqIdSource::new("call_")));
+
+        let req = Request::new("gemini-2.0-flash").message(Message::user("Weather in Paris?"));
+        let events = collect_stream(&client, &req).await;
+
+        assert!(matches!(events[0], StreamEvent::MessageStart { .. }));
+        assert!(matches!(events.last(), Some(StreamEvent::MessageStop)));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::MessageDelta {
+                stop_reason: Some(StopReason::ToolUse),
+                usage: Usage {
+                    input_tokens: 12,
+                    output_tokens: 7,
+                    ..
+                },
+            }
+        )));
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::ContentBlockStop {
+                block: Some(ContentBlock::ToolUse { input, .. }),
+                ..
+            } if input["location"] == "Paris"
+        )));
+
+        let mut accumulator = StreamAccumulator::new();
+        for event in &events {
+            accumulator.handle_event(event);
+        }
+        let content = accumulator.into_content();
+        assert_eq!(content.len(), 2);
+        assert!(
+            matches!(&content[0], ContentBlock::Text { text } if text == "Checking the weather")
+        );
+        match &content[1] {
+            ContentBlock::ToolUse { id, name, input } => {
+                assert_eq!(id, "call_1");
+                assert_eq!(name, "get_weather");
+                assert_eq!(input["location"], "Paris");
+            }
+            other => panic!("Expected tool use, got {:?}", other),
+        }
+
+        let raw_request = &server.await.unwrap()[0];
+        assert!(raw_request.starts_with(
+            "POST /models/gemini-2.0-flash:streamGenerateContent?key=test-key&alt=sse "
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_messages_asks_for_candidate_count() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, server) = serve(vec![RecordedResponse::json(
+            200,
+            r#"{
+                "candidates": [
+                    {"content": {"role": "model", "parts": [{"text": "Hi"}]}, "finishReason": "STOP"},
+                    {"content": {"role": "model", "parts": [{"text": "Hey"}]}, "finishReason": "STOP"}
+                ],
+                "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 2, "totalTokenCount": 7}
+            }"#,
+        )])
+        .await;
+        let client = GeminiClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gemini-2.0-flash")
+            .message(Message::user("Hello"))
+            .candidates(2);
+        let responses = client.create_messages(&req).await.unwrap();
+
+        let texts: Vec<String> = responses.iter().map(|r| r.text()).collect();
+        assert_eq!(texts, ["Hi", "Hey"]);
+        let total: Usage = responses.iter().map(|r| &r.usage).sum();
+        assert_eq!((total.input_tokens, total.output_tokens), (5, 2));
+
+        let raw_request = &server.await.unwrap()[0];
+        assert!(raw_request.contains(r#""generationConfig":{"candidateCount":2}"#));
+    }
+
+    #[tokio::test]
+    async fn test_stream_finish_without_parts() {
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let body = [
+            r#"data: {"candidates": [{"content": {"role": "model", "parts": [{"text": "Hi"}]}}]}"#,
+            r#"data: {"candidates": [{"finishReason": "MAX_TOKENS"}]}"#,
+        ]
+        .map(|line| format!("{}\n\n", line))
+        .concat();
+        let (base_url, _server) = serve(vec![RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/event-stream".into())],
+            body,
+        }])
+        .await;
+        let client = GeminiClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gemini-2.0-flash").message(Message::user("Hello"));
+        let events = collect_stream(&client, &req).await;
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::MessageDelta {
+                stop_reason: Some(StopReason::MaxTokens),
+                ..
+            }
+        )));
+        assert!(matches!(events.last(), Some(StreamEvent::MessageStop)));
+    }
+
+    #[test]
+    fn test_function_call_response_stop_reason() {
+        let resp: GeminiResponse = serde_json::from_str(
+            r#"{"candidates": [{"content": {"parts": [{"functionCall": {"name": "f", "args": {}}}]}, "finishReason": "STOP"}]}"#,
+        )
+        .unwrap();
+
+        let response =
+            convert_gemini_response(resp, "gemini-2.0-flash".into(), &UuidIdSource).unwrap();
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+    }
+
+    #[test]
+    fn test_safety_block_is_content_filter() {
+        for reason in [
+            "SAFETY",
+            "RECITATION",
+            "BLOCKLIST",
+            "PROHIBITED_CONTENT",
+            "SPII",
+        ] {
+            let resp: GeminiResponse = serde_json::from_value(serde_json::json!({
+                "candidates": [{"content": {"parts": []}, "finishReason": reason}]
+            }))
+            .unwrap();
+
+            let response =
+                convert_gemini_response(resp, "gemini-2.0-flash".into(), &UuidIdSource).unwrap();
+            assert_eq!(
+                response.stop_reason,
+                StopReason::ContentFilter,
+                "{}",
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_calls_take_ids_from_source() {
+        let resp: GeminiResponse = serde_json::from_str(
+            r#"{"candidates": [{"content": {"parts": [{"functionCall": {"name": "f", "args": {}}}, {"functionCall": {"name": "g", "args": {}}}]}, "finishReason": "STOP"}]}"#,
+        )
+        .unwrap();
+
+        let ids = SeqIdSource::new("call_");
+        let response = convert_gemini_response(resp, "gemini-2.0-flash".into(), &ids).unwrap();
+        let call_ids: Vec<&str> = response
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(call_ids, ["call_1", "call_2"]);
+    }
+
+    #[test]
+    fn test_model_version_reported_as_served_model() {
+        let resp: GeminiResponse = serde_json::from_str(
+            r#"{"candidates": [{"content": {"parts": [{"text": "Hi"}]}, "finishReason": "STOP"}], "modelVersion": "gemini-1.5-flash-002"}"#,
+        )
+        .unwrap();
+
+        let response =
+            convert_gemini_response(resp, "gemini-2.0-flash".into(), &UuidIdSource).unwrap();
+        assert_eq!(response.model, "gemini-2.0-flash");
+        assert_eq!(response.actual_model(), "gemini-1.5-flash-002");
+        assert!(response.model_substituted());
+    }
+
+    #[test]
+    fn test_grounding_metadata_becomes_citations() {
+        let resp: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Spain won Euro 2024."}]},
+                "finishReason": "STOP",
+                "groundingMetadata": {
+                    "webSearchQueries": ["who won euro 2024"],
+                    "groundingChunks": [
+                        {"web": {"uri": "https://example.com/uefa", "title": "uefa.com"}},
+                        {"web": {"uri": "https://example.com/news", "title": "news.com"}},
+                        {"web": {"uri": "https://example.com/unused", "title": "unused.com"}}
+                    ],
+                    "groundingSupports": [{
+                        "segment": {"startIndex": 0, "endIndex": 20, "text": "Spain won Euro 2024."},
+                        "groundingChunkIndices": [0, 1],
+                        "confidenceScores": [0.9, 0.8]
+                    }]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let response =
+            convert_gemini_response(resp, "gemini-2.0-flash".into(), &UuidIdSource).unwrap();
+        let cited = |url: &str, title: &str, text: Option<&str>| Citation {
+            url: url.into(),
+            title: Some(title.into()),
+            cited_text: text.map(Into::into),
+        };
+        assert_eq!(
+            response.citations(),
+            &[
+                cited(
+                    "https://example.com/uefa",
+                    "uefa.com",
+                    Some("Spain won Euro 2024.")
+                ),
+                cited(
+                    "https://example.com/news",
+                    "news.com",
+                    Some("Spain won Euro 2024.")
+                ),
+                cited("https://example.com/unused", "unused.com", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_golden() {
+        use crate::llm::payload::audit::*;
+
+        let body = serde_json::to_value(GeminiRequest::from(&representative_request())).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "contents": [
+                    {"role": "user", "parts": [{"text": "What time is it?"}]},
+                    {"role": "model", "parts": [
+                        {"text": "Checking."},
+                        {"functionCall": {"name": "get_time", "args": {}}}
+                    ]},
+                    {"role": "user", "parts": [
+                        {"functionResponse": {"name": "get_time", "response": {"result": "12:00"}}}
+                    ]}
+                ],
+                "systemInstruction": {"parts": [{"text": "You are helpful."}]},
+                "generationConfig": {
+                    "maxOutputTokens": 1024,
+                    "temperature": 0.5,
+                    "stopSequences": ["END"]
+                },
+                "tools": [{"functionDeclarations": [{
+                    "name": "get_time",
+                    "description": "Get the current time",
+                    "parameters": {"type": "object", "properties": {}}
+                }]}]
+            })
+        );
+
+        let body = serde_json::to_value(GeminiRequest::from(&sparse_request())).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "contents": [
+                    {"role": "user", "parts": [{"text": "Hi\n\nStill there?"}]}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_bodies_have_no_empty_fields() {
+        use crate::llm::payload::audit::*;
+
+        for req in [representative_request(), sparse_request()] {
+            let body = serde_json::to_value(GeminiRequest::from(&req)).unwrap();
+            assert_eq!(empty_fields(&body), Vec::<String>::new());
+        }
+    }
 }
@@ -0,0 +1,167 @@
+// ABOUTME: HttpConfig - proxy, timeout and TLS settings for the LLM clients.
+// ABOUTME: Builds the reqwest::Client a provider client sends its requests with.
+
+use std::time::Duration;
+
+use crate::error::LlmError;
+
+/// Default limit on establishing a connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default limit on waiting for the next bytes of a response. It applies
+/// between reads rather than to the whole response, so long streams are
+/// fine as long as they keep sending.
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Marks the start of each certificate in a PEM file.
+const PEM_CERTIFICATE_HEADER: &[u8] = b"-----BEGIN CERTIFICATE-----";
+
+/// HTTP settings for an LLM client, for networks that need a proxy or a
+/// private certificate authority.
+///
+/// Build a `reqwest::Client` from it and hand that to the client's
+/// `with_http_client`. One built client can be shared by several provider
+/// clients, which then share its connection pool.
+///
+/// ```
+/// use mux::llm::{AnthropicClient, HttpConfig};
+///
+/// let http = HttpConfig {
+///     proxy: Some("http://proxy.corp.example:3128".into()),
+///     ..Default::default()
+/// }
+/// .build()
+/// .unwrap();
+/// let client = AnthropicClient::new("sk-test").with_http_client(http);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConfig {
+    /// Send every request through this proxy URL. Without one, the
+    /// `HTTPS_PROXY` family of environment variables still applies.
+    pub proxy: Option<String>,
+
+    /// Limit on establishing a connection.
+    pub connect_timeout: Duration,
+
+    /// Limit on waiting for the next bytes of a response.
+    pub read_timeout: Duration,
+
+    /// PEM-encoded certificates, or bundles of them, to trust in addition to
+    /// the built-in roots.
+    pub root_certificates: Vec<Vec<u8>>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            root_certificates: Vec::new(),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Build a client with these settings. Fails with
+    /// [`LlmError::Configuration`] if the proxy URL or a certificate is
+    /// invalid.
+    pub fn build(&self) -> Result<reqwest::Client, LlmError> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout);
+
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| LlmError::Configuration(format!("Invalid proxy URL: {}", e)))?;
+            builder = builder.proxy(proxy);
+        }
+        for pem in &self.root_certificates {
+            let contains_certificate = pem
+                .windows(PEM_CERTIFICATE_HEADER.len())
+                .any(|w| w == PEM_CERTIFICATE_HEADER);
+            let certificates = reqwest::Certificate::from_pem_bundle(pem)
+                .ok()
+                .filter(|_| contains_certificate)
+                .ok_or_else(|| {
+                    LlmError::Configuration("Invalid root certificate: expected PEM".into())
+                })?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|e| LlmError::Configuration(format!("Failed to create HTTP client: {}", e)))
+    }
+}
+
+/// The client provider clients start with: default [`HttpConfig`] settings.
+pub(crate) fn default_http_client() -> reqwest::Client {
+    HttpConfig::default()
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_rejects_bad_settings() {
+        let bad_proxy = HttpConfig {
+            proxy: Some("not a url".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            bad_proxy.build(),
+            Err(LlmError::Configuration(message)) if message.starts_with("Invalid proxy URL")
+        ));
+
+        let bad_certificate = HttpConfig {
+            root_certificates: vec![b"not a certificate".to_vec()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            bad_certificate.build(),
+            Err(LlmError::Configuration(message)) if message.starts_with("Invalid root certificate")
+        ));
+
+        assert!(HttpConfig::default().build().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_uses_shared_http_client() {
+        use crate::llm::test_server::serve_once;
+        use crate::llm::{AnthropicClient, LlmClient, Message, Request};
+
+        let (base_url, server) = serve_once(
+            200,
+            r#"{
+                "id": "msg_1",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-test",
+                "content": [{"type": "text", "text": "Hi"}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 3, "output_tokens": 1}
+            }"#,
+        )
+        .await;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-corp-tag", "mux".parse().unwrap());
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let client = AnthropicClient::new("test-key")
+            .with_base_url(base_url)
+            .with_http_client(http);
+
+        let req = Request::new("claude-test").message(Message::user("Hello"));
+        client.create_message(&req).await.unwrap();
+
+        assert!(server.await.unwrap()[0].contains("x-corp-tag: mux"));
+    }
+}
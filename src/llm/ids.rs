@@ -0,0 +1,68 @@
+// ABOUTME: Sources of IDs for tool calls the provider doesn't name itself.
+// ABOUTME: UUIDs by default; SeqIdSource makes IDs reproducible in tests.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Mints IDs for tool calls that arrive without one.
+///
+/// Clients use [`UuidIdSource`] unless given another. Swap in a
+/// [`SeqIdSource`] to assert on IDs or to compare transcripts across runs.
+pub trait IdSource: Send + Sync + fmt::Debug {
+    /// A new ID, different from every ID this source returned before.
+    fn next_id(&self) -> String;
+}
+
+/// Random v4 UUIDs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidIdSource;
+
+impl IdSource for UuidIdSource {
+    fn next_id(&self) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// IDs `<prefix>1`, `<prefix>2`, and so on.
+#[derive(Debug)]
+pub struct SeqIdSource {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SeqIdSource {
+    /// Count up from 1, putting `prefix` before each number.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+impl IdSource for SeqIdSource {
+    fn next_id(&self) -> String {
+        format!(
+            "{}{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seq_ids_count_from_one() {
+        let ids = SeqIdSource::new("call_");
+        assert_eq!(ids.next_id(), "call_1");
+        assert_eq!(ids.next_id(), "call_2");
+    }
+
+    #[test]
+    fn test_uuid_ids_differ() {
+        assert_ne!(UuidIdSource.next_id(), UuidIdSource.next_id());
+    }
+}
@@ -0,0 +1,324 @@
+// ABOUTME: WithMiddleware - wraps any LlmClient with request/response callbacks.
+// ABOUTME: Includes LatencyLogger, which reports each call's latency and token usage.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+
+use super::stream_accumulator::StreamAccumulator;
+use super::{LlmClient, Request, Response, StreamEvent};
+use crate::error::LlmError;
+
+/// Callbacks around every call a [`WithMiddleware`] client makes.
+///
+/// All methods default to doing nothing, so a middleware only implements
+/// the ones it needs.
+pub trait Middleware: Send + Sync {
+    /// Adjust a request before it is sent, e.g. to add metadata.
+    fn before_request(&self, _req: &mut Request) {}
+
+    /// Observe a completed response and how long it took. Streamed responses
+    /// are assembled from their events and reported once the stream ends.
+    fn after_response(&self, _req: &Request, _response: &Response, _elapsed: Duration) {}
+
+    /// Observe a failed call and how long it took to fail.
+    fn on_error(&self, _req: &Request, _error: &LlmError, _elapsed: Duration) {}
+}
+
+/// An [`LlmClient`] that runs [`Middleware`] around another client's calls.
+///
+/// `before_request` runs in the order middleware was added, and
+/// `after_response` and `on_error` in reverse, so the first middleware
+/// added is the outermost: it changes the request first and sees the
+/// response last. Retries happen inside the wrapped client and rate
+/// limiting around this one, so middleware sees one call per request.
+///
+/// A stream dropped before it ends reports neither a response nor an error.
+///
+/// ```
+/// use mux::llm::{AnthropicClient, LatencyLogger, WithMiddleware};
+///
+/// let client = WithMiddleware::new(AnthropicClient::new("sk-test"))
+///     .with_middleware(LatencyLogger::new());
+/// ```
+pub struct WithMiddleware<C> {
+    inner: C,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl<C: LlmClient> WithMiddleware<C> {
+    /// Wrap `inner` with no middleware yet.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            middleware: Vec::new(),
+        }
+    }
+
+    /// Run `middleware` around every call, after any added before it.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// The wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn prepare(&self, req: &Request) -> Request {
+        let mut req = req.clone();
+        for middleware in &self.middleware {
+            middleware.before_request(&mut req);
+        }
+        req
+    }
+
+    fn finish(&self, req: &Request, outcome: Result<&Response, &LlmError>, elapsed: Duration) {
+        for middleware in self.middleware.iter().rev() {
+            match outcome {
+                Ok(response) => middleware.after_response(req, response, elapsed),
+                Err(error) => middleware.on_error(req, error, elapsed),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<C: LlmClient> LlmClient for WithMiddleware<C> {
+    async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        let req = self.prepare(req);
+        let started = Instant::now();
+        let result = self.inner.create_message(&req).await;
+        self.finish(&req, result.as_ref(), started.elapsed());
+        result
+    }
+
+    fn create_message_stream(
+        &self,
+        req: &Request,
+    ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + '_>> {
+        let req = self.prepare(req);
+        Box::pin(async_stream::try_stream! {
+            let started = Instant::now();
+            let mut stream = self.inner.create_message_stream(&req);
+            let mut accumulator = StreamAccumulator::new();
+
+            while let Some(event) = stream.next().await {
+                let event = event.inspect_err(|e| self.finish(&req, Err(e), started.elapsed()))?;
+                accumulator.handle_event(&event);
+                yield event;
+            }
+
+            let response = accumulator.into_response(req.model.clone());
+            self.finish(&req, Ok(&response), started.elapsed());
+        })
+    }
+
+    async fn create_messages(&self, req: &Request) -> Result<Vec<Response>, LlmError> {
+        let req = self.prepare(req);
+        let started = Instant::now();
+        let result = self.inner.create_messages(&req).await;
+        let elapsed = started.elapsed();
+        match &result {
+            Ok(responses) => {
+                for response in responses {
+                    self.finish(&req, Ok(response), elapsed);
+                }
+            }
+            Err(error) => self.finish(&req, Err(error), elapsed),
+        }
+        result
+    }
+
+    async fn count_tokens(&self, req: &Request) -> Result<usize, LlmError> {
+        self.inner.count_tokens(&self.prepare(req)).await
+    }
+
+    fn supports_tool_result_images(&self) -> bool {
+        self.inner.supports_tool_result_images()
+    }
+}
+
+/// Where [`LatencyLogger`] writes its lines.
+type LogSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Middleware that logs one line per call: the model, how long the call
+/// took, and the tokens it used, or the error it failed with.
+///
+/// Lines go to stderr unless [`with_sink`](Self::with_sink) sends them
+/// elsewhere.
+pub struct LatencyLogger {
+    sink: LogSink,
+}
+
+impl LatencyLogger {
+    /// Log to stderr.
+    pub fn new() -> Self {
+        Self {
+            sink: Arc::new(|line| eprintln!("{}", line)),
+        }
+    }
+
+    /// Pass each line to `sink` instead of printing it.
+    pub fn with_sink(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.sink = Arc::new(sink);
+        self
+    }
+}
+
+impl Default for LatencyLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for LatencyLogger {
+    fn after_response(&self, _req: &Request, response: &Response, elapsed: Duration) {
+        (self.sink)(&format!(
+            "LLM {} took {} ms: {} input tokens, {} output tokens",
+            response.actual_model(),
+            elapsed.as_millis(),
+            response.usage.input_tokens,
+            response.usage.output_tokens
+        ));
+    }
+
+    fn on_error(&self, req: &Request, error: &LlmError, elapsed: Duration) {
+        (self.sink)(&format!(
+            "LLM {} failed after {} ms: {}",
+            req.model,
+            elapsed.as_millis(),
+            error
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::llm::{Message, MockClient};
+
+    /// Tags requests with a system prompt and records what it sees.
+    struct Recorder {
+        name: &'static str,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before_request(&self, req: &mut Request) {
+            let system = req.system.take().unwrap_or_default();
+            req.system = Some(format!("{}{}", system, self.name));
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("before {}", self.name));
+        }
+
+        fn after_response(&self, _req: &Request, response: &Response, _elapsed: Duration) {
+            self.seen.lock().unwrap().push(format!(
+                "after {}: {:?} {}",
+                self.name, response.stop_reason, response.usage.output_tokens
+            ));
+        }
+
+        fn on_error(&self, _req: &Request, error: &LlmError, _elapsed: Duration) {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("error {}: {}", self.name, error));
+        }
+    }
+
+    fn recorded(client: MockClient) -> (WithMiddleware<MockClient>, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let client = WithMiddleware::new(client)
+            .with_middleware(Recorder {
+                name: "a",
+                seen: Arc::clone(&seen),
+            })
+            .with_middleware(Recorder {
+                name: "b",
+                seen: Arc::clone(&seen),
+            });
+        (client, seen)
+    }
+
+    fn request() -> Request {
+        Request::new("test-model").message(Message::user("Hi"))
+    }
+
+    #[tokio::test]
+    async fn test_middleware_wraps_create_message() {
+        let (client, seen) = recorded(MockClient::new().with_text("Hello").with_error(
+            LlmError::Api {
+                status: 500,
+                message: "boom".into(),
+            },
+        ));
+
+        client.create_message(&request()).await.unwrap();
+        assert_eq!(client.inner().requests()[0].system.as_deref(), Some("ab"));
+        assert!(client.create_message(&request()).await.is_err());
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[..2], ["before a", "before b"]);
+        assert!(seen[2].starts_with("after b: EndTurn"));
+        assert!(seen[3].starts_with("after a: EndTurn"));
+        assert_eq!(seen[6], "error b: API error (500): boom");
+        assert_eq!(seen[7], "error a: API error (500): boom");
+    }
+
+    #[tokio::test]
+    async fn test_middleware_sees_streamed_response_once_it_ends() {
+        let (client, seen) = recorded(MockClient::new().with_text("Hello"));
+
+        let mut stream = client.create_message_stream(&request());
+        let mut events = 0;
+        while let Some(event) = stream.next().await {
+            event.unwrap();
+            events += 1;
+            if seen.lock().unwrap().len() > 2 {
+                panic!("response reported before the stream ended");
+            }
+        }
+        drop(stream);
+
+        assert!(events > 0);
+        assert_eq!(client.inner().requests()[0].system.as_deref(), Some("ab"));
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 4);
+        assert!(seen[2].starts_with("after b: EndTurn"));
+    }
+
+    #[tokio::test]
+    async fn test_latency_logger_reports_tokens_and_errors() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let client = WithMiddleware::new(
+            MockClient::new()
+                .with_text("Hello")
+                .with_error(LlmError::StreamClosed),
+        )
+        .with_middleware(
+            LatencyLogger::new().with_sink(move |line| sink.lock().unwrap().push(line.to_string())),
+        );
+
+        let response = client.create_message(&request()).await.unwrap();
+        let _ = client.create_message(&request()).await;
+
+        let lines = lines.lock().unwrap();
+        assert!(lines[0].starts_with("LLM test-model took "));
+        assert!(lines[0].ends_with(&format!(
+            "{} input tokens, {} output tokens",
+            response.usage.input_tokens, response.usage.output_tokens
+        )));
+        assert!(lines[1].starts_with("LLM test-model failed after "));
+        assert!(lines[1].ends_with("Stream closed unexpectedly"));
+    }
+}
@@ -0,0 +1,271 @@
+// ABOUTME: Scripted LlmClient that replays queued responses without a network.
+// ABOUTME: Lets agent loops and tool registries be tested against canned turns.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{ContentBlock, LlmClient, Request, Response, StopReason, Usage};
+use crate::error::LlmError;
+
+/// Checks a request before its reply is returned, panicking on a mismatch.
+type RequestCheck = Box<dyn Fn(&Request) + Send + Sync>;
+
+/// One scripted turn: the reply, and an optional check on the request.
+struct Turn {
+    reply: Result<Response, LlmError>,
+    check: Option<RequestCheck>,
+}
+
+/// An [`LlmClient`] that answers each request with the next queued reply.
+///
+/// ```
+/// use mux::llm::MockClient;
+///
+/// let client = MockClient::new()
+///     .with_tool_use("read_file", serde_json::json!({"path": "README.md"}))
+///     .expecting(|req| assert_eq!(req.messages.len(), 1))
+///     .with_text("The README describes the project.");
+/// ```
+///
+/// Requests are recorded for inspection with [`requests`](Self::requests).
+/// Replies whose `model` is empty report the requested model. Once the queue
+/// runs out, requests fail with [`LlmError::InvalidRequest`].
+///
+/// Streaming uses the [`LlmClient`] default, which replays each reply as a
+/// burst of stream events, so agents with streaming on see the same turns.
+#[derive(Default)]
+pub struct MockClient {
+    turns: Mutex<VecDeque<Turn>>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl MockClient {
+    /// Create a client with nothing queued.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `response` as the next reply.
+    pub fn with_response(self, response: Response) -> Self {
+        self.push(Ok(response))
+    }
+
+    /// Queue a reply that ends the turn with `text`.
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        self.with_response(Self::response(
+            vec![ContentBlock::text(text)],
+            StopReason::EndTurn,
+        ))
+    }
+
+    /// Queue a reply that calls tool `name` with `input`.
+    ///
+    /// The call's id is `call_<n>`, where `n` is the reply's position in the
+    /// queue, counting from 0.
+    pub fn with_tool_use(self, name: impl Into<String>, input: serde_json::Value) -> Self {
+        let id = format!("call_{}", self.queued());
+        self.with_response(Self::response(
+            vec![ContentBlock::ToolUse {
+                id,
+                name: name.into(),
+                input,
+            }],
+            StopReason::ToolUse,
+        ))
+    }
+
+    /// Queue `error` as the next reply.
+    pub fn with_error(self, error: LlmError) -> Self {
+        self.push(Err(error))
+    }
+
+    /// Run `check` on the request answered by the most recently queued
+    /// reply. Panics in `check`, such as failed assertions, fail the test.
+    ///
+    /// # Panics
+    ///
+    /// If nothing is queued yet.
+    pub fn expecting(self, check: impl Fn(&Request) + Send + Sync + 'static) -> Self {
+        self.turns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .back_mut()
+            .expect("expecting() must follow a queued reply")
+            .check = Some(Box::new(check));
+        self
+    }
+
+    /// The requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// How many queued replies haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        self.queued()
+    }
+
+    fn queued(&self) -> usize {
+        self.turns.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    fn push(self, reply: Result<Response, LlmError>) -> Self {
+        self.turns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(Turn { reply, check: None });
+        self
+    }
+
+    fn response(content: Vec<ContentBlock>, stop_reason: StopReason) -> Response {
+        Response {
+            id: String::new(),
+            content,
+            stop_reason,
+            model: String::new(),
+            served_model: None,
+            system_fingerprint: None,
+            usage: Usage::default(),
+            attempts: 1,
+            citations: Vec::new(),
+            invalid_tool_inputs: Vec::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmClient for MockClient {
+    async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        let call = {
+            let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+            requests.push(req.clone());
+            requests.len()
+        };
+        let turn = self
+            .turns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front();
+        let Some(turn) = turn else {
+            return Err(LlmError::InvalidRequest(format!(
+                "MockClient has no reply queued for request {}",
+                call
+            )));
+        };
+
+        if let Some(check) = &turn.check {
+            check(req);
+        }
+        let mut response = turn.reply?;
+        if response.id.is_empty() {
+            response.id = format!("msg_{}", call);
+        }
+        if response.model.is_empty() {
+            response.model = req.model.clone();
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::agent::{AgentDefinition, AgentStopReason, SubAgent};
+    use crate::llm::{Message, StreamEvent};
+    use crate::tool::Registry;
+
+    #[tokio::test]
+    async fn test_replies_in_order_then_fails() {
+        let client = MockClient::new()
+            .with_tool_use("search", serde_json::json!({"query": "mux"}))
+            .with_error(LlmError::Api {
+                status: 529,
+                message: "overloaded".into(),
+            })
+            .with_text("Done");
+        let req = Request::new("test-model").message(Message::user("Hi"));
+
+        let first = client.create_message(&req).await.unwrap();
+        assert_eq!(first.id, "msg_1");
+        assert_eq!(first.model, "test-model");
+        assert_eq!(first.stop_reason, StopReason::ToolUse);
+        assert!(matches!(
+            &first.content[0],
+            ContentBlock::ToolUse { id, name, .. } if id == "call_0" && name == "search"
+        ));
+        assert!(matches!(
+            client.create_message(&req).await,
+            Err(LlmError::Api { status: 529, .. })
+        ));
+        assert_eq!(client.create_message(&req).await.unwrap().text(), "Done");
+        assert_eq!(client.remaining(), 0);
+
+        let err = client.create_message(&req).await.unwrap_err();
+        assert!(err.to_string().contains("no reply queued for request 4"));
+        assert_eq!(client.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected a system prompt")]
+    async fn test_expecting_checks_request() {
+        let client = MockClient::new()
+            .with_text("Hi")
+            .expecting(|req| assert!(req.system.is_some(), "expected a system prompt"));
+        let _ = client
+            .create_message(&Request::new("test-model").message(Message::user("Hi")))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_replays_reply() {
+        let client = MockClient::new().with_text("Streamed");
+        let req = Request::new("test-model").message(Message::user("Hi"));
+
+        let text: String = client
+            .create_message_stream(&req)
+            .filter_map(|event| async move {
+                match event.unwrap() {
+                    StreamEvent::ContentBlockDelta { text, .. } => Some(text),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        assert_eq!(text, "Streamed");
+    }
+
+    #[tokio::test]
+    async fn test_drives_subagent_loop() {
+        let client = Arc::new(
+            MockClient::new()
+                .with_tool_use("missing_tool", serde_json::json!({}))
+                .with_text("Recovered")
+                .expecting(|req| {
+                    // The failed tool call was reported back to the model
+                    let last = req.messages.last().unwrap();
+                    assert!(matches!(
+                        &last.content[0],
+                        ContentBlock::ToolResult { tool_use_id, is_error: true, .. }
+                            if tool_use_id == "call_0"
+                    ));
+                }),
+        );
+        let definition = AgentDefinition::new("worker", "You work.").model("test-model");
+        let mut agent = SubAgent::new(definition, client.clone(), Registry::new());
+
+        let result = agent.run("do it").await.unwrap();
+
+        assert_eq!(result.stop_reason, AgentStopReason::Completed);
+        assert_eq!(result.content, "Recovered");
+        assert_eq!(client.requests().len(), 2);
+    }
+}
@@ -2,24 +2,44 @@
 // ABOUTME: Defines types, traits, and provider implementations.
 
 mod anthropic;
+mod body;
 mod client;
 mod gemini;
+mod http;
+mod ids;
+mod middleware;
+mod mock;
 mod ollama;
 mod openai;
 mod openrouter;
+pub mod partial_json;
+mod payload;
+mod retry;
 pub mod stream_accumulator;
 mod types;
+mod usage;
 
 pub use anthropic::*;
+pub use body::DEFAULT_MAX_RESPONSE_BYTES;
+pub(crate) use body::read_body;
 pub use client::*;
 pub use gemini::*;
+pub use http::{DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT, HttpConfig};
+pub use ids::{IdSource, SeqIdSource, UuidIdSource};
+pub use middleware::{LatencyLogger, Middleware, WithMiddleware};
+pub use mock::MockClient;
 pub use ollama::*;
 pub use openai::*;
 pub use openrouter::*;
+pub use retry::RetryPolicy;
 pub use types::*;
+pub use usage::{CACHE_READ_PRICE_FACTOR, CACHE_WRITE_PRICE_FACTOR, PriceTable, UsageTracker};
 
 #[cfg(test)]
 mod types_test;
 
 #[cfg(test)]
 mod anthropic_test;
+
+#[cfg(test)]
+pub(crate) mod test_server;
@@ -1,8 +1,11 @@
 // ABOUTME: Ollama API client wrapping OpenAI-compatible API for local LLM inference.
 // ABOUTME: Connects to Ollama server (default localhost:11434) with dummy API key.
 
+use super::body::{DEFAULT_MAX_RESPONSE_BYTES, read_json, read_text};
 use super::client::StreamEvent;
+use super::http::default_http_client;
 use super::openai::{OpenAIError, OpenAIRequest, OpenAIResponse, parse_sse_line};
+use super::stream_accumulator::with_finished_blocks;
 use super::{ContentBlock, Request, Response, StopReason, Usage};
 use crate::error::LlmError;
 use async_trait::async_trait;
@@ -22,6 +25,7 @@ pub struct OllamaClient {
     base_url: String,
     http: reqwest::Client,
     default_model: String,
+    max_response_bytes: usize,
 }
 
 impl OllamaClient {
@@ -29,7 +33,8 @@ impl OllamaClient {
     pub fn new(model: &str) -> Self {
         Self {
             base_url: OLLAMA_BASE_URL.to_string(),
-            http: reqwest::Client::new(),
+            http: default_http_client(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
             default_model: if model.is_empty() {
                 OLLAMA_DEFAULT_MODEL.to_string()
             } else {
@@ -46,7 +51,8 @@ impl OllamaClient {
     pub fn with_base_url(base_url: &str, model: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
-            http: reqwest::Client::new(),
+            http: default_http_client(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
             default_model: if model.is_empty() {
                 OLLAMA_DEFAULT_MODEL.to_string()
             } else {
@@ -60,6 +66,14 @@ impl OllamaClient {
         self.default_model = model.into();
         self
     }
+
+    /// Fail non-streaming responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
 }
 
 impl Default for OllamaClient {
@@ -73,14 +87,28 @@ fn parse_stop_reason(s: Option<&str>) -> StopReason {
         Some("stop") => StopReason::EndTurn,
         Some("tool_calls") => StopReason::ToolUse,
         Some("length") => StopReason::MaxTokens,
+        Some("content_filter") => StopReason::ContentFilter,
         _ => StopReason::EndTurn,
     }
 }
 
+/// Ollama has no developer role, so developer instruction layers are sent
+/// as system messages.
+fn send_developer_as_system(req: &mut OpenAIRequest) {
+    for message in &mut req.messages {
+        if message.role == "developer" {
+            message.role = "system".to_string();
+        }
+    }
+}
+
 #[async_trait]
 impl super::client::LlmClient for OllamaClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        req.validate()?;
+
         let mut openai_req = OpenAIRequest::from(req);
+        send_developer_as_system(&mut openai_req);
 
         // Use default model if none specified
         if openai_req.model.is_empty() {
@@ -101,22 +129,26 @@ impl super::client::LlmClient for OllamaClient {
 
         let status = response.status();
         if !status.is_success() {
-            let error: OpenAIError = response.json().await?;
+            let error: OpenAIError = read_json(response, self.max_response_bytes).await?;
             return Err(LlmError::Api {
                 status: status.as_u16(),
                 message: error.error.message,
             });
         }
 
-        let openai_resp: OpenAIResponse = response.json().await?;
-        Ok(Response::from(openai_resp))
+        let openai_resp: OpenAIResponse = read_json(response, self.max_response_bytes).await?;
+        let mut response = Response::from(openai_resp);
+        response.model = openai_req.model;
+        Ok(response)
     }
 
     fn create_message_stream(
         &self,
         req: &Request,
     ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+        let validation = req.validate();
         let mut openai_req = OpenAIRequest::from(req);
+        send_developer_as_system(&mut openai_req);
 
         // Use default model if none specified
         if openai_req.model.is_empty() {
@@ -127,8 +159,11 @@ impl super::client::LlmClient for OllamaClient {
 
         let base_url = self.base_url.clone();
         let http = self.http.clone();
+        let max_response_bytes = self.max_response_bytes;
+
+        Box::pin(with_finished_blocks(async_stream::try_stream! {
+            validation?;
 
-        Box::pin(async_stream::try_stream! {
             let url = format!("{}/chat/completions", base_url);
             let response = http
                 .post(&url)
@@ -140,7 +175,7 @@ impl super::client::LlmClient for OllamaClient {
 
             let status = response.status();
             if !status.is_success() {
-                let error_text = response.text().await?;
+                let error_text = read_text(response, max_response_bytes).await?;
                 let error: OpenAIError = serde_json::from_str(&error_text)?;
                 Err(LlmError::Api {
                     status: status.as_u16(),
@@ -249,13 +284,13 @@ impl super::client::LlmClient for OllamaClient {
                             if let Some(reason) = choice.finish_reason {
                                 // Close text block if started
                                 if let Some(idx) = text_block_index {
-                                    yield StreamEvent::ContentBlockStop { index: idx };
+                                    yield StreamEvent::ContentBlockStop { index: idx, block: None };
                                 }
 
                                 // Close tool call blocks
                                 for (id, _, _, block_idx, started) in current_tool_calls.iter() {
                                     if *started && !id.is_empty() {
-                                        yield StreamEvent::ContentBlockStop { index: *block_idx };
+                                        yield StreamEvent::ContentBlockStop { index: *block_idx, block: None };
                                     }
                                 }
 
@@ -269,13 +304,14 @@ impl super::client::LlmClient for OllamaClient {
                     }
                 }
             }
-        })
+        }))
     }
 }
 
 #[cfg(test)]
 mod ollama_test {
     use super::*;
+    use crate::llm::{Instruction, Message};
 
     #[test]
     fn test_client_new() {
@@ -316,6 +352,33 @@ mod ollama_test {
         assert_eq!(client.default_model, OLLAMA_DEFAULT_MODEL);
     }
 
+    #[test]
+    fn test_developer_instructions_sent_as_system() {
+        let req = Request::new("llama3.2")
+            .system("Platform rules.")
+            .instruction(Instruction::developer("App rules."))
+            .message(Message::user("Hello"));
+
+        let mut openai_req = OpenAIRequest::from(&req);
+        send_developer_as_system(&mut openai_req);
+        let roles: Vec<&str> = openai_req
+            .messages
+            .iter()
+            .map(|m| m.role.as_str())
+            .collect();
+        assert_eq!(roles, vec!["system", "system", "user"]);
+    }
+
+    #[test]
+    fn test_parse_stop_reason() {
+        assert_eq!(parse_stop_reason(Some("stop")), StopReason::EndTurn);
+        assert_eq!(parse_stop_reason(Some("length")), StopReason::MaxTokens);
+        assert_eq!(
+            parse_stop_reason(Some("content_filter")),
+            StopReason::ContentFilter
+        );
+    }
+
     #[test]
     fn test_constants() {
         assert_eq!(OLLAMA_BASE_URL, "http://localhost:11434/v1");
@@ -1,8 +1,16 @@
 // ABOUTME: OpenAI API client implementation.
 // ABOUTME: Implements LlmClient trait for GPT models.
 
+use super::body::{DEFAULT_MAX_RESPONSE_BYTES, read_json, read_text};
 use super::client::StreamEvent;
-use super::{ContentBlock, Message, Request, Response, Role, StopReason, ToolDefinition, Usage};
+use super::http::default_http_client;
+use super::payload;
+use super::retry::{RetryPolicy, send_with_retry};
+use super::stream_accumulator::with_finished_blocks;
+use super::{
+    ContentBlock, ImageSource, InstructionRole, InvalidToolInput, Message, Request, Response, Role,
+    StopReason, ToolChoice, ToolDefinition, Usage,
+};
 use crate::error::LlmError;
 use async_trait::async_trait;
 use futures::Stream;
@@ -24,9 +32,18 @@ pub struct OpenAIRequest {
     pub max_completion_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<OpenAITool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<OpenAIToolChoice>,
+    /// Number of completions; only set by `create_messages`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
 }
 
@@ -35,16 +52,52 @@ pub struct OpenAIRequest {
 pub struct OpenAIMessage {
     pub role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub content: Option<String>,
+    pub content: Option<OpenAIContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<OpenAIToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
 }
 
+/// OpenAI message content: plain text, or parts when images are included.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+/// OpenAI content part.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAIContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+/// OpenAI image reference. Base64 images are sent as `data:` URLs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenAIImageUrl {
+    pub url: String,
+}
+
+impl From<&ImageSource> for OpenAIImageUrl {
+    fn from(source: &ImageSource) -> Self {
+        let url = match source {
+            ImageSource::Base64 { media_type, data } => {
+                format!("data:{};base64,{}", media_type, data)
+            }
+            ImageSource::Url { url } => url.clone(),
+        };
+        OpenAIImageUrl { url }
+    }
+}
+
 /// OpenAI tool call in a response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIToolCall {
+    /// Some OpenAI-compatible servers omit this; the agent loop fills it in.
+    #[serde(default)]
     pub id: String,
     #[serde(rename = "type")]
     pub call_type: String,
@@ -74,6 +127,39 @@ pub struct OpenAIFunction {
     pub parameters: serde_json::Value,
 }
 
+/// OpenAI tool choice: a mode, or a function the model must call.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum OpenAIToolChoice {
+    /// `auto`, `none` or `required`.
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        choice_type: String,
+        function: OpenAIToolChoiceFunction,
+    },
+}
+
+/// The function named by an [`OpenAIToolChoice`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OpenAIToolChoiceFunction {
+    pub name: String,
+}
+
+impl From<&ToolChoice> for OpenAIToolChoice {
+    fn from(choice: &ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => OpenAIToolChoice::Mode("auto".into()),
+            ToolChoice::None => OpenAIToolChoice::Mode("none".into()),
+            ToolChoice::Required => OpenAIToolChoice::Mode("required".into()),
+            ToolChoice::Tool(name) => OpenAIToolChoice::Function {
+                choice_type: "function".into(),
+                function: OpenAIToolChoiceFunction { name: name.clone() },
+            },
+        }
+    }
+}
+
 /// OpenAI API response format.
 #[derive(Debug, Deserialize)]
 pub struct OpenAIResponse {
@@ -81,6 +167,8 @@ pub struct OpenAIResponse {
     pub model: String,
     pub choices: Vec<OpenAIChoice>,
     pub usage: Option<OpenAIUsage>,
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
 }
 
 /// OpenAI response choice.
@@ -97,6 +185,9 @@ pub struct OpenAIResponseMessage {
     pub role: String,
     pub content: Option<String>,
     pub tool_calls: Option<Vec<OpenAIToolCall>>,
+    /// Why the model declined, sent instead of `content`.
+    #[serde(default)]
+    pub refusal: Option<String>,
 }
 
 /// OpenAI usage stats.
@@ -141,6 +232,8 @@ pub struct OpenAIStreamChoice {
 pub struct OpenAIDelta {
     pub role: Option<String>,
     pub content: Option<String>,
+    /// Streamed in place of `content` when the model refuses.
+    pub refusal: Option<String>,
     pub tool_calls: Option<Vec<OpenAIToolCallDelta>>,
 }
 
@@ -166,7 +259,12 @@ pub struct OpenAIFunctionDelta {
 pub struct OpenAIClient {
     api_key: String,
     base_url: String,
+    /// Set for Azure OpenAI, which takes the version as a query parameter
+    /// and the key in an `api-key` header.
+    azure_api_version: Option<String>,
     http: reqwest::Client,
+    retry: RetryPolicy,
+    max_response_bytes: usize,
 }
 
 impl OpenAIClient {
@@ -175,7 +273,10 @@ impl OpenAIClient {
         Self {
             api_key: api_key.into(),
             base_url: OPENAI_DEFAULT_BASE_URL.to_string(),
-            http: reqwest::Client::new(),
+            azure_api_version: None,
+            http: default_http_client(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            retry: RetryPolicy::none(),
         }
     }
 
@@ -194,6 +295,29 @@ impl OpenAIClient {
         self
     }
 
+    /// Send requests with `http`, e.g. one built from an
+    /// [`HttpConfig`](super::HttpConfig) with a proxy or custom
+    /// certificates. Defaults to a client with
+    /// [`HttpConfig::default`](super::HttpConfig::default) settings.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Retry rate-limited and overloaded requests according to `policy`.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Fail non-streaming responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
     /// Create an OpenRouter client with the given API key.
     pub fn openrouter(api_key: impl Into<String>) -> Self {
         Self::new(api_key).with_base_url("https://openrouter.ai/api/v1")
@@ -217,6 +341,78 @@ impl OpenAIClient {
     pub fn ollama_at(host: impl Into<String>) -> Self {
         Self::new("ollama").with_base_url(format!("{}/v1", host.into()))
     }
+
+    /// Create a client for an Azure OpenAI deployment.
+    ///
+    /// `endpoint` is the resource URL, like
+    /// `https://my-resource.openai.azure.com`. Requests go to
+    /// `{endpoint}/openai/deployments/{deployment}/chat/completions?api-version={api_version}`
+    /// with the key in an `api-key` header. The deployment decides the
+    /// model, so [`Request::model`] is ignored.
+    pub fn azure(
+        endpoint: impl Into<String>,
+        deployment: impl Into<String>,
+        api_version: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        let endpoint = endpoint.into();
+        let base_url = format!(
+            "{}/openai/deployments/{}",
+            endpoint.trim_end_matches('/'),
+            deployment.into()
+        );
+        Self {
+            azure_api_version: Some(api_version.into()),
+            ..Self::new(api_key).with_base_url(base_url)
+        }
+    }
+
+    /// The chat completions URL.
+    fn chat_url(&self) -> String {
+        match &self.azure_api_version {
+            Some(version) => format!("{}/chat/completions?api-version={}", self.base_url, version),
+            None => format!("{}/chat/completions", self.base_url),
+        }
+    }
+
+    /// The header that carries the API key.
+    fn auth_header(&self) -> (&'static str, String) {
+        match self.azure_api_version {
+            Some(_) => ("api-key", self.api_key.clone()),
+            None => ("Authorization", format!("Bearer {}", self.api_key)),
+        }
+    }
+
+    /// Send a chat completion request, returning the reply and the number
+    /// of attempts it took.
+    async fn complete(
+        &self,
+        openai_req: &OpenAIRequest,
+    ) -> Result<(OpenAIResponse, u32), LlmError> {
+        let url = self.chat_url();
+        let (auth_name, auth_value) = self.auth_header();
+
+        let (response, attempts) = send_with_retry(&self.retry, || {
+            self.http
+                .post(&url)
+                .header(auth_name, &auth_value)
+                .header("Content-Type", "application/json")
+                .json(openai_req)
+        })
+        .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error: OpenAIError = read_json(response, self.max_response_bytes).await?;
+            return Err(LlmError::Api {
+                status: status.as_u16(),
+                message: error.error.message,
+            });
+        }
+
+        let openai_resp = read_json(response, self.max_response_bytes).await?;
+        Ok((openai_resp, attempts))
+    }
 }
 
 impl From<&ToolDefinition> for OpenAITool {
@@ -272,20 +468,9 @@ impl From<&Message> for OpenAIMessage {
                 })
                 .collect();
 
-            // Extract text content
-            let text: String = msg
-                .content
-                .iter()
-                .filter_map(|b| match b {
-                    ContentBlock::Text { text } => Some(text.as_str()),
-                    _ => None,
-                })
-                .collect::<Vec<_>>()
-                .join("");
-
             OpenAIMessage {
                 role: role.to_string(),
-                content: if text.is_empty() { None } else { Some(text) },
+                content: convert_content(msg),
                 tool_calls: if tool_calls.is_empty() {
                     None
                 } else {
@@ -297,10 +482,47 @@ impl From<&Message> for OpenAIMessage {
     }
 }
 
+/// Text blocks joined into one string, or content parts if the message
+/// has images, keeping the blocks in order.
+fn convert_content(msg: &Message) -> Option<OpenAIContent> {
+    let has_images = msg
+        .content
+        .iter()
+        .any(|b| matches!(b, ContentBlock::Image { .. }));
+
+    if has_images {
+        let parts: Vec<OpenAIContentPart> = payload::content_blocks(msg)
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(OpenAIContentPart::Text { text: text.clone() }),
+                ContentBlock::Image { source } => Some(OpenAIContentPart::ImageUrl {
+                    image_url: OpenAIImageUrl::from(source),
+                }),
+                _ => None,
+            })
+            .collect();
+        return Some(OpenAIContent::Parts(parts));
+    }
+
+    let text: String = msg
+        .content
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    if text.is_empty() {
+        None
+    } else {
+        Some(OpenAIContent::Text(text))
+    }
+}
+
 fn convert_messages(messages: &[Message]) -> Vec<OpenAIMessage> {
     let mut result = Vec::new();
 
-    for msg in messages {
+    for msg in &payload::turns(messages) {
         // Check if this message contains tool results
         let tool_results: Vec<_> = msg
             .content
@@ -320,11 +542,24 @@ fn convert_messages(messages: &[Message]) -> Vec<OpenAIMessage> {
             for (tool_use_id, content) in tool_results {
                 result.push(OpenAIMessage {
                     role: "tool".to_string(),
-                    content: Some(content),
+                    content: Some(OpenAIContent::Text(content)),
                     tool_calls: None,
                     tool_call_id: Some(tool_use_id),
                 });
             }
+            // Text merged in after the results follows them as a user message
+            let rest = Message {
+                role: msg.role,
+                content: msg
+                    .content
+                    .iter()
+                    .filter(|b| !matches!(b, ContentBlock::ToolResult { .. }))
+                    .cloned()
+                    .collect(),
+            };
+            if payload::has_content(&rest) {
+                result.push(OpenAIMessage::from(&rest));
+            }
         } else {
             result.push(OpenAIMessage::from(msg));
         }
@@ -345,11 +580,15 @@ impl From<&Request> for OpenAIRequest {
     fn from(req: &Request) -> Self {
         let mut messages = Vec::new();
 
-        // Add system message if present
-        if let Some(ref system) = req.system {
+        // Add the system prompt and each instruction layer as its own message
+        for (role, text) in payload::instruction_layers(req) {
+            let role = match role {
+                InstructionRole::System => "system",
+                InstructionRole::Developer => "developer",
+            };
             messages.push(OpenAIMessage {
-                role: "system".to_string(),
-                content: Some(system.clone()),
+                role: role.to_string(),
+                content: Some(OpenAIContent::Text(text.to_string())),
                 tool_calls: None,
                 tool_call_id: None,
             });
@@ -371,7 +610,11 @@ impl From<&Request> for OpenAIRequest {
             max_tokens,
             max_completion_tokens,
             temperature: req.temperature,
+            top_p: req.top_p,
+            stop: req.stop_sequences.clone(),
             tools: req.tools.iter().map(OpenAITool::from).collect(),
+            tool_choice: payload::tool_choice(req).map(OpenAIToolChoice::from),
+            n: None,
             stream: None,
         }
     }
@@ -382,6 +625,7 @@ fn parse_stop_reason(s: Option<&str>) -> StopReason {
         Some("stop") => StopReason::EndTurn,
         Some("tool_calls") => StopReason::ToolUse,
         Some("length") => StopReason::MaxTokens,
+        Some("content_filter") => StopReason::ContentFilter,
         _ => StopReason::EndTurn,
     }
 }
@@ -394,49 +638,101 @@ impl From<OpenAIResponse> for Response {
                 role: "assistant".to_string(),
                 content: None,
                 tool_calls: None,
+                refusal: None,
             },
             finish_reason: None,
         });
+        let usage = resp.usage.map(Usage::from).unwrap_or_default();
+        choice_response(choice, resp.id, resp.model, resp.system_fingerprint, usage)
+    }
+}
 
-        let mut content = Vec::new();
-
-        // Add text content if present
-        if let Some(text) = choice.message.content {
-            if !text.is_empty() {
-                content.push(ContentBlock::Text { text });
-            }
+impl From<OpenAIUsage> for Usage {
+    fn from(usage: OpenAIUsage) -> Self {
+        Usage {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+            ..Default::default()
         }
+    }
+}
 
-        // Add tool calls if present
-        if let Some(tool_calls) = choice.message.tool_calls {
-            for call in tool_calls {
-                let input: serde_json::Value =
-                    serde_json::from_str(&call.function.arguments).unwrap_or_default();
-                content.push(ContentBlock::ToolUse {
-                    id: call.id,
-                    name: call.function.name,
-                    input,
-                });
-            }
+impl OpenAIResponse {
+    /// One response per choice, in choice order. The usage, which OpenAI
+    /// reports for the whole call, goes on the first.
+    pub fn into_responses(mut self) -> Vec<Response> {
+        if self.choices.is_empty() {
+            return vec![Response::from(self)];
         }
+        self.choices.sort_by_key(|choice| choice.index);
+        let mut usage = self.usage.map(Usage::from).unwrap_or_default();
+        self.choices
+            .into_iter()
+            .map(|choice| {
+                choice_response(
+                    choice,
+                    self.id.clone(),
+                    self.model.clone(),
+                    self.system_fingerprint.clone(),
+                    std::mem::take(&mut usage),
+                )
+            })
+            .collect()
+    }
+}
 
-        let usage = resp.usage.unwrap_or(OpenAIUsage {
-            prompt_tokens: 0,
-            completion_tokens: 0,
-            total_tokens: 0,
-        });
+/// The response for one choice of an OpenAI reply.
+fn choice_response(
+    choice: OpenAIChoice,
+    id: String,
+    model: String,
+    system_fingerprint: Option<String>,
+    usage: Usage,
+) -> Response {
+    let mut content = Vec::new();
+
+    // Add text content if present
+    if let Some(text) = choice.message.content {
+        if !text.is_empty() {
+            content.push(ContentBlock::Text { text });
+        }
+    }
 
-        Response {
-            id: resp.id,
-            content,
-            stop_reason: parse_stop_reason(choice.finish_reason.as_deref()),
-            model: resp.model,
-            usage: Usage {
-                input_tokens: usage.prompt_tokens,
-                output_tokens: usage.completion_tokens,
-                ..Default::default()
-            },
+    // A refusal arrives in its own field with an ordinary "stop"
+    let refusal = choice.message.refusal.filter(|r| !r.is_empty());
+    let stop_reason = match refusal {
+        Some(text) => {
+            content.push(ContentBlock::Text { text });
+            StopReason::Refusal
         }
+        None => parse_stop_reason(choice.finish_reason.as_deref()),
+    };
+
+    // Add tool calls if present
+    let mut invalid_tool_inputs = Vec::new();
+    if let Some(tool_calls) = choice.message.tool_calls {
+        for call in tool_calls {
+            let (input, invalid) = InvalidToolInput::check(content.len(), &call.function.arguments);
+            invalid_tool_inputs.extend(invalid);
+            content.push(ContentBlock::ToolUse {
+                id: call.id,
+                name: call.function.name,
+                input,
+            });
+        }
+    }
+
+    Response {
+        id,
+        content,
+        stop_reason,
+        model: model.clone(),
+        served_model: Some(model),
+        system_fingerprint,
+        usage,
+        attempts: 1,
+        citations: Vec::new(),
+        invalid_tool_inputs,
     }
 }
 
@@ -453,55 +749,58 @@ pub fn parse_sse_line(line: &str) -> Option<OpenAIStreamChunk> {
 #[async_trait]
 impl super::client::LlmClient for OpenAIClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        req.validate()?;
+
         let openai_req = OpenAIRequest::from(req);
-        let url = format!("{}/chat/completions", self.base_url);
-
-        let response = self
-            .http
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&openai_req)
-            .send()
-            .await?;
+        let (openai_resp, attempts) = self.complete(&openai_req).await?;
+        let mut response = Response::from(openai_resp);
+        response.model = openai_req.model;
+        response.attempts = attempts;
+        Ok(response)
+    }
 
-        let status = response.status();
-        if !status.is_success() {
-            let error: OpenAIError = response.json().await?;
-            return Err(LlmError::Api {
-                status: status.as_u16(),
-                message: error.error.message,
-            });
-        }
+    async fn create_messages(&self, req: &Request) -> Result<Vec<Response>, LlmError> {
+        req.validate()?;
 
-        let openai_resp: OpenAIResponse = response.json().await?;
-        Ok(Response::from(openai_resp))
+        let mut openai_req = OpenAIRequest::from(req);
+        openai_req.n = payload::candidates(req);
+        let (openai_resp, attempts) = self.complete(&openai_req).await?;
+        let mut responses = openai_resp.into_responses();
+        for response in &mut responses {
+            response.model = openai_req.model.clone();
+            response.attempts = attempts;
+        }
+        Ok(responses)
     }
 
     fn create_message_stream(
         &self,
         req: &Request,
     ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+        let validation = req.validate();
         let mut openai_req = OpenAIRequest::from(req);
         openai_req.stream = Some(true);
 
-        let api_key = self.api_key.clone();
-        let base_url = self.base_url.clone();
+        let url = self.chat_url();
+        let (auth_name, auth_value) = self.auth_header();
         let http = self.http.clone();
+        let max_response_bytes = self.max_response_bytes;
+        let retry = self.retry.clone();
 
-        Box::pin(async_stream::try_stream! {
-            let url = format!("{}/chat/completions", base_url);
-            let response = http
-                .post(&url)
-                .header("Authorization", format!("Bearer {}", api_key))
-                .header("Content-Type", "application/json")
-                .json(&openai_req)
-                .send()
-                .await?;
+        Box::pin(with_finished_blocks(async_stream::try_stream! {
+            validation?;
+
+            let (response, _) = send_with_retry(&retry, || {
+                http.post(&url)
+                    .header(auth_name, &auth_value)
+                    .header("Content-Type", "application/json")
+                    .json(&openai_req)
+            })
+            .await?;
 
             let status = response.status();
             if !status.is_success() {
-                let error_text = response.text().await?;
+                let error_text = read_text(response, max_response_bytes).await?;
                 let error: OpenAIError = serde_json::from_str(&error_text)?;
                 Err(LlmError::Api {
                     status: status.as_u16(),
@@ -514,6 +813,7 @@ impl super::client::LlmClient for OpenAIClient {
             let mut buffer = String::new();
             let mut message_started = false;
             let mut text_block_index: Option<usize> = None;
+            let mut refused = false;
             let mut next_block_index = 0usize;
             // Track tool calls: (id, name, args, block_index, block_started)
             let mut current_tool_calls: Vec<(String, String, String, usize, bool)> = Vec::new();
@@ -541,8 +841,12 @@ impl super::client::LlmClient for OpenAIClient {
                         }
 
                         for choice in chunk.choices {
+                            // A refusal streams like text, in its own field
+                            let refusal = choice.delta.refusal.filter(|r| !r.is_empty());
+                            refused |= refusal.is_some();
+
                             // Handle text content
-                            if let Some(text) = choice.delta.content {
+                            for text in choice.delta.content.into_iter().chain(refusal) {
                                 // Emit ContentBlockStart for text on first text delta
                                 if text_block_index.is_none() {
                                     let idx = next_block_index;
@@ -578,9 +882,10 @@ impl super::client::LlmClient for OpenAIClient {
                                             current_tool_calls[tc_idx].1 = name;
                                         }
 
-                                        // Emit ContentBlockStart when we have id and name (before JSON deltas)
+                                        // Emit ContentBlockStart once we have a name (before JSON deltas).
+                                        // The id may be missing on some servers; the agent loop fills it in.
                                         let (ref id, ref name, _, ref mut block_idx, ref mut started) = current_tool_calls[tc_idx];
-                                        if !*started && !id.is_empty() && !name.is_empty() {
+                                        if !*started && !name.is_empty() {
                                             *block_idx = next_block_index;
                                             next_block_index += 1;
                                             yield StreamEvent::ContentBlockStart {
@@ -610,18 +915,23 @@ impl super::client::LlmClient for OpenAIClient {
                             if let Some(reason) = choice.finish_reason {
                                 // Close text block if started
                                 if let Some(idx) = text_block_index {
-                                    yield StreamEvent::ContentBlockStop { index: idx };
+                                    yield StreamEvent::ContentBlockStop { index: idx, block: None };
                                 }
 
                                 // Close tool call blocks
                                 for (id, _, _, block_idx, started) in current_tool_calls.iter() {
                                     if *started && !id.is_empty() {
-                                        yield StreamEvent::ContentBlockStop { index: *block_idx };
+                                        yield StreamEvent::ContentBlockStop { index: *block_idx, block: None };
                                     }
                                 }
 
+                                let stop_reason = if refused {
+                                    StopReason::Refusal
+                                } else {
+                                    parse_stop_reason(Some(&reason))
+                                };
                                 yield StreamEvent::MessageDelta {
-                                    stop_reason: Some(parse_stop_reason(Some(&reason))),
+                                    stop_reason: Some(stop_reason),
                                     usage: Usage::default(),
                                 };
                                 yield StreamEvent::MessageStop;
@@ -630,13 +940,14 @@ impl super::client::LlmClient for OpenAIClient {
                     }
                 }
             }
-        })
+        }))
     }
 }
 
 #[cfg(test)]
 mod openai_test {
     use super::*;
+    use crate::llm::Instruction;
 
     #[test]
     fn test_client_from_env_missing() {
@@ -662,6 +973,90 @@ mod openai_test {
         assert_eq!(openai_req.messages[1].role, "user");
     }
 
+    #[test]
+    fn test_text_after_tool_results_follows_them() {
+        let req = Request::new("gpt-4o")
+            .message(Message::user("Read it"))
+            .message(Message {
+                role: Role::Assistant,
+                content: vec![ContentBlock::ToolUse {
+                    id: "call_1".into(),
+                    name: "read_file".into(),
+                    input: serde_json::json!({}),
+                }],
+            })
+            .message(Message::tool_results(vec![ContentBlock::tool_result(
+                "call_1", "contents",
+            )]))
+            .message(Message::user("Now summarize"));
+
+        let roles: Vec<String> = OpenAIRequest::from(&req)
+            .messages
+            .into_iter()
+            .map(|m| m.role)
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "tool", "user"]);
+    }
+
+    #[test]
+    fn test_instruction_layers_keep_their_roles() {
+        let req = Request::new("gpt-4o")
+            .message(Message::user("Hello"))
+            .system("Platform rules.")
+            .instruction(Instruction::developer("App rules."))
+            .instruction(Instruction::system("Safety rules."));
+
+        let body = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        let roles: Vec<&str> = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, vec!["system", "developer", "system", "user"]);
+        assert_eq!(body["messages"][1]["content"], "App rules.");
+    }
+
+    #[test]
+    fn test_image_content_parts() {
+        let req = Request::new("gpt-4o").message(Message::user_with_images(
+            "Compare these.",
+            [
+                ImageSource::base64("image/png", "iVBOR"),
+                ImageSource::url("https://example.com/cat.png"),
+            ],
+        ));
+
+        let body = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBOR"}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}},
+                {"type": "text", "text": "Compare these."}
+            ])
+        );
+
+        // Text-only messages keep the plain string form
+        let req = Request::new("gpt-4o").message(Message::user("Hi"));
+        let body = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        assert_eq!(body["messages"][0]["content"], "Hi");
+    }
+
+    #[test]
+    fn test_sampling_params_serialized() {
+        let req = Request::new("gpt-4o").temperature(1.2).top_p(0.95);
+
+        let json = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        assert_eq!(json["temperature"], 1.2);
+        assert_eq!(json["top_p"], 0.95);
+        assert!(json.get("stop").is_none());
+
+        let req = Request::new("gpt-4o").stop_sequences(vec!["END".to_string()]);
+        let json = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        assert_eq!(json["stop"], serde_json::json!(["END"]));
+    }
+
     #[test]
     fn test_tool_definition_conversion() {
         let tool = ToolDefinition {
@@ -679,4 +1074,416 @@ mod openai_test {
         assert_eq!(openai_tool.tool_type, "function");
         assert_eq!(openai_tool.function.name, "get_weather");
     }
+
+    #[test]
+    fn test_tool_choice_serialization() {
+        let req = Request::new("gpt-4o").tool(ToolDefinition {
+            name: "get_weather".to_string(),
+            description: "Get the weather".to_string(),
+            input_schema: serde_json::json!({"type": "object", "properties": {}}),
+        });
+
+        let json = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+        assert!(json.get("tool_choice").is_none());
+
+        for (choice, expected) in [
+            (ToolChoice::None, serde_json::json!("none")),
+            (ToolChoice::Required, serde_json::json!("required")),
+            (
+                ToolChoice::Tool("get_weather".into()),
+                serde_json::json!({"type": "function", "function": {"name": "get_weather"}}),
+            ),
+        ] {
+            let req = req.clone().tool_choice(choice);
+            let json = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+            assert_eq!(json["tool_choice"], expected);
+        }
+    }
+
+    #[test]
+    fn test_tool_call_without_id_deserializes() {
+        let body = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "type": "function",
+                        "function": {"name": "search", "arguments": "{\"q\":\"rust\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        });
+
+        let resp: OpenAIResponse = serde_json::from_value(body).unwrap();
+        let response = Response::from(resp);
+        match &response.content[0] {
+            ContentBlock::ToolUse { id, name, input } => {
+                assert!(id.is_empty());
+                assert_eq!(name, "search");
+                assert_eq!(input["q"], "rust");
+            }
+            other => panic!("expected tool use, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_filtered_and_refused_responses() {
+        let response_with = |message: serde_json::Value, finish_reason: &str| {
+            let body = serde_json::json!({
+                "id": "chatcmpl-1",
+                "model": "gpt-4o",
+                "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}]
+            });
+            Response::from(serde_json::from_value::<OpenAIResponse>(body).unwrap())
+        };
+
+        let filtered = response_with(
+            serde_json::json!({"role": "assistant", "content": null}),
+            "content_filter",
+        );
+        assert_eq!(filtered.stop_reason, StopReason::ContentFilter);
+        assert!(filtered.content.is_empty());
+
+        let refused = response_with(
+            serde_json::json!({
+                "role": "assistant",
+                "content": null,
+                "refusal": "I can't help with that."
+            }),
+            "stop",
+        );
+        assert_eq!(refused.stop_reason, StopReason::Refusal);
+        assert_eq!(refused.text(), "I can't help with that.");
+    }
+
+    #[tokio::test]
+    async fn test_azure_url_and_key_header() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::serve_once;
+
+        let (base_url, server) = serve_once(
+            200,
+            r#"{
+                "id": "chatcmpl-azure",
+                "model": "gpt-4o",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 1, "total_tokens": 6}
+            }"#,
+        )
+        .await;
+        let client = OpenAIClient::azure(
+            format!("{}/", base_url),
+            "gpt4o-prod",
+            "2024-06-01",
+            "azure-key",
+        );
+        assert_eq!(
+            client.chat_url(),
+            format!(
+                "{}/openai/deployments/gpt4o-prod/chat/completions?api-version=2024-06-01",
+                base_url
+            )
+        );
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        let response = client.create_message(&req).await.unwrap();
+        assert_eq!(response.text(), "Hi");
+
+        let raw_request = &server.await.unwrap()[0];
+        assert!(raw_request.starts_with(
+            "POST /openai/deployments/gpt4o-prod/chat/completions?api-version=2024-06-01 HTTP/1.1"
+        ));
+        assert!(raw_request.contains("api-key: azure-key"));
+        assert!(!raw_request.to_lowercase().contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_create_messages_asks_for_n_choices() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, server) = serve(vec![RecordedResponse::json(
+            200,
+            r#"{
+                "id": "chatcmpl-n",
+                "model": "gpt-4o",
+                "choices": [
+                    {"index": 1, "message": {"role": "assistant", "content": "Hey"}, "finish_reason": "stop"},
+                    {"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}
+                ],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+            }"#,
+        )])
+        .await;
+        let client = OpenAIClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gpt-4o")
+            .message(Message::user("Hello"))
+            .candidates(2);
+        let responses = client.create_messages(&req).await.unwrap();
+
+        let texts: Vec<String> = responses.iter().map(|r| r.text()).collect();
+        assert_eq!(texts, ["Hi", "Hey"]);
+        assert_eq!(responses[1].usage, Usage::default());
+        let total: Usage = responses.iter().map(|r| &r.usage).sum();
+        assert_eq!((total.input_tokens, total.output_tokens), (5, 2));
+
+        let raw_request = &server.await.unwrap()[0];
+        assert!(raw_request.contains(r#""n":2"#));
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_server_error() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, server) = serve(vec![
+            RecordedResponse::json(
+                503,
+                r#"{"error": {"type": "server_error", "message": "unavailable"}}"#,
+            ),
+            RecordedResponse::json(
+                200,
+                r#"{
+                    "id": "chatcmpl-retry",
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "Hi"},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 5, "completion_tokens": 1, "total_tokens": 6}
+                }"#,
+            ),
+        ])
+        .await;
+        let client = OpenAIClient::new("test-key")
+            .with_base_url(base_url)
+            .with_retry(RetryPolicy {
+                max_retries: 2,
+                base_delay: std::time::Duration::from_millis(1),
+                max_delay: std::time::Duration::from_millis(10),
+            });
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        let response = client.create_message(&req).await.unwrap();
+
+        assert_eq!(response.text(), "Hi");
+        assert_eq!(response.attempts, 2);
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_served_model_reported_separately() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::serve_once;
+
+        let (base_url, _server) = serve_once(
+            200,
+            r#"{
+                "id": "chatcmpl-fallback",
+                "model": "gpt-4o-mini-2024-07-18",
+                "system_fingerprint": "fp_44709d6fcb",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }]
+            }"#,
+        )
+        .await;
+        let client = OpenAIClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        let response = client.create_message(&req).await.unwrap();
+
+        assert_eq!(response.model, "gpt-4o");
+        assert_eq!(
+            response.served_model.as_deref(),
+            Some("gpt-4o-mini-2024-07-18")
+        );
+        assert_eq!(response.actual_model(), "gpt-4o-mini-2024-07-18");
+        assert_eq!(
+            response.system_fingerprint.as_deref(),
+            Some("fp_44709d6fcb")
+        );
+        assert!(response.model_substituted());
+    }
+
+    #[tokio::test]
+    async fn test_error_body_over_size_limit_rejected() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::serve_once;
+
+        let huge_message = "x".repeat(4096);
+        let body = format!(
+            r#"{{"error": {{"type": "server_error", "message": "{}"}}}}"#,
+            huge_message
+        );
+        let (base_url, _server) = serve_once(500, &body).await;
+        let client = OpenAIClient::new("test-key")
+            .with_base_url(base_url)
+            .with_max_response_size(1024);
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        match client.create_message(&req).await {
+            Err(crate::error::LlmError::ResponseTooLarge { limit }) => assert_eq!(limit, 1024),
+            other => panic!("Expected ResponseTooLarge, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_retries() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, _server) = serve(vec![
+            RecordedResponse::json(
+                429,
+                r#"{"error": {"type": "server_error", "message": "slow down"}}"#,
+            ),
+            RecordedResponse::json(
+                429,
+                r#"{"error": {"type": "server_error", "message": "still slow"}}"#,
+            ),
+        ])
+        .await;
+        let client = OpenAIClient::new("test-key")
+            .with_base_url(base_url)
+            .with_retry(RetryPolicy {
+                max_retries: 1,
+                base_delay: std::time::Duration::from_millis(1),
+                max_delay: std::time::Duration::from_millis(10),
+            });
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        match client.create_message(&req).await {
+            Err(crate::error::LlmError::Api { status, .. }) => assert_eq!(status, 429),
+            other => panic!("Expected Api error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_refusal_is_text_with_refusal_stop() {
+        use crate::llm::LlmClient;
+        use crate::llm::stream_accumulator::StreamAccumulator;
+        use crate::llm::test_server::{RecordedResponse, serve};
+        use futures::StreamExt;
+
+        let chunk = |delta: &str, finish: &str| {
+            format!(
+                "data: {{\"id\": \"chatcmpl-1\", \"model\": \"gpt-4o\", \"choices\": [{{\"index\": 0, \"delta\": {}, \"finish_reason\": {}}}]}}\n\n",
+                delta, finish
+            )
+        };
+        let body = [
+            chunk(r#"{"role": "assistant", "refusal": ""}"#, "null"),
+            chunk(r#"{"refusal": "I can't "}"#, "null"),
+            chunk(r#"{"refusal": "help with that."}"#, "null"),
+            chunk("{}", r#""stop""#),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        let (base_url, _server) = serve(vec![
+            RecordedResponse::json(200, body).header("content-type", "text/event-stream"),
+        ])
+        .await;
+        let client = OpenAIClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        let mut stream = client.create_message_stream(&req);
+        let mut accumulator = StreamAccumulator::new();
+        while let Some(event) = stream.next().await {
+            accumulator.handle_event(&event.unwrap());
+        }
+
+        let response = accumulator.into_response("gpt-4o");
+        assert_eq!(response.stop_reason, StopReason::Refusal);
+        assert_eq!(response.text(), "I can't help with that.");
+    }
+
+    #[tokio::test]
+    async fn test_dropping_stream_closes_connection() {
+        use crate::llm::LlmClient;
+        use crate::llm::test_server::serve_sse_until_closed;
+        use futures::StreamExt;
+
+        let chunk = "data: {\"id\": \"chatcmpl-1\", \"model\": \"gpt-4o\", \"choices\": [{\"index\": 0, \"delta\": {\"content\": \"hi\"}, \"finish_reason\": null}]}\n\n";
+        let (base_url, server) = serve_sse_until_closed(vec![], chunk.to_string(), 500).await;
+        let client = OpenAIClient::new("test-key").with_base_url(base_url);
+
+        let req = Request::new("gpt-4o").message(Message::user("Hello"));
+        let mut stream = client.create_message_stream(&req);
+        let first = stream.next().await.unwrap().unwrap();
+        assert!(matches!(first, StreamEvent::MessageStart { .. }));
+        drop(stream);
+
+        let outcome = tokio::time::timeout(std::time::Duration::from_secs(2), server)
+            .await
+            .expect("connection stayed open after the stream was dropped")
+            .unwrap();
+        assert!(outcome.closed_early);
+        assert!(outcome.chunks_sent < 500);
+    }
+
+    // Ollama and OpenRouter send this same body, so these cover them too.
+    #[test]
+    fn test_request_golden() {
+        use crate::llm::payload::audit::*;
+
+        let body = serde_json::to_value(OpenAIRequest::from(&representative_request())).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "model": "test-model",
+                "messages": [
+                    {"role": "system", "content": "You are helpful."},
+                    {"role": "user", "content": "What time is it?"},
+                    {"role": "assistant", "content": "Checking.", "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_time", "arguments": "{}"}
+                    }]},
+                    {"role": "tool", "content": "12:00", "tool_call_id": "call_1"}
+                ],
+                "max_tokens": 1024,
+                "temperature": 0.5,
+                "stop": ["END"],
+                "tools": [{
+                    "type": "function",
+                    "function": {
+                        "name": "get_time",
+                        "description": "Get the current time",
+                        "parameters": {"type": "object", "properties": {}}
+                    }
+                }]
+            })
+        );
+
+        let body = serde_json::to_value(OpenAIRequest::from(&sparse_request())).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "model": "test-model",
+                "messages": [
+                    {"role": "user", "content": "Hi\n\nStill there?"}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_bodies_have_no_empty_fields() {
+        use crate::llm::payload::audit::*;
+
+        for req in [representative_request(), sparse_request()] {
+            let body = serde_json::to_value(OpenAIRequest::from(&req)).unwrap();
+            assert_eq!(empty_fields(&body), Vec::<String>::new());
+        }
+    }
 }
@@ -1,8 +1,11 @@
 // ABOUTME: OpenRouter API client wrapping OpenAI-compatible API.
 // ABOUTME: Supports custom HTTP-Referer and X-Title headers for app identification.
 
+use super::body::{DEFAULT_MAX_RESPONSE_BYTES, read_json, read_text};
 use super::client::StreamEvent;
+use super::http::{DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT, default_http_client};
 use super::openai::{OpenAIError, OpenAIRequest, OpenAIResponse, parse_sse_line};
+use super::stream_accumulator::with_finished_blocks;
 use super::{ContentBlock, Request, Response, StopReason, Usage};
 use crate::error::LlmError;
 use async_trait::async_trait;
@@ -23,6 +26,7 @@ pub struct OpenRouterClient {
     api_key: String,
     http: reqwest::Client,
     default_model: String,
+    max_response_bytes: usize,
 }
 
 impl OpenRouterClient {
@@ -67,13 +71,16 @@ impl OpenRouterClient {
 
         let http = reqwest::Client::builder()
             .default_headers(headers)
+            .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+            .read_timeout(DEFAULT_READ_TIMEOUT)
             .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
+            .unwrap_or_else(|_| default_http_client());
 
         Self {
             api_key: api_key.into(),
             http,
             default_model: OPENROUTER_DEFAULT_MODEL.to_string(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
         }
     }
 
@@ -82,6 +89,14 @@ impl OpenRouterClient {
         self.default_model = model.into();
         self
     }
+
+    /// Fail non-streaming responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
 }
 
 fn parse_stop_reason(s: Option<&str>) -> StopReason {
@@ -89,6 +104,7 @@ fn parse_stop_reason(s: Option<&str>) -> StopReason {
         Some("stop") => StopReason::EndTurn,
         Some("tool_calls") => StopReason::ToolUse,
         Some("length") => StopReason::MaxTokens,
+        Some("content_filter") => StopReason::ContentFilter,
         _ => StopReason::EndTurn,
     }
 }
@@ -96,6 +112,8 @@ fn parse_stop_reason(s: Option<&str>) -> StopReason {
 #[async_trait]
 impl super::client::LlmClient for OpenRouterClient {
     async fn create_message(&self, req: &Request) -> Result<Response, LlmError> {
+        req.validate()?;
+
         let mut openai_req = OpenAIRequest::from(req);
 
         // Use default model if none specified
@@ -116,21 +134,24 @@ impl super::client::LlmClient for OpenRouterClient {
 
         let status = response.status();
         if !status.is_success() {
-            let error: OpenAIError = response.json().await?;
+            let error: OpenAIError = read_json(response, self.max_response_bytes).await?;
             return Err(LlmError::Api {
                 status: status.as_u16(),
                 message: error.error.message,
             });
         }
 
-        let openai_resp: OpenAIResponse = response.json().await?;
-        Ok(Response::from(openai_resp))
+        let openai_resp: OpenAIResponse = read_json(response, self.max_response_bytes).await?;
+        let mut response = Response::from(openai_resp);
+        response.model = openai_req.model;
+        Ok(response)
     }
 
     fn create_message_stream(
         &self,
         req: &Request,
     ) -> Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static>> {
+        let validation = req.validate();
         let mut openai_req = OpenAIRequest::from(req);
 
         // Use default model if none specified
@@ -142,8 +163,11 @@ impl super::client::LlmClient for OpenRouterClient {
 
         let api_key = self.api_key.clone();
         let http = self.http.clone();
+        let max_response_bytes = self.max_response_bytes;
+
+        Box::pin(with_finished_blocks(async_stream::try_stream! {
+            validation?;
 
-        Box::pin(async_stream::try_stream! {
             let url = format!("{}/chat/completions", OPENROUTER_BASE_URL);
             let response = http
                 .post(&url)
@@ -155,7 +179,7 @@ impl super::client::LlmClient for OpenRouterClient {
 
             let status = response.status();
             if !status.is_success() {
-                let error_text = response.text().await?;
+                let error_text = read_text(response, max_response_bytes).await?;
                 let error: OpenAIError = serde_json::from_str(&error_text)?;
                 Err(LlmError::Api {
                     status: status.as_u16(),
@@ -168,6 +192,7 @@ impl super::client::LlmClient for OpenRouterClient {
             let mut buffer = String::new();
             let mut message_started = false;
             let mut text_block_index: Option<usize> = None;
+            let mut refused = false;
             let mut next_block_index = 0usize;
             // Track tool calls: (id, name, args, block_index, block_started)
             let mut current_tool_calls: Vec<(String, String, String, usize, bool)> = Vec::new();
@@ -195,8 +220,12 @@ impl super::client::LlmClient for OpenRouterClient {
                         }
 
                         for choice in chunk.choices {
+                            // A refusal streams like text, in its own field
+                            let refusal = choice.delta.refusal.filter(|r| !r.is_empty());
+                            refused |= refusal.is_some();
+
                             // Handle text content
-                            if let Some(text) = choice.delta.content {
+                            for text in choice.delta.content.into_iter().chain(refusal) {
                                 // Emit ContentBlockStart for text on first text delta
                                 if text_block_index.is_none() {
                                     let idx = next_block_index;
@@ -264,18 +293,23 @@ impl super::client::LlmClient for OpenRouterClient {
                             if let Some(reason) = choice.finish_reason {
                                 // Close text block if started
                                 if let Some(idx) = text_block_index {
-                                    yield StreamEvent::ContentBlockStop { index: idx };
+                                    yield StreamEvent::ContentBlockStop { index: idx, block: None };
                                 }
 
                                 // Close tool call blocks
                                 for (id, _, _, block_idx, started) in current_tool_calls.iter() {
                                     if *started && !id.is_empty() {
-                                        yield StreamEvent::ContentBlockStop { index: *block_idx };
+                                        yield StreamEvent::ContentBlockStop { index: *block_idx, block: None };
                                     }
                                 }
 
+                                let stop_reason = if refused {
+                                    StopReason::Refusal
+                                } else {
+                                    parse_stop_reason(Some(&reason))
+                                };
                                 yield StreamEvent::MessageDelta {
-                                    stop_reason: Some(parse_stop_reason(Some(&reason))),
+                                    stop_reason: Some(stop_reason),
                                     usage: Usage::default(),
                                 };
                                 yield StreamEvent::MessageStop;
@@ -284,7 +318,7 @@ impl super::client::LlmClient for OpenRouterClient {
                     }
                 }
             }
-        })
+        }))
     }
 }
 
@@ -323,6 +357,16 @@ mod openrouter_test {
         assert_eq!(client.default_model, "openai/gpt-4-turbo");
     }
 
+    #[test]
+    fn test_parse_stop_reason() {
+        assert_eq!(parse_stop_reason(Some("stop")), StopReason::EndTurn);
+        assert_eq!(parse_stop_reason(Some("length")), StopReason::MaxTokens);
+        assert_eq!(
+            parse_stop_reason(Some("content_filter")),
+            StopReason::ContentFilter
+        );
+    }
+
     #[test]
     fn test_constants() {
         assert_eq!(OPENROUTER_BASE_URL, "https://openrouter.ai/api/v1");
@@ -0,0 +1,164 @@
+// ABOUTME: Extracts fields from incomplete JSON while tool input is still streaming.
+// ABOUTME: Lets UIs show arguments (e.g. a file path) before the full object arrives.
+
+/// Extract a top-level string field from a possibly incomplete JSON object.
+///
+/// Returns `Some` only once the field's closing quote has been received, so
+/// the value is never a truncated prefix. Nested objects and arrays are skipped.
+///
+/// ```
+/// use mux::llm::partial_json::partial_string_field;
+///
+/// let partial = r#"{"path": "src/main.rs", "content": "fn ma"#;
+/// assert_eq!(partial_string_field(partial, "path").as_deref(), Some("src/main.rs"));
+/// assert_eq!(partial_string_field(partial, "content"), None);
+/// ```
+pub fn partial_string_field(partial: &str, field: &str) -> Option<String> {
+    let bytes = partial.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    if bytes.get(pos) != Some(&b'{') {
+        return None;
+    }
+    pos += 1;
+
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        match bytes.get(pos)? {
+            b',' => {
+                pos += 1;
+                continue;
+            }
+            b'}' => return None,
+            b'"' => {}
+            _ => return None,
+        }
+
+        // Key
+        let key_end = string_end(bytes, pos)?;
+        let key: String = serde_json::from_str(&partial[pos..key_end]).ok()?;
+        pos = skip_whitespace(bytes, key_end);
+        if bytes.get(pos)? != &b':' {
+            return None;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+
+        // Value
+        let value_start = pos;
+        pos = value_end(bytes, pos)?;
+        if key == field {
+            if bytes[value_start] != b'"' {
+                return None;
+            }
+            return serde_json::from_str(&partial[value_start..pos]).ok();
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Given `pos` at an opening quote, return the index just past the closing quote.
+fn string_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    let mut i = pos + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Given `pos` at the start of a value, return the index just past its end.
+/// Returns `None` if the value has not been fully received yet.
+fn value_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => string_end(bytes, pos),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut i = pos;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            None
+        }
+        _ => {
+            // Scalar: runs until a delimiter. Without one it may still be streaming.
+            let mut i = pos;
+            while i < bytes.len() {
+                if matches!(bytes[i], b',' | b'}') || bytes[i].is_ascii_whitespace() {
+                    return Some(i);
+                }
+                i += 1;
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_field() {
+        let json = r#"{"path": "src/foo.rs", "content": "hello"}"#;
+        assert_eq!(
+            partial_string_field(json, "path").as_deref(),
+            Some("src/foo.rs")
+        );
+        assert_eq!(
+            partial_string_field(json, "content").as_deref(),
+            Some("hello")
+        );
+    }
+
+    #[test]
+    fn test_unterminated_value_is_none() {
+        assert_eq!(partial_string_field(r#"{"path": "src/fo"#, "path"), None);
+        assert_eq!(partial_string_field(r#"{"pa"#, "path"), None);
+        assert_eq!(partial_string_field("", "path"), None);
+    }
+
+    #[test]
+    fn test_skips_nested_values() {
+        let json = r#"{"opts": {"path": "nested"}, "list": [1, "a,b"], "n": 3, "path": "top"#;
+        assert_eq!(partial_string_field(json, "path"), None);
+
+        let json = r#"{"opts": {"path": "nested"}, "list": [1, "a,b"], "n": 3, "path": "top""#;
+        assert_eq!(partial_string_field(json, "path").as_deref(), Some("top"));
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let json = r#"{"path": "a \"quoted\" name", "x"#;
+        assert_eq!(
+            partial_string_field(json, "path").as_deref(),
+            Some(r#"a "quoted" name"#)
+        );
+    }
+
+    #[test]
+    fn test_non_string_field_is_none() {
+        assert_eq!(partial_string_field(r#"{"limit": 10, "#, "limit"), None);
+    }
+}
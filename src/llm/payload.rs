@@ -0,0 +1,241 @@
+// ABOUTME: Serialization policy shared by every provider's request body.
+// ABOUTME: Keeps empty optional fields, blocks and messages out of the payload.
+
+// Every provider request type follows the same rules, so a body only
+// carries what the caller actually set:
+//
+// - Optional fields are `Option`s or `Vec`s marked `skip_serializing_if`,
+//   never serialized as `null` or `[]`.
+// - Optional objects (e.g. Gemini's `generationConfig`) are only built when
+//   one of their fields is set, so they never serialize as `{}`.
+// - An empty system prompt is treated as no system prompt, and empty
+//   instruction layers are dropped.
+// - Empty text blocks are dropped, and messages left with no content are
+//   skipped rather than sent as `content: []`.
+// - Providers that need alternating turns (OpenAI-compatible and Gemini)
+//   get adjacent same-role messages merged by `Message::normalize`.
+//   Anthropic merges them itself, and its cache breakpoints are message
+//   indices, so it gets them as they are.
+// - Thinking blocks are dropped, except by Anthropic, which takes back
+//   signed ones from earlier turns.
+// - A tool choice is only sent when it isn't the default and there are
+//   tools to choose from.
+// - A candidate count is only sent when more than one completion is asked
+//   for.
+//
+// Values supplied by the caller, such as tool inputs and tool schemas, are
+// passed through untouched: `{}` is a valid input for a tool with no
+// parameters.
+
+use std::borrow::Cow;
+
+use super::{ContentBlock, InstructionRole, Message, Request, ToolChoice};
+
+/// The system prompt to send, if any, with instruction layers joined onto
+/// it. An empty prompt counts as none.
+pub(crate) fn system_prompt(req: &Request) -> Option<Cow<'_, str>> {
+    req.collapsed_system()
+}
+
+/// The system prompt and instruction layers, in order, for providers that
+/// keep them as separate messages. Empty layers are skipped.
+pub(crate) fn instruction_layers(req: &Request) -> impl Iterator<Item = (InstructionRole, &str)> {
+    req.system
+        .as_deref()
+        .map(|system| (InstructionRole::System, system))
+        .into_iter()
+        .chain(req.instructions.iter().map(|i| (i.role, i.text.as_str())))
+        .filter(|(_, text)| !text.is_empty())
+}
+
+/// The tool choice to send, if any. [`ToolChoice::Auto`] is every
+/// provider's default, and a choice without tools means nothing.
+pub(crate) fn tool_choice(req: &Request) -> Option<&ToolChoice> {
+    match &req.tool_choice {
+        ToolChoice::Auto => None,
+        _ if req.tools.is_empty() => None,
+        choice => Some(choice),
+    }
+}
+
+/// The number of completions to ask for, if more than one.
+pub(crate) fn candidates(req: &Request) -> Option<u32> {
+    req.candidates.filter(|&n| n > 1)
+}
+
+/// The blocks of `message` worth sending: everything but empty text and
+/// thinking.
+pub(crate) fn content_blocks(message: &Message) -> impl Iterator<Item = &ContentBlock> {
+    message.content.iter().filter(|block| is_sendable(block))
+}
+
+/// The blocks of `message` worth sending to a provider that takes back its
+/// reasoning: those from [`content_blocks`], signed thinking and redacted
+/// thinking, in order.
+pub(crate) fn content_blocks_with_thinking(
+    message: &Message,
+) -> impl Iterator<Item = &ContentBlock> {
+    message.content.iter().filter(|block| {
+        is_sendable(block)
+            || matches!(block, ContentBlock::Thinking { signature, .. } if !signature.is_empty())
+            || matches!(block, ContentBlock::RedactedThinking { .. })
+    })
+}
+
+fn is_sendable(block: &ContentBlock) -> bool {
+    match block {
+        ContentBlock::Text { text } => !text.is_empty(),
+        ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => false,
+        _ => true,
+    }
+}
+
+/// Whether `message` has at least one block left to send.
+pub(crate) fn has_content(message: &Message) -> bool {
+    content_blocks(message).next().is_some()
+}
+
+/// The messages worth sending: those with at least one block left to send.
+pub(crate) fn messages(messages: &[Message]) -> impl Iterator<Item = &Message> {
+    messages.iter().filter(|message| has_content(message))
+}
+
+/// The messages worth sending, with adjacent same-role messages merged.
+///
+/// Clients reject unanswered tool calls in [`Request::validate`] before
+/// building a body, so if [`Message::normalize`] fails here the messages
+/// are sent unmerged and the provider reports the problem.
+pub(crate) fn turns(messages: &[Message]) -> Vec<Message> {
+    Message::normalize(messages).unwrap_or_else(|_| self::messages(messages).cloned().collect())
+}
+
+/// Test harness for auditing serialized request bodies against the policy.
+#[cfg(test)]
+pub(crate) mod audit {
+    use serde_json::Value;
+
+    use crate::llm::{ContentBlock, Message, Request, Role, ToolChoice, ToolDefinition};
+
+    /// Keys whose values come from the caller and may legitimately be empty.
+    const PASSTHROUGH: &[&str] = &["input", "input_schema", "parameters", "args", "response"];
+
+    /// JSON paths of every `null`, `""`, `[]` or `{}` in `body`, skipping
+    /// caller-supplied values.
+    pub(crate) fn empty_fields(body: &Value) -> Vec<String> {
+        let mut found = Vec::new();
+        collect(body, "$", &mut found);
+        found
+    }
+
+    fn collect(value: &Value, path: &str, found: &mut Vec<String>) {
+        match value {
+            Value::Null => found.push(path.to_string()),
+            Value::String(s) if s.is_empty() => found.push(path.to_string()),
+            Value::Array(items) if items.is_empty() => found.push(path.to_string()),
+            Value::Object(fields) if fields.is_empty() => found.push(path.to_string()),
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    collect(item, &format!("{}[{}]", path, i), found);
+                }
+            }
+            Value::Object(fields) => {
+                for (key, child) in fields {
+                    if !PASSTHROUGH.contains(&key.as_str()) {
+                        collect(child, &format!("{}.{}", path, key), found);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A tool that takes no parameters, so its calls have `{}` input.
+    fn clock_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "get_time".into(),
+            description: "Get the current time".into(),
+            input_schema: serde_json::json!({"type": "object", "properties": {}}),
+        }
+    }
+
+    /// A typical tool-using conversation with every common field set.
+    pub(crate) fn representative_request() -> Request {
+        Request::new("test-model")
+            .system("You are helpful.")
+            .max_tokens(1024)
+            .temperature(0.5)
+            .stop_sequences(["END"])
+            .tool(clock_tool())
+            .message(Message::user("What time is it?"))
+            .message(Message {
+                role: Role::Assistant,
+                content: vec![
+                    ContentBlock::text("Checking."),
+                    ContentBlock::ToolUse {
+                        id: "call_1".into(),
+                        name: "get_time".into(),
+                        input: serde_json::json!({}),
+                    },
+                ],
+            })
+            .message(Message::tool_results(vec![ContentBlock::tool_result(
+                "call_1", "12:00",
+            )]))
+    }
+
+    /// A request with nothing optional set and some empty values the
+    /// serializers should leave out.
+    pub(crate) fn sparse_request() -> Request {
+        Request {
+            system: Some(String::new()),
+            tool_choice: ToolChoice::None,
+            ..Request::new("test-model")
+        }
+        .message(Message::user("Hi"))
+        .message(Message {
+            role: Role::Assistant,
+            content: vec![ContentBlock::text("")],
+        })
+        .message(Message {
+            role: Role::User,
+            content: vec![ContentBlock::text(""), ContentBlock::text("Still there?")],
+        })
+        .message(Message {
+            role: Role::Assistant,
+            content: vec![ContentBlock::thinking("They asked again.", "")],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::audit::*;
+    use super::*;
+
+    #[test]
+    fn test_empty_fields_skips_passthrough_values() {
+        let body = serde_json::json!({
+            "model": "m",
+            "system": "",
+            "tools": [],
+            "messages": [{"content": [{"type": "tool_use", "input": {}}], "extra": null}],
+            "config": {}
+        });
+        assert_eq!(
+            empty_fields(&body),
+            vec!["$.config", "$.messages[0].extra", "$.system", "$.tools"]
+        );
+    }
+
+    #[test]
+    fn test_sparse_request_is_trimmed() {
+        let req = sparse_request();
+        assert_eq!(system_prompt(&req), None);
+        assert_eq!(instruction_layers(&req).count(), 0);
+        assert_eq!(tool_choice(&req), None);
+
+        let sent: Vec<&Message> = messages(&req.messages).collect();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(content_blocks(sent[1]).count(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+// ABOUTME: Retry policy with exponential backoff for transient LLM API failures.
+// ABOUTME: Retries throttling/overload statuses and honors the retry-after header.
+
+use std::time::Duration;
+
+use crate::error::LlmError;
+
+/// Status codes treated as transient: rate limited, server errors, overloaded.
+const RETRYABLE_STATUSES: &[u16] = &[429, 500, 502, 503, 529];
+
+/// Controls how an LLM client retries transient HTTP failures.
+///
+/// The default performs no retries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    pub max_retries: u32,
+
+    /// Delay before the first retry. Doubles on each subsequent retry.
+    pub base_delay: Duration,
+
+    /// Upper bound for any single delay, including `retry-after` values.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+
+    /// A policy with `max_retries` retries and default delays.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::none()
+        }
+    }
+
+    /// Returns true if a response with this status should be retried.
+    pub fn is_retryable(status: u16) -> bool {
+        RETRYABLE_STATUSES.contains(&status)
+    }
+
+    /// Delay before retry number `retry` (0-based).
+    ///
+    /// A server-provided `retry_after` takes precedence over the backoff schedule.
+    pub fn delay_for(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let delay = retry_after
+            .unwrap_or_else(|| self.base_delay.saturating_mul(2u32.saturating_pow(retry)));
+        delay.min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Parse a `retry-after` header given in seconds.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    parse_retry_after(value.to_str().ok()?)
+}
+
+/// Seconds to wait, or `None` for a value that isn't a usable duration,
+/// e.g. negative, `inf` or too large for a `Duration`.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let seconds: f64 = value.trim().parse().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Send a request, retrying transient failures according to `policy`.
+///
+/// `build` is called once per attempt. Returns the final response (which may
+/// still be an error status) and the number of attempts made.
+pub(crate) async fn send_with_retry<F>(
+    policy: &RetryPolicy,
+    build: F,
+) -> Result<(reqwest::Response, u32), LlmError>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut retries = 0;
+    loop {
+        let response = build().send().await?;
+        let status = response.status().as_u16();
+
+        if retries >= policy.max_retries || !RetryPolicy::is_retryable(status) {
+            return Ok((response, retries + 1));
+        }
+
+        let delay = policy.delay_for(retries, retry_after(&response));
+        tokio::time::sleep(delay).await;
+        retries += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_no_retries() {
+        assert_eq!(RetryPolicy::default().max_retries, 0);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        for status in [429, 500, 502, 503, 529] {
+            assert!(RetryPolicy::is_retryable(status));
+        }
+        for status in [200, 400, 401, 404, 501] {
+            assert!(!RetryPolicy::is_retryable(status));
+        }
+    }
+
+    #[test]
+    fn test_exponential_backoff_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+
+        assert_eq!(policy.delay_for(0, None), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(1, None), Duration::from_millis(200));
+        assert_eq!(policy.delay_for(3, None), Duration::from_millis(800));
+        assert_eq!(policy.delay_for(4, None), Duration::from_millis(1000));
+        assert_eq!(policy.delay_for(40, None), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_retry_after_takes_precedence() {
+        let policy = RetryPolicy::new(3);
+
+        assert_eq!(
+            policy.delay_for(0, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        // Still bounded by max_delay
+        assert_eq!(
+            policy.delay_for(0, Some(Duration::from_secs(120))),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after_rejects_unusable_values() {
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        for value in ["inf", "1e30", "-1", "NaN", "soon"] {
+            assert_eq!(parse_retry_after(value), None, "{}", value);
+        }
+    }
+}
@@ -1,35 +1,69 @@
-// ABOUTME: Utility that accumulates StreamEvents into Vec<ContentBlock>.
-// ABOUTME: Handles text deltas, tool use JSON fragments, and block lifecycle.
+// ABOUTME: Utility that accumulates StreamEvents into content blocks or a Response.
+// ABOUTME: Handles text deltas, tool use JSON fragments, block lifecycle and usage.
 
-use super::{ContentBlock, StreamEvent};
+use std::collections::HashMap;
+
+use futures::{Stream, StreamExt};
+
+use super::{
+    ContentBlock, InvalidToolInput, Response, StopReason, StreamEvent, Usage, parse_tool_input,
+};
+use crate::error::LlmError;
 
 /// Accumulates streaming events into finalized content blocks.
 ///
 /// Feed events via [`handle_event`](Self::handle_event) and call
-/// [`into_content`](Self::into_content) to retrieve the assembled blocks.
+/// [`into_content`](Self::into_content) to retrieve the assembled blocks, or
+/// [`into_response`](Self::into_response) for the whole response.
 pub struct StreamAccumulator {
+    id: String,
+    model: String,
+    stop_reason: Option<StopReason>,
+    usage: Usage,
     content_blocks: Vec<ContentBlock>,
+    invalid_tool_inputs: Vec<InvalidToolInput>,
     current_text: String,
     current_tool_id: String,
     current_tool_name: String,
     current_tool_input: String,
+    /// Reasoning and signature of the open thinking block, if any.
+    current_thinking: Option<(String, String)>,
+    /// Data of the open redacted thinking block, which arrives whole.
+    current_redacted_thinking: Option<String>,
 }
 
 impl StreamAccumulator {
     /// Create a new empty accumulator.
     pub fn new() -> Self {
         Self {
+            id: String::new(),
+            model: String::new(),
+            stop_reason: None,
+            usage: Usage::default(),
             content_blocks: Vec::new(),
+            invalid_tool_inputs: Vec::new(),
             current_text: String::new(),
             current_tool_id: String::new(),
             current_tool_name: String::new(),
             current_tool_input: String::new(),
+            current_thinking: None,
+            current_redacted_thinking: None,
         }
     }
 
     /// Process a single stream event.
     pub fn handle_event(&mut self, event: &StreamEvent) {
         match event {
+            StreamEvent::MessageStart { id, model } => {
+                self.id = id.clone();
+                self.model = model.clone();
+            }
+            StreamEvent::MessageDelta { stop_reason, usage } => {
+                if stop_reason.is_some() {
+                    self.stop_reason = stop_reason.clone();
+                }
+                self.usage = usage.clone();
+            }
             StreamEvent::ContentBlockStart { block, .. } => match block {
                 ContentBlock::Text { .. } => {
                     self.current_text = String::new();
@@ -39,19 +73,64 @@ impl StreamAccumulator {
                     self.current_tool_name = name.clone();
                     self.current_tool_input = String::new();
                 }
+                ContentBlock::Thinking { .. } => {
+                    self.current_thinking = Some(Default::default());
+                }
+                ContentBlock::RedactedThinking { data } => {
+                    self.current_redacted_thinking = Some(data.clone());
+                }
                 _ => {}
             },
             StreamEvent::ContentBlockDelta { text, .. } => {
                 self.current_text.push_str(text);
             }
+            StreamEvent::ThinkingDelta { text, .. } => {
+                if let Some((so_far, _)) = &mut self.current_thinking {
+                    so_far.push_str(text);
+                }
+            }
+            StreamEvent::SignatureDelta { signature, .. } => {
+                if let Some((_, so_far)) = &mut self.current_thinking {
+                    so_far.push_str(signature);
+                }
+            }
             StreamEvent::InputJsonDelta { partial_json, .. } => {
                 self.current_tool_input.push_str(partial_json);
             }
-            StreamEvent::ContentBlockStop { .. } => {
-                if !self.current_tool_id.is_empty() {
+            StreamEvent::ContentBlockStop {
+                block: Some(block), ..
+            } => {
+                // The finished block has `{}` for input that didn't parse
+                if matches!(block, ContentBlock::ToolUse { .. })
+                    && let (_, Some(invalid)) =
+                        InvalidToolInput::check(self.content_blocks.len(), &self.current_tool_input)
+                {
+                    self.invalid_tool_inputs.push(invalid);
+                }
+                if !matches!(block, ContentBlock::Text { text } if text.is_empty()) {
+                    self.content_blocks.push(block.clone());
+                }
+                self.current_text.clear();
+                self.current_tool_id.clear();
+                self.current_tool_name.clear();
+                self.current_tool_input.clear();
+                self.current_thinking = None;
+                self.current_redacted_thinking = None;
+            }
+            StreamEvent::ContentBlockStop { block: None, .. } => {
+                if let Some((text, signature)) = self.current_thinking.take() {
+                    self.content_blocks
+                        .push(ContentBlock::Thinking { text, signature });
+                } else if let Some(data) = self.current_redacted_thinking.take() {
+                    self.content_blocks
+                        .push(ContentBlock::RedactedThinking { data });
+                } else if !self.current_tool_id.is_empty() {
                     // Finalize tool use block
-                    let input = serde_json::from_str(&self.current_tool_input)
-                        .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
+                    let (input, invalid) = InvalidToolInput::check(
+                        self.content_blocks.len(),
+                        &self.current_tool_input,
+                    );
+                    self.invalid_tool_inputs.extend(invalid);
                     self.content_blocks.push(ContentBlock::ToolUse {
                         id: std::mem::take(&mut self.current_tool_id),
                         name: std::mem::take(&mut self.current_tool_name),
@@ -73,10 +152,47 @@ impl StreamAccumulator {
         !self.current_tool_id.is_empty()
     }
 
+    /// The tool input JSON received so far for the current tool use block.
+    ///
+    /// This is usually incomplete; see [`partial_json`](super::partial_json)
+    /// for reading fields out of it before the block finishes.
+    pub fn current_tool_input(&self) -> &str {
+        &self.current_tool_input
+    }
+
+    /// The name of the tool in the current tool use block, or `""` outside one.
+    pub fn current_tool_name(&self) -> &str {
+        &self.current_tool_name
+    }
+
     /// Consume the accumulator and return the finalized content blocks.
+    ///
+    /// Tool input that isn't valid JSON is left as `{}`; use
+    /// [`into_response`](Self::into_response) to find out which.
     pub fn into_content(self) -> Vec<ContentBlock> {
         self.content_blocks
     }
+
+    /// Consume the accumulator and return the response the stream described.
+    ///
+    /// `model` is the model the request named; the one the stream reported
+    /// becomes [`Response::served_model`]. A stream that never said why it
+    /// stopped ended its turn. Tool calls whose input isn't valid JSON are
+    /// listed in [`Response::invalid_tool_inputs`].
+    pub fn into_response(self, model: impl Into<String>) -> Response {
+        Response {
+            id: self.id,
+            content: self.content_blocks,
+            stop_reason: self.stop_reason.unwrap_or(StopReason::EndTurn),
+            model: model.into(),
+            served_model: (!self.model.is_empty()).then_some(self.model),
+            system_fingerprint: None,
+            usage: self.usage,
+            attempts: 1,
+            citations: Vec::new(),
+            invalid_tool_inputs: self.invalid_tool_inputs,
+        }
+    }
 }
 
 impl Default for StreamAccumulator {
@@ -85,6 +201,88 @@ impl Default for StreamAccumulator {
     }
 }
 
+/// Assembles each block from its start and delta events, so the
+/// `ContentBlockStop` event can carry the finished block.
+#[derive(Default)]
+struct BlockAssembler {
+    /// Open blocks by index, with the tool input JSON received so far.
+    open: HashMap<usize, (ContentBlock, String)>,
+}
+
+impl BlockAssembler {
+    fn apply(&mut self, event: StreamEvent) -> StreamEvent {
+        match event {
+            StreamEvent::ContentBlockStart { index, ref block } => {
+                self.open.insert(index, (block.clone(), String::new()));
+            }
+            StreamEvent::ContentBlockDelta { index, ref text } => {
+                if let Some((ContentBlock::Text { text: so_far }, _)) = self.open.get_mut(&index) {
+                    so_far.push_str(text);
+                }
+            }
+            StreamEvent::ThinkingDelta { index, ref text } => {
+                if let Some((ContentBlock::Thinking { text: so_far, .. }, _)) =
+                    self.open.get_mut(&index)
+                {
+                    so_far.push_str(text);
+                }
+            }
+            StreamEvent::SignatureDelta {
+                index,
+                ref signature,
+            } => {
+                if let Some((
+                    ContentBlock::Thinking {
+                        signature: so_far, ..
+                    },
+                    _,
+                )) = self.open.get_mut(&index)
+                {
+                    so_far.push_str(signature);
+                }
+            }
+            StreamEvent::InputJsonDelta {
+                index,
+                ref partial_json,
+            } => {
+                if let Some((_, json)) = self.open.get_mut(&index) {
+                    json.push_str(partial_json);
+                }
+            }
+            StreamEvent::ContentBlockStop { index, block: None } => {
+                let block = self.open.remove(&index).map(|(block, json)| match block {
+                    ContentBlock::ToolUse { id, name, input } => ContentBlock::ToolUse {
+                        id,
+                        name,
+                        // Keep the start event's input if no deltas arrived,
+                        // and leave input that doesn't parse as `{}`
+                        input: if json.is_empty() {
+                            input
+                        } else {
+                            parse_tool_input(&json).unwrap_or_else(|_| serde_json::json!({}))
+                        },
+                    },
+                    block => block,
+                });
+                return StreamEvent::ContentBlockStop { index, block };
+            }
+            _ => {}
+        }
+        event
+    }
+}
+
+/// Fill in the finished block on every `ContentBlockStop` of `stream`.
+///
+/// Clients wrap their event streams in this so consumers don't have to
+/// reassemble text and tool input themselves.
+pub(crate) fn with_finished_blocks(
+    stream: impl Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static,
+) -> impl Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static {
+    let mut assembler = BlockAssembler::default();
+    stream.map(move |event| event.map(|event| assembler.apply(event)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,7 +308,10 @@ mod tests {
             index: 0,
             text: " world".into(),
         });
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 0 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
         acc.handle_event(&StreamEvent::MessageDelta {
             stop_reason: Some(StopReason::EndTurn),
             usage: crate::llm::Usage::default(),
@@ -145,7 +346,10 @@ mod tests {
             index: 0,
             partial_json: r#"mand": "ls"}"#.into(),
         });
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 0 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
 
         let blocks = acc.into_content();
         assert_eq!(blocks.len(), 1);
@@ -172,7 +376,10 @@ mod tests {
             index: 0,
             text: "Let me check.".into(),
         });
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 0 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
 
         // Tool use block
         acc.handle_event(&StreamEvent::ContentBlockStart {
@@ -187,7 +394,10 @@ mod tests {
             index: 1,
             partial_json: r#"{"path": "foo.rs"}"#.into(),
         });
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 1 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 1,
+            block: None,
+        });
 
         let blocks = acc.into_content();
         assert_eq!(blocks.len(), 2);
@@ -195,6 +405,40 @@ mod tests {
         assert!(matches!(&blocks[1], ContentBlock::ToolUse { name, .. } if name == "read"));
     }
 
+    #[test]
+    fn test_accumulate_thinking() {
+        let mut acc = StreamAccumulator::new();
+
+        acc.handle_event(&StreamEvent::ContentBlockStart {
+            index: 0,
+            block: ContentBlock::thinking("", ""),
+        });
+        acc.handle_event(&StreamEvent::ThinkingDelta {
+            index: 0,
+            text: "Check the tests".into(),
+        });
+        acc.handle_event(&StreamEvent::ThinkingDelta {
+            index: 0,
+            text: " first.".into(),
+        });
+        acc.handle_event(&StreamEvent::SignatureDelta {
+            index: 0,
+            signature: "sig_1".into(),
+        });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
+
+        let blocks = acc.into_content();
+        assert_eq!(blocks.len(), 1);
+        assert!(matches!(
+            &blocks[0],
+            ContentBlock::Thinking { text, signature }
+                if text == "Check the tests first." && signature == "sig_1"
+        ));
+    }
+
     #[test]
     fn test_in_tool_use() {
         let mut acc = StreamAccumulator::new();
@@ -210,7 +454,10 @@ mod tests {
         });
         assert!(acc.in_tool_use());
 
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 0 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
         assert!(!acc.in_tool_use());
     }
 
@@ -230,16 +477,242 @@ mod tests {
             index: 0,
             partial_json: "not valid json".into(),
         });
-        acc.handle_event(&StreamEvent::ContentBlockStop { index: 0 });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
 
-        let blocks = acc.into_content();
-        assert_eq!(blocks.len(), 1);
-        // Should fall back to empty object
-        match &blocks[0] {
+        let response = acc.into_response("test");
+        assert_eq!(response.content.len(), 1);
+        // The call is kept with an empty input and reported separately
+        match &response.content[0] {
             ContentBlock::ToolUse { input, .. } => {
                 assert_eq!(input, &serde_json::json!({}));
             }
             _ => panic!("Expected ToolUse block"),
         }
+        assert_eq!(response.invalid_tool_inputs.len(), 1);
+        assert_eq!(response.invalid_tool_inputs[0].index, 0);
+        assert_eq!(response.invalid_tool_inputs[0].arguments, "not valid json");
+    }
+
+    #[test]
+    fn test_into_response_collects_the_whole_message() {
+        let mut acc = StreamAccumulator::new();
+        let events = [
+            StreamEvent::MessageStart {
+                id: "msg_1".into(),
+                model: "claude-test-20250101".into(),
+            },
+            StreamEvent::ContentBlockStart {
+                index: 0,
+                block: ContentBlock::text(""),
+            },
+            StreamEvent::ContentBlockDelta {
+                index: 0,
+                text: "Checking.".into(),
+            },
+            StreamEvent::ContentBlockStop {
+                index: 0,
+                block: None,
+            },
+            StreamEvent::ContentBlockStart {
+                index: 1,
+                block: ContentBlock::ToolUse {
+                    id: "toolu_1".into(),
+                    name: "bash".into(),
+                    input: serde_json::json!({}),
+                },
+            },
+            StreamEvent::InputJsonDelta {
+                index: 1,
+                partial_json: r#"{"command": "ls"}"#.into(),
+            },
+            StreamEvent::ContentBlockStop {
+                index: 1,
+                block: None,
+            },
+            StreamEvent::MessageDelta {
+                stop_reason: Some(StopReason::ToolUse),
+                usage: Usage {
+                    input_tokens: 12,
+                    output_tokens: 7,
+                    ..Default::default()
+                },
+            },
+            StreamEvent::MessageStop,
+        ];
+        for event in &events {
+            acc.handle_event(event);
+        }
+
+        let response = acc.into_response("claude-test");
+        assert_eq!(response.id, "msg_1");
+        assert_eq!(response.model, "claude-test");
+        assert_eq!(
+            response.served_model.as_deref(),
+            Some("claude-test-20250101")
+        );
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(
+            (response.usage.input_tokens, response.usage.output_tokens),
+            (12, 7)
+        );
+        assert_eq!(response.text(), "Checking.");
+        assert!(matches!(
+            &response.content[1],
+            ContentBlock::ToolUse { input, .. } if input["command"] == "ls"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_into_response_reports_malformed_finished_tool_input() {
+        let tool_call = |index: usize, json: &str| {
+            [
+                StreamEvent::ContentBlockStart {
+                    index,
+                    block: ContentBlock::ToolUse {
+                        id: format!("toolu_{}", index),
+                        name: "bash".into(),
+                        input: serde_json::json!({}),
+                    },
+                },
+                StreamEvent::InputJsonDelta {
+                    index,
+                    partial_json: json.into(),
+                },
+                StreamEvent::ContentBlockStop { index, block: None },
+            ]
+        };
+        // A string is a valid, if unusual, input
+        let events: Vec<_> = tool_call(0, r#"{"command": "l"#)
+            .into_iter()
+            .chain(tool_call(1, r#""ls""#))
+            .map(Ok)
+            .collect();
+        let mut stream = std::pin::pin!(with_finished_blocks(futures::stream::iter(events)));
+
+        let mut acc = StreamAccumulator::new();
+        while let Some(event) = stream.next().await {
+            acc.handle_event(&event.unwrap());
+        }
+
+        let response = acc.into_response("test");
+        assert!(matches!(
+            &response.content[..],
+            [
+                ContentBlock::ToolUse { input: first, .. },
+                ContentBlock::ToolUse { input: second, .. },
+            ] if *first == serde_json::json!({}) && *second == serde_json::json!("ls")
+        ));
+        assert_eq!(response.invalid_tool_inputs.len(), 1);
+        assert_eq!(response.invalid_tool_inputs[0].index, 0);
+        assert!(
+            response.invalid_tool_inputs[0]
+                .error
+                .contains("EOF while parsing")
+        );
+    }
+
+    #[test]
+    fn test_empty_tool_input_is_empty_object() {
+        let mut acc = StreamAccumulator::new();
+
+        acc.handle_event(&StreamEvent::ContentBlockStart {
+            index: 0,
+            block: ContentBlock::ToolUse {
+                id: "toolu_1".into(),
+                name: "list_files".into(),
+                input: serde_json::json!({}),
+            },
+        });
+        acc.handle_event(&StreamEvent::ContentBlockStop {
+            index: 0,
+            block: None,
+        });
+
+        match &acc.into_content()[0] {
+            ContentBlock::ToolUse { input, .. } => {
+                assert_eq!(input, &serde_json::json!({}));
+            }
+            _ => panic!("Expected ToolUse block"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_events_carry_finished_blocks() {
+        let events = vec![
+            StreamEvent::ContentBlockStart {
+                index: 0,
+                block: ContentBlock::text(""),
+            },
+            StreamEvent::ContentBlockDelta {
+                index: 0,
+                text: "Let me ".into(),
+            },
+            StreamEvent::ContentBlockStart {
+                index: 1,
+                block: ContentBlock::ToolUse {
+                    id: "toolu_1".into(),
+                    name: "bash".into(),
+                    input: serde_json::json!({}),
+                },
+            },
+            StreamEvent::InputJsonDelta {
+                index: 1,
+                partial_json: r#"{"command": "#.into(),
+            },
+            StreamEvent::ContentBlockDelta {
+                index: 0,
+                text: "check.".into(),
+            },
+            StreamEvent::InputJsonDelta {
+                index: 1,
+                partial_json: r#""ls"}"#.into(),
+            },
+            StreamEvent::ContentBlockStop {
+                index: 0,
+                block: None,
+            },
+            StreamEvent::ContentBlockStop {
+                index: 1,
+                block: None,
+            },
+        ];
+        let events: Vec<StreamEvent> =
+            with_finished_blocks(futures::stream::iter(events.into_iter().map(Ok)))
+                .map(|event| event.unwrap())
+                .collect()
+                .await;
+
+        assert!(matches!(
+            &events[6],
+            StreamEvent::ContentBlockStop {
+                index: 0,
+                block: Some(ContentBlock::Text { text }),
+            } if text == "Let me check."
+        ));
+        match &events[7] {
+            StreamEvent::ContentBlockStop {
+                index: 1,
+                block: Some(ContentBlock::ToolUse { id, name, input }),
+            } => {
+                assert_eq!(id, "toolu_1");
+                assert_eq!(name, "bash");
+                assert_eq!(input, &serde_json::json!({"command": "ls"}));
+            }
+            other => panic!("Expected finished tool use, got {:?}", other),
+        }
+
+        // The accumulator takes finished blocks as they are
+        let mut acc = StreamAccumulator::new();
+        for event in &events {
+            acc.handle_event(event);
+        }
+        let blocks = acc.into_content();
+        assert_eq!(blocks.len(), 2);
+        assert!(
+            matches!(&blocks[1], ContentBlock::ToolUse { input, .. } if input["command"] == "ls")
+        );
     }
 }
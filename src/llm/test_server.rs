@@ -0,0 +1,157 @@
+// ABOUTME: Minimal local HTTP server that replays recorded responses for client tests.
+// ABOUTME: Captures raw requests so tests can assert on paths, headers, and bodies.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::JoinHandle;
+
+/// A recorded HTTP response to replay.
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedResponse {
+    /// A JSON response with the given status.
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type".into(), "application/json".into())],
+            body: body.into(),
+        }
+    }
+
+    /// Add a response header.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Serve `responses` in order, one per connection.
+/// Returns the base URL and a handle resolving to the raw requests received.
+pub async fn serve(responses: Vec<RecordedResponse>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            requests.push(read_request(&mut socket).await);
+
+            let mut raw = format!("HTTP/1.1 {} Recorded\r\n", response.status);
+            for (name, value) in &response.headers {
+                raw.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            raw.push_str(&format!(
+                "content-length: {}\r\nconnection: close\r\n\r\n{}",
+                response.body.len(),
+                response.body
+            ));
+            socket.write_all(raw.as_bytes()).await.unwrap();
+        }
+        requests
+    });
+
+    (base_url, handle)
+}
+
+/// Serve a single recorded response.
+pub async fn serve_once(status: u16, body: &str) -> (String, JoinHandle<Vec<String>>) {
+    serve(vec![RecordedResponse::json(status, body)]).await
+}
+
+/// How a client left an endless streamed response.
+pub struct StreamOutcome {
+    /// Chunks written before the client closed the connection.
+    pub chunks_sent: usize,
+    /// True if the client closed the connection before every chunk was sent.
+    pub closed_early: bool,
+}
+
+/// Serve one chunked `text/event-stream` response: each of `first` right away,
+/// then `filler` every 10ms, up to `max_chunks` in total. Stops as soon as the
+/// client closes the connection, so tests can tell an abort from a drain.
+pub async fn serve_sse_until_closed(
+    first: Vec<String>,
+    filler: String,
+    max_chunks: usize,
+) -> (String, JoinHandle<StreamOutcome>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        read_request(&mut socket).await;
+        let (mut reader, mut writer) = socket.into_split();
+        writer
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 64];
+        let mut chunks_sent = 0;
+        while chunks_sent < max_chunks {
+            let data = first.get(chunks_sent).unwrap_or(&filler);
+            let chunk = format!("{:x}\r\n{}\r\n", data.len(), data);
+            if writer.write_all(chunk.as_bytes()).await.is_err() {
+                return StreamOutcome {
+                    chunks_sent,
+                    closed_early: true,
+                };
+            }
+            chunks_sent += 1;
+
+            if chunks_sent >= first.len() {
+                tokio::select! {
+                    // The client never sends more after its request, so a read ends only on close
+                    _ = reader.read(&mut buf) => {
+                        return StreamOutcome { chunks_sent, closed_early: true };
+                    }
+                    _ = tokio::time::sleep(std::time::Duration::from_millis(10)) => {}
+                }
+            }
+        }
+
+        let _ = writer.write_all(b"0\r\n\r\n").await;
+        StreamOutcome {
+            chunks_sent,
+            closed_early: false,
+        }
+    });
+
+    (base_url, handle)
+}
+
+/// Read headers, then the body according to content-length.
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(header_end) = text.find("\r\n\r\n") {
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                })
+                .unwrap_or(0);
+            if request.len() >= header_end + 4 + content_length {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+
+    String::from_utf8_lossy(&request).to_string()
+}
@@ -1,8 +1,18 @@
 // ABOUTME: Core types for LLM communication - messages, content blocks,
 // ABOUTME: tool definitions, requests, and responses.
 
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
+use crate::error::LlmError;
+
+/// Smallest thinking budget providers accept.
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Room left for the answer when `max_tokens` is raised to fit a thinking budget.
+pub const MIN_TOKENS_AFTER_THINKING: u32 = 1024;
+
 /// Role of a message sender.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -12,12 +22,21 @@ pub enum Role {
 }
 
 /// Why the model stopped generating.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum StopReason {
     EndTurn,
     ToolUse,
     MaxTokens,
+    /// Generation hit one of the request's stop sequences.
+    /// Only reported by providers that say which sequence matched;
+    /// others report `EndTurn`.
+    StopSequence(String),
+    /// The provider's safety filter blocked or cut off the output, so the
+    /// content may be empty or incomplete.
+    ContentFilter,
+    /// The model declined to answer.
+    Refusal,
 }
 
 /// A block of content within a message.
@@ -37,21 +56,97 @@ pub enum ContentBlock {
         content: String,
         #[serde(default)]
         is_error: bool,
+        /// Images returned by the tool, for providers that accept them in
+        /// tool results (see [`LlmClient::supports_tool_result_images`]).
+        ///
+        /// [`LlmClient::supports_tool_result_images`]: super::LlmClient::supports_tool_result_images
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        images: Vec<ImageSource>,
+    },
+    /// An image for vision-capable models.
+    Image {
+        source: ImageSource,
+    },
+    /// The model's reasoning before it answered, from extended thinking.
+    ///
+    /// Not part of the answer: [`Response::text`] skips it. Keep it in the
+    /// transcript, since Anthropic requires it, signature intact, when a
+    /// thinking turn is continued after a tool call. Other providers don't
+    /// receive it.
+    Thinking {
+        text: String,
+        /// Anthropic's opaque signature verifying the reasoning.
+        #[serde(default)]
+        signature: String,
+    },
+    /// Reasoning the provider encrypted because its safety systems flagged
+    /// it. Opaque, but kept and sent back like [`ContentBlock::Thinking`].
+    RedactedThinking {
+        data: String,
     },
 }
 
+/// Where an image's data comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Image bytes, base64-encoded, with their MIME type (e.g. `image/png`).
+    Base64 { media_type: String, data: String },
+    /// An image the provider downloads itself.
+    Url { url: String },
+}
+
+impl ImageSource {
+    /// Base64-encoded image data of the given MIME type.
+    pub fn base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        Self::Base64 {
+            media_type: media_type.into(),
+            data: data.into(),
+        }
+    }
+
+    /// An image at `url`.
+    pub fn url(url: impl Into<String>) -> Self {
+        Self::Url { url: url.into() }
+    }
+
+    /// Text standing in for the image where images can't be sent.
+    pub fn placeholder(&self) -> String {
+        match self {
+            Self::Base64 { media_type, data } => {
+                format!("[Image: {} bytes, type: {}]", data.len(), media_type)
+            }
+            Self::Url { url } => format!("[Image: {}]", url),
+        }
+    }
+}
+
 impl ContentBlock {
     /// Create a text content block.
     pub fn text(text: impl Into<String>) -> Self {
         Self::Text { text: text.into() }
     }
 
+    /// Create a thinking content block.
+    pub fn thinking(text: impl Into<String>, signature: impl Into<String>) -> Self {
+        Self::Thinking {
+            text: text.into(),
+            signature: signature.into(),
+        }
+    }
+
+    /// Create an image content block.
+    pub fn image(source: ImageSource) -> Self {
+        Self::Image { source }
+    }
+
     /// Create a tool result content block.
     pub fn tool_result(tool_use_id: impl Into<String>, content: impl Into<String>) -> Self {
         Self::ToolResult {
             tool_use_id: tool_use_id.into(),
             content: content.into(),
             is_error: false,
+            images: Vec::new(),
         }
     }
 
@@ -61,8 +156,80 @@ impl ContentBlock {
             tool_use_id: tool_use_id.into(),
             content: error.into(),
             is_error: true,
+            images: Vec::new(),
+        }
+    }
+
+    /// Attach images to a tool result block. Other blocks are unchanged.
+    pub fn with_images(mut self, new_images: impl IntoIterator<Item = ImageSource>) -> Self {
+        if let Self::ToolResult { images, .. } = &mut self {
+            images.extend(new_images);
         }
+        self
+    }
+}
+
+/// Parse tool call arguments that arrive as a JSON string.
+///
+/// Empty arguments become `{}`. Providers give a call whose arguments fail to
+/// parse an input of `{}` and report it in [`Response::invalid_tool_inputs`],
+/// so the agent loop can tell the model its call was malformed instead of
+/// running the tool with an empty input.
+pub fn parse_tool_input(arguments: &str) -> Result<serde_json::Value, serde_json::Error> {
+    if arguments.trim().is_empty() {
+        return Ok(serde_json::Value::Object(serde_json::Map::new()));
     }
+    serde_json::from_str(arguments)
+}
+
+/// A tool call whose arguments weren't valid JSON.
+///
+/// The call's `ToolUse` block stays in [`Response::content`] with an input of
+/// `{}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidToolInput {
+    /// Position of the call's `ToolUse` block in [`Response::content`].
+    pub index: usize,
+    /// The arguments as the model sent them.
+    pub arguments: String,
+    /// Why they didn't parse.
+    pub error: String,
+}
+
+impl InvalidToolInput {
+    /// Parse `arguments` for the `ToolUse` block at `index`, returning the
+    /// input to store in the block and, if they didn't parse, the failure.
+    pub fn check(index: usize, arguments: &str) -> (serde_json::Value, Option<Self>) {
+        match parse_tool_input(arguments) {
+            Ok(input) => (input, None),
+            Err(e) => (
+                serde_json::Value::Object(serde_json::Map::new()),
+                Some(Self {
+                    index,
+                    arguments: arguments.to_string(),
+                    error: e.to_string(),
+                }),
+            ),
+        }
+    }
+}
+
+/// Merge runs of adjacent `Text` blocks into a single block.
+///
+/// Streaming can split one stretch of text into many blocks. Blocks separated
+/// by anything other than text are left apart, so text before and after a
+/// tool call stays in order around it.
+pub fn merge_adjacent_text(content: &mut Vec<ContentBlock>) {
+    let mut merged: Vec<ContentBlock> = Vec::with_capacity(content.len());
+    for block in content.drain(..) {
+        match (merged.last_mut(), block) {
+            (Some(ContentBlock::Text { text: prev }), ContentBlock::Text { text }) => {
+                prev.push_str(&text)
+            }
+            (_, block) => merged.push(block),
+        }
+    }
+    *content = merged;
 }
 
 /// A conversation message.
@@ -89,6 +256,24 @@ impl Message {
         }
     }
 
+    /// Create a user message with an image followed by text about it.
+    pub fn user_with_image(text: impl Into<String>, image: ImageSource) -> Self {
+        Self::user_with_images(text, [image])
+    }
+
+    /// Create a user message with images followed by text about them.
+    pub fn user_with_images(
+        text: impl Into<String>,
+        images: impl IntoIterator<Item = ImageSource>,
+    ) -> Self {
+        let mut content: Vec<ContentBlock> = images.into_iter().map(ContentBlock::image).collect();
+        content.push(ContentBlock::text(text));
+        Self {
+            role: Role::User,
+            content,
+        }
+    }
+
     /// Create a user message with tool results.
     pub fn tool_results(results: Vec<ContentBlock>) -> Self {
         Self {
@@ -96,6 +281,64 @@ impl Message {
             content: results,
         }
     }
+
+    /// A copy of `messages` that providers requiring alternating turns
+    /// accept.
+    ///
+    /// Adjacent messages with the same role are merged into one, and a text
+    /// block meeting a text block across the seam is joined with a blank
+    /// line. Messages with nothing to send are dropped first, so they don't
+    /// keep their neighbours apart.
+    ///
+    /// Fails with `LlmError::InvalidRequest` if a tool call isn't answered
+    /// by a tool result in the user message after it.
+    pub fn normalize(messages: &[Message]) -> Result<Vec<Message>, LlmError> {
+        let mut merged: Vec<Message> = Vec::new();
+        for message in super::payload::messages(messages) {
+            match merged.last_mut() {
+                Some(last) if last.role == message.role => {
+                    let mut blocks = message
+                        .content
+                        .iter()
+                        .filter(|b| !matches!(b, ContentBlock::Text { text } if text.is_empty()))
+                        .cloned()
+                        .peekable();
+                    if let Some(ContentBlock::Text { text }) = last.content.last_mut()
+                        && let Some(ContentBlock::Text { text: next }) = blocks.peek()
+                    {
+                        text.push_str("\n\n");
+                        text.push_str(next);
+                        blocks.next();
+                    }
+                    last.content.extend(blocks);
+                }
+                _ => merged.push(message.clone()),
+            }
+        }
+
+        for (i, message) in merged.iter().enumerate() {
+            if message.role != Role::Assistant {
+                continue;
+            }
+            let answers = merged.get(i + 1).map_or(&[][..], |next| &next.content[..]);
+            for block in &message.content {
+                let ContentBlock::ToolUse { id, name, .. } = block else {
+                    continue;
+                };
+                let answered = answers.iter().any(|b| {
+                    matches!(b, ContentBlock::ToolResult { tool_use_id, .. } if tool_use_id == id)
+                });
+                if !answered {
+                    return Err(LlmError::InvalidRequest(format!(
+                        "tool call '{}' to '{}' has no tool result in the following user message",
+                        id, name
+                    )));
+                }
+            }
+        }
+
+        Ok(merged)
+    }
 }
 
 /// Definition of a tool for the LLM.
@@ -107,7 +350,7 @@ pub struct ToolDefinition {
 }
 
 /// Token usage statistics from a single API response.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     pub input_tokens: u32,
     pub output_tokens: u32,
@@ -119,6 +362,28 @@ pub struct Usage {
     pub cache_write_tokens: u32,
 }
 
+impl Usage {
+    /// Add another response's counts to these.
+    pub fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+    }
+}
+
+/// Totals across responses, e.g. the candidates from
+/// [`LlmClient::create_messages`](super::LlmClient::create_messages).
+impl<'a> std::iter::Sum<&'a Usage> for Usage {
+    fn sum<I: Iterator<Item = &'a Usage>>(iter: I) -> Self {
+        let mut total = Usage::default();
+        for usage in iter {
+            total.add(usage);
+        }
+        total
+    }
+}
+
 /// Helper for skip_serializing_if on u32.
 fn is_zero_u32(val: &u32) -> bool {
     *val == 0
@@ -129,15 +394,95 @@ fn is_zero_u64(val: &u64) -> bool {
     *val == 0
 }
 
+/// Who an instruction layer speaks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstructionRole {
+    /// Platform-level instructions.
+    System,
+    /// Instructions from the application developer, below the system layer.
+    Developer,
+}
+
+/// A layer of instructions stacked after the system prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub role: InstructionRole,
+    pub text: String,
+}
+
+impl Instruction {
+    /// Create a system instruction layer.
+    pub fn system(text: impl Into<String>) -> Self {
+        Self {
+            role: InstructionRole::System,
+            text: text.into(),
+        }
+    }
+
+    /// Create a developer instruction layer.
+    pub fn developer(text: impl Into<String>) -> Self {
+        Self {
+            role: InstructionRole::Developer,
+            text: text.into(),
+        }
+    }
+}
+
+/// Separator between instruction layers when a provider takes a single
+/// system prompt.
+pub const INSTRUCTION_SEPARATOR: &str = "\n\n---\n\n";
+
+/// Whether and how the model may call the request's tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call tools.
+    #[default]
+    Auto,
+    /// The model must not call tools.
+    None,
+    /// The model must call at least one tool.
+    Required,
+    /// The model must call the named tool.
+    Tool(String),
+}
+
 /// Request to create a message.
-#[derive(Debug, Clone, Default)]
+///
+/// Serializes field by field, so hooks can inspect and rewrite it; fields
+/// missing when deserializing take their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Request {
     pub model: String,
     pub messages: Vec<Message>,
     pub tools: Vec<ToolDefinition>,
+    /// Whether and how the model may call `tools`. Not sent when `tools`
+    /// is empty.
+    pub tool_choice: ToolChoice,
     pub max_tokens: Option<u32>,
     pub system: Option<String>,
+    /// Instruction layers that follow `system`, in order. OpenAI sends each
+    /// as its own message; other providers join them into the system prompt.
+    pub instructions: Vec<Instruction>,
     pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub stop_sequences: Vec<String>,
+    /// Token budget for extended thinking. Only Anthropic uses this.
+    pub thinking: Option<u32>,
+    /// How many completions [`LlmClient::create_messages`] asks for. `None`
+    /// means one. Other methods ignore it.
+    ///
+    /// [`LlmClient::create_messages`]: super::LlmClient::create_messages
+    pub candidates: Option<u32>,
+    /// Cache the system prompt. Only Anthropic uses this.
+    pub cache_system: bool,
+    /// Cache the tool definitions. Only Anthropic uses this.
+    pub cache_tools: bool,
+    /// Indices into `messages` of messages that end a cached prefix.
+    /// Only Anthropic uses this.
+    pub cache_messages: Vec<usize>,
 }
 
 impl Request {
@@ -173,23 +518,234 @@ impl Request {
         self
     }
 
+    /// Control whether and how the model may call tools. Defaults to
+    /// [`ToolChoice::Auto`].
+    pub fn tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = choice;
+        self
+    }
+
     /// Set the system prompt.
     pub fn system(mut self, system: impl Into<String>) -> Self {
         self.system = Some(system.into());
         self
     }
 
+    /// Stack an instruction layer after the system prompt and earlier layers.
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// The system prompt followed by every instruction layer, joined with
+    /// [`INSTRUCTION_SEPARATOR`], for providers that take a single prompt.
+    ///
+    /// Empty layers are skipped, and `None` means there is nothing to send.
+    pub fn collapsed_system(&self) -> Option<Cow<'_, str>> {
+        let mut layers = self
+            .system
+            .as_deref()
+            .into_iter()
+            .chain(self.instructions.iter().map(|i| i.text.as_str()))
+            .filter(|text| !text.is_empty());
+        let first = layers.next()?;
+        let Some(second) = layers.next() else {
+            return Some(Cow::Borrowed(first));
+        };
+        let mut joined = format!("{}{}{}", first, INSTRUCTION_SEPARATOR, second);
+        for layer in layers {
+            joined.push_str(INSTRUCTION_SEPARATOR);
+            joined.push_str(layer);
+        }
+        Some(Cow::Owned(joined))
+    }
+
     /// Set max tokens.
     pub fn max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = Some(max_tokens);
         self
     }
 
-    /// Set temperature.
+    /// Set temperature. Must be within `0.0..=2.0`; see [`Request::validate`].
     pub fn temperature(mut self, temperature: f64) -> Self {
         self.temperature = Some(temperature);
         self
     }
+
+    /// Set nucleus sampling probability. Must be within `0.0..=1.0`; see [`Request::validate`].
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set sequences that end generation when the model produces them.
+    pub fn stop_sequences<S: Into<String>>(
+        mut self,
+        sequences: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.stop_sequences = sequences.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Enable extended thinking with the given token budget (at least 1024).
+    ///
+    /// If `max_tokens` does not leave room for an answer after the budget,
+    /// the Anthropic client raises it; see [`Request::validate_thinking`] for
+    /// the combinations it rejects instead. Providers without extended
+    /// thinking ignore it.
+    pub fn thinking(mut self, budget_tokens: u32) -> Self {
+        self.thinking = Some(budget_tokens);
+        self
+    }
+
+    /// Ask [`LlmClient::create_messages`] for `n` completions of the same
+    /// conversation. Must be at least 1.
+    ///
+    /// Every completion is billed for its output tokens. OpenAI and Gemini
+    /// generate them in one call, charging for the input once; other
+    /// providers send `n` separate requests, each paying for the input.
+    ///
+    /// [`LlmClient::create_messages`]: super::LlmClient::create_messages
+    pub fn candidates(mut self, n: u32) -> Self {
+        self.candidates = Some(n);
+        self
+    }
+
+    /// Cache the system prompt across requests (Anthropic prompt caching).
+    ///
+    /// Other providers ignore cache hints. Anthropic allows at most four
+    /// cache breakpoints per request, counting system, tools and messages.
+    pub fn cache_system(mut self, cache: bool) -> Self {
+        self.cache_system = cache;
+        self
+    }
+
+    /// Cache the tool definitions across requests (Anthropic prompt caching).
+    ///
+    /// The breakpoint goes on the last tool, which caches all of them.
+    pub fn cache_tools(mut self, cache: bool) -> Self {
+        self.cache_tools = cache;
+        self
+    }
+
+    /// Cache the conversation up to and including the message at `index`
+    /// (Anthropic prompt caching). Indices past the end are ignored.
+    pub fn cache_message(mut self, index: usize) -> Self {
+        if !self.cache_messages.contains(&index) {
+            self.cache_messages.push(index);
+        }
+        self
+    }
+
+    /// Check sampling parameters are in range.
+    ///
+    /// Clients call this before sending, so an out-of-range value fails fast
+    /// with `LlmError::Configuration` rather than being clamped silently.
+    /// A [`ToolChoice::Tool`] naming a tool the request doesn't define, or a
+    /// tool call left without a result (see [`Message::normalize`]), fails
+    /// with `LlmError::InvalidRequest`.
+    pub fn validate(&self) -> Result<(), LlmError> {
+        if let Some(temperature) = self.temperature
+            && !(0.0..=2.0).contains(&temperature)
+        {
+            return Err(LlmError::Configuration(format!(
+                "temperature must be between 0.0 and 2.0, got {}",
+                temperature
+            )));
+        }
+        if let Some(top_p) = self.top_p
+            && !(0.0..=1.0).contains(&top_p)
+        {
+            return Err(LlmError::Configuration(format!(
+                "top_p must be between 0.0 and 1.0, got {}",
+                top_p
+            )));
+        }
+        if self.candidates == Some(0) {
+            return Err(LlmError::InvalidRequest(
+                "candidates must be at least 1".to_string(),
+            ));
+        }
+        if let ToolChoice::Tool(name) = &self.tool_choice
+            && !self.tools.iter().any(|tool| tool.name == *name)
+        {
+            return Err(LlmError::InvalidRequest(format!(
+                "tool_choice names tool '{}', which the request does not define",
+                name
+            )));
+        }
+        Message::normalize(&self.messages)?;
+        Ok(())
+    }
+
+    /// Check the request is compatible with its thinking budget, if it has one.
+    ///
+    /// Clients that support extended thinking call this as well as
+    /// [`validate`](Self::validate). Combinations the provider would reject
+    /// fail with `LlmError::InvalidRequest`:
+    ///
+    /// - a budget below [`MIN_THINKING_BUDGET`]
+    /// - a `temperature` other than 1.0
+    /// - a `top_p` below 0.95
+    /// - a `tool_choice` that forces a tool call
+    pub fn validate_thinking(&self) -> Result<(), LlmError> {
+        let Some(budget) = self.thinking else {
+            return Ok(());
+        };
+        if budget < MIN_THINKING_BUDGET {
+            return Err(LlmError::InvalidRequest(format!(
+                "thinking budget must be at least {} tokens, got {}",
+                MIN_THINKING_BUDGET, budget
+            )));
+        }
+        if let Some(temperature) = self.temperature
+            && temperature != 1.0
+        {
+            return Err(LlmError::InvalidRequest(format!(
+                "temperature cannot be changed when thinking is enabled, got {}",
+                temperature
+            )));
+        }
+        if let Some(top_p) = self.top_p
+            && top_p < 0.95
+        {
+            return Err(LlmError::InvalidRequest(format!(
+                "top_p must be at least 0.95 when thinking is enabled, got {}",
+                top_p
+            )));
+        }
+        if matches!(self.tool_choice, ToolChoice::Required | ToolChoice::Tool(_)) {
+            return Err(LlmError::InvalidRequest(
+                "tool_choice cannot force a tool call when thinking is enabled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `max_tokens` to send when thinking is enabled.
+    ///
+    /// Thinking tokens count towards `max_tokens`, so a limit at or below the
+    /// budget would leave no room for the answer. In that case the limit is
+    /// raised to the budget plus [`MIN_TOKENS_AFTER_THINKING`].
+    pub fn max_tokens_with_thinking(&self, default: u32) -> u32 {
+        let max_tokens = self.max_tokens.unwrap_or(default);
+        match self.thinking {
+            Some(budget) if max_tokens <= budget => {
+                budget.saturating_add(MIN_TOKENS_AFTER_THINKING)
+            }
+            _ => max_tokens,
+        }
+    }
+}
+
+/// A source cited by a response, in a provider-agnostic form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    pub url: String,
+    pub title: Option<String>,
+    /// The text the citation covers. Anthropic quotes the source; Gemini
+    /// quotes the part of the response the source supports.
+    pub cited_text: Option<String>,
 }
 
 /// Response from creating a message.
@@ -198,11 +754,60 @@ pub struct Response {
     pub id: String,
     pub content: Vec<ContentBlock>,
     pub stop_reason: StopReason,
+    /// The model requested.
     pub model: String,
+    /// The model the provider reports serving, if it reports one. Gateways
+    /// may fall back to another model, and aliases resolve to snapshots.
+    pub served_model: Option<String>,
+    /// The provider's backend configuration fingerprint (OpenAI's
+    /// `system_fingerprint`), if reported.
+    pub system_fingerprint: Option<String>,
     pub usage: Usage,
+    /// Number of HTTP attempts made, including retries. 1 if no retry occurred.
+    pub attempts: u32,
+    /// Sources the response cites, from server-side web search or grounding.
+    pub citations: Vec<Citation>,
+    /// Tool calls in `content` whose arguments weren't valid JSON.
+    pub invalid_tool_inputs: Vec<InvalidToolInput>,
 }
 
 impl Response {
+    /// Sources the response cites, in the order the provider reported them.
+    ///
+    /// Empty unless the model searched the web (Anthropic's web search
+    /// tool) or grounded its answer (Gemini's Google Search grounding).
+    pub fn citations(&self) -> &[Citation] {
+        &self.citations
+    }
+
+    /// The model that served this response: the reported model, or the
+    /// requested one if the provider didn't report it.
+    pub fn actual_model(&self) -> &str {
+        self.served_model.as_deref().unwrap_or(&self.model)
+    }
+
+    /// Whether the provider served a different model than requested.
+    ///
+    /// An alias resolving to a dated snapshot doesn't count: `gpt-4o`
+    /// served as `gpt-4o-2024-08-06`, or `claude-3-5-sonnet-latest` as
+    /// `claude-3-5-sonnet-20241022`.
+    pub fn model_substituted(&self) -> bool {
+        let Some(served) = &self.served_model else {
+            return false;
+        };
+        if self.model.is_empty() {
+            return false;
+        }
+        let base = self.model.strip_suffix("-latest").unwrap_or(&self.model);
+        match served.strip_prefix(base) {
+            Some("") => false,
+            Some(rest) => !rest
+                .strip_prefix('-')
+                .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_digit() || c == '-')),
+            None => true,
+        }
+    }
+
     /// Check if the response contains tool use blocks.
     pub fn has_tool_use(&self) -> bool {
         self.content
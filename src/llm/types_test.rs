@@ -69,6 +69,30 @@ fn test_content_block_tool_error_serialization() {
     assert_eq!(json["is_error"], true);
 }
 
+#[test]
+fn test_message_user_with_image_helper() {
+    let msg = Message::user_with_image("What is this?", ImageSource::base64("image/png", "iVBOR"));
+    assert_eq!(msg.role, Role::User);
+    assert_eq!(msg.content.len(), 2);
+    assert!(matches!(
+        &msg.content[0],
+        ContentBlock::Image { source: ImageSource::Base64 { media_type, data } }
+            if media_type == "image/png" && data == "iVBOR"
+    ));
+    assert!(matches!(&msg.content[1], ContentBlock::Text { text } if text == "What is this?"));
+
+    let json = serde_json::to_value(&msg.content[0]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": "image/png", "data": "iVBOR"}
+        })
+    );
+    let block: ContentBlock = serde_json::from_value(json).unwrap();
+    assert!(matches!(block, ContentBlock::Image { .. }));
+}
+
 #[test]
 fn test_message_user_helper() {
     let msg = Message::user("Hello");
@@ -95,6 +119,167 @@ fn test_request_builder() {
     assert_eq!(req.temperature, Some(0.7));
 }
 
+#[test]
+fn test_collapsed_system_joins_instruction_layers() {
+    let req = Request::new("gpt-4o")
+        .system("Platform rules.")
+        .instruction(Instruction::developer("App rules."))
+        .instruction(Instruction::developer(""))
+        .instruction(Instruction::system("Safety rules."));
+    assert_eq!(
+        req.collapsed_system().as_deref(),
+        Some("Platform rules.\n\n---\n\nApp rules.\n\n---\n\nSafety rules.")
+    );
+
+    // A single layer is sent as is, even without a system prompt
+    let req = Request::new("gpt-4o").instruction(Instruction::developer("App rules."));
+    assert_eq!(req.collapsed_system().as_deref(), Some("App rules."));
+    assert_eq!(Request::new("gpt-4o").system("").collapsed_system(), None);
+}
+
+#[test]
+fn test_request_top_p_builder() {
+    let req = Request::new("gpt-4o").temperature(1.5).top_p(0.9);
+
+    assert_eq!(req.top_p, Some(0.9));
+    assert!(req.validate().is_ok());
+    assert!(Request::new("gpt-4o").validate().is_ok());
+}
+
+#[test]
+fn test_request_stop_sequences() {
+    assert!(Request::new("m").stop_sequences.is_empty());
+
+    let req = Request::new("m").stop_sequences(["END", "STOP"]);
+    assert_eq!(req.stop_sequences, vec!["END", "STOP"]);
+
+    let json = serde_json::to_value(StopReason::StopSequence("END".into())).unwrap();
+    assert_eq!(json, serde_json::json!({"stop_sequence": "END"}));
+    assert_eq!(
+        serde_json::to_value(StopReason::EndTurn).unwrap(),
+        serde_json::json!("end_turn")
+    );
+}
+
+#[test]
+fn test_thinking_rejects_incompatible_sampling() {
+    use crate::error::LlmError;
+
+    assert!(Request::new("m").thinking(2048).validate_thinking().is_ok());
+    assert!(
+        Request::new("m")
+            .thinking(2048)
+            .temperature(1.0)
+            .top_p(0.95)
+            .validate_thinking()
+            .is_ok()
+    );
+
+    for (req, expected) in [
+        (Request::new("m").thinking(512), "at least 1024"),
+        (
+            Request::new("m").thinking(2048).temperature(0.2),
+            "temperature",
+        ),
+        (Request::new("m").thinking(2048).top_p(0.5), "top_p"),
+        (
+            Request::new("m")
+                .thinking(2048)
+                .tool_choice(ToolChoice::Required),
+            "tool_choice",
+        ),
+    ] {
+        match req.validate_thinking() {
+            Err(LlmError::InvalidRequest(msg)) => assert!(msg.contains(expected), "{}", msg),
+            other => panic!("Expected InvalidRequest, got {:?}", other),
+        }
+        // Providers without thinking ignore the budget
+        assert!(req.validate().is_ok());
+    }
+
+    // Without thinking the same sampling values are fine
+    assert!(
+        Request::new("m")
+            .temperature(0.2)
+            .top_p(0.5)
+            .validate_thinking()
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_max_tokens_raised_above_thinking_budget() {
+    let req = Request::new("m").thinking(8000).max_tokens(4096);
+    assert_eq!(
+        req.max_tokens_with_thinking(4096),
+        8000 + MIN_TOKENS_AFTER_THINKING
+    );
+
+    // Default max_tokens is raised too
+    let req = Request::new("m").thinking(4096);
+    assert_eq!(
+        req.max_tokens_with_thinking(4096),
+        4096 + MIN_TOKENS_AFTER_THINKING
+    );
+
+    // Enough room already: left alone
+    let req = Request::new("m").thinking(2048).max_tokens(16000);
+    assert_eq!(req.max_tokens_with_thinking(4096), 16000);
+
+    // No thinking: untouched
+    let req = Request::new("m").max_tokens(100);
+    assert_eq!(req.max_tokens_with_thinking(4096), 100);
+
+    // A huge budget saturates instead of overflowing
+    let req = Request::new("m").thinking(u32::MAX);
+    assert_eq!(req.max_tokens_with_thinking(4096), u32::MAX);
+}
+
+#[test]
+fn test_request_validate_ranges() {
+    use crate::error::LlmError;
+
+    for req in [
+        Request::new("m").temperature(2.5),
+        Request::new("m").temperature(-0.1),
+        Request::new("m").top_p(1.1),
+        Request::new("m").top_p(f64::NAN),
+    ] {
+        assert!(matches!(req.validate(), Err(LlmError::Configuration(_))));
+    }
+
+    match Request::new("m").top_p(1.5).validate() {
+        Err(LlmError::Configuration(msg)) => assert!(msg.contains("top_p")),
+        other => panic!("Expected configuration error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_tool_choice_must_name_a_defined_tool() {
+    use crate::error::LlmError;
+
+    let req = Request::new("m").tool(ToolDefinition {
+        name: "get_weather".to_string(),
+        description: "Get the weather".to_string(),
+        input_schema: serde_json::json!({"type": "object", "properties": {}}),
+    });
+    assert_eq!(req.tool_choice, ToolChoice::Auto);
+    assert!(
+        req.clone()
+            .tool_choice(ToolChoice::Tool("get_weather".into()))
+            .validate()
+            .is_ok()
+    );
+
+    match req
+        .tool_choice(ToolChoice::Tool("get_time".into()))
+        .validate()
+    {
+        Err(LlmError::InvalidRequest(msg)) => assert!(msg.contains("get_time"), "{}", msg),
+        other => panic!("Expected InvalidRequest, got {:?}", other),
+    }
+}
+
 #[test]
 fn test_response_has_tool_use() {
     let response = Response {
@@ -109,7 +294,12 @@ fn test_response_has_tool_use() {
         ],
         stop_reason: StopReason::ToolUse,
         model: "claude-sonnet-4-20250514".to_string(),
+        served_model: None,
+        system_fingerprint: None,
         usage: Usage::default(),
+        attempts: 1,
+        citations: Vec::new(),
+        invalid_tool_inputs: Vec::new(),
     };
 
     assert!(response.has_tool_use());
@@ -124,13 +314,52 @@ fn test_response_no_tool_use() {
         content: vec![ContentBlock::text("Hello!")],
         stop_reason: StopReason::EndTurn,
         model: "claude-sonnet-4-20250514".to_string(),
+        served_model: None,
+        system_fingerprint: None,
         usage: Usage::default(),
+        attempts: 1,
+        citations: Vec::new(),
+        invalid_tool_inputs: Vec::new(),
     };
 
     assert!(!response.has_tool_use());
     assert!(response.tool_uses().is_empty());
 }
 
+#[test]
+fn test_response_served_model() {
+    let mut response = Response {
+        id: "123".to_string(),
+        content: vec![],
+        stop_reason: StopReason::EndTurn,
+        model: "gpt-4o".to_string(),
+        served_model: None,
+        system_fingerprint: None,
+        usage: Usage::default(),
+        attempts: 1,
+        citations: Vec::new(),
+        invalid_tool_inputs: Vec::new(),
+    };
+    assert_eq!(response.actual_model(), "gpt-4o");
+    assert!(!response.model_substituted());
+
+    // An alias resolving to a snapshot isn't a substitution
+    response.served_model = Some("gpt-4o-2024-08-06".to_string());
+    assert_eq!(response.actual_model(), "gpt-4o-2024-08-06");
+    assert!(!response.model_substituted());
+
+    response.model = "claude-3-5-sonnet-latest".to_string();
+    response.served_model = Some("claude-3-5-sonnet-20241022".to_string());
+    assert!(!response.model_substituted());
+
+    response.model = "gpt-4o".to_string();
+    response.served_model = Some("gpt-4o-mini".to_string());
+    assert!(response.model_substituted());
+
+    response.served_model = Some("gpt-3.5-turbo".to_string());
+    assert!(response.model_substituted());
+}
+
 #[test]
 fn test_stop_reason_serialization() {
     assert_eq!(
@@ -497,3 +726,105 @@ fn test_token_usage_snapshot_default() {
     assert_eq!(snapshot.cache_write_tokens, 0);
     assert_eq!(snapshot.request_count, 0);
 }
+
+#[test]
+fn test_merge_adjacent_text_blocks() {
+    let mut content = vec![
+        ContentBlock::text("Let me "),
+        ContentBlock::text("check "),
+        ContentBlock::text("that."),
+        ContentBlock::ToolUse {
+            id: "t1".into(),
+            name: "read_file".into(),
+            input: serde_json::json!({"path": "a.txt"}),
+        },
+        ContentBlock::text("Then "),
+        ContentBlock::text("this."),
+    ];
+    merge_adjacent_text(&mut content);
+
+    assert_eq!(content.len(), 3);
+    assert!(matches!(&content[0], ContentBlock::Text { text } if text == "Let me check that."));
+    assert!(matches!(&content[1], ContentBlock::ToolUse { id, .. } if id == "t1"));
+    assert!(matches!(&content[2], ContentBlock::Text { text } if text == "Then this."));
+}
+
+#[test]
+fn test_merge_adjacent_text_keeps_separated_text_apart() {
+    let mut content = vec![
+        ContentBlock::text("before"),
+        ContentBlock::ToolUse {
+            id: "t1".into(),
+            name: "bash".into(),
+            input: serde_json::json!({}),
+        },
+        ContentBlock::text("after"),
+    ];
+    merge_adjacent_text(&mut content);
+
+    assert_eq!(content.len(), 3);
+    assert!(matches!(&content[0], ContentBlock::Text { text } if text == "before"));
+    assert!(matches!(&content[2], ContentBlock::Text { text } if text == "after"));
+}
+
+#[test]
+fn test_normalize_merges_same_role_runs() {
+    let messages = vec![
+        Message::user("Hi"),
+        Message {
+            role: Role::Assistant,
+            content: vec![],
+        },
+        Message::user("Still there?"),
+        Message::assistant("Yes."),
+        Message::assistant("Sorry, I was busy."),
+    ];
+
+    let merged = Message::normalize(&messages).unwrap();
+
+    assert_eq!(merged.len(), 2);
+    assert!(
+        matches!(&merged[0].content[..], [ContentBlock::Text { text }] if text == "Hi\n\nStill there?")
+    );
+    assert_eq!(merged[1].role, Role::Assistant);
+    assert!(
+        matches!(&merged[1].content[..], [ContentBlock::Text { text }] if text == "Yes.\n\nSorry, I was busy.")
+    );
+}
+
+#[test]
+fn test_normalize_rejects_unanswered_tool_call() {
+    use crate::error::LlmError;
+
+    let call = Message {
+        role: Role::Assistant,
+        content: vec![ContentBlock::ToolUse {
+            id: "t1".into(),
+            name: "read_file".into(),
+            input: serde_json::json!({}),
+        }],
+    };
+
+    // Results and text sent as separate user messages still answer the call
+    let answered = vec![
+        Message::user("Read it"),
+        call.clone(),
+        Message::tool_results(vec![ContentBlock::tool_result("t1", "contents")]),
+        Message::user("And summarize"),
+    ];
+    let merged = Message::normalize(&answered).unwrap();
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged[2].content.len(), 2);
+
+    let unanswered = vec![Message::user("Read it"), call, Message::user("Well?")];
+    match Message::normalize(&unanswered) {
+        Err(LlmError::InvalidRequest(msg)) => {
+            assert!(msg.contains("'t1'") && msg.contains("read_file"), "{}", msg)
+        }
+        other => panic!("Expected invalid request error, got {:?}", other),
+    }
+    assert!(matches!(
+        Request::new("m").messages(unanswered).validate(),
+        Err(LlmError::InvalidRequest(_))
+    ));
+}
@@ -0,0 +1,185 @@
+// ABOUTME: UsageTracker - running token totals per model, with cost estimates.
+// ABOUTME: Prices come from a caller-supplied table, in USD per million tokens.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::Usage;
+
+/// Prices per model as `(input, output)` in USD per million tokens.
+///
+/// A model is priced by its exact name, or else by the longest key its name
+/// starts with, so `claude-sonnet-4` also prices `claude-sonnet-4-20250514`.
+pub type PriceTable = HashMap<String, (f64, f64)>;
+
+/// Cache reads cost this fraction of the input price (Anthropic's rate).
+pub const CACHE_READ_PRICE_FACTOR: f64 = 0.1;
+
+/// Cache writes cost this multiple of the input price (Anthropic's 5-minute rate).
+pub const CACHE_WRITE_PRICE_FACTOR: f64 = 1.25;
+
+/// Token usage accumulated per model over a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageTracker {
+    by_model: BTreeMap<String, Usage>,
+}
+
+impl UsageTracker {
+    /// Create an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one response's usage to the model's running total.
+    pub fn record(&mut self, model: &str, usage: &Usage) {
+        self.by_model
+            .entry(model.to_string())
+            .or_default()
+            .add(usage);
+    }
+
+    /// Add every model's totals from `other`.
+    pub fn merge(&mut self, other: &UsageTracker) {
+        for (model, usage) in &other.by_model {
+            self.record(model, usage);
+        }
+    }
+
+    /// Totals per model, sorted by model name.
+    pub fn by_model(&self) -> &BTreeMap<String, Usage> {
+        &self.by_model
+    }
+
+    /// Totals across all models.
+    pub fn total(&self) -> Usage {
+        let mut total = Usage::default();
+        for usage in self.by_model.values() {
+            total.add(usage);
+        }
+        total
+    }
+
+    /// Whether nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.by_model.is_empty()
+    }
+
+    /// Estimated cost of one model's usage in USD, if `prices` covers it.
+    ///
+    /// Cache reads and writes are priced from the input price using
+    /// [`CACHE_READ_PRICE_FACTOR`] and [`CACHE_WRITE_PRICE_FACTOR`].
+    pub fn model_cost(&self, model: &str, prices: &PriceTable) -> Option<f64> {
+        let usage = self.by_model.get(model)?;
+        let (input, output) = price_for(model, prices)?;
+        let tokens = |n: u32| n as f64 / 1_000_000.0;
+        Some(
+            tokens(usage.input_tokens) * input
+                + tokens(usage.output_tokens) * output
+                + tokens(usage.cache_read_tokens) * input * CACHE_READ_PRICE_FACTOR
+                + tokens(usage.cache_write_tokens) * input * CACHE_WRITE_PRICE_FACTOR,
+        )
+    }
+
+    /// Estimated cost of all usage in USD.
+    ///
+    /// `None` if any model used has no price, since a partial sum would
+    /// understate the cost; see [`unpriced_models`](Self::unpriced_models).
+    pub fn estimated_cost(&self, prices: &PriceTable) -> Option<f64> {
+        self.by_model
+            .keys()
+            .map(|model| self.model_cost(model, prices))
+            .sum()
+    }
+
+    /// Models with recorded usage but no entry in `prices`.
+    pub fn unpriced_models(&self, prices: &PriceTable) -> Vec<&str> {
+        self.by_model
+            .keys()
+            .filter(|model| price_for(model, prices).is_none())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn price_for(model: &str, prices: &PriceTable) -> Option<(f64, f64)> {
+    prices.get(model).copied().or_else(|| {
+        prices
+            .iter()
+            .filter(|(key, _)| model.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| *price)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u32, output: u32) -> Usage {
+        Usage {
+            input_tokens: input,
+            output_tokens: output,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tracker_accumulates_per_model() {
+        let mut tracker = UsageTracker::new();
+        tracker.record("claude-sonnet-4-20250514", &usage(100, 10));
+        tracker.record("gpt-4o", &usage(50, 5));
+        tracker.record(
+            "claude-sonnet-4-20250514",
+            &Usage {
+                cache_read_tokens: 1000,
+                ..usage(20, 2)
+            },
+        );
+
+        let sonnet = &tracker.by_model()["claude-sonnet-4-20250514"];
+        assert_eq!(sonnet.input_tokens, 120);
+        assert_eq!(sonnet.output_tokens, 12);
+        assert_eq!(sonnet.cache_read_tokens, 1000);
+
+        let total = tracker.total();
+        assert_eq!(total.input_tokens, 170);
+        assert_eq!(total.output_tokens, 17);
+
+        let mut merged = UsageTracker::new();
+        merged.merge(&tracker);
+        merged.merge(&tracker);
+        assert_eq!(merged.total().input_tokens, 340);
+    }
+
+    #[test]
+    fn test_estimated_cost() {
+        let mut tracker = UsageTracker::new();
+        tracker.record(
+            "claude-sonnet-4-20250514",
+            &Usage {
+                input_tokens: 1_000_000,
+                output_tokens: 100_000,
+                cache_read_tokens: 1_000_000,
+                cache_write_tokens: 1_000_000,
+            },
+        );
+
+        let mut prices = PriceTable::new();
+        prices.insert("claude".into(), (100.0, 100.0));
+        prices.insert("claude-sonnet-4".into(), (3.0, 15.0));
+
+        // 3 input + 1.5 output + 0.3 cache read + 3.75 cache write
+        let cost = tracker.estimated_cost(&prices).unwrap();
+        assert!((cost - 8.55).abs() < 1e-9, "cost was {}", cost);
+
+        tracker.record("local-model", &usage(10, 10));
+        assert_eq!(tracker.estimated_cost(&prices), None);
+        assert_eq!(tracker.unpriced_models(&prices), vec!["local-model"]);
+        assert!(
+            tracker
+                .model_cost("claude-sonnet-4-20250514", &prices)
+                .is_some()
+        );
+    }
+}
@@ -2,60 +2,319 @@
 // ABOUTME: Supports full MCP protocol: tools, resources, prompts, roots, logging.
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use tokio::sync::watch;
 
 use super::transport::{HttpTransport, SseTransport, StdioTransport, Transport};
 use super::{
-    McpInitializeResult, McpLogLevel, McpNotification, McpPromptGetResult, McpPromptsListResult,
-    McpRequest, McpResourceContent, McpResourceReadResult, McpResourceTemplatesListResult,
-    McpResourcesListResult, McpRoot, McpRootsListResult, McpSamplingParams, McpSamplingResult,
-    McpServerCapabilities, McpServerConfig, McpToolInfo, McpToolResult, McpTransport,
+    McpHealth, McpInitializeResult, McpLogLevel, McpNotification, McpProgress, McpPromptGetResult,
+    McpPromptInfo, McpPromptsListResult, McpRequest, McpResourceContent, McpResourceInfo,
+    McpResourceReadResult, McpResourceTemplatesListResult, McpResourcesListResult, McpRoot,
+    McpRootsListResult, McpRpcError, McpSamplingParams, McpSamplingResult, McpServerCapabilities,
+    McpServerConfig, McpServerEvent, McpToolInfo, McpToolResult, McpTransport,
 };
 use crate::error::McpError;
+use crate::tool::{SchemaViolation, validate_schema};
+
+/// Opens a fresh transport to the server when reconnecting.
+type Connector =
+    Arc<dyn Fn() -> BoxFuture<'static, Result<Arc<dyn Transport>, McpError>> + Send + Sync>;
+
+/// Called with the server name and attempt number after a reconnect succeeds.
+type ReconnectCallback = Arc<dyn Fn(&str, u32) + Send + Sync>;
+
+/// Called with each change the server announces.
+type EventObserver = Arc<dyn Fn(&McpServerEvent) + Send + Sync>;
+
+/// Called with each progress update for a tool call.
+type ProgressCallback = Arc<dyn Fn(&McpProgress) + Send + Sync>;
+
+/// Progress callbacks of the calls in flight, by progress token.
+type ProgressHandlers = RwLock<HashMap<u64, ProgressCallback>>;
+
+/// The live transport, and how many times it has been replaced.
+type Connection = RwLock<(Arc<dyn Transport>, u64)>;
+
+/// Most pages fetched when re-reading a list after a change notification.
+const MAX_LIST_PAGES: usize = 100;
+
+/// Lists last fetched in full.
+#[derive(Default)]
+struct McpLists {
+    tools: Option<Vec<McpToolInfo>>,
+    resources: Option<Vec<McpResourceInfo>>,
+    prompts: Option<Vec<McpPromptInfo>>,
+}
+
+impl McpLists {
+    fn apply(&mut self, event: &McpServerEvent) {
+        match event {
+            McpServerEvent::ToolsChanged(tools) => self.tools = Some(tools.clone()),
+            McpServerEvent::ResourcesChanged(resources) => self.resources = Some(resources.clone()),
+            McpServerEvent::PromptsChanged(prompts) => self.prompts = Some(prompts.clone()),
+            McpServerEvent::ResourceUpdated { .. } => {}
+        }
+    }
+}
+
+/// Removes a progress callback once its call finishes or is dropped.
+struct ProgressRegistration<'a> {
+    handlers: &'a ProgressHandlers,
+    token: u64,
+}
+
+impl Drop for ProgressRegistration<'_> {
+    fn drop(&mut self) {
+        self.handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.token);
+    }
+}
 
 /// Client for communicating with an MCP server.
+///
+/// If the connection drops during `list_tools` or `call_tool`, the client
+/// reconnects according to the config's `reconnect` policy, repeats the
+/// initialize handshake, and sends the request again. A tool call that was
+/// cut off mid-flight may therefore run twice.
+///
+/// Server notifications are handled once [`listen`](Self::listen) is called.
 pub struct McpClient {
     config: McpServerConfig,
-    transport: Arc<dyn Transport>,
-    capabilities: McpServerCapabilities,
+    /// Shared with the notification listener, which holds it weakly.
+    transport: Arc<Connection>,
+    connector: Option<Connector>,
+    on_reconnect: Option<ReconnectCallback>,
+    /// Held while reconnecting so concurrent failures reconnect only once.
+    reconnecting: tokio::sync::Mutex<()>,
+    /// Signalled after each reconnect so the listener moves to the new transport.
+    reconnected: watch::Sender<()>,
+    health: Mutex<McpHealth>,
+    lists: Arc<RwLock<McpLists>>,
+    observers: Arc<RwLock<Vec<EventObserver>>>,
+    progress: Arc<ProgressHandlers>,
+    next_progress_token: AtomicU64,
+    listening: AtomicBool,
+    /// From the last handshake, refreshed on reconnect.
+    capabilities: RwLock<McpServerCapabilities>,
 }
 
 impl McpClient {
     /// Connect to an MCP server.
+    ///
+    /// Variables in a stdio server's args and env are expanded first, per
+    /// the config's [`EnvExpansion`](super::EnvExpansion).
     pub async fn connect(config: McpServerConfig) -> Result<Self, McpError> {
-        let transport: Arc<dyn Transport> = match &config.transport {
-            McpTransport::Stdio { command, args, env } => {
-                Arc::new(StdioTransport::connect(command, args, env).await?)
-            }
-            McpTransport::Sse { url } => Arc::new(SseTransport::connect(url).await?),
-            McpTransport::Http { url } => Arc::new(HttpTransport::connect(url).await?),
-        };
-
-        Ok(Self {
-            config,
-            transport,
-            capabilities: McpServerCapabilities::default(),
-        })
+        let spec = config.env_expansion.apply(config.transport.clone())?;
+        let transport = open_transport(spec.clone()).await?;
+        Ok(Self::from_transport(config, transport)
+            .with_reconnector(move || open_transport(spec.clone())))
     }
 
     /// Create an MCP client with a custom transport.
     /// Useful for testing or custom transport implementations.
+    ///
+    /// The client can't reconnect unless given a way to with
+    /// [`with_reconnector`](Self::with_reconnector).
     pub fn from_transport(config: McpServerConfig, transport: Arc<dyn Transport>) -> Self {
         Self {
             config,
-            transport,
-            capabilities: McpServerCapabilities::default(),
+            transport: Arc::new(RwLock::new((transport, 0))),
+            connector: None,
+            on_reconnect: None,
+            reconnecting: tokio::sync::Mutex::new(()),
+            reconnected: watch::Sender::new(()),
+            health: Mutex::new(McpHealth::Connected),
+            lists: Arc::default(),
+            observers: Arc::default(),
+            progress: Arc::default(),
+            next_progress_token: AtomicU64::new(1),
+            listening: AtomicBool::new(false),
+            capabilities: RwLock::default(),
         }
     }
 
+    /// Open replacement transports with `connect` when reconnecting.
+    ///
+    /// `connect` sets this up for the built-in transports.
+    pub fn with_reconnector<F, Fut>(mut self, connect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn Transport>, McpError>> + Send + 'static,
+    {
+        self.connector = Some(Arc::new(move || Box::pin(connect())));
+        self
+    }
+
+    /// Call `callback` with the server name and attempt number each time the
+    /// client reconnects.
+    pub fn with_reconnect_callback(
+        mut self,
+        callback: impl Fn(&str, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_reconnect = Some(Arc::new(callback));
+        self
+    }
+
     /// Get the server name.
     pub fn name(&self) -> &str {
         &self.config.name
     }
 
-    /// Get the server capabilities (available after initialize).
-    pub fn capabilities(&self) -> &McpServerCapabilities {
-        &self.capabilities
+    /// Get the server capabilities (available after initialize). A
+    /// reconnect replaces them with what the restarted server reports.
+    pub fn capabilities(&self) -> McpServerCapabilities {
+        self.capabilities
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set_capabilities(&self, capabilities: McpServerCapabilities) {
+        *self.capabilities.write().unwrap_or_else(|e| e.into_inner()) = capabilities;
+    }
+
+    /// The connection state as of the last request.
+    pub fn health(&self) -> McpHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The tools as last fetched by `list_tools` or after a change
+    /// notification.
+    pub fn cached_tools(&self) -> Option<Vec<McpToolInfo>> {
+        self.lists().tools.clone()
+    }
+
+    /// The resources as last fetched in full: by `list_resources` without a
+    /// cursor, when the server returned a single page, or after a change
+    /// notification.
+    pub fn cached_resources(&self) -> Option<Vec<McpResourceInfo>> {
+        self.lists().resources.clone()
+    }
+
+    /// The prompts as last fetched in full, like
+    /// [`cached_resources`](Self::cached_resources).
+    pub fn cached_prompts(&self) -> Option<Vec<McpPromptInfo>> {
+        self.lists().prompts.clone()
+    }
+
+    fn lists(&self) -> std::sync::RwLockReadGuard<'_, McpLists> {
+        self.lists.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update_lists(&self, event: &McpServerEvent) {
+        self.lists
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .apply(event);
+    }
+
+    /// Call `observer` with each change the server announces. Changes are
+    /// only seen once the client is [`listen`](Self::listen)ing.
+    pub fn on_event(&self, observer: impl Fn(&McpServerEvent) + Send + Sync + 'static) {
+        self.observers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::new(observer));
+    }
+
+    /// Handle server notifications in the background until the client is
+    /// dropped, following the client across reconnects.
+    ///
+    /// When the server announces that its tools, resources or prompts
+    /// changed, the list is fetched again, cached, and passed to the
+    /// observers added with [`on_event`](Self::on_event). Progress updates go
+    /// to the [`call_tool_with_progress`](Self::call_tool_with_progress) call
+    /// they belong to. Transports that don't deliver notifications, like
+    /// HTTP, never announce changes.
+    ///
+    /// Does nothing if already listening. Must be called within a Tokio
+    /// runtime.
+    pub fn listen(&self) {
+        if self.listening.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let connection = Arc::downgrade(&self.transport);
+        let lists = self.lists.clone();
+        let observers = self.observers.clone();
+        let progress = self.progress.clone();
+        let mut reconnected = self.reconnected.subscribe();
+        let server = self.config.name.clone();
+
+        tokio::spawn(async move {
+            let current = |connection: &std::sync::Weak<Connection>| {
+                connection
+                    .upgrade()
+                    .map(|c| c.read().unwrap_or_else(|e| e.into_inner()).0.clone())
+            };
+
+            loop {
+                let Some(transport) = current(&connection) else {
+                    return;
+                };
+                let notifications = transport.take_notifications();
+                drop(transport);
+
+                if let Some(mut notifications) = notifications {
+                    while let Some(notification) = notifications.recv().await {
+                        if notification.method == "notifications/progress" {
+                            report_progress(&progress, notification.params);
+                            continue;
+                        }
+                        let Some(transport) = current(&connection) else {
+                            return;
+                        };
+                        let event = match server_event(transport.as_ref(), notification).await {
+                            Ok(Some(event)) => event,
+                            Ok(None) => continue,
+                            Err(e) => {
+                                eprintln!(
+                                    "Warning: failed to refresh after a notification from MCP server '{}': {}",
+                                    server, e
+                                );
+                                continue;
+                            }
+                        };
+                        lists
+                            .write()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .apply(&event);
+                        let observers = observers.read().unwrap_or_else(|e| e.into_inner()).clone();
+                        for observer in observers {
+                            observer(&event);
+                        }
+                    }
+                }
+
+                // The connection is gone; wait for a reconnect, or for the
+                // client to be dropped
+                if reconnected.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    fn set_health(&self, health: McpHealth) {
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) = health;
+    }
+
+    fn transport(&self) -> (Arc<dyn Transport>, u64) {
+        self.transport
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
     /// Send a request and wait for response.
@@ -64,29 +323,82 @@ impl McpClient {
         method: &str,
         params: Option<serde_json::Value>,
     ) -> Result<serde_json::Value, McpError> {
-        let request = McpRequest::new(method, params);
-        let response = self.transport.send(request).await?;
-
-        if let Some(error) = response.error {
-            return Err(McpError::Rpc {
-                code: error.code,
-                message: error.message,
-            });
-        }
-
-        response
-            .result
-            .ok_or_else(|| McpError::Protocol("No result in response".into()))
+        send_request(self.transport().0.as_ref(), method, params).await
     }
 
-    /// Send a notification (no response expected).
-    async fn notify(
+    /// Send a request, reconnecting and sending it again if the connection
+    /// has dropped.
+    async fn request_reconnecting(
         &self,
         method: &str,
         params: Option<serde_json::Value>,
-    ) -> Result<(), McpError> {
-        let notification = McpNotification::new(method, params);
-        self.transport.notify(notification).await
+    ) -> Result<serde_json::Value, McpError> {
+        let (transport, generation) = self.transport();
+        match send_request(transport.as_ref(), method, params.clone()).await {
+            Err(e) if is_disconnect(&e) => {
+                self.reconnect(generation, e).await?;
+                self.request(method, params).await
+            }
+            result => result,
+        }
+    }
+
+    /// Replace transport `generation`, which failed with `cause`, backing
+    /// off between attempts.
+    async fn reconnect(&self, generation: u64, cause: McpError) -> Result<(), McpError> {
+        let policy = &self.config.reconnect;
+        let Some(connector) = self.connector.as_ref().filter(|_| policy.max_retries > 0) else {
+            self.set_health(McpHealth::Disconnected {
+                error: cause.to_string(),
+            });
+            return Err(cause);
+        };
+
+        let _guard = self.reconnecting.lock().await;
+        if self.transport().1 != generation {
+            // Another request reconnected while this one waited
+            return Ok(());
+        }
+
+        let mut error = cause;
+        for attempt in 1..=policy.max_retries {
+            self.set_health(McpHealth::Reconnecting { attempt });
+            tokio::time::sleep(policy.delay_for(attempt - 1, None)).await;
+
+            let transport = match connector().await {
+                Ok(transport) => transport,
+                Err(e) => {
+                    error = e;
+                    continue;
+                }
+            };
+            let init_result = match handshake(transport.as_ref()).await {
+                Ok(init_result) => init_result,
+                Err(e) => {
+                    let _ = transport.shutdown().await;
+                    error = e;
+                    continue;
+                }
+            };
+            self.set_capabilities(init_result.capabilities);
+
+            let (old, _) = std::mem::replace(
+                &mut *self.transport.write().unwrap_or_else(|e| e.into_inner()),
+                (transport, generation + 1),
+            );
+            let _ = old.shutdown().await;
+            self.reconnected.send_replace(());
+            self.set_health(McpHealth::Connected);
+            if let Some(callback) = &self.on_reconnect {
+                callback(&self.config.name, attempt);
+            }
+            return Ok(());
+        }
+
+        self.set_health(McpHealth::Disconnected {
+            error: error.to_string(),
+        });
+        Err(error)
     }
 
     // ========================================================================
@@ -95,35 +407,17 @@ impl McpClient {
 
     /// Initialize the MCP connection.
     pub async fn initialize(&mut self) -> Result<McpInitializeResult, McpError> {
-        let params = serde_json::json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": {
-                "roots": {
-                    "listChanged": true
-                },
-                "sampling": {}
-            },
-            "clientInfo": {
-                "name": "mux-rs",
-                "version": env!("CARGO_PKG_VERSION")
-            }
-        });
-
-        let result = self.request("initialize", Some(params)).await?;
-        let init_result: McpInitializeResult = serde_json::from_value(result)?;
+        let init_result = handshake(self.transport().0.as_ref()).await?;
 
         // Store capabilities for later use
-        self.capabilities = init_result.capabilities.clone();
-
-        // Send initialized notification
-        self.notify("notifications/initialized", None).await?;
+        self.set_capabilities(init_result.capabilities.clone());
 
         Ok(init_result)
     }
 
     /// Shutdown the server connection gracefully.
     pub async fn shutdown(&self) -> Result<(), McpError> {
-        self.transport.shutdown().await
+        self.transport().0.shutdown().await
     }
 
     // ========================================================================
@@ -132,8 +426,9 @@ impl McpClient {
 
     /// List available tools from the server.
     pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>, McpError> {
-        let result = self.request("tools/list", None).await?;
+        let result = self.request_reconnecting("tools/list", None).await?;
         let tools: Vec<McpToolInfo> = serde_json::from_value(result["tools"].clone())?;
+        self.update_lists(&McpServerEvent::ToolsChanged(tools.clone()));
         Ok(tools)
     }
 
@@ -148,10 +443,88 @@ impl McpClient {
             "arguments": arguments
         });
 
-        let result = self.request("tools/call", Some(params)).await?;
+        let result = self
+            .request_reconnecting("tools/call", Some(params))
+            .await
+            .map_err(|e| tool_call_error(name, e))?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Call a tool, passing each progress update the server sends to
+    /// `on_progress`, and return the final result.
+    ///
+    /// The request carries a progress token in `_meta`, and the client starts
+    /// [`listen`](Self::listen)ing if it isn't already. Servers that don't
+    /// report progress, and transports without notifications like HTTP,
+    /// simply never call `on_progress`.
+    pub async fn call_tool_with_progress(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+        on_progress: impl Fn(&McpProgress) + Send + Sync + 'static,
+    ) -> Result<McpToolResult, McpError> {
+        let token = self.next_progress_token.fetch_add(1, Ordering::Relaxed);
+        self.progress
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(token, Arc::new(on_progress));
+        let _registration = ProgressRegistration {
+            handlers: &self.progress,
+            token,
+        };
+        self.listen();
+
+        let params = serde_json::json!({
+            "name": name,
+            "arguments": arguments,
+            "_meta": { "progressToken": token }
+        });
+
+        let result = self
+            .request_reconnecting("tools/call", Some(params))
+            .await
+            .map_err(|e| tool_call_error(name, e))?;
         Ok(serde_json::from_value(result)?)
     }
 
+    /// Call a tool and validate its `structuredContent` against the tool's `outputSchema`.
+    ///
+    /// Tools without an output schema, and error results, are returned as-is.
+    /// A tool that declares a schema but omits structured content, or returns
+    /// content that does not conform, fails with `McpError::OutputSchema`.
+    pub async fn call_tool_validated(
+        &self,
+        tool: &McpToolInfo,
+        arguments: serde_json::Value,
+    ) -> Result<McpToolResult, McpError> {
+        let result = self.call_tool(&tool.name, arguments).await?;
+
+        let Some(schema) = &tool.output_schema else {
+            return Ok(result);
+        };
+        if result.is_error {
+            return Ok(result);
+        }
+
+        let structured =
+            result
+                .structured_content
+                .as_ref()
+                .ok_or_else(|| McpError::OutputSchema {
+                    tool: tool.name.clone(),
+                    violation: SchemaViolation {
+                        path: "$".into(),
+                        message: "missing structuredContent".into(),
+                    },
+                })?;
+        validate_schema(schema, structured).map_err(|violation| McpError::OutputSchema {
+            tool: tool.name.clone(),
+            violation,
+        })?;
+
+        Ok(result)
+    }
+
     // ========================================================================
     // Resources
     // ========================================================================
@@ -163,7 +536,11 @@ impl McpClient {
     ) -> Result<McpResourcesListResult, McpError> {
         let params = cursor.map(|c| serde_json::json!({ "cursor": c }));
         let result = self.request("resources/list", params).await?;
-        Ok(serde_json::from_value(result)?)
+        let result: McpResourcesListResult = serde_json::from_value(result)?;
+        if cursor.is_none() && result.next_cursor.is_none() {
+            self.update_lists(&McpServerEvent::ResourcesChanged(result.resources.clone()));
+        }
+        Ok(result)
     }
 
     /// Read a resource by URI.
@@ -209,7 +586,11 @@ impl McpClient {
     ) -> Result<McpPromptsListResult, McpError> {
         let params = cursor.map(|c| serde_json::json!({ "cursor": c }));
         let result = self.request("prompts/list", params).await?;
-        Ok(serde_json::from_value(result)?)
+        let result: McpPromptsListResult = serde_json::from_value(result)?;
+        if cursor.is_none() && result.next_cursor.is_none() {
+            self.update_lists(&McpServerEvent::PromptsChanged(result.prompts.clone()));
+        }
+        Ok(result)
     }
 
     /// Get a prompt by name with arguments.
@@ -297,42 +678,601 @@ impl McpClient {
     // Ping
     // ========================================================================
 
-    /// Ping the server to check if it's alive.
-    pub async fn ping(&self) -> Result<(), McpError> {
-        self.request("ping", None).await?;
-        Ok(())
+    /// Ping the server to check if it's alive, returning the round-trip
+    /// latency. A server that doesn't answer in time fails with
+    /// [`McpError::Timeout`].
+    ///
+    /// The result updates [`health`](Self::health): a failed ping marks the
+    /// server disconnected, and an answered one connected.
+    pub async fn ping(&self) -> Result<Duration, McpError> {
+        let result = self.transport().0.ping().await;
+        match &result {
+            Ok(_) => self.set_health(McpHealth::Connected),
+            Err(e) => self.set_health(McpHealth::Disconnected {
+                error: e.to_string(),
+            }),
+        }
+        result
     }
 }
 
+/// Open a transport for `spec`.
+async fn open_transport(spec: McpTransport) -> Result<Arc<dyn Transport>, McpError> {
+    Ok(match spec {
+        McpTransport::Stdio { command, args, env } => {
+            Arc::new(StdioTransport::connect(&command, &args, &env).await?)
+        }
+        McpTransport::Sse { url, headers } => {
+            Arc::new(SseTransport::connect_with_headers(&url, &headers).await?)
+        }
+        McpTransport::Http { url, headers } => {
+            Arc::new(HttpTransport::connect_with_headers(&url, &headers).await?)
+        }
+    })
+}
+
+/// Send a request over `transport` and wait for its result.
+async fn send_request(
+    transport: &dyn Transport,
+    method: &str,
+    params: Option<serde_json::Value>,
+) -> Result<serde_json::Value, McpError> {
+    let request = McpRequest::new(method, params);
+    let response = transport.send(request).await?;
+
+    if let Some(error) = response.error {
+        return Err(error.into());
+    }
+
+    response
+        .result
+        .ok_or_else(|| McpError::Protocol("No result in response".into()))
+}
+
+/// Run the initialize handshake over `transport`.
+async fn handshake(transport: &dyn Transport) -> Result<McpInitializeResult, McpError> {
+    let params = serde_json::json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {
+            "roots": {
+                "listChanged": true
+            },
+            "sampling": {}
+        },
+        "clientInfo": {
+            "name": "mux-rs",
+            "version": env!("CARGO_PKG_VERSION")
+        }
+    });
+
+    let result = send_request(transport, "initialize", Some(params)).await?;
+    let init_result: McpInitializeResult = serde_json::from_value(result)?;
+
+    // Send initialized notification
+    transport
+        .notify(McpNotification::new("notifications/initialized", None))
+        .await?;
+
+    Ok(init_result)
+}
+
+/// The event a server notification announces, with any changed list
+/// fetched again. `None` for notifications that don't announce a change.
+async fn server_event(
+    transport: &dyn Transport,
+    notification: McpNotification,
+) -> Result<Option<McpServerEvent>, McpError> {
+    let event = match notification.method.as_str() {
+        "notifications/tools/list_changed" => {
+            McpServerEvent::ToolsChanged(list_all(transport, "tools/list", "tools").await?)
+        }
+        "notifications/resources/list_changed" => McpServerEvent::ResourcesChanged(
+            list_all(transport, "resources/list", "resources").await?,
+        ),
+        "notifications/prompts/list_changed" => {
+            McpServerEvent::PromptsChanged(list_all(transport, "prompts/list", "prompts").await?)
+        }
+        "notifications/resources/updated" => {
+            let Some(uri) = notification.params.as_ref().and_then(|p| p["uri"].as_str()) else {
+                return Ok(None);
+            };
+            McpServerEvent::ResourceUpdated {
+                uri: uri.to_string(),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+/// Pass a `notifications/progress` update to the callback waiting on its
+/// token. Updates for unknown tokens, such as those arriving after their
+/// call finished, are dropped.
+fn report_progress(handlers: &ProgressHandlers, params: Option<serde_json::Value>) {
+    let Some(Ok(progress)) = params.map(serde_json::from_value::<McpProgress>) else {
+        return;
+    };
+    let Some(token) = progress.progress_token.as_u64() else {
+        return;
+    };
+    let callback = handlers
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&token)
+        .cloned();
+    if let Some(callback) = callback {
+        callback(&progress);
+    }
+}
+
+/// Fetch every page of the list `method` returns under `key`.
+async fn list_all<T: DeserializeOwned>(
+    transport: &dyn Transport,
+    method: &str,
+    key: &str,
+) -> Result<Vec<T>, McpError> {
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+    for _ in 0..MAX_LIST_PAGES {
+        let params = cursor.map(|c| serde_json::json!({ "cursor": c }));
+        let mut result = send_request(transport, method, params).await?;
+        let page = result
+            .get_mut(key)
+            .map(serde_json::Value::take)
+            .unwrap_or_default();
+        items.extend(serde_json::from_value::<Vec<T>>(page)?);
+        cursor = result["nextCursor"].as_str().map(str::to_string);
+        if cursor.is_none() {
+            return Ok(items);
+        }
+    }
+    Err(McpError::Protocol(format!(
+        "{} returned more than {} pages",
+        method, MAX_LIST_PAGES
+    )))
+}
+
+/// `error` from calling tool `name`, as [`McpError::ToolNotFound`] if the
+/// server says it has no such tool. Servers report that as invalid params
+/// or, less often, method not found, so the message decides: it must say
+/// "unknown tool", or name the tool and say "not found". A tool that fails
+/// with something like "File not found" keeps its error.
+fn tool_call_error(name: &str, error: McpError) -> McpError {
+    match error {
+        McpError::Rpc { code, message }
+            if matches!(
+                code,
+                McpRpcError::INVALID_PARAMS | McpRpcError::METHOD_NOT_FOUND
+            ) && {
+                let lower = message.to_lowercase();
+                lower.contains("unknown tool")
+                    || (lower.contains("not found") && lower.contains(&name.to_lowercase()))
+            } =>
+        {
+            McpError::ToolNotFound {
+                name: name.to_string(),
+                message,
+            }
+        }
+        _ => error,
+    }
+}
+
+/// Whether `error` means the server is gone rather than that it refused
+/// the request.
+fn is_disconnect(error: &McpError) -> bool {
+    matches!(error, McpError::Connection(_) | McpError::Io(_))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::RetryPolicy;
 
     #[tokio::test]
     async fn test_connect_nonexistent_stdio() {
-        let config = McpServerConfig {
-            name: "test".into(),
-            transport: McpTransport::Stdio {
+        let config = McpServerConfig::new(
+            "test",
+            McpTransport::Stdio {
                 command: "/nonexistent/binary".into(),
                 args: vec![],
                 env: HashMap::new(),
             },
-        };
+        );
 
         let result = McpClient::connect(config).await;
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_connect_strict_env_expansion_fails_on_unset_variable() {
+        let config = McpServerConfig::new(
+            "test",
+            McpTransport::Stdio {
+                command: "/nonexistent/binary".into(),
+                args: vec!["--token=${MUX_TEST_UNSET_TOKEN}".into()],
+                env: HashMap::new(),
+            },
+        )
+        .with_env_expansion(crate::mcp::EnvExpansion::strict());
+
+        let err = McpClient::connect(config).await.err().unwrap();
+        assert!(matches!(err, McpError::UnsetVariable(name) if name == "MUX_TEST_UNSET_TOKEN"));
+    }
+
     #[tokio::test]
     async fn test_connect_invalid_sse() {
-        let config = McpServerConfig {
-            name: "test".into(),
-            transport: McpTransport::Sse {
+        let config = McpServerConfig::new(
+            "test",
+            McpTransport::Sse {
                 url: "http://localhost:99999/nonexistent".into(),
+                headers: HashMap::new(),
             },
-        };
+        );
 
         let result = McpClient::connect(config).await;
         assert!(result.is_err());
     }
+
+    fn weather_tool() -> McpToolInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": "get_weather",
+            "inputSchema": {"type": "object"},
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "temperature": {"type": "number"},
+                    "conditions": {"type": "string"}
+                },
+                "required": ["temperature", "conditions"]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn client_returning(result: serde_json::Value) -> McpClient {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let transport = MockTransport::new().respond("tools/call", result);
+        McpClient::from_transport(mock_config(), Arc::new(transport))
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_validated_accepts_matching_output() {
+        let client = client_returning(serde_json::json!({
+            "content": [{"type": "text", "text": "{\"temperature\": 22.5, \"conditions\": \"sunny\"}"}],
+            "structuredContent": {"temperature": 22.5, "conditions": "sunny"}
+        }));
+
+        let result = client
+            .call_tool_validated(&weather_tool(), serde_json::json!({"city": "Paris"}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.structured_content.unwrap()["conditions"],
+            serde_json::json!("sunny")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_validated_rejects_violating_output() {
+        let client = client_returning(serde_json::json!({
+            "content": [],
+            "structuredContent": {"temperature": "warm", "conditions": "sunny"}
+        }));
+
+        match client
+            .call_tool_validated(&weather_tool(), serde_json::json!({}))
+            .await
+        {
+            Err(McpError::OutputSchema { tool, violation }) => {
+                assert_eq!(tool, "get_weather");
+                assert_eq!(violation.path, "$.temperature");
+            }
+            other => panic!("Expected OutputSchema error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_validated_requires_structured_content() {
+        let client = client_returning(serde_json::json!({
+            "content": [{"type": "text", "text": "sunny"}]
+        }));
+
+        let err = client
+            .call_tool_validated(&weather_tool(), serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing structuredContent"));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_validated_skips_error_results() {
+        let client = client_returning(serde_json::json!({
+            "content": [{"type": "text", "text": "city not found"}],
+            "isError": true
+        }));
+
+        let result = client
+            .call_tool_validated(&weather_tool(), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(result.structured_content.is_none());
+    }
+
+    fn reconnect_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_reports_latency_or_timeout() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let transport = MockTransport::new().respond("ping", serde_json::json!({}));
+        let client = McpClient::from_transport(mock_config(), Arc::new(transport));
+        assert_eq!(client.ping().await.unwrap(), Duration::ZERO);
+
+        let (transport, _release) = MockTransport::new()
+            .respond("ping", serde_json::json!({}))
+            .hold("ping");
+        let client = McpClient::from_transport(mock_config(), Arc::new(transport));
+        let err = client.ping().await.unwrap_err();
+        assert!(matches!(err, McpError::Timeout(_)));
+        assert_eq!(
+            client.health(),
+            McpHealth::Disconnected {
+                error: err.to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors_are_split_by_code() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let transport = MockTransport::new()
+            .respond_error("tools/call", -32602, "Unknown tool: get_weather")
+            .respond_error("resources/read", -32002, "Resource not found")
+            .respond_error(
+                "prompts/get",
+                McpRpcError::INVALID_PARAMS,
+                "Missing argument",
+            );
+        let client = McpClient::from_transport(mock_config(), Arc::new(transport));
+
+        let err = client
+            .call_tool("get_weather", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, McpError::ToolNotFound { name, .. } if name == "get_weather"));
+
+        let err = client.read_resource("file:///missing").await.unwrap_err();
+        assert!(matches!(err, McpError::Server { code: -32002, .. }));
+
+        let err = client.get_prompt("review", None).await.unwrap_err();
+        assert!(matches!(err, McpError::Rpc { code: -32602, .. }));
+        // A tool that exists but can't find its input keeps its error
+        let transport = MockTransport::new().respond_error(
+            "tools/call",
+            -32602,
+            "File not found: /tmp/missing.txt",
+        );
+        let client = McpClient::from_transport(mock_config(), Arc::new(transport));
+        let err = client
+            .call_tool("read_file", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::Rpc { code: -32602, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_reconnects_after_disconnect() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let restarted = Arc::new(
+            MockTransport::new()
+                .respond(
+                    "initialize",
+                    serde_json::json!({
+                        "protocolVersion": "2024-11-05",
+                        "capabilities": {"tools": {"listChanged": true}}
+                    }),
+                )
+                .respond(
+                    "tools/call",
+                    serde_json::json!({"content": [{"type": "text", "text": "ok"}]}),
+                ),
+        );
+        let opened = Arc::new(AtomicU32::new(0));
+        let reconnects = Arc::new(Mutex::new(Vec::new()));
+
+        let client = McpClient::from_transport(
+            mock_config().with_reconnect(reconnect_policy(3)),
+            Arc::new(MockTransport::new().closed()),
+        )
+        .with_reconnector({
+            let restarted = restarted.clone();
+            let opened = opened.clone();
+            move || {
+                let restarted = restarted.clone();
+                // The server is still restarting on the first attempt
+                let first = opened.fetch_add(1, Ordering::SeqCst) == 0;
+                async move {
+                    if first {
+                        Err(McpError::Connection("connection refused".into()))
+                    } else {
+                        Ok(restarted as Arc<dyn Transport>)
+                    }
+                }
+            }
+        })
+        .with_reconnect_callback({
+            let reconnects = reconnects.clone();
+            move |name, attempt| reconnects.lock().unwrap().push((name.to_string(), attempt))
+        });
+
+        let result = client
+            .call_tool("echo", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert_eq!(client.health(), McpHealth::Connected);
+        assert_eq!(*reconnects.lock().unwrap(), vec![("mock".to_string(), 2)]);
+        // The restarted server's capabilities replace the old ones
+        assert!(client.capabilities().tools.is_some());
+
+        let methods: Vec<String> = restarted.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["initialize", "tools/call"]);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_without_reconnect_budget_fails() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let client =
+            McpClient::from_transport(mock_config(), Arc::new(MockTransport::new().closed()))
+                .with_reconnector(|| async {
+                    Ok(Arc::new(MockTransport::new()) as Arc<dyn Transport>)
+                });
+
+        let err = client.list_tools().await.unwrap_err();
+        assert!(matches!(err, McpError::Connection(_)));
+        assert!(matches!(client.health(), McpHealth::Disconnected { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up_after_budget() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let client = McpClient::from_transport(
+            mock_config().with_reconnect(reconnect_policy(2)),
+            Arc::new(MockTransport::new().closed()),
+        )
+        .with_reconnector(|| async {
+            Err::<Arc<dyn Transport>, _>(McpError::Connection("connection refused".into()))
+        });
+
+        let err = client.list_tools().await.unwrap_err();
+        assert!(matches!(&err, McpError::Connection(m) if m == "connection refused"));
+        assert_eq!(
+            err.to_string(),
+            McpError::Connection("connection refused".into()).to_string()
+        );
+        assert_eq!(
+            client.health(),
+            McpHealth::Disconnected {
+                error: err.to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_refetches_changed_lists() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let (transport, server) = MockTransport::new()
+            .respond(
+                "tools/list",
+                serde_json::json!({"tools": [{"name": "search", "inputSchema": {"type": "object"}}]}),
+            )
+            .with_notifications();
+        let client = McpClient::from_transport(mock_config(), Arc::new(transport));
+        let (events_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        client.on_event(move |event| {
+            let _ = events_tx.send(event.clone());
+        });
+        client.listen();
+        assert!(client.cached_tools().is_none());
+
+        for (method, params) in [
+            ("notifications/message", None),
+            ("notifications/tools/list_changed", None),
+            (
+                "notifications/resources/updated",
+                Some(serde_json::json!({"uri": "file:///notes.md"})),
+            ),
+        ] {
+            server.send(McpNotification::new(method, params)).unwrap();
+        }
+
+        match events.recv().await.unwrap() {
+            McpServerEvent::ToolsChanged(tools) => assert_eq!(tools[0].name, "search"),
+            other => panic!("Expected ToolsChanged, got {:?}", other),
+        }
+        assert_eq!(client.cached_tools().unwrap()[0].name, "search");
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            McpServerEvent::ResourceUpdated { uri } if uri == "file:///notes.md"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_with_progress_reports_updates() {
+        use crate::mcp::test_transport::{MockTransport, mock_config};
+
+        let (transport, server) = MockTransport::new()
+            .respond(
+                "tools/call",
+                serde_json::json!({"content": [{"type": "text", "text": "built"}]}),
+            )
+            .with_notifications();
+        let (transport, release) = transport.hold("tools/call");
+        let transport = Arc::new(transport);
+        let client = Arc::new(McpClient::from_transport(mock_config(), transport.clone()));
+
+        let (progress_tx, mut progress) = tokio::sync::mpsc::unbounded_channel();
+        let call = tokio::spawn({
+            let client = client.clone();
+            async move {
+                client
+                    .call_tool_with_progress("build", serde_json::json!({}), move |update| {
+                        let _ = progress_tx.send(update.clone());
+                    })
+                    .await
+            }
+        });
+
+        let token = loop {
+            if let Some(request) = transport.requests().pop() {
+                break request.params.unwrap()["_meta"]["progressToken"].clone();
+            }
+            tokio::task::yield_now().await;
+        };
+        for (id, progress) in [(serde_json::json!(999), 10), (token.clone(), 50)] {
+            server
+                .send(McpNotification::new(
+                    "notifications/progress",
+                    Some(serde_json::json!({
+                        "progressToken": id,
+                        "progress": progress,
+                        "total": 100,
+                        "message": "Compiling"
+                    })),
+                ))
+                .unwrap();
+        }
+
+        // Only the update carrying this call's token is reported
+        assert_eq!(
+            progress.recv().await.unwrap(),
+            McpProgress {
+                progress_token: token,
+                progress: 50.0,
+                total: Some(100.0),
+                message: Some("Compiling".into()),
+            }
+        );
+
+        release.notify_one();
+        let result = call.await.unwrap().unwrap();
+        assert!(!result.is_error);
+        assert!(client.progress.read().unwrap().is_empty());
+    }
 }
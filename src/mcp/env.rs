@@ -0,0 +1,177 @@
+// ABOUTME: EnvExpansion - expands ${VAR} and $VAR in stdio server args and env.
+// ABOUTME: Looks up overrides first, then the process environment.
+
+use std::collections::HashMap;
+
+use super::McpTransport;
+use crate::error::McpError;
+
+/// How environment variables in a stdio server's `args` and `env` values
+/// are expanded when [`McpClient::connect`](super::McpClient::connect)
+/// starts it.
+///
+/// `${VAR}` and `$VAR` are replaced with the variable's value and `$$` with
+/// a literal `$`. A variable that is unset is left as written, or fails the
+/// connection with [`McpError::UnsetVariable`] if `strict` is set.
+///
+/// Expansion is on by default, so an arg that names a variable which is set,
+/// like a literal `$HOME`, is rewritten; write it as `$$HOME` to keep it.
+///
+/// ```
+/// use mux::mcp::EnvExpansion;
+///
+/// let expansion = EnvExpansion::default().with_var("TOKEN", "abc");
+/// assert_eq!(expansion.expand("--token=${TOKEN}").unwrap(), "--token=abc");
+/// assert_eq!(expansion.expand("$$TOKEN").unwrap(), "$TOKEN");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EnvExpansion {
+    /// Values looked up before the process environment.
+    pub overrides: HashMap<String, String>,
+    /// Fail on unset variables instead of leaving them as written.
+    pub strict: bool,
+}
+
+impl EnvExpansion {
+    /// Fail on unset variables.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// Expand `name` to `value`, whatever the process environment says.
+    pub fn with_var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.insert(name.into(), value.into());
+        self
+    }
+
+    /// `text` with its variables expanded.
+    pub fn expand(&self, text: &str) -> Result<String, McpError> {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(at) = rest.find('$') {
+            expanded.push_str(&rest[..at]);
+            rest = &rest[at..];
+            let after = &rest[1..];
+            if let Some(escaped) = after.strip_prefix('$') {
+                expanded.push('$');
+                rest = escaped;
+                continue;
+            }
+
+            let (name, written) = if let Some(braced) = after.strip_prefix('{')
+                && let Some(end) = braced.find('}')
+            {
+                (&braced[..end], &rest[..end + 3])
+            } else {
+                let len = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..len], &rest[..len + 1])
+            };
+
+            if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                // Not a variable, e.g. a lone `$` or `$1`
+                expanded.push('$');
+                rest = after;
+                continue;
+            }
+            match self.lookup(name) {
+                Some(value) => expanded.push_str(&value),
+                None if self.strict => return Err(McpError::UnsetVariable(name.to_string())),
+                None => expanded.push_str(written),
+            }
+            rest = &rest[written.len()..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// `transport` with a stdio server's args and env values expanded.
+    /// Other transports are returned unchanged.
+    pub(crate) fn apply(&self, transport: McpTransport) -> Result<McpTransport, McpError> {
+        let McpTransport::Stdio { command, args, env } = transport else {
+            return Ok(transport);
+        };
+        Ok(McpTransport::Stdio {
+            command,
+            args: args
+                .iter()
+                .map(|arg| self.expand(arg))
+                .collect::<Result<_, _>>()?,
+            env: env
+                .into_iter()
+                .map(|(name, value)| Ok((name, self.expand(&value)?)))
+                .collect::<Result<_, McpError>>()?,
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.overrides
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expands_braced_and_bare_variables() {
+        let expansion = EnvExpansion::default()
+            .with_var("TOKEN", "abc")
+            .with_var("DIR", "/srv");
+
+        assert_eq!(
+            expansion.expand("${DIR}/data:$DIR-x:${TOKEN}").unwrap(),
+            "/srv/data:/srv-x:abc"
+        );
+        // Overrides win, and the process environment fills in the rest
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(expansion.expand("$PATH").unwrap(), path);
+        assert_eq!(
+            expansion.with_var("PATH", "/bin").expand("$PATH").unwrap(),
+            "/bin"
+        );
+    }
+
+    #[test]
+    fn test_unset_variables_follow_strict_flag() {
+        let text = "--key=${MUX_TEST_UNSET_VAR} $MUX_TEST_UNSET_VAR";
+
+        assert_eq!(EnvExpansion::default().expand(text).unwrap(), text);
+        let err = EnvExpansion::strict().expand(text).unwrap_err();
+        assert!(matches!(err, McpError::UnsetVariable(name) if name == "MUX_TEST_UNSET_VAR"));
+    }
+
+    #[test]
+    fn test_escapes_and_non_variables_are_literal() {
+        let expansion = EnvExpansion::strict().with_var("HOME", "/home/me");
+
+        assert_eq!(expansion.expand("$$HOME").unwrap(), "$HOME");
+        assert_eq!(expansion.expand("cost: $5 or $").unwrap(), "cost: $5 or $");
+        assert_eq!(expansion.expand("${HOME").unwrap(), "${HOME");
+        assert_eq!(expansion.expand("$$$HOME").unwrap(), "$/home/me");
+    }
+
+    #[test]
+    fn test_apply_expands_stdio_args_and_env() {
+        let expansion = EnvExpansion::default().with_var("TOKEN", "abc");
+        let transport = McpTransport::Stdio {
+            command: "server-$TOKEN".into(),
+            args: vec!["--token".into(), "${TOKEN}".into()],
+            env: HashMap::from([("GITHUB_TOKEN".into(), "$TOKEN".into())]),
+        };
+
+        let McpTransport::Stdio { command, args, env } = expansion.apply(transport).unwrap() else {
+            panic!("transport changed");
+        };
+        assert_eq!(command, "server-$TOKEN");
+        assert_eq!(args, ["--token", "abc"]);
+        assert_eq!(env["GITHUB_TOKEN"], "abc");
+    }
+}
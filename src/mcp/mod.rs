@@ -2,14 +2,20 @@
 // ABOUTME: Connects to MCP servers via stdio or SSE and proxies their tools.
 
 mod client;
+mod env;
 mod proxy;
 mod transport;
 mod types;
 
 pub use client::McpClient;
-pub use proxy::McpProxyTool;
+pub use env::EnvExpansion;
+pub use proxy::{
+    DEFAULT_TOOL_SEPARATOR, MCP_ERROR_KIND, McpErrorKind, McpProxyTool, McpToolNaming, ToolPrefix,
+};
 pub use transport::{HttpTransport, SseTransport, StdioTransport, Transport};
 pub use types::*;
 
+#[cfg(test)]
+pub(crate) mod test_transport;
 #[cfg(test)]
 mod types_test;
@@ -4,10 +4,202 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-use super::{McpClient, McpContentBlock, McpToolInfo};
+use super::{McpClient, McpContentBlock, McpToolInfo, McpToolResult};
+use crate::llm::ImageSource;
 use crate::tool::{Tool, ToolResult};
 
+/// Metadata key on failed MCP tool results holding their [`McpErrorKind`].
+pub const MCP_ERROR_KIND: &str = "mcp_error_kind";
+
+/// Why an MCP tool call produced an error result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpErrorKind {
+    /// The tool ran and reported a failure itself (`isError: true`), such as
+    /// "file not found". Its message goes to the model unchanged.
+    Tool,
+    /// The tool could not be run: the server answered with a JSON-RPC error,
+    /// the connection failed, or the response was invalid.
+    Protocol,
+}
+
+impl McpErrorKind {
+    /// The error result the model sees for this kind of failure.
+    ///
+    /// Protocol failures are worded so the model can tell them apart from
+    /// the tool's own errors, e.g. to retry rather than change its input.
+    pub fn tool_result(self, message: impl Into<String>) -> ToolResult {
+        let message = message.into();
+        let content = match self {
+            Self::Tool => message,
+            Self::Protocol => format!("The tool could not be run: {}", message),
+        };
+        ToolResult::error(content).with_metadata(MCP_ERROR_KIND, self)
+    }
+
+    /// The kind recorded on `result`, if it is a classified MCP error.
+    pub fn of(result: &ToolResult) -> Option<Self> {
+        result
+            .metadata
+            .get(MCP_ERROR_KIND)
+            .and_then(|kind| serde_json::from_value(kind.clone()).ok())
+    }
+}
+
+impl From<McpToolResult> for ToolResult {
+    /// Text blocks are joined into the content and images are kept as
+    /// [`ToolResult::images`]. Failed calls are classified as
+    /// [`McpErrorKind::Tool`].
+    fn from(result: McpToolResult) -> Self {
+        let mut texts = Vec::new();
+        let mut images = Vec::new();
+        for block in result.content {
+            match block {
+                McpContentBlock::Text { text } => texts.push(text),
+                McpContentBlock::Image { data, mime_type } => {
+                    images.push(ImageSource::base64(mime_type, data))
+                }
+            }
+        }
+        let content = texts.join("\n");
+
+        // Servers should mirror structured content as text, but not all do
+        let content = match &result.structured_content {
+            Some(structured) if content.is_empty() && images.is_empty() => structured.to_string(),
+            _ => content,
+        };
+
+        let mut tool_result = if result.is_error {
+            McpErrorKind::Tool.tool_result(content)
+        } else {
+            ToolResult::text(content)
+        };
+        tool_result.images = images;
+
+        match result.structured_content {
+            Some(structured) => tool_result.with_metadata("structured_content", structured),
+            None => tool_result,
+        }
+    }
+}
+
+/// Separator [`McpToolNaming`] puts between prefix and tool name by default.
+pub const DEFAULT_TOOL_SEPARATOR: &str = "_";
+
+/// What a merged MCP tool's name is prefixed with.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ToolPrefix {
+    /// Use the tool's own name.
+    None,
+    /// Prefix with the server's configured name.
+    #[default]
+    ServerName,
+    /// Prefix with the given text.
+    Custom(String),
+}
+
+/// How MCP tools are named in a [`Registry`](crate::tool::Registry):
+/// `{prefix}{separator}{tool}`, or just `{tool}` with no prefix.
+///
+/// The default separator is `_`, which every provider accepts in tool
+/// names; some reject `:`.
+///
+/// ```
+/// use mux::mcp::McpToolNaming;
+///
+/// let naming = McpToolNaming::server_name().with_separator("__");
+/// assert_eq!(naming.qualify("github", "create_issue"), "github__create_issue");
+/// assert_eq!(
+///     naming.parse("github", "github__create_issue"),
+///     Some("create_issue")
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpToolNaming {
+    /// What tool names are prefixed with.
+    pub prefix: ToolPrefix,
+    /// What goes between the prefix and the tool name.
+    pub separator: String,
+}
+
+impl Default for McpToolNaming {
+    fn default() -> Self {
+        Self::server_name()
+    }
+}
+
+impl McpToolNaming {
+    /// Keep tool names as the server gives them.
+    pub fn none() -> Self {
+        Self::new(ToolPrefix::None)
+    }
+
+    /// Prefix tool names with the server name.
+    pub fn server_name() -> Self {
+        Self::new(ToolPrefix::ServerName)
+    }
+
+    /// Prefix tool names with `prefix`.
+    pub fn custom(prefix: impl Into<String>) -> Self {
+        Self::new(ToolPrefix::Custom(prefix.into()))
+    }
+
+    fn new(prefix: ToolPrefix) -> Self {
+        Self {
+            prefix,
+            separator: DEFAULT_TOOL_SEPARATOR.to_string(),
+        }
+    }
+
+    /// Put `separator` between the prefix and the tool name.
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// The registry name for `tool` from the server named `server`.
+    pub fn qualify(&self, server: &str, tool: &str) -> String {
+        match &self.prefix {
+            ToolPrefix::None => tool.to_string(),
+            ToolPrefix::ServerName => format!("{}{}{}", server, self.separator, tool),
+            ToolPrefix::Custom(prefix) => format!("{}{}{}", prefix, self.separator, tool),
+        }
+    }
+
+    /// The tool name `name` stands for, undoing [`qualify`](Self::qualify)
+    /// for the server named `server`. Returns `None` if `name` doesn't start
+    /// with the prefix and separator this naming gives that server's tools.
+    pub fn parse<'a>(&self, server: &str, name: &'a str) -> Option<&'a str> {
+        let prefix = match &self.prefix {
+            ToolPrefix::None => return Some(name),
+            ToolPrefix::ServerName => server,
+            ToolPrefix::Custom(prefix) => prefix,
+        };
+        name.strip_prefix(prefix)?
+            .strip_prefix(self.separator.as_str())
+    }
+}
+
+impl From<&str> for McpToolNaming {
+    /// A custom prefix with the default separator.
+    fn from(prefix: &str) -> Self {
+        Self::custom(prefix)
+    }
+}
+
+impl From<Option<&str>> for McpToolNaming {
+    /// `Some(prefix)` is a custom prefix and `None` no prefix, both with
+    /// the default separator.
+    fn from(prefix: Option<&str>) -> Self {
+        match prefix {
+            Some(prefix) => Self::custom(prefix),
+            None => Self::none(),
+        }
+    }
+}
+
 /// A tool that proxies calls to an MCP server.
 pub struct McpProxyTool {
     client: Arc<McpClient>,
@@ -16,12 +208,14 @@ pub struct McpProxyTool {
 }
 
 impl McpProxyTool {
-    /// Create a new proxy tool.
+    /// Create a new proxy tool, named `{prefix}_{tool}` if a prefix is given.
     pub fn new(client: Arc<McpClient>, info: McpToolInfo, prefix: Option<&str>) -> Self {
-        let prefixed_name = match prefix {
-            Some(p) => format!("{}_{}", p, info.name),
-            None => info.name.clone(),
-        };
+        Self::named(client, info, &prefix.into())
+    }
+
+    /// Create a new proxy tool named by `naming`.
+    pub fn named(client: Arc<McpClient>, info: McpToolInfo, naming: &McpToolNaming) -> Self {
+        let prefixed_name = naming.qualify(client.name(), &info.name);
         Self {
             client,
             info,
@@ -45,26 +239,153 @@ impl Tool for McpProxyTool {
     }
 
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
-        let result = self.client.call_tool(&self.info.name, params).await?;
-
-        // Convert MCP result to ToolResult by extracting text from content blocks
-        // Note: Image content is represented as a placeholder since ToolResult is text-only
-        let content = result
-            .content
-            .iter()
-            .map(|c| match c {
-                McpContentBlock::Text { text } => text.clone(),
-                McpContentBlock::Image { mime_type, .. } => {
-                    format!("[Image: {}]", mime_type)
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+        let result = match self.client.call_tool_validated(&self.info, params).await {
+            Ok(result) => result,
+            Err(e) => return Ok(McpErrorKind::Protocol.tool_result(e.to_string())),
+        };
 
-        if result.is_error {
-            Ok(ToolResult::error(content))
-        } else {
-            Ok(ToolResult::text(content))
-        }
+        Ok(ToolResult::from(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    #[tokio::test]
+    async fn test_structured_content_in_metadata() {
+        let transport = MockTransport::new().respond(
+            "tools/call",
+            serde_json::json!({
+                "content": [],
+                "structuredContent": {"count": 3}
+            }),
+        );
+        let transport = Arc::new(transport);
+        let client = Arc::new(McpClient::from_transport(mock_config(), transport.clone()));
+        let info: McpToolInfo = serde_json::from_value(serde_json::json!({
+            "name": "count",
+            "inputSchema": {"type": "object"},
+            "outputSchema": {
+                "type": "object",
+                "properties": {"count": {"type": "integer"}},
+                "required": ["count"]
+            }
+        }))
+        .unwrap();
+
+        let tool = McpProxyTool::new(client, info, Some("srv"));
+        let result = tool.execute(serde_json::json!({})).await.unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(result.content, r#"{"count":3}"#);
+        assert_eq!(
+            result.metadata["structured_content"],
+            serde_json::json!({"count": 3})
+        );
+
+        // The server sees the unprefixed tool name
+        let requests = transport.requests();
+        assert_eq!(requests[0].params.as_ref().unwrap()["name"], "count");
+    }
+
+    fn echo_tool(transport: MockTransport) -> McpProxyTool {
+        let client = Arc::new(McpClient::from_transport(
+            mock_config(),
+            Arc::new(transport),
+        ));
+        let info: McpToolInfo = serde_json::from_value(serde_json::json!({
+            "name": "read",
+            "inputSchema": {"type": "object"}
+        }))
+        .unwrap();
+        McpProxyTool::new(client, info, None)
+    }
+
+    #[tokio::test]
+    async fn test_image_content_is_preserved() {
+        let tool = echo_tool(MockTransport::new().respond(
+            "tools/call",
+            serde_json::json!({
+                "content": [
+                    {"type": "text", "text": "Here is the chart"},
+                    {"type": "image", "data": "iVBOR", "mimeType": "image/png"}
+                ]
+            }),
+        ));
+        let result = tool.execute(serde_json::json!({})).await.unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(result.content, "Here is the chart");
+        assert_eq!(
+            result.images,
+            vec![ImageSource::base64("image/png", "iVBOR")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_reported_error_is_classified() {
+        let tool = echo_tool(MockTransport::new().respond(
+            "tools/call",
+            serde_json::json!({
+                "content": [{"type": "text", "text": "No such file: notes.txt"}],
+                "isError": true
+            }),
+        ));
+        let result = tool.execute(serde_json::json!({})).await.unwrap();
+
+        assert!(result.is_error);
+        assert_eq!(result.content, "No such file: notes.txt");
+        assert_eq!(McpErrorKind::of(&result), Some(McpErrorKind::Tool));
+    }
+
+    #[tokio::test]
+    async fn test_rpc_error_is_classified_as_protocol() {
+        let tool = echo_tool(MockTransport::new().respond_error(
+            "tools/call",
+            -32602,
+            "Unknown tool: read",
+        ));
+        let result = tool.execute(serde_json::json!({})).await.unwrap();
+
+        assert!(result.is_error);
+        assert!(result.content.starts_with("The tool could not be run:"));
+        assert!(result.content.contains("Unknown tool: read"));
+        assert_eq!(McpErrorKind::of(&result), Some(McpErrorKind::Protocol));
+
+        // Successful results carry no error kind
+        let tool = echo_tool(MockTransport::new().respond(
+            "tools/call",
+            serde_json::json!({"content": [{"type": "text", "text": "ok"}]}),
+        ));
+        let result = tool.execute(serde_json::json!({})).await.unwrap();
+        assert_eq!(McpErrorKind::of(&result), None);
+    }
+
+    #[test]
+    fn test_tool_naming_round_trips() {
+        let colon = McpToolNaming::server_name().with_separator(":");
+        assert_eq!(colon.qualify("fs", "read"), "fs:read");
+        assert_eq!(colon.parse("fs", "fs:read:all"), Some("read:all"));
+        assert_eq!(colon.parse("fs", "fs__read"), None);
+        assert_eq!(colon.parse("git", "fs:read"), None);
+
+        // The prefix and separator may themselves contain the separator
+        let custom = McpToolNaming::custom("my_files").with_separator("_");
+        assert_eq!(custom.qualify("fs", "read_all"), "my_files_read_all");
+        assert_eq!(custom.parse("fs", "my_files_read_all"), Some("read_all"));
+        assert_eq!(custom.parse("fs", "my_read_all"), None);
+
+        let none = McpToolNaming::none();
+        assert_eq!(none.qualify("fs", "read_all"), "read_all");
+        assert_eq!(none.parse("fs", "read_all"), Some("read_all"));
+
+        assert_eq!(McpToolNaming::default().qualify("fs", "read"), "fs_read");
+        assert_eq!(
+            McpToolNaming::from(Some("x")).qualify("fs", "read"),
+            "x_read"
+        );
+        assert_eq!(McpToolNaming::from("x").qualify("fs", "read"), "x_read");
     }
 }
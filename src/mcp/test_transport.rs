@@ -0,0 +1,143 @@
+// ABOUTME: In-memory MCP transport that answers requests with canned results.
+// ABOUTME: Lets client and proxy tests run without spawning a server process.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::{Notify, mpsc};
+
+use super::{
+    McpNotification, McpRequest, McpResponse, McpRpcError, McpServerConfig, McpTransport, Transport,
+};
+use crate::error::McpError;
+
+/// Transport that replies to each method with a fixed result.
+#[derive(Default)]
+pub struct MockTransport {
+    results: Mutex<HashMap<String, serde_json::Value>>,
+    errors: HashMap<String, McpRpcError>,
+    requests: Mutex<Vec<McpRequest>>,
+    closed: bool,
+    held: HashMap<String, Arc<Notify>>,
+    notifications: Mutex<Option<mpsc::UnboundedReceiver<McpNotification>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reply to `method` with `result`.
+    pub fn respond(self, method: &str, result: serde_json::Value) -> Self {
+        self.set_response(method, result);
+        self
+    }
+
+    /// Change the reply to `method` after the transport is in use.
+    pub fn set_response(&self, method: &str, result: serde_json::Value) {
+        self.results
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+    }
+
+    /// Deliver the notifications sent on the returned channel as if they
+    /// came from the server.
+    pub fn with_notifications(mut self) -> (Self, mpsc::UnboundedSender<McpNotification>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.notifications = Mutex::new(Some(rx));
+        (self, tx)
+    }
+
+    /// Hold replies to `method` until the returned `Notify` is notified,
+    /// as if the server were still working on them.
+    pub fn hold(mut self, method: &str) -> (Self, Arc<Notify>) {
+        let release = Arc::new(Notify::new());
+        self.held.insert(method.to_string(), release.clone());
+        (self, release)
+    }
+
+    /// Reply to `method` with a JSON-RPC error.
+    pub fn respond_error(mut self, method: &str, code: i32, message: &str) -> Self {
+        self.errors.insert(
+            method.to_string(),
+            McpRpcError {
+                code,
+                message: message.to_string(),
+                data: None,
+            },
+        );
+        self
+    }
+
+    /// Fail every request as if the server had exited.
+    pub fn closed(mut self) -> Self {
+        self.closed = true;
+        self
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<McpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, request: McpRequest) -> Result<McpResponse, McpError> {
+        if self.closed {
+            return Err(McpError::Connection("Server connection closed".into()));
+        }
+        if let Some(error) = self.errors.get(&request.method).cloned() {
+            let id = request.id;
+            self.requests.lock().unwrap().push(request);
+            return Ok(McpResponse {
+                jsonrpc: "2.0".into(),
+                id,
+                result: None,
+                error: Some(error),
+            });
+        }
+        let result = self.results.lock().unwrap().get(&request.method).cloned();
+        let result = result.ok_or_else(|| {
+            McpError::Protocol(format!("no mock response for {}", request.method))
+        })?;
+        let id = request.id;
+        let release = self.held.get(&request.method).cloned();
+        self.requests.lock().unwrap().push(request);
+        if let Some(release) = release {
+            release.notified().await;
+        }
+        Ok(McpResponse {
+            jsonrpc: "2.0".into(),
+            id,
+            result: Some(result),
+            error: None,
+        })
+    }
+
+    async fn notify(&self, _notification: McpNotification) -> Result<(), McpError> {
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<(), McpError> {
+        Ok(())
+    }
+
+    fn take_notifications(&self) -> Option<mpsc::UnboundedReceiver<McpNotification>> {
+        self.notifications.lock().unwrap().take()
+    }
+}
+
+/// A config for clients built on a mock transport.
+pub fn mock_config() -> McpServerConfig {
+    McpServerConfig::new(
+        "mock",
+        McpTransport::Stdio {
+            command: "unused".into(),
+            args: Vec::new(),
+            env: HashMap::new(),
+        },
+    )
+}
@@ -1,11 +1,14 @@
 // ABOUTME: HTTP transport for MCP communication.
 // ABOUTME: Simple request/response over HTTP with optional session management.
 
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
-use super::Transport;
-use crate::error::McpError;
+use super::{REQUEST_TIMEOUT, Transport, header_map};
+use crate::error::{LlmError, McpError};
+use crate::llm::{DEFAULT_MAX_RESPONSE_BYTES, read_body};
 use crate::mcp::{McpNotification, McpRequest, McpResponse};
 
 /// HTTP transport - simple request/response over HTTP.
@@ -14,17 +17,30 @@ use crate::mcp::{McpNotification, McpRequest, McpResponse};
 /// - POST with JSON-RPC request body
 /// - JSON-RPC response in response body
 /// - Optional streaming via chunked transfer encoding
+///
+/// Server notifications aren't received, since there is no stream for the
+/// server to send them on.
 pub struct HttpTransport {
     endpoint_url: String,
     http_client: reqwest::Client,
     session_id: Mutex<Option<String>>,
+    max_response_bytes: usize,
 }
 
 impl HttpTransport {
     /// Connect to an HTTP MCP server.
     pub async fn connect(url: &str) -> Result<Self, McpError> {
+        Self::connect_with_headers(url, &HashMap::new()).await
+    }
+
+    /// Connect to an HTTP MCP server, sending `headers` with every request.
+    pub async fn connect_with_headers(
+        url: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<Self, McpError> {
         let http_client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+            .default_headers(header_map(headers)?)
+            .timeout(REQUEST_TIMEOUT)
             .user_agent(format!("mux-rs/{}", env!("CARGO_PKG_VERSION")))
             .build()
             .map_err(|e| McpError::Connection(format!("Failed to create HTTP client: {}", e)))?;
@@ -37,9 +53,30 @@ impl HttpTransport {
             endpoint_url: url.to_string(),
             http_client,
             session_id: Mutex::new(None),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
         })
     }
 
+    /// Fail responses whose body exceeds `bytes`.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
+    /// Read a response body, enforcing the size limit.
+    async fn read_text(&self, response: reqwest::Response) -> Result<String, McpError> {
+        match read_body(response, self.max_response_bytes).await {
+            Ok(body) => Ok(String::from_utf8_lossy(&body).into_owned()),
+            Err(LlmError::ResponseTooLarge { limit }) => Err(McpError::ResponseTooLarge { limit }),
+            Err(e) => Err(McpError::Protocol(format!(
+                "Failed to read response: {}",
+                e
+            ))),
+        }
+    }
+
     /// Get the endpoint URL.
     #[allow(dead_code)]
     pub fn endpoint_url(&self) -> &str {
@@ -53,6 +90,16 @@ impl HttpTransport {
     }
 }
 
+/// A failed POST as [`McpError::Timeout`] if it ran out of time, else as
+/// a connection failure.
+fn send_error(error: reqwest::Error) -> McpError {
+    if error.is_timeout() {
+        McpError::Timeout(REQUEST_TIMEOUT)
+    } else {
+        McpError::Connection(format!("HTTP request failed: {}", error))
+    }
+}
+
 #[async_trait]
 impl Transport for HttpTransport {
     async fn send(&self, request: McpRequest) -> Result<McpResponse, McpError> {
@@ -70,11 +117,7 @@ impl Transport for HttpTransport {
             req_builder = req_builder.header("Mcp-Session-Id", session_id.clone());
         }
 
-        let response = req_builder
-            .body(json)
-            .send()
-            .await
-            .map_err(|e| McpError::Connection(format!("HTTP request failed: {}", e)))?;
+        let response = req_builder.body(json).send().await.map_err(send_error)?;
 
         // Check for session ID in response (server may establish one)
         if let Some(session_id) = response.headers().get("Mcp-Session-Id") {
@@ -85,7 +128,7 @@ impl Transport for HttpTransport {
 
         let status = response.status();
         if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
+            let body = self.read_text(response).await?;
             return Err(McpError::Protocol(format!(
                 "HTTP {} - {}",
                 status.as_u16(),
@@ -93,10 +136,7 @@ impl Transport for HttpTransport {
             )));
         }
 
-        let body = response
-            .text()
-            .await
-            .map_err(|e| McpError::Protocol(format!("Failed to read response: {}", e)))?;
+        let body = self.read_text(response).await?;
 
         let mcp_response: McpResponse = serde_json::from_str(&body)
             .map_err(|e| McpError::Protocol(format!("Invalid JSON-RPC response: {}", e)))?;
@@ -126,16 +166,12 @@ impl Transport for HttpTransport {
             req_builder = req_builder.header("Mcp-Session-Id", session_id.clone());
         }
 
-        let response = req_builder
-            .body(json)
-            .send()
-            .await
-            .map_err(|e| McpError::Connection(format!("HTTP request failed: {}", e)))?;
+        let response = req_builder.body(json).send().await.map_err(send_error)?;
 
         // Check response status (notifications should still succeed)
         let status = response.status();
         if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
+            let body = self.read_text(response).await?;
             return Err(McpError::Protocol(format!(
                 "HTTP {} on notify - {}",
                 status.as_u16(),
@@ -207,4 +243,56 @@ mod tests {
         transport.shutdown().await.unwrap();
         assert!(transport.session_id.lock().await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_headers_sent_with_requests() {
+        use crate::llm::test_server::serve_once;
+
+        let request = McpRequest::new("ping", None);
+        let body = format!(r#"{{"jsonrpc":"2.0","id":{},"result":{{}}}}"#, request.id);
+        let (url, handle) = serve_once(200, &body).await;
+
+        let headers = HashMap::from([
+            ("Authorization".to_string(), "Bearer secret".to_string()),
+            ("X-Team".to_string(), "infra".to_string()),
+        ]);
+        let transport = HttpTransport::connect_with_headers(&url, &headers)
+            .await
+            .unwrap();
+        transport.send(request).await.unwrap();
+
+        let raw = handle.await.unwrap().remove(0).to_ascii_lowercase();
+        assert!(raw.contains("authorization: bearer secret"));
+        assert!(raw.contains("x-team: infra"));
+    }
+
+    #[tokio::test]
+    async fn test_response_over_size_limit_rejected() {
+        use crate::llm::test_server::serve_once;
+
+        let request = McpRequest::new("tools/list", None);
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","id":{},"result":{{"tools":[],"padding":"{}"}}}}"#,
+            request.id,
+            "x".repeat(2048)
+        );
+        let (url, _handle) = serve_once(200, &body).await;
+
+        let transport = HttpTransport::connect(&url)
+            .await
+            .unwrap()
+            .with_max_response_size(1024);
+        match transport.send(request).await {
+            Err(McpError::ResponseTooLarge { limit }) => assert_eq!(limit, 1024),
+            other => panic!("Expected ResponseTooLarge, got {:?}", other.map(|r| r.id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_header_rejected() {
+        let headers = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        let result =
+            HttpTransport::connect_with_headers("http://localhost:8080/mcp", &headers).await;
+        assert!(matches!(result, Err(McpError::Connection(_))));
+    }
 }
@@ -9,11 +9,19 @@ pub use http::HttpTransport;
 pub use sse::SseTransport;
 pub use stdio::StdioTransport;
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::sync::mpsc;
 
 use super::{McpNotification, McpRequest, McpResponse};
 use crate::error::McpError;
 
+/// How long a transport waits for the response to a request.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Trait for MCP transport implementations.
 #[async_trait]
 pub trait Transport: Send + Sync {
@@ -25,4 +33,44 @@ pub trait Transport: Send + Sync {
 
     /// Shutdown the transport.
     async fn shutdown(&self) -> Result<(), McpError>;
+
+    /// Send a JSON-RPC `ping` and return how long the reply took.
+    ///
+    /// Fails with [`McpError::Timeout`] if no reply comes within the
+    /// request timeout, and with the server's error if it answers with one.
+    async fn ping(&self) -> Result<Duration, McpError> {
+        let started = tokio::time::Instant::now();
+        let response =
+            tokio::time::timeout(REQUEST_TIMEOUT, self.send(McpRequest::new("ping", None)))
+                .await
+                .map_err(|_| McpError::Timeout(REQUEST_TIMEOUT))??;
+        match response.error {
+            Some(error) => Err(error.into()),
+            None => Ok(started.elapsed()),
+        }
+    }
+
+    /// Take the notifications the server sends, such as
+    /// `notifications/tools/list_changed`.
+    ///
+    /// Returns `None` if the transport doesn't receive server notifications,
+    /// or if they were already taken.
+    fn take_notifications(&self) -> Option<mpsc::UnboundedReceiver<McpNotification>> {
+        None
+    }
+}
+
+/// Convert configured headers into a `HeaderMap` for the HTTP client.
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, McpError> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| McpError::Connection(format!("Invalid header name '{}': {}", name, e)))?;
+        let mut value = HeaderValue::from_str(value).map_err(|e| {
+            McpError::Connection(format!("Invalid value for header '{}': {}", name, e))
+        })?;
+        value.set_sensitive(name == reqwest::header::AUTHORIZATION);
+        map.insert(name, value);
+    }
+    Ok(map)
 }
@@ -9,7 +9,7 @@ use futures::StreamExt;
 use tokio::sync::{Mutex, mpsc};
 use tokio::task::JoinHandle;
 
-use super::Transport;
+use super::{REQUEST_TIMEOUT, Transport, header_map};
 use crate::error::McpError;
 use crate::mcp::{McpNotification, McpRequest, McpResponse};
 
@@ -25,18 +25,32 @@ pub struct SseTransport {
     pending: Arc<Mutex<HashMap<u64, mpsc::Sender<McpResponse>>>>,
     sse_handle: Mutex<Option<JoinHandle<()>>>,
     shutdown_tx: Mutex<Option<mpsc::Sender<()>>>,
+    notifications: std::sync::Mutex<Option<mpsc::UnboundedReceiver<McpNotification>>>,
 }
 
 impl SseTransport {
     /// Connect to an SSE MCP server.
     pub async fn connect(url: &str) -> Result<Self, McpError> {
-        let http_client = reqwest::Client::new();
+        Self::connect_with_headers(url, &HashMap::new()).await
+    }
+
+    /// Connect to an SSE MCP server, sending `headers` with every request,
+    /// including the initial event-stream request.
+    pub async fn connect_with_headers(
+        url: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<Self, McpError> {
+        let http_client = reqwest::Client::builder()
+            .default_headers(header_map(headers)?)
+            .build()
+            .map_err(|e| McpError::Connection(format!("Failed to create HTTP client: {}", e)))?;
 
         let pending: Arc<Mutex<HashMap<u64, mpsc::Sender<McpResponse>>>> =
             Arc::new(Mutex::new(HashMap::new()));
 
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         let (messages_tx, mut messages_rx) = mpsc::channel::<String>(1);
+        let (notifications_tx, notifications_rx) = mpsc::unbounded_channel();
 
         // Start SSE connection
         let sse_url = url.to_string();
@@ -86,6 +100,8 @@ impl SseTransport {
                                                 if let Some(tx) = pending.remove(&response.id) {
                                                     let _ = tx.send(response).await;
                                                 }
+                                            } else if let Ok(notification) = serde_json::from_str::<McpNotification>(&event_data) {
+                                                let _ = notifications_tx.send(notification);
                                             }
                                         }
                                         event_type.clear();
@@ -133,6 +149,7 @@ impl SseTransport {
             pending,
             sse_handle: Mutex::new(Some(sse_handle)),
             shutdown_tx: Mutex::new(Some(shutdown_tx)),
+            notifications: std::sync::Mutex::new(Some(notifications_rx)),
         })
     }
 
@@ -178,12 +195,12 @@ impl Transport for SseTransport {
         }
 
         // Wait for response via SSE
-        match tokio::time::timeout(std::time::Duration::from_secs(30), rx.recv()).await {
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx.recv()).await {
             Ok(Some(response)) => Ok(response),
             Ok(None) => Err(McpError::Protocol("No response received".into())),
             Err(_) => {
                 self.pending.lock().await.remove(&id);
-                Err(McpError::Protocol("Request timed out".into()))
+                Err(McpError::Timeout(REQUEST_TIMEOUT))
             }
         }
     }
@@ -222,6 +239,13 @@ impl Transport for SseTransport {
 
         Ok(())
     }
+
+    fn take_notifications(&self) -> Option<mpsc::UnboundedReceiver<McpNotification>> {
+        self.notifications
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
 }
 
 #[cfg(test)]
@@ -243,4 +267,26 @@ mod tests {
         // Should fail - can't parse as URL
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_headers_sent_on_event_stream_connection() {
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let endpoint = RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/event-stream".into())],
+            body: "event: endpoint\ndata: /messages\n\n".into(),
+        };
+        let (url, handle) = serve(vec![endpoint]).await;
+
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer secret".to_string())]);
+        let transport = SseTransport::connect_with_headers(&format!("{}/sse", url), &headers)
+            .await
+            .unwrap();
+
+        let raw = handle.await.unwrap().remove(0).to_ascii_lowercase();
+        assert!(raw.starts_with("get /sse"));
+        assert!(raw.contains("authorization: bearer secret"));
+        transport.shutdown().await.unwrap();
+    }
 }
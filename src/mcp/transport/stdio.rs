@@ -11,7 +11,7 @@ use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, mpsc};
 use tokio::task::JoinHandle;
 
-use super::Transport;
+use super::{REQUEST_TIMEOUT, Transport};
 use crate::error::McpError;
 use crate::mcp::{McpNotification, McpRequest, McpResponse};
 
@@ -21,6 +21,7 @@ pub struct StdioTransport {
     stdin: Mutex<Option<tokio::process::ChildStdin>>,
     pending: Arc<Mutex<HashMap<u64, mpsc::Sender<McpResponse>>>>,
     reader_handle: Mutex<Option<JoinHandle<()>>>,
+    notifications: std::sync::Mutex<Option<mpsc::UnboundedReceiver<McpNotification>>>,
 }
 
 impl StdioTransport {
@@ -54,6 +55,8 @@ impl StdioTransport {
         let pending: Arc<Mutex<HashMap<u64, mpsc::Sender<McpResponse>>>> =
             Arc::new(Mutex::new(HashMap::new()));
 
+        let (notifications_tx, notifications_rx) = mpsc::unbounded_channel();
+
         // Spawn reader task
         let pending_clone = pending.clone();
         let reader_handle = tokio::spawn(async move {
@@ -66,6 +69,10 @@ impl StdioTransport {
                             if let Some(tx) = pending.remove(&response.id) {
                                 let _ = tx.send(response).await;
                             }
+                        } else if let Ok(notification) =
+                            serde_json::from_str::<McpNotification>(&line)
+                        {
+                            let _ = notifications_tx.send(notification);
                         }
                     }
                     Ok(None) => break,
@@ -80,6 +87,7 @@ impl StdioTransport {
             stdin: Mutex::new(Some(stdin)),
             pending,
             reader_handle: Mutex::new(Some(reader_handle)),
+            notifications: std::sync::Mutex::new(Some(notifications_rx)),
         })
     }
 }
@@ -115,12 +123,12 @@ impl Transport for StdioTransport {
         }
 
         // Wait for response with timeout
-        match tokio::time::timeout(std::time::Duration::from_secs(30), rx.recv()).await {
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx.recv()).await {
             Ok(Some(response)) => Ok(response),
             Ok(None) => Err(McpError::Protocol("No response received".into())),
             Err(_) => {
                 self.pending.lock().await.remove(&id);
-                Err(McpError::Protocol("Request timed out".into()))
+                Err(McpError::Timeout(REQUEST_TIMEOUT))
             }
         }
     }
@@ -155,6 +163,13 @@ impl Transport for StdioTransport {
 
         Ok(())
     }
+
+    fn take_notifications(&self) -> Option<mpsc::UnboundedReceiver<McpNotification>> {
+        self.notifications
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
 }
 
 #[cfg(test)]
@@ -191,8 +206,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_connect_with_args() {
-        let result =
-            StdioTransport::connect(echo_command(), &echo_args(), &HashMap::new()).await;
+        let result = StdioTransport::connect(echo_command(), &echo_args(), &HashMap::new()).await;
 
         // Should succeed in spawning
         assert!(result.is_ok());
@@ -204,10 +218,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_shutdown_cleans_up() {
-        let transport =
-            StdioTransport::connect(echo_command(), &echo_args(), &HashMap::new())
-                .await
-                .unwrap();
+        let transport = StdioTransport::connect(echo_command(), &echo_args(), &HashMap::new())
+            .await
+            .unwrap();
 
         // Shutdown should succeed
         let result = transport.shutdown().await;
@@ -6,6 +6,10 @@ use std::sync::atomic::{AtomicU64, Ordering};
 
 use serde::{Deserialize, Serialize};
 
+use super::EnvExpansion;
+use crate::error::McpError;
+use crate::llm::RetryPolicy;
+
 static REQUEST_ID: AtomicU64 = AtomicU64::new(1);
 
 /// A JSON-RPC 2.0 request.
@@ -70,6 +74,44 @@ pub struct McpRpcError {
     pub data: Option<serde_json::Value>,
 }
 
+impl McpRpcError {
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+
+    /// Whether the code is one JSON-RPC reserves for protocol failures,
+    /// rather than one the server defines.
+    pub fn is_protocol_error(&self) -> bool {
+        matches!(
+            self.code,
+            Self::PARSE_ERROR
+                | Self::INVALID_REQUEST
+                | Self::METHOD_NOT_FOUND
+                | Self::INVALID_PARAMS
+                | Self::INTERNAL_ERROR
+        )
+    }
+}
+
+impl From<McpRpcError> for McpError {
+    fn from(error: McpRpcError) -> Self {
+        if error.is_protocol_error() {
+            McpError::Rpc {
+                code: error.code,
+                message: error.message,
+            }
+        } else {
+            McpError::Server {
+                code: error.code,
+                message: error.message,
+                data: error.data,
+            }
+        }
+    }
+}
+
 /// Information about an MCP tool.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpToolInfo {
@@ -78,6 +120,13 @@ pub struct McpToolInfo {
     pub description: String,
     #[serde(rename = "inputSchema")]
     pub input_schema: serde_json::Value,
+    /// Schema the tool's `structuredContent` must conform to, if declared.
+    #[serde(
+        default,
+        rename = "outputSchema",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub output_schema: Option<serde_json::Value>,
 }
 
 /// Result of listing tools.
@@ -114,6 +163,13 @@ pub struct McpToolResult {
     pub content: Vec<McpContentBlock>,
     #[serde(default, rename = "isError")]
     pub is_error: bool,
+    /// Machine-readable result, for tools that declare an `outputSchema`.
+    #[serde(
+        default,
+        rename = "structuredContent",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub structured_content: Option<serde_json::Value>,
 }
 
 /// Transport configuration for MCP.
@@ -126,16 +182,102 @@ pub enum McpTransport {
         env: HashMap<String, String>,
     },
     /// SSE transport - connect to HTTP endpoint with Server-Sent Events.
-    Sse { url: String },
+    Sse {
+        url: String,
+        /// Headers sent with every request, including the event-stream connection.
+        headers: HashMap<String, String>,
+    },
     /// HTTP transport - simple request/response over HTTP (Streamable HTTP).
-    Http { url: String },
+    Http {
+        url: String,
+        /// Headers sent with every request.
+        headers: HashMap<String, String>,
+    },
 }
 
 /// Configuration for an MCP server.
+///
+/// Build one with [`new`](Self::new) and the `with_*` methods. It is
+/// `#[non_exhaustive]` so settings can be added without breaking callers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct McpServerConfig {
     pub name: String,
     pub transport: McpTransport,
+    /// How often to try reconnecting after the server goes away, and how
+    /// long to back off between attempts. The default never reconnects.
+    pub reconnect: RetryPolicy,
+    /// How variables in a stdio server's args and env are expanded. The
+    /// default expands them, leaving unset ones as written.
+    pub env_expansion: EnvExpansion,
+}
+
+impl McpServerConfig {
+    /// Configure the server called `name`, reached over `transport`. It
+    /// never reconnects and expands variables leaving unset ones as written.
+    pub fn new(name: impl Into<String>, transport: McpTransport) -> Self {
+        Self {
+            name: name.into(),
+            transport,
+            reconnect: RetryPolicy::default(),
+            env_expansion: EnvExpansion::default(),
+        }
+    }
+
+    /// Add a header to every request. Ignored for stdio transports.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        match &mut self.transport {
+            McpTransport::Sse { headers, .. } | McpTransport::Http { headers, .. } => {
+                headers.insert(name.into(), value.into());
+            }
+            McpTransport::Stdio { .. } => {}
+        }
+        self
+    }
+
+    /// Authenticate with `Authorization: Bearer <token>`. Ignored for stdio transports.
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        self.with_header("Authorization", format!("Bearer {}", token.as_ref()))
+    }
+
+    /// Reconnect after a dropped connection, backing off per `policy`.
+    pub fn with_reconnect(mut self, policy: RetryPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Expand variables in a stdio server's args and env per `expansion`.
+    pub fn with_env_expansion(mut self, expansion: EnvExpansion) -> Self {
+        self.env_expansion = expansion;
+        self
+    }
+}
+
+/// Connection state of an [`McpClient`](super::McpClient).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpHealth {
+    /// The last request reached the server.
+    Connected,
+    /// The connection dropped and reconnect attempt `attempt` (1-based) is
+    /// under way.
+    Reconnecting { attempt: u32 },
+    /// The connection dropped and could not be re-established. The next
+    /// request that fails to reach the server starts reconnecting again.
+    Disconnected { error: String },
+}
+
+/// A change a server announced with a notification.
+///
+/// List changes carry the list as re-fetched after the notification.
+#[derive(Debug, Clone)]
+pub enum McpServerEvent {
+    ToolsChanged(Vec<McpToolInfo>),
+    ResourcesChanged(Vec<McpResourceInfo>),
+    PromptsChanged(Vec<McpPromptInfo>),
+    /// A subscribed resource changed; read it again for the new content.
+    ResourceUpdated {
+        uri: String,
+    },
 }
 
 /// Client info for MCP handshake.
@@ -377,7 +519,7 @@ pub struct McpLogMessage {
 // ============================================================================
 
 /// Progress notification for long-running operations.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct McpProgress {
     /// Progress token identifying the operation.
     #[serde(rename = "progressToken")]
@@ -387,6 +529,9 @@ pub struct McpProgress {
     /// Optional total value for the operation.
     #[serde(default)]
     pub total: Option<f64>,
+    /// Optional description of the current step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 // ============================================================================
@@ -2,6 +2,7 @@
 // ABOUTME: Verifies JSON format matches MCP protocol.
 
 use super::*;
+use std::collections::HashMap;
 
 #[test]
 fn test_request_serialization() {
@@ -112,3 +113,40 @@ fn test_request_ids_increment() {
 
     assert!(req2.id > req1.id);
 }
+
+#[test]
+fn test_server_config_bearer_token() {
+    let config = McpServerConfig::new(
+        "remote",
+        McpTransport::Http {
+            url: "https://mcp.example.com".into(),
+            headers: HashMap::new(),
+        },
+    )
+    .with_bearer_token("secret")
+    .with_header("X-Team", "infra");
+
+    let McpTransport::Http { headers, .. } = &config.transport else {
+        panic!("transport changed");
+    };
+    assert_eq!(headers["Authorization"], "Bearer secret");
+    assert_eq!(headers["X-Team"], "infra");
+}
+
+#[test]
+fn test_server_config_headers_ignored_for_stdio() {
+    let config = McpServerConfig::new(
+        "local",
+        McpTransport::Stdio {
+            command: "server".into(),
+            args: vec![],
+            env: HashMap::new(),
+        },
+    )
+    .with_bearer_token("secret");
+
+    let McpTransport::Stdio { env, .. } = &config.transport else {
+        panic!("transport changed");
+    };
+    assert!(env.is_empty());
+}
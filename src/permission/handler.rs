@@ -1,8 +1,13 @@
 // ABOUTME: Defines the ApprovalHandler trait for async user approval.
-// ABOUTME: Called when policy returns Decision::Ask.
+// ABOUTME: Called when policy returns Decision::Ask; can remember answers.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
 
+use crate::tool::canonical_json;
+
 /// Context provided to approval handlers.
 #[derive(Debug, Clone)]
 pub struct ApprovalContext {
@@ -56,3 +61,285 @@ impl ApprovalHandler for AlwaysReject {
         Ok(false)
     }
 }
+
+/// An answer to an interactive approval prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// Run this call.
+    Allow,
+    /// Run this call and later calls to the same tool with the same arguments.
+    AllowExact,
+    /// Run this call and every later call to the same tool.
+    AllowAlways,
+    /// Reject this call.
+    Deny,
+    /// Reject this call and every later call to the same tool.
+    DenyAlways,
+}
+
+impl ApprovalDecision {
+    /// Whether the call being asked about may run.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allow | Self::AllowExact | Self::AllowAlways)
+    }
+}
+
+/// Asks the user about a tool call, e.g. with a y/n/always prompt.
+///
+/// Wrap an implementation in [`RememberingApprovalHandler`] so answers that
+/// apply beyond one call aren't asked again.
+#[async_trait]
+pub trait ApprovalPrompt: Send + Sync {
+    /// Ask whether `tool` may run with `params`.
+    async fn prompt(
+        &self,
+        tool: &str,
+        params: &serde_json::Value,
+        context: &ApprovalContext,
+    ) -> Result<ApprovalDecision, anyhow::Error>;
+}
+
+/// Approval decisions remembered for the rest of a session.
+///
+/// Clones share the same decisions.
+#[derive(Clone, Default)]
+pub struct ApprovalMemory {
+    state: Arc<Mutex<RememberedDecisions>>,
+}
+
+#[derive(Default)]
+struct RememberedDecisions {
+    /// Tool-wide decisions: true for allow always, false for deny always.
+    tools: HashMap<String, bool>,
+    /// Exact calls allowed, as (tool, arguments JSON).
+    calls: HashSet<(String, String)>,
+}
+
+impl ApprovalMemory {
+    /// Create an empty memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The remembered answer for a call, if there is one.
+    ///
+    /// A tool-wide decision takes precedence over an allowed exact call.
+    /// Arguments match regardless of key order.
+    pub fn recall(&self, tool: &str, params: &serde_json::Value) -> Option<bool> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&allowed) = state.tools.get(tool) {
+            return Some(allowed);
+        }
+        state
+            .calls
+            .contains(&(tool.to_string(), canonical_json(params)))
+            .then_some(true)
+    }
+
+    /// Remember `decision` for later calls if it applies beyond this one.
+    ///
+    /// A tool-wide decision replaces any earlier one for the same tool.
+    pub fn record(&self, tool: &str, params: &serde_json::Value, decision: ApprovalDecision) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match decision {
+            ApprovalDecision::AllowAlways => {
+                state.tools.insert(tool.to_string(), true);
+            }
+            ApprovalDecision::DenyAlways => {
+                state.tools.insert(tool.to_string(), false);
+            }
+            ApprovalDecision::AllowExact => {
+                state
+                    .calls
+                    .insert((tool.to_string(), canonical_json(params)));
+            }
+            ApprovalDecision::Allow | ApprovalDecision::Deny => {}
+        }
+    }
+
+    /// Forget every remembered decision.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tools.clear();
+        state.calls.clear();
+    }
+}
+
+/// An approval handler that prompts through an [`ApprovalPrompt`] and
+/// remembers "always" and exact-call answers.
+///
+/// Remembered decisions are checked before prompting, so once a tool is
+/// allowed always, later calls run without asking. The memory lasts as long
+/// as the handler, or can be shared with [`with_memory`](Self::with_memory).
+pub struct RememberingApprovalHandler<P> {
+    prompt: P,
+    memory: ApprovalMemory,
+}
+
+impl<P: ApprovalPrompt> RememberingApprovalHandler<P> {
+    /// Create a handler with an empty memory.
+    pub fn new(prompt: P) -> Self {
+        Self {
+            prompt,
+            memory: ApprovalMemory::new(),
+        }
+    }
+
+    /// Use `memory` instead, e.g. to share decisions between agents.
+    pub fn with_memory(mut self, memory: ApprovalMemory) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// The decisions remembered so far.
+    pub fn memory(&self) -> &ApprovalMemory {
+        &self.memory
+    }
+}
+
+#[async_trait]
+impl<P: ApprovalPrompt> ApprovalHandler for RememberingApprovalHandler<P> {
+    async fn request_approval(
+        &self,
+        tool: &str,
+        params: &serde_json::Value,
+        context: &ApprovalContext,
+    ) -> Result<bool, anyhow::Error> {
+        if let Some(allowed) = self.memory.recall(tool, params) {
+            return Ok(allowed);
+        }
+        let decision = self.prompt.prompt(tool, params, context).await?;
+        self.memory.record(tool, params, decision);
+        Ok(decision.is_allowed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Prompt that gives scripted answers and records what it was asked.
+    struct ScriptedPrompt {
+        answers: Mutex<VecDeque<ApprovalDecision>>,
+        asked: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ScriptedPrompt {
+        fn new(answers: impl IntoIterator<Item = ApprovalDecision>) -> Self {
+            Self {
+                answers: Mutex::new(answers.into_iter().collect()),
+                asked: Arc::default(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ApprovalPrompt for ScriptedPrompt {
+        async fn prompt(
+            &self,
+            tool: &str,
+            params: &serde_json::Value,
+            _context: &ApprovalContext,
+        ) -> Result<ApprovalDecision, anyhow::Error> {
+            self.asked
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", tool, params));
+            Ok(self.answers.lock().unwrap().pop_front().unwrap())
+        }
+    }
+
+    fn context() -> ApprovalContext {
+        ApprovalContext {
+            tool_description: "Runs a command".into(),
+            request_id: "req-1".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allow_always_is_not_prompted_again() {
+        let prompt = ScriptedPrompt::new([ApprovalDecision::AllowAlways]);
+        let asked = prompt.asked.clone();
+        let handler = RememberingApprovalHandler::new(prompt);
+
+        let ls = serde_json::json!({"command": "ls"});
+        assert!(
+            handler
+                .request_approval("bash", &ls, &context())
+                .await
+                .unwrap()
+        );
+        assert!(
+            handler
+                .request_approval("bash", &ls, &context())
+                .await
+                .unwrap()
+        );
+        let pwd = serde_json::json!({"command": "pwd"});
+        assert!(
+            handler
+                .request_approval("bash", &pwd, &context())
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(asked.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exact_and_deny_decisions() {
+        let prompt = ScriptedPrompt::new([
+            ApprovalDecision::AllowExact,
+            ApprovalDecision::Deny,
+            ApprovalDecision::DenyAlways,
+        ]);
+        let asked = prompt.asked.clone();
+        let handler = RememberingApprovalHandler::new(prompt);
+
+        // An exact allow covers the same arguments in any key order
+        let push = serde_json::json!({"command": "git push", "cwd": "/repo"});
+        let push_reordered = serde_json::json!({"cwd": "/repo", "command": "git push"});
+        assert!(
+            handler
+                .request_approval("bash", &push, &context())
+                .await
+                .unwrap()
+        );
+        assert!(
+            handler
+                .request_approval("bash", &push_reordered, &context())
+                .await
+                .unwrap()
+        );
+
+        // Other arguments are asked about; a plain deny isn't remembered
+        let rm = serde_json::json!({"command": "rm -rf /"});
+        assert!(
+            !handler
+                .request_approval("bash", &rm, &context())
+                .await
+                .unwrap()
+        );
+        assert!(
+            !handler
+                .request_approval("bash", &rm, &context())
+                .await
+                .unwrap()
+        );
+        assert_eq!(asked.lock().unwrap().len(), 3);
+
+        // Deny always overrides the exact allow
+        assert!(
+            !handler
+                .request_approval("bash", &push, &context())
+                .await
+                .unwrap()
+        );
+        assert_eq!(asked.lock().unwrap().len(), 3);
+
+        handler.memory().clear();
+        assert_eq!(handler.memory().recall("bash", &push), None);
+    }
+}
@@ -1,7 +1,10 @@
 // ABOUTME: Defines the policy engine - rules, decisions, and evaluation.
-// ABOUTME: Supports globs, conditionals, and default policies.
+// ABOUTME: Supports globs, conditionals, per-session rate limits, and default policies.
 
-use std::sync::Arc;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::error::PermissionError;
 
 /// The decision made by a policy rule.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -17,6 +20,9 @@ pub enum Decision {
 /// A condition function for conditional rules.
 pub type ConditionFn = Arc<dyn Fn(&serde_json::Value) -> Decision + Send + Sync>;
 
+/// A predicate on a tool's input for `AllowWhen` and `DenyWhen` rules.
+pub type PredicateFn = Arc<dyn Fn(&serde_json::Value) -> bool + Send + Sync>;
+
 /// A rule in the policy.
 pub enum PolicyRule {
     /// Allow a specific tool by exact name.
@@ -36,9 +42,38 @@ pub enum PolicyRule {
         tool: String,
         condition: ConditionFn,
     },
+
+    /// Allow a tool by exact name when its input satisfies the predicate.
+    /// Otherwise the rule doesn't match and evaluation moves on.
+    AllowWhen {
+        tool: String,
+        predicate: PredicateFn,
+    },
+
+    /// Deny a tool by exact name when its input satisfies the predicate.
+    /// Otherwise the rule doesn't match and evaluation moves on.
+    DenyWhen {
+        tool: String,
+        predicate: PredicateFn,
+    },
+
+    /// Cap the number of calls to tools matching a glob pattern.
+    ///
+    /// Only enforced by [`PolicySession`]; [`Policy::evaluate`] skips it.
+    RateLimit {
+        pattern: glob::Pattern,
+        max_per_session: usize,
+    },
 }
 
 /// A policy that evaluates tool execution requests.
+///
+/// Rules are checked in the order they were added and the first match
+/// decides; unmatched tools get the default decision. Exact-name, pattern
+/// and `Conditional` rules match on the tool name alone. `AllowWhen` and
+/// `DenyWhen` rules also need their predicate to hold, so a call that fails
+/// the predicate falls through to later rules, pattern rules included. Put
+/// a `DenyWhen` before a broader allow to carve an exception out of it.
 pub struct Policy {
     rules: Vec<PolicyRule>,
     default: Decision,
@@ -51,6 +86,9 @@ impl Policy {
     }
 
     /// Evaluate whether a tool should be allowed.
+    ///
+    /// Rate limits are ignored here because they need per-session call
+    /// counts; use [`PolicySession::evaluate`] to enforce them.
     pub fn evaluate(&self, tool: &str, params: &serde_json::Value) -> Decision {
         for rule in &self.rules {
             match rule {
@@ -63,6 +101,12 @@ impl Policy {
                 PolicyRule::Conditional { tool: t, condition } if t == tool => {
                     return condition(params);
                 }
+                PolicyRule::AllowWhen { tool: t, predicate } if t == tool && predicate(params) => {
+                    return Decision::Allow;
+                }
+                PolicyRule::DenyWhen { tool: t, predicate } if t == tool && predicate(params) => {
+                    return Decision::Deny;
+                }
                 _ => continue,
             }
         }
@@ -79,6 +123,91 @@ impl Default for Policy {
     }
 }
 
+/// A policy plus the call counts for one session.
+///
+/// Create one per agent run so rate limits reset between sessions.
+pub struct PolicySession {
+    policy: Arc<Policy>,
+    calls: Mutex<Vec<usize>>,
+}
+
+impl PolicySession {
+    /// Start a session with all call counts at zero.
+    pub fn new(policy: Arc<Policy>) -> Self {
+        let calls = Mutex::new(vec![0; policy.rules.len()]);
+        Self { policy, calls }
+    }
+
+    /// The policy this session enforces.
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// A new session for the same policy, with all call counts at zero.
+    pub fn restart(&self) -> Self {
+        Self::new(self.policy.clone())
+    }
+
+    /// Evaluate a call, enforcing rate limits on top of the other rules.
+    ///
+    /// Returns [`PermissionError::RateLimited`] once a matching limit is used
+    /// up. Evaluating doesn't use up the limit: call [`record`](Self::record)
+    /// once the call is cleared to run, e.g. after it has been approved.
+    pub fn evaluate(
+        &self,
+        tool: &str,
+        params: &serde_json::Value,
+    ) -> Result<Decision, PermissionError> {
+        let decision = self.policy.evaluate(tool, params);
+        if decision == Decision::Deny {
+            return Ok(decision);
+        }
+
+        let calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        self.check_limits(tool, &calls)?;
+        Ok(decision)
+    }
+
+    /// Count a call that is about to run against every matching rate limit,
+    /// so a pattern limit caps the matching tools together.
+    ///
+    /// Fails with [`PermissionError::RateLimited`], counting nothing, if a
+    /// limit was used up since the call was evaluated.
+    pub fn record(&self, tool: &str) -> Result<(), PermissionError> {
+        let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        self.check_limits(tool, &calls)?;
+        for (i, _) in self.limits_for(tool) {
+            calls[i] += 1;
+        }
+        Ok(())
+    }
+
+    fn check_limits(&self, tool: &str, calls: &[usize]) -> Result<(), PermissionError> {
+        match self.limits_for(tool).find(|(i, max)| calls[*i] >= *max) {
+            Some((_, limit)) => Err(PermissionError::RateLimited {
+                tool: tool.to_string(),
+                limit,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The index and limit of every rate-limit rule matching `tool`.
+    fn limits_for<'a>(&'a self, tool: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.policy
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, rule)| match rule {
+                PolicyRule::RateLimit {
+                    pattern,
+                    max_per_session,
+                } if pattern.matches(tool) => Some((i, *max_per_session)),
+                _ => None,
+            })
+    }
+}
+
 /// Builder for constructing policies.
 pub struct PolicyBuilder {
     rules: Vec<PolicyRule>,
@@ -140,6 +269,59 @@ impl PolicyBuilder {
         self
     }
 
+    /// Allow a tool when its input satisfies `predicate`, e.g. `bash` only
+    /// for git commands:
+    ///
+    /// ```
+    /// # use mux::permission::{Decision, Policy};
+    /// let policy = Policy::builder()
+    ///     .allow_when("bash", |input| {
+    ///         input["command"].as_str().is_some_and(|c| c.starts_with("git "))
+    ///     })
+    ///     .build();
+    /// let input = serde_json::json!({"command": "git status"});
+    /// assert_eq!(policy.evaluate("bash", &input), Decision::Allow);
+    /// ```
+    ///
+    /// Calls that fail the predicate fall through to later rules.
+    pub fn allow_when<F>(mut self, tool: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> bool + Send + Sync + 'static,
+    {
+        self.rules.push(PolicyRule::AllowWhen {
+            tool: tool.into(),
+            predicate: Arc::new(predicate),
+        });
+        self
+    }
+
+    /// Deny a tool when its input satisfies `predicate`. Calls that fail
+    /// the predicate fall through to later rules.
+    pub fn deny_when<F>(mut self, tool: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> bool + Send + Sync + 'static,
+    {
+        self.rules.push(PolicyRule::DenyWhen {
+            tool: tool.into(),
+            predicate: Arc::new(predicate),
+        });
+        self
+    }
+
+    /// Allow at most `max_per_session` calls to tools matching a glob pattern.
+    ///
+    /// An exact tool name is a valid pattern. The limit applies on top of the
+    /// other rules regardless of order and is enforced by [`PolicySession`].
+    pub fn rate_limit(mut self, pattern: &str, max_per_session: usize) -> Self {
+        if let Ok(p) = glob::Pattern::new(pattern) {
+            self.rules.push(PolicyRule::RateLimit {
+                pattern: p,
+                max_per_session,
+            });
+        }
+        self
+    }
+
     /// Set the default decision for unmatched tools.
     pub fn default(mut self, decision: Decision) -> Self {
         self.default = decision;
@@ -154,3 +336,31 @@ impl PolicyBuilder {
         }
     }
 }
+
+/// Whether `path` stays inside `root` once `.` and `..` are resolved.
+///
+/// Relative paths are taken relative to `root`, and a relative `root` relative
+/// to the current directory. The check is lexical: it doesn't touch the
+/// filesystem, so a symlink inside `root` can still point outside it.
+pub fn path_within(root: impl AsRef<Path>, path: &str) -> bool {
+    let Ok(root) = std::path::absolute(root.as_ref()) else {
+        return false;
+    };
+    let root = normalize(&root);
+    normalize(&root.join(path)).starts_with(&root)
+}
+
+/// Resolve `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
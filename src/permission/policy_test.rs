@@ -1,7 +1,10 @@
 // ABOUTME: Tests for Policy - rules, patterns, conditionals, defaults.
 // ABOUTME: Verifies policy evaluation works correctly.
 
+use std::sync::Arc;
+
 use super::*;
+use crate::error::PermissionError;
 
 #[test]
 fn test_allow_exact() {
@@ -92,6 +95,74 @@ fn test_conditional() {
     );
 }
 
+fn is_git(input: &serde_json::Value) -> bool {
+    input["command"]
+        .as_str()
+        .is_some_and(|c| c.starts_with("git "))
+}
+
+#[test]
+fn test_allow_when_falls_through() {
+    let git = serde_json::json!({"command": "git status"});
+    let rm = serde_json::json!({"command": "rm -rf /"});
+
+    let policy = Policy::builder()
+        .allow_when("bash", is_git)
+        .default(Decision::Ask)
+        .build();
+    assert_eq!(policy.evaluate("bash", &git), Decision::Allow);
+    assert_eq!(policy.evaluate("bash", &rm), Decision::Ask);
+
+    // A failed predicate falls through to later pattern rules too
+    let policy = Policy::builder()
+        .allow_when("bash", is_git)
+        .deny_pattern("ba*")
+        .default(Decision::Allow)
+        .build();
+    assert_eq!(policy.evaluate("bash", &git), Decision::Allow);
+    assert_eq!(policy.evaluate("bash", &rm), Decision::Deny);
+}
+
+#[test]
+fn test_deny_when_path_escapes_workspace() {
+    let policy = Policy::builder()
+        .deny_when("write_file", |input| {
+            input["path"]
+                .as_str()
+                .is_none_or(|path| !path_within("/work/project", path))
+        })
+        .allow_pattern("*_file")
+        .build();
+
+    let write = |path: &str| policy.evaluate("write_file", &serde_json::json!({"path": path}));
+    assert_eq!(write("src/main.rs"), Decision::Allow);
+    assert_eq!(write("/work/project/docs/../README.md"), Decision::Allow);
+    assert_eq!(write("../other/secrets.txt"), Decision::Deny);
+    assert_eq!(write("src/../../../etc/passwd"), Decision::Deny);
+    assert_eq!(write("/etc/passwd"), Decision::Deny);
+    assert_eq!(write("/work/project-evil/x"), Decision::Deny);
+    assert_eq!(
+        policy.evaluate("write_file", &serde_json::json!({})),
+        Decision::Deny
+    );
+
+    // The deny only covers write_file; the pattern still allows read_file
+    assert_eq!(
+        policy.evaluate("read_file", &serde_json::json!({"path": "/etc/passwd"})),
+        Decision::Allow
+    );
+}
+
+#[test]
+fn test_path_within_relative_root() {
+    assert!(path_within(".", "src/main.rs"));
+    assert!(path_within(".", "src/../Cargo.toml"));
+    assert!(!path_within(".", "../x"));
+    assert!(!path_within(".", "src/../../x"));
+    assert!(!path_within(".", "/etc/passwd"));
+    assert!(!path_within("sub", "../x"));
+}
+
 #[test]
 fn test_rule_order() {
     // First matching rule wins
@@ -166,3 +237,92 @@ fn test_complex_policy() {
         Decision::Ask
     );
 }
+
+#[test]
+fn test_rate_limit_denies_after_max_calls() {
+    let policy = Arc::new(
+        Policy::builder()
+            .rate_limit("send_email", 3)
+            .default(Decision::Allow)
+            .build(),
+    );
+    let session = PolicySession::new(policy.clone());
+    let params = serde_json::json!({});
+
+    for _ in 0..3 {
+        assert_eq!(
+            session.evaluate("send_email", &params).unwrap(),
+            Decision::Allow
+        );
+        session.record("send_email").unwrap();
+    }
+    let err = session.evaluate("send_email", &params).unwrap_err();
+    assert!(matches!(err, PermissionError::RateLimited { limit: 3, .. }));
+    assert!(err.to_string().contains("'send_email'"));
+    assert!(err.to_string().contains("limit of 3 calls per session"));
+
+    // Other tools are unaffected, and the stateless evaluate ignores limits
+    assert_eq!(
+        session.evaluate("read_file", &params).unwrap(),
+        Decision::Allow
+    );
+    assert_eq!(policy.evaluate("send_email", &params), Decision::Allow);
+
+    // A new session starts counting from zero
+    let fresh = PolicySession::new(policy);
+    assert_eq!(
+        fresh.evaluate("send_email", &params).unwrap(),
+        Decision::Allow
+    );
+}
+
+#[test]
+fn test_rate_limit_pattern_shares_count_and_skips_denied_calls() {
+    let session = PolicySession::new(Arc::new(
+        Policy::builder()
+            .deny("web_delete")
+            .rate_limit("web_*", 2)
+            .default(Decision::Ask)
+            .build(),
+    ));
+    let params = serde_json::json!({});
+
+    // Denied calls don't use up the limit
+    assert_eq!(
+        session.evaluate("web_delete", &params).unwrap(),
+        Decision::Deny
+    );
+    assert_eq!(
+        session.evaluate("web_fetch", &params).unwrap(),
+        Decision::Ask
+    );
+    session.record("web_fetch").unwrap();
+    assert_eq!(
+        session.evaluate("web_search", &params).unwrap(),
+        Decision::Ask
+    );
+    session.record("web_search").unwrap();
+    assert!(session.evaluate("web_fetch", &params).is_err());
+    assert!(session.record("web_fetch").is_err());
+}
+
+#[test]
+fn test_rate_limit_counts_only_recorded_calls() {
+    let session = PolicySession::new(Arc::new(
+        Policy::builder()
+            .rate_limit("send_email", 1)
+            .default(Decision::Ask)
+            .build(),
+    ));
+    let params = serde_json::json!({});
+
+    // Calls that were asked about but never approved don't count
+    for _ in 0..3 {
+        assert_eq!(
+            session.evaluate("send_email", &params).unwrap(),
+            Decision::Ask
+        );
+    }
+    session.record("send_email").unwrap();
+    assert!(session.evaluate("send_email", &params).is_err());
+}
@@ -2,25 +2,32 @@
 // ABOUTME: Use `use mux::prelude::*;` to get started quickly.
 
 pub use crate::agent::{
-    AgentDefinition, AgentRegistry, FilteredRegistry, SubAgent, SubAgentResult, TaskTool,
+    AgentDefinition, AgentGraph, AgentRegistry, AgentStopReason, AgentTool, FilteredRegistry,
+    Orchestrator, RepeatedCallPolicy, SubAgent, SubAgentResult, SystemPromptBuilder, TaskTool,
+    ToolErrorPolicy,
+};
+pub use crate::error::{
+    AgentLoadError, GraphError, LlmError, McpError, MuxError, PermissionError, ToolError,
 };
-pub use crate::error::{LlmError, McpError, MuxError, PermissionError, ToolError};
 pub use crate::llm::{
-    AnthropicClient, ContentBlock, LlmClient, Message, OpenAIClient, Request, Response, Role,
-    StopReason, StreamEvent, ToolDefinition, Usage,
+    AnthropicClient, Citation, ContentBlock, ImageSource, Instruction, InstructionRole, LlmClient,
+    Message, OpenAIClient, Request, Response, RetryPolicy, Role, StopReason, StreamEvent,
+    ToolChoice, ToolDefinition, Usage, UsageTracker,
 };
 pub use crate::mcp::{
-    HttpTransport, McpClient, McpContentBlock, McpLogLevel, McpPromptGetResult, McpPromptInfo,
-    McpPromptsListResult, McpProxyTool, McpResourceContent, McpResourceInfo,
-    McpResourcesListResult, McpRoot, McpSamplingParams, McpSamplingResult, McpServerCapabilities,
-    McpServerConfig, McpToolInfo, McpToolResult, McpTransport, SseTransport, StdioTransport,
-    Transport,
+    HttpTransport, McpClient, McpContentBlock, McpHealth, McpLogLevel, McpProgress,
+    McpPromptGetResult, McpPromptInfo, McpPromptsListResult, McpProxyTool, McpResourceContent,
+    McpResourceInfo, McpResourcesListResult, McpRoot, McpSamplingParams, McpSamplingResult,
+    McpServerCapabilities, McpServerConfig, McpServerEvent, McpToolInfo, McpToolNaming,
+    McpToolResult, McpTransport, SseTransport, StdioTransport, Transport,
 };
 pub use crate::permission::{
-    AlwaysApprove, AlwaysReject, ApprovalContext, ApprovalHandler, Decision, Policy, PolicyBuilder,
+    AlwaysApprove, AlwaysReject, ApprovalContext, ApprovalDecision, ApprovalHandler,
+    ApprovalMemory, ApprovalPrompt, Decision, Policy, PolicyBuilder, PolicySession,
+    RememberingApprovalHandler,
 };
-pub use crate::tool::{Registry, Tool, ToolExecute, ToolResult};
+pub use crate::tool::{ProgressReporter, Registry, Tool, ToolExecute, ToolProgress, ToolResult};
 pub use crate::tools::{
-    BashTool, ListFilesTool, ReadFileTool, SearchResult, SearchTool, WebFetchTool, WebSearchTool,
-    WriteFileTool,
+    BashTool, EditTool, ListFilesTool, ReadFileTool, SearchResult, SearchTool, WebFetchTool,
+    WebSearchTool, WriteFileTool,
 };
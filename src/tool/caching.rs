@@ -0,0 +1,174 @@
+// ABOUTME: CachingRegistry - reuses results of identical cacheable tool calls.
+// ABOUTME: Keyed by tool name and canonical JSON input, with an optional TTL.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{ProgressReporter, Registry, Tool, ToolResult};
+
+type CacheKey = (String, String);
+
+/// Wraps a [`Registry`] and remembers the results of tools marked
+/// [`Tool::cacheable`], so repeating a call with the same input returns
+/// the earlier result instead of running the tool again.
+///
+/// Inputs are compared as canonical JSON, so key order doesn't matter.
+/// Only successful results are kept. The cache lives as long as the
+/// wrapper and is shared by its clones: create one per session, and pass
+/// it to [`SubAgent::with_tool_cache`] to use it in the agent loop.
+///
+/// A call whose result reports [changed files](ToolResult::files_changed),
+/// such as `write_file` or `edit`, clears the cache, since any cached read
+/// or search may cover those files. Call [`invalidate`](Self::invalidate)
+/// or [`clear`](Self::clear) after changes the cache can't see, such as
+/// files written by `bash`.
+///
+/// [`SubAgent::with_tool_cache`]: crate::agent::SubAgent::with_tool_cache
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use mux::tool::{CachingRegistry, Registry};
+///
+/// let tools = CachingRegistry::new(Registry::new()).with_ttl(Duration::from_secs(60));
+/// tools.invalidate("read_file");
+/// ```
+#[derive(Clone)]
+pub struct CachingRegistry {
+    registry: Registry,
+    ttl: Option<Duration>,
+    entries: Arc<Mutex<HashMap<CacheKey, (Instant, ToolResult)>>>,
+}
+
+impl CachingRegistry {
+    /// Cache results of calls made through `registry`.
+    pub fn new(registry: Registry) -> Self {
+        Self {
+            registry,
+            ttl: None,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Forget results once they are older than `ttl`. Without a TTL they
+    /// are kept until invalidated.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The wrapped registry.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Execute a tool through the wrapped registry, reusing an earlier
+    /// result for the same input if the tool is cacheable.
+    pub async fn execute_tool(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+    ) -> Result<ToolResult, anyhow::Error> {
+        self.execute(tool, params, None).await
+    }
+
+    /// Execute a tool like [`execute_tool`](Self::execute_tool), letting it
+    /// report to `progress` if it runs. A cached result reports nothing.
+    pub async fn execute_tool_with_progress(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+        progress: &ProgressReporter,
+    ) -> Result<ToolResult, anyhow::Error> {
+        self.execute(tool, params, Some(progress)).await
+    }
+
+    async fn execute(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<ToolResult, anyhow::Error> {
+        let key = tool
+            .cacheable()
+            .then(|| (tool.name().to_string(), canonical_json(&params)));
+        if let Some(result) = key.as_ref().and_then(|key| self.lookup(key)) {
+            return Ok(result);
+        }
+
+        let result = match progress {
+            Some(progress) => {
+                self.registry
+                    .execute_tool_with_progress(tool, params, progress)
+                    .await?
+            }
+            None => self.registry.execute_tool(tool, params).await?,
+        };
+        self.forget_if_files_changed(&result);
+        if let Some(key) = key
+            && !result.is_error
+        {
+            self.entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key, (Instant::now(), result.clone()));
+        }
+        Ok(result)
+    }
+
+    /// Forget every cached result of the tool called `name`.
+    pub fn invalidate(&self, name: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(tool, _), _| tool != name);
+    }
+
+    /// Forget every cached result.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Clear the cache if `result` says files changed.
+    fn forget_if_files_changed(&self, result: &ToolResult) {
+        if !result.files_changed().is_empty() {
+            self.clear();
+        }
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Option<ToolResult> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (stored, result) = entries.get(key)?;
+        if self.ttl.is_some_and(|ttl| stored.elapsed() >= ttl) {
+            entries.remove(key);
+            return None;
+        }
+        Some(result.clone())
+    }
+}
+
+/// `value` as JSON text with object keys sorted at every level.
+pub(crate) fn canonical_json(value: &serde_json::Value) -> String {
+    fn sorted(value: &serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(fields) => {
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                let mut map = serde_json::Map::new();
+                for key in keys {
+                    map.insert(key.clone(), sorted(&fields[key]));
+                }
+                serde_json::Value::Object(map)
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.iter().map(sorted).collect())
+            }
+            other => other.clone(),
+        }
+    }
+    sorted(value).to_string()
+}
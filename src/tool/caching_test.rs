@@ -0,0 +1,160 @@
+// ABOUTME: Tests for CachingRegistry - reuse, invalidation, TTL and opt-out.
+// ABOUTME: Uses a tool that counts how often it actually runs.
+
+use super::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Counts executions and reports the count in its result.
+struct CountingTool {
+    cacheable: bool,
+    runs: Arc<AtomicUsize>,
+}
+
+impl CountingTool {
+    fn new(cacheable: bool) -> (Self, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let tool = Self {
+            cacheable,
+            runs: Arc::clone(&runs),
+        };
+        (tool, runs)
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for CountingTool {
+    fn name(&self) -> &str {
+        "count"
+    }
+
+    fn description(&self) -> &str {
+        "Counts its runs"
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        serde_json::json!({"type": "object"})
+    }
+
+    fn cacheable(&self) -> bool {
+        self.cacheable
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        let run = self.runs.fetch_add(1, Ordering::SeqCst) + 1;
+        if params["fail"] == true {
+            return Ok(ToolResult::error("failed"));
+        }
+        Ok(ToolResult::text(format!("run {}", run)))
+    }
+}
+
+#[tokio::test]
+async fn test_identical_cacheable_calls_run_once() {
+    let (tool, runs) = CountingTool::new(true);
+    let tools = CachingRegistry::new(Registry::new());
+
+    let first = tools
+        .execute_tool(&tool, serde_json::json!({"path": "a", "lines": [1, 2]}))
+        .await
+        .unwrap();
+    let second = tools
+        .execute_tool(&tool, serde_json::json!({"lines": [1, 2], "path": "a"}))
+        .await
+        .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(first.content, "run 1");
+    assert_eq!(second.content, "run 1");
+
+    tools
+        .execute_tool(&tool, serde_json::json!({"path": "b"}))
+        .await
+        .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_uncacheable_tools_and_errors_always_run() {
+    let tools = CachingRegistry::new(Registry::new());
+
+    let (plain, plain_runs) = CountingTool::new(false);
+    for _ in 0..2 {
+        tools
+            .execute_tool(&plain, serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+    assert_eq!(plain_runs.load(Ordering::SeqCst), 2);
+
+    let (failing, failing_runs) = CountingTool::new(true);
+    for _ in 0..2 {
+        let result = tools
+            .execute_tool(&failing, serde_json::json!({"fail": true}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+    }
+    assert_eq!(failing_runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_invalidate_forgets_only_that_tool() {
+    let (tool, runs) = CountingTool::new(true);
+    let tools = CachingRegistry::new(Registry::new());
+    let input = serde_json::json!({"path": "a"});
+
+    tools.execute_tool(&tool, input.clone()).await.unwrap();
+    tools.invalidate("other");
+    tools.execute_tool(&tool, input.clone()).await.unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    tools.invalidate("count");
+    let result = tools.execute_tool(&tool, input).await.unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    assert_eq!(result.content, "run 2");
+}
+
+#[tokio::test]
+async fn test_results_expire_after_ttl() {
+    let (tool, runs) = CountingTool::new(true);
+    let tools = CachingRegistry::new(Registry::new()).with_ttl(Duration::from_millis(20));
+    let input = serde_json::json!({"path": "a"});
+
+    tools.execute_tool(&tool, input.clone()).await.unwrap();
+    tools.execute_tool(&tool, input.clone()).await.unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    tools.execute_tool(&tool, input).await.unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_file_changes_clear_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "before").unwrap();
+    let path = path.to_string_lossy().into_owned();
+    let tools = CachingRegistry::new(Registry::new());
+    let read = crate::tools::ReadFileTool;
+
+    let first = tools
+        .execute_tool(&read, serde_json::json!({"path": path}))
+        .await
+        .unwrap();
+    tools
+        .execute_tool(
+            &crate::tools::WriteFileTool,
+            serde_json::json!({"path": path, "content": "after"}),
+        )
+        .await
+        .unwrap();
+    let second = tools
+        .execute_tool(&read, serde_json::json!({"path": path}))
+        .await
+        .unwrap();
+
+    assert!(first.content.contains("before"));
+    assert!(second.content.contains("after"));
+}
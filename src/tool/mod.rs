@@ -1,14 +1,26 @@
 // ABOUTME: Tool module - defines tools, registry, and execution.
 // ABOUTME: Core abstraction for agent capabilities.
 
+mod caching;
+mod progress;
+mod redact;
 mod registry;
 mod result;
+mod schema;
 mod traits;
 
+pub use caching::*;
+pub use progress::*;
+pub use redact::*;
 pub use registry::*;
 pub use result::*;
+pub use schema::*;
 pub use traits::*;
 
+#[cfg(test)]
+mod caching_test;
+#[cfg(test)]
+mod redact_test;
 #[cfg(test)]
 mod registry_test;
 #[cfg(test)]
@@ -0,0 +1,281 @@
+// ABOUTME: Progress reporting for long-running tools.
+// ABOUTME: Streams ToolProgress updates and guarantees a terminal "done" event.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::{Registry, Tool, ToolResult};
+
+/// A progress update from a running tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolProgress {
+    /// Human-readable status message.
+    pub message: String,
+
+    /// Completion fraction in `0.0..=1.0`, if the tool can estimate it.
+    pub fraction: Option<f64>,
+
+    /// True for the final event of a run. No further events follow.
+    pub done: bool,
+
+    /// The final result. Only set when `done` is true.
+    pub result: Option<ToolResult>,
+}
+
+impl ToolProgress {
+    /// Create an intermediate progress update.
+    pub fn update(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            fraction: None,
+            done: false,
+            result: None,
+        }
+    }
+
+    /// Create the terminal event carrying the final result.
+    pub fn completed(result: ToolResult) -> Self {
+        Self {
+            message: if result.is_error { "failed" } else { "done" }.to_string(),
+            fraction: Some(1.0),
+            done: true,
+            result: Some(result),
+        }
+    }
+
+    /// Set the completion fraction.
+    pub fn with_fraction(mut self, fraction: f64) -> Self {
+        self.fraction = Some(fraction.clamp(0.0, 1.0));
+        self
+    }
+}
+
+/// Handle a tool uses to report progress while it runs.
+///
+/// Sending never fails from the tool's perspective; if nobody is listening
+/// the updates are dropped.
+#[derive(Debug, Clone)]
+pub struct ProgressReporter {
+    tx: mpsc::UnboundedSender<ToolProgress>,
+}
+
+impl ProgressReporter {
+    /// Create a reporter and the receiver that observes its updates.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ToolProgress>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    /// Report an intermediate progress message.
+    pub fn report(&self, message: impl Into<String>) {
+        let _ = self.tx.send(ToolProgress::update(message));
+    }
+
+    /// Report an intermediate progress message with a completion fraction.
+    pub fn report_fraction(&self, message: impl Into<String>, fraction: f64) {
+        let _ = self
+            .tx
+            .send(ToolProgress::update(message).with_fraction(fraction));
+    }
+
+    /// Run a tool through `registry`, with its timeout, schema check and
+    /// redaction, then emit the terminal `done` event carrying its result.
+    ///
+    /// Errors returned by the tool are converted to an error `ToolResult` so
+    /// the final event is always sent. The reporter is consumed, which closes
+    /// the channel once the tool drops any clones it held.
+    pub async fn run(
+        self,
+        registry: &Registry,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+    ) -> ToolResult {
+        let result = match registry
+            .execute_tool_with_progress(tool, params, &self)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => ToolResult::error(e.to_string()),
+        };
+        self.complete(result.clone());
+        result
+    }
+
+    /// Emit the terminal `done` event carrying `result`, for callers that
+    /// run the tool themselves.
+    pub fn complete(self, result: ToolResult) {
+        let _ = self.tx.send(ToolProgress::completed(result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct CountingTool {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Tool for CountingTool {
+        fn name(&self) -> &str {
+            "count"
+        }
+
+        fn description(&self) -> &str {
+            "Counts to three"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            Ok(ToolResult::text("counted"))
+        }
+
+        async fn execute_with_progress(
+            &self,
+            _params: serde_json::Value,
+            progress: &ProgressReporter,
+        ) -> Result<ToolResult, anyhow::Error> {
+            for i in 1..=3 {
+                progress.report_fraction(format!("step {}", i), i as f64 / 3.0);
+            }
+            if self.fail {
+                anyhow::bail!("ran out of numbers");
+            }
+            Ok(ToolResult::text("counted to 3"))
+        }
+    }
+
+    struct PlainTool;
+
+    #[async_trait]
+    impl Tool for PlainTool {
+        fn name(&self) -> &str {
+            "plain"
+        }
+
+        fn description(&self) -> &str {
+            "Does not report progress"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            Ok(ToolResult::text("plain result"))
+        }
+    }
+
+    async fn collect(mut rx: mpsc::UnboundedReceiver<ToolProgress>) -> Vec<ToolProgress> {
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_last_event_is_done_with_result() {
+        let (reporter, rx) = ProgressReporter::channel();
+        let result = reporter
+            .run(
+                &Registry::new(),
+                &CountingTool { fail: false },
+                serde_json::json!({}),
+            )
+            .await;
+
+        let events = collect(rx).await;
+        assert_eq!(events.len(), 4);
+        assert!(events[..3].iter().all(|e| !e.done && e.result.is_none()));
+
+        let last = events.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.fraction, Some(1.0));
+        assert_eq!(last.result.as_ref().unwrap().content, "counted to 3");
+        assert_eq!(result.content, "counted to 3");
+    }
+
+    #[tokio::test]
+    async fn test_failed_tool_still_sends_done() {
+        let (reporter, rx) = ProgressReporter::channel();
+        let result = reporter
+            .run(
+                &Registry::new(),
+                &CountingTool { fail: true },
+                serde_json::json!({}),
+            )
+            .await;
+
+        let events = collect(rx).await;
+        let last = events.last().unwrap();
+        assert!(last.done);
+        assert!(last.result.as_ref().unwrap().is_error);
+        assert_eq!(last.message, "failed");
+        assert!(result.content.contains("ran out of numbers"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_without_progress_emits_only_done() {
+        let (reporter, rx) = ProgressReporter::channel();
+        reporter
+            .run(&Registry::new(), &PlainTool, serde_json::json!({}))
+            .await;
+
+        let events = collect(rx).await;
+        assert_eq!(events.len(), 1);
+        assert!(events[0].done);
+        assert_eq!(events[0].result.as_ref().unwrap().content, "plain result");
+    }
+
+    struct StuckTool;
+
+    #[async_trait]
+    impl Tool for StuckTool {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+
+        fn description(&self) -> &str {
+            "Reports once, then never finishes"
+        }
+
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+            std::future::pending().await
+        }
+
+        async fn execute_with_progress(
+            &self,
+            params: serde_json::Value,
+            progress: &ProgressReporter,
+        ) -> Result<ToolResult, anyhow::Error> {
+            progress.report("started");
+            self.execute(params).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_applies_registry_timeout() {
+        let registry = Registry::new().with_timeout(std::time::Duration::from_secs(5));
+        let (reporter, rx) = ProgressReporter::channel();
+        let result = reporter
+            .run(&registry, &StuckTool, serde_json::json!({}))
+            .await;
+
+        assert!(result.is_error);
+        assert!(result.content.contains("timed out"));
+        let events = collect(rx).await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].message, "started");
+        assert!(events[1].done);
+    }
+}
@@ -0,0 +1,158 @@
+// ABOUTME: RegexRedactor - wraps any tool and masks secrets in its results.
+// ABOUTME: Matches AWS keys, bearer tokens and API keys by default.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use super::{ProgressReporter, Tool, ToolResult};
+
+/// What a masked secret is replaced with.
+pub const REDACTED: &str = "***";
+
+/// Patterns [`RegexRedactor::new`] masks: AWS access key IDs and secret
+/// keys, bearer tokens, and `sk-` style and GitHub API keys.
+pub const DEFAULT_REDACT_PATTERNS: &[&str] = &[
+    r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b",
+    r"(?i)aws_secret_access_key\s*[=:]\s*\S+",
+    r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*",
+    r"\bsk-[A-Za-z0-9_\-]{20,}",
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+];
+
+/// Wraps a tool and replaces text matching secret patterns with
+/// [`REDACTED`] in its results: the content and every string in the
+/// metadata.
+///
+/// Results are masked as the wrapper's `execute` returns them, as are the
+/// messages of errors it returns, so calling it directly is as safe as
+/// through a [`Registry`](super::Registry). Its [`Tool::redact`] masks
+/// after the wrapped tool's own redaction.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use mux::tool::RegexRedactor;
+/// use mux::tools::BashTool;
+///
+/// let bash = RegexRedactor::new(Arc::new(BashTool::new()))
+///     .with_patterns([regex::Regex::new(r"internal-[0-9]+").unwrap()]);
+/// ```
+pub struct RegexRedactor {
+    inner: Arc<dyn Tool>,
+    patterns: Vec<Regex>,
+}
+
+impl RegexRedactor {
+    /// Wrap `inner`, masking [`DEFAULT_REDACT_PATTERNS`].
+    pub fn new(inner: Arc<dyn Tool>) -> Self {
+        let patterns = DEFAULT_REDACT_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).expect("default redact patterns are valid"))
+            .collect();
+        Self { inner, patterns }
+    }
+
+    /// Mask `patterns` as well.
+    pub fn with_patterns(mut self, patterns: impl IntoIterator<Item = Regex>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// `text` with every match replaced by [`REDACTED`].
+    pub fn mask(&self, text: &str) -> String {
+        let mut masked = text.to_string();
+        for pattern in &self.patterns {
+            if pattern.is_match(&masked) {
+                masked = pattern.replace_all(&masked, REDACTED).into_owned();
+            }
+        }
+        masked
+    }
+
+    /// `result` with its content and metadata masked.
+    fn mask_result(&self, mut result: ToolResult) -> ToolResult {
+        result.content = self.mask(&result.content);
+        for value in result.metadata.values_mut() {
+            self.mask_value(value);
+        }
+        result
+    }
+
+    /// `outcome` of running the wrapped tool, with any secrets masked.
+    fn mask_outcome(
+        &self,
+        outcome: Result<ToolResult, anyhow::Error>,
+    ) -> Result<ToolResult, anyhow::Error> {
+        match outcome {
+            Ok(result) => Ok(self.mask_result(result)),
+            Err(e) => Err(anyhow::anyhow!(self.mask(&format!("{:#}", e)))),
+        }
+    }
+
+    fn mask_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => *s = self.mask(s),
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.mask_value(item);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for field in fields.values_mut() {
+                    self.mask_value(field);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for RegexRedactor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        self.inner.schema()
+    }
+
+    fn requires_approval(&self, params: &serde_json::Value) -> bool {
+        self.inner.requires_approval(params)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn parallel_safe(&self) -> bool {
+        self.inner.parallel_safe()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.inner.cacheable()
+    }
+
+    fn redact(&self, result: ToolResult) -> ToolResult {
+        self.mask_result(self.inner.redact(result))
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        self.mask_outcome(self.inner.execute(params).await)
+    }
+
+    async fn execute_with_progress(
+        &self,
+        params: serde_json::Value,
+        progress: &ProgressReporter,
+    ) -> Result<ToolResult, anyhow::Error> {
+        self.mask_outcome(self.inner.execute_with_progress(params, progress).await)
+    }
+}
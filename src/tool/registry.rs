@@ -1,20 +1,79 @@
 // ABOUTME: Implements the Registry - a thread-safe container for discovering
 // ABOUTME: and managing available tools at runtime.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
+use std::time::Duration;
 
+use async_trait::async_trait;
+use futures::FutureExt;
 use tokio::sync::RwLock;
 
-use super::Tool;
-use crate::error::McpError;
-use crate::llm::ToolDefinition;
-use crate::mcp::{McpClient, McpProxyTool};
+use super::{ProgressReporter, Tool, ToolResult, schema_violations};
+use crate::error::{MuxError, ToolError};
+use crate::llm::{ContentBlock, ToolDefinition};
+use crate::mcp::{McpClient, McpProxyTool, McpServerEvent, McpToolInfo, McpToolNaming};
+
+/// A change to the set of tools in a [`Registry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryChange {
+    /// A tool was added, or replaced one with the same name.
+    Registered(String),
+    /// A tool was removed.
+    Unregistered(String),
+}
+
+/// Callback for [`Registry::on_change`].
+pub type ChangeObserver = Arc<dyn Fn(&RegistryChange) + Send + Sync>;
+
+/// What a [`Registry`] does when a tool is registered under a name that is
+/// already taken, e.g. by a builtin and an MCP tool both called `read_file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace the registered tool.
+    #[default]
+    Overwrite,
+    /// Refuse the new tool with [`ToolError::Conflict`].
+    Error,
+    /// Keep the registered tool and skip the new one.
+    KeepFirst,
+    /// Register the new tool with a numeric suffix: `read_file_2`, then
+    /// `read_file_3`, and so on.
+    Rename,
+}
+
+/// What registering a tool did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Registration {
+    /// Registered under its own name.
+    Added(String),
+    /// Registered under its own name, replacing the tool that had it.
+    Replaced(String),
+    /// Not registered, since the name was taken.
+    Skipped(String),
+    /// Registered as `name` since its own name, `original`, was taken.
+    Renamed { original: String, name: String },
+}
+
+impl Registration {
+    /// The name the tool is registered under, or `None` if it was skipped.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Added(name) | Self::Replaced(name) | Self::Renamed { name, .. } => Some(name),
+            Self::Skipped(_) => None,
+        }
+    }
+}
 
 /// A thread-safe registry of tools.
 #[derive(Default)]
 pub struct Registry {
     tools: Arc<RwLock<HashMap<String, Arc<dyn Tool>>>>,
+    timeout: Option<Duration>,
+    schema_validation: bool,
+    conflict_policy: ConflictPolicy,
+    observers: Arc<std::sync::RwLock<Vec<ChangeObserver>>>,
 }
 
 impl Registry {
@@ -23,21 +82,212 @@ impl Registry {
         Self::default()
     }
 
-    /// Register a tool.
+    /// Limit how long any tool may run, unless the tool sets its own
+    /// [`Tool::timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Check tool inputs against each tool's [`Tool::schema`] before running it.
+    ///
+    /// Off by default, since some tools accept looser input than their schema
+    /// describes. When on, an input that doesn't conform never reaches the
+    /// tool: the call returns an error result listing every missing or
+    /// mismatched field, also available as `schema_violations` metadata.
+    pub fn with_schema_validation(mut self, enabled: bool) -> Self {
+        self.schema_validation = enabled;
+        self
+    }
+
+    /// Resolve name clashes with `policy` instead of overwriting.
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// The timeout that applies to `tool`: its own, else the registry's.
+    pub fn timeout_for(&self, tool: &dyn Tool) -> Option<Duration> {
+        tool.timeout().or(self.timeout)
+    }
+
+    /// Execute a tool, enforcing its timeout and, if enabled, its schema.
+    ///
+    /// A call that runs past the timeout is dropped and reported as an error
+    /// `ToolResult` so the agent loop can carry on. Whatever the tool
+    /// returns passes through its [`Tool::redact`].
+    pub async fn execute_tool(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+    ) -> Result<ToolResult, anyhow::Error> {
+        let result = self.execute_unredacted(tool, params, None).await?;
+        Ok(tool.redact(result))
+    }
+
+    /// Execute a tool like [`execute_tool`](Self::execute_tool), running it
+    /// with [`Tool::execute_with_progress`] so it can report to `progress`.
+    ///
+    /// The terminal `done` event is not sent here; see
+    /// [`ProgressReporter::run`].
+    pub async fn execute_tool_with_progress(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+        progress: &ProgressReporter,
+    ) -> Result<ToolResult, anyhow::Error> {
+        let result = self
+            .execute_unredacted(tool, params, Some(progress))
+            .await?;
+        Ok(tool.redact(result))
+    }
+
+    async fn execute_unredacted(
+        &self,
+        tool: &dyn Tool,
+        params: serde_json::Value,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<ToolResult, anyhow::Error> {
+        if self.schema_validation {
+            let violations = schema_violations(&tool.schema(), &params);
+            if !violations.is_empty() {
+                let listed: Vec<String> = violations.iter().map(|v| format!("- {}", v)).collect();
+                let details: Vec<serde_json::Value> = violations
+                    .iter()
+                    .map(|v| serde_json::json!({"path": v.path, "message": v.message}))
+                    .collect();
+                return Ok(ToolResult::error(format!(
+                    "Invalid input for tool '{}':\n{}",
+                    tool.name(),
+                    listed.join("\n")
+                ))
+                .with_metadata("schema_violations", details));
+            }
+        }
+
+        let execute = async {
+            match progress {
+                Some(progress) => tool.execute_with_progress(params, progress).await,
+                None => tool.execute(params).await,
+            }
+        };
+        let Some(timeout) = self.timeout_for(tool) else {
+            return execute.await;
+        };
+
+        match tokio::time::timeout(timeout, execute).await {
+            Ok(result) => result,
+            Err(_) => Ok(ToolResult::error(format!(
+                "Tool '{}' timed out after {:?}",
+                tool.name(),
+                timeout
+            ))),
+        }
+    }
+
+    /// Call `observer` after every change to the tool set.
+    ///
+    /// Observers are shared by all clones of the registry and see changes
+    /// made through any of them, including the tools added by
+    /// [`merge_mcp`](Self::merge_mcp), one change per tool. They run on the
+    /// task making the change, after it is visible, so they should be quick:
+    /// hand the change off to a channel for anything slow.
+    pub fn on_change(&self, observer: impl Fn(&RegistryChange) + Send + Sync + 'static) {
+        self.observers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::new(observer));
+    }
+
+    fn notify(&self, change: RegistryChange) {
+        let observers = self
+            .observers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for observer in observers {
+            observer(&change);
+        }
+    }
+
+    /// Register a tool, resolving a name clash by the registry's
+    /// [`ConflictPolicy`].
+    ///
+    /// Under [`ConflictPolicy::Error`] a tool whose name is taken is skipped
+    /// with a warning on stderr; use [`try_register`](Self::try_register) to
+    /// handle the clash.
     pub async fn register<T: Tool + 'static>(&self, tool: T) {
         self.register_arc(Arc::new(tool)).await;
     }
 
-    /// Register a tool from an Arc.
+    /// Register a tool from an Arc, like [`register`](Self::register).
     pub async fn register_arc(&self, tool: Arc<dyn Tool>) {
+        if let Err(e) = self.try_register_arc(tool).await {
+            eprintln!("Warning: skipping tool: {}", e);
+        }
+    }
+
+    /// Register a tool and report what happened to it.
+    pub async fn try_register<T: Tool + 'static>(
+        &self,
+        tool: T,
+    ) -> Result<Registration, ToolError> {
+        self.try_register_arc(Arc::new(tool)).await
+    }
+
+    /// Register a tool from an Arc and report what happened to it.
+    ///
+    /// Fails with [`ToolError::Conflict`] only under [`ConflictPolicy::Error`].
+    pub async fn try_register_arc(&self, tool: Arc<dyn Tool>) -> Result<Registration, ToolError> {
         let mut tools = self.tools.write().await;
+        let registration = self.insert(&mut tools, tool)?;
+        drop(tools);
+
+        if let Some(name) = registration.name() {
+            self.notify(RegistryChange::Registered(name.to_string()));
+        }
+        Ok(registration)
+    }
+
+    /// Add `tool` to the locked `tools`, resolving a clash by the registry's
+    /// [`ConflictPolicy`].
+    fn insert(
+        &self,
+        tools: &mut HashMap<String, Arc<dyn Tool>>,
+        tool: Arc<dyn Tool>,
+    ) -> Result<Registration, ToolError> {
+        let name = tool.name().to_string();
+        let (registration, tool) = match self.conflict_policy {
+            _ if !tools.contains_key(&name) => (Registration::Added(name), tool),
+            ConflictPolicy::Overwrite => (Registration::Replaced(name), tool),
+            ConflictPolicy::Error => return Err(ToolError::Conflict(name)),
+            ConflictPolicy::KeepFirst => return Ok(Registration::Skipped(name)),
+            ConflictPolicy::Rename => {
+                let renamed = (2..)
+                    .map(|n| format!("{}_{}", name, n))
+                    .find(|candidate| !tools.contains_key(candidate))
+                    .expect("some suffix is free");
+                let tool: Arc<dyn Tool> = Arc::new(RenamedTool {
+                    name: renamed.clone(),
+                    inner: tool,
+                });
+                let registration = Registration::Renamed {
+                    original: name,
+                    name: renamed,
+                };
+                (registration, tool)
+            }
+        };
         tools.insert(tool.name().to_string(), tool);
+        Ok(registration)
     }
 
     /// Unregister a tool by name.
     pub async fn unregister(&self, name: &str) {
-        let mut tools = self.tools.write().await;
-        tools.remove(name);
+        let removed = self.tools.write().await.remove(name);
+        if removed.is_some() {
+            self.notify(RegistryChange::Unregistered(name.to_string()));
+        }
     }
 
     /// Get a tool by name.
@@ -54,6 +304,30 @@ impl Registry {
         names
     }
 
+    /// List up to `limit` tool names starting at `offset`, in the same
+    /// alphabetical order as [`list`](Self::list). Pass the previous
+    /// offset plus `limit` to get the next page; a short page is the last.
+    pub async fn list_page(&self, offset: usize, limit: usize) -> Vec<String> {
+        let tools = self.tools.read().await;
+        sorted_page(&tools, offset, limit)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// List the names containing `query`, ignoring case, sorted
+    /// alphabetically.
+    pub async fn list_matching(&self, query: &str) -> Vec<String> {
+        let query = query.to_lowercase();
+        let tools = self.tools.read().await;
+        let mut names: Vec<_> = tools
+            .keys()
+            .filter(|name| name.to_lowercase().contains(&query))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Get all registered tools.
     pub async fn all(&self) -> Vec<Arc<dyn Tool>> {
         let tools = self.tools.read().await;
@@ -69,31 +343,271 @@ impl Registry {
     /// Convert all tools to LLM tool definitions.
     pub async fn to_definitions(&self) -> Vec<ToolDefinition> {
         let tools = self.tools.read().await;
-        tools
-            .values()
-            .map(|t| ToolDefinition {
-                name: t.name().to_string(),
-                description: t.description().to_string(),
-                input_schema: t.schema(),
-            })
+        tools.values().map(|t| definition(t.as_ref())).collect()
+    }
+
+    /// Definitions for the tools [`list_page`](Self::list_page) would name.
+    /// Only the tools on the page build their schemas.
+    pub async fn definitions_page(&self, offset: usize, limit: usize) -> Vec<ToolDefinition> {
+        let tools = self.tools.read().await;
+        sorted_page(&tools, offset, limit)
+            .map(|(_, tool)| definition(tool.as_ref()))
             .collect()
     }
 
-    /// Merge tools from an MCP client into the registry.
+    /// Split calls to the tools named `names` into groups that may run
+    /// concurrently, as index ranges in call order.
+    ///
+    /// Consecutive calls to [parallel-safe](Tool::parallel_safe) tools share
+    /// a group, and a call to any other tool gets a group of its own, so it
+    /// waits for the calls before it and runs alone. Unknown tools count as
+    /// parallel-safe.
+    pub async fn parallel_groups(&self, names: &[&str]) -> Vec<Range<usize>> {
+        let tools = self.tools.read().await;
+        let mut groups = Vec::new();
+        let mut start = 0;
+        for (i, name) in names.iter().enumerate() {
+            if tools.get(*name).is_some_and(|tool| !tool.parallel_safe()) {
+                if start < i {
+                    groups.push(start..i);
+                }
+                groups.push(i..i + 1);
+                start = i + 1;
+            }
+        }
+        if start < names.len() {
+            groups.push(start..names.len());
+        }
+        groups
+    }
+
+    /// Execute a batch of tool calls, concurrently where the tools allow.
+    ///
+    /// Takes the `ToolUse` blocks from a model response (other blocks are
+    /// skipped) and returns one `ToolResult` block per call, in request order.
+    /// Calls run in the groups given by [`parallel_groups`](Self::parallel_groups).
+    /// A failing call never aborts the batch: unknown tools, errors and panics
+    /// become results marked `is_error` so the model can react to the mix.
+    pub async fn execute_batch(&self, blocks: &[ContentBlock]) -> Vec<ContentBlock> {
+        let calls: Vec<_> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } => Some((id, name, input)),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<&str> = calls.iter().map(|(_, name, _)| name.as_str()).collect();
+
+        let mut results = Vec::with_capacity(calls.len());
+        for group in self.parallel_groups(&names).await {
+            let futures = calls[group].iter().map(|(id, name, input)| async move {
+                let result = match self.get(name).await {
+                    Some(tool) => {
+                        std::panic::AssertUnwindSafe(self.execute_tool(&*tool, (*input).clone()))
+                            .catch_unwind()
+                            .await
+                            .unwrap_or_else(|_| Err(anyhow::anyhow!("Tool '{}' panicked", name)))
+                            .unwrap_or_else(|e| ToolResult::error(e.to_string()))
+                    }
+                    None => ToolResult::error(format!("Tool '{}' not found", name)),
+                };
+
+                if result.is_error {
+                    ContentBlock::tool_error(*id, &result.content)
+                } else {
+                    ContentBlock::tool_result(*id, &result.content)
+                }
+            });
+            results.extend(futures::future::join_all(futures).await);
+        }
+        results
+    }
+
+    /// Merge tools from an MCP client into the registry, named by
+    /// `naming`. Passing `prefix` or `Some(prefix)` names them
+    /// `{prefix}_{tool}`, and `None` keeps the server's names.
+    ///
+    /// Name clashes are resolved by the registry's [`ConflictPolicy`], and
+    /// the result says what happened to each tool. Under
+    /// [`ConflictPolicy::Error`] a clash with an already registered tool, or
+    /// two tools from the server with the same name, fails the merge before
+    /// any tool is added.
     pub async fn merge_mcp(
         &self,
         client: Arc<McpClient>,
-        prefix: Option<&str>,
-    ) -> Result<usize, McpError> {
+        naming: impl Into<McpToolNaming>,
+    ) -> Result<Vec<Registration>, MuxError> {
+        let tools = client.list_tools().await?;
+        Ok(self.merge_mcp_tools(&client, tools, &naming.into()).await?)
+    }
+
+    /// Merge tools from an MCP client, then keep them in step with the
+    /// server: when it announces its tool list changed, the tools merged
+    /// from it are replaced with the new list.
+    ///
+    /// Starts the client [`listen`](McpClient::listen)ing for notifications.
+    /// Must be called within a Tokio runtime.
+    pub async fn track_mcp(
+        &self,
+        client: Arc<McpClient>,
+        naming: impl Into<McpToolNaming>,
+    ) -> Result<Vec<Registration>, MuxError> {
         let tools = client.list_tools().await?;
-        let count = tools.len();
+        let naming = naming.into();
+        let registrations = self.merge_mcp_tools(&client, tools, &naming).await?;
+        let mut merged = registered_names(&registrations);
+
+        // Apply changes in order on one task; the observer can't await
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        client.on_event(move |event| {
+            if let McpServerEvent::ToolsChanged(tools) = event {
+                let _ = tx.send(tools.clone());
+            }
+        });
+
+        let registry = self.clone();
+        let weak_client = Arc::downgrade(&client);
+        tokio::spawn(async move {
+            while let Some(tools) = rx.recv().await {
+                let Some(client) = weak_client.upgrade() else {
+                    return;
+                };
+                for name in merged.drain(..) {
+                    registry.unregister(&name).await;
+                }
+                merged = match registry.merge_mcp_tools(&client, tools, &naming).await {
+                    Ok(registrations) => registered_names(&registrations),
+                    Err(e) => {
+                        eprintln!(
+                            "Warning: Failed to update tools from {}: {}",
+                            client.name(),
+                            e
+                        );
+                        Vec::new()
+                    }
+                };
+            }
+        });
+        client.listen();
+
+        Ok(registrations)
+    }
+
+    /// Register a proxy for each tool, reporting what happened to each.
+    async fn merge_mcp_tools(
+        &self,
+        client: &Arc<McpClient>,
+        tools: Vec<McpToolInfo>,
+        naming: &McpToolNaming,
+    ) -> Result<Vec<Registration>, ToolError> {
+        let proxies: Vec<Arc<dyn Tool>> = tools
+            .into_iter()
+            .map(|info| {
+                Arc::new(McpProxyTool::named(client.clone(), info, naming)) as Arc<dyn Tool>
+            })
+            .collect();
+
+        // Check and insert under one lock, so the set is merged whole or not at all
+        let mut registered = self.tools.write().await;
+        if self.conflict_policy == ConflictPolicy::Error {
+            let mut incoming = HashSet::new();
+            if let Some(taken) = proxies
+                .iter()
+                .find(|p| registered.contains_key(p.name()) || !incoming.insert(p.name()))
+            {
+                return Err(ToolError::Conflict(taken.name().to_string()));
+            }
+        }
+
+        let mut registrations = Vec::with_capacity(proxies.len());
+        for proxy in proxies {
+            registrations.push(self.insert(&mut registered, proxy)?);
+        }
+        drop(registered);
 
-        for info in tools {
-            let proxy = McpProxyTool::new(client.clone(), info, prefix);
-            self.register(proxy).await;
+        for name in registered_names(&registrations) {
+            self.notify(RegistryChange::Registered(name));
         }
+        Ok(registrations)
+    }
+}
+
+fn registered_names(registrations: &[Registration]) -> Vec<String> {
+    registrations
+        .iter()
+        .filter_map(|r| r.name().map(str::to_string))
+        .collect()
+}
+
+/// A tool registered under a name other than its own.
+struct RenamedTool {
+    name: String,
+    inner: Arc<dyn Tool>,
+}
+
+#[async_trait]
+impl Tool for RenamedTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        self.inner.schema()
+    }
+
+    fn requires_approval(&self, params: &serde_json::Value) -> bool {
+        self.inner.requires_approval(params)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn parallel_safe(&self) -> bool {
+        self.inner.parallel_safe()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.inner.cacheable()
+    }
+
+    fn redact(&self, result: ToolResult) -> ToolResult {
+        self.inner.redact(result)
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        self.inner.execute(params).await
+    }
+
+    async fn execute_with_progress(
+        &self,
+        params: serde_json::Value,
+        progress: &ProgressReporter,
+    ) -> Result<ToolResult, anyhow::Error> {
+        self.inner.execute_with_progress(params, progress).await
+    }
+}
+
+/// The entries from `offset` to `offset + limit`, ordered by name.
+fn sorted_page(
+    tools: &HashMap<String, Arc<dyn Tool>>,
+    offset: usize,
+    limit: usize,
+) -> impl Iterator<Item = (&String, &Arc<dyn Tool>)> {
+    let mut entries: Vec<_> = tools.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries.into_iter().skip(offset).take(limit)
+}
 
-        Ok(count)
+fn definition(tool: &dyn Tool) -> ToolDefinition {
+    ToolDefinition {
+        name: tool.name().to_string(),
+        description: tool.description().to_string(),
+        input_schema: tool.schema(),
     }
 }
 
@@ -101,6 +615,10 @@ impl Clone for Registry {
     fn clone(&self) -> Self {
         Self {
             tools: Arc::clone(&self.tools),
+            timeout: self.timeout,
+            schema_validation: self.schema_validation,
+            conflict_policy: self.conflict_policy,
+            observers: Arc::clone(&self.observers),
         }
     }
 }
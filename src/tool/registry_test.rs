@@ -2,6 +2,9 @@
 // ABOUTME: Uses a mock tool for testing.
 
 use super::*;
+use crate::error::ToolError;
+use crate::llm::ContentBlock;
+use std::sync::Arc;
 
 /// A simple test tool.
 struct EchoTool;
@@ -80,6 +83,150 @@ async fn test_to_definitions() {
     assert_eq!(defs[0].description, "Echoes input back");
 }
 
+async fn registry_of(names: &[&'static str]) -> Registry {
+    let registry = Registry::new();
+    for &name in names {
+        registry
+            .register(RendezvousTool {
+                name,
+                barrier: Arc::new(tokio::sync::Barrier::new(1)),
+                delay_ms: 0,
+                fail: false,
+            })
+            .await;
+    }
+    registry
+}
+
+#[tokio::test]
+async fn test_list_page_walks_tools_in_order() {
+    let registry = registry_of(&["fs_write", "echo", "fs_read", "bash", "grep"]).await;
+    assert_eq!(registry.count().await, 5);
+
+    assert_eq!(registry.list_page(0, 2).await, ["bash", "echo"]);
+    assert_eq!(registry.list_page(2, 2).await, ["fs_read", "fs_write"]);
+    assert_eq!(registry.list_page(4, 2).await, ["grep"]);
+    assert!(registry.list_page(6, 2).await.is_empty());
+
+    let names: Vec<_> = registry
+        .definitions_page(1, 3)
+        .await
+        .into_iter()
+        .map(|d| d.name)
+        .collect();
+    assert_eq!(names, ["echo", "fs_read", "fs_write"]);
+}
+
+#[tokio::test]
+async fn test_list_matching_ignores_case() {
+    let registry = registry_of(&["fs_write", "echo", "FS_list", "bash"]).await;
+
+    assert_eq!(registry.list_matching("fs_").await, ["FS_list", "fs_write"]);
+    assert!(registry.list_matching("missing").await.is_empty());
+    assert_eq!(registry.list_matching("").await.len(), 4);
+}
+
+/// An echo tool with its own description, to tell registered copies apart.
+struct LabelledEcho(&'static str);
+
+#[async_trait::async_trait]
+impl Tool for LabelledEcho {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn description(&self) -> &str {
+        self.0
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        serde_json::json!({"type": "object"})
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        Ok(ToolResult::text(self.0))
+    }
+}
+
+async fn description_of(registry: &Registry, name: &str) -> String {
+    registry.get(name).await.unwrap().description().to_string()
+}
+
+#[tokio::test]
+async fn test_conflict_policy_overwrite() {
+    let registry = Registry::new();
+    let first = registry.try_register(LabelledEcho("first")).await.unwrap();
+    let second = registry.try_register(LabelledEcho("second")).await.unwrap();
+
+    assert_eq!(first, Registration::Added("echo".into()));
+    assert_eq!(second, Registration::Replaced("echo".into()));
+    assert_eq!(description_of(&registry, "echo").await, "second");
+}
+
+#[tokio::test]
+async fn test_conflict_policy_error() {
+    let registry = Registry::new().with_conflict_policy(ConflictPolicy::Error);
+    registry.register(LabelledEcho("first")).await;
+
+    let err = registry
+        .try_register(LabelledEcho("second"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ToolError::Conflict(ref name) if name == "echo"));
+    assert_eq!(description_of(&registry, "echo").await, "first");
+}
+
+#[tokio::test]
+async fn test_register_skips_conflict_under_error_policy() {
+    let registry = Registry::new().with_conflict_policy(ConflictPolicy::Error);
+    registry.register(LabelledEcho("first")).await;
+    registry.register(LabelledEcho("second")).await;
+
+    assert_eq!(description_of(&registry, "echo").await, "first");
+    assert_eq!(registry.count().await, 1);
+}
+
+#[tokio::test]
+async fn test_conflict_policy_keep_first() {
+    let registry = Registry::new().with_conflict_policy(ConflictPolicy::KeepFirst);
+    let changes = record_changes(&registry);
+    registry.register(LabelledEcho("first")).await;
+
+    let second = registry.try_register(LabelledEcho("second")).await.unwrap();
+
+    assert_eq!(second, Registration::Skipped("echo".into()));
+    assert_eq!(second.name(), None);
+    assert_eq!(description_of(&registry, "echo").await, "first");
+    assert_eq!(changes.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_conflict_policy_rename() {
+    let registry = Registry::new().with_conflict_policy(ConflictPolicy::Rename);
+    registry.register(LabelledEcho("first")).await;
+
+    let second = registry.try_register(LabelledEcho("second")).await.unwrap();
+    let third = registry.try_register(LabelledEcho("third")).await.unwrap();
+
+    assert_eq!(
+        second,
+        Registration::Renamed {
+            original: "echo".into(),
+            name: "echo_2".into()
+        }
+    );
+    assert_eq!(third.name(), Some("echo_3"));
+    assert_eq!(registry.list().await, ["echo", "echo_2", "echo_3"]);
+
+    let renamed = registry.get("echo_2").await.unwrap();
+    assert_eq!(renamed.name(), "echo_2");
+    let result = registry
+        .execute_tool(renamed.as_ref(), serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(result.content, "second");
+}
+
 #[tokio::test]
 async fn test_clone_shares_state() {
     let registry = Registry::new();
@@ -88,3 +235,508 @@ async fn test_clone_shares_state() {
     registry.register(EchoTool).await;
     assert_eq!(clone.count().await, 1);
 }
+
+/// Waits until all three batch members are running, then succeeds or fails.
+struct RendezvousTool {
+    name: &'static str,
+    barrier: Arc<tokio::sync::Barrier>,
+    delay_ms: u64,
+    fail: bool,
+}
+
+#[async_trait::async_trait]
+impl Tool for RendezvousTool {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        "Meets the other batch members"
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        serde_json::json!({"type": "object"})
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        // Only completes if the batch really runs concurrently
+        self.barrier.wait().await;
+        tokio::time::sleep(std::time::Duration::from_millis(self.delay_ms)).await;
+        if self.fail {
+            anyhow::bail!("{} is broken", self.name);
+        }
+        Ok(ToolResult::text(format!("{} ok", self.name)))
+    }
+}
+
+fn tool_use(id: &str, name: &str) -> ContentBlock {
+    ContentBlock::ToolUse {
+        id: id.into(),
+        name: name.into(),
+        input: serde_json::json!({}),
+    }
+}
+
+#[tokio::test]
+async fn test_execute_batch_keeps_partial_results_in_order() {
+    let registry = Registry::new();
+    let barrier = Arc::new(tokio::sync::Barrier::new(3));
+    // Finish in reverse request order to prove results are reordered
+    for (name, delay_ms, fail) in [
+        ("first", 30, false),
+        ("second", 15, true),
+        ("third", 0, false),
+    ] {
+        registry
+            .register(RendezvousTool {
+                name,
+                barrier: barrier.clone(),
+                delay_ms,
+                fail,
+            })
+            .await;
+    }
+
+    let blocks = vec![
+        ContentBlock::text("Running three tools"),
+        tool_use("tu_1", "first"),
+        tool_use("tu_2", "second"),
+        tool_use("tu_3", "third"),
+    ];
+    let results = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        registry.execute_batch(&blocks),
+    )
+    .await
+    .expect("batch should run tools concurrently");
+
+    let summary: Vec<(&str, &str, bool)> = results
+        .iter()
+        .map(|block| match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+                ..
+            } => (tool_use_id.as_str(), content.as_str(), *is_error),
+            other => panic!("Expected tool result, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("tu_1", "first ok", false),
+            ("tu_2", "second is broken", true),
+            ("tu_3", "third ok", false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_parallel_groups_isolate_unsafe_tools() {
+    let registry = Registry::new();
+    registry.register(EchoTool).await;
+    registry.register(crate::tools::WriteFileTool).await;
+    registry.register(crate::tools::EditTool).await;
+
+    let groups = registry
+        .parallel_groups(&["echo", "missing", "write_file", "edit", "echo"])
+        .await;
+
+    assert_eq!(groups, vec![0..2, 2..3, 3..4, 4..5]);
+    assert!(registry.parallel_groups(&[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_execute_batch_unknown_tool_is_error() {
+    let registry = Registry::new();
+    registry.register(EchoTool).await;
+
+    let results = registry
+        .execute_batch(&[tool_use("tu_1", "missing"), tool_use("tu_2", "echo")])
+        .await;
+
+    assert_eq!(results.len(), 2);
+    assert!(matches!(
+        &results[0],
+        ContentBlock::ToolResult { is_error: true, content, .. } if content.contains("missing")
+    ));
+    assert!(matches!(
+        &results[1],
+        ContentBlock::ToolResult {
+            is_error: false,
+            ..
+        }
+    ));
+}
+
+/// Sleeps far longer than any test timeout.
+struct SleepyTool {
+    own_timeout: Option<std::time::Duration>,
+}
+
+#[async_trait::async_trait]
+impl Tool for SleepyTool {
+    fn name(&self) -> &str {
+        "sleepy"
+    }
+
+    fn description(&self) -> &str {
+        "Never finishes in time"
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        serde_json::json!({"type": "object"})
+    }
+
+    fn timeout(&self) -> Option<std::time::Duration> {
+        self.own_timeout
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        Ok(ToolResult::text("woke up"))
+    }
+}
+
+#[tokio::test]
+async fn test_execute_tool_times_out() {
+    let registry = Registry::new().with_timeout(std::time::Duration::from_millis(20));
+    let tool = SleepyTool { own_timeout: None };
+
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        registry.execute_tool(&tool, serde_json::json!({})),
+    )
+    .await
+    .expect("registry timeout should fire first")
+    .unwrap();
+
+    assert!(result.is_error);
+    assert_eq!(result.content, "Tool 'sleepy' timed out after 20ms");
+}
+
+#[tokio::test]
+async fn test_tool_timeout_overrides_registry() {
+    let registry = Registry::new().with_timeout(std::time::Duration::from_secs(60));
+    let tool = SleepyTool {
+        own_timeout: Some(std::time::Duration::from_millis(10)),
+    };
+    assert_eq!(
+        registry.timeout_for(&tool),
+        Some(std::time::Duration::from_millis(10))
+    );
+
+    let result = registry
+        .execute_tool(&tool, serde_json::json!({}))
+        .await
+        .unwrap();
+    assert!(result.content.contains("timed out after 10ms"));
+
+    // Fast tools are unaffected, and no timeout applies by default
+    assert_eq!(Registry::new().timeout_for(&EchoTool), None);
+    let result = registry
+        .execute_tool(&EchoTool, serde_json::json!({"message": "hi"}))
+        .await
+        .unwrap();
+    assert_eq!(result.content, "hi");
+}
+
+#[tokio::test]
+async fn test_execute_batch_applies_timeout() {
+    let registry = Registry::new().with_timeout(std::time::Duration::from_millis(20));
+    registry.register(SleepyTool { own_timeout: None }).await;
+    registry.register(EchoTool).await;
+
+    let results = registry
+        .execute_batch(&[
+            tool_use("tu_1", "sleepy"),
+            ContentBlock::ToolUse {
+                id: "tu_2".into(),
+                name: "echo".into(),
+                input: serde_json::json!({"message": "still here"}),
+            },
+        ])
+        .await;
+
+    assert!(matches!(
+        &results[0],
+        ContentBlock::ToolResult { is_error: true, content, .. } if content.contains("timed out")
+    ));
+    assert!(matches!(
+        &results[1],
+        ContentBlock::ToolResult { is_error: false, content, .. } if content == "still here"
+    ));
+}
+
+#[tokio::test]
+async fn test_schema_validation_rejects_missing_field() {
+    let registry = Registry::new().with_schema_validation(true);
+
+    let result = registry
+        .execute_tool(&EchoTool, serde_json::json!({"text": "hi"}))
+        .await
+        .unwrap();
+    assert!(result.is_error);
+    assert_eq!(
+        result.content,
+        "Invalid input for tool 'echo':\n- $: missing required property 'message'"
+    );
+    assert_eq!(result.metadata["schema_violations"][0]["path"], "$");
+
+    // Valid input still runs the tool
+    let result = registry
+        .execute_tool(&EchoTool, serde_json::json!({"message": "hi"}))
+        .await
+        .unwrap();
+    assert_eq!(result.content, "hi");
+}
+
+#[tokio::test]
+async fn test_schema_validation_rejects_wrong_type_in_batch() {
+    let registry = Registry::new().with_schema_validation(true);
+    registry.register(EchoTool).await;
+
+    let results = registry
+        .execute_batch(&[ContentBlock::ToolUse {
+            id: "tu_1".into(),
+            name: "echo".into(),
+            input: serde_json::json!({"message": 42}),
+        }])
+        .await;
+    assert!(matches!(
+        &results[0],
+        ContentBlock::ToolResult { content, is_error: true, .. }
+            if content.contains("$.message: expected string, got integer")
+    ));
+
+    // Clones keep the setting; without it the tool sees the loose input
+    assert!(
+        registry
+            .clone()
+            .execute_tool(&EchoTool, serde_json::json!({}))
+            .await
+            .unwrap()
+            .is_error
+    );
+    let result = Registry::new()
+        .execute_tool(&EchoTool, serde_json::json!({"message": 42}))
+        .await
+        .unwrap();
+    assert!(!result.is_error);
+}
+
+/// Record every change a registry reports.
+fn record_changes(registry: &Registry) -> Arc<std::sync::Mutex<Vec<RegistryChange>>> {
+    let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = changes.clone();
+    registry.on_change(move |change| sink.lock().unwrap().push(change.clone()));
+    changes
+}
+
+#[tokio::test]
+async fn test_on_change_reports_register_and_unregister() {
+    let registry = Registry::new();
+    let changes = record_changes(&registry);
+
+    registry.register(EchoTool).await;
+    registry.unregister("echo").await;
+    // Removing a tool that isn't there changes nothing
+    registry.unregister("echo").await;
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            RegistryChange::Registered("echo".into()),
+            RegistryChange::Unregistered("echo".into()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_on_change_is_shared_by_clones() {
+    let registry = Registry::new();
+    let changes = record_changes(&registry);
+
+    registry.clone().register(EchoTool).await;
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![RegistryChange::Registered("echo".into())]
+    );
+}
+
+#[tokio::test]
+async fn test_on_change_reports_merged_mcp_tools() {
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    let transport = MockTransport::new().respond(
+        "tools/list",
+        serde_json::json!({"tools": [
+            {"name": "read", "inputSchema": {"type": "object"}},
+            {"name": "write", "inputSchema": {"type": "object"}}
+        ]}),
+    );
+    let client = Arc::new(crate::mcp::McpClient::from_transport(
+        mock_config(),
+        Arc::new(transport),
+    ));
+
+    let registry = Registry::new();
+    let changes = record_changes(&registry);
+    registry.merge_mcp(client, Some("fs")).await.unwrap();
+
+    let mut changes = changes.lock().unwrap().clone();
+    changes.sort_by_key(|c| format!("{:?}", c));
+    assert_eq!(
+        changes,
+        vec![
+            RegistryChange::Registered("fs_read".into()),
+            RegistryChange::Registered("fs_write".into()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_merge_mcp_uses_naming() {
+    use crate::mcp::McpToolNaming;
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    let transport = MockTransport::new().respond(
+        "tools/list",
+        serde_json::json!({"tools": [{"name": "read", "inputSchema": {"type": "object"}}]}),
+    );
+    let client = Arc::new(crate::mcp::McpClient::from_transport(
+        mock_config(),
+        Arc::new(transport),
+    ));
+
+    let registry = Registry::new();
+    let naming = McpToolNaming::server_name().with_separator("__");
+    registry.merge_mcp(client.clone(), naming).await.unwrap();
+    registry
+        .merge_mcp(client, McpToolNaming::none())
+        .await
+        .unwrap();
+
+    assert_eq!(registry.list().await, ["mock__read", "read"]);
+}
+
+#[tokio::test]
+async fn test_merge_mcp_conflicts_follow_policy() {
+    use crate::mcp::McpToolNaming;
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    let client = || {
+        let transport = MockTransport::new().respond(
+            "tools/list",
+            serde_json::json!({"tools": [
+                {"name": "echo", "inputSchema": {"type": "object"}},
+                {"name": "grep", "inputSchema": {"type": "object"}}
+            ]}),
+        );
+        Arc::new(crate::mcp::McpClient::from_transport(
+            mock_config(),
+            Arc::new(transport),
+        ))
+    };
+
+    let strict = Registry::new().with_conflict_policy(ConflictPolicy::Error);
+    strict.register(LabelledEcho("builtin")).await;
+    let err = strict
+        .merge_mcp(client(), McpToolNaming::none())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("'echo' is already registered"));
+    // Nothing from the server was merged
+    assert_eq!(strict.list().await, ["echo"]);
+
+    let lenient = Registry::new().with_conflict_policy(ConflictPolicy::KeepFirst);
+    lenient.register(LabelledEcho("builtin")).await;
+    let merged = lenient
+        .merge_mcp(client(), McpToolNaming::none())
+        .await
+        .unwrap();
+    assert_eq!(
+        merged,
+        [
+            Registration::Skipped("echo".into()),
+            Registration::Added("grep".into())
+        ]
+    );
+    assert_eq!(description_of(&lenient, "echo").await, "builtin");
+}
+
+#[tokio::test]
+async fn test_merge_mcp_refuses_duplicate_server_tools_under_error_policy() {
+    use crate::mcp::McpToolNaming;
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    let transport = MockTransport::new().respond(
+        "tools/list",
+        serde_json::json!({"tools": [
+            {"name": "grep", "inputSchema": {"type": "object"}},
+            {"name": "read", "inputSchema": {"type": "object"}},
+            {"name": "read", "inputSchema": {"type": "object"}}
+        ]}),
+    );
+    let client = Arc::new(crate::mcp::McpClient::from_transport(
+        mock_config(),
+        Arc::new(transport),
+    ));
+
+    let registry = Registry::new().with_conflict_policy(ConflictPolicy::Error);
+    let err = registry
+        .merge_mcp(client, McpToolNaming::none())
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("'read' is already registered"));
+    assert_eq!(registry.count().await, 0);
+}
+
+#[tokio::test]
+async fn test_track_mcp_follows_tool_list_changes() {
+    use crate::mcp::McpNotification;
+    use crate::mcp::test_transport::{MockTransport, mock_config};
+
+    let (transport, server) = MockTransport::new()
+        .respond(
+            "tools/list",
+            serde_json::json!({"tools": [{"name": "read", "inputSchema": {"type": "object"}}]}),
+        )
+        .with_notifications();
+    let transport = Arc::new(transport);
+    let client = Arc::new(crate::mcp::McpClient::from_transport(
+        mock_config(),
+        transport.clone(),
+    ));
+
+    let registry = Registry::new();
+    registry.track_mcp(client, Some("fs")).await.unwrap();
+    assert_eq!(registry.list().await, vec!["fs_read"]);
+
+    let (changed_tx, mut changed) = tokio::sync::mpsc::unbounded_channel();
+    registry.on_change(move |change| {
+        let _ = changed_tx.send(change.clone());
+    });
+    transport.set_response(
+        "tools/list",
+        serde_json::json!({"tools": [{"name": "write", "inputSchema": {"type": "object"}}]}),
+    );
+    server
+        .send(McpNotification::new(
+            "notifications/tools/list_changed",
+            None,
+        ))
+        .unwrap();
+
+    assert_eq!(
+        changed.recv().await.unwrap(),
+        RegistryChange::Unregistered("fs_read".into())
+    );
+    assert_eq!(
+        changed.recv().await.unwrap(),
+        RegistryChange::Registered("fs_write".into())
+    );
+    assert_eq!(registry.list().await, vec!["fs_write"]);
+}
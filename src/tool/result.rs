@@ -3,10 +3,18 @@
 
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::llm::ImageSource;
+
+/// Metadata key listing the files a tool created or modified, as a JSON array
+/// of path strings. Agents aggregate it into [`SubAgentResult::files_changed`].
+///
+/// [`SubAgentResult::files_changed`]: crate::agent::SubAgentResult::files_changed
+pub const FILES_CHANGED: &str = "files_changed";
 
 /// Result of a tool execution.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     /// The output content.
     pub content: String,
@@ -15,7 +23,12 @@ pub struct ToolResult {
     pub is_error: bool,
 
     /// Optional metadata about the execution.
+    #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
+
+    /// Images produced by the tool, such as screenshots.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageSource>,
 }
 
 impl ToolResult {
@@ -25,6 +38,7 @@ impl ToolResult {
             content: content.into(),
             is_error: false,
             metadata: HashMap::new(),
+            images: Vec::new(),
         }
     }
 
@@ -34,6 +48,7 @@ impl ToolResult {
             content: message.into(),
             is_error: true,
             metadata: HashMap::new(),
+            images: Vec::new(),
         }
     }
 
@@ -44,6 +59,95 @@ impl ToolResult {
         }
         self
     }
+
+    /// Add an image to the result.
+    pub fn with_image(mut self, image: ImageSource) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    /// Record the files this tool created or modified under [`FILES_CHANGED`].
+    pub fn with_files_changed<I, S>(self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let paths: Vec<String> = paths.into_iter().map(Into::into).collect();
+        self.with_metadata(FILES_CHANGED, paths)
+    }
+
+    /// Cut `content` to at most `max_bytes`, on a character boundary, and
+    /// append a `[truncated N of M bytes]` marker saying how much was cut.
+    ///
+    /// The marker comes on top of `max_bytes`. Results within the limit are
+    /// returned unchanged; truncated ones get `truncated: true` metadata.
+    pub fn truncated(mut self, max_bytes: usize) -> Self {
+        let total = self.content.len();
+        if total <= max_bytes {
+            return self;
+        }
+        let mut end = max_bytes;
+        while !self.content.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.content.truncate(end);
+        if !self.content.is_empty() {
+            self.content.push('\n');
+        }
+        self.content
+            .push_str(&format!("[truncated {} of {} bytes]", total - end, total));
+        self.with_metadata("truncated", true)
+    }
+
+    /// The files recorded under [`FILES_CHANGED`], or none if the key is missing or malformed.
+    pub fn files_changed(&self) -> Vec<String> {
+        self.metadata
+            .get(FILES_CHANGED)
+            .and_then(|paths| serde_json::from_value(paths.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Caps on how much of a tool's output is sent to the model, in bytes.
+///
+/// A limit set for a tool by name overrides the default. Without either,
+/// results are sent whole. See [`ToolResult::truncated`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolResultLimits {
+    default: Option<usize>,
+    per_tool: HashMap<String, usize>,
+}
+
+impl ToolResultLimits {
+    /// No limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit every tool without a limit of its own to `max_bytes`.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.default = Some(max_bytes);
+        self
+    }
+
+    /// Limit results from the tool called `tool` to `max_bytes`.
+    pub fn for_tool(mut self, tool: impl Into<String>, max_bytes: usize) -> Self {
+        self.per_tool.insert(tool.into(), max_bytes);
+        self
+    }
+
+    /// The limit for results from `tool`, if any.
+    pub fn limit_for(&self, tool: &str) -> Option<usize> {
+        self.per_tool.get(tool).copied().or(self.default)
+    }
+
+    /// `result`, truncated to the limit for `tool`.
+    pub fn apply(&self, tool: &str, result: ToolResult) -> ToolResult {
+        match self.limit_for(tool) {
+            Some(max_bytes) => result.truncated(max_bytes),
+            None => result,
+        }
+    }
 }
 
 impl Default for ToolResult {
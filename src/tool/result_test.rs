@@ -34,3 +34,60 @@ fn test_default() {
     assert_eq!(result.content, "");
     assert!(!result.is_error);
 }
+
+#[test]
+fn test_files_changed() {
+    let result = ToolResult::text("ok").with_files_changed(["src/a.rs", "src/b.rs"]);
+    assert_eq!(
+        result.metadata[FILES_CHANGED],
+        serde_json::json!(["src/a.rs", "src/b.rs"])
+    );
+    assert_eq!(result.files_changed(), vec!["src/a.rs", "src/b.rs"]);
+
+    assert!(ToolResult::text("ok").files_changed().is_empty());
+    let malformed = ToolResult::text("ok").with_metadata(FILES_CHANGED, "src/a.rs");
+    assert!(malformed.files_changed().is_empty());
+}
+
+#[test]
+fn test_truncated_marks_cut_bytes() {
+    let result = ToolResult::text("a".repeat(100)).truncated(10);
+    assert_eq!(
+        result.content,
+        format!("{}\n[truncated 90 of 100 bytes]", "a".repeat(10))
+    );
+    assert_eq!(result.metadata["truncated"], true);
+
+    let short = ToolResult::error("short").truncated(10);
+    assert_eq!(short.content, "short");
+    assert!(short.is_error);
+    assert!(short.metadata.is_empty());
+}
+
+#[test]
+fn test_truncated_keeps_whole_characters() {
+    // "é" is two bytes, so a 2-byte cut keeps just "h"
+    let result = ToolResult::text("héllo").truncated(2);
+    assert_eq!(result.content, "h\n[truncated 5 of 6 bytes]");
+}
+
+#[test]
+fn test_limits_prefer_per_tool() {
+    let limits = ToolResultLimits::new().max_bytes(10).for_tool("search", 50);
+
+    assert_eq!(limits.limit_for("bash"), Some(10));
+    assert_eq!(limits.limit_for("search"), Some(50));
+    assert_eq!(ToolResultLimits::new().limit_for("bash"), None);
+
+    let output = "x".repeat(30);
+    assert_eq!(
+        limits.apply("search", ToolResult::text(&output)).content,
+        output
+    );
+    assert!(
+        limits
+            .apply("bash", ToolResult::text(&output))
+            .content
+            .ends_with("[truncated 20 of 30 bytes]")
+    );
+}
@@ -0,0 +1,346 @@
+// ABOUTME: Minimal JSON Schema validator for tool inputs and outputs.
+// ABOUTME: Covers the keywords tool schemas use in practice; unknown keywords are ignored.
+
+use std::fmt;
+
+use serde_json::Value;
+
+/// A value that does not conform to a JSON Schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON path to the offending value, e.g. `$.items[0].name`.
+    pub path: String,
+
+    /// What was wrong with it.
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaViolation {}
+
+/// Validate `value` against `schema`, returning the first violation found.
+///
+/// Supports `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, `minimum`, `maximum`, `minLength`,
+/// `maxLength`, `minItems`, `maxItems`, `allOf`, `anyOf` and `oneOf`.
+/// `$ref` and format checks are not supported and always pass.
+pub fn validate_schema(schema: &Value, value: &Value) -> Result<(), SchemaViolation> {
+    match schema_violations(schema, value).into_iter().next() {
+        Some(violation) => Err(violation),
+        None => Ok(()),
+    }
+}
+
+/// Validate `value` against `schema`, returning every violation found.
+///
+/// Supports the same keywords as [`validate_schema`]. A value of the wrong
+/// type is reported once, without checking anything inside it.
+pub fn schema_violations(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, value, "$", &mut violations);
+    violations
+}
+
+fn violation(path: &str, message: impl Into<String>) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value
+            .as_f64()
+            .is_some_and(|n| value.is_i64() || value.is_u64() || n.fract() == 0.0),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn is_valid(schema: &Value, value: &Value, path: &str) -> bool {
+    let mut violations = Vec::new();
+    validate_at(schema, value, path, &mut violations);
+    violations.is_empty()
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+    let schema = match schema {
+        Value::Bool(false) => {
+            out.push(violation(path, "no value is allowed here"));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            out.push(violation(
+                path,
+                format!(
+                    "expected {}, got {}",
+                    allowed.join(" or "),
+                    type_name(value)
+                ),
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum")
+        && !options.contains(value)
+    {
+        out.push(violation(
+            path,
+            format!("{} is not one of {:?}", value, options),
+        ));
+    }
+
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        out.push(violation(
+            path,
+            format!("expected {}, got {}", expected, value),
+        ));
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        out.push(violation(
+                            path,
+                            format!("missing required property '{}'", key),
+                        ));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, child) in object {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child_schema, child, &child_path, out),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            out.push(violation(path, format!("unexpected property '{}'", key)));
+                        }
+                        Some(additional) => validate_at(additional, child, &child_path, out),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && (items.len() as u64) < min
+            {
+                out.push(violation(path, format!("expected at least {} items", min)));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && (items.len() as u64) > max
+            {
+                out.push(violation(path, format!("expected at most {} items", max)));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}[{}]", path, i), out);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && len < min
+            {
+                out.push(violation(
+                    path,
+                    format!("expected at least {} characters", min),
+                ));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && len > max
+            {
+                out.push(violation(
+                    path,
+                    format!("expected at most {} characters", max),
+                ));
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                out.push(violation(
+                    path,
+                    format!("{} is less than minimum {}", n, min),
+                ));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                out.push(violation(
+                    path,
+                    format!("{} is greater than maximum {}", n, max),
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            validate_at(sub, value, path, out);
+        }
+    }
+
+    if let Some(Value::Array(any)) = schema.get("anyOf")
+        && !any.iter().any(|sub| is_valid(sub, value, path))
+    {
+        out.push(violation(path, "does not match any allowed schema"));
+    }
+
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        let matching = one.iter().filter(|sub| is_valid(sub, value, path)).count();
+        if matching != 1 {
+            out.push(violation(
+                path,
+                format!("must match exactly one schema, matched {}", matching),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn weather_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "temperature": {"type": "number", "minimum": -100, "maximum": 100},
+                "conditions": {"type": "string", "enum": ["sunny", "cloudy", "rain"]},
+                "hourly": {"type": "array", "items": {"type": "integer"}}
+            },
+            "required": ["temperature", "conditions"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_value() {
+        let value = json!({"temperature": 21.5, "conditions": "sunny", "hourly": [20, 21]});
+        assert!(validate_schema(&weather_schema(), &value).is_ok());
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let err = validate_schema(&weather_schema(), &json!({"temperature": 3})).unwrap_err();
+        assert_eq!(err.path, "$");
+        assert!(err.message.contains("'conditions'"));
+    }
+
+    #[test]
+    fn test_nested_type_mismatch_reports_path() {
+        let value = json!({"temperature": 3, "conditions": "rain", "hourly": [1, "two"]});
+        let err = validate_schema(&weather_schema(), &value).unwrap_err();
+        assert_eq!(err.path, "$.hourly[1]");
+        assert_eq!(err.message, "expected integer, got string");
+        assert_eq!(err.to_string(), "$.hourly[1]: expected integer, got string");
+    }
+
+    #[test]
+    fn test_schema_violations_lists_every_problem() {
+        let value = json!({"conditions": 7, "hourly": ["one", 2, "three"]});
+        let violations: Vec<String> = schema_violations(&weather_schema(), &value)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "$: missing required property 'temperature'",
+                "$.conditions: expected string, got integer",
+                "$.hourly[0]: expected integer, got string",
+                "$.hourly[2]: expected integer, got string",
+            ]
+        );
+        assert!(
+            schema_violations(
+                &weather_schema(),
+                &json!({"temperature": 1, "conditions": "rain"})
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_enum_range_and_additional_properties() {
+        let schema = weather_schema();
+        assert!(
+            validate_schema(&schema, &json!({"temperature": 3, "conditions": "snow"})).is_err()
+        );
+        assert!(
+            validate_schema(&schema, &json!({"temperature": 300, "conditions": "rain"})).is_err()
+        );
+        assert!(
+            validate_schema(
+                &schema,
+                &json!({"temperature": 3, "conditions": "rain", "wind": 4})
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_combinators_and_type_lists() {
+        let schema = json!({"anyOf": [{"type": "string"}, {"type": "integer"}]});
+        assert!(validate_schema(&schema, &json!("x")).is_ok());
+        assert!(validate_schema(&schema, &json!(1)).is_ok());
+        assert!(validate_schema(&schema, &json!(1.5)).is_err());
+
+        let schema = json!({"type": ["string", "null"]});
+        assert!(validate_schema(&schema, &json!(null)).is_ok());
+        assert!(validate_schema(&schema, &json!(true)).is_err());
+    }
+
+    #[test]
+    fn test_empty_schema_accepts_anything() {
+        assert!(validate_schema(&json!({}), &json!({"any": ["thing"]})).is_ok());
+        assert!(validate_schema(&json!(true), &json!(1)).is_ok());
+        assert!(validate_schema(&json!(false), &json!(1)).is_err());
+    }
+}
@@ -1,9 +1,11 @@
 // ABOUTME: Defines the Tool trait - the core abstraction for agent capabilities.
 // ABOUTME: Tools have a name, description, schema, and async execute method.
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 
-use super::ToolResult;
+use super::{ProgressReporter, ToolResult};
 
 /// A tool that can be executed by an agent.
 #[async_trait]
@@ -22,8 +24,51 @@ pub trait Tool: Send + Sync {
         false
     }
 
+    /// Maximum time a single execution may take.
+    ///
+    /// Overrides the registry-wide timeout set with
+    /// [`Registry::with_timeout`](super::Registry::with_timeout). `None` defers to it.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether calls may run at the same time as other calls in the same
+    /// turn. Return false for tools whose calls must not overlap, such as
+    /// shell commands sharing a working directory. Defaults to true.
+    fn parallel_safe(&self) -> bool {
+        true
+    }
+
+    /// Whether identical calls may share a result. Return true for
+    /// read-only tools whose output depends only on their input, so a
+    /// [`CachingRegistry`](super::CachingRegistry) can reuse it. Defaults
+    /// to false.
+    fn cacheable(&self) -> bool {
+        false
+    }
+
+    /// Post-process a result before it reaches the model, e.g. to mask
+    /// secrets. [`Registry`](super::Registry) applies it to every result the
+    /// tool returns, including error results; an `Err` from `execute` is
+    /// passed on as is. The default returns `result` as is.
+    fn redact(&self, result: ToolResult) -> ToolResult {
+        result
+    }
+
     /// Execute the tool with the given parameters.
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error>;
+
+    /// Execute the tool, reporting progress for long-running work.
+    ///
+    /// The default ignores the reporter and calls [`execute`](Self::execute).
+    /// Use [`ProgressReporter::run`] to get a terminal `done` event after either.
+    async fn execute_with_progress(
+        &self,
+        params: serde_json::Value,
+        _progress: &ProgressReporter,
+    ) -> Result<ToolResult, anyhow::Error> {
+        self.execute(params).await
+    }
 }
 
 /// Trait for typed tool execution.
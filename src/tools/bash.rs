@@ -1,16 +1,288 @@
 // ABOUTME: BashTool - executes shell commands.
-// ABOUTME: Returns stdout/stderr and handles non-zero exit codes.
+// ABOUTME: Enforces a timeout, caps output size and can restrict which commands run.
 
 use std::process::Stdio;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use regex::Regex;
 use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::tool::{Tool, ToolResult};
 
+/// Default time a command may run before it is killed.
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// Default number of bytes kept from each of stdout and stderr.
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 100_000;
+
 /// Tool for executing shell commands.
 /// Uses `bash -c` on Unix and `cmd.exe /C` on Windows.
-pub struct BashTool;
+///
+/// Commands are killed after a timeout. On Unix each command runs in its own
+/// process group, and the whole group is killed, so background jobs it
+/// started don't outlive it.
+///
+/// A command policy can restrict what runs. It is checked before anything is
+/// spawned and works alongside, not instead of, a permission
+/// [`Policy`](crate::permission::Policy) on the `bash` tool.
+#[derive(Default, Clone)]
+pub struct BashTool {
+    /// Program names every command in the line must use, if set.
+    allowed: Option<Vec<String>>,
+    /// Patterns that reject the command line when any of them matches.
+    denied_patterns: Vec<Regex>,
+}
+
+impl BashTool {
+    /// Create a tool that runs any command.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only run command lines whose every command is one of `commands`.
+    ///
+    /// The whole line is checked: each command in a pipeline or list
+    /// (`|`, `;`, `&&`, `||`, `&`) must be allowed, and command substitution
+    /// (`$(...)`, backticks, `<(...)`) is rejected because it can't be checked.
+    /// Commands are compared by program name, so `/usr/bin/git` counts as
+    /// `git`. Allowing a command that runs other commands (`env`, `xargs`,
+    /// `sh`) effectively allows everything.
+    pub fn with_allowed<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed = Some(commands.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Reject command lines matching any of `patterns`.
+    ///
+    /// Each pattern is tried against the line as written and against each
+    /// command in it with quotes and escapes removed, so `rm\s+-rf` also
+    /// rejects `r'm' "-rf"`.
+    pub fn with_denied_patterns<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = Regex>,
+    {
+        self.denied_patterns.extend(patterns);
+        self
+    }
+
+    /// Check a command line against the policy, returning why it is rejected.
+    fn check_command(&self, command: &str) -> Result<(), String> {
+        let (commands, _) = split_commands(command);
+        let unquoted: Vec<String> = commands
+            .iter()
+            .map(|words| {
+                let words: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+                words.join(" ")
+            })
+            .collect();
+        if let Some(pattern) = self
+            .denied_patterns
+            .iter()
+            .find(|p| p.is_match(command) || unquoted.iter().any(|c| p.is_match(c)))
+        {
+            return Err(format!(
+                "Command denied: it matches the blocked pattern '{}'",
+                pattern.as_str()
+            ));
+        }
+
+        if let Some(allowed) = &self.allowed {
+            for name in command_names(command)? {
+                if !allowed.contains(&name) {
+                    return Err(format!(
+                        "Command not allowed: '{}' is not in the allowed commands ({})",
+                        name,
+                        allowed.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Characters that group commands and may be written against a command word.
+const GROUPING: [char; 3] = ['(', '{', '!'];
+
+/// A word of a shell command.
+#[derive(Default)]
+struct Word {
+    /// The word as the shell sees it, with quotes and escapes removed.
+    text: String,
+    /// The word as written.
+    raw: String,
+}
+
+/// Split a shell command line into commands and their words.
+///
+/// Splits on the operators that start a new command (`|`, `;`, `&`, newline)
+/// and on whitespace, honouring quotes and backslash escapes. Also returns
+/// whether the line uses command substitution.
+fn split_commands(command: &str) -> (Vec<Vec<Word>>, bool) {
+    let mut commands: Vec<Vec<Word>> = vec![Vec::new()];
+    let mut word = Word::default();
+    let mut substitution = false;
+    let mut chars = command.chars().peekable();
+    let (mut single, mut double) = (false, false);
+    while let Some(c) = chars.next() {
+        let opens = chars.peek() == Some(&'(');
+        // `2>&1` and `&>file` are redirections, not a background `&`
+        let redirect = c == '&' && (word.raw.ends_with(['<', '>']) || chars.peek() == Some(&'>'));
+        match c {
+            '\'' if !double => single = !single,
+            _ if single => word.text.push(c),
+            '"' => double = !double,
+            '\\' => {
+                word.raw.push(c);
+                if let Some(next) = chars.next() {
+                    word.raw.push(next);
+                    word.text.push(next);
+                }
+                continue;
+            }
+            '|' | ';' | '&' | '\n' if !double && !redirect => {
+                if !word.raw.is_empty() {
+                    commands.last_mut().unwrap().push(std::mem::take(&mut word));
+                }
+                commands.push(Vec::new());
+                continue;
+            }
+            _ if c.is_whitespace() && !double => {
+                if !word.raw.is_empty() {
+                    commands.last_mut().unwrap().push(std::mem::take(&mut word));
+                }
+                continue;
+            }
+            _ => {
+                substitution |=
+                    c == '`' || (c == '$' && opens) || (matches!(c, '<' | '>') && !double && opens);
+                word.text.push(c);
+            }
+        }
+        word.raw.push(c);
+    }
+    if !word.raw.is_empty() {
+        commands.last_mut().unwrap().push(word);
+    }
+    (commands, substitution)
+}
+
+/// The program name of every command in a shell command line.
+///
+/// Leading variable assignments and grouping characters are skipped, and
+/// quotes and paths are removed, so `FOO='a b' '/bin/rm'` yields `rm`.
+/// Command substitution is an error, and so is a command made only of
+/// assignments, since it changes what later commands run.
+fn command_names(command: &str) -> Result<Vec<String>, String> {
+    let (commands, substitution) = split_commands(command);
+    if substitution {
+        return Err("Command not allowed: command substitution can't be checked against the allowed commands"
+            .to_string());
+    }
+
+    let mut names = Vec::new();
+    for words in &commands {
+        let words: Vec<(&str, &str)> = words
+            .iter()
+            .map(|word| {
+                (
+                    word.raw.trim_start_matches(GROUPING),
+                    word.text.trim_start_matches(GROUPING),
+                )
+            })
+            .filter(|(raw, _)| !raw.is_empty())
+            .collect();
+        let Some((_, word)) = words.iter().find(|(raw, _)| !is_assignment(raw)) else {
+            if !words.is_empty() {
+                return Err("Command not allowed: variable assignments without a command can't be checked against the allowed commands"
+                    .to_string());
+            }
+            continue;
+        };
+        let name = word.rsplit('/').next().unwrap_or_default();
+        names.push(name.to_string());
+    }
+    Ok(names)
+}
+
+/// Whether a word, as written, is a `NAME=value` variable assignment.
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Kills a command's process group when dropped, unless disarmed.
+///
+/// Covers both the timeout and the tool call itself being dropped (e.g. when
+/// the agent run is cancelled).
+struct ProcessGroupGuard {
+    pid: Option<u32>,
+}
+
+impl ProcessGroupGuard {
+    fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            // SAFETY: kill() has no memory-safety preconditions. The command
+            // leads its own group, so its pid is the group id.
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Read all of `reader`, keeping at most `max` bytes.
+///
+/// Reading continues past the limit so the command never blocks on a full
+/// pipe. Returns the kept bytes and the total size.
+async fn read_capped<R: AsyncRead + Unpin>(reader: Option<R>, max: usize) -> (Vec<u8>, usize) {
+    let Some(mut reader) = reader else {
+        return (Vec::new(), 0);
+    };
+    let mut kept = Vec::new();
+    let mut total = 0;
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        let room = max.saturating_sub(kept.len());
+        kept.extend_from_slice(&buf[..n.min(room)]);
+        total += n;
+    }
+    (kept, total)
+}
+
+/// Render captured output, noting when it was cut short.
+fn render_output(kept: &[u8], total: usize) -> String {
+    let text = String::from_utf8_lossy(kept);
+    if total > kept.len() {
+        format!(
+            "{}\n[Truncated: showing {} of {} bytes]",
+            text,
+            kept.len(),
+            total
+        )
+    } else {
+        text.into_owned()
+    }
+}
 
 #[async_trait]
 impl Tool for BashTool {
@@ -33,19 +305,40 @@ impl Tool for BashTool {
                 "working_dir": {
                     "type": "string",
                     "description": "The working directory for the command (default: current directory)"
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": format!("Kill the command after this many seconds (default: {})", DEFAULT_TIMEOUT_SECS)
+                },
+                "max_output_bytes": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": format!("Keep at most this many bytes of stdout and of stderr (default: {})", DEFAULT_MAX_OUTPUT_BYTES)
                 }
             },
             "required": ["command"]
         })
     }
 
+    fn parallel_safe(&self) -> bool {
+        false
+    }
+
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         #[derive(Deserialize)]
         struct Params {
             command: String,
             working_dir: Option<String>,
+            timeout_secs: Option<u64>,
+            max_output_bytes: Option<usize>,
         }
         let params: Params = serde_json::from_value(params)?;
+        if let Err(reason) = self.check_command(&params.command) {
+            return Ok(ToolResult::error(reason));
+        }
+        let timeout_secs = params.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let max_output = params.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES);
 
         let mut cmd = if cfg!(target_os = "windows") {
             let mut c = tokio::process::Command::new("cmd.exe");
@@ -56,37 +349,74 @@ impl Tool for BashTool {
             c.arg("-c").arg(&params.command);
             c
         };
+        cmd.stdin(Stdio::null());
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
 
         if let Some(dir) = params.working_dir {
             cmd.current_dir(dir);
         }
 
-        let output = cmd.output().await?;
+        let mut child = cmd.spawn()?;
+        let mut guard = ProcessGroupGuard { pid: child.id() };
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let run = async {
+            let (stdout, stderr, status) = tokio::join!(
+                read_capped(stdout, max_output),
+                read_capped(stderr, max_output),
+                child.wait()
+            );
+            status.map(|status| (stdout, stderr, status))
+        };
+        let (stdout, stderr, status) =
+            match tokio::time::timeout(Duration::from_secs(timeout_secs), run).await {
+                Ok(finished) => finished?,
+                Err(_) => {
+                    // Kill the whole group, then reap the shell
+                    drop(guard);
+                    let _ = child.kill().await;
+                    return Ok(ToolResult::error(format!(
+                        "Command timed out after {}s and was killed",
+                        timeout_secs
+                    ))
+                    .with_metadata("timed_out", true));
+                }
+            };
+        guard.disarm();
 
-        let result = if output.status.success() {
+        let truncated = stdout.1 > stdout.0.len() || stderr.1 > stderr.0.len();
+        let stdout = render_output(&stdout.0, stdout.1);
+        let stderr = render_output(&stderr.0, stderr.1);
+
+        let result = if status.success() {
             if stderr.is_empty() {
-                stdout.to_string()
+                stdout
             } else {
                 format!("{}\n\nstderr:\n{}", stdout, stderr)
             }
         } else {
             format!(
                 "Command failed with exit code {}\n\nstdout:\n{}\n\nstderr:\n{}",
-                output.status.code().unwrap_or(-1),
+                status.code().unwrap_or(-1),
                 stdout,
                 stderr
             )
         };
 
-        if output.status.success() {
-            Ok(ToolResult::text(result))
+        let result = if status.success() {
+            ToolResult::text(result)
+        } else {
+            ToolResult::error(result)
+        };
+        if truncated {
+            Ok(result.with_metadata("truncated", true))
         } else {
-            Ok(ToolResult::error(result))
+            Ok(result)
         }
     }
 }
@@ -97,7 +427,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_bash_echo() {
-        let tool = BashTool;
+        let tool = BashTool::new();
         let result = tool
             .execute(serde_json::json!({
                 "command": "echo Hello, world!"
@@ -111,7 +441,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_bash_failing_command() {
-        let tool = BashTool;
+        let tool = BashTool::new();
         let result = tool
             .execute(serde_json::json!({
                 "command": "exit 1"
@@ -125,7 +455,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_bash_with_working_dir() {
-        let tool = BashTool;
+        let tool = BashTool::new();
         let tmp = std::env::temp_dir();
         let tmp_str = tmp.to_string_lossy().to_string();
         let command = if cfg!(target_os = "windows") {
@@ -149,4 +479,172 @@ mod tests {
             "Command should produce output for the working directory"
         );
     }
+
+    /// Whether a process is gone (or only a zombie waiting to be reaped).
+    #[cfg(target_os = "linux")]
+    fn process_gone(pid: &str) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rsplit(')')
+                .next()
+                .is_some_and(|rest| rest.trim_start().starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_bash_timeout_kills_process_group() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pid_file = dir.path().join("bg.pid");
+        let tool = BashTool::new();
+        let result = tool
+            .execute(serde_json::json!({
+                "command": format!("sleep 30 & echo $! > {}; sleep 30", pid_file.display()),
+                "timeout_secs": 1
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.is_error);
+        assert!(result.content.contains("timed out after 1s"));
+        assert_eq!(result.metadata["timed_out"], true);
+
+        // The background job was killed along with the shell
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let pid = pid.trim();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while !process_gone(pid) && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(process_gone(pid), "background process {} survived", pid);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bash_truncates_output() {
+        let tool = BashTool::new();
+        let result = tool
+            .execute(serde_json::json!({
+                "command": "head -c 5000 /dev/zero | tr '\\0' a",
+                "max_output_bytes": 100
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert!(result.content.starts_with(&"a".repeat(100)));
+        assert!(
+            result
+                .content
+                .contains("[Truncated: showing 100 of 5000 bytes]")
+        );
+        assert_eq!(result.metadata["truncated"], true);
+    }
+
+    #[test]
+    fn test_command_names() {
+        assert_eq!(
+            command_names("git status && ls -la | grep foo").unwrap(),
+            vec!["git", "ls", "grep"]
+        );
+        assert_eq!(
+            command_names("FOO=1 '/bin/rm' -rf x; (cat a)").unwrap(),
+            vec!["rm", "cat"]
+        );
+        // Separators inside quotes don't start a new command
+        assert_eq!(
+            command_names("echo 'a; rm -rf /' \"b | c\"").unwrap(),
+            vec!["echo"]
+        );
+        assert_eq!(
+            command_names("ls 2>&1 | cat &>/dev/null").unwrap(),
+            vec!["ls", "cat"]
+        );
+        assert!(command_names("echo $(rm -rf /)").is_err());
+        assert!(command_names("echo \"`rm -rf /`\"").is_err());
+        // Single quotes make substitution literal
+        assert_eq!(command_names("echo '$(rm)'").unwrap(), vec!["echo"]);
+        // Quoted assignment values are one word, a quoted name is a command
+        assert_eq!(
+            command_names("X='a b' Y=\"touch c\" git status").unwrap(),
+            vec!["git"]
+        );
+        assert_eq!(command_names("'X=1' ls").unwrap(), vec!["X=1"]);
+        assert!(command_names("PATH=/tmp/evil; ls").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bash_allowed_command_runs() {
+        let tool = BashTool::new().with_allowed(["echo", "git"]);
+        let result = tool
+            .execute(serde_json::json!({"command": "echo allowed"}))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert!(result.content.contains("allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_bash_denied_command_is_not_run() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("ran");
+        let tool = BashTool::new().with_allowed(["echo", "ls"]);
+        let result = tool
+            .execute(serde_json::json!({
+                "command": format!("touch {}", marker.display())
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.is_error);
+        assert!(
+            result
+                .content
+                .contains("'touch' is not in the allowed commands")
+        );
+        assert!(!marker.exists());
+
+        let tool = BashTool::new().with_denied_patterns([Regex::new(r"rm\s+-rf").unwrap()]);
+        for command in [
+            "echo hi; rm -rf /tmp/nothing",
+            "echo hi; r'm' \"-rf\" /tmp/nothing",
+            "r\\m   -rf /tmp/nothing",
+        ] {
+            let result = tool
+                .execute(serde_json::json!({"command": command}))
+                .await
+                .unwrap();
+            assert!(result.is_error, "'{}' should be rejected", command);
+            assert!(result.content.contains("blocked pattern"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bash_metacharacters_cannot_bypass_allowlist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("ran");
+        let touch = format!("touch {}", marker.display());
+        let tool = BashTool::new().with_allowed(["echo", "ls"]);
+
+        for command in [
+            format!("echo hi; {}", touch),
+            format!("echo hi && {}", touch),
+            format!("ls | {}", touch),
+            format!("echo hi & {}", touch),
+            format!("echo hi\n{}", touch),
+            format!("echo $({})", touch),
+            format!("echo `{}`", touch),
+            format!("echo \"$({})\"", touch),
+            format!("X=1 {}", touch),
+        ] {
+            let result = tool
+                .execute(serde_json::json!({"command": command}))
+                .await
+                .unwrap();
+            assert!(result.is_error, "'{}' should be rejected", command);
+        }
+        assert!(!marker.exists());
+    }
 }
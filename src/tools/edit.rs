@@ -15,7 +15,8 @@ pub struct EditTool;
 
 #[derive(Deserialize)]
 struct EditParams {
-    file_path: String,
+    #[serde(alias = "file_path")]
+    path: String,
     old_string: String,
     new_string: String,
     #[serde(default)]
@@ -38,7 +39,7 @@ impl Tool for EditTool {
         serde_json::json!({
             "type": "object",
             "properties": {
-                "file_path": {
+                "path": {
                     "type": "string",
                     "description": "The path to the file to edit"
                 },
@@ -56,20 +57,24 @@ impl Tool for EditTool {
                     "default": false
                 }
             },
-            "required": ["file_path", "old_string", "new_string"]
+            "required": ["path", "old_string", "new_string"]
         })
     }
 
+    fn parallel_safe(&self) -> bool {
+        false
+    }
+
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         let params: EditParams = serde_json::from_value(params)?;
 
         // Read the file
-        let content = match std::fs::read_to_string(&params.file_path) {
+        let content = match std::fs::read_to_string(&params.path) {
             Ok(c) => c,
             Err(e) => {
                 return Ok(ToolResult::error(format!(
                     "Failed to read file '{}': {}",
-                    params.file_path, e
+                    params.path, e
                 )));
             }
         };
@@ -81,7 +86,7 @@ impl Tool for EditTool {
             return Ok(ToolResult::error(format!(
                 "String not found in file '{}'. Make sure old_string matches exactly, \
                  including whitespace and indentation.",
-                params.file_path
+                params.path
             )));
         }
 
@@ -90,7 +95,7 @@ impl Tool for EditTool {
                 "String appears {} times in file '{}'. Either:\n\
                  1. Add more surrounding context to make old_string unique, or\n\
                  2. Set replace_all: true to replace all occurrences",
-                occurrences, params.file_path
+                occurrences, params.path
             )));
         }
 
@@ -102,21 +107,18 @@ impl Tool for EditTool {
         };
 
         // Write the file
-        match std::fs::write(&params.file_path, &new_content) {
+        match std::fs::write(&params.path, &new_content) {
             Ok(()) => {
                 let msg = if params.replace_all && occurrences > 1 {
-                    format!(
-                        "Replaced {} occurrences in '{}'",
-                        occurrences, params.file_path
-                    )
+                    format!("Replaced {} occurrences in '{}'", occurrences, params.path)
                 } else {
-                    format!("Successfully edited '{}'", params.file_path)
+                    format!("Successfully edited '{}'", params.path)
                 };
-                Ok(ToolResult::text(msg))
+                Ok(ToolResult::text(msg).with_files_changed([params.path]))
             }
             Err(e) => Ok(ToolResult::error(format!(
                 "Failed to write file '{}': {}",
-                params.file_path, e
+                params.path, e
             ))),
         }
     }
@@ -145,6 +147,7 @@ mod tests {
 
         assert!(!result.is_error, "Error: {}", result.content);
         assert!(result.content.contains("Successfully edited"));
+        assert_eq!(result.files_changed(), vec![path.to_str().unwrap()]);
 
         let content = std::fs::read_to_string(&path).unwrap();
         assert_eq!(content, "Hello, Rust!");
@@ -279,4 +282,35 @@ mod tests {
         let content = std::fs::read_to_string(&path).unwrap();
         assert_eq!(content, "first\nsecond\nline3\n");
     }
+
+    #[tokio::test]
+    async fn test_edit_accepts_path_and_file_path() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.txt");
+        std::fs::write(&path, "alpha beta").unwrap();
+
+        let tool = EditTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": path.to_str().unwrap(),
+                "old_string": "alpha",
+                "new_string": "gamma"
+            }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert_eq!(tool.schema()["required"][0], "path");
+
+        // The older parameter name still works
+        let result = tool
+            .execute(serde_json::json!({
+                "file_path": path.to_str().unwrap(),
+                "old_string": "beta",
+                "new_string": "delta"
+            }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "gamma delta");
+    }
 }
@@ -0,0 +1,493 @@
+// ABOUTME: Minimal HTML-to-markdown conversion for tool output.
+// ABOUTME: Keeps headings, links, lists, emphasis and code; drops scripts and styles.
+
+/// Elements whose content is never shown.
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "head", "iframe",
+];
+
+/// Elements that start and end a paragraph-like block.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "main",
+    "nav",
+    "aside",
+    "form",
+    "table",
+    "figure",
+    "figcaption",
+    "blockquote",
+    "dl",
+    "dd",
+    "dt",
+    "address",
+    "details",
+    "summary",
+];
+
+/// Convert an HTML document to readable markdown.
+///
+/// This is a best-effort converter for feeding pages to a model, not a
+/// conforming HTML parser: it keeps the structure that helps reading
+/// (headings, paragraphs, links, images, lists, emphasis, code blocks and
+/// rough tables) and drops everything else, including scripts, styles and
+/// the document head.
+pub(crate) fn to_markdown(html: &str) -> String {
+    let mut converter = Converter::default();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            converter.text(rest);
+            break;
+        };
+        if start > 0 {
+            converter.text(&rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let Some((tag, after)) = Tag::parse(rest) else {
+            // A stray '<' that doesn't start a tag
+            converter.text("<");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = after;
+
+        if !tag.closing && SKIPPED.contains(&tag.name.as_str()) {
+            rest = skip_element(&tag.name, rest);
+            continue;
+        }
+        converter.tag(&tag);
+    }
+
+    converter.finish()
+}
+
+/// Skip past the end of an element whose opening tag has just been read.
+fn skip_element<'a>(name: &str, html: &'a str) -> &'a str {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let close = format!("</{}", name);
+    let mut depth = 1;
+    let mut pos = 0;
+
+    while depth > 0 {
+        let next_close = lower[pos..].find(&close).map(|i| pos + i);
+        // Script and style contents are raw text, so nesting is impossible
+        let next_open = if matches!(name, "script" | "style") {
+            None
+        } else {
+            lower[pos..].find(&open).map(|i| pos + i)
+        };
+        match (next_open, next_close) {
+            (Some(o), Some(c)) if o < c => {
+                depth += 1;
+                pos = o + open.len();
+            }
+            (_, Some(c)) => {
+                depth -= 1;
+                pos = c + close.len();
+            }
+            (_, None) => return "",
+        }
+    }
+
+    html[pos..]
+        .find('>')
+        .map_or("", |end| &html[pos + end + 1..])
+}
+
+/// One parsed HTML tag.
+struct Tag {
+    name: String,
+    closing: bool,
+    attrs: Vec<(String, String)>,
+}
+
+impl Tag {
+    /// Parse the tag at the start of `html`, returning it and the remaining input.
+    fn parse(html: &str) -> Option<(Tag, &str)> {
+        let body = html.strip_prefix('<')?;
+        let (closing, body) = match body.strip_prefix('/') {
+            Some(body) => (true, body),
+            None => (false, body),
+        };
+        let name_len = body
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(body.len());
+        if name_len == 0 {
+            return None;
+        }
+        let name = body[..name_len].to_ascii_lowercase();
+
+        // Find the closing '>' outside of quoted attribute values
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in body.char_indices().skip(name_len) {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '>') => {
+                    end = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end.unwrap_or(body.len());
+        let attrs = parse_attrs(&body[name_len..end]);
+        let rest = body.get(end + 1..).unwrap_or("");
+
+        Some((
+            Tag {
+                name,
+                closing,
+                attrs,
+            },
+            rest,
+        ))
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn parse_attrs(mut s: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_len = s
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(s.len());
+        if name_len == 0 {
+            return attrs;
+        }
+        let name = s[..name_len].to_ascii_lowercase();
+        s = s[name_len..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after_eq) = s.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (raw, rest) = match after_eq.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let inner = &after_eq[1..];
+                    let end = inner.find(q).unwrap_or(inner.len());
+                    (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    after_eq.split_at(end)
+                }
+            };
+            value = decode_entities(raw);
+            s = rest;
+        }
+        attrs.push((name, value));
+    }
+}
+
+/// Decode HTML character references: the common named ones plus numeric
+/// `&#NN;` and `&#xHH;` forms. Unknown references are left as they are.
+pub(crate) fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end + 1])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        _ => return None,
+    })
+}
+
+#[derive(Default)]
+struct Converter {
+    out: String,
+    /// Targets of the open links; `None` for anchors that aren't rendered as links.
+    links: Vec<Option<String>>,
+    /// Open lists: `None` for bullets, or the next number of an ordered list.
+    lists: Vec<Option<usize>>,
+    /// Depth of `<pre>` elements, inside which whitespace is kept.
+    pre: usize,
+}
+
+impl Converter {
+    fn text(&mut self, raw: &str) {
+        let text = decode_entities(raw);
+        if self.pre > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        let mut prev_space = self.out.is_empty() || self.out.ends_with(char::is_whitespace);
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !prev_space {
+                    collapsed.push(' ');
+                }
+                prev_space = true;
+            } else {
+                collapsed.push(c);
+                prev_space = false;
+            }
+        }
+        self.out.push_str(&collapsed);
+    }
+
+    fn tag(&mut self, tag: &Tag) {
+        let name = tag.name.as_str();
+        match (name, tag.closing) {
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                self.block();
+                let level = name[1..].parse().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => self.block(),
+            ("br", _) => self.line(),
+            ("hr", _) => {
+                self.block();
+                self.out.push_str("---");
+                self.block();
+            }
+            ("strong" | "b", _) => self.out.push_str("**"),
+            ("em" | "i", _) => self.out.push('*'),
+            ("code", _) if self.pre == 0 => self.out.push('`'),
+            ("pre", false) => {
+                self.block();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            }
+            ("pre", true) if self.pre > 0 => {
+                self.pre -= 1;
+                self.line();
+                self.out.push_str("```");
+                self.block();
+            }
+            ("a", false) => {
+                let href = tag
+                    .attr("href")
+                    .filter(|h| {
+                        !h.is_empty() && !h.starts_with('#') && !h.starts_with("javascript:")
+                    })
+                    .map(str::to_string);
+                if href.is_some() {
+                    self.out.push('[');
+                }
+                self.links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = self.links.pop() {
+                    if self.out.ends_with('[') {
+                        // Nothing to show for an empty link
+                        self.out.pop();
+                    } else {
+                        self.out.push_str(&format!("]({})", href));
+                    }
+                }
+            }
+            ("img", _) => {
+                if let Some(src) = tag.attr("src").filter(|s| !s.is_empty()) {
+                    let alt = tag.attr("alt").unwrap_or_default();
+                    self.out.push_str(&format!("![{}]({})", alt, src));
+                }
+            }
+            ("ul" | "ol", false) => {
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line();
+                }
+                let start = tag.attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+                self.lists.push((name == "ol").then_some(start));
+            }
+            ("ul" | "ol", true) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line();
+                }
+            }
+            ("li", false) => {
+                self.line();
+                let depth = self.lists.len().saturating_sub(1);
+                self.out.push_str(&"  ".repeat(depth));
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => self.out.push_str("- "),
+                }
+            }
+            ("tr", true) => self.line(),
+            ("td" | "th", false) if !self.out.is_empty() && !self.out.ends_with('\n') => {
+                self.out.push_str(" | ");
+            }
+            _ if BLOCKS.contains(&name) => self.block(),
+            _ => {}
+        }
+    }
+
+    /// Make sure the output ends a line.
+    fn line(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    /// Make sure the output ends with a blank line.
+    fn block(&mut self) {
+        self.line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        if self.pre == 0 {
+            let len = self.out.trim_end_matches([' ', '\t']).len();
+            self.out.truncate(len);
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut result = String::with_capacity(self.out.len());
+        let mut blank_lines = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank_lines += 1;
+                if blank_lines > 1 {
+                    continue;
+                }
+            } else {
+                blank_lines = 0;
+            }
+            result.push_str(line);
+            result.push('\n');
+        }
+        result.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_markdown_structure() {
+        let html = r#"<html><head><title>Page</title><style>body { color: red }</style></head>
+            <body>
+              <h1>Main  Title</h1>
+              <p>Some <strong>bold</strong> and <em>italic</em> text with
+                 <a href="https://example.com/docs?a=1&amp;b=2">a link</a>
+                 and <code>inline()</code>.</p>
+              <script>if (a < b) { alert("x") }</script>
+              <ul>
+                <li>First</li>
+                <li>Second
+                  <ol start="3"><li>Nested</li></ol>
+                </li>
+              </ul>
+              <pre><code>fn main() {
+    println!("hi");
+}</code></pre>
+              <img src="/logo.png" alt="Logo">
+            </body></html>"#;
+
+        let markdown = to_markdown(html);
+        assert_eq!(
+            markdown,
+            "# Main Title\n\n\
+             Some **bold** and *italic* text with [a link](https://example.com/docs?a=1&b=2) and `inline()`.\n\n\
+             - First\n\
+             - Second\n  \
+               3. Nested\n\n\
+             ```\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+             ![Logo](/logo.png)"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_drops_unrenderable_content() {
+        let html = "<!-- hidden --><div><a href=\"#top\">Top</a> <a href=\"/x\"></a>\
+                    <noscript><p>Enable JS</p></noscript>x &lt; y &#8212; z&#x21;</div>";
+        assert_eq!(to_markdown(html), "Top x < y — z!");
+    }
+
+    #[test]
+    fn test_decode_entities_keeps_unknown_references() {
+        assert_eq!(
+            decode_entities("a &bogus; b & c &amp;"),
+            "a &bogus; b & c &"
+        );
+    }
+}
@@ -1,11 +1,88 @@
-// ABOUTME: ListFilesTool - lists files matching a glob pattern.
-// ABOUTME: Shows directories with [dir] prefix.
+// ABOUTME: ListFilesTool - lists files matching a glob pattern, optionally skipping gitignored ones.
+// ABOUTME: Shows directories with [dir] prefix, sorted in a stable Unicode-aware order.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
 
 use async_trait::async_trait;
 use serde::Deserialize;
 
 use crate::tool::{Tool, ToolResult};
 
+/// Appended to names that are not valid UTF-8 and were displayed lossily.
+const NON_UTF8_MARKER: &str = " [non-utf8 name]";
+
+/// Ordering used for listings.
+///
+/// Paths are compared by Unicode scalar value. With `case_insensitive`, they
+/// are first compared by their Unicode lowercase form, so "b" sorts between
+/// "A" and "C"; names that differ only in case fall back to the exact
+/// comparison so the order is total and stable across runs.
+fn compare_names(a: &str, b: &str, case_insensitive: bool) -> Ordering {
+    if case_insensitive {
+        a.to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b))
+    } else {
+        a.cmp(b)
+    }
+}
+
+/// Match a single-level pattern against directory entries directly.
+///
+/// Unlike `glob::glob`, this keeps entries whose names are not valid UTF-8,
+/// matching them by their lossy form.
+fn list_dir_matching(base: &Path, pattern: &glob::Pattern) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(base) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        })
+        .collect()
+}
+
+/// Paths under `base` that no `.gitignore` excludes, within `max_depth` levels.
+///
+/// Follows git's rules: `.gitignore` files in `base`, its subdirectories and
+/// its parents all apply, and ignored directories are not descended into.
+/// Hidden files are kept, but `.git` directories are skipped like git skips
+/// them. No git repository is required.
+fn not_gitignored(base: &Path, max_depth: Option<usize>) -> HashSet<PathBuf> {
+    ignore::WalkBuilder::new(base)
+        .standard_filters(false)
+        .git_ignore(true)
+        .parents(true)
+        .require_git(false)
+        .max_depth(max_depth)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .flatten()
+        .map(|entry| without_cur_dir(entry.path()))
+        .collect()
+}
+
+/// `path` without `.` components, so `./src/lib.rs` and `src/lib.rs` compare
+/// equal whichever of `glob` and the walker produced them.
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Render a path for display, marking names that could not be decoded.
+fn display_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(s) => s.to_string(),
+        None => format!("{}{}", path.to_string_lossy(), NON_UTF8_MARKER),
+    }
+}
+
 /// Tool for listing files in a directory with glob patterns.
 pub struct ListFilesTool;
 
@@ -16,7 +93,7 @@ impl Tool for ListFilesTool {
     }
 
     fn description(&self) -> &str {
-        "List files in a directory matching a glob pattern."
+        "List files in a directory matching a glob pattern, sorted by name."
     }
 
     fn schema(&self) -> serde_json::Value {
@@ -29,7 +106,15 @@ impl Tool for ListFilesTool {
                 },
                 "glob": {
                     "type": "string",
-                    "description": "Glob pattern to match (default: *)"
+                    "description": "Glob pattern to match, e.g. *.txt or **/*.rs (default: *)"
+                },
+                "respect_gitignore": {
+                    "type": "boolean",
+                    "description": "Skip files and directories excluded by .gitignore (default: false)"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Sort ignoring letter case (default: false)"
                 }
             }
         })
@@ -39,25 +124,53 @@ impl Tool for ListFilesTool {
         #[derive(Deserialize, Default)]
         struct Params {
             path: Option<String>,
+            #[serde(alias = "pattern")]
             glob: Option<String>,
+            #[serde(default)]
+            case_insensitive: bool,
+            #[serde(default)]
+            respect_gitignore: bool,
         }
         let params: Params = serde_json::from_value(params).unwrap_or_default();
 
         let base_path = params.path.unwrap_or_else(|| ".".to_string());
         let glob_pattern = params.glob.unwrap_or_else(|| "*".to_string());
-        let full_pattern = std::path::Path::new(&base_path)
-            .join(&glob_pattern)
-            .to_string_lossy()
-            .to_string();
-
-        let mut files = Vec::new();
-        for entry in glob::glob(&full_pattern).unwrap_or_else(|_| glob::glob("").unwrap()) {
-            if let Ok(path) = entry {
-                let prefix = if path.is_dir() { "[dir] " } else { "" };
-                files.push(format!("{}{}", prefix, path.display()));
-            }
+
+        let recursive = glob_pattern.contains('/') || glob_pattern.contains("**");
+        let mut paths: Vec<PathBuf> = if recursive {
+            let full_pattern = Path::new(&base_path)
+                .join(&glob_pattern)
+                .to_string_lossy()
+                .to_string();
+            glob::glob(&full_pattern)
+                .map(|paths| paths.flatten().collect())
+                .unwrap_or_default()
+        } else {
+            glob::Pattern::new(&glob_pattern)
+                .map(|pattern| list_dir_matching(Path::new(&base_path), &pattern))
+                .unwrap_or_default()
+        };
+
+        if params.respect_gitignore {
+            let max_depth = if recursive { None } else { Some(1) };
+            let allowed = not_gitignored(Path::new(&base_path), max_depth);
+            paths.retain(|path| allowed.contains(&without_cur_dir(path)));
         }
 
+        let mut entries: Vec<(String, bool)> = paths
+            .iter()
+            .map(|path| (display_path(path), path.is_dir()))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| compare_names(a, b, params.case_insensitive));
+
+        let files: Vec<String> = entries
+            .into_iter()
+            .map(|(name, is_dir)| {
+                let prefix = if is_dir { "[dir] " } else { "" };
+                format!("{}{}", prefix, name)
+            })
+            .collect();
+
         if files.is_empty() {
             Ok(ToolResult::text("No files found"))
         } else {
@@ -127,4 +240,239 @@ mod tests {
         assert!(!result.is_error);
         assert!(result.content.contains("No files found"));
     }
+
+    /// File names in listing order, relative to `dir`.
+    fn listed_names(content: &str, dir: &TempDir) -> Vec<String> {
+        let base = format!("{}/", dir.path().display());
+        content
+            .lines()
+            .map(|line| line.replace(&base, ""))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_files_sorted_case_insensitive() {
+        let dir = TempDir::new().unwrap();
+        for name in [
+            "banana.txt",
+            "Apple.txt",
+            "cherry.txt",
+            "apple.txt",
+            "Éclair.txt",
+            "éclair.txt",
+            "Zebra.txt",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let tool = ListFilesTool;
+        let params = serde_json::json!({
+            "path": dir.path().to_str().unwrap(),
+            "case_insensitive": true
+        });
+        let first = tool.execute(params.clone()).await.unwrap();
+        let second = tool.execute(params).await.unwrap();
+
+        assert_eq!(
+            listed_names(&first.content, &dir),
+            vec![
+                "Apple.txt",
+                "apple.txt",
+                "banana.txt",
+                "cherry.txt",
+                "Zebra.txt",
+                "Éclair.txt",
+                "éclair.txt",
+            ]
+        );
+        assert_eq!(first.content, second.content);
+    }
+
+    #[tokio::test]
+    async fn test_list_files_sorted_case_sensitive_by_default() {
+        let dir = TempDir::new().unwrap();
+        for name in ["banana.txt", "Apple.txt", "ñandú.txt", "apple.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let tool = ListFilesTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": dir.path().to_str().unwrap()
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listed_names(&result.content, &dir),
+            vec!["Apple.txt", "apple.txt", "banana.txt", "ñandú.txt"]
+        );
+    }
+
+    /// A tree with a root `.gitignore` and a nested one in `sub/`.
+    fn gitignored_tree() -> TempDir {
+        gitignored_tree_in(TempDir::new().unwrap())
+    }
+
+    fn gitignored_tree_in(dir: TempDir) -> TempDir {
+        let root = dir.path();
+        for sub in ["target", "sub/deep"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("sub/.gitignore"), "secret.rs\n").unwrap();
+        for file in [
+            "main.rs",
+            "debug.log",
+            "target/out.rs",
+            "sub/lib.rs",
+            "sub/secret.rs",
+            "sub/deep/mod.rs",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_list_files_recursive_pattern_respects_nested_gitignore() {
+        let dir = gitignored_tree();
+        let tool = ListFilesTool;
+
+        let result = tool
+            .execute(serde_json::json!({
+                "path": dir.path().to_str().unwrap(),
+                "pattern": "**/*.rs",
+                "respect_gitignore": true
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            listed_names(&result.content, &dir),
+            vec!["main.rs", "sub/deep/mod.rs", "sub/lib.rs"]
+        );
+
+        // Without the flag, ignored files are listed too
+        let result = tool
+            .execute(serde_json::json!({
+                "path": dir.path().to_str().unwrap(),
+                "pattern": "**/*.rs"
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            listed_names(&result.content, &dir),
+            vec![
+                "main.rs",
+                "sub/deep/mod.rs",
+                "sub/lib.rs",
+                "sub/secret.rs",
+                "target/out.rs"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_files_respects_gitignore_under_dot_path() {
+        // Created under the working directory so it can be named as `./<dir>`
+        let dir = gitignored_tree_in(TempDir::new_in(".").unwrap());
+        let tool = ListFilesTool;
+        let path = Path::new(".").join(dir.path().file_name().unwrap());
+
+        for (pattern, expected) in [
+            ("**/*.rs", vec!["main.rs", "sub/deep/mod.rs", "sub/lib.rs"]),
+            ("*", vec![".gitignore", "main.rs", "[dir] sub"]),
+        ] {
+            let result = tool
+                .execute(serde_json::json!({
+                    "path": path.to_str().unwrap(),
+                    "pattern": pattern,
+                    "respect_gitignore": true
+                }))
+                .await
+                .unwrap();
+            let prefix = path.strip_prefix(".").unwrap().display().to_string();
+            let names: Vec<String> = result
+                .content
+                .lines()
+                .map(|line| line.replace("./", "").replace(&format!("{prefix}/"), ""))
+                .collect();
+            assert_eq!(names, expected, "pattern {pattern}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_files_respecting_gitignore_skips_git_dir() {
+        let dir = gitignored_tree();
+        std::fs::create_dir_all(dir.path().join(".git/objects")).unwrap();
+        std::fs::write(dir.path().join(".git/HEAD"), "").unwrap();
+        std::fs::write(dir.path().join(".git/objects/pack.rs"), "").unwrap();
+        let tool = ListFilesTool;
+
+        for (pattern, expected) in [
+            ("**/*.rs", vec!["main.rs", "sub/deep/mod.rs", "sub/lib.rs"]),
+            ("*", vec![".gitignore", "main.rs", "[dir] sub"]),
+        ] {
+            let result = tool
+                .execute(serde_json::json!({
+                    "path": dir.path().to_str().unwrap(),
+                    "pattern": pattern,
+                    "respect_gitignore": true
+                }))
+                .await
+                .unwrap();
+            assert_eq!(
+                listed_names(&result.content, &dir),
+                expected,
+                "pattern {pattern}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_files_top_level_respects_gitignore() {
+        let dir = gitignored_tree();
+
+        let tool = ListFilesTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": dir.path().to_str().unwrap(),
+                "respect_gitignore": true
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listed_names(&result.content, &dir),
+            vec![".gitignore", "main.rs", "[dir] sub"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_list_files_non_utf8_name_is_marked() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"bad\xffname.txt");
+        if std::fs::write(dir.path().join(name), "").is_err() {
+            // Some filesystems reject non-UTF-8 names outright
+            return;
+        }
+        std::fs::write(dir.path().join("good.txt"), "").unwrap();
+
+        let tool = ListFilesTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": dir.path().to_str().unwrap(),
+                "glob": "*.txt"
+            }))
+            .await
+            .unwrap();
+
+        let names = listed_names(&result.content, &dir);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0], format!("bad\u{FFFD}name.txt{}", NON_UTF8_MARKER));
+        assert_eq!(names[1], "good.txt");
+    }
 }
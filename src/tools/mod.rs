@@ -3,6 +3,7 @@
 
 mod bash;
 mod edit;
+mod html;
 mod list_files;
 mod read_file;
 mod search;
@@ -14,7 +15,8 @@ pub use bash::BashTool;
 pub use edit::EditTool;
 pub use list_files::ListFilesTool;
 pub use read_file::ReadFileTool;
-pub use search::SearchTool;
+pub use search::{SearchMatch, SearchTool};
 pub use web_fetch::WebFetchTool;
 pub use web_search::{SearchResult, WebSearchTool};
 pub use write_file::WriteFileTool;
+pub(crate) use write_file::write_atomic;
@@ -1,11 +1,28 @@
-// ABOUTME: ReadFileTool - reads file contents as text.
-// ABOUTME: Returns file contents or error message if file cannot be read.
+// ABOUTME: ReadFileTool - reads file contents as text, whole or by line range.
+// ABOUTME: Numbers lines in ranged reads and can cap whole-file reads at a byte limit.
 
 use async_trait::async_trait;
 use serde::Deserialize;
 
 use crate::tool::{Tool, ToolResult};
 
+/// Number each line like `cat -n`, starting at `first_line`.
+fn number_lines<'a>(lines: impl Iterator<Item = &'a str>, first_line: usize) -> String {
+    lines
+        .enumerate()
+        .map(|(i, line)| format!("{:>6}\t{}\n", first_line + i, line))
+        .collect()
+}
+
+/// Cut `content` to at most `max_bytes`, on a character boundary.
+fn truncate_to(content: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    &content[..end]
+}
+
 /// Tool for reading file contents.
 pub struct ReadFileTool;
 
@@ -16,7 +33,8 @@ impl Tool for ReadFileTool {
     }
 
     fn description(&self) -> &str {
-        "Read the contents of a file. Returns the file contents as text."
+        "Read the contents of a file. Returns the file contents as text. \
+         Pass start_line/end_line to read only some lines, numbered like `cat -n`."
     }
 
     fn schema(&self) -> serde_json::Value {
@@ -26,22 +44,83 @@ impl Tool for ReadFileTool {
                 "path": {
                     "type": "string",
                     "description": "The path to the file to read"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "First line to read, 1-based (default: 1)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Last line to read, inclusive (default: end of file)"
+                },
+                "max_bytes": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Truncate a whole-file read after this many bytes"
                 }
             },
             "required": ["path"]
         })
     }
 
+    fn cacheable(&self) -> bool {
+        true
+    }
+
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         #[derive(Deserialize)]
         struct Params {
             path: String,
+            start_line: Option<usize>,
+            end_line: Option<usize>,
+            max_bytes: Option<usize>,
         }
         let params: Params = serde_json::from_value(params)?;
 
-        match std::fs::read_to_string(&params.path) {
-            Ok(content) => Ok(ToolResult::text(content)),
-            Err(e) => Ok(ToolResult::error(format!("Failed to read file: {}", e))),
+        let content = match std::fs::read_to_string(&params.path) {
+            Ok(content) => content,
+            Err(e) => return Ok(ToolResult::error(format!("Failed to read file: {}", e))),
+        };
+
+        if params.start_line.is_some() || params.end_line.is_some() {
+            let total_lines = content.lines().count();
+            let start = params.start_line.unwrap_or(1);
+            let end = params.end_line.unwrap_or(total_lines).min(total_lines);
+            if start == 0 {
+                return Ok(ToolResult::error("start_line must be at least 1"));
+            }
+            if start > total_lines {
+                return Ok(ToolResult::error(format!(
+                    "start_line {} is past the end of the file ({} lines)",
+                    start, total_lines
+                )));
+            }
+            if start > end {
+                return Ok(ToolResult::error(format!(
+                    "start_line {} is after end_line {}",
+                    start, end
+                )));
+            }
+
+            let lines = content.lines().skip(start - 1).take(end - start + 1);
+            return Ok(ToolResult::text(number_lines(lines, start))
+                .with_metadata("total_lines", total_lines));
+        }
+
+        match params.max_bytes {
+            Some(max_bytes) if content.len() > max_bytes => {
+                let shown = truncate_to(&content, max_bytes);
+                Ok(ToolResult::text(format!(
+                    "{}\n\n[Truncated: showing {} of {} bytes. Use start_line and end_line to read the rest.]",
+                    shown,
+                    shown.len(),
+                    content.len()
+                ))
+                .with_metadata("truncated", true))
+            }
+            _ => Ok(ToolResult::text(content)),
         }
     }
 }
@@ -69,6 +148,114 @@ mod tests {
         assert!(result.content.contains("Hello, world!"));
     }
 
+    fn numbered_file(lines: usize) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for i in 1..=lines {
+            writeln!(file, "line {}", i).unwrap();
+        }
+        file
+    }
+
+    #[tokio::test]
+    async fn test_read_file_line_range() {
+        let file = numbered_file(10);
+
+        let tool = ReadFileTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "start_line": 3,
+                "end_line": 5
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(
+            result.content,
+            "     3\tline 3\n     4\tline 4\n     5\tline 5\n"
+        );
+        assert_eq!(result.metadata["total_lines"], 10);
+
+        // An open-ended range runs to the end of the file
+        let result = tool
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "start_line": 9,
+                "end_line": 50
+            }))
+            .await
+            .unwrap();
+        assert_eq!(result.content, "     9\tline 9\n    10\tline 10\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_file_invalid_range() {
+        let file = numbered_file(3);
+        let tool = ReadFileTool;
+        let path = file.path().to_str().unwrap();
+
+        for (params, expected) in [
+            (
+                serde_json::json!({"path": path, "start_line": 0}),
+                "at least 1",
+            ),
+            (
+                serde_json::json!({"path": path, "start_line": 7}),
+                "past the end",
+            ),
+            (
+                serde_json::json!({"path": path, "start_line": 3, "end_line": 2}),
+                "after end_line",
+            ),
+        ] {
+            let result = tool.execute(params).await.unwrap();
+            assert!(result.is_error);
+            assert!(result.content.contains(expected), "{}", result.content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_file_max_bytes() {
+        let file = numbered_file(100);
+        let total = std::fs::metadata(file.path()).unwrap().len();
+
+        let tool = ReadFileTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "max_bytes": 14
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert!(result.content.starts_with("line 1\nline 2\n\n\n[Truncated"));
+        assert!(
+            result
+                .content
+                .contains(&format!("showing 14 of {} bytes", total))
+        );
+        assert_eq!(result.metadata["truncated"], true);
+
+        // A limit the file fits in returns it unchanged
+        let result = tool
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "max_bytes": 100_000
+            }))
+            .await
+            .unwrap();
+        assert_eq!(result.content.len() as u64, total);
+    }
+
+    #[test]
+    fn test_truncate_to_char_boundary() {
+        assert_eq!(truncate_to("héllo", 2), "h");
+        assert_eq!(truncate_to("héllo", 3), "hé");
+        assert_eq!(truncate_to("hi", 10), "hi");
+    }
+
     #[tokio::test]
     async fn test_read_file_not_found() {
         let tool = ReadFileTool;
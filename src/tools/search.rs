@@ -1,13 +1,64 @@
 // ABOUTME: SearchTool - grep-like content search in files.
-// ABOUTME: Supports regex patterns and glob file matching.
+// ABOUTME: Supports regex or literal patterns, context lines and glob file matching.
 
 use async_trait::async_trait;
-use regex::Regex;
-use serde::Deserialize;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
 
 use crate::tool::{Tool, ToolResult};
 
-/// Tool for searching file contents with regex patterns.
+/// Matches returned when the caller doesn't say.
+const DEFAULT_MAX_MATCHES: usize = 100;
+
+/// One line matching a [`SearchTool`] pattern, with the lines around it.
+///
+/// The tool's results carry these as JSON in their `matches` metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub path: String,
+    /// 1-based.
+    pub line_number: usize,
+    pub line: String,
+    /// Up to `context_lines` lines before the match, in file order.
+    pub before: Vec<String>,
+    /// Up to `context_lines` lines after the match.
+    pub after: Vec<String>,
+}
+
+impl SearchMatch {
+    /// The match as `path:line: text`, with context lines as
+    /// `path-line- text` around it, as grep shows them.
+    fn render(&self) -> String {
+        let first = self.line_number - self.before.len();
+        let context =
+            |offset: usize, text: &str| format!("{}-{}- {}", self.path, offset, text.trim_end());
+        let mut lines: Vec<String> = self
+            .before
+            .iter()
+            .enumerate()
+            .map(|(i, text)| context(first + i, text))
+            .collect();
+        lines.push(format!(
+            "{}:{}: {}",
+            self.path,
+            self.line_number,
+            self.line.trim_end()
+        ));
+        lines.extend(
+            self.after
+                .iter()
+                .enumerate()
+                .map(|(i, text)| context(self.line_number + 1 + i, text)),
+        );
+        lines.join("\n")
+    }
+}
+
+/// Tool for searching file contents with regex or literal patterns.
+///
+/// Results list each match as `path:line: text`. The same matches are in
+/// the `matches` metadata as [`SearchMatch`]es, and `truncated` is set
+/// when `max_matches` cut the search short.
 pub struct SearchTool;
 
 #[async_trait]
@@ -17,7 +68,7 @@ impl Tool for SearchTool {
     }
 
     fn description(&self) -> &str {
-        "Search for a pattern in files. Supports glob patterns for file matching and regex for content matching."
+        "Search for a pattern in files. Supports glob patterns for file matching and regex or literal content matching, with optional context lines."
     }
 
     fn schema(&self) -> serde_json::Value {
@@ -26,7 +77,7 @@ impl Tool for SearchTool {
             "properties": {
                 "pattern": {
                     "type": "string",
-                    "description": "The regex pattern to search for in file contents"
+                    "description": "The pattern to search for in file contents"
                 },
                 "path": {
                     "type": "string",
@@ -35,6 +86,27 @@ impl Tool for SearchTool {
                 "glob": {
                     "type": "string",
                     "description": "Glob pattern for files to search (default: **/*)"
+                },
+                "regex": {
+                    "type": "boolean",
+                    "description": "Treat the pattern as a regex rather than literal text (default: true)",
+                    "default": true
+                },
+                "case_sensitive": {
+                    "type": "boolean",
+                    "description": "Match case exactly (default: true)",
+                    "default": true
+                },
+                "context_lines": {
+                    "type": "integer",
+                    "description": "Lines of context to show before and after each match (default: 0)",
+                    "default": 0
+                },
+                "max_matches": {
+                    "type": "integer",
+                    "description": format!("Stop after this many matches (default: {})", DEFAULT_MAX_MATCHES),
+                    "default": DEFAULT_MAX_MATCHES,
+                    "minimum": 1
                 }
             },
             "required": ["pattern"]
@@ -47,47 +119,104 @@ impl Tool for SearchTool {
             pattern: String,
             path: Option<String>,
             glob: Option<String>,
+            #[serde(default = "default_true")]
+            regex: bool,
+            #[serde(default = "default_true")]
+            case_sensitive: bool,
+            #[serde(default)]
+            context_lines: usize,
+            #[serde(default = "default_max_matches")]
+            max_matches: usize,
+        }
+
+        fn default_true() -> bool {
+            true
         }
+
+        fn default_max_matches() -> usize {
+            DEFAULT_MAX_MATCHES
+        }
+
         let params: Params = serde_json::from_value(params)?;
+        if params.max_matches == 0 {
+            return Ok(ToolResult::error("max_matches must be at least 1"));
+        }
 
         let base_path = params.path.unwrap_or_else(|| ".".to_string());
         let glob_pattern = params.glob.unwrap_or_else(|| "**/*".to_string());
         let full_pattern = format!("{}/{}", base_path, glob_pattern);
 
-        let mut results = Vec::new();
-        let regex = match Regex::new(&params.pattern) {
+        let pattern = if params.regex {
+            params.pattern
+        } else {
+            regex::escape(&params.pattern)
+        };
+        let regex = match RegexBuilder::new(&pattern)
+            .case_insensitive(!params.case_sensitive)
+            .build()
+        {
             Ok(r) => r,
             Err(e) => return Ok(ToolResult::error(format!("Invalid regex: {}", e))),
         };
 
-        for entry in glob::glob(&full_pattern).unwrap_or_else(|_| glob::glob("").unwrap()) {
-            if let Ok(path) = entry {
-                if path.is_file() {
-                    if let Ok(content) = std::fs::read_to_string(&path) {
-                        for (line_num, line) in content.lines().enumerate() {
-                            if regex.is_match(line) {
-                                results.push(format!(
-                                    "{}:{}: {}",
-                                    path.display(),
-                                    line_num + 1,
-                                    line.trim()
-                                ));
-                            }
-                        }
+        let mut matches = Vec::new();
+        let mut truncated = false;
+        'files: for entry in glob::glob(&full_pattern).unwrap_or_else(|_| glob::glob("").unwrap()) {
+            if let Ok(path) = entry
+                && path.is_file()
+                && let Ok(content) = std::fs::read_to_string(&path)
+            {
+                let lines: Vec<&str> = content.lines().collect();
+                for (index, line) in lines.iter().enumerate() {
+                    if !regex.is_match(line) {
+                        continue;
                     }
+                    if matches.len() == params.max_matches {
+                        truncated = true;
+                        break 'files;
+                    }
+                    let start = index.saturating_sub(params.context_lines);
+                    let end = (index + 1 + params.context_lines).min(lines.len());
+                    matches.push(SearchMatch {
+                        path: path.display().to_string(),
+                        line_number: index + 1,
+                        line: line.to_string(),
+                        before: lines[start..index].iter().map(|l| l.to_string()).collect(),
+                        after: lines[index + 1..end]
+                            .iter()
+                            .map(|l| l.to_string())
+                            .collect(),
+                    });
                 }
             }
         }
 
-        if results.is_empty() {
-            Ok(ToolResult::text("No matches found"))
+        if matches.is_empty() {
+            return Ok(ToolResult::text("No matches found")
+                .with_metadata("matches", Vec::<SearchMatch>::new())
+                .with_metadata("truncated", false));
+        }
+
+        let separator = if params.context_lines > 0 {
+            "\n--\n"
         } else {
-            Ok(ToolResult::text(format!(
-                "Found {} matches:\n{}",
-                results.len(),
-                results.join("\n")
-            )))
+            "\n"
+        };
+        let rendered: Vec<String> = matches.iter().map(SearchMatch::render).collect();
+        let mut content = format!(
+            "Found {} matches:\n{}",
+            matches.len(),
+            rendered.join(separator)
+        );
+        if truncated {
+            content.push_str(&format!(
+                "\n(stopped after {} matches; raise max_matches or narrow the search for more)",
+                params.max_matches
+            ));
         }
+        Ok(ToolResult::text(content)
+            .with_metadata("matches", matches)
+            .with_metadata("truncated", truncated))
     }
 }
 
@@ -151,4 +280,97 @@ mod tests {
         assert!(result.is_error);
         assert!(result.content.contains("Invalid regex"));
     }
+
+    fn matches(result: &ToolResult) -> Vec<SearchMatch> {
+        serde_json::from_value(result.metadata["matches"].clone()).unwrap()
+    }
+
+    async fn search_file(contents: &str, params: serde_json::Value) -> ToolResult {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("code.rs"), contents).unwrap();
+        let mut params = params;
+        params["path"] = dir.path().to_str().unwrap().into();
+        SearchTool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_regex_vs_literal() {
+        let contents = "let a = foo(1);\nlet b = foo.bar;\nlet c = FOO(2);\nlet d = fooXbar;\n";
+
+        let regex = search_file(contents, serde_json::json!({"pattern": "foo\\(\\d\\)"})).await;
+        let lines: Vec<usize> = matches(&regex).iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, [1]);
+
+        let literal = search_file(
+            contents,
+            serde_json::json!({"pattern": "foo.bar", "regex": false}),
+        )
+        .await;
+        assert_eq!(matches(&literal)[0].line, "let b = foo.bar;");
+        // "." is literal, so "fooXbar" doesn't match
+        assert_eq!(matches(&literal).len(), 1);
+
+        let insensitive = search_file(
+            contents,
+            serde_json::json!({"pattern": "foo(", "regex": false, "case_sensitive": false}),
+        )
+        .await;
+        let lines: Vec<usize> = matches(&insensitive)
+            .iter()
+            .map(|m| m.line_number)
+            .collect();
+        assert_eq!(lines, [1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_search_context_window() {
+        let contents = "one\ntwo\nthree\nfour\nfive\n";
+
+        let result = search_file(
+            contents,
+            serde_json::json!({"pattern": "one|four", "context_lines": 1}),
+        )
+        .await;
+
+        let found = matches(&result);
+        assert_eq!(found[0].before, Vec::<String>::new());
+        assert_eq!(found[0].after, ["two"]);
+        assert_eq!(found[1].line_number, 4);
+        assert_eq!(found[1].before, ["three"]);
+        assert_eq!(found[1].after, ["five"]);
+
+        let path = &found[1].path;
+        assert!(
+            result
+                .content
+                .contains(&format!("{path}-3- three\n{path}:4: four\n{path}-5- five"))
+        );
+        assert!(result.content.contains("\n--\n"));
+    }
+
+    #[tokio::test]
+    async fn test_search_stops_at_max_matches() {
+        let result = search_file(
+            "hit\nhit\nhit\n",
+            serde_json::json!({"pattern": "hit", "max_matches": 2}),
+        )
+        .await;
+
+        assert_eq!(matches(&result).len(), 2);
+        assert_eq!(result.metadata["truncated"], true);
+        assert!(result.content.contains("stopped after 2 matches"));
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_zero_max_matches() {
+        let result = search_file(
+            "hit
+",
+            serde_json::json!({"pattern": "hit", "max_matches": 0}),
+        )
+        .await;
+
+        assert!(result.is_error);
+        assert!(result.content.contains("max_matches must be at least 1"));
+    }
 }
@@ -1,14 +1,52 @@
 // ABOUTME: WebFetchTool - fetches content from URLs.
-// ABOUTME: Returns page content with optional HTML-to-text conversion.
+// ABOUTME: Returns pages as markdown, plain text or raw HTML, refusing private and blocked hosts.
+
+use std::net::IpAddr;
 
 use async_trait::async_trait;
 use serde::Deserialize;
 
+use super::html;
 use crate::tool::{Tool, ToolResult};
 
+/// Redirects followed before a fetch is abandoned.
+const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// Content returned when the caller doesn't say.
+const DEFAULT_MAX_BYTES: usize = 50_000;
+
+/// How HTML pages are returned. Other content types are always returned as is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Markdown,
+    Text,
+    Html,
+}
+
 /// Tool for fetching web content from URLs.
+///
+/// HTML pages are converted to markdown by default; `format` can ask for
+/// plain text or the raw HTML instead.
+///
+/// To keep the model from reaching internal services, only `http` and
+/// `https` URLs are fetched, and hosts on loopback, private or link-local
+/// networks are refused unless allowed with
+/// [`with_private_hosts`](Self::with_private_hosts) or
+/// [`with_private_network_access`](Self::with_private_network_access).
+/// Hostnames are checked against the addresses they resolve to. Redirects
+/// are followed by the tool itself, up to a limit, and every hop is checked
+/// the same way as the first URL. The URL that finally answered is reported
+/// in the `final_url` metadata, with the URLs that redirected to it in
+/// `redirect_chain` and the `content_type`.
 pub struct WebFetchTool {
     client: reqwest::Client,
+    max_redirects: usize,
+    cross_origin_redirects: bool,
+    private_network_access: bool,
+    private_hosts: Vec<String>,
+    blocked_hosts: Vec<String>,
 }
 
 impl Default for WebFetchTool {
@@ -23,14 +61,141 @@ impl WebFetchTool {
         let client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(30))
             .user_agent("mux-rs/0.2.0")
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .expect("Failed to create HTTP client");
-        Self { client }
+        Self::with_client(client)
     }
 
     /// Create with a custom reqwest client.
+    ///
+    /// The client should not follow redirects itself
+    /// (`reqwest::redirect::Policy::none()`), or the tool's redirect limit
+    /// and `final_url` reporting won't see them.
     pub fn with_client(client: reqwest::Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            cross_origin_redirects: true,
+            private_network_access: false,
+            private_hosts: Vec::new(),
+            blocked_hosts: Vec::new(),
+        }
+    }
+
+    /// Set how many redirects to follow before giving up (default: 5).
+    /// Zero refuses all redirects.
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Whether to follow redirects to a different scheme, host or port
+    /// (default: true).
+    pub fn with_cross_origin_redirects(mut self, allowed: bool) -> Self {
+        self.cross_origin_redirects = allowed;
+        self
+    }
+
+    /// Allow fetching from any loopback, private or link-local address
+    /// (default: false).
+    pub fn with_private_network_access(mut self, allowed: bool) -> Self {
+        self.private_network_access = allowed;
+        self
+    }
+
+    /// Hosts that may be fetched even though they are on a private network,
+    /// e.g. `127.0.0.1` or `docs.internal`. Matched exactly.
+    pub fn with_private_hosts(
+        mut self,
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.private_hosts = hosts.into_iter().map(|h| h.into().to_lowercase()).collect();
+        self
+    }
+
+    /// Hosts that are never fetched. Each entry also blocks its subdomains,
+    /// so `example.com` blocks `api.example.com`.
+    pub fn with_blocked_hosts(
+        mut self,
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.blocked_hosts = hosts.into_iter().map(|h| h.into().to_lowercase()).collect();
+        self
+    }
+
+    /// Check a URL against the host policy, returning why it is refused.
+    ///
+    /// Resolution happens again when the request is sent, so a host whose
+    /// DNS answer changes in between is not caught.
+    async fn check_url(&self, url: &reqwest::Url) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("'{}' URLs are not supported", url.scheme()));
+        }
+        let Some(host) = url.host_str() else {
+            return Err("the URL has no host".to_string());
+        };
+        let host = host.to_lowercase();
+
+        let blocked = self
+            .blocked_hosts
+            .iter()
+            .any(|b| host == *b || host.ends_with(&format!(".{}", b)));
+        if blocked {
+            return Err(format!("host '{}' is blocked", host));
+        }
+
+        if self.private_network_access || self.private_hosts.contains(&host) {
+            return Ok(());
+        }
+        let ip = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = ip.parse::<IpAddr>() {
+            if is_private(ip) {
+                return Err(format!("'{}' is a private network address", host));
+            }
+            return Ok(());
+        }
+        if host == "localhost" || host.ends_with(".localhost") {
+            return Err(format!("'{}' is a private network address", host));
+        }
+
+        // Unresolvable hosts are left for the request itself to report
+        let port = url.port_or_known_default().unwrap_or(80);
+        if let Ok(addrs) = tokio::net::lookup_host((host.as_str(), port)).await
+            && let Some(addr) = addrs.into_iter().find(|a| is_private(a.ip()))
+        {
+            return Err(format!(
+                "'{}' resolves to private network address {}",
+                host,
+                addr.ip()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Read at most `limit` bytes of the body, returning them and whether
+    /// the body had more. A multi-byte character cut at the limit is dropped.
+    async fn read_prefix(
+        mut response: reqwest::Response,
+        limit: usize,
+    ) -> Result<(String, bool), reqwest::Error> {
+        let mut body = Vec::new();
+        let mut more = false;
+        while let Some(chunk) = response.chunk().await? {
+            let room = limit - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                more = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        if let Err(e) = std::str::from_utf8(&body)
+            && e.error_len().is_none()
+        {
+            body.truncate(e.valid_up_to());
+        }
+        Ok((String::from_utf8_lossy(&body).into_owned(), more))
     }
 
     /// Simple HTML to text conversion - strips tags and decodes entities.
@@ -115,6 +280,35 @@ impl WebFetchTool {
     }
 }
 
+/// Whether `ip` is on a loopback, private, link-local or otherwise
+/// non-public network.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_private(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
 #[async_trait]
 impl Tool for WebFetchTool {
     fn name(&self) -> &str {
@@ -122,7 +316,7 @@ impl Tool for WebFetchTool {
     }
 
     fn description(&self) -> &str {
-        "Fetch content from a URL. Returns the page content as text, optionally converting HTML to plain text."
+        "Fetch content from a URL. HTML pages are returned as markdown by default, or as plain text or raw HTML."
     }
 
     fn schema(&self) -> serde_json::Value {
@@ -133,39 +327,49 @@ impl Tool for WebFetchTool {
                     "type": "string",
                     "description": "The URL to fetch"
                 },
-                "convert_html": {
-                    "type": "boolean",
-                    "description": "Convert HTML to plain text (default: true)",
-                    "default": true
+                "format": {
+                    "type": "string",
+                    "enum": ["markdown", "text", "html"],
+                    "description": "How to return HTML pages: markdown (default), plain text, or the raw HTML",
+                    "default": "markdown"
                 },
-                "max_length": {
+                "max_bytes": {
                     "type": "integer",
-                    "description": "Maximum content length to return (default: 50000)",
-                    "default": 50000
+                    "description": format!("Maximum bytes to read from the response and return (default: {})", DEFAULT_MAX_BYTES),
+                    "default": DEFAULT_MAX_BYTES
                 }
             },
             "required": ["url"]
         })
     }
 
+    fn cacheable(&self) -> bool {
+        true
+    }
+
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         #[derive(Deserialize)]
         struct Params {
             url: String,
-            #[serde(default = "default_convert_html")]
-            convert_html: bool,
-            #[serde(default = "default_max_length")]
-            max_length: usize,
+            #[serde(default)]
+            format: Option<Format>,
+            /// Older name for `format`; `false` meant raw HTML.
+            #[serde(default)]
+            convert_html: Option<bool>,
+            #[serde(default = "default_max_bytes", alias = "max_length")]
+            max_bytes: usize,
         }
 
-        fn default_convert_html() -> bool {
-            true
-        }
-        fn default_max_length() -> usize {
-            50000
+        fn default_max_bytes() -> usize {
+            DEFAULT_MAX_BYTES
         }
 
         let params: Params = serde_json::from_value(params)?;
+        let format = match (params.format, params.convert_html) {
+            (Some(format), _) => format,
+            (None, Some(false)) => Format::Html,
+            (None, _) => Format::Markdown,
+        };
 
         // Validate URL
         let url = if !params.url.starts_with("http://") && !params.url.starts_with("https://") {
@@ -173,11 +377,63 @@ impl Tool for WebFetchTool {
         } else {
             params.url
         };
+        let mut url = match reqwest::Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => return Ok(ToolResult::error(format!("Invalid URL '{}': {}", url, e))),
+        };
+        if let Err(reason) = self.check_url(&url).await {
+            return Ok(ToolResult::error(format!(
+                "Refusing to fetch {}: {}",
+                url, reason
+            )));
+        }
 
-        // Fetch content
-        let response = match self.client.get(&url).send().await {
-            Ok(resp) => resp,
-            Err(e) => return Ok(ToolResult::error(format!("Failed to fetch URL: {}", e))),
+        // Fetch content, following redirects ourselves so each hop is checked
+        let mut redirect_chain: Vec<String> = Vec::new();
+        let response = loop {
+            let response = match self.client.get(url.clone()).send().await {
+                Ok(resp) => resp,
+                Err(e) => return Ok(ToolResult::error(format!("Failed to fetch URL: {}", e))),
+            };
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok());
+            let Some(location) = location.filter(|_| response.status().is_redirection()) else {
+                break response;
+            };
+            if redirect_chain.len() == self.max_redirects {
+                return Ok(ToolResult::error(format!(
+                    "Too many redirects (limit is {})",
+                    self.max_redirects
+                ))
+                .with_metadata("final_url", url.as_str())
+                .with_metadata("redirect_chain", redirect_chain));
+            }
+            let next = match url.join(location) {
+                Ok(next) => next,
+                Err(e) => {
+                    return Ok(ToolResult::error(format!(
+                        "Invalid redirect location '{}': {}",
+                        location, e
+                    )));
+                }
+            };
+            redirect_chain.push(url.to_string());
+
+            let refusal = if !self.cross_origin_redirects && next.origin() != url.origin() {
+                Some("cross-origin redirects are not allowed".to_string())
+            } else {
+                self.check_url(&next).await.err()
+            };
+            if let Some(reason) = refusal {
+                return Ok(
+                    ToolResult::error(format!("Redirect to {} refused: {}", next, reason))
+                        .with_metadata("final_url", url.as_str())
+                        .with_metadata("redirect_chain", redirect_chain),
+                );
+            }
+            url = next;
         };
 
         // Check status
@@ -187,7 +443,8 @@ impl Tool for WebFetchTool {
                 "HTTP error: {} {}",
                 status.as_u16(),
                 status.canonical_reason().unwrap_or("Unknown")
-            )));
+            ))
+            .with_metadata("final_url", url.as_str()));
         }
 
         // Get content type
@@ -196,40 +453,56 @@ impl Tool for WebFetchTool {
             .get("content-type")
             .and_then(|v| v.to_str().ok())
             .unwrap_or("")
-            .to_lowercase();
-
-        // Get body
-        let body = match response.text().await {
-            Ok(text) => text,
+            .to_string();
+        let lower_content_type = content_type.to_lowercase();
+        let is_html = lower_content_type.contains("text/html")
+            || lower_content_type.contains("application/xhtml");
+
+        // Get body, reading no more than max_bytes of it
+        let declared = response.content_length();
+        let (body, more) = match Self::read_prefix(response, params.max_bytes).await {
+            Ok(read) => read,
             Err(e) => return Ok(ToolResult::error(format!("Failed to read response: {}", e))),
         };
 
         // Convert if HTML and requested
-        let content = if params.convert_html && content_type.contains("text/html") {
-            Self::html_to_text(&body)
-        } else {
-            body
+        let mut content = match format {
+            Format::Markdown if is_html => html::to_markdown(&body),
+            Format::Text if is_html => Self::html_to_text(&body),
+            _ => body,
         };
 
-        // Truncate if needed
-        let content = if content.len() > params.max_length {
-            format!(
-                "{}...\n\n[Content truncated at {} characters, total {} characters]",
-                &content[..params.max_length],
-                params.max_length,
-                content.len()
-            )
-        } else {
-            content
-        };
+        // Conversion can lengthen the text, so cut again on a character boundary
+        let truncated = more || content.len() > params.max_bytes;
+        if content.len() > params.max_bytes {
+            let mut end = params.max_bytes;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+        }
+        if truncated {
+            let note = match declared {
+                Some(total) if more => {
+                    format!("[Truncated: showing {} of {} bytes]", content.len(), total)
+                }
+                _ => format!("[Truncated: showing the first {} bytes]", content.len()),
+            };
+            content = format!("{}\n\n{}", content, note);
+        }
 
-        Ok(ToolResult::text(content))
+        Ok(ToolResult::text(content)
+            .with_metadata("final_url", url.as_str())
+            .with_metadata("content_type", content_type)
+            .with_metadata("redirect_chain", redirect_chain)
+            .with_metadata("truncated", truncated))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::test_server::{RecordedResponse, serve};
 
     #[test]
     fn test_html_to_text() {
@@ -259,6 +532,222 @@ mod tests {
         assert!(text.contains("\"quoted\""));
     }
 
+    fn html_response(body: &str) -> RecordedResponse {
+        RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/html; charset=utf-8".into())],
+            body: body.to_string(),
+        }
+    }
+
+    fn redirect(location: &str) -> RecordedResponse {
+        RecordedResponse {
+            status: 302,
+            headers: vec![("location".into(), location.into())],
+            body: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_formats() {
+        let page = "<html><script>track()</script><body><h2>News</h2><p>Read <a href=\"/more\">more</a></p></body></html>";
+        let (base_url, _server) = serve(vec![
+            html_response(page),
+            html_response(page),
+            html_response(page),
+        ])
+        .await;
+        let tool = WebFetchTool::new().with_private_network_access(true);
+
+        let markdown = tool
+            .execute(serde_json::json!({"url": base_url}))
+            .await
+            .unwrap();
+        assert_eq!(markdown.content, "## News\n\nRead [more](/more)");
+        assert_eq!(
+            markdown.metadata["content_type"],
+            "text/html; charset=utf-8"
+        );
+
+        let text = tool
+            .execute(serde_json::json!({"url": base_url, "format": "text"}))
+            .await
+            .unwrap();
+        assert_eq!(text.content, "News\nRead more");
+
+        let raw = tool
+            .execute(serde_json::json!({"url": base_url, "format": "html"}))
+            .await
+            .unwrap();
+        assert_eq!(raw.content, page);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_truncates_to_max_bytes() {
+        let body = "é".repeat(100);
+        let (base_url, _server) = serve(vec![RecordedResponse::json(200, &body)]).await;
+
+        let result = WebFetchTool::new()
+            .with_private_network_access(true)
+            .execute(serde_json::json!({"url": base_url, "max_bytes": 51}))
+            .await
+            .unwrap();
+
+        // Cut back to a character boundary
+        assert!(result.content.starts_with(&"é".repeat(25)));
+        assert!(
+            result
+                .content
+                .ends_with("\n\n[Truncated: showing 50 of 200 bytes]")
+        );
+        assert_eq!(result.metadata["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reads_only_max_bytes_of_large_body() {
+        let body = "a".repeat(1_000_000);
+        let (base_url, _server) = serve(vec![RecordedResponse::json(200, &body)]).await;
+
+        let result = WebFetchTool::new()
+            .with_private_network_access(true)
+            .execute(serde_json::json!({"url": base_url, "max_bytes": 10}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.content,
+            "aaaaaaaaaa\n\n[Truncated: showing 10 of 1000000 bytes]"
+        );
+        assert_eq!(result.metadata["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_redirects_up_to_limit() {
+        let (base_url, server) = serve(vec![
+            redirect("/step"),
+            redirect("/final"),
+            html_response("<p>Arrived</p>"),
+        ])
+        .await;
+
+        let result = WebFetchTool::new()
+            .with_private_hosts(["127.0.0.1"])
+            .execute(serde_json::json!({"url": format!("{}/start", base_url)}))
+            .await
+            .unwrap();
+        assert_eq!(result.content, "Arrived");
+        assert_eq!(result.metadata["final_url"], format!("{}/final", base_url));
+        assert_eq!(
+            result.metadata["redirect_chain"],
+            serde_json::json!([format!("{}/start", base_url), format!("{}/step", base_url)])
+        );
+        let requests = server.await.unwrap();
+        assert!(requests[2].starts_with("GET /final "));
+
+        let (base_url, server) = serve(vec![redirect("/a"), redirect("/b")]).await;
+        let result = WebFetchTool::new()
+            .with_private_hosts(["127.0.0.1"])
+            .with_max_redirects(1)
+            .execute(serde_json::json!({"url": base_url}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(result.content.contains("Too many redirects"));
+        assert_eq!(result.metadata["final_url"], format!("{}/a", base_url));
+        // The redirect past the cap is never requested
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_private_host_is_refused() {
+        // The test server itself is allowed; where it redirects to is not
+        let tool = WebFetchTool::new().with_private_hosts(["127.0.0.1"]);
+        for target in [
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.1/admin",
+            "http://[::1]/",
+            "file:///etc/passwd",
+        ] {
+            let (base_url, server) = serve(vec![redirect(target)]).await;
+            let result = tool
+                .execute(serde_json::json!({"url": base_url}))
+                .await
+                .unwrap();
+            assert!(result.is_error, "followed redirect to {}", target);
+            assert!(result.content.starts_with("Redirect to "));
+            assert_eq!(
+                result.metadata["redirect_chain"],
+                serde_json::json!([format!("{}/", base_url)])
+            );
+            assert_eq!(server.await.unwrap().len(), 1);
+        }
+
+        // So is localhost, whatever it resolves to
+        let (base_url, _server) = serve(vec![redirect("http://localhost/")]).await;
+        let result = tool
+            .execute(serde_json::json!({"url": base_url}))
+            .await
+            .unwrap();
+        assert!(result.content.contains("private network"));
+    }
+
+    #[tokio::test]
+    async fn test_host_policy_applies_to_first_url() {
+        let result = WebFetchTool::new()
+            .execute(serde_json::json!({"url": "http://192.168.1.1/"}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(result.content.starts_with("Refusing to fetch"));
+
+        let result = WebFetchTool::new()
+            .with_private_network_access(true)
+            .with_blocked_hosts(["example.com"])
+            .execute(serde_json::json!({"url": "https://api.example.com/data"}))
+            .await
+            .unwrap();
+        assert!(result.content.contains("host 'api.example.com' is blocked"));
+    }
+
+    #[tokio::test]
+    async fn test_cross_origin_redirects_can_be_refused() {
+        let (base_url, _server) = serve(vec![redirect("http://localhost:1/elsewhere")]).await;
+        let result = WebFetchTool::new()
+            .with_private_network_access(true)
+            .with_cross_origin_redirects(false)
+            .execute(serde_json::json!({"url": base_url}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(
+            result
+                .content
+                .contains("cross-origin redirects are not allowed")
+        );
+    }
+
+    #[test]
+    fn test_is_private() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(is_private(ip.parse().unwrap()), "{} should be private", ip);
+        }
+        for ip in ["8.8.8.8", "93.184.216.34", "2606:4700::1111"] {
+            assert!(!is_private(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
     #[tokio::test]
     async fn test_invalid_url() {
         let tool = WebFetchTool::new();
@@ -1,5 +1,5 @@
 // ABOUTME: WebSearchTool - performs web searches.
-// ABOUTME: Uses DuckDuckGo HTML search or configurable search API.
+// ABOUTME: Uses DuckDuckGo's HTML endpoint, with paging and region support.
 
 use async_trait::async_trait;
 use serde::Deserialize;
@@ -14,9 +14,33 @@ pub struct SearchResult {
     pub snippet: String,
 }
 
+/// DuckDuckGo's JavaScript-free search endpoint.
+const DDG_HTML_URL: &str = "https://html.duckduckgo.com/html/";
+
+/// Results returned when the caller doesn't say.
+const DEFAULT_MAX_RESULTS: usize = 10;
+
+/// Most results returned per call; about one page of DuckDuckGo results.
+const MAX_RESULTS_CAP: usize = 30;
+
 /// Tool for performing web searches.
+///
+/// Searches DuckDuckGo's HTML endpoint (`html.duckduckgo.com/html/`). The
+/// tool's parameters map onto it as follows:
+///
+/// - `query` is sent as `q`.
+/// - `offset` is sent as `s`, the index of the first result. `dc` is set to
+///   match, as the site's own "next page" form does.
+/// - `region` is sent as `kl`, a DuckDuckGo region code such as `us-en` or
+///   `de-de`. Without it DuckDuckGo picks a region itself.
+/// - `max_results` is applied locally to the page returned, up to 30.
+///
+/// The response's `offset` and `next_offset` metadata let an agent fetch the
+/// next page without changing its query. DuckDuckGo doesn't report how many
+/// results exist, so `total_results` is only included for backends that do.
 pub struct WebSearchTool {
     client: reqwest::Client,
+    base_url: String,
 }
 
 impl Default for WebSearchTool {
@@ -33,12 +57,22 @@ impl WebSearchTool {
             .user_agent("Mozilla/5.0 (compatible; mux-rs/0.2.0)")
             .build()
             .expect("Failed to create HTTP client");
-        Self { client }
+        Self::with_client(client)
     }
 
     /// Create with a custom reqwest client.
     pub fn with_client(client: reqwest::Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            base_url: DDG_HTML_URL.to_string(),
+        }
+    }
+
+    /// Send searches to a different URL that serves DuckDuckGo-style HTML,
+    /// such as a proxy or a test server.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
     }
 
     /// Parse DuckDuckGo HTML search results.
@@ -170,8 +204,17 @@ impl Tool for WebSearchTool {
                 },
                 "max_results": {
                     "type": "integer",
-                    "description": "Maximum number of results to return (default: 10)",
-                    "default": 10
+                    "description": format!("Maximum number of results to return (default: {}, at most {})", DEFAULT_MAX_RESULTS, MAX_RESULTS_CAP),
+                    "default": DEFAULT_MAX_RESULTS
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Number of results to skip, for fetching later pages. Use the next_offset from the previous call (default: 0)",
+                    "default": 0
+                },
+                "region": {
+                    "type": "string",
+                    "description": "Region and language for localized results, e.g. \"us-en\", \"uk-en\", \"de-de\", \"fr-fr\""
                 }
             },
             "required": ["query"]
@@ -184,19 +227,29 @@ impl Tool for WebSearchTool {
             query: String,
             #[serde(default = "default_max_results")]
             max_results: usize,
+            #[serde(default)]
+            offset: usize,
+            #[serde(default, alias = "locale")]
+            region: Option<String>,
         }
 
         fn default_max_results() -> usize {
-            10
+            DEFAULT_MAX_RESULTS
         }
 
         let params: Params = serde_json::from_value(params)?;
+        let max_results = params.max_results.clamp(1, MAX_RESULTS_CAP);
 
-        // Use DuckDuckGo HTML search
-        let encoded_query = urlencoding::encode(&params.query);
-        let url = format!("https://html.duckduckgo.com/html/?q={}", encoded_query);
+        let mut query = vec![("q", params.query.clone())];
+        if params.offset > 0 {
+            query.push(("s", params.offset.to_string()));
+            query.push(("dc", (params.offset + 1).to_string()));
+        }
+        if let Some(region) = &params.region {
+            query.push(("kl", region.clone()));
+        }
 
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&self.base_url).query(&query).send().await {
             Ok(resp) => resp,
             Err(e) => return Ok(ToolResult::error(format!("Search failed: {}", e))),
         };
@@ -214,22 +267,25 @@ impl Tool for WebSearchTool {
         };
 
         let results = Self::parse_ddg_results(&html);
-        let results: Vec<_> = results.into_iter().take(params.max_results).collect();
+        let results: Vec<_> = results.into_iter().take(max_results).collect();
 
         if results.is_empty() {
-            return Ok(ToolResult::text("No results found.".to_string()));
+            let message = if params.offset > 0 {
+                "No more results."
+            } else {
+                "No results found."
+            };
+            return Ok(ToolResult::text(message).with_metadata("offset", params.offset));
         }
 
-        // Format results
-        let mut output = format!(
-            "Found {} results for \"{}\":\n\n",
-            results.len(),
-            params.query
-        );
+        // Format results, numbered from the offset so pages read as one list
+        let first = params.offset + 1;
+        let last = params.offset + results.len();
+        let mut output = format!("Results {}-{} for \"{}\":\n\n", first, last, params.query);
         for (i, result) in results.iter().enumerate() {
             output.push_str(&format!(
                 "{}. {}\n   {}\n   {}\n\n",
-                i + 1,
+                first + i,
                 result.title,
                 result.url,
                 if result.snippet.is_empty() {
@@ -239,8 +295,14 @@ impl Tool for WebSearchTool {
                 }
             ));
         }
-
-        Ok(ToolResult::text(output))
+        output.push_str(&format!(
+            "For more results, search again with offset {}.",
+            last
+        ));
+
+        Ok(ToolResult::text(output)
+            .with_metadata("offset", params.offset)
+            .with_metadata("next_offset", last))
     }
 }
 
@@ -261,6 +323,86 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    fn ddg_page(count: usize) -> String {
+        (1..=count)
+            .map(|i| {
+                format!(
+                    r#"<div class="result"><a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fexample.com%2F{i}&amp;rut=x">Result {i}</a>
+                    <a class="result__snippet" href="x">Snippet {i}</a></div>"#
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_maps_paging_and_region_params() {
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, server) = serve(vec![RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/html".into())],
+            body: ddg_page(5),
+        }])
+        .await;
+        let tool = WebSearchTool::new().with_base_url(format!("{}/html/", base_url));
+
+        let result = tool
+            .execute(serde_json::json!({
+                "query": "rust async",
+                "max_results": 3,
+                "offset": 10,
+                "region": "de-de"
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert!(
+            result
+                .content
+                .starts_with("Results 11-13 for \"rust async\"")
+        );
+        assert!(
+            result
+                .content
+                .contains("11. Result 1\n   https://example.com/1")
+        );
+        assert!(result.content.contains("13. Result 3"));
+        assert!(!result.content.contains("Result 4"));
+        assert_eq!(result.metadata["offset"], 10);
+        assert_eq!(result.metadata["next_offset"], 13);
+        assert!(!result.metadata.contains_key("total_results"));
+
+        let request = &server.await.unwrap()[0];
+        let request_line = request.lines().next().unwrap();
+        assert!(request_line.starts_with("GET /html/?q=rust+async&s=10&dc=11&kl=de-de "));
+    }
+
+    #[tokio::test]
+    async fn test_search_caps_max_results() {
+        use crate::llm::test_server::{RecordedResponse, serve};
+
+        let (base_url, server) = serve(vec![RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "text/html".into())],
+            body: ddg_page(40),
+        }])
+        .await;
+        let tool = WebSearchTool::new().with_base_url(base_url);
+
+        let result = tool
+            .execute(serde_json::json!({"query": "rust", "max_results": 500}))
+            .await
+            .unwrap();
+
+        assert!(result.content.starts_with("Results 1-30 for"));
+        assert_eq!(result.metadata["next_offset"], 30);
+
+        // No paging or region parameters on a first-page search
+        let request = &server.await.unwrap()[0];
+        assert!(request.starts_with("GET /?q=rust "));
+    }
+
     // Live test - commented out by default
     // #[tokio::test]
     // async fn test_live_search() {
@@ -1,6 +1,7 @@
 // ABOUTME: WriteFileTool - writes content to a file.
-// ABOUTME: Creates parent directories if needed, overwrites existing files.
+// ABOUTME: Overwrites by default; can write atomically, keep a .bak, or create parent dirs.
 
+use std::io::Write;
 use std::path::Path;
 
 use async_trait::async_trait;
@@ -32,38 +33,129 @@ impl Tool for WriteFileTool {
                 "content": {
                     "type": "string",
                     "description": "The content to write to the file"
+                },
+                "atomic": {
+                    "type": "boolean",
+                    "description": "Write to a temporary file and rename it into place, so the file is never left half-written (default: false)"
+                },
+                "backup": {
+                    "type": "boolean",
+                    "description": "Save the previous contents to <path>.bak before overwriting (default: false)"
+                },
+                "create_dirs": {
+                    "type": "boolean",
+                    "description": "Create missing parent directories (default: true)"
                 }
             },
             "required": ["path", "content"]
         })
     }
 
+    fn parallel_safe(&self) -> bool {
+        false
+    }
+
     async fn execute(&self, params: serde_json::Value) -> Result<ToolResult, anyhow::Error> {
         #[derive(Deserialize)]
         struct Params {
             path: String,
             content: String,
+            #[serde(default)]
+            atomic: bool,
+            #[serde(default)]
+            backup: bool,
+            #[serde(default = "default_create_dirs")]
+            create_dirs: bool,
+        }
+        fn default_create_dirs() -> bool {
+            true
         }
         let params: Params = serde_json::from_value(params)?;
+        let path = Path::new(&params.path);
 
-        // Create parent directories if needed
-        if let Some(parent) = Path::new(&params.path).parent() {
-            if !parent.as_os_str().is_empty() {
-                std::fs::create_dir_all(parent)?;
-            }
+        if params.create_dirs
+            && let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
         }
 
-        match std::fs::write(&params.path, &params.content) {
-            Ok(()) => Ok(ToolResult::text(format!(
-                "Successfully wrote {} bytes to {}",
-                params.content.len(),
-                params.path
-            ))),
+        let backup_created = if params.backup && path.is_file() {
+            let backup_path = format!("{}.bak", params.path);
+            if let Err(e) = std::fs::copy(path, &backup_path) {
+                return Ok(ToolResult::error(format!(
+                    "Failed to back up {} to {}: {}",
+                    params.path, backup_path, e
+                )));
+            }
+            true
+        } else {
+            false
+        };
+
+        let written = if params.atomic {
+            write_atomic(path, params.content.as_bytes())
+        } else {
+            std::fs::write(path, &params.content)
+        };
+
+        match written {
+            Ok(()) => {
+                let mut message = format!(
+                    "Successfully wrote {} bytes to {}",
+                    params.content.len(),
+                    params.path
+                );
+                if backup_created {
+                    message.push_str(&format!(
+                        " (previous contents saved to {}.bak)",
+                        params.path
+                    ));
+                }
+                Ok(ToolResult::text(message)
+                    .with_metadata("bytes_written", params.content.len())
+                    .with_metadata("backup_created", backup_created)
+                    .with_files_changed([params.path]))
+            }
             Err(e) => Ok(ToolResult::error(format!("Failed to write file: {}", e))),
         }
     }
 }
 
+/// Write to a temporary file next to `path`, then rename it over `path`.
+///
+/// The rename is atomic on the same filesystem, so readers see either the old
+/// contents or the new ones. An existing file's permissions are kept.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        std::fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,6 +177,7 @@ mod tests {
 
         assert!(!result.is_error);
         assert!(result.content.contains("Successfully wrote"));
+        assert_eq!(result.files_changed(), vec![path.to_str().unwrap()]);
 
         let content = std::fs::read_to_string(&path).unwrap();
         assert_eq!(content, "Hello, world!");
@@ -107,4 +200,63 @@ mod tests {
         assert!(!result.is_error);
         assert!(path.exists());
     }
+
+    #[tokio::test]
+    async fn test_write_file_atomic_with_backup() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "old = true\n").unwrap();
+
+        let tool = WriteFileTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": path.to_str().unwrap(),
+                "content": "new = true\n",
+                "atomic": true,
+                "backup": true
+            }))
+            .await
+            .unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(result.metadata["bytes_written"], 11);
+        assert_eq!(result.metadata["backup_created"], true);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new = true\n");
+        let backup = dir.path().join("config.toml.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "old = true\n");
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_write_file_backup_of_new_file_and_no_create_dirs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("fresh.txt");
+
+        let tool = WriteFileTool;
+        let result = tool
+            .execute(serde_json::json!({
+                "path": path.to_str().unwrap(),
+                "content": "hi",
+                "backup": true
+            }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert_eq!(result.metadata["backup_created"], false);
+        assert!(!dir.path().join("fresh.txt.bak").exists());
+
+        let nested = dir.path().join("missing").join("file.txt");
+        let result = tool
+            .execute(serde_json::json!({
+                "path": nested.to_str().unwrap(),
+                "content": "hi",
+                "create_dirs": false
+            }))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(!nested.exists());
+    }
 }